@@ -2,11 +2,31 @@
 
 use crate::{create_progress_bar, format_size, parse_address, Cli};
 use colored::Colorize;
+use openflash_core::emmc::{EmmcHealthReport, EmmcHealthVerdict, ExtCsd};
+use openflash_core::programmer::{ProgrammerLink, ProgrammerTransport};
 use openflash_core::scripting::*;
 use std::path::PathBuf;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Programmer opened when `--port` is not given
+const DEFAULT_PORT: &str = "/dev/ttyACM0";
+
+/// Open the programmer on `--port`
+fn open_programmer(cli: &Cli) -> Result<ProgrammerLink> {
+    open_port(cli.port.as_deref().unwrap_or(DEFAULT_PORT))
+}
+
+/// Open a programmer by `serial://` or `tcp://` URI, or serial device path
+fn open_port(port: &str) -> Result<ProgrammerLink> {
+    let uri = if port.contains("://") {
+        port.to_string()
+    } else {
+        format!("serial://{}", port)
+    };
+    Ok(ProgrammerLink::connect(&uri)?)
+}
+
 /// Scan for connected devices
 pub fn scan(cli: &Cli) -> Result<()> {
     if !cli.quiet {
@@ -145,6 +165,13 @@ pub fn read(
 }
 
 /// Write/program chip
+///
+/// Runs the image through `ChipProgrammer`: blocks are erased, programmed
+/// and verified. Bad blocks are skipped and the rest of the image shifts to
+/// the next good block; failing blocks are retried, then marked bad on the
+/// chip so later runs skip them too. An interrupted write leaves `<input>.job` behind and the next run with
+/// the same image resumes from it. With `diff` only the blocks that differ
+/// from the chip are touched (see `write_diff`).
 pub fn write(
     cli: &Cli,
     input: PathBuf,
@@ -152,16 +179,66 @@ pub fn write(
     verify: bool,
     erase: bool,
     skip_bad: bool,
+    diff: bool,
 ) -> Result<()> {
+    use openflash_core::job_executor::DeviceTransport;
+    use openflash_core::write_ops::{ProgramImage, ProgramJob, ProgramOptions};
+
     let start_addr = parse_address(start)?;
     let data = std::fs::read(&input)?;
 
+    let mut nand = ProgrammerTransport::open(open_programmer(cli)?)?;
+    let block_size = nand.erase_size()?;
+    if start_addr % block_size != 0 {
+        return Err(format!(
+            "start address 0x{:X} is not aligned to the 0x{:X} byte block",
+            start_addr, block_size
+        )
+        .into());
+    }
+
+    let mut programmer = nand.chip_programmer();
+    programmer.set_options(ProgramOptions {
+        verify,
+        skip_bad_blocks: skip_bad,
+        erase_before_program: erase,
+        ..Default::default()
+    });
+    // Factory bad blocks are skipped, or refused without --skip-bad
+    let markers: Vec<(u32, u8)> = nand
+        .bad_blocks()?
+        .iter()
+        .map(|&address| ((address / block_size) as u32, 0x00))
+        .collect();
+    programmer
+        .bad_block_table_mut()
+        .scan_factory_bad_blocks(&markers);
+
+    let start_block = (start_addr / block_size) as u32;
+    if diff {
+        return write_diff(cli, &mut nand, &data, start_block, verify, &markers);
+    }
+
+    let image = ProgramImage::new(&data).at_block(start_block);
+    let plan = programmer.plan(&image)?;
+    let job_path = PathBuf::from(format!("{}.job", input.display()));
+    let mut job = std::fs::read(&job_path)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<ProgramJob>(&json).ok())
+        .filter(|job| {
+            job.start_block == plan.start_block
+                && job.image_len == plan.image_len
+                && job.image_checksum == plan.image_checksum
+        })
+        .unwrap_or(plan);
+
     if !cli.quiet {
         println!(
-            "{} {} from {}",
+            "{} {} from {} to {}",
             "Writing".green(),
             format_size(data.len() as u64).yellow(),
-            input.display().to_string().cyan()
+            input.display().to_string().cyan(),
+            nand.chip().model.cyan()
         );
         if erase {
             println!("  Erase before write: {}", "yes".green());
@@ -169,16 +246,54 @@ pub fn write(
         if verify {
             println!("  Verify after write: {}", "yes".green());
         }
+        if !markers.is_empty() {
+            println!("  Factory bad blocks: {}", markers.len());
+        }
+        if job.next_block() > 0 {
+            println!(
+                "  Resuming at block {} of {}",
+                job.next_block(),
+                job.image_blocks
+            );
+        }
     }
 
     let pb = if !cli.quiet {
-        Some(create_progress_bar(data.len() as u64, "Writing..."))
+        Some(create_progress_bar(
+            job.image_blocks as u64 * block_size,
+            "Writing...",
+        ))
     } else {
         None
     };
 
-    // Mock write operation
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    let result = programmer.program_image(&mut nand, &image, &mut job, |status| {
+        if let Some(pb) = &pb {
+            pb.set_position(status.bytes_written);
+            if let Some(eta) = status.eta_seconds {
+                pb.set_message(format!("ETA {}s", eta));
+            }
+        }
+        true
+    });
+    let done = match result {
+        Ok(done) => {
+            let _ = std::fs::remove_file(&job_path);
+            done
+        }
+        Err(e) => {
+            if let Some(pb) = &pb {
+                pb.abandon();
+            }
+            std::fs::write(&job_path, serde_json::to_vec(&job)?)?;
+            return Err(format!(
+                "{} (progress saved to {}, run the same command to resume)",
+                e,
+                job_path.display()
+            )
+            .into());
+        }
+    };
 
     if let Some(pb) = pb {
         pb.finish_with_message("Done!");
@@ -186,6 +301,90 @@ pub fn write(
 
     if !cli.quiet {
         println!("\n{}", "Write complete!".green().bold());
+        println!("  Blocks:         {}", job.image_blocks);
+        println!("  Verify retries: {}", done.verify_retries);
+        for (block, reason) in &job.bad_blocks {
+            println!("  Marked bad:     block {} ({:?})", block, reason);
+        }
+    }
+    Ok(())
+}
+
+/// Differential write: read the chip back and only erase and program the
+/// blocks whose contents differ from the image
+///
+/// Differential writes never remap, so the image must not cover a bad
+/// block.
+fn write_diff(
+    cli: &Cli,
+    nand: &mut ProgrammerTransport,
+    data: &[u8],
+    start_block: u32,
+    verify: bool,
+    bad_blocks: &[(u32, u8)],
+) -> Result<()> {
+    use openflash_core::diff_write::{DiffGeometry, DiffWriter};
+
+    let writer =
+        DiffWriter::new(DiffGeometry::nand(nand.chip_programmer().geometry())).with_verify(verify);
+    let unit_size = writer.geometry().unit_size() as u64;
+    let end_block = start_block as u64 + (data.len() as u64 + unit_size - 1) / unit_size;
+    if let Some((block, _)) = bad_blocks
+        .iter()
+        .find(|(block, _)| (start_block as u64..end_block).contains(&(*block as u64)))
+    {
+        return Err(format!(
+            "the image covers bad block {}; write it without --diff",
+            block
+        )
+        .into());
+    }
+
+    if !cli.quiet {
+        println!(
+            "{} {} against {}",
+            "Comparing".yellow(),
+            format_size(data.len() as u64).yellow(),
+            nand.chip().model.cyan()
+        );
+    }
+    let plan = writer.plan(nand, data, start_block, None)?;
+    let changed = plan.changed_units() as u64;
+
+    let pb = if !cli.quiet && changed > 0 {
+        Some(create_progress_bar(changed * unit_size, "Writing..."))
+    } else {
+        None
+    };
+    let report = writer.apply(nand, data, &plan, None, |status| {
+        if let Some(pb) = &pb {
+            pb.set_position(status.bytes_written);
+            if let Some(eta) = status.eta_seconds {
+                pb.set_message(format!("ETA {}s", eta));
+            }
+        }
+        true
+    })?;
+    if let Some(pb) = pb {
+        pb.finish_with_message("Done!");
+    }
+
+    match cli.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ if !cli.quiet => {
+            println!("\n{}", "Differential write complete!".green().bold());
+            println!("  Blocks:         {}", report.units_total);
+            println!("  Skipped:        {}", report.units_unchanged);
+            println!("  Rewritten:      {}", report.units_rewritten);
+            println!("  Retries:        {}", report.retries);
+            println!("  Duration:       {} ms", report.elapsed_ms);
+            println!(
+                "  Time saved:     ~{} ms (full write ~{} ms)",
+                report.time_saved_ms(),
+                report.estimated_full_ms
+            );
+        }
+        _ => {}
     }
     Ok(())
 }
@@ -382,23 +581,116 @@ pub fn compare(cli: &Cli, file1: PathBuf, file2: PathBuf, output: Option<PathBuf
     Ok(())
 }
 
-/// Clone chip-to-chip
-pub fn clone_chip(cli: &Cli, mode: &str, verify: bool) -> Result<()> {
+/// Chip-to-chip clone between two programmers
+///
+/// The source is `--source` (default `--port`). With `resume`, target
+/// blocks that already hold their data are left untouched, so a clone that
+/// was interrupted picks up where it stopped. `exact` cannot resume: the
+/// bad block markers it copied would read as target bad blocks. Ctrl-C
+/// aborts the clone after the current page.
+pub fn clone_chip(
+    cli: &Cli,
+    mode: &str,
+    verify: bool,
+    source: Option<&str>,
+    target: &str,
+    resume: bool,
+) -> Result<()> {
+    use openflash_core::write_ops::{ChipCloner, CloneMode, CloneOptions};
+
+    let mode = match (mode, resume) {
+        ("exact", false) => CloneMode::Exact,
+        ("exact", true) => return Err("--resume needs the skip-bad or wear-aware mode".into()),
+        ("skip-bad", _) => CloneMode::SkipBadBlocks,
+        ("wear-aware", _) => CloneMode::WearAware,
+        _ => {
+            return Err(format!(
+                "unknown clone mode '{}' (exact, skip-bad, wear-aware)",
+                mode
+            )
+            .into())
+        }
+    };
+
+    let source_port = source.or(cli.port.as_deref()).unwrap_or(DEFAULT_PORT);
+    let mut source = ProgrammerTransport::open(open_port(source_port)?)?;
+    let mut target = ProgrammerTransport::open(open_port(target)?)?;
+    let source_geometry = source.chip_programmer().geometry().clone();
+    let target_geometry = target.chip_programmer().geometry().clone();
+    let mut cloner = ChipCloner::new(
+        source_geometry.page_size,
+        source_geometry.pages_per_block,
+        source_geometry.total_blocks(),
+        target_geometry.page_size,
+        target_geometry.pages_per_block,
+        target_geometry.total_blocks(),
+    )?
+    .with_oob_sizes(source_geometry.oob_size, target_geometry.oob_size);
+    cloner.set_options(CloneOptions {
+        mode,
+        verify,
+        resume,
+        ..Default::default()
+    });
+    let abort = cloner.abort_handle();
+    ctrlc::set_handler(move || abort.abort())?;
+
     if !cli.quiet {
         println!(
-            "{} (mode: {})",
-            "Starting chip-to-chip clone".cyan(),
-            mode.yellow()
+            "{} {} -> {} (mode: {:?})",
+            "Cloning".cyan(),
+            source.chip().model.cyan(),
+            target.chip().model.cyan(),
+            mode
         );
         println!(
             "  Verify: {}",
             if verify { "yes".green() } else { "no".red() }
         );
     }
-    println!(
-        "{}",
-        "Clone operation requires two devices connected.".yellow()
-    );
+
+    let pb = if !cli.quiet {
+        Some(create_progress_bar(cloner.source_capacity(), "Scanning..."))
+    } else {
+        None
+    };
+    let result = cloner.clone_chip(&mut source, &mut target, |status| {
+        if let Some(pb) = &pb {
+            pb.set_length(status.total_bytes);
+            pb.set_position(status.bytes_cloned);
+            pb.set_message(format!("{:?}", status.phase));
+        }
+        true
+    });
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            if let Some(pb) = &pb {
+                pb.abandon();
+            }
+            if mode == CloneMode::Exact {
+                return Err(e.into());
+            }
+            return Err(format!("{} (run again with --resume to continue)", e).into());
+        }
+    };
+    if let Some(pb) = pb {
+        pb.finish_with_message("Done!");
+    }
+
+    match cli.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ if !cli.quiet => {
+            println!("\n{}", "Clone complete!".green().bold());
+            println!("  Blocks copied:     {}", report.mapping.len());
+            println!("  Unchanged:         {}", report.unchanged_blocks);
+            println!("  Source bad blocks: {}", report.source_bad_blocks.len());
+            println!("  Target bad blocks: {}", report.target_bad_blocks.len());
+            println!("  Target remapped:   {}", report.progress.target_remapped);
+            println!("  Verify errors:     {}", report.progress.verify_errors);
+        }
+        _ => {}
+    }
     Ok(())
 }
 
@@ -499,8 +791,15 @@ pub fn list_chips(
     Ok(())
 }
 
-/// Show device info
-pub fn info(cli: &Cli) -> Result<()> {
+/// Show device information
+///
+/// eMMC devices also get the health report, from the EXT_CSD read off the
+/// programmer or from an `--ext-csd` dump.
+pub fn info(cli: &Cli, ext_csd: Option<PathBuf>) -> Result<()> {
+    if let Some(path) = ext_csd {
+        return emmc_health(cli, &std::fs::read(path)?);
+    }
+
     let mut of = OpenFlash::new();
     of.connect_with_config(ConnectionConfig {
         port: cli.port.clone(),
@@ -508,9 +807,19 @@ pub fn info(cli: &Cli) -> Result<()> {
     })?;
 
     let info = of.device_info().ok_or("Not connected")?;
+    // Fails on programmers without an eMMC attached
+    let emmc = open_programmer(cli)
+        .and_then(|mut link| Ok(link.read_ext_csd()?))
+        .and_then(|raw| decode_ext_csd(&raw));
 
     match cli.format.as_str() {
-        "json" => println!("{}", serde_json::to_string_pretty(&info)?),
+        "json" => {
+            let mut json = serde_json::to_value(info)?;
+            if let Ok((ext, report)) = &emmc {
+                json["emmc"] = serde_json::json!({ "ext_csd": ext, "health": report });
+            }
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
         _ => {
             println!("\n{}", "Device Information:".green().bold());
             println!("  Port:       {}", info.port.cyan());
@@ -518,11 +827,84 @@ pub fn info(cli: &Cli) -> Result<()> {
             println!("  Firmware:   {}", info.firmware_version);
             println!("  Serial:     {}", info.serial_number.dimmed());
             println!("  Interfaces: {}", info.interfaces.join(", "));
+            match &emmc {
+                Ok((ext, report)) => print_emmc_health(ext, report),
+                Err(e) if cli.verbose => println!("  eMMC:       {}", e.to_string().dimmed()),
+                Err(_) => {}
+            }
         }
     }
     Ok(())
 }
 
+/// Decode an EXT_CSD register and assess it
+fn decode_ext_csd(raw: &[u8]) -> Result<(ExtCsd, EmmcHealthReport)> {
+    let ext = ExtCsd::parse(raw).ok_or("EXT_CSD dump must be at least 512 bytes")?;
+    let report = EmmcHealthReport::from_ext_csd(&ext);
+    Ok((ext, report))
+}
+
+/// Decode an EXT_CSD dump and print the eMMC health report
+fn emmc_health(cli: &Cli, raw: &[u8]) -> Result<()> {
+    let (ext, report) = decode_ext_csd(raw)?;
+    match cli.format.as_str() {
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "ext_csd": ext,
+                "health": report,
+            }))?
+        ),
+        _ => print_emmc_health(&ext, &report),
+    }
+    Ok(())
+}
+
+fn print_emmc_health(ext: &ExtCsd, report: &EmmcHealthReport) {
+    println!("\n{}", "eMMC Health:".green().bold());
+    println!("  Version:    {}", report.version.cyan());
+    println!("  Firmware:   {}", report.firmware_version);
+    println!("  Capacity:   {}", format_size(report.capacity_bytes));
+    println!(
+        "  Boot:       2 x {}",
+        format_size(ext.boot_size_bytes as u64)
+    );
+    println!("  RPMB:       {}", format_size(ext.rpmb_size_bytes as u64));
+    for (i, size) in ext.partitioning.gp_sizes.iter().enumerate() {
+        if *size > 0 {
+            println!("  GP{}:        {}", i + 1, format_size(*size));
+        }
+    }
+    println!(
+        "  Boot from:  {:?} (ack: {}, bus x{})",
+        ext.partition_config.boot_partition,
+        ext.partition_config.boot_ack,
+        ext.boot_bus_conditions.bus_width
+    );
+    println!(
+        "  Timing:     {} (cache: {}, {})",
+        ext.hs_timing.as_str(),
+        format_size(ext.cache.size_bytes),
+        if ext.cache.enabled { "on" } else { "off" }
+    );
+    println!("  Pre-EOL:    {}", report.pre_eol_info.as_str());
+    println!("  Life A:     {}", report.life_time_est_a);
+    println!("  Life B:     {}", report.life_time_est_b);
+    if !report.vendor_health_report.is_empty() {
+        println!("  Vendor:     {}", report.vendor_health_report.dimmed());
+    }
+    let verdict = match report.verdict {
+        EmmcHealthVerdict::Good => report.verdict.as_str().green(),
+        EmmcHealthVerdict::Worn => report.verdict.as_str().yellow(),
+        EmmcHealthVerdict::Critical => report.verdict.as_str().red(),
+        EmmcHealthVerdict::Unknown => report.verdict.as_str().dimmed(),
+    };
+    println!("  Verdict:    {}", verdict.bold());
+    for note in &report.notes {
+        println!("    - {}", note);
+    }
+}
+
 /// Set interface
 pub fn set_interface(cli: &Cli, interface: &str) -> Result<()> {
     if !cli.quiet {
@@ -956,8 +1338,30 @@ pub fn signatures_list(cli: &Cli) -> Result<()> {
 use openflash_core::server::*;
 
 /// Start server mode
-pub fn server_start(cli: &Cli, host: &str, port: u16, config_file: Option<PathBuf>) -> Result<()> {
-    let config = if let Some(path) = config_file {
+#[allow(clippy::too_many_arguments)]
+pub fn server_start(
+    cli: &Cli,
+    host: &str,
+    port: u16,
+    config_file: Option<PathBuf>,
+    api_keys: &[String],
+    users: &[String],
+    artifacts: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    access_users: &[String],
+    discover: bool,
+    announce_secret: Option<&str>,
+) -> Result<()> {
+    use openflash_core::access::{Credentials, Role, UserAccount};
+    use openflash_core::discovery::DeviceMonitor;
+    use openflash_core::grpc_server::{GrpcServer, GrpcService};
+    use openflash_core::job_store::PersistenceConfig;
+    use openflash_core::job_executor::{connect_uri, JobExecutor};
+    use openflash_core::metrics::MetricsServer;
+    use openflash_core::rest_server::{RestApi, RestServer};
+    use std::sync::{Arc, Mutex};
+
+    let mut config: ServerConfig = if let Some(path) = config_file {
         let content = std::fs::read_to_string(&path)?;
         serde_json::from_str(&content)?
     } else {
@@ -971,16 +1375,48 @@ pub fn server_start(cli: &Cli, host: &str, port: u16, config_file: Option<PathBu
         }
     };
 
+    if let Some(dir) = data_dir {
+        let persistence = config.persistence.get_or_insert_with(PersistenceConfig::default);
+        persistence.data_dir = dir.to_string_lossy().into_owned();
+    }
+    for user in access_users {
+        let mut parts = user.splitn(3, ':');
+        let (name, role, secret) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(role), Some(secret)) if !name.is_empty() && !secret.is_empty() => {
+                (name, role, secret)
+            }
+            _ => return Err("--access-user expects NAME:ROLE:SECRET".into()),
+        };
+        let role = Role::parse(role).ok_or_else(|| format!("unknown role '{}'", role))?;
+        config.access.enabled = true;
+        config
+            .access
+            .users
+            .push(UserAccount::new(name, role, secret));
+    }
+    if discover {
+        config.discovery.enabled = true;
+    }
+    if let Some(secret) = announce_secret {
+        config.discovery.announce_secret = Some(secret.to_string());
+    }
+
     if !cli.quiet {
         println!("{}", "Starting OpenFlash Server v2.0".cyan().bold());
         println!(
-            "  REST API:   http://{}:{}{}",
-            config.rest.host, config.rest.port, config.rest.prefix
+            "  REST API:   {}://{}:{}{}",
+            if config.rest.https { "https" } else { "http" },
+            config.rest.host,
+            config.rest.port,
+            config.rest.prefix
         );
         if config.websocket.enabled {
             println!(
-                "  WebSocket:  ws://{}:{}{}",
-                config.rest.host, config.rest.port, config.websocket.path
+                "  WebSocket:  {}://{}:{}{}",
+                if config.rest.https { "wss" } else { "ws" },
+                config.rest.host,
+                config.rest.port,
+                config.websocket.path
             );
         }
         if config.grpc.enabled {
@@ -992,16 +1428,159 @@ pub fn server_start(cli: &Cli, host: &str, port: u16, config_file: Option<PathBu
                 config.rest.host, config.metrics_port
             );
         }
-        println!("\n{}", "Press Ctrl+C to stop the server.".dimmed());
+        println!("\n{}", "Server configuration:".green());
+        println!("  Max devices:    {}", config.max_devices);
+        println!("  Max queue size: {}", config.max_queue_size);
+        println!("  Job timeout:    {} seconds", config.default_job_timeout);
+        if let Some(persistence) = &config.persistence {
+            println!("  Data dir:       {}", persistence.data_dir);
+        }
+        if config.access.enabled {
+            println!(
+                "  Access:         {} user(s), others act as {}",
+                config.access.users.len(),
+                config.access.default_role
+            );
+            println!("                  users log in with 'Authorization: Bearer <secret>'");
+        }
+        if config.discovery.enabled {
+            let usb = if config.discovery.scan_usb { "USB" } else { "no USB" };
+            let host = config
+                .discovery
+                .announce_host
+                .as_deref()
+                .unwrap_or(&config.rest.host);
+            let signed = if config.discovery.announce_secret.is_some() {
+                " (signed)"
+            } else {
+                ""
+            };
+            match config.discovery.announce_port {
+                Some(port) => println!(
+                    "  Discovery:      {}, announcements on UDP {}:{}{}",
+                    usb, host, port, signed
+                ),
+                None => println!("  Discovery:      {}", usb),
+            }
+        }
+    }
+
+    let auth = config.rest.auth.clone();
+    let grpc_enabled = config.grpc.enabled;
+    let metrics_addr = config
+        .metrics_enabled
+        .then(|| (config.rest.host.clone(), config.metrics_port));
+    let discovery = config
+        .discovery
+        .enabled
+        .then(|| (config.discovery.clone(), config.rest.host.clone()));
+    let shared = Arc::new(Mutex::new(OpenFlashServer::open(config)?));
+    let mut credentials = Credentials::default();
+    for key in api_keys {
+        credentials = match auth {
+            AuthMethod::BearerToken => credentials.with_bearer_token(key),
+            _ => credentials.with_api_key(key),
+        };
+    }
+    for user in users {
+        let (name, password) = user
+            .split_once(':')
+            .ok_or("--user expects NAME:PASSWORD")?;
+        credentials = credentials.with_basic_user(name, password);
+    }
+    let mut api = RestApi::new(shared.clone()).with_credentials(credentials.clone());
+    if let Some(dir) = &artifacts {
+        api = api.with_artifact_root(dir);
     }
 
-    // In real implementation, this would start the actual server
-    // For now, just show the configuration
-    println!("\n{}", "Server configuration:".green());
-    println!("  Max devices:    {}", config.max_devices);
-    println!("  Max queue size: {}", config.max_queue_size);
-    println!("  Job timeout:    {} seconds", config.default_job_timeout);
+    let mut executor = JobExecutor::new(shared.clone(), Arc::new(connect_uri));
+    if let Some(dir) = &artifacts {
+        executor = executor.with_artifact_root(dir);
+    }
+
+    let monitor = match discovery {
+        Some((discovery, rest_host)) => Some(
+            DeviceMonitor::new(shared.clone(), Arc::new(connect_uri))
+                .with_connections(executor.connections())
+                .listen_announcements(&discovery, &rest_host)?,
+        ),
+        None => None,
+    };
+
+    let metrics_server = shared.clone();
+    let grpc = if grpc_enabled {
+        let mut service = GrpcService::new(shared).with_credentials(credentials);
+        if let Some(dir) = artifacts {
+            service = service.with_artifact_root(dir);
+        }
+        Some(service)
+    } else {
+        None
+    };
+
+    let executor = executor.start();
+    let monitor = monitor.map(DeviceMonitor::start);
+    let runtime = tokio::runtime::Runtime::new()?;
+    let served = runtime.block_on(async {
+        let server = RestServer::bind(api).await?;
+        let grpc = match grpc {
+            Some(service) => Some(GrpcServer::bind(service).await?),
+            None => None,
+        };
+        let metrics = match &metrics_addr {
+            Some((host, port)) => Some(MetricsServer::bind(metrics_server, host, *port).await?),
+            None => None,
+        };
+        if !cli.quiet {
+            println!("\n{} {}", "Listening on".green(), server.local_addr()?);
+            if let Some(grpc) = &grpc {
+                println!("{} {} (gRPC)", "Listening on".green(), grpc.local_addr()?);
+            }
+            if let Some(metrics) = &metrics {
+                println!("{} {} (metrics)", "Listening on".green(), metrics.local_addr()?);
+            }
+            println!("{}", "Press Ctrl+C to stop the server.".dimmed());
+        }
 
+        let (stop, stopped) = tokio::sync::watch::channel(false);
+        let shutdown = move || {
+            let mut stopped = stopped.clone();
+            async move {
+                let _ = stopped.wait_for(|stop| *stop).await;
+            }
+        };
+        let rest = async {
+            server.serve_with_shutdown(shutdown()).await?;
+            Result::<()>::Ok(())
+        };
+        let grpc = async {
+            if let Some(grpc) = grpc {
+                grpc.serve_with_shutdown(shutdown()).await?;
+            }
+            Result::<()>::Ok(())
+        };
+        let metrics = async {
+            if let Some(metrics) = metrics {
+                metrics.serve_with_shutdown(shutdown()).await?;
+            }
+            Result::<()>::Ok(())
+        };
+        let signal = async {
+            let _ = tokio::signal::ctrl_c().await;
+            let _ = stop.send(true);
+            Result::<()>::Ok(())
+        };
+        tokio::try_join!(rest, grpc, metrics, signal).map(|_| ())
+    });
+    if let Some(monitor) = monitor {
+        monitor.shutdown();
+    }
+    executor.shutdown();
+    served?;
+
+    if !cli.quiet {
+        println!("{}", "Server stopped.".green());
+    }
     Ok(())
 }
 
@@ -1423,14 +2002,37 @@ pub fn job_list(cli: &Cli, status_filter: Option<&str>, limit: usize) -> Result<
 }
 
 /// Start parallel dump
+#[allow(clippy::too_many_arguments)]
 pub fn parallel_dump(
     cli: &Cli,
     output_dir: PathBuf,
-    device_count: usize,
+    device_count: Option<usize>,
     chunk_size: &str,
     merge: bool,
+    device_uris: &[String],
+    size: Option<&str>,
+    each: bool,
 ) -> Result<()> {
+    use openflash_core::job_executor::connect_uri;
+    use openflash_core::parallel_dump::ParallelDumper;
+    use std::sync::{Arc, Mutex};
+
     let chunk_bytes = parse_address(chunk_size)?;
+    if chunk_bytes == 0 {
+        return Err("chunk size must not be zero".into());
+    }
+    let device_count = device_count.unwrap_or(device_uris.len());
+    if device_count == 0 {
+        return Err("--devices must not be zero".into());
+    }
+    if device_count > device_uris.len() {
+        return Err(format!(
+            "--devices {} but only {} given with --device",
+            device_count,
+            device_uris.len()
+        )
+        .into());
+    }
 
     if !cli.quiet {
         println!("{}", "Starting parallel dump...".cyan().bold());
@@ -1439,11 +2041,15 @@ pub fn parallel_dump(
             output_dir.display().to_string().yellow()
         );
         println!("  Devices:     {}", device_count);
-        println!("  Chunk size:  {}", format_size(chunk_bytes));
-        println!(
-            "  Merge:       {}",
-            if merge { "yes".green() } else { "no".red() }
-        );
+        if each {
+            println!("  Mode:        one chip per device");
+        } else {
+            println!("  Chunk size:  {}", format_size(chunk_bytes));
+            println!(
+                "  Merge:       {}",
+                if merge { "yes".green() } else { "no".red() }
+            );
+        }
     }
 
     let config = ParallelDumpConfig {
@@ -1454,20 +2060,94 @@ pub fn parallel_dump(
         verify: true,
     };
 
-    // Mock parallel dump - would actually coordinate multiple devices
-    let total_size = 128 * 1024 * 1024; // 128MB mock
-    let job = ParallelDumpJob::new(total_size, config);
+    // Local pool made of the given devices
+    let mut server = OpenFlashServer::with_defaults();
+    for (i, uri) in device_uris.iter().enumerate() {
+        let id = format!("dev{}", i);
+        server.register_device(PoolDevice::new(&id, &id, uri, DevicePlatform::Unknown))?;
+        server.set_device_status(&id, DeviceStatus::Available)?;
+    }
+    let first = server.device_pool.get_device("dev0").cloned();
+    let shared = Arc::new(Mutex::new(server));
+    let dumper = ParallelDumper::new(shared, Arc::new(connect_uri));
+
+    if each {
+        let dumps = dumper.dump_each(&config)?;
+        match cli.format.as_str() {
+            "json" => println!("{}", serde_json::to_string_pretty(&dumps)?),
+            _ => {
+                println!("\n{}", "Parallel dump finished:".green());
+                for dump in &dumps {
+                    match &dump.error {
+                        None => println!(
+                            "  {}  {}  {}  {}",
+                            dump.device_id.cyan(),
+                            dump.output_file,
+                            format_size(dump.bytes),
+                            dump.checksum.as_deref().unwrap_or("-").dimmed()
+                        ),
+                        Some(error) => {
+                            println!("  {}  {}", dump.device_id.cyan(), error.red())
+                        }
+                    }
+                }
+            }
+        }
+        if dumps.iter().any(|d| d.error.is_some()) {
+            return Err("some devices failed to dump".into());
+        }
+        return Ok(());
+    }
 
-    println!("\n{}", "Parallel dump job created:".green());
-    println!("  Job ID:  {}", job.id.to_string().cyan());
-    println!("  Chunks:  {}", job.chunks.len());
-    println!("  Total:   {}", format_size(job.total_size));
+    let total_size = match (size, first) {
+        (Some(size), _) => parse_address(size)?,
+        (None, Some(device)) => connect_uri(&device)?.capacity(false)?,
+        (None, None) => return Err("no device given".into()),
+    };
+    let mut job = ParallelDumpJob::new(total_size, config);
+    if !cli.quiet {
+        println!("  Job ID:      {}", job.id.to_string().cyan());
+        println!("  Chunks:      {}", job.chunks.len());
+        println!("  Total:       {}", format_size(job.total_size));
+    }
+    let manifest = dumper.run(&mut job)?;
+
+    match cli.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&manifest)?),
+        _ => {
+            println!("\n{}", "Parallel dump finished:".green());
+            if let Some(image) = &manifest.image {
+                println!("  Image:     {}", image);
+            }
+            println!("  SHA-256:   {}", manifest.checksum);
+            println!("  Devices:   {}", manifest.devices.join(", "));
+            let retried = manifest.chunks.iter().filter(|c| c.attempts > 1).count();
+            if retried > 0 {
+                println!("  Retried:   {} chunks", retried.to_string().yellow());
+            }
+            println!(
+                "  Duration:  {} ms",
+                manifest.completed_at - manifest.started_at
+            );
+        }
+    }
 
     Ok(())
 }
 
 /// Start production mode
-pub fn production_start(cli: &Cli, config_file: PathBuf, line_id: Option<&str>) -> Result<()> {
+pub fn production_start(
+    cli: &Cli,
+    config_file: PathBuf,
+    line_id: Option<&str>,
+    devices: &[String],
+    units: Option<u64>,
+) -> Result<()> {
+    use openflash_core::job_executor::connect_uri;
+    use openflash_core::production::ProductionRunner;
+    use std::io::BufRead;
+    use std::sync::{Arc, Mutex};
+
     if !cli.quiet {
         println!("{}", "Starting production mode...".cyan().bold());
         println!("  Config: {}", config_file.display().to_string().yellow());
@@ -1476,12 +2156,112 @@ pub fn production_start(cli: &Cli, config_file: PathBuf, line_id: Option<&str>)
         }
     }
 
-    // Mock production config
+    // A single line or a list of lines
+    let content = std::fs::read_to_string(&config_file)?;
+    let lines: Vec<ProductionLineConfig> = match serde_json::from_str(&content) {
+        Ok(line) => vec![line],
+        Err(_) => serde_json::from_str(&content)?,
+    };
+    let line = match line_id {
+        Some(id) => lines.into_iter().find(|l| l.line_id == id),
+        None => lines.into_iter().next(),
+    }
+    .ok_or("production line not found in config")?;
+
+    let mut server = OpenFlashServer::with_defaults();
+    for device in devices {
+        let (id, uri) = device
+            .split_once('=')
+            .ok_or_else(|| format!("expected ID=URI, got {}", device))?;
+        server.register_device(PoolDevice::new(id, id, uri, DevicePlatform::Unknown))?;
+        server.set_device_status(id, DeviceStatus::Available)?;
+    }
+    let stations: Vec<String> = line
+        .stations
+        .iter()
+        .filter(|s| s.device_id.is_some())
+        .map(|s| s.id.clone())
+        .collect();
+    if stations.is_empty() {
+        return Err("no station has a device assigned".into());
+    }
+
+    let base_dir = config_file
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let mut runner = ProductionRunner::new(
+        line,
+        Arc::new(Mutex::new(server)),
+        Arc::new(connect_uri),
+    )?
+    .with_base_dir(base_dir);
+
     println!("\n{}", "Production line ready:".green());
-    println!("  Auto-start:   enabled");
-    println!("  Verification: full");
-    println!("  Logging:      enabled");
-    println!("\n{}", "Waiting for units...".dimmed());
+    println!("  Line:         {}", runner.line().name);
+    println!("  Stations:     {}", stations.join(", "));
+    println!("  Verification: {:?}", runner.line().verification);
+    if units.is_none() {
+        println!(
+            "\n{}",
+            "Press Enter to run the next units, q to quit".dimmed()
+        );
+    }
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock().lines();
+    let mut round = 0;
+    loop {
+        match units {
+            Some(units) if round >= units => break,
+            Some(_) => {}
+            None => match input.next() {
+                Some(Ok(answer)) if answer.trim() != "q" => {}
+                _ => break,
+            },
+        }
+        round += 1;
+        for station in &stations {
+            let unit = runner.run_unit(station)?;
+            if unit.passed {
+                println!(
+                    "  {}  {}  {}  {} ms",
+                    station.cyan(),
+                    unit.serial_number,
+                    "PASS".green().bold(),
+                    unit.duration_ms
+                );
+            } else {
+                println!(
+                    "  {}  {}  {}  {}",
+                    station.cyan(),
+                    unit.serial_number,
+                    "FAIL".red().bold(),
+                    unit.failure_reason.unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    let stats = runner.stats();
+    match cli.format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(stats)?),
+        _ => {
+            println!("\n{}", "Production Statistics:".green().bold());
+            println!("  Total units:    {}", stats.total_units);
+            println!(
+                "  Passed:         {} ({:.1}%)",
+                stats.passed_units.to_string().green(),
+                stats.pass_rate
+            );
+            println!("  Failed:         {}", stats.failed_units.to_string().red());
+            println!("  Avg cycle time: {} ms", stats.avg_cycle_time_ms);
+            println!("  Units/hour:     {:.1}", stats.units_per_hour);
+            for (reason, count) in &stats.failure_reasons {
+                println!("    {}: {}", reason, count);
+            }
+        }
+    }
 
     Ok(())
 }
@@ -99,6 +99,10 @@ enum Commands {
         /// Skip bad blocks
         #[arg(long, default_value = "true")]
         skip_bad: bool,
+
+        /// Only erase and program the blocks that differ from the chip
+        #[arg(long)]
+        diff: bool,
     },
 
     /// Erase flash chip (full or partial)
@@ -167,6 +171,18 @@ enum Commands {
         /// Verify after clone
         #[arg(long, default_value = "true")]
         verify: bool,
+
+        /// Source programmer (port or URI, default: --port)
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Target programmer (port or URI)
+        #[arg(long)]
+        target: String,
+
+        /// Continue an interrupted clone, leaving blocks already copied
+        #[arg(long)]
+        resume: bool,
     },
 
     /// Run batch processing jobs
@@ -205,7 +221,11 @@ enum Commands {
     },
 
     /// Show device information
-    Info,
+    Info {
+        /// Decode a raw 512-byte eMMC EXT_CSD dump and print a health report
+        #[arg(long)]
+        ext_csd: Option<PathBuf>,
+    },
 
     /// Set flash interface
     Interface {
@@ -310,9 +330,9 @@ enum Commands {
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Number of devices to use
-        #[arg(short, long, default_value = "4")]
-        devices: usize,
+        /// Number of devices to use (default: one per --device)
+        #[arg(short, long)]
+        devices: Option<usize>,
 
         /// Chunk size per device
         #[arg(long, default_value = "64M")]
@@ -321,6 +341,19 @@ enum Commands {
         /// Merge output files
         #[arg(long, default_value = "true")]
         merge: bool,
+
+        /// Device URI; repeat for every device (all see the same chip
+        /// unless --each is given)
+        #[arg(long = "device", required = true)]
+        device_uris: Vec<String>,
+
+        /// Bytes to dump (default: the whole chip)
+        #[arg(long)]
+        size: Option<String>,
+
+        /// Dump a different chip on each device into its own file
+        #[arg(long)]
+        each: bool,
     },
 
     /// Production line mode
@@ -371,7 +404,7 @@ enum ServerAction {
     /// Start OpenFlash server
     Start {
         /// Listen host
-        #[arg(long, default_value = "0.0.0.0")]
+        #[arg(long, default_value = "127.0.0.1")]
         host: String,
 
         /// Listen port
@@ -381,6 +414,40 @@ enum ServerAction {
         /// Configuration file
         #[arg(short, long)]
         config: Option<PathBuf>,
+
+        /// Accepted API keys or bearer tokens, depending on the configured auth
+        #[arg(long = "api-key", env = "OPENFLASH_API_KEYS", value_delimiter = ',')]
+        api_keys: Vec<String>,
+
+        /// Accepted basic auth users as NAME:PASSWORD
+        #[arg(long = "user", env = "OPENFLASH_USERS", value_delimiter = ',')]
+        users: Vec<String>,
+
+        /// Directory served under /artifacts
+        #[arg(long)]
+        artifacts: Option<PathBuf>,
+
+        /// Keep the job queue and device registry in this directory
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
+
+        /// Enable role-based access with a user as NAME:ROLE:SECRET
+        /// (roles: viewer, operator, engineer, admin)
+        #[arg(
+            long = "access-user",
+            env = "OPENFLASH_ACCESS_USERS",
+            value_delimiter = ','
+        )]
+        access_users: Vec<String>,
+
+        /// Discover USB and network programmers and monitor device health
+        #[arg(long)]
+        discover: bool,
+
+        /// Shared secret network programmers sign their announcements with;
+        /// needed to hear announcements on a non-loopback host
+        #[arg(long, env = "OPENFLASH_ANNOUNCE_SECRET")]
+        announce_secret: Option<String>,
     },
     /// Stop OpenFlash server
     Stop,
@@ -477,6 +544,15 @@ enum ProductionAction {
         /// Production line ID
         #[arg(long)]
         line: Option<String>,
+
+        /// Station device as ID=URI; repeat for every station device
+        #[arg(long = "device", value_name = "ID=URI")]
+        devices: Vec<String>,
+
+        /// Run this many units per station, then stop (default: one round
+        /// per Enter until "q")
+        #[arg(long)]
+        units: Option<u64>,
     },
     /// Get production status
     Status {
@@ -516,7 +592,16 @@ fn main() {
             verify,
             erase,
             skip_bad,
-        } => commands::write(&cli, input.clone(), start, *verify, *erase, *skip_bad),
+            diff,
+        } => commands::write(
+            &cli,
+            input.clone(),
+            start,
+            *verify,
+            *erase,
+            *skip_bad,
+            *diff,
+        ),
         Commands::Erase {
             start,
             length,
@@ -534,7 +619,13 @@ fn main() {
             file2,
             output,
         } => commands::compare(&cli, file1.clone(), file2.clone(), output.clone()),
-        Commands::Clone { mode, verify } => commands::clone_chip(&cli, mode, *verify),
+        Commands::Clone {
+            mode,
+            verify,
+            source,
+            target,
+            resume,
+        } => commands::clone_chip(&cli, mode, *verify, source.as_deref(), target, *resume),
         Commands::Batch {
             file,
             stop_on_error,
@@ -550,7 +641,7 @@ fn main() {
             manufacturer.clone(),
             search.clone(),
         ),
-        Commands::Info => commands::info(&cli),
+        Commands::Info { ext_csd } => commands::info(&cli, ext_csd.clone()),
         Commands::Interface { interface } => commands::set_interface(&cli, interface),
         Commands::Config { action } => match action {
             ConfigAction::Show => commands::config_show(&cli),
@@ -593,9 +684,30 @@ fn main() {
         },
         // v2.0 - Multi-device & Enterprise commands
         Commands::Server { action } => match action {
-            ServerAction::Start { host, port, config } => {
-                commands::server_start(&cli, host, *port, config.clone())
-            }
+            ServerAction::Start {
+                host,
+                port,
+                config,
+                api_keys,
+                users,
+                artifacts,
+                data_dir,
+                access_users,
+                discover,
+                announce_secret,
+            } => commands::server_start(
+                &cli,
+                host,
+                *port,
+                config.clone(),
+                api_keys,
+                users,
+                artifacts.clone(),
+                data_dir.clone(),
+                access_users,
+                *discover,
+                announce_secret.as_deref(),
+            ),
             ServerAction::Stop => commands::server_stop(&cli),
             ServerAction::Status { url } => commands::server_status(&cli, url.as_deref()),
         },
@@ -633,11 +745,32 @@ fn main() {
             devices,
             chunk_size,
             merge,
-        } => commands::parallel_dump(&cli, output.clone(), *devices, chunk_size, *merge),
+            device_uris,
+            size,
+            each,
+        } => commands::parallel_dump(
+            &cli,
+            output.clone(),
+            *devices,
+            chunk_size,
+            *merge,
+            device_uris,
+            size.as_deref(),
+            *each,
+        ),
         Commands::Production { action } => match action {
-            ProductionAction::Start { config, line } => {
-                commands::production_start(&cli, config.clone(), line.as_deref())
-            }
+            ProductionAction::Start {
+                config,
+                line,
+                devices,
+                units,
+            } => commands::production_start(
+                &cli,
+                config.clone(),
+                line.as_deref(),
+                devices,
+                *units,
+            ),
             ProductionAction::Status { line } => commands::production_status(&cli, line.as_deref()),
         },
     };
@@ -0,0 +1,33 @@
+//! Build script for openflash-core
+//!
+//! With the `grpc-server` feature, generates the gRPC bindings for
+//! `proto/openflash.proto`. A `PROTOC` from the environment is preferred;
+//! otherwise the vendored protoc binary is used so no system install is
+//! needed.
+
+fn main() {
+    #[cfg(feature = "grpc-server")]
+    grpc::compile();
+}
+
+#[cfg(feature = "grpc-server")]
+mod grpc {
+    use std::path::PathBuf;
+
+    pub fn compile() {
+        println!("cargo:rerun-if-changed=proto/openflash.proto");
+        println!("cargo:rerun-if-env-changed=PROTOC");
+
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc = protoc_bin_vendored::protoc_bin_path()
+                .expect("no vendored protoc for this platform; set PROTOC");
+            std::env::set_var("PROTOC", protoc);
+        }
+
+        let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR not set"));
+        tonic_build::configure()
+            .file_descriptor_set_path(out_dir.join("openflash_descriptor.bin"))
+            .compile_protos(&["proto/openflash.proto"], &["proto"])
+            .expect("failed to compile proto/openflash.proto");
+    }
+}
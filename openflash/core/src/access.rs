@@ -0,0 +1,1025 @@
+//! Role-based access control and the access log for the OpenFlash server
+//!
+//! [`AccessConfig`] (in `ServerConfig.access`) maps users to a [`Role`]
+//! and each role to a [`RolePolicy`]: the job operations it may queue, the
+//! device tags it is confined to and whether it may cancel other users'
+//! jobs, fetch artifacts or read the access log. User secrets are stored
+//! as SHA-256 hashes only. Users log in with `Authorization: Bearer
+//! <secret>` whatever the configured authentication scheme, or with the
+//! secret in place of that scheme's credential.
+//!
+//! Operations are named after the job type (`read`, `write`, `erase`,
+//! `verify`, `analyze`, `clone`) or `custom:<command>` for custom
+//! commands. Policy entries match an operation exactly, by prefix with a
+//! trailing `*`, and deny it when prefixed with `!`; denials win. The
+//! default policies are
+//! - viewer: nothing but reading server, device and job state
+//! - operator: `read`, `write`, `verify`, `analyze` and artifacts
+//! - engineer: every job type (`read`, `write`, `erase`, `verify`,
+//!   `analyze`, `clone`) and `custom:rpmb_read`, cancelling any job; other
+//!   custom commands, such as the one-time programmable
+//!   `custom:emmc_partition*`, need an admin or a configured policy
+//! - admin: everything, including the access log
+//!
+//! Every decision on a job and every job outcome is appended to an
+//! [`AccessLog`]: who ran which operation on which device and chip. Each
+//! record carries the SHA-256 of its predecessor, so editing or removing a
+//! record breaks the chain ([`verify_chain`]); comparing the head hash
+//! with a copy kept elsewhere also reveals a truncated log.
+
+use crate::job_store::{open_append, read_lines, StoreError};
+use crate::server::{AuthMethod, DevicePool, Job, JobType, RateLimitConfig};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+pub mod limits {
+    /// Access log file name inside the persistence data directory
+    pub const LOG_FILE: &str = "access.jsonl";
+    /// `prev_hash` of the first record
+    pub const GENESIS_HASH: &str =
+        "0000000000000000000000000000000000000000000000000000000000000000";
+    /// Records kept by a log without a file (oldest dropped first)
+    pub const MAX_MEMORY_RECORDS: usize = 10_000;
+    /// Job metadata key holding the submitter's role
+    pub const ROLE_METADATA: &str = "role";
+    /// Rate limiter buckets kept before idle clients are evicted
+    pub const MAX_TRACKED_CLIENTS: usize = 4096;
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Access log errors
+#[derive(Debug)]
+pub enum AccessError {
+    /// The log file cannot be read or written
+    Store(StoreError),
+    /// A record does not match the hash chain
+    Tampered { seq: u64, reason: String },
+}
+
+impl std::fmt::Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Store(e) => write!(f, "Access log: {}", e),
+            Self::Tampered { seq, reason } => {
+                write!(f, "Access log record {} was tampered with: {}", seq, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+impl From<StoreError> for AccessError {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+impl From<std::io::Error> for AccessError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Store(StoreError::Io(e))
+    }
+}
+
+pub type AccessResult<T> = Result<T, AccessError>;
+
+// ============================================================================
+// Roles and Policies
+// ============================================================================
+
+/// Server role, from least to most privileged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only access to server, device and job state
+    Viewer,
+    /// Runs non-destructive production jobs
+    Operator,
+    /// Runs any job except the admin-only ones
+    Engineer,
+    /// Full control
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Viewer, Role::Operator, Role::Engineer, Role::Admin];
+
+    /// Lower-case role name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Engineer => "engineer",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Parse a role name (case-insensitive)
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Server actions besides jobs (reading server, device and job state
+/// is open to every role)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Artifact downloads and uploads
+    Artifacts,
+    /// The access log
+    ViewAccessLog,
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Artifacts => "access artifacts",
+            Self::ViewAccessLog => "read the access log",
+        })
+    }
+}
+
+/// What one role may do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolePolicy {
+    /// Operation patterns (see the module docs)
+    pub operations: Vec<String>,
+    /// Devices the role may use must carry one of these tags (empty = any)
+    #[serde(default)]
+    pub device_tags: Vec<String>,
+    /// May cancel jobs submitted by other users
+    #[serde(default)]
+    pub cancel_any: bool,
+    /// May download and upload artifacts
+    #[serde(default)]
+    pub artifacts: bool,
+    /// May read the access log
+    #[serde(default)]
+    pub view_access_log: bool,
+}
+
+impl RolePolicy {
+    /// Built-in policy of `role`
+    pub fn defaults(role: Role) -> Self {
+        let operations: &[&str] = match role {
+            Role::Viewer => &[],
+            Role::Operator => &["read", "write", "verify", "analyze"],
+            Role::Engineer => &[
+                "read",
+                "write",
+                "erase",
+                "verify",
+                "analyze",
+                "clone",
+                "custom:rpmb_read",
+            ],
+            Role::Admin => &["*"],
+        };
+        Self {
+            operations: operations.iter().map(|op| op.to_string()).collect(),
+            device_tags: Vec::new(),
+            cancel_any: role >= Role::Engineer,
+            artifacts: role >= Role::Operator,
+            view_access_log: role == Role::Admin,
+        }
+    }
+
+    /// Whether `operation` (see [`operation_name`]) is allowed
+    pub fn allows_operation(&self, operation: &str) -> bool {
+        let denied = self
+            .operations
+            .iter()
+            .filter_map(|p| p.strip_prefix('!'))
+            .any(|p| pattern_matches(p, operation));
+        !denied
+            && self
+                .operations
+                .iter()
+                .filter(|p| !p.starts_with('!'))
+                .any(|p| pattern_matches(p, operation))
+    }
+
+    /// Whether the role may use a device carrying `tags`
+    pub fn allows_device(&self, tags: &[String]) -> bool {
+        self.device_tags.is_empty() || tags.iter().any(|t| self.device_tags.contains(t))
+    }
+
+    /// Whether `permission` is granted
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Artifacts => self.artifacts,
+            Permission::ViewAccessLog => self.view_access_log,
+        }
+    }
+}
+
+fn pattern_matches(pattern: &str, operation: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => operation.starts_with(prefix),
+        None => pattern == operation,
+    }
+}
+
+/// Operation name of a job type: its name, or `custom:<command>`
+pub fn operation_name(job_type: &JobType) -> String {
+    match job_type {
+        JobType::Custom { command, .. } => format!("custom:{}", command),
+        other => other.name().to_string(),
+    }
+}
+
+/// Hex SHA-256 of a secret, as stored in [`UserAccount::key_sha256`]
+pub fn hash_secret(secret: &str) -> String {
+    hex(&Sha256::digest(secret.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Comparison whose duration does not depend on where the inputs differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// ============================================================================
+// Users
+// ============================================================================
+
+/// Server user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserAccount {
+    /// User name, recorded in the access log
+    pub name: String,
+    /// Role of the user
+    pub role: Role,
+    /// Hex SHA-256 of the user's API key, bearer token or password
+    pub key_sha256: String,
+}
+
+impl UserAccount {
+    /// User authenticating with `secret`
+    pub fn new(name: &str, role: Role, secret: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            role,
+            key_sha256: hash_secret(secret),
+        }
+    }
+}
+
+/// Authenticated caller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Client ID recorded on jobs, e.g. `user:alice` or `key:1a2b…`
+    pub id: String,
+    /// Role the caller acts with
+    pub role: Role,
+}
+
+impl Principal {
+    pub fn new(id: &str, role: Role) -> Self {
+        Self {
+            id: id.to_string(),
+            role,
+        }
+    }
+}
+
+/// Access control settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessConfig {
+    /// Enforce roles; when disabled every caller acts as admin
+    pub enabled: bool,
+    /// Known users
+    #[serde(default)]
+    pub users: Vec<UserAccount>,
+    /// Role of callers authenticated by credentials given at start-up
+    /// rather than a user account
+    #[serde(default = "default_role")]
+    pub default_role: Role,
+    /// Policies replacing the built-in ones
+    #[serde(default)]
+    pub policies: BTreeMap<Role, RolePolicy>,
+    /// Access log file (default: `access.jsonl` in the persistence data
+    /// directory, else kept in memory)
+    #[serde(default)]
+    pub log_path: Option<String>,
+}
+
+fn default_role() -> Role {
+    Role::Viewer
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            users: Vec::new(),
+            default_role: default_role(),
+            policies: BTreeMap::new(),
+            log_path: None,
+        }
+    }
+}
+
+impl AccessConfig {
+    /// Effective policy of `role`
+    pub fn policy(&self, role: Role) -> RolePolicy {
+        self.policies
+            .get(&role)
+            .cloned()
+            .unwrap_or_else(|| RolePolicy::defaults(role))
+    }
+
+    /// Add a user
+    pub fn with_user(mut self, user: UserAccount) -> Self {
+        self.users.push(user);
+        self
+    }
+
+    /// User whose secret is `secret`, and whose name is `name` when given
+    /// (basic authentication)
+    ///
+    /// Every account is checked so timing does not reveal which matched.
+    pub fn authenticate(&self, name: Option<&str>, secret: &str) -> Option<Principal> {
+        let hash = hash_secret(secret);
+        let mut matched = None;
+        for user in &self.users {
+            let ok = constant_time_eq(user.key_sha256.as_bytes(), hash.as_bytes())
+                & name.map_or(true, |n| {
+                    constant_time_eq(n.as_bytes(), user.name.as_bytes())
+                });
+            if ok && matched.is_none() {
+                matched = Some(Principal::new(&format!("user:{}", user.name), user.role));
+            }
+        }
+        matched
+    }
+
+    /// Role of a caller authenticated without a user account
+    pub fn fallback_role(&self) -> Role {
+        if self.enabled {
+            self.default_role
+        } else {
+            Role::Admin
+        }
+    }
+
+    /// Check that `principal` may queue `job`
+    ///
+    /// A role confined to a single device tag has it added to
+    /// `job.required_tags` when the job names neither a device nor one of
+    /// its tags. Returns the reason when denied.
+    pub fn authorize_job(
+        &self,
+        principal: &Principal,
+        job: &mut Job,
+        pool: &DevicePool,
+    ) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let role = principal.role;
+        let policy = self.policy(role);
+        let operation = operation_name(&job.job_type);
+        if !policy.allows_operation(&operation) {
+            return Err(format!("role {} may not run '{}'", role, operation));
+        }
+        if policy.device_tags.is_empty() {
+            return Ok(());
+        }
+
+        let mut devices: Vec<&str> = job.device_id.iter().map(String::as_str).collect();
+        if let JobType::Clone {
+            source_device,
+            target_device,
+        } = &job.job_type
+        {
+            devices.extend([source_device.as_str(), target_device.as_str()]);
+        }
+        for id in &devices {
+            let tags = pool
+                .get_device(id)
+                .map(|d| d.tags.as_slice())
+                .unwrap_or(&[]);
+            if !policy.allows_device(tags) {
+                return Err(format!("role {} may not use device {}", role, id));
+            }
+        }
+        if job.device_id.is_some() || policy.allows_device(&job.required_tags) {
+            return Ok(());
+        }
+        match policy.device_tags.as_slice() {
+            [tag] => {
+                job.required_tags.push(tag.clone());
+                Ok(())
+            }
+            tags => Err(format!(
+                "role {} must target a device tagged {}",
+                role,
+                tags.join(" or ")
+            )),
+        }
+    }
+}
+
+// ============================================================================
+// Start-up Credentials
+// ============================================================================
+
+/// Secrets accepted by the configured [`AuthMethod`]
+///
+/// `RestApiConfig` only names the scheme; the secrets are supplied at
+/// start-up so they never end up in a serialized config file. The REST and
+/// gRPC front ends check them the same way, see
+/// [`Credentials::authenticate`].
+#[derive(Clone, Default)]
+pub struct Credentials {
+    api_keys: Vec<String>,
+    bearer_tokens: Vec<String>,
+    basic_users: Vec<(String, String)>,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_keys", &self.api_keys.len())
+            .field("bearer_tokens", &self.bearer_tokens.len())
+            .field("basic_users", &self.basic_users.len())
+            .finish()
+    }
+}
+
+impl Credentials {
+    /// Accept an API key (for [`AuthMethod::ApiKey`])
+    pub fn with_api_key(mut self, key: &str) -> Self {
+        self.api_keys.push(key.to_string());
+        self
+    }
+
+    /// Accept a bearer token (for [`AuthMethod::BearerToken`])
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.bearer_tokens.push(token.to_string());
+        self
+    }
+
+    /// Accept a user/password pair (for [`AuthMethod::BasicAuth`])
+    pub fn with_basic_user(mut self, user: &str, password: &str) -> Self {
+        self.basic_users
+            .push((user.to_string(), password.to_string()));
+        self
+    }
+
+    /// Whether any secret usable with `method` is configured
+    pub fn supports(&self, method: &AuthMethod) -> bool {
+        match method {
+            AuthMethod::None => true,
+            AuthMethod::ApiKey { .. } => !self.api_keys.is_empty(),
+            AuthMethod::BearerToken => !self.bearer_tokens.is_empty(),
+            AuthMethod::BasicAuth => !self.basic_users.is_empty(),
+        }
+    }
+
+    /// Identify a caller by the credentials `method` expects
+    ///
+    /// `header` looks up a request header (or gRPC metadata entry) by
+    /// lower-case name. User accounts of `access` are tried first, with the
+    /// secret in place of the scheme's credential or as a bearer token
+    /// under any scheme (so users can log in when `method` is `None`); the
+    /// start-up credentials act with its fallback role under an ID derived
+    /// from the secret, which doubles as the rate-limit key. Anyone else is
+    /// `peer` when `method` is `None`. `None` when the credentials are
+    /// missing or invalid.
+    #[cfg(any(feature = "rest-server", feature = "grpc-server"))]
+    pub fn authenticate<'a>(
+        &self,
+        access: &AccessConfig,
+        method: &AuthMethod,
+        header: impl Fn(&str) -> Option<&'a str>,
+        peer: &str,
+    ) -> Option<Principal> {
+        use base64::Engine;
+
+        let fallback = |id: String| Principal::new(&id, access.fallback_role());
+        let bearer = header("authorization").and_then(|h| strip_scheme(h, "Bearer"));
+        if let Some(principal) = bearer.and_then(|token| access.authenticate(None, token)) {
+            return Some(principal);
+        }
+        match method {
+            // A wrong user secret is refused rather than downgraded
+            AuthMethod::None if bearer.is_some() => None,
+            AuthMethod::None => Some(fallback(peer.to_string())),
+            AuthMethod::ApiKey { header_name } => {
+                let key = header(&header_name.to_ascii_lowercase()).unwrap_or_default();
+                if let Some(principal) = access.authenticate(None, key) {
+                    return Some(principal);
+                }
+                self.api_keys
+                    .iter()
+                    .find(|k| constant_time_eq(k.as_bytes(), key.as_bytes()))
+                    .map(|k| fallback(format!("key:{}", fingerprint(k))))
+            }
+            AuthMethod::BearerToken => {
+                let token = bearer.unwrap_or_default();
+                self.bearer_tokens
+                    .iter()
+                    .find(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+                    .map(|t| fallback(format!("token:{}", fingerprint(t))))
+            }
+            AuthMethod::BasicAuth => {
+                let decoded = header("authorization")
+                    .and_then(|h| strip_scheme(h, "Basic"))
+                    .and_then(|b64| base64::engine::general_purpose::STANDARD.decode(b64).ok())
+                    .and_then(|raw| String::from_utf8(raw).ok())
+                    .unwrap_or_default();
+                let (user, password) = decoded.split_once(':').unwrap_or_default();
+                if let Some(principal) = access.authenticate(Some(user), password) {
+                    return Some(principal);
+                }
+                // Check every entry so timing does not reveal valid user names
+                let mut matched = None;
+                for (u, p) in &self.basic_users {
+                    let ok = constant_time_eq(u.as_bytes(), user.as_bytes())
+                        & constant_time_eq(p.as_bytes(), password.as_bytes());
+                    if ok && matched.is_none() {
+                        matched = Some(fallback(format!("user:{}", u)));
+                    }
+                }
+                matched
+            }
+        }
+    }
+}
+
+/// Short stable identifier for a secret
+#[cfg(any(feature = "rest-server", feature = "grpc-server"))]
+fn fingerprint(secret: &str) -> String {
+    hex(&Sha256::digest(secret.as_bytes())[..8])
+}
+
+/// Value of an `Authorization` header using `scheme` (case-insensitive)
+#[cfg(any(feature = "rest-server", feature = "grpc-server"))]
+fn strip_scheme<'a>(header: &'a str, scheme: &str) -> Option<&'a str> {
+    let (name, value) = header.trim().split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme).then(|| value.trim())
+}
+
+// ============================================================================
+// Rate Limiting
+// ============================================================================
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-client token bucket built from [`RateLimitConfig`]
+///
+/// Each client starts with `burst_size` tokens which refill at
+/// `requests_per_minute / 60` per second. The REST and gRPC front ends
+/// charge the caller's bucket before checking [`Credentials`].
+#[derive(Debug)]
+pub struct RateLimiter {
+    enabled: bool,
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: HashMap<String, Bucket>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            capacity: config.burst_size.max(1) as f64,
+            refill_per_sec: config.requests_per_minute as f64 / 60.0,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Take one token for `client`, or return how long to wait for one
+    pub fn check(&mut self, client: &str, now: Instant) -> Result<(), Duration> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.buckets.len() >= limits::MAX_TRACKED_CLIENTS && !self.buckets.contains_key(client) {
+            self.evict_idle(now);
+        }
+
+        let capacity = self.capacity;
+        let refill = self.refill_per_sec;
+        let bucket = self.buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if refill > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill))
+        } else {
+            Err(Duration::from_secs(60))
+        }
+    }
+
+    /// Drop buckets that have refilled completely; they behave like new ones
+    fn evict_idle(&mut self, now: Instant) {
+        let (capacity, refill) = (self.capacity, self.refill_per_sec);
+        self.buckets.retain(|_, b| {
+            let elapsed = now.saturating_duration_since(b.updated).as_secs_f64();
+            b.tokens + elapsed * refill < capacity
+        });
+    }
+
+    /// Number of clients currently tracked
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+// ============================================================================
+// Access Log
+// ============================================================================
+
+/// One access log entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
+pub struct AccessRecord {
+    /// Position in the log, starting at 1
+    pub seq: u64,
+    /// Unix epoch ms
+    pub timestamp: u64,
+    /// Client ID of the caller or job submitter
+    pub user: String,
+    /// Role the caller acted with, if known
+    pub role: Option<Role>,
+    /// `submit`, `cancel` or `run`
+    pub action: String,
+    /// `allowed`, `denied`, `completed`, `failed`, `timed_out`, ...
+    pub outcome: String,
+    pub job_id: Option<u64>,
+    /// Operation name (see [`operation_name`])
+    pub operation: Option<String>,
+    pub device_id: Option<String>,
+    /// Chip reported by the device
+    pub chip_id: Option<String>,
+    /// Denial reason or error
+    pub detail: Option<String>,
+    /// Hash of the previous record ([`limits::GENESIS_HASH`] for the first)
+    pub prev_hash: String,
+    /// SHA-256 over `prev_hash` and this record without `hash`
+    pub hash: String,
+}
+
+impl AccessRecord {
+    /// Entry for `user` doing `action`; sequence, time and hashes are set
+    /// when it is appended
+    pub fn new(user: &str, action: &str, outcome: &str) -> Self {
+        Self {
+            seq: 0,
+            timestamp: 0,
+            user: user.to_string(),
+            role: None,
+            action: action.to_string(),
+            outcome: outcome.to_string(),
+            job_id: None,
+            operation: None,
+            device_id: None,
+            chip_id: None,
+            detail: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    pub fn with_role(mut self, role: Option<Role>) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Job ID and operation of `job`
+    pub fn with_job(mut self, job: &Job) -> Self {
+        self.job_id = Some(job.id);
+        self.operation = Some(operation_name(&job.job_type));
+        self
+    }
+
+    pub fn with_device(mut self, device_id: Option<String>) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    pub fn with_chip(mut self, chip_id: Option<String>) -> Self {
+        self.chip_id = chip_id;
+        self
+    }
+
+    pub fn with_detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    /// Hash this record should carry
+    pub fn compute_hash(&self) -> String {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(serde_json::to_vec(&unhashed).unwrap_or_default());
+        hex(&hasher.finalize())
+    }
+}
+
+/// Check that `records` (consecutive, oldest first) form an intact chain
+///
+/// A slice starting at sequence 1 must start from
+/// [`limits::GENESIS_HASH`].
+pub fn verify_chain(records: &[AccessRecord]) -> AccessResult<()> {
+    let mut prev: Option<&AccessRecord> = None;
+    for record in records {
+        let tampered = |reason: &str| AccessError::Tampered {
+            seq: record.seq,
+            reason: reason.to_string(),
+        };
+        match prev {
+            Some(prev) if record.seq != prev.seq + 1 => {
+                return Err(tampered("sequence gap"));
+            }
+            Some(prev) if record.prev_hash != prev.hash => {
+                return Err(tampered("previous hash does not match"));
+            }
+            None if record.seq == 1 && record.prev_hash != limits::GENESIS_HASH => {
+                return Err(tampered("first record does not start the chain"));
+            }
+            _ => {}
+        }
+        if record.hash != record.compute_hash() {
+            return Err(tampered("hash does not match contents"));
+        }
+        prev = Some(record);
+    }
+    Ok(())
+}
+
+/// Append-only, hash-chained access log, in a JSON-lines file or in memory
+#[derive(Debug)]
+pub struct AccessLog {
+    file: Option<(File, std::path::PathBuf)>,
+    records: Vec<AccessRecord>,
+    head: String,
+    next_seq: u64,
+}
+
+impl AccessLog {
+    /// Log kept in memory (the last [`limits::MAX_MEMORY_RECORDS`] records)
+    pub fn in_memory() -> Self {
+        Self {
+            file: None,
+            records: Vec::new(),
+            head: limits::GENESIS_HASH.to_string(),
+            next_seq: 1,
+        }
+    }
+
+    /// Open or create the log at `path`, refusing a broken chain
+    pub fn open(path: &Path) -> AccessResult<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let (records, good_len) = read_lines::<AccessRecord>(path)?;
+        if records.first().is_some_and(|r| r.seq != 1) {
+            return Err(AccessError::Tampered {
+                seq: records[0].seq,
+                reason: "log does not start at record 1".to_string(),
+            });
+        }
+        verify_chain(&records)?;
+        let (head, next_seq) = match records.last() {
+            Some(last) => (last.hash.clone(), last.seq + 1),
+            None => (limits::GENESIS_HASH.to_string(), 1),
+        };
+        Ok(Self {
+            file: Some((open_append(path, good_len)?, path.to_path_buf())),
+            records: Vec::new(),
+            head,
+            next_seq,
+        })
+    }
+
+    /// Hash of the latest record
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Chain `record` onto the log and store it
+    pub fn append(&mut self, mut record: AccessRecord) -> AccessResult<AccessRecord> {
+        record.seq = self.next_seq;
+        record.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        record.prev_hash = self.head.clone();
+        record.hash = record.compute_hash();
+        match &mut self.file {
+            Some((file, _)) => {
+                let mut line = serde_json::to_vec(&record)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                line.push(b'\n');
+                file.write_all(&line)?;
+                file.sync_data()?;
+            }
+            None => {
+                if self.records.len() >= limits::MAX_MEMORY_RECORDS {
+                    self.records.remove(0);
+                }
+                self.records.push(record.clone());
+            }
+        }
+        self.head = record.hash.clone();
+        self.next_seq += 1;
+        Ok(record)
+    }
+
+    /// Stored records, oldest first
+    pub fn records(&self) -> AccessResult<Vec<AccessRecord>> {
+        match &self.file {
+            Some((_, path)) => Ok(read_lines(path)?.0),
+            None => Ok(self.records.clone()),
+        }
+    }
+}
+
+/// Response of `GET /access-log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
+pub struct AccessLogResponse {
+    /// Hash of the latest record
+    pub head: String,
+    /// Whether the returned records form an intact chain
+    pub verified: bool,
+    pub records: Vec<AccessRecord>,
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{DevicePlatform, PoolDevice};
+    use crate::test_support::scratch_dir;
+
+    fn custom(command: &str) -> JobType {
+        JobType::Custom {
+            command: command.to_string(),
+            params: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_default_policies() {
+        let erase = JobType::Erase {
+            start_address: 0,
+            length: None,
+        };
+        let operator = RolePolicy::defaults(Role::Operator);
+        let engineer = RolePolicy::defaults(Role::Engineer);
+        let admin = RolePolicy::defaults(Role::Admin);
+        assert!(operator.allows_operation("read"));
+        assert!(!operator.allows_operation(&operation_name(&erase)));
+        assert!(engineer.allows_operation(&operation_name(&erase)));
+        assert!(engineer.allows_operation("clone"));
+        assert!(engineer.allows_operation(&operation_name(&custom("rpmb_read"))));
+        assert!(!engineer.allows_operation(&operation_name(&custom("rpmb_write_key"))));
+        assert!(!engineer.allows_operation(&operation_name(&custom("emmc_partition_commit"))));
+        assert!(admin.allows_operation(&operation_name(&custom("emmc_partition_commit"))));
+        assert!(!RolePolicy::defaults(Role::Viewer).allows_operation("read"));
+        assert!(!engineer.allows(Permission::ViewAccessLog));
+    }
+
+    #[test]
+    fn test_authorize_job_by_role_and_device_tag() {
+        let mut config = AccessConfig {
+            enabled: true,
+            ..Default::default()
+        }
+        .with_user(UserAccount::new("alice", Role::Operator, "s3cret"));
+        let mut policy = RolePolicy::defaults(Role::Operator);
+        policy.device_tags = vec!["line1".to_string()];
+        config.policies.insert(Role::Operator, policy);
+
+        assert_eq!(config.authenticate(None, "wrong"), None);
+        assert_eq!(config.authenticate(Some("bob"), "s3cret"), None);
+        let alice = config.authenticate(Some("alice"), "s3cret").unwrap();
+        assert_eq!(alice, Principal::new("user:alice", Role::Operator));
+
+        let mut pool = DevicePool::new(4);
+        let mut bench = PoolDevice::new("bench", "Bench", "tcp://a", DevicePlatform::RP2040);
+        bench.tags = vec!["lab".to_string()];
+        pool.add_device(bench).unwrap();
+
+        let read = JobType::Read {
+            output_path: "dump.bin".to_string(),
+            start_address: 0,
+            length: None,
+            include_oob: false,
+        };
+        let mut job = Job::new("read", read.clone());
+        assert!(config.authorize_job(&alice, &mut job, &pool).is_ok());
+        assert_eq!(job.required_tags, vec!["line1".to_string()]);
+
+        let mut job = Job::new("read", read).with_device("bench");
+        assert!(config.authorize_job(&alice, &mut job, &pool).is_err());
+        let mut job = Job::new(
+            "erase",
+            JobType::Erase {
+                start_address: 0,
+                length: None,
+            },
+        );
+        assert!(config
+            .authorize_job(&alice, &mut job, &pool)
+            .unwrap_err()
+            .contains("erase"));
+    }
+
+    #[test]
+    fn test_rate_limiter_refills() {
+        let mut limiter = RateLimiter::new(&RateLimitConfig {
+            requests_per_minute: 60,
+            burst_size: 1,
+            enabled: true,
+        });
+        let start = Instant::now();
+        assert!(limiter.check("a", start).is_ok());
+        assert!(limiter.check("a", start).is_err());
+        assert!(limiter.check("a", start + Duration::from_secs(1)).is_ok());
+        assert_eq!(limiter.tracked_clients(), 1);
+    }
+
+    #[test]
+    fn test_access_log_chain_detects_tampering() {
+        let dir = scratch_dir("openflash_access_log");
+        let path = dir.join(limits::LOG_FILE);
+        {
+            let mut log = AccessLog::open(&path).unwrap();
+            log.append(AccessRecord::new("user:alice", "submit", "allowed"))
+                .unwrap();
+            log.append(AccessRecord::new("user:bob", "submit", "denied"))
+                .unwrap();
+        }
+        let mut log = AccessLog::open(&path).unwrap();
+        let third = log
+            .append(AccessRecord::new("user:alice", "cancel", "allowed"))
+            .unwrap();
+        assert_eq!(third.seq, 3);
+        let records = log.records().unwrap();
+        assert!(verify_chain(&records).is_ok());
+        assert_eq!(log.head(), records[2].hash);
+
+        let mut edited = records.clone();
+        edited[1].outcome = "allowed".to_string();
+        assert!(matches!(
+            verify_chain(&edited),
+            Err(AccessError::Tampered { seq: 2, .. })
+        ));
+        let removed = [records[0].clone(), records[2].clone()];
+        assert!(verify_chain(&removed).is_err());
+
+        let text = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("user:bob", "user:eve");
+        std::fs::write(&path, text).unwrap();
+        assert!(AccessLog::open(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,915 @@
+//! Persistent incremental backup repository
+//!
+//! Stores NAND dumps on disk as a content-addressed block store so that the
+//! history of a device costs little more than the blocks that actually
+//! changed between dumps. Layout under the repository root:
+//!
+//! ```text
+//! repo.json                      format version
+//! blocks/<aa>/<sha256 hex>       raw erase-block contents, deduplicated
+//! manifests/<backup id>.json     BackupMetadata + block -> hash map
+//! ```
+//!
+//! A full backup references every block of the image. An incremental backup
+//! references only the blocks whose contents differ from its parent, so a
+//! point-in-time image is rebuilt by walking the parent chain from the full
+//! backup forward.
+
+use crate::write_ops::{BackupMetadata, ChangeTracker};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Backup repository errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupError {
+    /// Filesystem error
+    Io(String),
+    /// Root is not a usable repository
+    InvalidRepository(String),
+    /// Backup ID not found
+    NotFound(String),
+    /// Backup references a parent that no longer exists
+    BrokenChain { id: String, missing: String },
+    /// Image size does not match the parent backup
+    GeometryMismatch { expected: u64, actual: u64 },
+    /// Image does not end on a block boundary
+    TruncatedImage { expected: usize, actual: usize },
+    /// Block index outside the image
+    InvalidBlock(u32),
+    /// Stored block does not match its hash
+    CorruptBlock { block: u32, hash: String },
+    /// Block missing from the resolved backup
+    MissingBlock(u32),
+    /// Manifest (de)serialization error
+    Serialization(String),
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Io(msg) => write!(f, "I/O error: {}", msg),
+            BackupError::InvalidRepository(msg) => write!(f, "Invalid repository: {}", msg),
+            BackupError::NotFound(id) => write!(f, "Backup {} not found", id),
+            BackupError::BrokenChain { id, missing } => {
+                write!(f, "Backup {} depends on missing backup {}", id, missing)
+            }
+            BackupError::GeometryMismatch { expected, actual } => write!(
+                f,
+                "Image size mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            BackupError::TruncatedImage { expected, actual } => write!(
+                f,
+                "Image ends mid-block: expected {} bytes, got {}",
+                expected, actual
+            ),
+            BackupError::InvalidBlock(block) => write!(f, "Block {} is outside the image", block),
+            BackupError::CorruptBlock { block, hash } => {
+                write!(f, "Block {} does not match stored hash {}", block, hash)
+            }
+            BackupError::MissingBlock(block) => write!(f, "Block {} missing from backup", block),
+            BackupError::Serialization(msg) => write!(f, "Manifest error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BackupError {
+    fn from(e: serde_json::Error) -> Self {
+        BackupError::Serialization(e.to_string())
+    }
+}
+
+pub type BackupResult<T> = Result<T, BackupError>;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// On-disk repository format version
+pub const REPO_FORMAT_VERSION: u32 = 1;
+
+const REPO_CONFIG_FILE: &str = "repo.json";
+const BLOCKS_DIR: &str = "blocks";
+const MANIFESTS_DIR: &str = "manifests";
+
+// ============================================================================
+// Manifests and Reports
+// ============================================================================
+
+/// Per-backup manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Backup ID (also the manifest file name)
+    pub id: String,
+    /// Dump description; `included_blocks` and `checksums` cover `blocks`
+    pub metadata: BackupMetadata,
+    /// SHA-256 of every block stored by this backup
+    pub blocks: BTreeMap<u32, String>,
+}
+
+impl BackupManifest {
+    /// Bytes per erase block
+    pub fn block_bytes(&self) -> usize {
+        self.metadata.page_size as usize * self.metadata.block_size as usize
+    }
+
+    /// Number of blocks in the image
+    pub fn total_blocks(&self) -> u32 {
+        match self.block_bytes() {
+            0 => 0,
+            bytes => (self.metadata.total_size / bytes as u64) as u32,
+        }
+    }
+}
+
+/// Result of `BackupRepository::prune`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    /// Backups whose manifests were deleted
+    pub removed_backups: Vec<String>,
+    /// Block objects no longer referenced by any backup
+    pub removed_blocks: usize,
+    /// Bytes released from the block store
+    pub freed_bytes: u64,
+}
+
+/// Result of `BackupRepository::verify`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// Manifests examined
+    pub backups_checked: usize,
+    /// Distinct block objects rehashed
+    pub blocks_checked: usize,
+    /// Objects whose contents no longer match their hash
+    pub corrupt_blocks: Vec<String>,
+    /// Objects referenced by a manifest but absent from the store
+    pub missing_blocks: Vec<String>,
+    /// Backups whose parent chain cannot be resolved
+    pub broken_chains: Vec<String>,
+}
+
+impl VerifyReport {
+    /// True when every backup can be restored
+    pub fn is_ok(&self) -> bool {
+        self.corrupt_blocks.is_empty()
+            && self.missing_blocks.is_empty()
+            && self.broken_chains.is_empty()
+    }
+}
+
+/// Repository space usage
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoStats {
+    /// Number of backups
+    pub backups: usize,
+    /// Distinct block objects on disk
+    pub unique_blocks: usize,
+    /// Bytes used by the block store
+    pub stored_bytes: u64,
+    /// Sum of the image sizes of all backups
+    pub logical_bytes: u64,
+}
+
+impl RepoStats {
+    /// Logical bytes per stored byte
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            return 0.0;
+        }
+        self.logical_bytes as f64 / self.stored_bytes as f64
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RepoConfig {
+    version: u32,
+}
+
+// ============================================================================
+// Repository
+// ============================================================================
+
+/// On-disk backup repository
+#[derive(Debug, Clone)]
+pub struct BackupRepository {
+    root: PathBuf,
+}
+
+impl BackupRepository {
+    /// Create a repository at `root`, or open it if one already exists
+    pub fn init<P: AsRef<Path>>(root: P) -> BackupResult<Self> {
+        let root = root.as_ref().to_path_buf();
+        if root.join(REPO_CONFIG_FILE).exists() {
+            return Self::open(root);
+        }
+        fs::create_dir_all(root.join(BLOCKS_DIR))?;
+        fs::create_dir_all(root.join(MANIFESTS_DIR))?;
+        let config = RepoConfig {
+            version: REPO_FORMAT_VERSION,
+        };
+        write_atomic(
+            &root.join(REPO_CONFIG_FILE),
+            &serde_json::to_vec_pretty(&config)?,
+        )?;
+        Ok(Self { root })
+    }
+
+    /// Open an existing repository
+    pub fn open<P: AsRef<Path>>(root: P) -> BackupResult<Self> {
+        let root = root.as_ref().to_path_buf();
+        let raw = fs::read(root.join(REPO_CONFIG_FILE)).map_err(|_| {
+            BackupError::InvalidRepository(format!(
+                "{} has no {}",
+                root.display(),
+                REPO_CONFIG_FILE
+            ))
+        })?;
+        let config: RepoConfig = serde_json::from_slice(&raw)?;
+        if config.version != REPO_FORMAT_VERSION {
+            return Err(BackupError::InvalidRepository(format!(
+                "unsupported format version {}",
+                config.version
+            )));
+        }
+        Ok(Self { root })
+    }
+
+    /// Repository root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // ------------------------------------------------------------------------
+    // Backup
+    // ------------------------------------------------------------------------
+
+    /// Store a full dump read from `image`
+    ///
+    /// `block_size` is in pages, matching `BackupMetadata`. The image is
+    /// consumed one erase block at a time, so it never has to fit in memory.
+    pub fn backup_full<R: Read>(
+        &self,
+        chip_id: &str,
+        page_size: u32,
+        block_size: u32,
+        bad_blocks: &[u32],
+        image: R,
+    ) -> BackupResult<BackupManifest> {
+        let block_bytes = page_size as usize * block_size as usize;
+        if block_bytes == 0 {
+            return Err(BackupError::InvalidBlock(0));
+        }
+        let mut metadata = BackupMetadata::new_full(chip_id.to_string(), 0, page_size, block_size);
+        let mut blocks = BTreeMap::new();
+        let total_size = self.ingest(image, block_bytes, &mut metadata, &mut blocks, None)?;
+        metadata.total_size = total_size;
+        self.commit(metadata, blocks, bad_blocks)
+    }
+
+    /// Store a full re-dump of the device as an incremental on `parent_id`
+    ///
+    /// Only blocks whose contents differ from the parent's point-in-time
+    /// image are recorded.
+    pub fn backup_incremental<R: Read>(
+        &self,
+        parent_id: &str,
+        bad_blocks: &[u32],
+        image: R,
+    ) -> BackupResult<BackupManifest> {
+        let (parent, state) = self.resolve(parent_id)?;
+        let mut metadata = incremental_metadata(&parent);
+        let mut blocks = BTreeMap::new();
+        let total_size = self.ingest(
+            image,
+            parent.block_bytes(),
+            &mut metadata,
+            &mut blocks,
+            Some(&state),
+        )?;
+        if total_size != parent.metadata.total_size {
+            return Err(BackupError::GeometryMismatch {
+                expected: parent.metadata.total_size,
+                actual: total_size,
+            });
+        }
+        self.commit(metadata, blocks, bad_blocks)
+    }
+
+    /// Store an incremental from individually read blocks
+    ///
+    /// Intended for dumps driven by `ChangeTracker::get_modified_blocks`,
+    /// where only the blocks known to have changed are read back.
+    pub fn backup_blocks<I>(
+        &self,
+        parent_id: &str,
+        bad_blocks: &[u32],
+        changed: I,
+    ) -> BackupResult<BackupManifest>
+    where
+        I: IntoIterator<Item = (u32, Vec<u8>)>,
+    {
+        let (parent, state) = self.resolve(parent_id)?;
+        let block_bytes = parent.block_bytes();
+        let total_blocks = parent.total_blocks();
+        let mut metadata = incremental_metadata(&parent);
+        let mut blocks = BTreeMap::new();
+
+        for (block, data) in changed {
+            if block >= total_blocks {
+                return Err(BackupError::InvalidBlock(block));
+            }
+            if data.len() != block_bytes {
+                return Err(BackupError::TruncatedImage {
+                    expected: block_bytes,
+                    actual: data.len(),
+                });
+            }
+            self.record_block(block, &data, &mut metadata, &mut blocks, Some(&state))?;
+        }
+        self.commit(metadata, blocks, bad_blocks)
+    }
+
+    /// Change tracker seeded with the block checksums of backup `id`
+    pub fn tracker(&self, id: &str) -> BackupResult<ChangeTracker> {
+        let chain = self.chain(id)?;
+        let mut tracker = ChangeTracker::new(chain[chain.len() - 1].total_blocks());
+        for manifest in &chain {
+            for (&block, &checksum) in &manifest.metadata.checksums {
+                tracker.update_checksum(block, checksum);
+            }
+        }
+        tracker.clear_modified();
+        Ok(tracker)
+    }
+
+    fn ingest<R: Read>(
+        &self,
+        mut image: R,
+        block_bytes: usize,
+        metadata: &mut BackupMetadata,
+        blocks: &mut BTreeMap<u32, String>,
+        parent: Option<&BTreeMap<u32, String>>,
+    ) -> BackupResult<u64> {
+        let mut buf = vec![0u8; block_bytes];
+        let mut total = 0u64;
+        let mut block = 0u32;
+        loop {
+            let n = read_full(&mut image, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            if n < block_bytes {
+                return Err(BackupError::TruncatedImage {
+                    expected: total as usize + block_bytes,
+                    actual: total as usize + n,
+                });
+            }
+            self.record_block(block, &buf, metadata, blocks, parent)?;
+            total += n as u64;
+            block += 1;
+        }
+        Ok(total)
+    }
+
+    fn record_block(
+        &self,
+        block: u32,
+        data: &[u8],
+        metadata: &mut BackupMetadata,
+        blocks: &mut BTreeMap<u32, String>,
+        parent: Option<&BTreeMap<u32, String>>,
+    ) -> BackupResult<()> {
+        let hash = hash_block(data);
+        if parent.and_then(|p| p.get(&block)) == Some(&hash) {
+            return Ok(());
+        }
+        self.store_object(&hash, data)?;
+        metadata
+            .checksums
+            .insert(block, ChangeTracker::calculate_checksum(data));
+        blocks.insert(block, hash);
+        Ok(())
+    }
+
+    fn commit(
+        &self,
+        mut metadata: BackupMetadata,
+        blocks: BTreeMap<u32, String>,
+        bad_blocks: &[u32],
+    ) -> BackupResult<BackupManifest> {
+        metadata.included_blocks = blocks.keys().copied().collect();
+        metadata.bad_blocks = bad_blocks.to_vec();
+        metadata.bad_blocks.sort_unstable();
+        metadata.bad_blocks.dedup();
+
+        let base = sanitize_id(&metadata.generate_id());
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while self.manifest_path(&id).exists() {
+            id = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+
+        let manifest = BackupManifest {
+            id,
+            metadata,
+            blocks,
+        };
+        write_atomic(
+            &self.manifest_path(&manifest.id),
+            &serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        Ok(manifest)
+    }
+
+    // ------------------------------------------------------------------------
+    // Catalog
+    // ------------------------------------------------------------------------
+
+    /// Load a single manifest
+    pub fn manifest(&self, id: &str) -> BackupResult<BackupManifest> {
+        let path = self.manifest_path(id);
+        let raw = match fs::read(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(BackupError::NotFound(id.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    /// All backups, oldest first
+    pub fn list(&self) -> BackupResult<Vec<BackupManifest>> {
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(self.root.join(MANIFESTS_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                manifests.push(serde_json::from_slice::<BackupManifest>(&fs::read(&path)?)?);
+            }
+        }
+        manifests.sort_by(|a, b| (a.metadata.timestamp, &a.id).cmp(&(b.metadata.timestamp, &b.id)));
+        Ok(manifests)
+    }
+
+    /// Backups of one chip, oldest first
+    pub fn history(&self, chip_id: &str) -> BackupResult<Vec<BackupManifest>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|m| m.metadata.chip_id == chip_id)
+            .collect())
+    }
+
+    /// Parent chain of `id`, from the full backup up to `id` itself
+    pub fn chain(&self, id: &str) -> BackupResult<Vec<BackupManifest>> {
+        let mut chain = vec![self.manifest(id)?];
+        let mut seen = HashSet::new();
+        seen.insert(id.to_string());
+        while let Some(parent_id) = chain[chain.len() - 1].metadata.parent_id.clone() {
+            let child = chain[chain.len() - 1].id.clone();
+            if !seen.insert(parent_id.clone()) {
+                return Err(BackupError::BrokenChain {
+                    id: child,
+                    missing: parent_id,
+                });
+            }
+            match self.manifest(&parent_id) {
+                Ok(parent) => chain.push(parent),
+                Err(BackupError::NotFound(_)) => {
+                    return Err(BackupError::BrokenChain {
+                        id: child,
+                        missing: parent_id,
+                    })
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// Block map of the image as it was at backup `id`
+    fn resolve(&self, id: &str) -> BackupResult<(BackupManifest, BTreeMap<u32, String>)> {
+        let chain = self.chain(id)?;
+        let mut state = BTreeMap::new();
+        for manifest in &chain {
+            state.extend(manifest.blocks.iter().map(|(&b, h)| (b, h.clone())));
+        }
+        let target = chain.into_iter().last().expect("chain contains the target");
+        Ok((target, state))
+    }
+
+    /// Space usage across the whole repository
+    pub fn stats(&self) -> BackupResult<RepoStats> {
+        let manifests = self.list()?;
+        let mut stats = RepoStats {
+            backups: manifests.len(),
+            logical_bytes: manifests.iter().map(|m| m.metadata.total_size).sum(),
+            ..Default::default()
+        };
+        for (_, size) in self.objects()? {
+            stats.unique_blocks += 1;
+            stats.stored_bytes += size;
+        }
+        Ok(stats)
+    }
+
+    // ------------------------------------------------------------------------
+    // Restore
+    // ------------------------------------------------------------------------
+
+    /// Write the point-in-time image of backup `id` to `out`
+    ///
+    /// Every block is checked against its hash before it is written.
+    /// Returns the number of bytes written.
+    pub fn restore_to<W: Write>(&self, id: &str, mut out: W) -> BackupResult<u64> {
+        let (manifest, state) = self.resolve(id)?;
+        let mut written = 0u64;
+        for block in 0..manifest.total_blocks() {
+            let data = self.load_block(block, &state)?;
+            out.write_all(&data)?;
+            written += data.len() as u64;
+        }
+        out.flush()?;
+        Ok(written)
+    }
+
+    /// Point-in-time image of backup `id`
+    pub fn restore(&self, id: &str) -> BackupResult<Vec<u8>> {
+        let mut image = Vec::new();
+        self.restore_to(id, &mut image)?;
+        Ok(image)
+    }
+
+    /// Contents of one block as of backup `id`
+    pub fn restore_block(&self, id: &str, block: u32) -> BackupResult<Vec<u8>> {
+        let (manifest, state) = self.resolve(id)?;
+        if block >= manifest.total_blocks() {
+            return Err(BackupError::InvalidBlock(block));
+        }
+        self.load_block(block, &state)
+    }
+
+    fn load_block(&self, block: u32, state: &BTreeMap<u32, String>) -> BackupResult<Vec<u8>> {
+        let hash = state.get(&block).ok_or(BackupError::MissingBlock(block))?;
+        let data = match fs::read(self.object_path(hash)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(BackupError::MissingBlock(block))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if hash_block(&data) != *hash {
+            return Err(BackupError::CorruptBlock {
+                block,
+                hash: hash.clone(),
+            });
+        }
+        Ok(data)
+    }
+
+    // ------------------------------------------------------------------------
+    // Maintenance
+    // ------------------------------------------------------------------------
+
+    /// Keep the newest `keep_last` backups of `chip_id` and drop the rest
+    ///
+    /// Ancestors of kept backups are retained so that every kept backup can
+    /// still be restored. Blocks no longer referenced are deleted.
+    pub fn prune(&self, chip_id: &str, keep_last: usize) -> BackupResult<PruneReport> {
+        let history = self.history(chip_id)?;
+        let mut keep = HashSet::new();
+        for manifest in history.iter().rev().take(keep_last) {
+            for ancestor in self.chain(&manifest.id)? {
+                keep.insert(ancestor.id);
+            }
+        }
+
+        let mut report = PruneReport::default();
+        for manifest in history {
+            if !keep.contains(&manifest.id) {
+                fs::remove_file(self.manifest_path(&manifest.id))?;
+                report.removed_backups.push(manifest.id);
+            }
+        }
+        let (removed, freed) = self.collect_garbage()?;
+        report.removed_blocks = removed;
+        report.freed_bytes = freed;
+        Ok(report)
+    }
+
+    /// Delete block objects not referenced by any manifest
+    ///
+    /// Returns the number of objects removed and the bytes freed.
+    pub fn collect_garbage(&self) -> BackupResult<(usize, u64)> {
+        let referenced: HashSet<String> = self
+            .list()?
+            .into_iter()
+            .flat_map(|m| m.blocks.into_values())
+            .collect();
+        let mut removed = 0;
+        let mut freed = 0;
+        for (hash, size) in self.objects()? {
+            if !referenced.contains(&hash) {
+                fs::remove_file(self.object_path(&hash))?;
+                removed += 1;
+                freed += size;
+            }
+        }
+        Ok((removed, freed))
+    }
+
+    /// Rehash every stored block and check every parent chain
+    pub fn verify(&self) -> BackupResult<VerifyReport> {
+        let manifests = self.list()?;
+        let mut report = VerifyReport {
+            backups_checked: manifests.len(),
+            ..Default::default()
+        };
+
+        let mut checked = HashSet::new();
+        for manifest in &manifests {
+            if let Err(BackupError::BrokenChain { .. }) = self.chain(&manifest.id) {
+                report.broken_chains.push(manifest.id.clone());
+            }
+            for hash in manifest.blocks.values() {
+                if !checked.insert(hash.clone()) {
+                    continue;
+                }
+                match fs::read(self.object_path(hash)) {
+                    Ok(data) => {
+                        report.blocks_checked += 1;
+                        if hash_block(&data) != *hash {
+                            report.corrupt_blocks.push(hash.clone());
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        report.missing_blocks.push(hash.clone());
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    // ------------------------------------------------------------------------
+    // Block store
+    // ------------------------------------------------------------------------
+
+    fn manifest_path(&self, id: &str) -> PathBuf {
+        self.root
+            .join(MANIFESTS_DIR)
+            .join(format!("{}.json", sanitize_id(id)))
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join(BLOCKS_DIR).join(&hash[..2]).join(hash)
+    }
+
+    fn store_object(&self, hash: &str, data: &[u8]) -> BackupResult<()> {
+        let path = self.object_path(hash);
+        if path.exists() {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&path, data)
+    }
+
+    /// All stored objects with their sizes
+    fn objects(&self) -> BackupResult<Vec<(String, u64)>> {
+        let mut objects = Vec::new();
+        for shard in fs::read_dir(self.root.join(BLOCKS_DIR))? {
+            let shard = shard?;
+            if !shard.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(shard.path())? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    objects.push((name, entry.metadata()?.len()));
+                }
+            }
+        }
+        Ok(objects)
+    }
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+fn incremental_metadata(parent: &BackupManifest) -> BackupMetadata {
+    BackupMetadata::new_incremental(
+        parent.metadata.chip_id.clone(),
+        parent.metadata.total_size,
+        parent.metadata.page_size,
+        parent.metadata.block_size,
+        parent.id.clone(),
+    )
+}
+
+/// Lowercase hex SHA-256 of a block
+fn hash_block(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Make a backup ID safe to use as a file name
+fn sanitize_id(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Fill `buf` from `reader`, returning fewer bytes only at end of input
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Write via a temporary file so an interrupted backup never leaves a
+/// half-written manifest or block behind
+fn write_atomic(path: &Path, data: &[u8]) -> BackupResult<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: u32 = 64;
+    const PAGES_PER_BLOCK: u32 = 4;
+    const BLOCK_BYTES: usize = (PAGE * PAGES_PER_BLOCK) as usize;
+
+    fn temp_repo(name: &str) -> (PathBuf, BackupRepository) {
+        let root =
+            std::env::temp_dir().join(format!("openflash_backup_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let repo = BackupRepository::init(&root).unwrap();
+        (root, repo)
+    }
+
+    fn image(blocks: usize) -> Vec<u8> {
+        let mut data = vec![0xFF; blocks * BLOCK_BYTES];
+        for (i, chunk) in data.chunks_mut(BLOCK_BYTES).enumerate().step_by(2) {
+            chunk.fill(i as u8);
+        }
+        data
+    }
+
+    #[test]
+    fn test_full_and_incremental_restore() {
+        let (root, repo) = temp_repo("chain");
+        let v1 = image(8);
+        let full = repo
+            .backup_full("CHIP", PAGE, PAGES_PER_BLOCK, &[3], &v1[..])
+            .unwrap();
+        assert!(full.metadata.is_full);
+        assert_eq!(full.total_blocks(), 8);
+        assert_eq!(full.metadata.bad_blocks, vec![3]);
+
+        let mut v2 = v1.clone();
+        v2[2 * BLOCK_BYTES] = 0xAA;
+        let inc = repo.backup_incremental(&full.id, &[3], &v2[..]).unwrap();
+        assert_eq!(inc.metadata.parent_id.as_deref(), Some(full.id.as_str()));
+        assert_eq!(inc.metadata.included_blocks, vec![2]);
+
+        let mut v3 = v2.clone();
+        v3[5 * BLOCK_BYTES..6 * BLOCK_BYTES].fill(0x11);
+        let inc2 = repo
+            .backup_blocks(
+                &inc.id,
+                &[3, 7],
+                vec![(5, v3[5 * BLOCK_BYTES..6 * BLOCK_BYTES].to_vec())],
+            )
+            .unwrap();
+
+        assert_eq!(repo.restore(&full.id).unwrap(), v1);
+        assert_eq!(repo.restore(&inc.id).unwrap(), v2);
+        assert_eq!(repo.restore(&inc2.id).unwrap(), v3);
+        assert_eq!(repo.chain(&inc2.id).unwrap().len(), 3);
+
+        let tracker = repo.tracker(&inc2.id).unwrap();
+        let block5 = ChangeTracker::calculate_checksum(&v3[5 * BLOCK_BYTES..6 * BLOCK_BYTES]);
+        assert!(!tracker.has_changed(5, block5));
+
+        assert!(matches!(
+            repo.backup_incremental(&full.id, &[], &v1[..BLOCK_BYTES]),
+            Err(BackupError::GeometryMismatch { .. })
+        ));
+
+        let reopened = BackupRepository::open(&root).unwrap();
+        assert_eq!(reopened.list().unwrap().len(), 3);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_deduplication_and_prune() {
+        let (root, repo) = temp_repo("prune");
+        let v1 = image(8);
+        let full = repo
+            .backup_full("CHIP", PAGE, PAGES_PER_BLOCK, &[], &v1[..])
+            .unwrap();
+        let second = repo
+            .backup_full("CHIP", PAGE, PAGES_PER_BLOCK, &[], &v1[..])
+            .unwrap();
+        assert_ne!(full.id, second.id);
+
+        // 4 patterned blocks plus one shared erased block
+        let stats = repo.stats().unwrap();
+        assert_eq!(stats.unique_blocks, 5);
+        assert_eq!(stats.logical_bytes, 2 * v1.len() as u64);
+        assert!(stats.dedup_ratio() > 3.0);
+
+        let mut v2 = v1.clone();
+        v2[BLOCK_BYTES..2 * BLOCK_BYTES].fill(0x42);
+        let inc = repo.backup_incremental(&full.id, &[], &v2[..]).unwrap();
+
+        let report = repo.prune("CHIP", 1).unwrap();
+        assert_eq!(report.removed_backups, vec![second.id.clone()]);
+        assert_eq!(report.removed_blocks, 0);
+        assert_eq!(repo.restore(&inc.id).unwrap(), v2);
+        assert!(matches!(
+            repo.manifest(&second.id),
+            Err(BackupError::NotFound(_))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_verify_detects_damage() {
+        let (root, repo) = temp_repo("verify");
+        let v1 = image(4);
+        let full = repo
+            .backup_full("CHIP", PAGE, PAGES_PER_BLOCK, &[], &v1[..])
+            .unwrap();
+        let inc = repo
+            .backup_blocks(&full.id, &[], vec![(1, vec![0x5A; BLOCK_BYTES])])
+            .unwrap();
+        assert!(repo.verify().unwrap().is_ok());
+
+        let hash = full.blocks[&0].clone();
+        fs::write(repo.object_path(&hash), b"garbage").unwrap();
+        let report = repo.verify().unwrap();
+        assert_eq!(report.corrupt_blocks, vec![hash]);
+        assert!(matches!(
+            repo.restore(&full.id),
+            Err(BackupError::CorruptBlock { block: 0, .. })
+        ));
+
+        fs::remove_file(repo.manifest_path(&full.id)).unwrap();
+        let report = repo.verify().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.broken_chains, vec![inc.id.clone()]);
+        assert!(matches!(
+            repo.restore(&inc.id),
+            Err(BackupError::BrokenChain { .. })
+        ));
+        assert!(matches!(
+            repo.backup_full("CHIP", PAGE, PAGES_PER_BLOCK, &[], &v1[..10]),
+            Err(BackupError::TruncatedImage { .. })
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,811 @@
+//! Differential ("write only what changed") programming
+//!
+//! Compares a new image with the chip one erase unit at a time and only
+//! touches the units that differ: NAND blocks, or SPI NOR sub-sectors. The
+//! current contents come from reading the chip back, or from the checksum
+//! map of a `ChangeTracker` kept from the previous write.
+//!
+//! NOR can clear bits without an erase, so a NOR unit whose new contents
+//! only turn 1s into 0s is programmed in place. NAND units that differ are
+//! always erased and rewritten whole.
+//!
+//! Both chip types are driven through `ProgramTarget`. For SPI NOR the
+//! "block" is the sub-sector index and the "page" is the program page
+//! within it, with no OOB.
+
+use crate::ecc::{encode_with_ecc, EccAlgorithm};
+use crate::nand_geometry::NandGeometry;
+use crate::spi_nor::SpiNorChipInfo;
+use crate::write_ops::{
+    ChangeTracker, ProgramOperation, ProgramProgress, ProgramTarget, WriteError, WriteResult,
+};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Geometry and Timings
+// ============================================================================
+
+/// Per-operation timings used to estimate the time a write takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashTimings {
+    /// Read one page
+    pub read_page_us: u32,
+    /// Program one page
+    pub program_page_us: u32,
+    /// Erase one unit
+    pub erase_unit_us: u32,
+}
+
+impl FlashTimings {
+    /// Typical SLC NAND (tR, tPROG, tBERS)
+    pub const NAND: Self = Self {
+        read_page_us: 25,
+        program_page_us: 300,
+        erase_unit_us: 3_000,
+    };
+
+    /// Typical SPI NOR: 256-byte read at 50 MHz, page program, 4 KB erase
+    pub const SPI_NOR: Self = Self {
+        read_page_us: 45,
+        program_page_us: 700,
+        erase_unit_us: 45_000,
+    };
+}
+
+/// Erase-unit layout of the chip being written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffGeometry {
+    /// Program page size
+    pub page_size: u32,
+    /// OOB bytes per page (0 for NOR)
+    pub oob_size: u32,
+    /// Pages per erase unit
+    pub pages_per_unit: u32,
+    /// Erase units on the chip
+    pub total_units: u32,
+    /// Bits can be cleared by programming without an erase (NOR)
+    pub program_without_erase: bool,
+    /// Timings for the time-saved estimate
+    pub timings: FlashTimings,
+}
+
+impl DiffGeometry {
+    /// NAND, one unit per erase block
+    pub fn nand(geometry: &NandGeometry) -> Self {
+        Self {
+            page_size: geometry.page_size,
+            oob_size: geometry.oob_size,
+            pages_per_unit: geometry.pages_per_block,
+            total_units: geometry.total_blocks(),
+            program_without_erase: false,
+            timings: FlashTimings::NAND,
+        }
+    }
+
+    /// SPI NOR, one unit per sub-sector (`sector_size`, typically 4 KB)
+    pub fn spi_nor(chip: &SpiNorChipInfo) -> Self {
+        Self {
+            page_size: chip.page_size,
+            oob_size: 0,
+            pages_per_unit: chip.sector_size / chip.page_size,
+            total_units: chip.size_bytes / chip.sector_size,
+            program_without_erase: true,
+            timings: FlashTimings::SPI_NOR,
+        }
+    }
+
+    /// Use measured or datasheet timings instead of the typical ones
+    pub fn with_timings(mut self, timings: FlashTimings) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Bytes per erase unit
+    pub fn unit_size(&self) -> usize {
+        self.page_size as usize * self.pages_per_unit as usize
+    }
+}
+
+// ============================================================================
+// Plan
+// ============================================================================
+
+/// What has to happen to one erase unit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnitAction {
+    /// Contents already match
+    Unchanged,
+    /// Only bits 1 -> 0 change; program these pages without erasing (NOR)
+    ProgramInPlace(Vec<u32>),
+    /// Erase and program the whole unit
+    Rewrite,
+}
+
+/// Planned action for one erase unit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffUnit {
+    /// Erase unit on the chip
+    pub unit: u32,
+    /// Required action
+    pub action: UnitAction,
+}
+
+/// Result of comparing an image with the chip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffPlan {
+    /// First unit of the image
+    pub start_unit: u32,
+    /// Image length in bytes
+    pub image_len: u64,
+    /// FNV-1a checksum of the image
+    pub image_checksum: u64,
+    /// Action per unit, in image order
+    pub units: Vec<DiffUnit>,
+    /// Pages read while planning
+    pub pages_read: u32,
+    /// Non-blank pages a full write would program
+    pub full_program_pages: u32,
+    /// Non-blank pages this plan programs
+    pub program_pages: u32,
+}
+
+impl DiffPlan {
+    /// Units that need any write
+    pub fn changed_units(&self) -> usize {
+        self.units
+            .iter()
+            .filter(|u| u.action != UnitAction::Unchanged)
+            .count()
+    }
+
+    /// Units that need an erase
+    pub fn erase_units(&self) -> usize {
+        self.units
+            .iter()
+            .filter(|u| u.action == UnitAction::Rewrite)
+            .count()
+    }
+
+    /// Estimated time for a conventional erase-everything write
+    pub fn estimated_full_us(&self, timings: &FlashTimings) -> u64 {
+        self.units.len() as u64 * timings.erase_unit_us as u64
+            + self.full_program_pages as u64 * timings.program_page_us as u64
+    }
+
+    /// Estimated time for this plan, including the reads made to build it
+    pub fn estimated_us(&self, timings: &FlashTimings) -> u64 {
+        self.pages_read as u64 * timings.read_page_us as u64
+            + self.erase_units() as u64 * timings.erase_unit_us as u64
+            + self.program_pages as u64 * timings.program_page_us as u64
+    }
+}
+
+/// Outcome of a differential write
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffWriteReport {
+    /// Units covered by the image
+    pub units_total: u32,
+    /// Units left untouched
+    pub units_unchanged: u32,
+    /// Units erased and rewritten
+    pub units_rewritten: u32,
+    /// Units programmed without an erase
+    pub units_programmed_in_place: u32,
+    /// Pages read (planning and verify)
+    pub pages_read: u32,
+    /// Pages programmed
+    pub pages_programmed: u32,
+    /// Units retried after a failed erase, program or verify
+    pub retries: u32,
+    /// Wall-clock time of the write phase
+    pub elapsed_ms: u64,
+    /// Estimated time of a full erase-and-program write
+    pub estimated_full_ms: u64,
+    /// Estimated time of the differential write
+    pub estimated_diff_ms: u64,
+}
+
+impl DiffWriteReport {
+    /// Estimated time saved compared with a full write
+    pub fn time_saved_ms(&self) -> u64 {
+        self.estimated_full_ms
+            .saturating_sub(self.estimated_diff_ms)
+    }
+}
+
+// ============================================================================
+// Differential Writer
+// ============================================================================
+
+/// Differential programmer
+#[derive(Debug, Clone)]
+pub struct DiffWriter {
+    geometry: DiffGeometry,
+    verify: bool,
+    retry_count: u8,
+    ecc: EccAlgorithm,
+    ecc_offset: u32,
+}
+
+/// Outcome of one attempt at writing a unit
+enum UnitAttempt {
+    Written,
+    Failed(WriteError),
+}
+
+impl DiffWriter {
+    /// Create a differential writer (verify on, 3 retries, no ECC)
+    pub fn new(geometry: DiffGeometry) -> Self {
+        Self {
+            geometry,
+            verify: true,
+            retry_count: 3,
+            ecc: EccAlgorithm::None,
+            ecc_offset: 0,
+        }
+    }
+
+    /// Read back each written page
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Retries per unit before giving up
+    pub fn with_retry_count(mut self, retry_count: u8) -> Self {
+        self.retry_count = retry_count;
+        self
+    }
+
+    /// Generate ECC into the OOB area at `offset` (NAND)
+    pub fn with_ecc(mut self, ecc: EccAlgorithm, offset: u32) -> Self {
+        self.ecc = ecc;
+        self.ecc_offset = offset;
+        self
+    }
+
+    /// Chip layout
+    pub fn geometry(&self) -> &DiffGeometry {
+        &self.geometry
+    }
+
+    /// Image bytes of one page (0xFF beyond the image end)
+    fn image_page(&self, image: &[u8], image_unit: usize, page: usize) -> Vec<u8> {
+        let page_size = self.geometry.page_size as usize;
+        let start = image_unit * self.geometry.unit_size() + page * page_size;
+        let mut data = vec![0xFF; page_size];
+        if start < image.len() {
+            let end = (start + page_size).min(image.len());
+            data[..end - start].copy_from_slice(&image[start..end]);
+        }
+        data
+    }
+
+    /// Image bytes of one unit (0xFF beyond the image end)
+    fn image_unit(&self, image: &[u8], image_unit: usize) -> Vec<u8> {
+        (0..self.geometry.pages_per_unit as usize)
+            .flat_map(|page| self.image_page(image, image_unit, page))
+            .collect()
+    }
+
+    /// OOB for a page, with generated ECC when configured
+    fn page_oob(&self, data: &[u8]) -> Vec<u8> {
+        let oob_size = self.geometry.oob_size as usize;
+        let mut oob = vec![0xFF; oob_size];
+        // Blank pages stay erased so they read back as such
+        if data.iter().all(|&b| b == 0xFF) {
+            return oob;
+        }
+        let (_, ecc) = encode_with_ecc(data, &self.ecc);
+        let offset = self.ecc_offset as usize;
+        if !ecc.is_empty() && offset + ecc.len() <= oob_size {
+            oob[offset..offset + ecc.len()].copy_from_slice(&ecc);
+        }
+        oob
+    }
+
+    /// Compare `image` with the chip, starting at `start_unit`
+    ///
+    /// Units whose checksum in `tracker` matches the image are taken as
+    /// unchanged without reading them, so the tracker must describe what
+    /// is on the chip now. Without a tracker every unit is read back.
+    pub fn plan<T: ProgramTarget>(
+        &self,
+        target: &mut T,
+        image: &[u8],
+        start_unit: u32,
+        tracker: Option<&ChangeTracker>,
+    ) -> WriteResult<DiffPlan> {
+        let unit_size = self.geometry.unit_size();
+        if unit_size == 0 {
+            return Err(WriteError::DataSizeMismatch {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        let image_units = (image.len() + unit_size - 1) / unit_size;
+        if start_unit as u64 + image_units as u64 > self.geometry.total_units as u64 {
+            return Err(WriteError::DataSizeMismatch {
+                expected: (self.geometry.total_units.saturating_sub(start_unit)) as usize
+                    * unit_size,
+                actual: image.len(),
+            });
+        }
+
+        let page_size = self.geometry.page_size as usize;
+        let mut plan = DiffPlan {
+            start_unit,
+            image_len: image.len() as u64,
+            image_checksum: ChangeTracker::calculate_checksum(image),
+            units: Vec::with_capacity(image_units),
+            pages_read: 0,
+            full_program_pages: 0,
+            program_pages: 0,
+        };
+        let mut old = vec![0u8; page_size];
+        let mut oob = vec![0u8; self.geometry.oob_size as usize];
+
+        for image_unit in 0..image_units {
+            let unit = start_unit + image_unit as u32;
+            let new = self.image_unit(image, image_unit);
+            let blank_pages: Vec<bool> = new
+                .chunks(page_size)
+                .map(|p| p.iter().all(|&b| b == 0xFF))
+                .collect();
+            let non_blank = blank_pages.iter().filter(|&&b| !b).count() as u32;
+            plan.full_program_pages += non_blank;
+
+            let cached = tracker.and_then(|t| t.get_checksum(unit));
+            let checksum = ChangeTracker::calculate_checksum(&new);
+            let action = if cached == Some(checksum) {
+                UnitAction::Unchanged
+            } else if cached.is_some() && !self.geometry.program_without_erase {
+                UnitAction::Rewrite
+            } else {
+                let mut differing = Vec::new();
+                let mut needs_erase = false;
+                for (page, expected) in new.chunks(page_size).enumerate() {
+                    target.read_page(unit, page as u32, &mut old, &mut oob)?;
+                    plan.pages_read += 1;
+                    if old[..] == expected[..] {
+                        continue;
+                    }
+                    differing.push(page as u32);
+                    if !self.geometry.program_without_erase
+                        || old.iter().zip(expected).any(|(&o, &n)| o & n != n)
+                    {
+                        needs_erase = true;
+                        break;
+                    }
+                }
+                if needs_erase {
+                    UnitAction::Rewrite
+                } else if differing.is_empty() {
+                    UnitAction::Unchanged
+                } else {
+                    UnitAction::ProgramInPlace(differing)
+                }
+            };
+
+            plan.program_pages += match &action {
+                UnitAction::Unchanged => 0,
+                UnitAction::ProgramInPlace(pages) => pages.len() as u32,
+                UnitAction::Rewrite => non_blank,
+            };
+            plan.units.push(DiffUnit { unit, action });
+        }
+        Ok(plan)
+    }
+
+    /// Program `(page, data)` pairs of a unit and verify them
+    fn program_pages<T: ProgramTarget>(
+        &self,
+        target: &mut T,
+        unit: u32,
+        pages: Vec<(u32, Vec<u8>)>,
+        report: &mut DiffWriteReport,
+        status: &mut ProgramProgress,
+    ) -> WriteResult<UnitAttempt> {
+        let mut read_data = vec![0u8; self.geometry.page_size as usize];
+        let mut read_oob = vec![0u8; self.geometry.oob_size as usize];
+        for (page, data) in pages {
+            let oob = self.page_oob(&data);
+            status.current_page = page;
+            status.operation = ProgramOperation::Programming;
+            match target.program_page(unit, page, &data, &oob) {
+                Ok(()) => report.pages_programmed += 1,
+                Err(e @ WriteError::ProgramFailed { .. }) => return Ok(UnitAttempt::Failed(e)),
+                Err(e) => return Err(e),
+            }
+
+            if self.verify {
+                status.operation = ProgramOperation::Verifying;
+                target.read_page(unit, page, &mut read_data, &mut read_oob)?;
+                report.pages_read += 1;
+                let mismatch = read_data
+                    .iter()
+                    .zip(&data)
+                    .chain(read_oob.iter().zip(&oob))
+                    .position(|(a, b)| a != b);
+                if let Some(offset) = mismatch {
+                    return Ok(UnitAttempt::Failed(WriteError::VerifyFailed {
+                        block: unit,
+                        page,
+                        offset: offset as u32,
+                    }));
+                }
+            }
+        }
+        Ok(UnitAttempt::Written)
+    }
+
+    /// Erase a unit and program its non-blank pages
+    fn rewrite_unit<T: ProgramTarget>(
+        &self,
+        target: &mut T,
+        image: &[u8],
+        image_unit: usize,
+        unit: u32,
+        report: &mut DiffWriteReport,
+        status: &mut ProgramProgress,
+    ) -> WriteResult<UnitAttempt> {
+        status.operation = ProgramOperation::Erasing;
+        match target.erase_block(unit) {
+            Ok(()) => {}
+            Err(e @ WriteError::EraseFailed(_)) => return Ok(UnitAttempt::Failed(e)),
+            Err(e) => return Err(e),
+        }
+        let pages = (0..self.geometry.pages_per_unit)
+            .map(|page| (page, self.image_page(image, image_unit, page as usize)))
+            .filter(|(_, data)| data.iter().any(|&b| b != 0xFF))
+            .collect();
+        self.program_pages(target, unit, pages, report, status)
+    }
+
+    /// Carry out `plan`
+    ///
+    /// A unit that fails to erase, program or verify is retried (in-place
+    /// programs fall back to a full rewrite); after `retry_count` retries the
+    /// last error is returned. Differential writes never remap, so the
+    /// chip layout stays what the image expects. `tracker`, if given, gets
+    /// the new checksums and has the written units marked modified.
+    /// `progress` is called after each written unit; returning `false`
+    /// cancels.
+    pub fn apply<T, F>(
+        &self,
+        target: &mut T,
+        image: &[u8],
+        plan: &DiffPlan,
+        mut tracker: Option<&mut ChangeTracker>,
+        mut progress: F,
+    ) -> WriteResult<DiffWriteReport>
+    where
+        T: ProgramTarget,
+        F: FnMut(&ProgramProgress) -> bool,
+    {
+        if plan.image_len != image.len() as u64
+            || plan.image_checksum != ChangeTracker::calculate_checksum(image)
+        {
+            return Err(WriteError::ResumeMismatch);
+        }
+
+        let timings = &self.geometry.timings;
+        let unit_bytes = self.geometry.unit_size() as u64;
+        let changed = plan.changed_units() as u32;
+        let mut report = DiffWriteReport {
+            units_total: plan.units.len() as u32,
+            pages_read: plan.pages_read,
+            estimated_full_ms: plan.estimated_full_us(timings) / 1000,
+            estimated_diff_ms: plan.estimated_us(timings) / 1000,
+            ..Default::default()
+        };
+        let mut status = ProgramProgress {
+            current_block: plan.start_unit,
+            current_page: 0,
+            total_blocks: changed,
+            bytes_written: 0,
+            total_bytes: changed as u64 * unit_bytes,
+            bad_blocks_skipped: 0,
+            verify_retries: 0,
+            eta_seconds: None,
+            operation: ProgramOperation::Idle,
+        };
+        let started = std::time::Instant::now();
+
+        for (image_unit, entry) in plan.units.iter().enumerate() {
+            let unit = entry.unit;
+            let checksum = ChangeTracker::calculate_checksum(&self.image_unit(image, image_unit));
+            if entry.action == UnitAction::Unchanged {
+                report.units_unchanged += 1;
+                if let Some(t) = tracker.as_deref_mut() {
+                    t.update_checksum(unit, checksum);
+                }
+                continue;
+            }
+
+            status.current_block = unit;
+            let mut in_place = match &entry.action {
+                UnitAction::ProgramInPlace(pages) => Some(pages),
+                _ => None,
+            };
+            let mut attempts = 0u8;
+            loop {
+                let attempt = match in_place {
+                    Some(pages) => {
+                        let pages = pages
+                            .iter()
+                            .map(|&page| (page, self.image_page(image, image_unit, page as usize)))
+                            .collect();
+                        self.program_pages(target, unit, pages, &mut report, &mut status)?
+                    }
+                    None => self.rewrite_unit(
+                        target,
+                        image,
+                        image_unit,
+                        unit,
+                        &mut report,
+                        &mut status,
+                    )?,
+                };
+                match attempt {
+                    UnitAttempt::Written => break,
+                    UnitAttempt::Failed(e) if attempts >= self.retry_count => return Err(e),
+                    UnitAttempt::Failed(_) => {
+                        attempts += 1;
+                        report.retries += 1;
+                        status.verify_retries += 1;
+                        in_place = None;
+                    }
+                }
+            }
+
+            if in_place.is_some() {
+                report.units_programmed_in_place += 1;
+            } else {
+                report.units_rewritten += 1;
+            }
+            if let Some(t) = tracker.as_deref_mut() {
+                t.update_checksum(unit, checksum);
+                t.mark_modified(unit);
+            }
+
+            status.bytes_written += unit_bytes;
+            let elapsed = started.elapsed().as_secs_f64();
+            if elapsed > 0.0 {
+                let remaining = (status.total_bytes - status.bytes_written) as f64;
+                status.eta_seconds =
+                    Some((remaining * elapsed / status.bytes_written as f64).ceil() as u32);
+            }
+            if !progress(&status) {
+                return Err(WriteError::Cancelled);
+            }
+        }
+
+        report.elapsed_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Plan and apply in one go
+    pub fn write<T, F>(
+        &self,
+        target: &mut T,
+        image: &[u8],
+        start_unit: u32,
+        tracker: Option<&mut ChangeTracker>,
+        progress: F,
+    ) -> WriteResult<DiffWriteReport>
+    where
+        T: ProgramTarget,
+        F: FnMut(&ProgramProgress) -> bool,
+    {
+        let plan = self.plan(target, image, start_unit, tracker.as_deref())?;
+        self.apply(target, image, &plan, tracker, progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Flash model that only clears bits on program, like real NOR/NAND
+    struct MockFlash {
+        page_size: usize,
+        oob_size: usize,
+        pages: HashMap<(u32, u32), (Vec<u8>, Vec<u8>)>,
+        erases: u32,
+        programs: u32,
+        reads: u32,
+        /// Remaining erase failures per unit
+        erase_fail: HashMap<u32, u32>,
+    }
+
+    impl MockFlash {
+        fn new(page_size: usize, oob_size: usize) -> Self {
+            Self {
+                page_size,
+                oob_size,
+                pages: HashMap::new(),
+                erases: 0,
+                programs: 0,
+                reads: 0,
+                erase_fail: HashMap::new(),
+            }
+        }
+
+        fn reset_counters(&mut self) {
+            self.erases = 0;
+            self.programs = 0;
+            self.reads = 0;
+        }
+    }
+
+    impl ProgramTarget for MockFlash {
+        fn erase_block(&mut self, block: u32) -> WriteResult<()> {
+            if let Some(n) = self.erase_fail.get_mut(&block) {
+                if *n > 0 {
+                    *n -= 1;
+                    return Err(WriteError::EraseFailed(block));
+                }
+            }
+            self.erases += 1;
+            self.pages.retain(|&(b, _), _| b != block);
+            Ok(())
+        }
+
+        fn program_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &[u8],
+            oob: &[u8],
+        ) -> WriteResult<()> {
+            self.programs += 1;
+            let erased = (vec![0xFF; self.page_size], vec![0xFF; self.oob_size]);
+            let (d, o) = self.pages.entry((block, page)).or_insert(erased);
+            d.iter_mut().zip(data).for_each(|(c, &n)| *c &= n);
+            o.iter_mut().zip(oob).for_each(|(c, &n)| *c &= n);
+            Ok(())
+        }
+
+        fn read_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &mut [u8],
+            oob: &mut [u8],
+        ) -> WriteResult<()> {
+            self.reads += 1;
+            match self.pages.get(&(block, page)) {
+                Some((d, o)) => {
+                    data.copy_from_slice(d);
+                    oob.copy_from_slice(o);
+                }
+                None => {
+                    data.fill(0xFF);
+                    oob.fill(0xFF);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn read_back(flash: &mut MockFlash, units: u32, pages: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut data = vec![0u8; flash.page_size];
+        let mut oob = vec![0u8; flash.oob_size];
+        for unit in 0..units {
+            for page in 0..pages {
+                flash.read_page(unit, page, &mut data, &mut oob).unwrap();
+                out.extend_from_slice(&data);
+            }
+        }
+        out
+    }
+
+    fn nor_geometry() -> DiffGeometry {
+        DiffGeometry::spi_nor(&SpiNorChipInfo {
+            manufacturer: "Test".into(),
+            model: "NOR".into(),
+            jedec_id: [0xEF, 0x40, 0x14],
+            size_bytes: 8 * 4096,
+            page_size: 256,
+            sector_size: 4096,
+            block_size: 65536,
+            voltage: "3.3V".into(),
+            max_clock_mhz: 50,
+            has_qspi: false,
+            has_dual: false,
+            address_bytes: 3,
+        })
+    }
+
+    #[test]
+    fn test_nor_diff_write_sub_sectors() {
+        let geometry = nor_geometry();
+        assert_eq!(geometry.pages_per_unit, 16);
+        assert_eq!(geometry.total_units, 8);
+
+        let writer = DiffWriter::new(geometry);
+        let mut flash = MockFlash::new(256, 0);
+        let v1: Vec<u8> = (0..4 * 4096).map(|i| (i % 253) as u8).collect();
+        // An erased chip only needs programming
+        let first = writer.write(&mut flash, &v1, 0, None, |_| true).unwrap();
+        assert_eq!(first.units_programmed_in_place, 4);
+        assert_eq!(flash.erases, 0);
+
+        // Unit 1: clear bits only; unit 3: set a bit, needs an erase
+        let mut v2 = v1.clone();
+        v2[4096 + 10] &= 0x0F;
+        v2[3 * 4096 + 5] = 0xFF;
+        flash.reset_counters();
+        let plan = writer.plan(&mut flash, &v2, 0, None).unwrap();
+        assert_eq!(plan.units[0].action, UnitAction::Unchanged);
+        assert_eq!(plan.units[1].action, UnitAction::ProgramInPlace(vec![0]));
+        assert_eq!(plan.units[3].action, UnitAction::Rewrite);
+
+        let report = writer
+            .apply(&mut flash, &v2, &plan, None, |_| true)
+            .unwrap();
+        assert_eq!(report.units_unchanged, 2);
+        assert_eq!(report.units_programmed_in_place, 1);
+        assert_eq!(report.units_rewritten, 1);
+        assert_eq!(flash.erases, 1);
+        assert_eq!(report.pages_programmed, 17);
+        assert!(report.time_saved_ms() > 0);
+        assert_eq!(read_back(&mut flash, 4, 16), v2);
+
+        // Mismatched plan is rejected
+        assert_eq!(
+            writer
+                .apply(&mut flash, &v1, &plan, None, |_| true)
+                .unwrap_err(),
+            WriteError::ResumeMismatch
+        );
+    }
+
+    #[test]
+    fn test_nand_diff_write_with_tracker() {
+        let geometry = DiffGeometry::nand(&NandGeometry::new(512, 16, 4, 16));
+        let writer = DiffWriter::new(geometry).with_ecc(EccAlgorithm::Hamming, 0);
+        let mut flash = MockFlash::new(512, 16);
+        let mut tracker = ChangeTracker::new(16);
+
+        let v1: Vec<u8> = (0..6 * 2048 - 100).map(|i| (i % 241) as u8).collect();
+        writer
+            .write(&mut flash, &v1, 2, Some(&mut tracker), |_| true)
+            .unwrap();
+        assert_eq!(tracker.get_modified_blocks(), vec![2, 3, 4, 5, 6, 7]);
+        tracker.clear_modified();
+
+        let mut v2 = v1.clone();
+        v2[2 * 2048 + 700] ^= 0x80;
+        flash.reset_counters();
+        flash.erase_fail.insert(4, 1);
+        let report = writer
+            .write(&mut flash, &v2, 2, Some(&mut tracker), |_| true)
+            .unwrap();
+
+        // The tracker answers for every unit, so nothing is read to plan
+        assert_eq!(report.units_rewritten, 1);
+        assert_eq!(report.units_unchanged, 5);
+        assert_eq!(report.retries, 1);
+        assert_eq!(flash.erases, 1);
+        assert_eq!(flash.reads, 4);
+        assert_eq!(tracker.get_modified_blocks(), vec![4]);
+        assert!(report.estimated_full_ms > report.estimated_diff_ms);
+
+        let image = read_back(&mut flash, 8, 4);
+        assert_eq!(&image[2 * 2048..2 * 2048 + v2.len()], &v2[..]);
+        let mut data = vec![0u8; 512];
+        let mut oob = vec![0u8; 16];
+        flash.read_page(4, 1, &mut data, &mut oob).unwrap();
+        assert_eq!(oob, writer.page_oob(&data));
+
+        // Without the tracker the chip is read back and found identical
+        flash.reset_counters();
+        let plan = writer.plan(&mut flash, &v2, 2, None).unwrap();
+        assert_eq!(plan.changed_units(), 0);
+        assert_eq!(flash.reads, 24);
+    }
+}
@@ -0,0 +1,1317 @@
+//! Device auto-discovery and health monitoring for the OpenFlash server
+//!
+//! Besides manual registrations, [`DeviceMonitor`] keeps the pool in step
+//! with the programmers that are actually reachable:
+//! - locally attached USB programmers are found by their vendor/product ID
+//!   under `/sys/class/tty` ([`scan_usb`]) and use `serial:///dev/<tty>`
+//! - network programmers (Raspberry Pi/Orange Pi daemons) broadcast an
+//!   [`Announcement`] datagram on UDP port [`limits::ANNOUNCE_PORT`] and
+//!   use `tcp://<host>:<port>`
+//!
+//! Announcements register devices the scheduler then sends jobs to, so
+//! [`DeviceMonitor::listen_announcements`] listens on the REST API host
+//! (loopback by default) and refuses any other address unless
+//! announcements are signed with a shared secret
+//! ([`Announcement::to_signed_datagram`]) or limited to known senders.
+//! An announcement never changes a device registered by hand or found
+//! over USB: such an ID collision is ignored and reported as an error
+//! event.
+//!
+//! Every sweep opens newly found devices through the transport factory
+//! and registers them `Available` only if that works (`Offline`
+//! otherwise); a known device that reappears is revived the same way. USB
+//! programmers stay in service while listed in sysfs and network
+//! programmers while they announce: one that vanished from sysfs is lost
+//! at once, even mid-job, and one silent for `device_timeout` is lost
+//! whatever it still answers. Other devices not heard from for
+//! `health_check_interval` are pinged by reading the chip size, over the
+//! job executor's open transport when given one
+//! ([`DeviceMonitor::with_connections`]); a device that answers comes
+//! back from `Offline`/`Error`, one silent for `device_timeout` is marked
+//! lost, and one the factory has no transport for is marked lost at once.
+//! Lost devices go `Offline`: their running job fails (and is re-queued
+//! for any device while retries remain, unless it named this one) and
+//! queued jobs pinned to them wait until they come back.
+//!
+//! Independently of the monitor, the server quarantines a device whose
+//! jobs failed `quarantine_after_failures` times in a row: it is put into
+//! `Maintenance` until an operator makes it available again.
+
+use crate::job_executor::{Connections, ExecError, TransportFactory};
+use crate::server::{
+    lock_server, DevicePlatform, DeviceStatus, PoolDevice, SharedServer, WsMessage,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+pub mod limits {
+    /// USB vendor/product IDs of OpenFlash programmer firmware
+    /// (RP2040/STM32, RP2350)
+    pub const USB_IDS: &[(u16, u16)] = &[(0xC0DE, 0xCAFE), (0x1209, 0x0F1A)];
+    /// Where serial ports are listed
+    pub const SYSFS_TTY: &str = "/sys/class/tty";
+    /// UDP port network programmers announce themselves on
+    pub const ANNOUNCE_PORT: u16 = 47474;
+    /// `service` field of a valid announcement
+    pub const ANNOUNCE_SERVICE: &str = "openflash";
+    /// Largest announcement datagram read
+    pub const MAX_DATAGRAM: usize = 2048;
+    /// Largest difference between a signed announcement's `sent_at` and
+    /// the local clock (s)
+    pub const ANNOUNCE_MAX_AGE_SECS: u64 = 30;
+    /// Device metadata key naming how the device was found (`usb`, `announce`)
+    pub const SOURCE_METADATA: &str = "discovered_by";
+    /// Device metadata key holding the reason a device is quarantined
+    pub const QUARANTINE_METADATA: &str = "quarantined";
+    /// How often a sleeping monitor checks for shutdown (ms)
+    pub const STOP_POLL_MS: u64 = 100;
+}
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Discovery and health monitoring settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// Run the [`DeviceMonitor`]
+    pub enabled: bool,
+    /// Look for USB programmers
+    pub scan_usb: bool,
+    /// Listen for network programmer announcements (None = don't)
+    pub announce_port: Option<u16>,
+    /// Address announcements are received on (None = the REST API host)
+    pub announce_host: Option<String>,
+    /// Shared secret announcements must be signed with (HMAC-SHA256)
+    pub announce_secret: Option<String>,
+    /// Sender IP addresses announcements are accepted from (empty = any)
+    pub announce_allow: Vec<String>,
+    /// Seconds between sweeps, and of silence before a device is pinged
+    pub interval_secs: u64,
+    /// Seconds without an answer before a device is marked lost
+    pub offline_after_secs: u64,
+    /// Failed jobs in a row that quarantine a device (0 = never)
+    pub quarantine_after_failures: u32,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scan_usb: true,
+            announce_port: Some(limits::ANNOUNCE_PORT),
+            announce_host: None,
+            announce_secret: None,
+            announce_allow: Vec::new(),
+            interval_secs: 30,
+            offline_after_secs: 60,
+            quarantine_after_failures: 3,
+        }
+    }
+}
+
+// ============================================================================
+// USB Discovery
+// ============================================================================
+
+/// OpenFlash programmers attached over USB
+///
+/// `sysfs_tty` is normally [`limits::SYSFS_TTY`]. Devices are identified
+/// by USB serial number; programmers sharing one (or without one) are
+/// told apart by their tty name.
+pub fn scan_usb(sysfs_tty: &Path) -> Vec<PoolDevice> {
+    let Ok(entries) = std::fs::read_dir(sysfs_tty) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let tty = entry.file_name().to_string_lossy().into_owned();
+        // `device` links to the USB interface; its parent is the device
+        let Ok(interface) = std::fs::canonicalize(entry.path().join("device")) else {
+            continue;
+        };
+        let Some(usb) = interface
+            .ancestors()
+            .find(|dir| dir.join("idVendor").is_file())
+        else {
+            continue;
+        };
+        let ids = (read_hex(usb, "idVendor"), read_hex(usb, "idProduct"));
+        let (Some(vid), Some(pid)) = ids else {
+            continue;
+        };
+        if !limits::USB_IDS.contains(&(vid, pid)) {
+            continue;
+        }
+        let serial = read_attr(usb, "serial");
+        let name = read_attr(usb, "product").unwrap_or_else(|| "OpenFlash".to_string());
+        let platform = usb_platform(vid, pid, serial.as_deref());
+        let mut device = PoolDevice::new(&tty, &name, &format!("serial:///dev/{}", tty), platform);
+        if let Some(serial) = serial {
+            device.metadata.insert("serial".to_string(), serial);
+        }
+        found.push(device);
+    }
+
+    let mut serials: HashMap<String, usize> = HashMap::new();
+    for device in &found {
+        if let Some(serial) = device.metadata.get("serial") {
+            *serials.entry(serial.clone()).or_default() += 1;
+        }
+    }
+    for device in &mut found {
+        let unique = device.metadata.get("serial").filter(|s| serials[*s] == 1);
+        device.id = match unique {
+            Some(serial) => format!("usb-{}", serial),
+            None => format!("usb-{}", device.id),
+        };
+        mark_found(device, "usb");
+    }
+    found.sort_by(|a, b| a.id.cmp(&b.id));
+    found
+}
+
+fn read_attr(dir: &Path, name: &str) -> Option<String> {
+    let value = std::fs::read_to_string(dir.join(name)).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn read_hex(dir: &Path, name: &str) -> Option<u16> {
+    u16::from_str_radix(&read_attr(dir, name)?, 16).ok()
+}
+
+/// Platform of a USB programmer; RP2040 and STM32 firmware share an ID and
+/// differ in their serial number prefix
+fn usb_platform(vid: u16, pid: u16, serial: Option<&str>) -> DevicePlatform {
+    if (vid, pid) == (0x1209, 0x0F1A) {
+        return DevicePlatform::RP2350;
+    }
+    match serial.unwrap_or("") {
+        s if s.starts_with("OF-RP2040") => DevicePlatform::RP2040,
+        s if s.starts_with("OF-STM32F4") => DevicePlatform::STM32F4,
+        s if s.starts_with("OF-STM32") => DevicePlatform::STM32F1,
+        _ => DevicePlatform::Unknown,
+    }
+}
+
+fn mark_found(device: &mut PoolDevice, source: &str) {
+    device.status = DeviceStatus::Available;
+    device.touch();
+    device
+        .metadata
+        .insert(limits::SOURCE_METADATA.to_string(), source.to_string());
+}
+
+// ============================================================================
+// Network Announcements
+// ============================================================================
+
+/// Datagram a network programmer broadcasts to make itself known
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Announcement {
+    /// Always [`limits::ANNOUNCE_SERVICE`]
+    pub service: String,
+    /// Device ID, stable across restarts
+    pub id: String,
+    /// Display name (default: the ID)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Address to connect to (default: the sender's)
+    #[serde(default)]
+    pub host: Option<String>,
+    /// TCP port of the daemon
+    pub port: u16,
+    /// Platform name, e.g. `RaspberryPi`
+    #[serde(default)]
+    pub platform: Option<String>,
+    /// Firmware version
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Supported flash interfaces (default: the pool default)
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+    /// Tags for job routing
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When it was sent (Unix seconds); required when signed
+    #[serde(default)]
+    pub sent_at: Option<u64>,
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+impl Announcement {
+    /// Announcement of `id` listening on TCP `port`
+    pub fn new(id: &str, port: u16) -> Self {
+        Self {
+            service: limits::ANNOUNCE_SERVICE.to_string(),
+            id: id.to_string(),
+            name: None,
+            host: None,
+            port,
+            platform: None,
+            version: None,
+            interfaces: Vec::new(),
+            tags: Vec::new(),
+            sent_at: None,
+        }
+    }
+
+    /// JSON datagram to broadcast
+    pub fn to_datagram(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Datagram for a listener that requires `secret`: the JSON, stamped
+    /// with the current time, a newline and the hex HMAC-SHA256 of the JSON
+    pub fn to_signed_datagram(&self, secret: &str) -> Vec<u8> {
+        let stamped = Self {
+            sent_at: Some(now_ms() / 1000),
+            ..self.clone()
+        };
+        let mut datagram = stamped.to_datagram();
+        let mac = announce_mac(secret, &datagram).finalize().into_bytes();
+        datagram.push(b'\n');
+        datagram.extend(mac.iter().flat_map(|b| format!("{:02x}", b).into_bytes()));
+        datagram
+    }
+
+    /// Pool device announced by `datagram`, received from `sender`
+    pub fn parse(datagram: &[u8], sender: SocketAddr) -> Option<PoolDevice> {
+        Self::decode(datagram).map(|announcement| announcement.into_device(sender))
+    }
+
+    fn decode(datagram: &[u8]) -> Option<Self> {
+        let announcement: Self = serde_json::from_slice(datagram).ok()?;
+        (announcement.service == limits::ANNOUNCE_SERVICE && !announcement.id.is_empty())
+            .then_some(announcement)
+    }
+
+    fn into_device(self, sender: SocketAddr) -> PoolDevice {
+        let host = self.host.unwrap_or_else(|| sender.ip().to_string());
+        let platform = self
+            .platform
+            .as_deref()
+            .map_or(DevicePlatform::Unknown, DevicePlatform::from_str);
+        let mut device = PoolDevice::new(
+            &self.id,
+            self.name.as_deref().unwrap_or(&self.id),
+            &format!("tcp://{}:{}", host, self.port),
+            platform,
+        );
+        if let Some(version) = self.version {
+            device.firmware_version = version;
+        }
+        if !self.interfaces.is_empty() {
+            device.capabilities.interfaces = self.interfaces;
+        }
+        device.capabilities.has_wifi = true;
+        device.tags = self.tags;
+        mark_found(&mut device, "announce");
+        device
+    }
+}
+
+fn announce_mac(secret: &str, json: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(json);
+    mac
+}
+
+/// JSON of a datagram signed with `secret`, if the signature holds
+fn verify_signed<'a>(datagram: &'a [u8], secret: &str) -> Option<&'a [u8]> {
+    let split = datagram.iter().rposition(|&b| b == b'\n')?;
+    let (json, hex) = (&datagram[..split], &datagram[split + 1..]);
+    let hex = std::str::from_utf8(hex).ok()?.trim();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let signature = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    announce_mac(secret, json)
+        .verify_slice(&signature)
+        .ok()
+        .map(|_| json)
+}
+
+/// UDP socket collecting [`Announcement`]s
+#[derive(Debug)]
+pub struct AnnouncementListener {
+    socket: UdpSocket,
+    /// Secret announcements must be signed with
+    secret: Option<String>,
+    /// Senders announcements are accepted from (empty = any)
+    allow: Vec<IpAddr>,
+}
+
+impl AnnouncementListener {
+    /// Listen on `addr`, e.g. `127.0.0.1:47474`, accepting any announcement
+    pub fn bind(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            secret: None,
+            allow: Vec::new(),
+        })
+    }
+
+    /// Accept only announcements signed with `secret` and sent within
+    /// [`limits::ANNOUNCE_MAX_AGE_SECS`]
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = Some(secret.to_string());
+        self
+    }
+
+    /// Accept only announcements sent from `senders`
+    pub fn with_allowlist(mut self, senders: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.allow = senders.into_iter().collect();
+        self
+    }
+
+    /// Bound address
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Devices announced since the last call (latest announcement per ID);
+    /// malformed, unsigned, stale and unlisted datagrams are ignored
+    pub fn drain(&self) -> Vec<PoolDevice> {
+        let mut buf = [0u8; limits::MAX_DATAGRAM];
+        let mut found: Vec<PoolDevice> = Vec::new();
+        while let Ok((len, sender)) = self.socket.recv_from(&mut buf) {
+            if let Some(device) = self.accept(&buf[..len], sender) {
+                found.retain(|d| d.id != device.id);
+                found.push(device);
+            }
+        }
+        found
+    }
+
+    fn accept(&self, datagram: &[u8], sender: SocketAddr) -> Option<PoolDevice> {
+        if !self.allow.is_empty() && !self.allow.contains(&sender.ip()) {
+            return None;
+        }
+        let Some(secret) = &self.secret else {
+            return Announcement::parse(datagram, sender);
+        };
+        let announcement = Announcement::decode(verify_signed(datagram, secret)?)?;
+        let age = (now_ms() / 1000).abs_diff(announcement.sent_at?);
+        (age <= limits::ANNOUNCE_MAX_AGE_SECS).then(|| announcement.into_device(sender))
+    }
+}
+
+// ============================================================================
+// Health Monitor
+// ============================================================================
+
+/// What one [`DeviceMonitor::sweep`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    /// Devices added to the pool
+    pub discovered: Vec<String>,
+    /// Devices that became available again
+    pub revived: Vec<String>,
+    /// Devices marked lost
+    pub lost: Vec<String>,
+    /// Queued jobs waiting for the lost devices they are pinned to
+    pub held: Vec<u64>,
+    /// Found IDs that belong to a device registered or found another way
+    pub ignored: Vec<String>,
+}
+
+/// Result of pinging a device
+enum Ping {
+    Alive,
+    Silent,
+    /// The transport factory cannot open this device
+    Unsupported,
+}
+
+/// Discovers programmers and checks the health of the device pool
+pub struct DeviceMonitor {
+    server: SharedServer,
+    factory: TransportFactory,
+    sysfs_tty: Option<PathBuf>,
+    listener: Option<AnnouncementListener>,
+    /// Transports of the job executor, shared by pings
+    connections: Option<Connections>,
+    /// When each network programmer last announced itself (ms)
+    announced: HashMap<String, u64>,
+}
+
+impl DeviceMonitor {
+    /// Monitor the pool of `server`, pinging devices through `factory`
+    ///
+    /// USB scanning follows `ServerConfig.discovery.scan_usb`;
+    /// announcements need [`Self::listen`].
+    pub fn new(server: SharedServer, factory: TransportFactory) -> Self {
+        let scan_usb = lock_server(&server).config.discovery.scan_usb;
+        Self {
+            server,
+            factory,
+            sysfs_tty: scan_usb.then(|| PathBuf::from(limits::SYSFS_TTY)),
+            listener: None,
+            connections: None,
+            announced: HashMap::new(),
+        }
+    }
+
+    /// Scan `sysfs_tty` instead of [`limits::SYSFS_TTY`] (None = no USB
+    /// scanning)
+    pub fn with_sysfs_root(mut self, sysfs_tty: Option<PathBuf>) -> Self {
+        self.sysfs_tty = sysfs_tty;
+        self
+    }
+
+    /// Ping devices over the transports the job executor keeps open
+    /// ([`JobExecutor::connections`](crate::job_executor::JobExecutor::connections))
+    /// instead of opening a second one next to them
+    pub fn with_connections(mut self, connections: Connections) -> Self {
+        self.connections = Some(connections);
+        self
+    }
+
+    /// Collect announcements arriving on `addr`, from anyone
+    pub fn listen(mut self, addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        self.listener = Some(AnnouncementListener::bind(addr)?);
+        Ok(self)
+    }
+
+    /// Collect announcements as `config` sets up: on `announce_port` of
+    /// `announce_host` (default `rest_host`), signed with `announce_secret`
+    /// and from `announce_allow` when set
+    ///
+    /// A non-loopback address needs a secret or an allowlist; otherwise
+    /// anyone on the network could register devices and receive jobs.
+    pub fn listen_announcements(
+        mut self,
+        config: &DiscoveryConfig,
+        rest_host: &str,
+    ) -> std::io::Result<Self> {
+        let Some(port) = config.announce_port else {
+            return Ok(self);
+        };
+        let invalid = |message: String| std::io::Error::new(ErrorKind::InvalidInput, message);
+        let host = config.announce_host.as_deref().unwrap_or(rest_host);
+        let allow = config
+            .announce_allow
+            .iter()
+            .map(|ip| {
+                ip.parse::<IpAddr>()
+                    .map_err(|_| invalid(format!("announce_allow: '{}' is not an IP address", ip)))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let addrs: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+        let loopback = !addrs.is_empty() && addrs.iter().all(|a| a.ip().is_loopback());
+        if !loopback && config.announce_secret.is_none() && allow.is_empty() {
+            return Err(invalid(format!(
+                "announcements on {} need announce_secret or announce_allow",
+                host
+            )));
+        }
+
+        let mut listener = AnnouncementListener::bind(&addrs[..])?.with_allowlist(allow);
+        if let Some(secret) = &config.announce_secret {
+            listener = listener.with_secret(secret);
+        }
+        self.listener = Some(listener);
+        Ok(self)
+    }
+
+    /// Discover devices and check the health of the pool once
+    pub fn sweep(&mut self) -> SweepReport {
+        let mut report = SweepReport::default();
+        let now = now_ms();
+        let usb = self.sysfs_tty.as_deref().map(scan_usb);
+        let mut found: Vec<PoolDevice> = usb.iter().flatten().cloned().collect();
+        if let Some(listener) = &self.listener {
+            for device in listener.drain() {
+                self.announced.insert(device.id.clone(), now);
+                found.push(device);
+            }
+        }
+
+        // New devices, and known ones that could come back, are opened
+        // before they are made available
+        let mut server = lock_server(&self.server);
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for device in found {
+            // An ID found one way never takes over a device known another
+            // way, e.g. a spoofed announcement for a USB or manual device
+            let source = device.metadata.get(limits::SOURCE_METADATA);
+            let collides = server
+                .device_pool
+                .get_device(&device.id)
+                .is_some_and(|known| known.metadata.get(limits::SOURCE_METADATA) != source);
+            if collides {
+                server.events.emit(WsMessage::Error {
+                    code: "discovery".to_string(),
+                    message: format!(
+                        "ignored {} found by {}: the ID belongs to another device",
+                        device.id,
+                        source.map_or("-", String::as_str)
+                    ),
+                });
+                report.ignored.push(device.id);
+                continue;
+            }
+            seen.insert(device.id.clone());
+            match server.device_pool.get_device_mut(&device.id) {
+                Some(known) => {
+                    // Programmers may come back on another address or tty
+                    if known.uri != device.uri {
+                        known.uri = device.uri;
+                    }
+                    known.touch();
+                    let down = matches!(known.status, DeviceStatus::Offline | DeviceStatus::Error);
+                    if down && !known.metadata.contains_key(limits::QUARANTINE_METADATA) {
+                        candidates.push(known.clone());
+                    }
+                }
+                None => candidates.push(device),
+            }
+        }
+        drop(server);
+
+        for mut device in candidates {
+            // Transports may be slow to open; ping without holding the lock
+            let alive = matches!(self.ping(&device), Ping::Alive);
+            let mut server = lock_server(&self.server);
+            if server.device_pool.get_device(&device.id).is_some() {
+                if alive {
+                    if let Ok(true) = server.device_heartbeat(&device.id) {
+                        report.revived.push(device.id);
+                    }
+                }
+            } else {
+                if !alive {
+                    device.status = DeviceStatus::Offline;
+                }
+                let id = device.id.clone();
+                if server.register_device(device).is_ok() {
+                    report.discovered.push(id);
+                }
+            }
+        }
+
+        let mut server = lock_server(&self.server);
+        let timeout = server.device_pool.device_timeout.saturating_mul(1000);
+        let mut vanished = Vec::new();
+        for device in server.device_pool.devices.values() {
+            if device.status == DeviceStatus::Offline || seen.contains(&device.id) {
+                continue;
+            }
+            match self.source(device) {
+                Some("usb") => vanished.push((device.id.clone(), "device disconnected")),
+                Some("announce") => {
+                    // Announcements heard before this monitor started are unknown
+                    let heard = *self.announced.entry(device.id.clone()).or_insert(now);
+                    if now.saturating_sub(heard) >= timeout {
+                        vanished.push((device.id.clone(), "device stopped announcing"));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (device_id, reason) in vanished {
+            if let Ok(held) = server.mark_device_lost(&device_id, reason) {
+                report.held.extend(held);
+                report.lost.push(device_id);
+            }
+        }
+
+        let interval = server
+            .device_pool
+            .health_check_interval
+            .saturating_mul(1000);
+        let due: Vec<PoolDevice> = server
+            .device_pool
+            .devices
+            .values()
+            .filter(|d| !seen.contains(&d.id) && self.source(d).is_none())
+            .filter(|d| {
+                matches!(
+                    d.status,
+                    DeviceStatus::Available | DeviceStatus::Offline | DeviceStatus::Error
+                )
+            })
+            .filter(|d| !d.metadata.contains_key(limits::QUARANTINE_METADATA))
+            .filter(|d| now.saturating_sub(d.last_seen) >= interval)
+            .cloned()
+            .collect();
+        drop(server);
+
+        for device in due {
+            let ping = self.ping(&device);
+            let mut server = lock_server(&self.server);
+            let current = server.device_pool.get_device(&device.id).map(|d| d.status);
+            let online = matches!(
+                current,
+                Some(DeviceStatus::Available) | Some(DeviceStatus::Error)
+            );
+            let lost = match ping {
+                Ping::Alive => {
+                    if let Ok(true) = server.device_heartbeat(&device.id) {
+                        report.revived.push(device.id);
+                    }
+                    continue;
+                }
+                Ping::Silent if now.saturating_sub(device.last_seen) >= timeout => {
+                    "device stopped responding".to_string()
+                }
+                Ping::Silent => continue,
+                Ping::Unsupported => format!("no transport for {}", device.uri),
+            };
+            if online {
+                if let Ok(held) = server.mark_device_lost(&device.id, &lost) {
+                    report.held.extend(held);
+                    report.lost.push(device.id);
+                }
+            }
+        }
+        report
+    }
+
+    /// Discovery source that decides whether `device` is present (`usb`
+    /// while scanning sysfs, `announce` while listening)
+    fn source<'a>(&self, device: &'a PoolDevice) -> Option<&'a str> {
+        match device
+            .metadata
+            .get(limits::SOURCE_METADATA)
+            .map(String::as_str)
+        {
+            Some("usb") if self.sysfs_tty.is_some() => Some("usb"),
+            Some("announce") if self.listener.is_some() => Some("announce"),
+            _ => None,
+        }
+    }
+
+    fn ping(&self, device: &PoolDevice) -> Ping {
+        let size = match &self.connections {
+            Some(connections) => match connections.ping(device, &self.factory) {
+                Some(size) => size,
+                // A job is using it
+                None => return Ping::Alive,
+            },
+            None => (self.factory)(device).and_then(|mut t| t.capacity(false)),
+        };
+        match size {
+            Ok(_) => Ping::Alive,
+            Err(ExecError::Unsupported(_)) => Ping::Unsupported,
+            Err(_) => Ping::Silent,
+        }
+    }
+
+    /// Sweep every `health_check_interval` on a background thread
+    pub fn start(mut self) -> MonitorHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("openflash-monitor".to_string())
+            .spawn(move || {
+                while !stopped.load(Ordering::SeqCst) {
+                    self.sweep();
+                    let interval = lock_server(&self.server).device_pool.health_check_interval;
+                    let next = Instant::now() + Duration::from_secs(interval.max(1));
+                    while Instant::now() < next && !stopped.load(Ordering::SeqCst) {
+                        std::thread::sleep(Duration::from_millis(limits::STOP_POLL_MS));
+                    }
+                }
+            })
+            .expect("failed to spawn monitor thread");
+        MonitorHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl std::fmt::Debug for DeviceMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceMonitor")
+            .field("sysfs_tty", &self.sysfs_tty)
+            .field("listener", &self.listener)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Running monitor; stops when shut down or dropped
+#[derive(Debug)]
+pub struct MonitorHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    /// Stop the monitor and wait for the current sweep to finish
+    pub fn shutdown(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MonitorHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_executor::{ExecResult, JobExecutor, MemoryTransport};
+    use crate::server::{Job, JobPriority, JobStatus, JobType, OpenFlashServer, ServerConfig};
+    use crate::DeviceTransport;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    fn erase_job(device_id: &str) -> Job {
+        let job_type = JobType::Erase {
+            start_address: 0,
+            length: None,
+        };
+        Job::new("erase", job_type).with_device(device_id)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_usb_and_announcements() {
+        let root = std::env::temp_dir().join(format!("openflash-discovery-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let tty_dir = root.join("class/tty");
+        std::fs::create_dir_all(&tty_dir).unwrap();
+        let programmers = [
+            ("ttyACM0", "c0de", "cafe", "OF-RP2040-001"),
+            ("ttyACM1", "c0de", "cafe", "OF-STM32F4-001"),
+            ("ttyACM2", "1209", "0f1a", "OF-RP2350-001"),
+            ("ttyACM3", "c0de", "cafe", "OF-STM32F4-001"),
+            ("ttyUSB0", "0403", "6001", "FTDI"),
+        ];
+        for (i, (tty, vid, pid, serial)) in programmers.iter().enumerate() {
+            let usb = root.join(format!("devices/usb1/1-{}", i));
+            let interface = usb.join(format!("1-{}:1.0", i));
+            std::fs::create_dir_all(&interface).unwrap();
+            std::fs::write(usb.join("idVendor"), format!("{}\n", vid)).unwrap();
+            std::fs::write(usb.join("idProduct"), format!("{}\n", pid)).unwrap();
+            std::fs::write(usb.join("serial"), format!("{}\n", serial)).unwrap();
+            std::fs::write(usb.join("product"), "OpenFlash NAND Programmer\n").unwrap();
+            std::fs::create_dir_all(tty_dir.join(tty)).unwrap();
+            std::os::unix::fs::symlink(&interface, tty_dir.join(tty).join("device")).unwrap();
+        }
+        // Virtual consoles have no device link
+        std::fs::create_dir_all(tty_dir.join("tty0")).unwrap();
+
+        let found: Vec<(String, String, DevicePlatform)> = scan_usb(&tty_dir)
+            .into_iter()
+            .map(|d| (d.id, d.uri, d.platform))
+            .collect();
+        let expected = [
+            ("usb-OF-RP2040-001", "ttyACM0", DevicePlatform::RP2040),
+            ("usb-OF-RP2350-001", "ttyACM2", DevicePlatform::RP2350),
+            // Same serial number: told apart by tty
+            ("usb-ttyACM1", "ttyACM1", DevicePlatform::STM32F4),
+            ("usb-ttyACM3", "ttyACM3", DevicePlatform::STM32F4),
+        ];
+        let expected: Vec<(String, String, DevicePlatform)> = expected
+            .iter()
+            .map(|(id, tty, p)| (id.to_string(), format!("serial:///dev/{}", tty), *p))
+            .collect();
+        assert_eq!(found, expected);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let listener = AnnouncementListener::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut pi = Announcement::new("pi-bench-1", 7070);
+        pi.platform = Some("RaspberryPi".to_string());
+        pi.tags = vec!["bench".to_string()];
+        let addr = listener.local_addr().unwrap();
+        sender.send_to(b"not json", addr).unwrap();
+        sender
+            .send_to(&Announcement::new("pi-bench-1", 7000).to_datagram(), addr)
+            .unwrap();
+        sender.send_to(&pi.to_datagram(), addr).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut announced = Vec::new();
+        while announced.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            announced = listener.drain();
+        }
+        assert_eq!(announced.len(), 1);
+        assert_eq!(announced[0].uri, "tcp://127.0.0.1:7070");
+        assert_eq!(announced[0].platform, DevicePlatform::RaspberryPi);
+        assert_eq!(announced[0].tags, vec!["bench".to_string()]);
+        assert!(announced[0].is_available());
+    }
+
+    #[test]
+    fn test_announcements_need_secret_or_allowlist() {
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(ServerConfig::default())));
+        let factory: TransportFactory =
+            Arc::new(|_: &PoolDevice| Ok(Box::new(MemoryTransport::new(4096, 4096)) as _));
+        let monitor = || DeviceMonitor::new(server.clone(), factory.clone());
+        let mut config = DiscoveryConfig {
+            announce_port: Some(0),
+            ..Default::default()
+        };
+        let err = monitor()
+            .listen_announcements(&config, "0.0.0.0")
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        config.announce_allow = vec!["pi-bench-1".to_string()];
+        assert!(monitor().listen_announcements(&config, "0.0.0.0").is_err());
+
+        // Signed announcements over loopback, the REST API default
+        config.announce_allow.clear();
+        config.announce_secret = Some("s3cret".to_string());
+        let monitor = monitor()
+            .listen_announcements(&config, "127.0.0.1")
+            .unwrap();
+        let listener = monitor.listener.as_ref().unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(addr.ip().is_loopback());
+        let pi = Announcement::new("pi-bench-1", 7070);
+        let mut stale = pi.clone();
+        stale.sent_at = Some(now_ms() / 1000 - limits::ANNOUNCE_MAX_AGE_SECS - 5);
+        let mut stale = stale.to_datagram();
+        let mac = announce_mac("s3cret", &stale).finalize().into_bytes();
+        stale.push(b'\n');
+        stale.extend(mac.iter().flat_map(|b| format!("{:02x}", b).into_bytes()));
+        let mut tampered = pi.to_signed_datagram("s3cret");
+        let port = tampered.windows(4).position(|w| w == b"7070").unwrap();
+        tampered[port..port + 4].copy_from_slice(b"7071");
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for datagram in [
+            pi.to_datagram(),
+            pi.to_signed_datagram("wrong"),
+            stale,
+            tampered,
+            pi.to_signed_datagram("s3cret"),
+        ] {
+            sender.send_to(&datagram, addr).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut announced = Vec::new();
+        while announced.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            announced = listener.drain();
+        }
+        assert_eq!(announced.len(), 1);
+        assert_eq!(announced[0].uri, "tcp://127.0.0.1:7070");
+
+        // Unsigned announcements from listed senders only
+        let listener = AnnouncementListener::bind("127.0.0.1:0")
+            .unwrap()
+            .with_allowlist(["192.0.2.10".parse().unwrap()]);
+        let datagram = pi.to_datagram();
+        assert!(listener
+            .accept(&datagram, "192.0.2.11:5000".parse().unwrap())
+            .is_none());
+        let device = listener
+            .accept(&datagram, "192.0.2.10:5000".parse().unwrap())
+            .unwrap();
+        assert_eq!(device.uri, "tcp://192.0.2.10:7070");
+    }
+
+    /// Programmer behind a bridge that takes one connection at a time
+    struct Bridge {
+        chip: MemoryTransport,
+        open: Arc<AtomicUsize>,
+    }
+
+    impl Drop for Bridge {
+        fn drop(&mut self) {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    impl DeviceTransport for Bridge {
+        fn capacity(&mut self, include_oob: bool) -> ExecResult<u64> {
+            self.chip.capacity(include_oob)
+        }
+
+        fn erase_size(&mut self) -> ExecResult<u64> {
+            self.chip.erase_size()
+        }
+
+        fn read(&mut self, address: u64, buf: &mut [u8], include_oob: bool) -> ExecResult<()> {
+            self.chip.read(address, buf, include_oob)
+        }
+
+        fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+            self.chip.write(address, data)
+        }
+
+        fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+            self.chip.erase(address, length)
+        }
+    }
+
+    #[test]
+    fn test_pings_share_the_executor_connection() {
+        let mut config = ServerConfig::default();
+        config.discovery.interval_secs = 0;
+        config.discovery.offline_after_secs = 0;
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        {
+            let mut server = lock_server(&server);
+            let device = PoolDevice::new(
+                "bridge",
+                "bridge",
+                "tcp://10.0.0.9:2000",
+                DevicePlatform::RP2040,
+            );
+            server.register_device(device).unwrap();
+            server
+                .set_device_status("bridge", DeviceStatus::Available)
+                .unwrap();
+        }
+        let chip = MemoryTransport::new(4096, 4096);
+        let open = Arc::new(AtomicUsize::new(0));
+        let opened = Arc::new(AtomicUsize::new(0));
+        let (bridge_open, bridge_opened) = (open.clone(), opened.clone());
+        let factory: TransportFactory = Arc::new(move |_: &PoolDevice| {
+            if bridge_open.fetch_add(1, Ordering::SeqCst) > 0 {
+                bridge_open.fetch_sub(1, Ordering::SeqCst);
+                return Err(ExecError::Connect("bridge is busy".to_string()));
+            }
+            bridge_opened.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Bridge {
+                chip: chip.clone(),
+                open: bridge_open.clone(),
+            }) as Box<dyn DeviceTransport>)
+        });
+        let executor = JobExecutor::new(server.clone(), factory.clone())
+            .with_poll_interval(Duration::from_millis(5));
+        let connections = executor.connections();
+        let executor = executor.start();
+        let run_job = || {
+            let job_id = lock_server(&server)
+                .submit_job(erase_job("bridge"))
+                .unwrap();
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while lock_server(&server).get_job_status(job_id).unwrap().status != "completed" {
+                assert!(Instant::now() < deadline, "job {} did not complete", job_id);
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        let sweep_idle = |monitor: &mut DeviceMonitor| {
+            for device in lock_server(&server).device_pool.devices.values_mut() {
+                device.last_seen = 0;
+            }
+            monitor.sweep()
+        };
+
+        // The idle worker keeps its connection; pings go over it
+        run_job();
+        let mut monitor = DeviceMonitor::new(server.clone(), factory.clone())
+            .with_sysfs_root(None)
+            .with_connections(connections);
+        let report = sweep_idle(&mut monitor);
+        assert!(report.lost.is_empty());
+        assert!(
+            lock_server(&server)
+                .device_pool
+                .get_device("bridge")
+                .unwrap()
+                .last_seen
+                > 0
+        );
+        run_job();
+        assert_eq!(opened.load(Ordering::SeqCst), 1);
+
+        // A second connection next to the worker's is refused
+        let mut monitor = DeviceMonitor::new(server.clone(), factory).with_sysfs_root(None);
+        let report = sweep_idle(&mut monitor);
+        assert_eq!(report.lost, vec!["bridge".to_string()]);
+        executor.shutdown();
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_sweep_marks_lost_and_revives_devices() {
+        let mut config = ServerConfig::default();
+        config.discovery.interval_secs = 0;
+        config.discovery.offline_after_secs = 0;
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        let reachable = Arc::new(Mutex::new(HashSet::from(["pi-1".to_string()])));
+        let alive = reachable.clone();
+        let factory: TransportFactory = Arc::new(move |device: &PoolDevice| {
+            if device.uri.starts_with("serial://") {
+                return Err(ExecError::Unsupported(device.uri.clone()));
+            }
+            if !alive.lock().unwrap().contains(&device.id) {
+                return Err(ExecError::Connect(format!("{} is unreachable", device.id)));
+            }
+            Ok(Box::new(MemoryTransport::new(4096, 4096)) as Box<dyn crate::DeviceTransport>)
+        });
+        {
+            let mut server = lock_server(&server);
+            for id in ["pi-1", "pi-2"] {
+                let uri = format!("tcp://{}:7070", id);
+                let device = PoolDevice::new(id, id, &uri, DevicePlatform::RaspberryPi);
+                server.register_device(device).unwrap();
+                server
+                    .set_device_status(id, DeviceStatus::Available)
+                    .unwrap();
+            }
+            let serial = PoolDevice::new(
+                "manual",
+                "manual",
+                "serial:///dev/ttyS0",
+                DevicePlatform::RP2040,
+            );
+            server.register_device(serial).unwrap();
+            server
+                .set_device_status("manual", DeviceStatus::Available)
+                .unwrap();
+            server.submit_job(erase_job("pi-2")).unwrap();
+            for device in server.device_pool.devices.values_mut() {
+                device.last_seen = 0;
+            }
+        }
+
+        let mut monitor = DeviceMonitor::new(server.clone(), factory).with_sysfs_root(None);
+        let report = monitor.sweep();
+        let mut lost = report.lost.clone();
+        lost.sort();
+        assert_eq!(lost, vec!["manual".to_string(), "pi-2".to_string()]);
+        assert_eq!(report.held.len(), 1);
+        {
+            let server = lock_server(&server);
+            let pi2 = server.device_pool.get_device("pi-2").unwrap();
+            assert_eq!(pi2.status, DeviceStatus::Offline);
+            assert!(server.device_pool.get_device("pi-1").unwrap().last_seen > 0);
+            // Devices the factory cannot open are not available
+            let manual = server.device_pool.get_device("manual").unwrap();
+            assert_eq!(manual.status, DeviceStatus::Offline);
+            // The job stays on the chip it was submitted for
+            let job = &server.job_queue.pending[0];
+            assert_eq!(job.device_id.as_deref(), Some("pi-2"));
+            assert_eq!(job.id, report.held[0]);
+        }
+
+        reachable.lock().unwrap().insert("pi-2".to_string());
+        let report = monitor.sweep();
+        assert_eq!(report.revived, vec!["pi-2".to_string()]);
+        assert!(report.lost.is_empty());
+        let server = lock_server(&server);
+        assert!(server
+            .device_pool
+            .get_device("pi-2")
+            .unwrap()
+            .is_available());
+    }
+
+    /// Announce `ids` to the monitor and sweep until `done` holds for the
+    /// combined reports
+    fn announce_until(
+        monitor: &mut DeviceMonitor,
+        ids: &[&str],
+        done: impl Fn(&SweepReport) -> bool,
+    ) -> SweepReport {
+        let addr = monitor.listener.as_ref().unwrap().local_addr().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for id in ids {
+            sender
+                .send_to(&Announcement::new(id, 7070).to_datagram(), addr)
+                .unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut report = SweepReport::default();
+        while !done(&report) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            let sweep = monitor.sweep();
+            report.discovered.extend(sweep.discovered);
+            report.revived.extend(sweep.revived);
+            report.lost.extend(sweep.lost);
+            report.ignored.extend(sweep.ignored);
+        }
+        report
+    }
+
+    #[test]
+    fn test_announced_devices_follow_their_announcements() {
+        let mut config = ServerConfig::default();
+        config.discovery.interval_secs = 0;
+        config.discovery.offline_after_secs = 0;
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        let reachable = Arc::new(Mutex::new(HashSet::from(["pi-a".to_string()])));
+        let alive = reachable.clone();
+        let factory: TransportFactory = Arc::new(move |device: &PoolDevice| {
+            if !alive.lock().unwrap().contains(&device.id) {
+                return Err(ExecError::Connect(format!("{} is unreachable", device.id)));
+            }
+            Ok(Box::new(MemoryTransport::new(4096, 4096)) as Box<dyn crate::DeviceTransport>)
+        });
+        let mut monitor = DeviceMonitor::new(server.clone(), factory)
+            .with_sysfs_root(None)
+            .listen("127.0.0.1:0")
+            .unwrap();
+        let status = |id: &str| {
+            lock_server(&server)
+                .device_pool
+                .get_device(id)
+                .unwrap()
+                .status
+        };
+
+        // Only devices the factory opens are made available
+        let report = announce_until(&mut monitor, &["pi-a", "pi-b"], |r| r.discovered.len() == 2);
+        assert_eq!(report.discovered.len(), 2);
+        assert_eq!(status("pi-a"), DeviceStatus::Available);
+        assert_eq!(status("pi-b"), DeviceStatus::Offline);
+
+        // A device that stops announcing is lost even though it still opens
+        let report = monitor.sweep();
+        assert_eq!(report.lost, vec!["pi-a".to_string()]);
+        assert_eq!(status("pi-a"), DeviceStatus::Offline);
+
+        reachable.lock().unwrap().insert("pi-b".to_string());
+        let report = announce_until(&mut monitor, &["pi-b"], |r| !r.revived.is_empty());
+        assert_eq!(report.revived, vec!["pi-b".to_string()]);
+        assert_eq!(status("pi-b"), DeviceStatus::Available);
+
+        // Announcements cannot take over manually registered devices
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut server = lock_server(&server);
+            let bench = PoolDevice::new(
+                "bench",
+                "bench",
+                "tcp://10.0.0.5:7070",
+                DevicePlatform::RP2040,
+            );
+            server.register_device(bench).unwrap();
+            server
+                .set_device_status("bench", DeviceStatus::Available)
+                .unwrap();
+            let errors = errors.clone();
+            server.events.subscribe(move |event| {
+                if let WsMessage::Error { code, message } = event {
+                    errors
+                        .lock()
+                        .unwrap()
+                        .push(format!("{}: {}", code, message));
+                }
+            });
+        }
+        let report = announce_until(&mut monitor, &["bench"], |r| !r.ignored.is_empty());
+        assert_eq!(report.ignored, vec!["bench".to_string()]);
+        let server = lock_server(&server);
+        let bench = server.device_pool.get_device("bench").unwrap();
+        assert_eq!(bench.uri, "tcp://10.0.0.5:7070");
+        assert!(!bench.metadata.contains_key(limits::SOURCE_METADATA));
+        assert!(errors.lock().unwrap()[0].starts_with("discovery: ignored bench"));
+    }
+
+    #[test]
+    fn test_quarantine_after_failures() {
+        let mut config = ServerConfig::default();
+        config.discovery.quarantine_after_failures = 2;
+        let mut server = OpenFlashServer::new(config);
+        for id in ["flaky", "spare"] {
+            let uri = format!("mem://{}", 4096);
+            server
+                .register_device(PoolDevice::new(id, id, &uri, DevicePlatform::RP2040))
+                .unwrap();
+        }
+        server
+            .set_device_status("flaky", DeviceStatus::Available)
+            .unwrap();
+        let mut job = erase_job("flaky");
+        job.max_retries = 0;
+        server.submit_job(job).unwrap();
+        server.submit_job(erase_job("flaky")).unwrap();
+        let pinned = server.submit_job(erase_job("flaky")).unwrap();
+
+        for _ in 0..2 {
+            let job = server.start_next_job("flaky").unwrap();
+            server.fail_job(job.id, "program failed").unwrap();
+        }
+        let flaky = server.device_pool.get_device("flaky").unwrap();
+        assert_eq!(flaky.status, DeviceStatus::Maintenance);
+        assert!(flaky.metadata.contains_key(limits::QUARANTINE_METADATA));
+        assert!(server.start_next_job("flaky").is_none());
+        let pending = server.job_queue.get_job(pinned).unwrap();
+        assert_eq!(pending.device_id.as_deref(), Some("flaky"));
+
+        // Answering health checks does not lift the quarantine
+        server
+            .set_device_status("flaky", DeviceStatus::Error)
+            .unwrap();
+        assert!(!server.device_heartbeat("flaky").unwrap());
+        server
+            .set_device_status("flaky", DeviceStatus::Available)
+            .unwrap();
+        let flaky = server.device_pool.get_device("flaky").unwrap();
+        assert_eq!(flaky.consecutive_failures, 0);
+        assert!(!flaky.metadata.contains_key(limits::QUARANTINE_METADATA));
+
+        // A device lost mid-job hands the job back to the queue; pinned
+        // jobs keep waiting for their device
+        let pinned_spare = server.submit_job(erase_job("spare")).unwrap();
+        let mut any = erase_job("spare");
+        any.device_id = None;
+        any.priority = JobPriority::High;
+        let any = server.submit_job(any).unwrap();
+        server
+            .set_device_status("spare", DeviceStatus::Available)
+            .unwrap();
+        let job = server.start_next_job("spare").unwrap();
+        assert_eq!(job.id, any);
+        let held = server
+            .mark_device_lost("spare", "device disconnected")
+            .unwrap();
+        assert_eq!(held, vec![pinned_spare]);
+        assert_eq!(
+            server.device_pool.get_device("spare").unwrap().status,
+            DeviceStatus::Offline
+        );
+        let requeued = server.job_queue.get_job(job.id).unwrap();
+        assert_eq!(requeued.status, JobStatus::Queued);
+        assert_eq!(requeued.device_id, None);
+        assert_eq!(
+            server
+                .job_queue
+                .get_job(pinned_spare)
+                .unwrap()
+                .device_id
+                .as_deref(),
+            Some("spare")
+        );
+    }
+}
@@ -11,6 +11,17 @@ pub enum EccAlgorithm {
     Bch { t: u8 }, // t = number of correctable errors
 }
 
+impl EccAlgorithm {
+    /// Bits correctable per 512-byte ECC step
+    pub fn correction_capability(&self) -> u32 {
+        match self {
+            EccAlgorithm::None => 0,
+            EccAlgorithm::Hamming => 1,
+            EccAlgorithm::Bch { t } => *t as u32,
+        }
+    }
+}
+
 /// ECC processing result
 #[derive(Debug, Clone)]
 pub struct EccResult {
@@ -508,41 +519,58 @@ pub fn decode_with_ecc(
     ecc_data: &[u8],
     algorithm: &EccAlgorithm,
 ) -> Result<u32, EccError> {
+    decode_sectors(data, ecc_data, algorithm).into_iter().sum()
+}
+
+/// Decode and correct data one 512-byte ECC step at a time
+///
+/// Returns the corrected bit count (or error) of every step that has ECC
+/// bytes, in order. Unlike `decode_with_ecc`, an uncorrectable step does
+/// not stop the remaining steps from being corrected.
+pub fn decode_sectors(
+    data: &mut [u8],
+    ecc_data: &[u8],
+    algorithm: &EccAlgorithm,
+) -> Vec<Result<u32, EccError>> {
     match algorithm {
-        EccAlgorithm::None => Ok(0),
+        EccAlgorithm::None => Vec::new(),
         EccAlgorithm::Hamming => {
             let ecc = HammingEcc::new(512);
             let ecc_per_sector = 4; // 512-byte sector
-            let mut total_corrected = 0u32;
-
-            for (i, chunk) in data.chunks_mut(512).enumerate() {
-                if chunk.len() == 512 {
-                    let ecc_start = i * ecc_per_sector;
-                    let ecc_end = ecc_start + ecc_per_sector;
-                    if ecc_end <= ecc_data.len() {
-                        total_corrected += ecc.correct(chunk, &ecc_data[ecc_start..ecc_end])?;
-                    }
-                }
-            }
-            Ok(total_corrected)
+            correct_sectors(data, ecc_data, ecc_per_sector, |chunk, stored| {
+                ecc.correct(chunk, stored)
+            })
         }
         EccAlgorithm::Bch { t } => {
             let ecc = BchEcc::new(512, *t);
             let ecc_per_sector = ecc.generator.len() / 8 + 1;
-            let mut total_corrected = 0u32;
+            correct_sectors(data, ecc_data, ecc_per_sector, |chunk, stored| {
+                ecc.correct(chunk, stored)
+            })
+        }
+    }
+}
 
-            for (i, chunk) in data.chunks_mut(512).enumerate() {
-                if chunk.len() == 512 {
-                    let ecc_start = i * ecc_per_sector;
-                    let ecc_end = ecc_start + ecc_per_sector;
-                    if ecc_end <= ecc_data.len() {
-                        total_corrected += ecc.correct(chunk, &ecc_data[ecc_start..ecc_end])?;
-                    }
-                }
+fn correct_sectors<F>(
+    data: &mut [u8],
+    ecc_data: &[u8],
+    ecc_per_sector: usize,
+    correct: F,
+) -> Vec<Result<u32, EccError>>
+where
+    F: Fn(&mut [u8], &[u8]) -> Result<u32, EccError>,
+{
+    let mut results = Vec::new();
+    for (i, chunk) in data.chunks_mut(512).enumerate() {
+        if chunk.len() == 512 {
+            let ecc_start = i * ecc_per_sector;
+            let ecc_end = ecc_start + ecc_per_sector;
+            if ecc_end <= ecc_data.len() {
+                results.push(correct(chunk, &ecc_data[ecc_start..ecc_end]));
             }
-            Ok(total_corrected)
         }
     }
+    results
 }
 
 #[cfg(test)]
@@ -41,12 +41,19 @@ pub mod commands {
     pub const SET_BLOCKLEN: u8 = 16; // CMD16 - Set block length
     pub const READ_SINGLE_BLOCK: u8 = 17; // CMD17 - Read single block
     pub const READ_MULTIPLE_BLOCK: u8 = 18; // CMD18 - Read multiple blocks
+    pub const SET_BLOCK_COUNT: u8 = 23; // CMD23 - Set block count
 
     // Block write commands (class 4)
     pub const WRITE_BLOCK: u8 = 24; // CMD24 - Write single block
     pub const WRITE_MULTIPLE_BLOCK: u8 = 25; // CMD25 - Write multiple blocks
     pub const PROGRAM_CSD: u8 = 27; // CMD27 - Program CSD
 
+    // Write protection commands (class 6)
+    pub const SET_WRITE_PROT: u8 = 28; // CMD28 - Set write protect group
+    pub const CLR_WRITE_PROT: u8 = 29; // CMD29 - Clear write protect group
+    pub const SEND_WRITE_PROT: u8 = 30; // CMD30 - Send write protect status
+    pub const SEND_WRITE_PROT_TYPE: u8 = 31; // CMD31 - Send write protect type
+
     // Erase commands (class 5)
     pub const ERASE_GROUP_START: u8 = 35; // CMD35 - Set erase start
     pub const ERASE_GROUP_END: u8 = 36; // CMD36 - Set erase end
@@ -149,6 +156,68 @@ pub mod ext_csd {
     pub const EXT_CSD_REV: usize = 192;
     pub const BOOT_SIZE_MULT: usize = 226;
     pub const RPMB_SIZE_MULT: usize = 168;
+
+    /// Total size of the EXT_CSD register in bytes
+    pub const SIZE: usize = 512;
+
+    // Modes segment
+    pub const FFU_STATUS: usize = 26;
+    pub const MODE_CONFIG: usize = 30;
+    pub const CACHE_CTRL: usize = 33;
+    pub const ENH_START_ADDR: usize = 136; // 4 bytes
+    pub const ENH_SIZE_MULT: usize = 140; // 3 bytes
+    pub const GP_SIZE_MULT: usize = 143; // 4 x 3 bytes
+    pub const PARTITION_SETTING_COMPLETED: usize = 155;
+    pub const PARTITIONS_ATTRIBUTE: usize = 156;
+    pub const MAX_ENH_SIZE_MULT: usize = 157; // 3 bytes
+    pub const PARTITIONING_SUPPORT: usize = 160;
+    pub const RST_N_FUNCTION: usize = 162;
+    pub const BKOPS_EN: usize = 163;
+    pub const SANITIZE_START: usize = 165;
+    pub const WR_REL_PARAM: usize = 166;
+    pub const WR_REL_SET: usize = 167;
+    pub const USER_WP: usize = 171;
+    pub const BOOT_WP: usize = 173;
+    pub const BOOT_WP_STATUS: usize = 174;
+    pub const ERASE_GROUP_DEF: usize = 175;
+    pub const BOOT_BUS_CONDITIONS: usize = 177;
+    pub const BOOT_CONFIG_PROT: usize = 178;
+    pub const ERASED_MEM_CONT: usize = 181;
+    pub const STROBE_SUPPORT: usize = 184;
+
+    // Properties segment
+    pub const DRIVER_STRENGTH: usize = 197;
+    pub const OUT_OF_INTERRUPT_TIME: usize = 198;
+    pub const PARTITION_SWITCH_TIME: usize = 199;
+    pub const S_A_TIMEOUT: usize = 217;
+    pub const S_C_VCCQ: usize = 219;
+    pub const S_C_VCC: usize = 220;
+    pub const HC_WP_GRP_SIZE: usize = 221;
+    pub const REL_WR_SEC_C: usize = 222;
+    pub const ERASE_TIMEOUT_MULT: usize = 223;
+    pub const HC_ERASE_GRP_SIZE: usize = 224;
+    pub const ACC_SIZE: usize = 225;
+    pub const BOOT_INFO: usize = 228;
+    pub const SEC_TRIM_MULT: usize = 229;
+    pub const SEC_ERASE_MULT: usize = 230;
+    pub const SEC_FEATURE_SUPPORT: usize = 231;
+    pub const TRIM_MULT: usize = 232;
+    pub const BKOPS_STATUS: usize = 246;
+    pub const POWER_OFF_LONG_TIME: usize = 247;
+    pub const GENERIC_CMD6_TIME: usize = 248;
+    pub const CACHE_SIZE: usize = 249; // 4 bytes, in kibibytes
+    pub const FIRMWARE_VERSION: usize = 254; // 8 bytes
+    pub const DEVICE_VERSION: usize = 262; // 2 bytes
+    pub const PRE_EOL_INFO: usize = 267;
+    pub const DEVICE_LIFE_TIME_EST_TYP_A: usize = 268;
+    pub const DEVICE_LIFE_TIME_EST_TYP_B: usize = 269;
+    pub const VENDOR_PROPRIETARY_HEALTH_REPORT: usize = 270; // 32 bytes
+    pub const VENDOR_PROPRIETARY_HEALTH_REPORT_LEN: usize = 32;
+    pub const SUPPORTED_MODES: usize = 493;
+    pub const FFU_FEATURES: usize = 492;
+    pub const BKOPS_SUPPORT: usize = 502;
+    pub const HPI_FEATURES: usize = 503;
+    pub const S_CMD_SET: usize = 504;
 }
 
 /// Get manufacturer name from CID manufacturer ID
@@ -613,6 +682,747 @@ pub fn parse_boot_size_from_ext_csd(ext_csd: &[u8]) -> u32 {
     (ext_csd[ext_csd::BOOT_SIZE_MULT] as u32) * 128 * 1024
 }
 
+// ============ Typed EXT_CSD decode ============
+
+/// Partition selected for data access (PARTITION_CONFIG[2:0])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PartitionAccess {
+    User,
+    Boot1,
+    Boot2,
+    Rpmb,
+    /// General purpose partition 1-4
+    GeneralPurpose(u8),
+}
+
+impl PartitionAccess {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0x07 {
+            0 => PartitionAccess::User,
+            1 => PartitionAccess::Boot1,
+            2 => PartitionAccess::Boot2,
+            3 => PartitionAccess::Rpmb,
+            n => PartitionAccess::GeneralPurpose(n - 3),
+        }
+    }
+
+    pub fn to_bits(self) -> u8 {
+        match self {
+            PartitionAccess::User => 0,
+            PartitionAccess::Boot1 => 1,
+            PartitionAccess::Boot2 => 2,
+            PartitionAccess::Rpmb => 3,
+            PartitionAccess::GeneralPurpose(n) => 3 + n.clamp(1, 4),
+        }
+    }
+}
+
+/// Partition the device boots from (PARTITION_CONFIG[5:3])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootPartitionEnable {
+    Disabled,
+    Boot1,
+    Boot2,
+    User,
+    Reserved(u8),
+}
+
+impl BootPartitionEnable {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0x07 {
+            0 => BootPartitionEnable::Disabled,
+            1 => BootPartitionEnable::Boot1,
+            2 => BootPartitionEnable::Boot2,
+            7 => BootPartitionEnable::User,
+            n => BootPartitionEnable::Reserved(n),
+        }
+    }
+
+    pub fn to_bits(self) -> u8 {
+        match self {
+            BootPartitionEnable::Disabled => 0,
+            BootPartitionEnable::Boot1 => 1,
+            BootPartitionEnable::Boot2 => 2,
+            BootPartitionEnable::User => 7,
+            BootPartitionEnable::Reserved(n) => n & 0x07,
+        }
+    }
+}
+
+/// PARTITION_CONFIG [179]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionConfig {
+    pub boot_ack: bool,
+    pub boot_partition: BootPartitionEnable,
+    pub access: PartitionAccess,
+}
+
+impl PartitionConfig {
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            boot_ack: value & 0x40 != 0,
+            boot_partition: BootPartitionEnable::from_bits(value >> 3),
+            access: PartitionAccess::from_bits(value),
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        ((self.boot_ack as u8) << 6) | (self.boot_partition.to_bits() << 3) | self.access.to_bits()
+    }
+}
+
+/// Boot mode timing (BOOT_BUS_CONDITIONS[4:3])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootMode {
+    /// Single data rate, backward compatible timing
+    SdrBackward,
+    /// Single data rate, high speed timing
+    SdrHighSpeed,
+    /// Dual data rate
+    Ddr,
+    Reserved,
+}
+
+/// BOOT_BUS_CONDITIONS [177]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootBusConditions {
+    pub mode: BootMode,
+    /// Keep the boot bus width after boot instead of resetting to x1
+    pub retain_bus_width: bool,
+    /// Boot bus width in data lines (1, 4 or 8; 0 if reserved)
+    pub bus_width: u8,
+}
+
+impl BootBusConditions {
+    pub fn from_byte(value: u8) -> Self {
+        Self {
+            mode: match (value >> 3) & 0x03 {
+                0 => BootMode::SdrBackward,
+                1 => BootMode::SdrHighSpeed,
+                2 => BootMode::Ddr,
+                _ => BootMode::Reserved,
+            },
+            retain_bus_width: value & 0x04 != 0,
+            bus_width: match value & 0x03 {
+                0 => 1,
+                1 => 4,
+                2 => 8,
+                _ => 0,
+            },
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        let mode = match self.mode {
+            BootMode::SdrBackward => 0,
+            BootMode::SdrHighSpeed => 1,
+            BootMode::Ddr => 2,
+            BootMode::Reserved => 3,
+        };
+        let width = match self.bus_width {
+            4 => 1,
+            8 => 2,
+            _ => 0,
+        };
+        (mode << 3) | ((self.retain_bus_width as u8) << 2) | width
+    }
+}
+
+/// Write protection state of one boot area (BOOT_WP_STATUS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootAreaProtection {
+    None,
+    PowerOn,
+    Permanent,
+    Reserved,
+}
+
+impl BootAreaProtection {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => BootAreaProtection::None,
+            1 => BootAreaProtection::PowerOn,
+            2 => BootAreaProtection::Permanent,
+            _ => BootAreaProtection::Reserved,
+        }
+    }
+}
+
+/// Boot and user area write-protect settings (BOOT_WP, BOOT_WP_STATUS, USER_WP)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteProtectSettings {
+    pub boot_power_on_wp_enabled: bool,
+    pub boot_permanent_wp_enabled: bool,
+    pub boot_power_on_wp_disabled: bool,
+    pub boot_permanent_wp_disabled: bool,
+    /// Protection applies to the area selected by the SEC_SEL bits only
+    pub boot_wp_per_area: bool,
+    pub boot1_status: BootAreaProtection,
+    pub boot2_status: BootAreaProtection,
+    pub user_power_on_wp_enabled: bool,
+    pub user_permanent_wp_enabled: bool,
+    pub user_power_on_wp_disabled: bool,
+    pub user_permanent_wp_disabled: bool,
+    pub csd_permanent_wp_disabled: bool,
+    pub password_protection_disabled: bool,
+    /// Write-protect group size in bytes (0 if undefined)
+    pub wp_group_size: u64,
+}
+
+/// HS_TIMING [185] bus timing interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HsTiming {
+    Legacy,
+    HighSpeed,
+    Hs200,
+    Hs400,
+    Reserved(u8),
+}
+
+impl HsTiming {
+    pub fn from_byte(value: u8) -> Self {
+        match value & 0x0F {
+            0 => HsTiming::Legacy,
+            1 => HsTiming::HighSpeed,
+            2 => HsTiming::Hs200,
+            3 => HsTiming::Hs400,
+            n => HsTiming::Reserved(n),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HsTiming::Legacy => "Legacy",
+            HsTiming::HighSpeed => "High Speed",
+            HsTiming::Hs200 => "HS200",
+            HsTiming::Hs400 => "HS400",
+            HsTiming::Reserved(_) => "Reserved",
+        }
+    }
+}
+
+/// DEVICE_TYPE [196] supported bus modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DeviceTypeSupport {
+    pub hs26: bool,
+    pub hs52: bool,
+    pub ddr52_1v8_3v: bool,
+    pub ddr52_1v2: bool,
+    pub hs200_1v8: bool,
+    pub hs200_1v2: bool,
+    pub hs400_1v8: bool,
+    pub hs400_1v2: bool,
+    /// Enhanced strobe for HS400 (STROBE_SUPPORT)
+    pub enhanced_strobe: bool,
+}
+
+/// Volatile cache settings (CACHE_SIZE, CACHE_CTRL)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CacheInfo {
+    pub size_bytes: u64,
+    pub enabled: bool,
+}
+
+/// General purpose and enhanced user area partitioning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PartitioningInfo {
+    pub supported: bool,
+    pub enhanced_supported: bool,
+    pub extended_attributes_supported: bool,
+    /// PARTITION_SETTING_COMPLETED - partitioning is one-time and already done
+    pub setting_completed: bool,
+    pub attributes: u8,
+    /// GP1..GP4 sizes in bytes
+    pub gp_sizes: [u64; 4],
+    pub enhanced_start: u32,
+    pub enhanced_size: u64,
+    pub max_enhanced_size: u64,
+}
+
+/// Erase and trim parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct EraseInfo {
+    /// ERASE_GROUP_DEF - high capacity erase group size is in use
+    pub high_capacity_groups: bool,
+    /// High capacity erase group size in bytes
+    pub erase_group_size: u64,
+    pub erase_timeout_ms: u32,
+    pub trim_timeout_ms: u32,
+    pub secure_erase_timeout_ms: u32,
+    pub secure_trim_timeout_ms: u32,
+    pub secure_erase_supported: bool,
+    pub secure_bad_block_supported: bool,
+    pub trim_supported: bool,
+    pub sanitize_supported: bool,
+    /// ERASED_MEM_CONT - erased memory reads as 0xFF instead of 0x00
+    pub erased_reads_ones: bool,
+}
+
+/// Background operations state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BkopsInfo {
+    pub supported: bool,
+    pub manual_enabled: bool,
+    pub auto_enabled: bool,
+    /// 0 = not required ... 3 = critical
+    pub status: u8,
+}
+
+/// PRE_EOL_INFO [267] - consumption of reserved blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PreEolInfo {
+    Undefined,
+    Normal,
+    /// 80% of reserved blocks consumed
+    Warning,
+    /// 90% of reserved blocks consumed
+    Urgent,
+    Reserved(u8),
+}
+
+impl PreEolInfo {
+    pub fn from_byte(value: u8) -> Self {
+        match value {
+            0 => PreEolInfo::Undefined,
+            1 => PreEolInfo::Normal,
+            2 => PreEolInfo::Warning,
+            3 => PreEolInfo::Urgent,
+            n => PreEolInfo::Reserved(n),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PreEolInfo::Undefined => "Undefined",
+            PreEolInfo::Normal => "Normal",
+            PreEolInfo::Warning => "Warning (80% reserved blocks used)",
+            PreEolInfo::Urgent => "Urgent (90% reserved blocks used)",
+            PreEolInfo::Reserved(_) => "Reserved",
+        }
+    }
+}
+
+/// DEVICE_LIFE_TIME_EST_TYP_A/B - estimated wear in 10% steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifeTimeEstimate {
+    Undefined,
+    /// Between `min_percent` and `max_percent` of rated life used
+    Used { min_percent: u8, max_percent: u8 },
+    /// Rated life exceeded
+    Exceeded,
+    Reserved(u8),
+}
+
+impl LifeTimeEstimate {
+    pub fn from_byte(value: u8) -> Self {
+        match value {
+            0 => LifeTimeEstimate::Undefined,
+            1..=10 => LifeTimeEstimate::Used {
+                min_percent: (value - 1) * 10,
+                max_percent: value * 10,
+            },
+            11 => LifeTimeEstimate::Exceeded,
+            n => LifeTimeEstimate::Reserved(n),
+        }
+    }
+
+    /// Upper bound of the used life in percent, if known
+    pub fn max_percent(&self) -> Option<u8> {
+        match self {
+            LifeTimeEstimate::Used { max_percent, .. } => Some(*max_percent),
+            LifeTimeEstimate::Exceeded => Some(100),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LifeTimeEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LifeTimeEstimate::Undefined => write!(f, "Undefined"),
+            LifeTimeEstimate::Used {
+                min_percent,
+                max_percent,
+            } => write!(f, "{}-{}% used", min_percent, max_percent),
+            LifeTimeEstimate::Exceeded => write!(f, "Exceeded"),
+            LifeTimeEstimate::Reserved(n) => write!(f, "Reserved (0x{:02X})", n),
+        }
+    }
+}
+
+/// Decoded Extended CSD register
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtCsd {
+    pub revision: u8,
+    pub csd_structure: u8,
+    pub command_set: u8,
+    pub sector_count: u32,
+    pub capacity_bytes: u64,
+    pub device_type: DeviceTypeSupport,
+    pub hs_timing: HsTiming,
+    /// Driver strength selected in HS_TIMING[7:4]
+    pub driver_strength: u8,
+    pub supported_driver_strengths: u8,
+    /// BUS_WIDTH raw value (0=x1, 1=x4, 2=x8, 5/6=DDR x4/x8)
+    pub bus_width: u8,
+    pub power_class: u8,
+    pub partition_config: PartitionConfig,
+    /// BOOT_CONFIG_PROT - boot configuration locked (power-on or permanent)
+    pub boot_config_protection: u8,
+    pub boot_bus_conditions: BootBusConditions,
+    pub boot_size_bytes: u32,
+    pub alternate_boot_supported: bool,
+    pub rpmb_size_bytes: u32,
+    pub write_protect: WriteProtectSettings,
+    pub partitioning: PartitioningInfo,
+    pub erase: EraseInfo,
+    pub cache: CacheInfo,
+    pub bkops: BkopsInfo,
+    pub hpi_supported: bool,
+    pub reliable_write_sectors: u8,
+    pub partition_switch_time_ms: u32,
+    pub generic_cmd6_time_ms: u32,
+    pub firmware_version: [u8; 8],
+    pub device_version: u16,
+    pub ffu_supported: bool,
+    pub pre_eol_info: PreEolInfo,
+    pub life_time_est_a: LifeTimeEstimate,
+    pub life_time_est_b: LifeTimeEstimate,
+    pub vendor_health_report: [u8; 32],
+}
+
+impl ExtCsd {
+    /// Decode a raw 512-byte EXT_CSD register (as returned by CMD8)
+    pub fn parse(raw: &[u8]) -> Option<Self> {
+        use self::ext_csd as e;
+
+        if raw.len() < e::SIZE {
+            return None;
+        }
+
+        let le24 = |o: usize| u32::from_le_bytes([raw[o], raw[o + 1], raw[o + 2], 0]) as u64;
+        let le32 = |o: usize| u32::from_le_bytes([raw[o], raw[o + 1], raw[o + 2], raw[o + 3]]);
+        // Timeouts in units of 10ms / 300ms multipliers
+        let ms10 = |o: usize| raw[o] as u32 * 10;
+
+        let hc_erase_grp = raw[e::HC_ERASE_GRP_SIZE] as u64;
+        let hc_wp_grp = raw[e::HC_WP_GRP_SIZE] as u64;
+        // HC_ERASE_GRP_SIZE is in units of 512KiB
+        let erase_group_size = hc_erase_grp * 512 * 1024;
+        let wp_group_size = erase_group_size * hc_wp_grp;
+
+        let mut gp_sizes = [0u64; 4];
+        for (i, size) in gp_sizes.iter_mut().enumerate() {
+            *size = le24(e::GP_SIZE_MULT + i * 3) * wp_group_size;
+        }
+
+        let sec_feature = raw[e::SEC_FEATURE_SUPPORT];
+        let erase_timeout_ms = raw[e::ERASE_TIMEOUT_MULT] as u32 * 300;
+        let boot_wp = raw[e::BOOT_WP];
+        let boot_wp_status = raw[e::BOOT_WP_STATUS];
+        let user_wp = raw[e::USER_WP];
+        let partitioning_support = raw[e::PARTITIONING_SUPPORT];
+        let device_type = raw[e::DEVICE_TYPE];
+        let sector_count = le32(e::SEC_COUNT);
+
+        let mut firmware_version = [0u8; 8];
+        firmware_version.copy_from_slice(&raw[e::FIRMWARE_VERSION..e::FIRMWARE_VERSION + 8]);
+        let mut vendor_health_report = [0u8; 32];
+        vendor_health_report.copy_from_slice(
+            &raw[e::VENDOR_PROPRIETARY_HEALTH_REPORT
+                ..e::VENDOR_PROPRIETARY_HEALTH_REPORT + e::VENDOR_PROPRIETARY_HEALTH_REPORT_LEN],
+        );
+
+        Some(Self {
+            revision: raw[e::EXT_CSD_REV],
+            csd_structure: raw[e::CSD_STRUCTURE],
+            command_set: raw[e::S_CMD_SET],
+            sector_count,
+            capacity_bytes: sector_count as u64 * 512,
+            device_type: DeviceTypeSupport {
+                hs26: device_type & 0x01 != 0,
+                hs52: device_type & 0x02 != 0,
+                ddr52_1v8_3v: device_type & 0x04 != 0,
+                ddr52_1v2: device_type & 0x08 != 0,
+                hs200_1v8: device_type & 0x10 != 0,
+                hs200_1v2: device_type & 0x20 != 0,
+                hs400_1v8: device_type & 0x40 != 0,
+                hs400_1v2: device_type & 0x80 != 0,
+                enhanced_strobe: raw[e::STROBE_SUPPORT] & 0x01 != 0,
+            },
+            hs_timing: HsTiming::from_byte(raw[e::HS_TIMING]),
+            driver_strength: raw[e::HS_TIMING] >> 4,
+            supported_driver_strengths: raw[e::DRIVER_STRENGTH],
+            bus_width: raw[e::BUS_WIDTH],
+            power_class: raw[e::POWER_CLASS],
+            partition_config: PartitionConfig::from_byte(raw[e::PARTITION_CONFIG]),
+            boot_config_protection: raw[e::BOOT_CONFIG_PROT],
+            boot_bus_conditions: BootBusConditions::from_byte(raw[e::BOOT_BUS_CONDITIONS]),
+            boot_size_bytes: parse_boot_size_from_ext_csd(raw),
+            alternate_boot_supported: raw[e::BOOT_INFO] & 0x01 != 0,
+            rpmb_size_bytes: raw[e::RPMB_SIZE_MULT] as u32 * 128 * 1024,
+            write_protect: WriteProtectSettings {
+                boot_power_on_wp_enabled: boot_wp & 0x01 != 0,
+                boot_permanent_wp_enabled: boot_wp & 0x04 != 0,
+                boot_permanent_wp_disabled: boot_wp & 0x10 != 0,
+                boot_power_on_wp_disabled: boot_wp & 0x40 != 0,
+                boot_wp_per_area: boot_wp & 0x80 != 0,
+                boot1_status: BootAreaProtection::from_bits(boot_wp_status),
+                boot2_status: BootAreaProtection::from_bits(boot_wp_status >> 2),
+                user_power_on_wp_enabled: user_wp & 0x01 != 0,
+                user_permanent_wp_enabled: user_wp & 0x04 != 0,
+                user_power_on_wp_disabled: user_wp & 0x08 != 0,
+                user_permanent_wp_disabled: user_wp & 0x10 != 0,
+                csd_permanent_wp_disabled: user_wp & 0x40 != 0,
+                password_protection_disabled: user_wp & 0x80 != 0,
+                wp_group_size,
+            },
+            partitioning: PartitioningInfo {
+                supported: partitioning_support & 0x01 != 0,
+                enhanced_supported: partitioning_support & 0x02 != 0,
+                extended_attributes_supported: partitioning_support & 0x04 != 0,
+                setting_completed: raw[e::PARTITION_SETTING_COMPLETED] & 0x01 != 0,
+                attributes: raw[e::PARTITIONS_ATTRIBUTE],
+                gp_sizes,
+                enhanced_start: le32(e::ENH_START_ADDR),
+                enhanced_size: le24(e::ENH_SIZE_MULT) * wp_group_size,
+                max_enhanced_size: le24(e::MAX_ENH_SIZE_MULT) * wp_group_size,
+            },
+            erase: EraseInfo {
+                high_capacity_groups: raw[e::ERASE_GROUP_DEF] & 0x01 != 0,
+                erase_group_size,
+                erase_timeout_ms,
+                trim_timeout_ms: raw[e::TRIM_MULT] as u32 * 300,
+                secure_erase_timeout_ms: erase_timeout_ms * raw[e::SEC_ERASE_MULT] as u32,
+                secure_trim_timeout_ms: erase_timeout_ms * raw[e::SEC_TRIM_MULT] as u32,
+                secure_erase_supported: sec_feature & 0x01 != 0,
+                secure_bad_block_supported: sec_feature & 0x04 != 0,
+                trim_supported: sec_feature & 0x10 != 0,
+                sanitize_supported: sec_feature & 0x40 != 0,
+                erased_reads_ones: raw[e::ERASED_MEM_CONT] & 0x01 != 0,
+            },
+            cache: CacheInfo {
+                size_bytes: le32(e::CACHE_SIZE) as u64 * 1024,
+                enabled: raw[e::CACHE_CTRL] & 0x01 != 0,
+            },
+            bkops: BkopsInfo {
+                supported: raw[e::BKOPS_SUPPORT] & 0x01 != 0,
+                manual_enabled: raw[e::BKOPS_EN] & 0x01 != 0,
+                auto_enabled: raw[e::BKOPS_EN] & 0x02 != 0,
+                status: raw[e::BKOPS_STATUS] & 0x03,
+            },
+            hpi_supported: raw[e::HPI_FEATURES] & 0x01 != 0,
+            reliable_write_sectors: raw[e::REL_WR_SEC_C],
+            partition_switch_time_ms: ms10(e::PARTITION_SWITCH_TIME),
+            generic_cmd6_time_ms: ms10(e::GENERIC_CMD6_TIME),
+            firmware_version,
+            device_version: u16::from_le_bytes([
+                raw[e::DEVICE_VERSION],
+                raw[e::DEVICE_VERSION + 1],
+            ]),
+            ffu_supported: raw[e::SUPPORTED_MODES] & 0x01 != 0,
+            pre_eol_info: PreEolInfo::from_byte(raw[e::PRE_EOL_INFO]),
+            life_time_est_a: LifeTimeEstimate::from_byte(raw[e::DEVICE_LIFE_TIME_EST_TYP_A]),
+            life_time_est_b: LifeTimeEstimate::from_byte(raw[e::DEVICE_LIFE_TIME_EST_TYP_B]),
+            vendor_health_report,
+        })
+    }
+
+    /// Human readable EXT_CSD revision (e.g. "eMMC 5.1")
+    pub fn version_str(&self) -> &'static str {
+        match self.revision {
+            0 => "MMC 4.0",
+            1 => "MMC 4.1",
+            2 => "MMC 4.2",
+            3 => "MMC 4.3",
+            5 => "eMMC 4.41",
+            6 => "eMMC 4.5",
+            7 => "eMMC 5.0",
+            8 => "eMMC 5.1",
+            _ => "Unknown",
+        }
+    }
+
+    /// Firmware version, as ASCII if printable, hex otherwise
+    pub fn firmware_version_string(&self) -> String {
+        let end = self
+            .firmware_version
+            .iter()
+            .rposition(|&b| b != 0 && b != b' ')
+            .map_or(0, |i| i + 1);
+        let trimmed = &self.firmware_version[..end];
+        if !trimmed.is_empty() && trimmed.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            String::from_utf8_lossy(trimmed).into_owned()
+        } else {
+            self.firmware_version
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect()
+        }
+    }
+}
+
+// ============ Health report ============
+
+/// Overall triage verdict for a used eMMC part
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmmcHealthVerdict {
+    /// Wear indicators look fine
+    Good,
+    /// Noticeably worn, usable but with reduced margin
+    Worn,
+    /// Near or past end of life, do not reball
+    Critical,
+    /// Device does not report health (pre-5.0 or fields undefined)
+    Unknown,
+}
+
+impl EmmcHealthVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmmcHealthVerdict::Good => "GOOD",
+            EmmcHealthVerdict::Worn => "WORN",
+            EmmcHealthVerdict::Critical => "CRITICAL",
+            EmmcHealthVerdict::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// Wear level (percent of rated life used) at which a part is reported as worn
+pub const HEALTH_WORN_PERCENT: u8 = 70;
+
+/// Triage verdict from the JEDEC pre-EOL and life time estimate fields
+///
+/// The encoding is shared by eMMC EXT_CSD and the UFS Health descriptor.
+pub fn health_verdict(
+    pre_eol_info: PreEolInfo,
+    life_time_est_a: LifeTimeEstimate,
+    life_time_est_b: LifeTimeEstimate,
+) -> EmmcHealthVerdict {
+    let worst = [life_time_est_a, life_time_est_b]
+        .iter()
+        .filter_map(|l| l.max_percent())
+        .max();
+
+    if matches!(pre_eol_info, PreEolInfo::Urgent)
+        || matches!(life_time_est_a, LifeTimeEstimate::Exceeded)
+        || matches!(life_time_est_b, LifeTimeEstimate::Exceeded)
+    {
+        EmmcHealthVerdict::Critical
+    } else if matches!(pre_eol_info, PreEolInfo::Warning)
+        || worst.is_some_and(|p| p >= HEALTH_WORN_PERCENT)
+    {
+        EmmcHealthVerdict::Worn
+    } else if worst.is_none() && !matches!(pre_eol_info, PreEolInfo::Normal) {
+        EmmcHealthVerdict::Unknown
+    } else {
+        EmmcHealthVerdict::Good
+    }
+}
+
+/// eMMC health summary derived from EXT_CSD
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmmcHealthReport {
+    pub version: String,
+    pub firmware_version: String,
+    pub capacity_bytes: u64,
+    pub pre_eol_info: PreEolInfo,
+    /// SLC (type A) area wear
+    pub life_time_est_a: LifeTimeEstimate,
+    /// MLC/TLC (type B) area wear
+    pub life_time_est_b: LifeTimeEstimate,
+    /// Vendor health report bytes as hex, empty if all zero
+    pub vendor_health_report: String,
+    pub bkops_status: u8,
+    pub verdict: EmmcHealthVerdict,
+    pub notes: Vec<String>,
+}
+
+impl EmmcHealthReport {
+    pub fn from_ext_csd(ext: &ExtCsd) -> Self {
+        let mut notes = Vec::new();
+        let worst = [ext.life_time_est_a, ext.life_time_est_b]
+            .iter()
+            .filter_map(|l| l.max_percent())
+            .max();
+        let verdict = health_verdict(ext.pre_eol_info, ext.life_time_est_a, ext.life_time_est_b);
+
+        if ext.revision < 7 {
+            notes.push(format!(
+                "{} predates health reporting (eMMC 5.0)",
+                ext.version_str()
+            ));
+        }
+        match ext.pre_eol_info {
+            PreEolInfo::Warning => notes.push("80% of reserved blocks consumed".into()),
+            PreEolInfo::Urgent => notes.push("90% of reserved blocks consumed".into()),
+            _ => {}
+        }
+        if let Some(p) = worst {
+            if p >= HEALTH_WORN_PERCENT {
+                notes.push(format!("Up to {}% of rated erase cycles used", p));
+            }
+        }
+        if ext.bkops.status >= 2 {
+            notes.push("Background operations pending (performance impacted)".into());
+        }
+        if ext.write_protect.boot1_status == BootAreaProtection::Permanent
+            || ext.write_protect.boot2_status == BootAreaProtection::Permanent
+        {
+            notes.push("Boot area permanently write protected".into());
+        }
+        if ext.write_protect.user_permanent_wp_enabled {
+            notes.push("User area permanently write protected".into());
+        }
+
+        let vendor_health_report = if ext.vendor_health_report.iter().all(|&b| b == 0) {
+            String::new()
+        } else {
+            ext.vendor_health_report
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect()
+        };
+
+        Self {
+            version: ext.version_str().to_string(),
+            firmware_version: ext.firmware_version_string(),
+            capacity_bytes: ext.capacity_bytes,
+            pre_eol_info: ext.pre_eol_info,
+            life_time_est_a: ext.life_time_est_a,
+            life_time_est_b: ext.life_time_est_b,
+            vendor_health_report,
+            bkops_status: ext.bkops.status,
+            verdict,
+            notes,
+        }
+    }
+}
+
+impl std::fmt::Display for EmmcHealthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "eMMC Health Report")?;
+        writeln!(f, "  Version:        {}", self.version)?;
+        writeln!(f, "  Firmware:       {}", self.firmware_version)?;
+        writeln!(
+            f,
+            "  Capacity:       {:.2} GB",
+            self.capacity_bytes as f64 / 1_000_000_000.0
+        )?;
+        writeln!(f, "  Pre-EOL:        {}", self.pre_eol_info.as_str())?;
+        writeln!(f, "  Life (SLC/A):   {}", self.life_time_est_a)?;
+        writeln!(f, "  Life (MLC/B):   {}", self.life_time_est_b)?;
+        if !self.vendor_health_report.is_empty() {
+            writeln!(f, "  Vendor report:  {}", self.vendor_health_report)?;
+        }
+        writeln!(f, "  Verdict:        {}", self.verdict.as_str())?;
+        for note in &self.notes {
+            writeln!(f, "  - {}", note)?;
+        }
+        Ok(())
+    }
+}
+
 /// eMMC operation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmmcReadResult {
@@ -688,4 +1498,99 @@ mod tests {
         let capacity = parse_capacity_from_ext_csd(&ext_csd);
         assert_eq!(capacity, 15_269_888 * 512);
     }
+
+    fn sample_ext_csd() -> [u8; 512] {
+        let mut raw = [0u8; 512];
+        raw[ext_csd::EXT_CSD_REV] = 8;
+        raw[ext_csd::SEC_COUNT..ext_csd::SEC_COUNT + 4]
+            .copy_from_slice(&0x00E9_0000u32.to_le_bytes());
+        raw[ext_csd::PARTITION_CONFIG] = 0x48; // boot ack, boot from BOOT1, access user
+        raw[ext_csd::BOOT_BUS_CONDITIONS] = 0x0A; // SDR HS, x8
+        raw[ext_csd::HS_TIMING] = 0x13; // HS400, driver strength 1
+        raw[ext_csd::DEVICE_TYPE] = 0x57;
+        raw[ext_csd::BOOT_SIZE_MULT] = 32;
+        raw[ext_csd::RPMB_SIZE_MULT] = 32;
+        raw[ext_csd::HC_ERASE_GRP_SIZE] = 1;
+        raw[ext_csd::HC_WP_GRP_SIZE] = 16;
+        raw[ext_csd::GP_SIZE_MULT + 3] = 2; // GP2 = 2 WP groups
+        raw[ext_csd::BOOT_WP_STATUS] = 0x02; // BOOT1 permanent
+        raw[ext_csd::SEC_FEATURE_SUPPORT] = 0x55;
+        raw[ext_csd::CACHE_CTRL] = 1;
+        raw[ext_csd::CACHE_SIZE..ext_csd::CACHE_SIZE + 4]
+            .copy_from_slice(&512u32.to_le_bytes());
+        raw[ext_csd::FIRMWARE_VERSION..ext_csd::FIRMWARE_VERSION + 3].copy_from_slice(b"FW1");
+        raw[ext_csd::PRE_EOL_INFO] = 1;
+        raw[ext_csd::DEVICE_LIFE_TIME_EST_TYP_A] = 0x02;
+        raw[ext_csd::DEVICE_LIFE_TIME_EST_TYP_B] = 0x03;
+        raw
+    }
+
+    #[test]
+    fn test_ext_csd_decode() {
+        let ext = ExtCsd::parse(&sample_ext_csd()).unwrap();
+        assert_eq!(ext.version_str(), "eMMC 5.1");
+        assert_eq!(ext.capacity_bytes, 15_269_888 * 512);
+        assert!(ext.partition_config.boot_ack);
+        assert_eq!(ext.partition_config.boot_partition, BootPartitionEnable::Boot1);
+        assert_eq!(ext.partition_config.access, PartitionAccess::User);
+        assert_eq!(ext.boot_bus_conditions.mode, BootMode::SdrHighSpeed);
+        assert_eq!(ext.boot_bus_conditions.bus_width, 8);
+        assert_eq!(ext.hs_timing, HsTiming::Hs400);
+        assert_eq!(ext.driver_strength, 1);
+        assert!(ext.device_type.hs400_1v8 && !ext.device_type.hs400_1v2);
+        assert_eq!(ext.boot_size_bytes, 4 * 1024 * 1024);
+        assert_eq!(ext.rpmb_size_bytes, 4 * 1024 * 1024);
+        assert_eq!(ext.erase.erase_group_size, 512 * 1024);
+        assert_eq!(ext.write_protect.wp_group_size, 8 * 1024 * 1024);
+        assert_eq!(ext.partitioning.gp_sizes, [0, 16 * 1024 * 1024, 0, 0]);
+        assert_eq!(ext.write_protect.boot1_status, BootAreaProtection::Permanent);
+        assert!(ext.erase.trim_supported && ext.erase.sanitize_supported);
+        assert_eq!(ext.cache.size_bytes, 512 * 1024);
+        assert!(ext.cache.enabled);
+        assert_eq!(ext.firmware_version_string(), "FW1");
+        assert_eq!(
+            ext.life_time_est_b,
+            LifeTimeEstimate::Used {
+                min_percent: 20,
+                max_percent: 30
+            }
+        );
+        assert!(ExtCsd::parse(&[0u8; 256]).is_none());
+    }
+
+    #[test]
+    fn test_partition_config_roundtrip() {
+        for value in [0x00u8, 0x48, 0x53, 0x7F, 0x3C] {
+            assert_eq!(PartitionConfig::from_byte(value).to_byte(), value & 0x7F);
+        }
+        for value in [0x00u8, 0x01, 0x06, 0x0A, 0x11] {
+            assert_eq!(BootBusConditions::from_byte(value).to_byte(), value);
+        }
+    }
+
+    #[test]
+    fn test_health_report_verdict() {
+        let mut raw = sample_ext_csd();
+        let report = EmmcHealthReport::from_ext_csd(&ExtCsd::parse(&raw).unwrap());
+        assert_eq!(report.verdict, EmmcHealthVerdict::Good);
+        assert!(report.notes.iter().any(|n| n.contains("permanently")));
+
+        raw[ext_csd::DEVICE_LIFE_TIME_EST_TYP_B] = 0x08;
+        let report = EmmcHealthReport::from_ext_csd(&ExtCsd::parse(&raw).unwrap());
+        assert_eq!(report.verdict, EmmcHealthVerdict::Worn);
+
+        raw[ext_csd::PRE_EOL_INFO] = 3;
+        let report = EmmcHealthReport::from_ext_csd(&ExtCsd::parse(&raw).unwrap());
+        assert_eq!(report.verdict, EmmcHealthVerdict::Critical);
+
+        raw[ext_csd::PRE_EOL_INFO] = 0;
+        raw[ext_csd::DEVICE_LIFE_TIME_EST_TYP_A] = 0;
+        raw[ext_csd::DEVICE_LIFE_TIME_EST_TYP_B] = 0;
+        let report = EmmcHealthReport::from_ext_csd(&ExtCsd::parse(&raw).unwrap());
+        assert_eq!(report.verdict, EmmcHealthVerdict::Unknown);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"verdict\":\"Unknown\""));
+        assert!(report.to_string().contains("Verdict:        UNKNOWN"));
+    }
 }
@@ -0,0 +1,746 @@
+//! eMMC partition management
+//!
+//! High-level access to the eMMC hardware partitions (user area, boot0/boot1,
+//! RPMB and general purpose partitions) on top of the decoded EXT_CSD.
+//!
+//! Partition selection and boot configuration are CMD6 SWITCH writes to
+//! PARTITION_CONFIG and BOOT_BUS_CONDITIONS. Write protection is queried per
+//! write-protect group with CMD31 and temporary protection is cleared with
+//! CMD29. One-time hardware partitioning (GP and enhanced areas) is only
+//! produced after an explicit confirmation string, since it can never be
+//! undone on the device.
+//!
+//! All operations return `Packet`s to send; the manager performs no I/O.
+//! The CMD6/CMD29/CMD31 packets are host-only planning output
+//! ([`Command::is_host_only`]): no programmer firmware handles them yet.
+
+use crate::emmc::{
+    ext_csd, BootAreaProtection, BootBusConditions, BootMode, BootPartitionEnable, ExtCsd,
+    PartitionAccess, PartitionConfig,
+};
+use crate::protocol::{Command, Packet};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// eMMC partition errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EmmcPartitionError {
+    /// Partition does not exist on this device
+    NoSuchPartition(PartitionAccess),
+    /// Boot configuration is locked by BOOT_CONFIG_PROT
+    BootConfigLocked,
+    /// Boot bus setting not allowed
+    InvalidBootBus(String),
+    /// Device does not support hardware partitioning
+    PartitioningUnsupported,
+    /// PARTITION_SETTING_COMPLETED is already set
+    AlreadyPartitioned,
+    /// Size or address not aligned to the write-protect group size
+    Misaligned { value: u64, unit: u64 },
+    /// Enhanced areas exceed MAX_ENH_SIZE_MULT
+    EnhancedTooLarge { requested: u64, max: u64 },
+    /// Partitions do not fit in the device
+    ExceedsCapacity { requested: u64, capacity: u64 },
+    /// Write-protect status response is malformed
+    InvalidWriteProtectStatus,
+    /// Group is power-on protected; only a power cycle clears it
+    PowerCycleRequired { group: u32 },
+    /// Group is permanently protected
+    PermanentlyProtected { group: u32 },
+    /// Irreversible operation attempted without confirmation
+    ConfirmationRequired,
+}
+
+impl fmt::Display for EmmcPartitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmmcPartitionError::NoSuchPartition(p) => write!(f, "Partition {:?} not present", p),
+            EmmcPartitionError::BootConfigLocked => {
+                write!(f, "Boot configuration is write protected")
+            }
+            EmmcPartitionError::InvalidBootBus(msg) => write!(f, "Invalid boot bus: {}", msg),
+            EmmcPartitionError::PartitioningUnsupported => {
+                write!(f, "Device does not support hardware partitioning")
+            }
+            EmmcPartitionError::AlreadyPartitioned => {
+                write!(f, "Hardware partitioning already completed")
+            }
+            EmmcPartitionError::Misaligned { value, unit } => write!(
+                f,
+                "Value {} is not a multiple of the write-protect group size {}",
+                value, unit
+            ),
+            EmmcPartitionError::EnhancedTooLarge { requested, max } => write!(
+                f,
+                "Enhanced area size {} exceeds maximum {}",
+                requested, max
+            ),
+            EmmcPartitionError::ExceedsCapacity {
+                requested,
+                capacity,
+            } => write!(
+                f,
+                "Partition sizes {} exceed capacity {}",
+                requested, capacity
+            ),
+            EmmcPartitionError::InvalidWriteProtectStatus => {
+                write!(f, "Invalid write-protect status response")
+            }
+            EmmcPartitionError::PowerCycleRequired { group } => write!(
+                f,
+                "Write-protect group {} is power-on protected, power cycle required",
+                group
+            ),
+            EmmcPartitionError::PermanentlyProtected { group } => {
+                write!(f, "Write-protect group {} is permanently protected", group)
+            }
+            EmmcPartitionError::ConfirmationRequired => {
+                write!(f, "Hardware partitioning requires explicit confirmation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EmmcPartitionError {}
+
+pub type EmmcPartitionResult<T> = Result<T, EmmcPartitionError>;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Confirmation string required to commit one-time hardware partitioning
+pub const HW_PARTITION_CONFIRMATION: &str = "PARTITION EMMC PERMANENTLY";
+
+/// CMD6 SWITCH access modes
+pub mod switch_access {
+    pub const COMMAND_SET: u8 = 0;
+    pub const SET_BITS: u8 = 1;
+    pub const CLEAR_BITS: u8 = 2;
+    pub const WRITE_BYTE: u8 = 3;
+}
+
+/// BOOT_CONFIG_PROT bits
+pub mod boot_config_prot {
+    pub const PWR_BOOT_CONFIG_PROT: u8 = 0x01;
+    pub const PERM_BOOT_CONFIG_PROT: u8 = 0x10;
+}
+
+/// Number of write-protect groups reported by one CMD31
+pub const WP_GROUPS_PER_QUERY: usize = 32;
+
+// ============================================================================
+// CMD6 SWITCH
+// ============================================================================
+
+/// Build the CMD6 argument for an EXT_CSD access
+pub fn switch_argument(access: u8, index: u8, value: u8) -> u32 {
+    ((access as u32 & 0x03) << 24) | ((index as u32) << 16) | ((value as u32) << 8)
+}
+
+/// Packet that writes one EXT_CSD byte with CMD6
+pub fn switch_write_byte(index: usize, value: u8) -> Packet {
+    let arg = switch_argument(switch_access::WRITE_BYTE, index as u8, value);
+    Packet::new(Command::EmmcSwitch, &arg.to_le_bytes())
+}
+
+// ============================================================================
+// Partitions
+// ============================================================================
+
+/// One hardware partition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmmcPartition {
+    pub access: PartitionAccess,
+    /// Linux style name (user, boot0, boot1, rpmb, gp0..gp3)
+    pub name: String,
+    pub size_bytes: u64,
+    /// Boot area protection state (boot partitions only)
+    pub boot_protection: Option<BootAreaProtection>,
+}
+
+/// Linux style name of a partition
+pub fn partition_name(access: PartitionAccess) -> String {
+    match access {
+        PartitionAccess::User => "user".into(),
+        PartitionAccess::Boot1 => "boot0".into(),
+        PartitionAccess::Boot2 => "boot1".into(),
+        PartitionAccess::Rpmb => "rpmb".into(),
+        PartitionAccess::GeneralPurpose(n) => format!("gp{}", n.saturating_sub(1)),
+    }
+}
+
+/// Write protection type of one group (CMD31)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WriteProtectType {
+    None,
+    Temporary,
+    PowerOn,
+    Permanent,
+}
+
+impl WriteProtectType {
+    pub fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => WriteProtectType::None,
+            1 => WriteProtectType::Temporary,
+            2 => WriteProtectType::PowerOn,
+            _ => WriteProtectType::Permanent,
+        }
+    }
+}
+
+/// Decode the 8-byte CMD31 response; index 0 is the addressed group
+pub fn parse_write_protect_types(
+    data: &[u8],
+) -> EmmcPartitionResult<[WriteProtectType; WP_GROUPS_PER_QUERY]> {
+    if data.len() < 8 {
+        return Err(EmmcPartitionError::InvalidWriteProtectStatus);
+    }
+
+    let bits = u64::from_be_bytes([
+        data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+    ]);
+    let mut types = [WriteProtectType::None; WP_GROUPS_PER_QUERY];
+    for (i, t) in types.iter_mut().enumerate() {
+        *t = WriteProtectType::from_bits((bits >> (i * 2)) as u8);
+    }
+    Ok(types)
+}
+
+/// Enhanced (pseudo-SLC) user data area
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnhancedUserArea {
+    /// Start address in 512-byte sectors
+    pub start_sector: u32,
+    pub size_bytes: u64,
+}
+
+/// Requested one-time hardware partition layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct HardwarePartitionLayout {
+    /// GP1..GP4 sizes in bytes (0 = not created)
+    pub gp_sizes: [u64; 4],
+    /// Mark GP1..GP4 as enhanced
+    pub gp_enhanced: [bool; 4],
+    pub enhanced_user: Option<EnhancedUserArea>,
+}
+
+// ============================================================================
+// Partition Manager
+// ============================================================================
+
+/// eMMC partition manager
+///
+/// Tracks PARTITION_CONFIG and BOOT_BUS_CONDITIONS from the EXT_CSD it was
+/// created with, and updates them as switch packets are built. Re-read the
+/// EXT_CSD after a failed switch.
+#[derive(Debug, Clone)]
+pub struct EmmcPartitionManager {
+    ext_csd: ExtCsd,
+}
+
+impl EmmcPartitionManager {
+    pub fn new(ext_csd: ExtCsd) -> Self {
+        Self { ext_csd }
+    }
+
+    pub fn ext_csd(&self) -> &ExtCsd {
+        &self.ext_csd
+    }
+
+    /// Currently selected partition
+    pub fn current(&self) -> PartitionAccess {
+        self.ext_csd.partition_config.access
+    }
+
+    /// Size of a partition in bytes (0 if not present)
+    pub fn partition_size(&self, access: PartitionAccess) -> u64 {
+        let ext = &self.ext_csd;
+        match access {
+            PartitionAccess::User => ext.capacity_bytes,
+            PartitionAccess::Boot1 | PartitionAccess::Boot2 => ext.boot_size_bytes as u64,
+            PartitionAccess::Rpmb => ext.rpmb_size_bytes as u64,
+            PartitionAccess::GeneralPurpose(n @ 1..=4) => ext.partitioning.gp_sizes[n as usize - 1],
+            PartitionAccess::GeneralPurpose(_) => 0,
+        }
+    }
+
+    /// All partitions present on the device
+    pub fn partitions(&self) -> Vec<EmmcPartition> {
+        let wp = &self.ext_csd.write_protect;
+        [
+            PartitionAccess::User,
+            PartitionAccess::Boot1,
+            PartitionAccess::Boot2,
+            PartitionAccess::Rpmb,
+            PartitionAccess::GeneralPurpose(1),
+            PartitionAccess::GeneralPurpose(2),
+            PartitionAccess::GeneralPurpose(3),
+            PartitionAccess::GeneralPurpose(4),
+        ]
+        .into_iter()
+        .filter(|&access| self.partition_size(access) > 0)
+        .map(|access| EmmcPartition {
+            access,
+            name: partition_name(access),
+            size_bytes: self.partition_size(access),
+            boot_protection: match access {
+                PartitionAccess::Boot1 => Some(wp.boot1_status),
+                PartitionAccess::Boot2 => Some(wp.boot2_status),
+                _ => None,
+            },
+        })
+        .collect()
+    }
+
+    /// Switch data access to another partition
+    pub fn select(&mut self, access: PartitionAccess) -> EmmcPartitionResult<Packet> {
+        if self.partition_size(access) == 0 {
+            return Err(EmmcPartitionError::NoSuchPartition(access));
+        }
+
+        let config = PartitionConfig {
+            access,
+            ..self.ext_csd.partition_config
+        };
+        self.ext_csd.partition_config = config;
+        Ok(switch_write_byte(
+            ext_csd::PARTITION_CONFIG,
+            config.to_byte(),
+        ))
+    }
+
+    /// Check that a boot partition can be written
+    pub fn check_boot_writable(&self, access: PartitionAccess) -> EmmcPartitionResult<()> {
+        let status = match access {
+            PartitionAccess::Boot1 => self.ext_csd.write_protect.boot1_status,
+            PartitionAccess::Boot2 => self.ext_csd.write_protect.boot2_status,
+            _ => return Ok(()),
+        };
+        let group = if access == PartitionAccess::Boot1 {
+            0
+        } else {
+            1
+        };
+        match status {
+            BootAreaProtection::PowerOn => Err(EmmcPartitionError::PowerCycleRequired { group }),
+            BootAreaProtection::Permanent | BootAreaProtection::Reserved => {
+                Err(EmmcPartitionError::PermanentlyProtected { group })
+            }
+            BootAreaProtection::None => Ok(()),
+        }
+    }
+
+    fn check_boot_config_unlocked(&self) -> EmmcPartitionResult<()> {
+        let prot = self.ext_csd.boot_config_protection;
+        if prot & (boot_config_prot::PWR_BOOT_CONFIG_PROT | boot_config_prot::PERM_BOOT_CONFIG_PROT)
+            != 0
+        {
+            return Err(EmmcPartitionError::BootConfigLocked);
+        }
+        Ok(())
+    }
+
+    /// Select the partition the device boots from and the boot acknowledge
+    pub fn configure_boot(
+        &mut self,
+        boot_partition: BootPartitionEnable,
+        boot_ack: bool,
+    ) -> EmmcPartitionResult<Packet> {
+        self.check_boot_config_unlocked()?;
+        if let BootPartitionEnable::Boot1 | BootPartitionEnable::Boot2 = boot_partition {
+            if self.ext_csd.boot_size_bytes == 0 {
+                return Err(EmmcPartitionError::NoSuchPartition(
+                    if boot_partition == BootPartitionEnable::Boot1 {
+                        PartitionAccess::Boot1
+                    } else {
+                        PartitionAccess::Boot2
+                    },
+                ));
+            }
+        }
+
+        let config = PartitionConfig {
+            boot_ack,
+            boot_partition,
+            ..self.ext_csd.partition_config
+        };
+        self.ext_csd.partition_config = config;
+        Ok(switch_write_byte(
+            ext_csd::PARTITION_CONFIG,
+            config.to_byte(),
+        ))
+    }
+
+    /// Configure boot bus width and timing
+    pub fn configure_boot_bus(
+        &mut self,
+        conditions: BootBusConditions,
+    ) -> EmmcPartitionResult<Packet> {
+        self.check_boot_config_unlocked()?;
+        if !matches!(conditions.bus_width, 1 | 4 | 8) {
+            return Err(EmmcPartitionError::InvalidBootBus(format!(
+                "unsupported width x{}",
+                conditions.bus_width
+            )));
+        }
+        match conditions.mode {
+            BootMode::Reserved => {
+                return Err(EmmcPartitionError::InvalidBootBus("reserved mode".into()))
+            }
+            BootMode::Ddr if conditions.bus_width == 1 => {
+                return Err(EmmcPartitionError::InvalidBootBus(
+                    "DDR boot requires x4 or x8".into(),
+                ))
+            }
+            BootMode::Ddr if !self.ext_csd.device_type.ddr52_1v8_3v => {
+                return Err(EmmcPartitionError::InvalidBootBus(
+                    "device does not support DDR".into(),
+                ))
+            }
+            BootMode::SdrHighSpeed if !self.ext_csd.device_type.hs52 => {
+                return Err(EmmcPartitionError::InvalidBootBus(
+                    "device does not support high speed".into(),
+                ))
+            }
+            _ => {}
+        }
+
+        self.ext_csd.boot_bus_conditions = conditions;
+        Ok(switch_write_byte(
+            ext_csd::BOOT_BUS_CONDITIONS,
+            conditions.to_byte(),
+        ))
+    }
+
+    /// Write-protect group size in 512-byte sectors
+    pub fn wp_group_sectors(&self) -> u32 {
+        (self.ext_csd.write_protect.wp_group_size / 512) as u32
+    }
+
+    /// CMD31 query for the 32 write-protect groups starting at `sector`
+    pub fn query_write_protect(&self, sector: u32) -> Packet {
+        Packet::new(Command::EmmcSendWpType, &sector.to_le_bytes())
+    }
+
+    /// CMD29 packets clearing temporary protection on the queried groups
+    ///
+    /// `types` is the decoded CMD31 response for the query at `start_sector`.
+    /// Power-on and permanent protection cannot be cleared by command.
+    pub fn clear_temporary_write_protect(
+        &self,
+        start_sector: u32,
+        types: &[WriteProtectType],
+    ) -> EmmcPartitionResult<Vec<Packet>> {
+        let group_sectors = self.wp_group_sectors().max(1);
+        let first_group = start_sector / group_sectors;
+        let mut packets = Vec::new();
+
+        for (i, t) in types.iter().enumerate() {
+            let group = first_group + i as u32;
+            match t {
+                WriteProtectType::None => {}
+                WriteProtectType::Temporary => {
+                    let sector = group * group_sectors;
+                    packets.push(Packet::new(Command::EmmcClearWp, &sector.to_le_bytes()));
+                }
+                WriteProtectType::PowerOn => {
+                    return Err(EmmcPartitionError::PowerCycleRequired { group })
+                }
+                WriteProtectType::Permanent => {
+                    return Err(EmmcPartitionError::PermanentlyProtected { group })
+                }
+            }
+        }
+        Ok(packets)
+    }
+
+    /// Build the one-time hardware partitioning sequence
+    ///
+    /// Writes ERASE_GROUP_DEF, the enhanced user area, GP sizes and
+    /// attributes, then sets PARTITION_SETTING_COMPLETED. The new layout
+    /// takes effect after a power cycle and can never be changed again.
+    pub fn hardware_partition(
+        &self,
+        layout: &HardwarePartitionLayout,
+        confirmation: &str,
+    ) -> EmmcPartitionResult<Vec<Packet>> {
+        if confirmation != HW_PARTITION_CONFIRMATION {
+            return Err(EmmcPartitionError::ConfirmationRequired);
+        }
+
+        let part = &self.ext_csd.partitioning;
+        let unit = self.ext_csd.write_protect.wp_group_size;
+        if !part.supported || unit == 0 {
+            return Err(EmmcPartitionError::PartitioningUnsupported);
+        }
+        if part.setting_completed {
+            return Err(EmmcPartitionError::AlreadyPartitioned);
+        }
+
+        let check_aligned = |value: u64| {
+            if value % unit != 0 {
+                Err(EmmcPartitionError::Misaligned { value, unit })
+            } else {
+                Ok(())
+            }
+        };
+
+        let mut attributes = 0u8;
+        let mut enhanced_total = 0u64;
+        for (i, &size) in layout.gp_sizes.iter().enumerate() {
+            check_aligned(size)?;
+            if layout.gp_enhanced[i] && size > 0 {
+                attributes |= 1 << (i + 1);
+                enhanced_total += size;
+            }
+        }
+        if let Some(enh) = layout.enhanced_user {
+            check_aligned(enh.start_sector as u64 * 512)?;
+            check_aligned(enh.size_bytes)?;
+            if enh.size_bytes > 0 {
+                attributes |= 0x01;
+                enhanced_total += enh.size_bytes;
+            }
+        }
+        if attributes != 0 && !part.enhanced_supported {
+            return Err(EmmcPartitionError::PartitioningUnsupported);
+        }
+        if enhanced_total > part.max_enhanced_size {
+            return Err(EmmcPartitionError::EnhancedTooLarge {
+                requested: enhanced_total,
+                max: part.max_enhanced_size,
+            });
+        }
+
+        let gp_total: u64 = layout.gp_sizes.iter().sum();
+        if gp_total >= self.ext_csd.capacity_bytes {
+            return Err(EmmcPartitionError::ExceedsCapacity {
+                requested: gp_total,
+                capacity: self.ext_csd.capacity_bytes,
+            });
+        }
+
+        let mut packets = vec![switch_write_byte(ext_csd::ERASE_GROUP_DEF, 1)];
+
+        if let Some(enh) = layout.enhanced_user {
+            for (i, b) in enh.start_sector.to_le_bytes().iter().enumerate() {
+                packets.push(switch_write_byte(ext_csd::ENH_START_ADDR + i, *b));
+            }
+            let mult = (enh.size_bytes / unit) as u32;
+            for (i, b) in mult.to_le_bytes()[..3].iter().enumerate() {
+                packets.push(switch_write_byte(ext_csd::ENH_SIZE_MULT + i, *b));
+            }
+        }
+
+        for (gp, &size) in layout.gp_sizes.iter().enumerate() {
+            let mult = (size / unit) as u32;
+            for (i, b) in mult.to_le_bytes()[..3].iter().enumerate() {
+                packets.push(switch_write_byte(ext_csd::GP_SIZE_MULT + gp * 3 + i, *b));
+            }
+        }
+
+        packets.push(switch_write_byte(ext_csd::PARTITIONS_ATTRIBUTE, attributes));
+        packets.push(switch_write_byte(ext_csd::PARTITION_SETTING_COMPLETED, 1));
+        Ok(packets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_ext_csd() -> ExtCsd {
+        let mut raw = [0u8; ext_csd::SIZE];
+        raw[ext_csd::EXT_CSD_REV] = 8;
+        raw[ext_csd::SEC_COUNT..ext_csd::SEC_COUNT + 4]
+            .copy_from_slice(&0x01D5_A000u32.to_le_bytes());
+        raw[ext_csd::DEVICE_TYPE] = 0x57;
+        raw[ext_csd::BOOT_SIZE_MULT] = 32;
+        raw[ext_csd::RPMB_SIZE_MULT] = 32;
+        raw[ext_csd::HC_ERASE_GRP_SIZE] = 1;
+        raw[ext_csd::HC_WP_GRP_SIZE] = 16;
+        raw[ext_csd::PARTITIONING_SUPPORT] = 0x07;
+        raw[ext_csd::MAX_ENH_SIZE_MULT] = 64; // 512MB
+        ExtCsd::parse(&raw).unwrap()
+    }
+
+    fn switch_arg(packet: &Packet) -> (u8, u8, u8) {
+        let arg = u32::from_le_bytes([
+            packet.args[0],
+            packet.args[1],
+            packet.args[2],
+            packet.args[3],
+        ]);
+        (
+            (arg >> 24) as u8 & 0x03,
+            (arg >> 16) as u8,
+            (arg >> 8) as u8,
+        )
+    }
+
+    #[test]
+    fn test_partitions_and_select() {
+        let mut mgr = EmmcPartitionManager::new(test_ext_csd());
+        let names: Vec<_> = mgr.partitions().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["user", "boot0", "boot1", "rpmb"]);
+
+        let packet = mgr.select(PartitionAccess::Boot2).unwrap();
+        assert_eq!(packet.cmd, Command::EmmcSwitch);
+        assert_eq!(
+            switch_arg(&packet),
+            (
+                switch_access::WRITE_BYTE,
+                ext_csd::PARTITION_CONFIG as u8,
+                0x02
+            )
+        );
+        assert_eq!(mgr.current(), PartitionAccess::Boot2);
+
+        assert_eq!(
+            mgr.select(PartitionAccess::GeneralPurpose(1)).unwrap_err(),
+            EmmcPartitionError::NoSuchPartition(PartitionAccess::GeneralPurpose(1))
+        );
+    }
+
+    #[test]
+    fn test_boot_configuration() {
+        let mut mgr = EmmcPartitionManager::new(test_ext_csd());
+        mgr.select(PartitionAccess::Boot1).unwrap();
+        let packet = mgr
+            .configure_boot(BootPartitionEnable::Boot2, true)
+            .unwrap();
+        // Access bits are preserved
+        assert_eq!(switch_arg(&packet).2, 0x40 | (2 << 3) | 0x01);
+
+        let bus = BootBusConditions {
+            mode: BootMode::Ddr,
+            retain_bus_width: false,
+            bus_width: 1,
+        };
+        assert!(matches!(
+            mgr.configure_boot_bus(bus),
+            Err(EmmcPartitionError::InvalidBootBus(_))
+        ));
+        let packet = mgr
+            .configure_boot_bus(BootBusConditions {
+                bus_width: 8,
+                ..bus
+            })
+            .unwrap();
+        assert_eq!(
+            switch_arg(&packet),
+            (
+                switch_access::WRITE_BYTE,
+                ext_csd::BOOT_BUS_CONDITIONS as u8,
+                0x12
+            )
+        );
+
+        let mut ext = test_ext_csd();
+        ext.boot_config_protection = boot_config_prot::PWR_BOOT_CONFIG_PROT;
+        let mut locked = EmmcPartitionManager::new(ext);
+        assert_eq!(
+            locked
+                .configure_boot(BootPartitionEnable::Boot1, false)
+                .unwrap_err(),
+            EmmcPartitionError::BootConfigLocked
+        );
+    }
+
+    #[test]
+    fn test_write_protect_query_and_clear() {
+        let mgr = EmmcPartitionManager::new(test_ext_csd());
+        assert_eq!(mgr.wp_group_sectors(), 16384);
+
+        // Group 0 temporary, group 2 temporary, group 31 power-on
+        let bits: u64 = 0b01 | (0b01 << 4) | (0b10 << 62);
+        let types = parse_write_protect_types(&bits.to_be_bytes()).unwrap();
+        assert_eq!(types[0], WriteProtectType::Temporary);
+        assert_eq!(types[1], WriteProtectType::None);
+        assert_eq!(types[31], WriteProtectType::PowerOn);
+        assert_eq!(
+            mgr.clear_temporary_write_protect(0, &types).unwrap_err(),
+            EmmcPartitionError::PowerCycleRequired { group: 31 }
+        );
+
+        let packets = mgr
+            .clear_temporary_write_protect(16384, &types[..31])
+            .unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].cmd, Command::EmmcClearWp);
+        assert_eq!(packets[1].args[..4], (3 * 16384u32).to_le_bytes());
+
+        assert!(parse_write_protect_types(&[0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn test_hardware_partitioning() {
+        let mgr = EmmcPartitionManager::new(test_ext_csd());
+        let unit = 8 * 1024 * 1024;
+        let layout = HardwarePartitionLayout {
+            gp_sizes: [4 * unit, 0, 0, 0],
+            gp_enhanced: [true, false, false, false],
+            enhanced_user: None,
+        };
+
+        assert_eq!(
+            mgr.hardware_partition(&layout, "yes").unwrap_err(),
+            EmmcPartitionError::ConfirmationRequired
+        );
+
+        let packets = mgr
+            .hardware_partition(&layout, HW_PARTITION_CONFIRMATION)
+            .unwrap();
+        assert_eq!(packets.len(), 1 + 12 + 2);
+        assert_eq!(switch_arg(&packets[0]).1, ext_csd::ERASE_GROUP_DEF as u8);
+        assert_eq!(
+            switch_arg(&packets[1]),
+            (switch_access::WRITE_BYTE, ext_csd::GP_SIZE_MULT as u8, 4)
+        );
+        assert_eq!(
+            switch_arg(&packets[13]),
+            (
+                switch_access::WRITE_BYTE,
+                ext_csd::PARTITIONS_ATTRIBUTE as u8,
+                0x02
+            )
+        );
+        assert_eq!(
+            switch_arg(packets.last().unwrap()).1,
+            ext_csd::PARTITION_SETTING_COMPLETED as u8
+        );
+
+        let misaligned = HardwarePartitionLayout {
+            gp_sizes: [unit + 512, 0, 0, 0],
+            ..layout
+        };
+        assert!(matches!(
+            mgr.hardware_partition(&misaligned, HW_PARTITION_CONFIRMATION),
+            Err(EmmcPartitionError::Misaligned { .. })
+        ));
+
+        let too_large = HardwarePartitionLayout {
+            gp_sizes: [128 * unit, 0, 0, 0],
+            ..layout
+        };
+        assert!(matches!(
+            mgr.hardware_partition(&too_large, HW_PARTITION_CONFIRMATION),
+            Err(EmmcPartitionError::EnhancedTooLarge { .. })
+        ));
+
+        let mut ext = test_ext_csd();
+        ext.partitioning.setting_completed = true;
+        assert_eq!(
+            EmmcPartitionManager::new(ext)
+                .hardware_partition(&layout, HW_PARTITION_CONFIRMATION)
+                .unwrap_err(),
+            EmmcPartitionError::AlreadyPartitioned
+        );
+    }
+}
@@ -0,0 +1,869 @@
+//! Managed erase for eMMC and UFS
+//!
+//! Plans the full eMMC erase family (ERASE, TRIM, DISCARD, SECURE ERASE,
+//! SECURE TRIM and SANITIZE) and the UFS equivalents (UNMAP, FORMAT UNIT and
+//! purge via fPurgeEnable) as a list of steps, tracks progress while the
+//! steps run, and verifies that the range reads back as erased.
+//!
+//! eMMC erase and secure erase operate on whole erase groups, so ranges must
+//! be aligned to `EmmcChipInfo::erase_group_size`. Support for the optional
+//! methods is taken from EXT_CSD SEC_FEATURE_SUPPORT.
+
+use crate::emmc::{ext_csd, EmmcChipInfo, ExtCsd};
+use crate::emmc_partition::switch_write_byte;
+use crate::protocol::{Command, Packet};
+use crate::ufs::{attributes, flags, purge_status, ScsiCdbBuilder};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Erase errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EraseError {
+    /// Method not supported by this device
+    Unsupported(EraseMethod),
+    /// Range is empty
+    EmptyRange,
+    /// Range extends past the end of the device
+    OutOfRange {
+        start: u64,
+        count: u64,
+        capacity: u64,
+    },
+    /// Range is not aligned to the erase group
+    Misaligned {
+        start: u64,
+        count: u64,
+        alignment: u64,
+    },
+    /// Block did not read back as erased
+    VerifyFailed {
+        block: u64,
+        offset: usize,
+        expected: u8,
+        actual: u8,
+    },
+    /// UFS purge reported a failure (bPurgeStatus)
+    PurgeFailed(u8),
+}
+
+impl fmt::Display for EraseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EraseError::Unsupported(method) => {
+                write!(f, "{} is not supported by this device", method.as_str())
+            }
+            EraseError::EmptyRange => write!(f, "Erase range is empty"),
+            EraseError::OutOfRange {
+                start,
+                count,
+                capacity,
+            } => write!(
+                f,
+                "Erase range {}+{} exceeds capacity of {} blocks",
+                start, count, capacity
+            ),
+            EraseError::Misaligned {
+                start,
+                count,
+                alignment,
+            } => write!(
+                f,
+                "Erase range {}+{} is not aligned to {} blocks",
+                start, count, alignment
+            ),
+            EraseError::VerifyFailed {
+                block,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Block {} offset {} reads 0x{:02X}, expected 0x{:02X}",
+                block, offset, actual, expected
+            ),
+            EraseError::PurgeFailed(status) => write!(f, "Purge failed (status 0x{:02X})", status),
+        }
+    }
+}
+
+impl std::error::Error for EraseError {}
+
+pub type EraseResult<T> = Result<T, EraseError>;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// CMD38 arguments
+pub mod cmd38_args {
+    pub const ERASE: u32 = 0x0000_0000;
+    pub const TRIM: u32 = 0x0000_0001;
+    pub const DISCARD: u32 = 0x0000_0003;
+    pub const SECURE_ERASE: u32 = 0x8000_0000;
+    pub const SECURE_TRIM_STEP1: u32 = 0x8000_0001;
+    pub const SECURE_TRIM_STEP2: u32 = 0x8000_8000;
+}
+
+/// Erase groups covered by one eMMC erase step
+pub const EMMC_GROUPS_PER_STEP: u64 = 64;
+
+/// Blocks covered by one UFS UNMAP step
+pub const UFS_BLOCKS_PER_STEP: u64 = 0x10000;
+
+/// Timeout used when the device does not report one
+pub const DEFAULT_ERASE_TIMEOUT_MS: u32 = 60_000;
+
+// ============================================================================
+// Erase Methods
+// ============================================================================
+
+/// Erase method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EraseMethod {
+    /// eMMC ERASE (erase group granularity)
+    Erase,
+    /// eMMC TRIM (write block granularity)
+    Trim,
+    /// eMMC DISCARD (content indeterminate afterwards)
+    Discard,
+    /// eMMC SECURE ERASE
+    SecureErase,
+    /// eMMC SECURE TRIM (two pass)
+    SecureTrim,
+    /// eMMC ERASE followed by SANITIZE of all unmapped memory
+    Sanitize,
+    /// UFS UNMAP
+    Unmap,
+    /// UFS FORMAT UNIT (whole logical unit)
+    FormatUnit,
+    /// UFS UNMAP followed by a device purge
+    Purge,
+}
+
+impl EraseMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EraseMethod::Erase => "ERASE",
+            EraseMethod::Trim => "TRIM",
+            EraseMethod::Discard => "DISCARD",
+            EraseMethod::SecureErase => "SECURE ERASE",
+            EraseMethod::SecureTrim => "SECURE TRIM",
+            EraseMethod::Sanitize => "SANITIZE",
+            EraseMethod::Unmap => "UNMAP",
+            EraseMethod::FormatUnit => "FORMAT UNIT",
+            EraseMethod::Purge => "PURGE",
+        }
+    }
+
+    /// Whether this method is an eMMC operation
+    pub fn is_emmc(&self) -> bool {
+        !self.is_ufs()
+    }
+
+    /// Whether this method is a UFS operation
+    pub fn is_ufs(&self) -> bool {
+        matches!(
+            self,
+            EraseMethod::Unmap | EraseMethod::FormatUnit | EraseMethod::Purge
+        )
+    }
+
+    /// Whether the range must be aligned to whole erase groups
+    pub fn needs_group_alignment(&self) -> bool {
+        matches!(
+            self,
+            EraseMethod::Erase | EraseMethod::SecureErase | EraseMethod::Sanitize
+        )
+    }
+}
+
+/// One operation within an erase step
+#[derive(Debug, Clone)]
+pub enum EraseOp {
+    /// eMMC protocol packet
+    Emmc(Packet),
+    /// UFS SCSI command with optional data-out payload
+    Scsi { cdb: Vec<u8>, data_out: Vec<u8> },
+    /// UFS QUERY SET FLAG
+    UfsSetFlag { idn: u8 },
+    /// UFS QUERY READ ATTRIBUTE, repeated until `check_purge_status` reports done
+    UfsPollAttribute { idn: u8 },
+}
+
+/// A unit of erase work reported as one progress increment
+#[derive(Debug, Clone)]
+pub struct EraseStep {
+    /// First block covered by this step
+    pub start: u64,
+    /// Blocks covered by this step
+    pub count: u64,
+    pub ops: Vec<EraseOp>,
+    /// Time to allow for the step to finish
+    pub timeout_ms: u32,
+}
+
+/// Planned erase operation
+#[derive(Debug, Clone)]
+pub struct ErasePlan {
+    pub method: EraseMethod,
+    pub start: u64,
+    pub count: u64,
+    /// Bytes per block (sector for eMMC, logical block for UFS)
+    pub block_size: u32,
+    pub steps: Vec<EraseStep>,
+    /// Byte value erased blocks read back as, `None` if indeterminate
+    pub expected_pattern: Option<u8>,
+}
+
+impl ErasePlan {
+    /// Ranges to read back for verification, at most `max_blocks` each
+    pub fn verify_ranges(&self, max_blocks: u64) -> Vec<(u64, u64)> {
+        let max_blocks = max_blocks.max(1);
+        let mut ranges = Vec::new();
+        let end = self.start + self.count;
+        let mut block = self.start;
+        while block < end {
+            let count = max_blocks.min(end - block);
+            ranges.push((block, count));
+            block += count;
+        }
+        ranges
+    }
+}
+
+fn check_range(start: u64, count: u64, capacity: u64) -> EraseResult<()> {
+    if count == 0 {
+        return Err(EraseError::EmptyRange);
+    }
+    if start.checked_add(count).map_or(true, |end| end > capacity) {
+        return Err(EraseError::OutOfRange {
+            start,
+            count,
+            capacity,
+        });
+    }
+    Ok(())
+}
+
+fn div_ceil(value: u64, divisor: u64) -> u64 {
+    (value + divisor - 1) / divisor
+}
+
+/// Expand a range outwards to whole erase groups
+pub fn expand_to_groups(start: u64, count: u64, group: u64) -> (u64, u64) {
+    let group = group.max(1);
+    let aligned_start = start - start % group;
+    let end = start + count;
+    let aligned_end = div_ceil(end, group) * group;
+    (aligned_start, aligned_end - aligned_start)
+}
+
+// ============================================================================
+// eMMC
+// ============================================================================
+
+/// Erase methods available on an eMMC device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct EmmcEraseCapabilities {
+    pub trim: bool,
+    pub discard: bool,
+    pub secure_erase: bool,
+    pub secure_trim: bool,
+    pub sanitize: bool,
+}
+
+impl EmmcEraseCapabilities {
+    pub fn from_ext_csd(ext: &ExtCsd) -> Self {
+        let erase = &ext.erase;
+        Self {
+            trim: erase.trim_supported,
+            // DISCARD was added in eMMC 4.5
+            discard: erase.trim_supported && ext.revision >= 6,
+            secure_erase: erase.secure_erase_supported,
+            secure_trim: erase.secure_erase_supported && erase.trim_supported,
+            sanitize: erase.sanitize_supported,
+        }
+    }
+
+    pub fn supports(&self, method: EraseMethod) -> bool {
+        match method {
+            EraseMethod::Erase => true,
+            EraseMethod::Trim => self.trim,
+            EraseMethod::Discard => self.discard,
+            EraseMethod::SecureErase => self.secure_erase,
+            EraseMethod::SecureTrim => self.secure_trim,
+            EraseMethod::Sanitize => self.sanitize,
+            _ => false,
+        }
+    }
+}
+
+/// eMMC erase planner
+#[derive(Debug, Clone)]
+pub struct EmmcEraser {
+    /// Erase group size in sectors
+    erase_group_size: u64,
+    total_sectors: u64,
+    capabilities: EmmcEraseCapabilities,
+    erased_pattern: u8,
+    erase_timeout_ms: u32,
+    trim_timeout_ms: u32,
+    secure_erase_timeout_ms: u32,
+    secure_trim_timeout_ms: u32,
+}
+
+impl EmmcEraser {
+    pub fn new(chip: &EmmcChipInfo, ext: &ExtCsd) -> Self {
+        let or_default = |ms: u32| {
+            if ms == 0 {
+                DEFAULT_ERASE_TIMEOUT_MS
+            } else {
+                ms
+            }
+        };
+        Self {
+            erase_group_size: chip.erase_group_size.max(1) as u64,
+            total_sectors: ext.sector_count as u64,
+            capabilities: EmmcEraseCapabilities::from_ext_csd(ext),
+            erased_pattern: if ext.erase.erased_reads_ones {
+                0xFF
+            } else {
+                0x00
+            },
+            erase_timeout_ms: or_default(ext.erase.erase_timeout_ms),
+            trim_timeout_ms: or_default(ext.erase.trim_timeout_ms),
+            secure_erase_timeout_ms: or_default(ext.erase.secure_erase_timeout_ms),
+            secure_trim_timeout_ms: or_default(ext.erase.secure_trim_timeout_ms),
+        }
+    }
+
+    pub fn capabilities(&self) -> &EmmcEraseCapabilities {
+        &self.capabilities
+    }
+
+    pub fn erase_group_size(&self) -> u64 {
+        self.erase_group_size
+    }
+
+    fn erase_packet(start: u64, end: u64, arg: u32) -> Packet {
+        let mut args = [0u8; 12];
+        args[0..4].copy_from_slice(&(start as u32).to_le_bytes());
+        args[4..8].copy_from_slice(&(end as u32).to_le_bytes());
+        args[8..12].copy_from_slice(&arg.to_le_bytes());
+        Packet::new(Command::EmmcErase, &args)
+    }
+
+    /// Plan an erase of `count` sectors starting at `start`
+    pub fn plan(&self, method: EraseMethod, start: u64, count: u64) -> EraseResult<ErasePlan> {
+        if !self.capabilities.supports(method) {
+            return Err(EraseError::Unsupported(method));
+        }
+        check_range(start, count, self.total_sectors)?;
+
+        let group = self.erase_group_size;
+        if method.needs_group_alignment() && (start % group != 0 || count % group != 0) {
+            return Err(EraseError::Misaligned {
+                start,
+                count,
+                alignment: group,
+            });
+        }
+
+        let (arg, per_group_ms) = match method {
+            EraseMethod::Trim => (cmd38_args::TRIM, self.trim_timeout_ms),
+            EraseMethod::Discard => (cmd38_args::DISCARD, self.trim_timeout_ms),
+            EraseMethod::SecureErase => (cmd38_args::SECURE_ERASE, self.secure_erase_timeout_ms),
+            EraseMethod::SecureTrim => (cmd38_args::SECURE_TRIM_STEP1, self.secure_trim_timeout_ms),
+            _ => (cmd38_args::ERASE, self.erase_timeout_ms),
+        };
+
+        let step_sectors = group * EMMC_GROUPS_PER_STEP;
+        let mut steps = Vec::new();
+        let end = start + count;
+        let mut sector = start;
+        while sector < end {
+            let n = step_sectors.min(end - sector);
+            let groups = div_ceil(n, group) as u32;
+            steps.push(EraseStep {
+                start: sector,
+                count: n,
+                ops: vec![EraseOp::Emmc(Self::erase_packet(
+                    sector,
+                    sector + n - 1,
+                    arg,
+                ))],
+                timeout_ms: per_group_ms.saturating_mul(groups),
+            });
+            sector += n;
+        }
+
+        match method {
+            EraseMethod::SecureTrim => steps.push(EraseStep {
+                start,
+                count: 0,
+                ops: vec![EraseOp::Emmc(Self::erase_packet(
+                    start,
+                    end - 1,
+                    cmd38_args::SECURE_TRIM_STEP2,
+                ))],
+                timeout_ms: self
+                    .secure_trim_timeout_ms
+                    .saturating_mul(div_ceil(count, group) as u32),
+            }),
+            EraseMethod::Sanitize => steps.push(EraseStep {
+                start,
+                count: 0,
+                ops: vec![EraseOp::Emmc(switch_write_byte(ext_csd::SANITIZE_START, 1))],
+                // SANITIZE has no reported timeout; allow for a full device erase
+                timeout_ms: self
+                    .erase_timeout_ms
+                    .saturating_mul(div_ceil(self.total_sectors, group) as u32),
+            }),
+            _ => {}
+        }
+
+        Ok(ErasePlan {
+            method,
+            start,
+            count,
+            block_size: 512,
+            steps,
+            expected_pattern: match method {
+                EraseMethod::Discard => None,
+                _ => Some(self.erased_pattern),
+            },
+        })
+    }
+}
+
+// ============================================================================
+// UFS
+// ============================================================================
+
+/// UFS erase planner for one logical unit
+#[derive(Debug, Clone)]
+pub struct UfsEraser {
+    block_size: u32,
+    total_blocks: u64,
+}
+
+impl UfsEraser {
+    pub fn new(block_size: u32, total_blocks: u64) -> Self {
+        Self {
+            block_size,
+            total_blocks,
+        }
+    }
+
+    /// Plan an erase of `count` logical blocks starting at `lba`
+    ///
+    /// FORMAT UNIT always covers the whole logical unit.
+    pub fn plan(&self, method: EraseMethod, lba: u64, count: u64) -> EraseResult<ErasePlan> {
+        if !method.is_ufs() {
+            return Err(EraseError::Unsupported(method));
+        }
+
+        let (start, count) = if method == EraseMethod::FormatUnit {
+            (0, self.total_blocks)
+        } else {
+            (lba, count)
+        };
+        check_range(start, count, self.total_blocks)?;
+
+        let mut steps = Vec::new();
+        if method == EraseMethod::FormatUnit {
+            steps.push(EraseStep {
+                start,
+                count,
+                ops: vec![EraseOp::Scsi {
+                    cdb: ScsiCdbBuilder::build_format_unit().to_vec(),
+                    data_out: Vec::new(),
+                }],
+                timeout_ms: DEFAULT_ERASE_TIMEOUT_MS.saturating_mul(10),
+            });
+        } else {
+            let end = start + count;
+            let mut block = start;
+            while block < end {
+                let n = UFS_BLOCKS_PER_STEP.min(end - block);
+                let data_out = ScsiCdbBuilder::build_unmap_parameter_list(&[(block, n as u32)]);
+                steps.push(EraseStep {
+                    start: block,
+                    count: n,
+                    ops: vec![EraseOp::Scsi {
+                        cdb: ScsiCdbBuilder::build_unmap(data_out.len() as u16).to_vec(),
+                        data_out,
+                    }],
+                    timeout_ms: DEFAULT_ERASE_TIMEOUT_MS,
+                });
+                block += n;
+            }
+        }
+
+        if method == EraseMethod::Purge {
+            steps.push(EraseStep {
+                start,
+                count: 0,
+                ops: vec![
+                    EraseOp::UfsSetFlag {
+                        idn: flags::PURGE_ENABLE,
+                    },
+                    EraseOp::UfsPollAttribute {
+                        idn: attributes::PURGE_STATUS,
+                    },
+                ],
+                timeout_ms: DEFAULT_ERASE_TIMEOUT_MS.saturating_mul(10),
+            });
+        }
+
+        Ok(ErasePlan {
+            method,
+            start,
+            count,
+            block_size: self.block_size,
+            steps,
+            // Unmapped logical blocks read back as zeros
+            expected_pattern: Some(0x00),
+        })
+    }
+}
+
+/// Interpret bPurgeStatus: `Ok(true)` when finished, `Ok(false)` while running
+pub fn check_purge_status(status: u8) -> EraseResult<bool> {
+    match status {
+        purge_status::IN_PROGRESS => Ok(false),
+        purge_status::IDLE | purge_status::COMPLETED => Ok(true),
+        other => Err(EraseError::PurgeFailed(other)),
+    }
+}
+
+// ============================================================================
+// Progress and Verification
+// ============================================================================
+
+/// Erase phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErasePhase {
+    Erasing,
+    Verifying,
+    Complete,
+}
+
+/// Erase progress information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EraseProgress {
+    pub method: EraseMethod,
+    pub phase: ErasePhase,
+    pub current_step: usize,
+    pub total_steps: usize,
+    /// Blocks erased so far
+    pub blocks_erased: u64,
+    /// Blocks verified so far
+    pub blocks_verified: u64,
+    pub total_blocks: u64,
+}
+
+impl EraseProgress {
+    /// Calculate completion percentage (erase and verify weighted equally
+    /// when verification is enabled)
+    pub fn percent_complete(&self, verify: bool) -> f64 {
+        if self.total_blocks == 0 {
+            return 0.0;
+        }
+        let total = self.total_blocks as f64;
+        if verify {
+            (self.blocks_erased + self.blocks_verified) as f64 / (2.0 * total) * 100.0
+        } else {
+            self.blocks_erased as f64 / total * 100.0
+        }
+    }
+}
+
+/// Runs an `ErasePlan`, tracking progress and verifying the result
+///
+/// Send the ops of `next_step()`, then call `complete_step()`. Once all
+/// steps are done, read back each range from `ErasePlan::verify_ranges` and
+/// pass it to `verify()`.
+#[derive(Debug, Clone)]
+pub struct EraseSession {
+    plan: ErasePlan,
+    verify: bool,
+    next_step: usize,
+    blocks_erased: u64,
+    blocks_verified: u64,
+}
+
+impl EraseSession {
+    pub fn new(plan: ErasePlan, verify: bool) -> Self {
+        // Verification is impossible when the erased content is indeterminate
+        let verify = verify && plan.expected_pattern.is_some();
+        Self {
+            plan,
+            verify,
+            next_step: 0,
+            blocks_erased: 0,
+            blocks_verified: 0,
+        }
+    }
+
+    pub fn plan(&self) -> &ErasePlan {
+        &self.plan
+    }
+
+    pub fn verify_enabled(&self) -> bool {
+        self.verify
+    }
+
+    /// Next step to run, `None` once every step has completed
+    pub fn next_step(&self) -> Option<&EraseStep> {
+        self.plan.steps.get(self.next_step)
+    }
+
+    /// Mark the current step as done
+    pub fn complete_step(&mut self) -> EraseProgress {
+        if let Some(step) = self.plan.steps.get(self.next_step) {
+            self.blocks_erased += step.count;
+            self.next_step += 1;
+        }
+        self.progress()
+    }
+
+    /// Check read-back data for blocks starting at `start`
+    pub fn verify(&mut self, start: u64, data: &[u8]) -> EraseResult<EraseProgress> {
+        if let Some(expected) = self.plan.expected_pattern {
+            let block_size = self.plan.block_size.max(1) as usize;
+            if let Some(pos) = data.iter().position(|&b| b != expected) {
+                return Err(EraseError::VerifyFailed {
+                    block: start + (pos / block_size) as u64,
+                    offset: pos % block_size,
+                    expected,
+                    actual: data[pos],
+                });
+            }
+            self.blocks_verified += (data.len() / block_size) as u64;
+        }
+        Ok(self.progress())
+    }
+
+    pub fn phase(&self) -> ErasePhase {
+        if self.next_step < self.plan.steps.len() {
+            ErasePhase::Erasing
+        } else if self.verify && self.blocks_verified < self.plan.count {
+            ErasePhase::Verifying
+        } else {
+            ErasePhase::Complete
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.phase() == ErasePhase::Complete
+    }
+
+    pub fn progress(&self) -> EraseProgress {
+        EraseProgress {
+            method: self.plan.method,
+            phase: self.phase(),
+            current_step: self.next_step,
+            total_steps: self.plan.steps.len(),
+            blocks_erased: self.blocks_erased,
+            blocks_verified: self.blocks_verified,
+            total_blocks: self.plan.count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_chip() -> EmmcChipInfo {
+        EmmcChipInfo {
+            manufacturer: "Samsung".into(),
+            model: "TEST".into(),
+            size_gb: 8,
+            sector_size: 512,
+            erase_group_size: 1024,
+            voltage: "3.3V".into(),
+            max_clock_mhz: 200,
+            ddr_support: true,
+            hs200_support: true,
+            hs400_support: false,
+            boot_partition: true,
+            rpmb_support: true,
+        }
+    }
+
+    fn test_ext_csd(sec_feature: u8) -> ExtCsd {
+        let mut raw = [0u8; ext_csd::SIZE];
+        raw[ext_csd::EXT_CSD_REV] = 8;
+        raw[ext_csd::SEC_COUNT..ext_csd::SEC_COUNT + 4]
+            .copy_from_slice(&(1024u32 * 1024).to_le_bytes());
+        raw[ext_csd::SEC_FEATURE_SUPPORT] = sec_feature;
+        raw[ext_csd::ERASE_TIMEOUT_MULT] = 1;
+        raw[ext_csd::TRIM_MULT] = 1;
+        raw[ext_csd::SEC_ERASE_MULT] = 2;
+        raw[ext_csd::SEC_TRIM_MULT] = 2;
+        ExtCsd::parse(&raw).unwrap()
+    }
+
+    fn packet_args(op: &EraseOp) -> (u32, u32, u32) {
+        match op {
+            EraseOp::Emmc(p) => {
+                let word = |i: usize| u32::from_le_bytes(p.args[i..i + 4].try_into().unwrap());
+                (word(0), word(4), word(8))
+            }
+            _ => panic!("expected eMMC packet"),
+        }
+    }
+
+    #[test]
+    fn test_emmc_capabilities() {
+        let eraser = EmmcEraser::new(&test_chip(), &test_ext_csd(0x00));
+        assert!(eraser.capabilities().supports(EraseMethod::Erase));
+        assert_eq!(
+            eraser.plan(EraseMethod::Trim, 0, 8).unwrap_err(),
+            EraseError::Unsupported(EraseMethod::Trim)
+        );
+        assert_eq!(
+            eraser.plan(EraseMethod::Unmap, 0, 8).unwrap_err(),
+            EraseError::Unsupported(EraseMethod::Unmap)
+        );
+
+        let caps = EmmcEraseCapabilities::from_ext_csd(&test_ext_csd(0x51));
+        assert!(caps.trim && caps.discard && caps.secure_erase && caps.secure_trim);
+        assert!(caps.sanitize);
+    }
+
+    #[test]
+    fn test_emmc_erase_alignment_and_steps() {
+        let eraser = EmmcEraser::new(&test_chip(), &test_ext_csd(0x51));
+        assert!(matches!(
+            eraser.plan(EraseMethod::Erase, 512, 1024),
+            Err(EraseError::Misaligned { .. })
+        ));
+        assert!(matches!(
+            eraser.plan(EraseMethod::Erase, 0, 2 * 1024 * 1024),
+            Err(EraseError::OutOfRange { .. })
+        ));
+        assert_eq!(expand_to_groups(512, 1024, 1024), (0, 2048));
+
+        let count = 1024 * (EMMC_GROUPS_PER_STEP + 1);
+        let plan = eraser.plan(EraseMethod::Erase, 1024, count).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(
+            packet_args(&plan.steps[0].ops[0]),
+            (
+                1024,
+                1024 + 1024 * EMMC_GROUPS_PER_STEP as u32 - 1,
+                cmd38_args::ERASE
+            )
+        );
+        assert_eq!(plan.steps[1].count, 1024);
+        assert_eq!(plan.steps[0].timeout_ms, 300 * EMMC_GROUPS_PER_STEP as u32);
+
+        // TRIM is sector granular
+        let plan = eraser.plan(EraseMethod::Trim, 3, 5).unwrap();
+        assert_eq!(packet_args(&plan.steps[0].ops[0]), (3, 7, cmd38_args::TRIM));
+
+        // Secure trim ends with the second pass over the full range
+        let plan = eraser.plan(EraseMethod::SecureTrim, 8, 16).unwrap();
+        assert_eq!(
+            packet_args(&plan.steps.last().unwrap().ops[0]),
+            (8, 23, cmd38_args::SECURE_TRIM_STEP2)
+        );
+
+        let plan = eraser.plan(EraseMethod::Sanitize, 0, 1024).unwrap();
+        match &plan.steps.last().unwrap().ops[0] {
+            EraseOp::Emmc(p) => assert_eq!(p.cmd, Command::EmmcSwitch),
+            _ => panic!("expected CMD6"),
+        }
+
+        let plan = eraser.plan(EraseMethod::Discard, 0, 8).unwrap();
+        assert_eq!(plan.expected_pattern, None);
+        assert!(!EraseSession::new(plan, true).verify_enabled());
+    }
+
+    #[test]
+    fn test_ufs_plans() {
+        let eraser = UfsEraser::new(4096, 1 << 20);
+        let plan = eraser
+            .plan(EraseMethod::Unmap, 10, UFS_BLOCKS_PER_STEP + 6)
+            .unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        match &plan.steps[1].ops[0] {
+            EraseOp::Scsi { cdb, data_out } => {
+                assert_eq!(cdb[0], crate::ufs::scsi::UNMAP);
+                assert_eq!(data_out.len(), 24);
+                assert_eq!(
+                    u64::from_be_bytes(data_out[8..16].try_into().unwrap()),
+                    10 + UFS_BLOCKS_PER_STEP
+                );
+                assert_eq!(u32::from_be_bytes(data_out[16..20].try_into().unwrap()), 6);
+            }
+            _ => panic!("expected SCSI command"),
+        }
+
+        let plan = eraser.plan(EraseMethod::FormatUnit, 100, 1).unwrap();
+        assert_eq!((plan.start, plan.count), (0, 1 << 20));
+
+        let plan = eraser.plan(EraseMethod::Purge, 0, 16).unwrap();
+        assert!(matches!(
+            plan.steps.last().unwrap().ops[..],
+            [
+                EraseOp::UfsSetFlag {
+                    idn: flags::PURGE_ENABLE
+                },
+                EraseOp::UfsPollAttribute { .. }
+            ]
+        ));
+        assert_eq!(check_purge_status(purge_status::IN_PROGRESS), Ok(false));
+        assert_eq!(check_purge_status(purge_status::COMPLETED), Ok(true));
+        assert_eq!(
+            check_purge_status(purge_status::GENERAL_FAILURE),
+            Err(EraseError::PurgeFailed(purge_status::GENERAL_FAILURE))
+        );
+        assert_eq!(
+            eraser.plan(EraseMethod::Trim, 0, 1).unwrap_err(),
+            EraseError::Unsupported(EraseMethod::Trim)
+        );
+    }
+
+    #[test]
+    fn test_session_progress_and_verify() {
+        let eraser = UfsEraser::new(512, 1024);
+        let plan = eraser.plan(EraseMethod::Unmap, 0, 4).unwrap();
+        let mut session = EraseSession::new(plan, true);
+        assert_eq!(session.phase(), ErasePhase::Erasing);
+
+        while session.next_step().is_some() {
+            session.complete_step();
+        }
+        assert_eq!(session.phase(), ErasePhase::Verifying);
+        assert_eq!(session.progress().percent_complete(true), 50.0);
+
+        let ranges = session.plan().verify_ranges(3);
+        assert_eq!(ranges, vec![(0, 3), (3, 1)]);
+
+        let mut data = vec![0u8; 3 * 512];
+        data[512 + 7] = 0xAA;
+        assert_eq!(
+            session.verify(0, &data).unwrap_err(),
+            EraseError::VerifyFailed {
+                block: 1,
+                offset: 7,
+                expected: 0x00,
+                actual: 0xAA
+            }
+        );
+
+        data[512 + 7] = 0;
+        session.verify(0, &data).unwrap();
+        let progress = session.verify(3, &[0u8; 512]).unwrap();
+        assert_eq!(progress.phase, ErasePhase::Complete);
+        assert_eq!(progress.percent_complete(true), 100.0);
+    }
+}
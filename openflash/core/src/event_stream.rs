@@ -0,0 +1,483 @@
+//! Live WebSocket event stream for the OpenFlash server
+//!
+//! [`EventHub`] listens to the [`EventBus`](crate::server::EventBus) of an
+//! [`OpenFlashServer`] and fans job progress, parallel dump chunk status,
+//! device connect/disconnect and production unit results out to WebSocket
+//! clients. Each client chooses what it receives with the
+//! `Subscribe`/`SubscribeDevices`/`SubscribeProduction` messages of
+//! [`WsMessage`]; nothing is delivered until the client subscribes.
+//!
+//! The hub enforces `max_connections`, sends a ping every `ping_interval`
+//! seconds and drops clients that stay silent for `timeout` seconds (see
+//! [`WebSocketConfig`]). The HTTP upgrade itself is done by
+//! [`RestServer`](crate::rest_server::RestServer) on `WebSocketConfig.path`.
+//!
+//! Only available with the `rest-server` feature.
+
+use crate::server::{OpenFlashServer, WebSocketConfig, WsMessage};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Stream limits
+pub mod limits {
+    /// Events buffered per client before it is reported as lagging
+    pub const EVENT_BUFFER: usize = 1024;
+}
+
+// ============================================================================
+// Subscriptions
+// ============================================================================
+
+/// Which keys of one event category a client wants
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Filter<T: Hash + Eq> {
+    /// Nothing (initial state)
+    #[default]
+    Off,
+    /// Every key
+    All,
+    /// Only these keys
+    Only(HashSet<T>),
+}
+
+impl<T: Hash + Eq> Filter<T> {
+    /// Add keys; an empty list subscribes to everything
+    fn add(&mut self, keys: Vec<T>) {
+        if keys.is_empty() {
+            *self = Self::All;
+            return;
+        }
+        match self {
+            Self::All => {}
+            Self::Only(set) => set.extend(keys),
+            Self::Off => *self = Self::Only(keys.into_iter().collect()),
+        }
+    }
+
+    /// Remove keys; an empty list unsubscribes from everything. Removing
+    /// single keys from an `All` subscription is not supported.
+    fn remove(&mut self, keys: Vec<T>) {
+        if keys.is_empty() {
+            *self = Self::Off;
+            return;
+        }
+        if let Self::Only(set) = self {
+            for key in &keys {
+                set.remove(key);
+            }
+            if set.is_empty() {
+                *self = Self::Off;
+            }
+        }
+    }
+
+    /// Whether `key` passes the filter
+    pub fn matches(&self, key: &T) -> bool {
+        match self {
+            Self::Off => false,
+            Self::All => true,
+            Self::Only(set) => set.contains(key),
+        }
+    }
+}
+
+/// Per-connection subscription state
+#[derive(Debug, Clone, Default)]
+pub struct Subscription {
+    /// Job, job result and chunk events by job ID
+    pub jobs: Filter<u64>,
+    /// Device events by device ID
+    pub devices: Filter<String>,
+    /// Production results by line ID
+    pub production: Filter<String>,
+}
+
+impl Subscription {
+    /// Apply a message received from the client, returning the reply to
+    /// send back (if any)
+    pub fn apply(&mut self, message: WsMessage) -> Option<WsMessage> {
+        match message {
+            WsMessage::Subscribe { job_ids } => self.jobs.add(job_ids),
+            WsMessage::Unsubscribe { job_ids } => self.jobs.remove(job_ids),
+            WsMessage::SubscribeDevices { device_ids } => self.devices.add(device_ids),
+            WsMessage::SubscribeProduction { line_ids } => self.production.add(line_ids),
+            WsMessage::Ping => return Some(WsMessage::Pong),
+            WsMessage::Pong => {}
+            other => {
+                return Some(WsMessage::Error {
+                    code: "unexpected_message".to_string(),
+                    message: format!("{} is sent by the server only", message_name(&other)),
+                })
+            }
+        }
+        None
+    }
+
+    /// Whether an event should be forwarded to this client
+    pub fn wants(&self, event: &WsMessage) -> bool {
+        match event {
+            WsMessage::JobUpdate { job_id, .. }
+            | WsMessage::JobCompleted { job_id, .. }
+            | WsMessage::JobFailed { job_id, .. }
+            | WsMessage::ChunkUpdate { job_id, .. } => self.jobs.matches(job_id),
+            WsMessage::DeviceUpdate { device_id, .. } => self.devices.matches(device_id),
+            WsMessage::ProductionResult { result } => self.production.matches(&result.line_id),
+            WsMessage::Error { .. } | WsMessage::Ping | WsMessage::Pong => true,
+            WsMessage::Subscribe { .. }
+            | WsMessage::Unsubscribe { .. }
+            | WsMessage::SubscribeDevices { .. }
+            | WsMessage::SubscribeProduction { .. } => false,
+        }
+    }
+}
+
+fn message_name(message: &WsMessage) -> String {
+    serde_json::to_value(message)
+        .ok()
+        .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(str::to_string))
+        .unwrap_or_else(|| "message".to_string())
+}
+
+// ============================================================================
+// Event Hub
+// ============================================================================
+
+/// Releases a connection slot when dropped
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    connections: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Broadcasts server events to WebSocket clients
+#[derive(Debug, Clone)]
+pub struct EventHub {
+    config: WebSocketConfig,
+    sender: broadcast::Sender<WsMessage>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl EventHub {
+    pub fn new(config: WebSocketConfig) -> Self {
+        let (sender, _) = broadcast::channel(limits::EVENT_BUFFER);
+        Self {
+            config,
+            sender,
+            connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Forward every event emitted by `server` to the hub
+    pub fn attach(&self, server: &mut OpenFlashServer) {
+        let sender = self.sender.clone();
+        server.events.subscribe(move |event| {
+            // No receivers just means nobody is connected
+            let _ = sender.send(event.clone());
+        });
+    }
+
+    /// Publish an event that did not come from the server's event bus
+    pub fn publish(&self, event: WsMessage) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn config(&self) -> &WebSocketConfig {
+        &self.config
+    }
+
+    /// Number of open connections
+    pub fn connection_count(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Reserve a connection slot, `None` once `max_connections` is reached
+    pub fn try_acquire(&self) -> Option<ConnectionGuard> {
+        let max = self.config.max_connections;
+        self.connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| ConnectionGuard {
+                connections: self.connections.clone(),
+            })
+    }
+
+    /// Drive one client until it disconnects, times out or the hub closes
+    pub async fn run_connection<S>(&self, ws: WebSocketStream<S>, guard: ConnectionGuard)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let _guard = guard;
+        let mut events = self.sender.subscribe();
+        let mut subscription = Subscription::default();
+        let ping_every = Duration::from_secs(self.config.ping_interval.max(1));
+        let timeout = Duration::from_secs(self.config.timeout.max(1));
+        let mut ping = tokio::time::interval_at(Instant::now() + ping_every, ping_every);
+        let mut last_seen = Instant::now();
+        let (mut sink, mut stream) = ws.split();
+
+        loop {
+            let outgoing = tokio::select! {
+                frame = stream.next() => {
+                    let frame = match frame {
+                        Some(Ok(frame)) => frame,
+                        _ => break,
+                    };
+                    last_seen = Instant::now();
+                    match frame {
+                        Message::Text(text) => match serde_json::from_str::<WsMessage>(&text) {
+                            Ok(message) => subscription.apply(message),
+                            Err(e) => Some(WsMessage::Error {
+                                code: "invalid_message".to_string(),
+                                message: e.to_string(),
+                            }),
+                        },
+                        Message::Binary(_) => Some(WsMessage::Error {
+                            code: "invalid_message".to_string(),
+                            message: "binary frames are not supported".to_string(),
+                        }),
+                        Message::Close(_) => break,
+                        // Pings are answered by tungstenite; pongs only refresh last_seen
+                        _ => None,
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) => subscription.wants(&event).then_some(event),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => Some(WsMessage::Error {
+                        code: "lagged".to_string(),
+                        message: format!("{} events were dropped", missed),
+                    }),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ping.tick() => {
+                    if last_seen.elapsed() >= timeout {
+                        let _ = sink.send(Message::Close(None)).await;
+                        break;
+                    }
+                    if sink.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
+                    None
+                }
+            };
+
+            if let Some(message) = outgoing {
+                let text = match serde_json::to_string(&message) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                if sink.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest_server::{RestApi, RestServer};
+    use crate::server::{
+        DevicePlatform, DeviceStatus, Job, JobType, PoolDevice, ProductionUnitResult,
+        RestApiConfig, ServerConfig,
+    };
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_subscription_filters() {
+        let mut sub = Subscription::default();
+        let update = |job_id| WsMessage::JobUpdate {
+            job_id,
+            status: "running".to_string(),
+            progress: Some(10),
+        };
+        assert!(!sub.wants(&update(1)));
+
+        assert!(sub
+            .apply(WsMessage::Subscribe {
+                job_ids: vec![1, 2]
+            })
+            .is_none());
+        assert!(sub.wants(&update(1)));
+        assert!(!sub.wants(&update(3)));
+        sub.apply(WsMessage::Unsubscribe { job_ids: vec![1] });
+        assert!(!sub.wants(&update(1)));
+        assert!(sub.wants(&update(2)));
+
+        sub.apply(WsMessage::SubscribeProduction {
+            line_ids: vec!["line-a".to_string()],
+        });
+        let unit = |line: &str| WsMessage::ProductionResult {
+            result: ProductionUnitResult {
+                serial_number: "SN1".to_string(),
+                line_id: line.to_string(),
+                station_id: "st1".to_string(),
+                passed: true,
+                timestamp: 0,
+                duration_ms: 0,
+                chip_info: None,
+                bad_blocks: 0,
+                ecc_corrections: 0,
+                failure_reason: None,
+                data: HashMap::new(),
+            },
+        };
+        assert!(sub.wants(&unit("line-a")));
+        assert!(!sub.wants(&unit("line-b")));
+
+        sub.apply(WsMessage::SubscribeDevices { device_ids: vec![] });
+        assert!(sub.wants(&WsMessage::DeviceUpdate {
+            device_id: "any".to_string(),
+            status: "connected".to_string(),
+        }));
+        assert!(matches!(sub.apply(WsMessage::Ping), Some(WsMessage::Pong)));
+        assert!(matches!(
+            sub.apply(update(5)),
+            Some(WsMessage::Error { .. })
+        ));
+    }
+
+    async fn next(ws: &mut WebSocketStream<tokio::net::TcpStream>) -> WsMessage {
+        match tokio::time::timeout(Duration::from_secs(5), ws.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => serde_json::from_str(&text).unwrap(),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streams_events_over_websocket() {
+        let config = ServerConfig {
+            rest: RestApiConfig {
+                port: 0,
+                ..Default::default()
+            },
+            websocket: WebSocketConfig {
+                max_connections: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        let rest = RestServer::bind(RestApi::new(server.clone()))
+            .await
+            .unwrap();
+        let addr = rest.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(rest.serve_with_shutdown(async {
+            let _ = stopped.await;
+        }));
+
+        let url = format!("ws://{}/ws", addr);
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut ws, _) = tokio_tungstenite::client_async(url.as_str(), stream)
+            .await
+            .unwrap();
+        ws.send(Message::Text(
+            r#"{"type":"Subscribe","job_ids":[]}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+        ws.send(Message::Text(
+            r#"{"type":"SubscribeDevices","device_ids":[]}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+        ws.send(Message::Text(r#"{"type":"Ping"}"#.to_string()))
+            .await
+            .unwrap();
+        // The Pong proves both subscriptions were processed
+        assert!(matches!(next(&mut ws).await, WsMessage::Pong));
+
+        // A second client exceeds max_connections
+        let second = tokio::net::TcpStream::connect(addr).await.unwrap();
+        assert!(tokio_tungstenite::client_async(url.as_str(), second)
+            .await
+            .is_err());
+
+        let job_id = {
+            let mut server = server.lock().unwrap();
+            server
+                .register_device(PoolDevice::new(
+                    "dev1",
+                    "Bench 1",
+                    "serial:///dev/ttyACM0",
+                    DevicePlatform::RP2040,
+                ))
+                .unwrap();
+            server
+                .set_device_status("dev1", DeviceStatus::Available)
+                .unwrap();
+            let job_id = server
+                .submit_job(Job::new(
+                    "erase",
+                    JobType::Erase {
+                        start_address: 0,
+                        length: None,
+                    },
+                ))
+                .unwrap();
+            server.process_queue();
+            server.update_job_progress(job_id, 40).unwrap();
+            job_id
+        };
+
+        assert!(matches!(
+            next(&mut ws).await,
+            WsMessage::DeviceUpdate { ref status, .. } if status == "connected"
+        ));
+        assert!(matches!(
+            next(&mut ws).await,
+            WsMessage::DeviceUpdate { ref status, .. } if status == "available"
+        ));
+        assert!(matches!(
+            next(&mut ws).await,
+            WsMessage::JobUpdate { job_id: id, ref status, .. } if id == job_id && status == "queued"
+        ));
+        assert!(matches!(
+            next(&mut ws).await,
+            WsMessage::JobUpdate {
+                progress: Some(0),
+                ..
+            }
+        ));
+        assert!(
+            matches!(next(&mut ws).await, WsMessage::DeviceUpdate { ref status, .. } if status == "busy")
+        );
+        assert!(matches!(
+            next(&mut ws).await,
+            WsMessage::JobUpdate {
+                progress: Some(40),
+                ..
+            }
+        ));
+
+        ws.close(None).await.unwrap();
+        stop.send(()).unwrap();
+        task.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,1214 @@
+//! gRPC front end for the OpenFlash server
+//!
+//! Implements the `openflash.v1.OpenFlash` service from
+//! `proto/openflash.proto` on top of a [`SharedServer`]: the job and device
+//! operations of the REST API plus
+//! - `WatchJob`: server-streamed job status and parallel dump chunk updates,
+//!   fed from the server's [`EventBus`](crate::server::EventBus)
+//! - `ReadDump`: server-streamed dump data from the artifact directory,
+//!   ending with the SHA-256 of the range
+//! - `UploadImage`: client-streamed image upload into the artifact
+//!   directory, verified against the announced size and hash
+//!
+//! [`GrpcServer`] serves it according to [`GrpcConfig`] (TLS, reflection,
+//! `max_message_size`). Client stubs for other languages can be generated
+//! from the same `.proto` file.
+//!
+//! Callers authenticate like REST clients: the scheme of
+//! `RestApiConfig.auth` with the start-up [`Credentials`] or a user's
+//! secret, sent as metadata (`authorization: Bearer <token>`, the API key
+//! header, ...). Each caller IP is charged a `RestApiConfig.rate_limit`
+//! token before its credentials are checked, and each authenticated user
+//! another. With `ServerConfig.access` enabled every call is checked
+//! against the caller's role.
+//!
+//! Only available with the `grpc-server` feature.
+
+use crate::access::{AccessConfig, Credentials, Permission, Principal, RateLimiter};
+use crate::server::{
+    is_safe_artifact_name, lock_server, resolve_artifact, AuthMethod, DeviceListResponse,
+    GrpcConfig, JobStatusResponse, JobType, ServerError, ServerInfo, SharedServer,
+    SubmitJobRequest, WsMessage,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::transport::{Identity, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Types and service stubs generated from `proto/openflash.proto`
+#[allow(clippy::all, missing_docs)]
+pub mod pb {
+    tonic::include_proto!("openflash.v1");
+
+    /// Encoded descriptor set, served through gRPC reflection
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        tonic::include_file_descriptor_set!("openflash_descriptor");
+}
+
+use pb::open_flash_server::{OpenFlash, OpenFlashServer as OpenFlashService};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Streaming limits
+pub mod limits {
+    /// `ReadDump` chunk size when the client does not ask for one
+    pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+    /// Room left in each message for framing around the chunk data
+    pub const MESSAGE_OVERHEAD: usize = 1024;
+    /// Messages buffered per stream
+    pub const STREAM_BUFFER: usize = 16;
+    /// Events buffered for `WatchJob` subscribers
+    pub const EVENT_BUFFER: usize = 1024;
+    /// Upload directory inside the artifact directory
+    pub const UPLOAD_DIR: &str = "uploads";
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// gRPC server start-up errors
+#[derive(Debug)]
+pub enum GrpcError {
+    /// Socket or file I/O error
+    Io(std::io::Error),
+    /// Certificate or key could not be loaded
+    Tls(String),
+    /// tonic transport failure
+    Transport(String),
+    /// `GrpcConfig` cannot be served as configured
+    InvalidConfig(String),
+}
+
+impl std::fmt::Display for GrpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Tls(s) => write!(f, "TLS error: {}", s),
+            Self::Transport(s) => write!(f, "Transport error: {}", s),
+            Self::InvalidConfig(s) => write!(f, "Invalid gRPC configuration: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for GrpcError {}
+
+impl From<std::io::Error> for GrpcError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<tonic::transport::Error> for GrpcError {
+    fn from(e: tonic::transport::Error) -> Self {
+        Self::Transport(e.to_string())
+    }
+}
+
+pub type GrpcResult<T> = Result<T, GrpcError>;
+
+/// gRPC status for a server-side error
+fn status_for(err: ServerError) -> Status {
+    let message = err.to_string();
+    match err {
+        ServerError::DeviceNotFound(_) | ServerError::JobNotFound(_) => Status::not_found(message),
+        ServerError::InvalidRequest(_) | ServerError::InvalidConfig(_) => {
+            Status::invalid_argument(message)
+        }
+        ServerError::DeviceBusy(_) | ServerError::JobFailed { .. } => {
+            Status::failed_precondition(message)
+        }
+        ServerError::QueueFull | ServerError::RateLimitExceeded => {
+            Status::resource_exhausted(message)
+        }
+        ServerError::AuthFailed(_) => Status::unauthenticated(message),
+        ServerError::PermissionDenied(_) => Status::permission_denied(message),
+        ServerError::Timeout(_) => Status::deadline_exceeded(message),
+        ServerError::DeviceOffline(_) | ServerError::ConnectionFailed(_) => {
+            Status::unavailable(message)
+        }
+        ServerError::InternalError(_) => Status::internal(message),
+    }
+}
+
+// ============================================================================
+// Conversions
+// ============================================================================
+
+impl From<ServerInfo> for pb::ServerInfo {
+    fn from(info: ServerInfo) -> Self {
+        let pool = info.pool_stats;
+        let queue = info.queue_stats;
+        Self {
+            name: info.name,
+            version: info.version,
+            uptime_ms: info.uptime_ms,
+            pool_stats: Some(pb::PoolStats {
+                total_devices: pool.total_devices as u64,
+                available_devices: pool.available_devices as u64,
+                busy_devices: pool.busy_devices as u64,
+                offline_devices: pool.offline_devices as u64,
+                error_devices: pool.error_devices as u64,
+                total_jobs_completed: pool.total_jobs_completed,
+                total_bytes_processed: pool.total_bytes_processed,
+            }),
+            queue_stats: Some(pb::QueueStats {
+                pending_count: queue.pending_count as u64,
+                running_count: queue.running_count as u64,
+                completed_count: queue.completed_count as u64,
+                failed_count: queue.failed_count as u64,
+                cancelled_count: queue.cancelled_count as u64,
+            }),
+        }
+    }
+}
+
+impl From<DeviceListResponse> for pb::DeviceList {
+    fn from(list: DeviceListResponse) -> Self {
+        Self {
+            devices: list
+                .devices
+                .into_iter()
+                .map(|d| pb::Device {
+                    id: d.id,
+                    name: d.name,
+                    platform: d.platform,
+                    status: d.status,
+                    current_job: d.current_job,
+                    interfaces: d.interfaces,
+                    tags: d.tags,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<JobStatusResponse> for pb::JobStatus {
+    fn from(status: JobStatusResponse) -> Self {
+        Self {
+            job_id: status.job_id,
+            name: status.name,
+            status: status.status,
+            progress: status.progress.map(u32::from),
+            device_id: status.device_id,
+            created_at: status.created_at,
+            started_at: status.started_at,
+            completed_at: status.completed_at,
+            result: status.result.map(|r| pb::JobResult {
+                bytes_processed: r.bytes_processed,
+                pages_processed: r.pages_processed,
+                blocks_processed: r.blocks_processed,
+                ecc_corrections: r.ecc_corrections,
+                bad_blocks: r.bad_blocks,
+                output_path: r.output_path,
+                checksum: r.checksum,
+                data: r.data,
+            }),
+            error: status.error,
+        }
+    }
+}
+
+impl From<pb::SubmitJobRequest> for SubmitJobRequest {
+    /// Parameter values that parse as JSON scalars (numbers, booleans, null)
+    /// keep that type; anything else is passed as a string
+    fn from(req: pb::SubmitJobRequest) -> Self {
+        let params: HashMap<String, serde_json::Value> = req
+            .params
+            .into_iter()
+            .map(|(key, value)| {
+                let parsed = match serde_json::from_str::<serde_json::Value>(&value) {
+                    Ok(v) if !v.is_object() && !v.is_array() => v,
+                    _ => serde_json::Value::String(value),
+                };
+                (key, parsed)
+            })
+            .collect();
+        Self {
+            name: req.name,
+            job_type: req.job_type,
+            params,
+            priority: req.priority,
+            device_id: req.device_id,
+            interface: req.interface,
+            tags: (!req.tags.is_empty()).then_some(req.tags),
+            timeout: req.timeout,
+            callback_url: req.callback_url,
+        }
+    }
+}
+
+fn is_finished(status: &str) -> bool {
+    matches!(status, "completed" | "failed" | "cancelled" | "timed_out")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Upload staging file, removed on drop unless it was renamed into place
+///
+/// Dropping covers early returns as well as the call being dropped when
+/// the client disconnects, which would otherwise leave the file behind and
+/// refuse later uploads of the same name as in progress.
+struct StagingFile {
+    path: PathBuf,
+    kept: bool,
+}
+
+impl Drop for StagingFile {
+    fn drop(&mut self) {
+        if !self.kept {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// ============================================================================
+// Service
+// ============================================================================
+
+/// `openflash.v1.OpenFlash` implementation
+#[derive(Debug, Clone)]
+pub struct GrpcService {
+    server: SharedServer,
+    artifact_root: Option<PathBuf>,
+    max_message_size: usize,
+    auth: AuthMethod,
+    credentials: Credentials,
+    access: AccessConfig,
+    limiter: Arc<Mutex<RateLimiter>>,
+    events: broadcast::Sender<WsMessage>,
+}
+
+// Helpers return `Status` directly, like the generated service methods
+#[allow(clippy::result_large_err)]
+impl GrpcService {
+    /// Serve `server`, subscribing to its event bus for `WatchJob`
+    pub fn new(server: SharedServer) -> Self {
+        let (events, _) = broadcast::channel(limits::EVENT_BUFFER);
+        let (max_message_size, auth, access, limiter) = {
+            let mut guard = lock_server(&server);
+            let sender = events.clone();
+            guard.events.subscribe(move |event| {
+                let _ = sender.send(event.clone());
+            });
+            (
+                guard.config.grpc.max_message_size,
+                guard.config.rest.auth.clone(),
+                guard.config.access.clone(),
+                RateLimiter::new(&guard.config.rest.rate_limit),
+            )
+        };
+        Self {
+            server,
+            artifact_root: None,
+            max_message_size,
+            auth,
+            credentials: Credentials::default(),
+            access,
+            limiter: Arc::new(Mutex::new(limiter)),
+            events,
+        }
+    }
+
+    /// Accept the start-up secrets of `credentials` for `RestApiConfig.auth`
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Directory used by `ReadDump` and `UploadImage`; both are refused
+    /// without it
+    pub fn with_artifact_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.artifact_root = Some(root.into());
+        self
+    }
+
+    /// Caller of `request`, see [`Credentials::authenticate`]
+    fn principal<T>(&self, request: &Request<T>) -> Result<Principal, Status> {
+        // Keyed on the IP alone so reconnecting does not refill the bucket
+        let peer = request
+            .remote_addr()
+            .map(|addr| format!("grpc:{}", addr.ip()))
+            .unwrap_or_else(|| "grpc".to_string());
+        self.rate_limit(&peer)?;
+        let metadata = request.metadata();
+        let header = |name: &str| metadata.get(name).and_then(|v| v.to_str().ok());
+        let principal = self
+            .credentials
+            .authenticate(&self.access, &self.auth, header, &peer)
+            .ok_or_else(|| Status::unauthenticated("missing or invalid credentials"))?;
+        if principal.id != peer {
+            self.rate_limit(&principal.id)?;
+        }
+        Ok(principal)
+    }
+
+    fn rate_limit(&self, client: &str) -> Result<(), Status> {
+        let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+        limiter.check(client, Instant::now()).map_err(|wait| {
+            Status::resource_exhausted(format!(
+                "rate limit exceeded, retry in {}s",
+                wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+            ))
+        })
+    }
+
+    /// Check that the caller of `request` has `permission`
+    fn authorize<T>(&self, request: &Request<T>, permission: Permission) -> Result<(), Status> {
+        let principal = self.principal(request)?;
+        lock_server(&self.server)
+            .authorize(&principal, permission)
+            .map_err(status_for)
+    }
+
+    fn artifact_root(&self) -> Result<&Path, Status> {
+        self.artifact_root
+            .as_deref()
+            .ok_or_else(|| Status::failed_precondition("artifact directory is not configured"))
+    }
+
+    fn job_status(&self, job_id: u64) -> Result<pb::JobStatus, Status> {
+        lock_server(&self.server)
+            .get_job_status(job_id)
+            .map(pb::JobStatus::from)
+            .ok_or_else(|| status_for(ServerError::JobNotFound(job_id)))
+    }
+
+    /// File behind a `ReadDump` source
+    fn dump_path(&self, source: Option<pb::read_dump_request::Source>) -> Result<PathBuf, Status> {
+        use pb::read_dump_request::Source;
+
+        let root = self.artifact_root()?;
+        let relative = match source {
+            Some(Source::Path(path)) => path,
+            Some(Source::JobId(job_id)) => {
+                let server = lock_server(&self.server);
+                let job = server
+                    .job_queue
+                    .get_job(job_id)
+                    .ok_or_else(|| status_for(ServerError::JobNotFound(job_id)))?;
+                let from_result = job.result.as_ref().and_then(|r| r.output_path.clone());
+                match (from_result, &job.job_type) {
+                    (Some(path), _) => path,
+                    (None, JobType::Read { output_path, .. }) => output_path.clone(),
+                    _ => {
+                        return Err(Status::failed_precondition(format!(
+                            "job {} has no dump output",
+                            job_id
+                        )))
+                    }
+                }
+            }
+            None => return Err(Status::invalid_argument("job_id or path is required")),
+        };
+
+        let found = if Path::new(&relative).is_absolute() {
+            // Absolute job outputs are accepted only inside the artifact root
+            let root = root.canonicalize().ok();
+            Path::new(&relative)
+                .canonicalize()
+                .ok()
+                .filter(|p| root.as_ref().is_some_and(|r| p.starts_with(r)) && p.is_file())
+        } else {
+            let components: Vec<&str> = relative
+                .split('/')
+                .filter(|c| !c.is_empty() && *c != ".")
+                .collect();
+            resolve_artifact(root, &components)
+        };
+        found.ok_or_else(|| Status::not_found(format!("dump {} not found", relative)))
+    }
+}
+
+#[tonic::async_trait]
+impl OpenFlash for GrpcService {
+    async fn get_server_info(
+        &self,
+        request: Request<pb::GetServerInfoRequest>,
+    ) -> Result<Response<pb::ServerInfo>, Status> {
+        self.principal(&request)?;
+        let info = lock_server(&self.server).server_info();
+        Ok(Response::new(info.into()))
+    }
+
+    async fn list_devices(
+        &self,
+        request: Request<pb::ListDevicesRequest>,
+    ) -> Result<Response<pb::DeviceList>, Status> {
+        self.principal(&request)?;
+        let devices = lock_server(&self.server).list_devices();
+        Ok(Response::new(devices.into()))
+    }
+
+    async fn submit_job(
+        &self,
+        request: Request<pb::SubmitJobRequest>,
+    ) -> Result<Response<pb::SubmitJobResponse>, Status> {
+        let principal = self.principal(&request)?;
+        let job = SubmitJobRequest::from(request.into_inner())
+            .to_job()
+            .map_err(status_for)?;
+
+        let mut server = lock_server(&self.server);
+        let job_id = server.submit_job_as(&principal, job).map_err(status_for)?;
+        Ok(Response::new(pb::SubmitJobResponse {
+            job_id,
+            status: "queued".to_string(),
+            message: format!("Job queued at position {}", server.job_queue.pending.len()),
+        }))
+    }
+
+    async fn get_job(
+        &self,
+        request: Request<pb::GetJobRequest>,
+    ) -> Result<Response<pb::JobStatus>, Status> {
+        self.principal(&request)?;
+        self.job_status(request.into_inner().job_id)
+            .map(Response::new)
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<pb::CancelJobRequest>,
+    ) -> Result<Response<pb::JobStatus>, Status> {
+        let principal = self.principal(&request)?;
+        let job_id = request.into_inner().job_id;
+        let mut server = lock_server(&self.server);
+        match server.cancel_job_as(&principal, job_id) {
+            Ok(()) => {}
+            // Finished jobs stay visible in the history but cannot be cancelled
+            Err(ServerError::JobNotFound(_)) if server.get_job_status(job_id).is_some() => {
+                return Err(Status::failed_precondition(format!(
+                    "Job {} already finished",
+                    job_id
+                )))
+            }
+            Err(e) => return Err(status_for(e)),
+        }
+        drop(server);
+        self.job_status(job_id).map(Response::new)
+    }
+
+    type WatchJobStream = ReceiverStream<Result<pb::JobEvent, Status>>;
+
+    async fn watch_job(
+        &self,
+        request: Request<pb::WatchJobRequest>,
+    ) -> Result<Response<Self::WatchJobStream>, Status> {
+        use pb::job_event::Event;
+
+        self.principal(&request)?;
+        let job_id = request.into_inner().job_id;
+        // Subscribe before taking the snapshot so no update falls in between
+        let mut events = self.events.subscribe();
+        let snapshot = self.job_status(job_id)?;
+        let (tx, rx) = mpsc::channel(limits::STREAM_BUFFER);
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            let mut finished = is_finished(&snapshot.status);
+            let status_event = |status: pb::JobStatus| pb::JobEvent {
+                job_id,
+                event: Some(Event::Status(status)),
+            };
+            if tx.send(Ok(status_event(snapshot))).await.is_err() {
+                return;
+            }
+
+            while !finished {
+                let event = match events.recv().await {
+                    Ok(WsMessage::ChunkUpdate {
+                        job_id: id,
+                        chunk_index,
+                        status,
+                        device_id,
+                        progress,
+                    }) if id == job_id => pb::JobEvent {
+                        job_id,
+                        event: Some(Event::Chunk(pb::ChunkStatus {
+                            chunk_index: chunk_index as u64,
+                            status,
+                            device_id,
+                            progress: u32::from(progress),
+                        })),
+                    },
+                    Ok(WsMessage::JobUpdate { job_id: id, .. })
+                    | Ok(WsMessage::JobCompleted { job_id: id, .. })
+                    | Ok(WsMessage::JobFailed { job_id: id, .. })
+                        if id == job_id =>
+                    {
+                        match service.job_status(job_id) {
+                            Ok(status) => {
+                                finished = is_finished(&status.status);
+                                status_event(status)
+                            }
+                            Err(e) => {
+                                let _ = tx.send(Err(e)).await;
+                                return;
+                            }
+                        }
+                    }
+                    Ok(_) => continue,
+                    // Missed updates: resynchronise from the current state
+                    Err(broadcast::error::RecvError::Lagged(_)) => match service.job_status(job_id)
+                    {
+                        Ok(status) => {
+                            finished = is_finished(&status.status);
+                            status_event(status)
+                        }
+                        Err(e) => {
+                            let _ = tx.send(Err(e)).await;
+                            return;
+                        }
+                    },
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ReadDumpStream = ReceiverStream<Result<pb::DataChunk, Status>>;
+
+    async fn read_dump(
+        &self,
+        request: Request<pb::ReadDumpRequest>,
+    ) -> Result<Response<Self::ReadDumpStream>, Status> {
+        self.authorize(&request, Permission::Artifacts)?;
+        let req = request.into_inner();
+        let path = self.dump_path(req.source)?;
+        let mut file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| Status::internal(format!("cannot open dump: {}", e)))?;
+        let total_size = file
+            .metadata()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .len();
+        if req.offset > total_size {
+            return Err(Status::out_of_range(format!(
+                "offset {} is beyond the end of the dump ({} bytes)",
+                req.offset, total_size
+            )));
+        }
+        let length = match req.length {
+            0 => total_size - req.offset,
+            n if n <= total_size - req.offset => n,
+            n => {
+                return Err(Status::out_of_range(format!(
+                    "range {}+{} is beyond the end of the dump ({} bytes)",
+                    req.offset, n, total_size
+                )))
+            }
+        };
+        let max_chunk = self
+            .max_message_size
+            .saturating_sub(limits::MESSAGE_OVERHEAD)
+            .max(1);
+        let chunk_size = match req.chunk_size as usize {
+            0 => limits::DEFAULT_CHUNK_SIZE,
+            n => n,
+        }
+        .min(max_chunk);
+
+        file.seek(std::io::SeekFrom::Start(req.offset))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let (tx, rx) = mpsc::channel(limits::STREAM_BUFFER);
+        tokio::spawn(async move {
+            let mut hasher = Sha256::new();
+            let mut offset = req.offset;
+            let mut remaining = length;
+            let mut buf = vec![0u8; chunk_size];
+            loop {
+                let want = remaining.min(chunk_size as u64) as usize;
+                let read = match file.read(&mut buf[..want]).await {
+                    Ok(0) if want > 0 => {
+                        let _ = tx
+                            .send(Err(Status::data_loss("dump shrank while being read")))
+                            .await;
+                        return;
+                    }
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = tx.send(Err(Status::internal(e.to_string()))).await;
+                        return;
+                    }
+                };
+                hasher.update(&buf[..read]);
+                remaining -= read as u64;
+                let last = remaining == 0;
+                let chunk = pb::DataChunk {
+                    offset,
+                    data: buf[..read].to_vec(),
+                    total_size,
+                    sha256: last.then(|| to_hex(&hasher.clone().finalize())),
+                };
+                offset += read as u64;
+                if tx.send(Ok(chunk)).await.is_err() || last {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn upload_image(
+        &self,
+        request: Request<Streaming<pb::UploadChunk>>,
+    ) -> Result<Response<pb::UploadImageResponse>, Status> {
+        use pb::upload_chunk::Payload;
+
+        self.authorize(&request, Permission::Artifacts)?;
+        let root = self.artifact_root()?.to_path_buf();
+        let mut stream = request.into_inner();
+        let header = match stream.message().await? {
+            Some(pb::UploadChunk {
+                payload: Some(Payload::Header(header)),
+            }) => header,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "first message must be an UploadHeader",
+                ))
+            }
+        };
+        if !is_safe_artifact_name(&header.name) {
+            return Err(Status::invalid_argument(format!(
+                "invalid file name: {}",
+                header.name
+            )));
+        }
+
+        let dir = root.join(limits::UPLOAD_DIR);
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let target = dir.join(&header.name);
+        if !header.overwrite && tokio::fs::try_exists(&target).await.unwrap_or(false) {
+            return Err(Status::already_exists(format!(
+                "{}/{} already exists",
+                limits::UPLOAD_DIR,
+                header.name
+            )));
+        }
+
+        // Stage next to the target so the final rename is atomic
+        let staging = dir.join(format!(".{}.partial", header.name));
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&staging)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    Status::aborted(format!("an upload of {} is in progress", header.name))
+                }
+                _ => Status::internal(e.to_string()),
+            })?;
+        let mut staged = StagingFile {
+            path: staging,
+            kept: false,
+        };
+
+        let received = async {
+            let mut hasher = Sha256::new();
+            let mut size = 0u64;
+            while let Some(chunk) = stream.message().await? {
+                let data = match chunk.payload {
+                    Some(Payload::Data(data)) => data,
+                    _ => return Err(Status::invalid_argument("expected image data")),
+                };
+                size += data.len() as u64;
+                if size > header.total_size {
+                    return Err(Status::invalid_argument(format!(
+                        "received more than the announced {} bytes",
+                        header.total_size
+                    )));
+                }
+                hasher.update(&data);
+                file.write_all(&data)
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))?;
+            }
+            file.sync_all()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            let sha256 = to_hex(&hasher.finalize());
+            if size != header.total_size {
+                return Err(Status::invalid_argument(format!(
+                    "received {} of {} bytes",
+                    size, header.total_size
+                )));
+            }
+            if let Some(expected) = &header.sha256 {
+                if !expected.eq_ignore_ascii_case(&sha256) {
+                    return Err(Status::data_loss(format!(
+                        "SHA-256 mismatch: expected {}, got {}",
+                        expected, sha256
+                    )));
+                }
+            }
+            Ok((size, sha256))
+        }
+        .await;
+
+        let (size, sha256) = received?;
+        tokio::fs::rename(&staged.path, &target)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        staged.kept = true;
+
+        Ok(Response::new(pb::UploadImageResponse {
+            path: format!("{}/{}", limits::UPLOAD_DIR, header.name),
+            size,
+            sha256,
+        }))
+    }
+}
+
+// ============================================================================
+// Transport
+// ============================================================================
+
+/// Listening gRPC server
+pub struct GrpcServer {
+    service: GrpcService,
+    config: GrpcConfig,
+    listener: TcpListener,
+}
+
+impl GrpcServer {
+    /// Bind `service` using the server's `config.grpc`
+    pub async fn bind(service: GrpcService) -> GrpcResult<Self> {
+        let config = lock_server(&service.server).config.grpc.clone();
+        Self::bind_with_config(service, config).await
+    }
+
+    /// Bind `service` with an explicit configuration
+    pub async fn bind_with_config(service: GrpcService, config: GrpcConfig) -> GrpcResult<Self> {
+        if config.tls && (config.cert_path.is_none() || config.key_path.is_none()) {
+            return Err(GrpcError::InvalidConfig(
+                "TLS requires cert_path and key_path".to_string(),
+            ));
+        }
+        if config.max_message_size <= limits::MESSAGE_OVERHEAD {
+            return Err(GrpcError::InvalidConfig(format!(
+                "max_message_size must exceed {} bytes",
+                limits::MESSAGE_OVERHEAD
+            )));
+        }
+        let service = GrpcService {
+            max_message_size: config.max_message_size,
+            ..service
+        };
+        let listener = TcpListener::bind((config.host.as_str(), config.port)).await?;
+        Ok(Self {
+            service,
+            config,
+            listener,
+        })
+    }
+
+    /// Address actually bound (useful with port 0)
+    pub fn local_addr(&self) -> GrpcResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve until the process exits
+    pub async fn serve(self) -> GrpcResult<()> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve until `shutdown` completes
+    pub async fn serve_with_shutdown<F>(self, shutdown: F) -> GrpcResult<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let mut builder = tonic::transport::Server::builder();
+        if self.config.tls {
+            let (cert, key) = match (&self.config.cert_path, &self.config.key_path) {
+                (Some(cert), Some(key)) => (cert, key),
+                _ => unreachable!("checked in bind_with_config"),
+            };
+            let cert = std::fs::read(cert)
+                .map_err(|e| GrpcError::Tls(format!("cannot read {}: {}", cert, e)))?;
+            let key = std::fs::read(key)
+                .map_err(|e| GrpcError::Tls(format!("cannot read {}: {}", key, e)))?;
+            builder = builder
+                .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+                .map_err(|e| GrpcError::Tls(e.to_string()))?;
+        }
+
+        let size = self.config.max_message_size;
+        let service = OpenFlashService::new(self.service)
+            .max_decoding_message_size(size)
+            .max_encoding_message_size(size);
+        let reflection = if self.config.reflection {
+            Some(
+                tonic_reflection::server::Builder::configure()
+                    .register_encoded_file_descriptor_set(pb::FILE_DESCRIPTOR_SET)
+                    .build_v1()
+                    .map_err(|e| GrpcError::Transport(e.to_string()))?,
+            )
+        } else {
+            None
+        };
+
+        builder
+            .add_service(service)
+            .add_optional_service(reflection)
+            .serve_with_incoming_shutdown(TcpListenerStream::new(self.listener), shutdown)
+            .await?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{OpenFlashServer, ServerConfig};
+    use pb::open_flash_client::OpenFlashClient;
+
+    fn shared() -> SharedServer {
+        Arc::new(Mutex::new(OpenFlashServer::new(ServerConfig::default())))
+    }
+
+    async fn start(
+        service: GrpcService,
+    ) -> (
+        OpenFlashClient<tonic::transport::Channel>,
+        tokio::sync::oneshot::Sender<()>,
+    ) {
+        let config = GrpcConfig {
+            port: 0,
+            ..Default::default()
+        };
+        let server = GrpcServer::bind_with_config(service, config).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(server.serve_with_shutdown(async {
+            let _ = stopped.await;
+        }));
+        let client = OpenFlashClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        (client, stop)
+    }
+
+    #[test]
+    fn test_submit_request_params() {
+        let req = pb::SubmitJobRequest {
+            name: "dump".to_string(),
+            job_type: "read".to_string(),
+            params: [
+                ("output_path".to_string(), "dump.bin".to_string()),
+                ("length".to_string(), "4096".to_string()),
+                ("include_oob".to_string(), "true".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let job = SubmitJobRequest::from(req).to_job().unwrap();
+        assert!(matches!(
+            job.job_type,
+            JobType::Read {
+                ref output_path,
+                length: Some(4096),
+                include_oob: true,
+                ..
+            } if output_path == "dump.bin"
+        ));
+        assert_eq!(
+            status_for(ServerError::QueueFull).code(),
+            tonic::Code::ResourceExhausted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jobs_and_watch_stream() {
+        let server = shared();
+        let (mut client, stop) = start(GrpcService::new(server.clone())).await;
+
+        let submitted = client
+            .submit_job(pb::SubmitJobRequest {
+                name: "erase".to_string(),
+                job_type: "erase".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut watch = client
+            .watch_job(pb::WatchJobRequest {
+                job_id: submitted.job_id,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let first = watch.message().await.unwrap().unwrap();
+        assert!(matches!(
+            first.event,
+            Some(pb::job_event::Event::Status(ref s)) if s.status == "queued"
+        ));
+
+        let cancelled = client
+            .cancel_job(pb::CancelJobRequest {
+                job_id: submitted.job_id,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(cancelled.status, "cancelled");
+        let last = watch.message().await.unwrap().unwrap();
+        assert!(matches!(
+            last.event,
+            Some(pb::job_event::Event::Status(ref s)) if s.status == "cancelled"
+        ));
+        // The stream ends once the job is finished
+        assert!(watch.message().await.unwrap().is_none());
+
+        let err = client
+            .get_job(pb::GetJobRequest { job_id: u64::MAX })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        let info = client
+            .get_server_info(pb::GetServerInfoRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.queue_stats.unwrap().cancelled_count, 1);
+        stop.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_then_read_dump() {
+        let dir = std::env::temp_dir().join(format!("openflash_grpc_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let service = GrpcService::new(shared()).with_artifact_root(&dir);
+        let (mut client, stop) = start(service).await;
+
+        let image: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let sha = to_hex(&Sha256::digest(&image));
+        let mut messages = vec![pb::UploadChunk {
+            payload: Some(pb::upload_chunk::Payload::Header(pb::UploadHeader {
+                name: "fw.bin".to_string(),
+                total_size: image.len() as u64,
+                sha256: Some(sha.clone()),
+                overwrite: false,
+            })),
+        }];
+        messages.extend(image.chunks(4096).map(|c| pb::UploadChunk {
+            payload: Some(pb::upload_chunk::Payload::Data(c.to_vec())),
+        }));
+        let uploaded = client
+            .upload_image(tokio_stream::iter(messages.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(uploaded.path, "uploads/fw.bin");
+        assert_eq!(uploaded.sha256, sha);
+        let again = client
+            .upload_image(tokio_stream::iter(messages))
+            .await
+            .unwrap_err();
+        assert_eq!(again.code(), tonic::Code::AlreadyExists);
+
+        let mut stream = client
+            .read_dump(pb::ReadDumpRequest {
+                source: Some(pb::read_dump_request::Source::Path(
+                    "uploads/fw.bin".to_string(),
+                )),
+                offset: 1000,
+                length: 5000,
+                chunk_size: 2048,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut data = Vec::new();
+        let mut digest = None;
+        while let Some(chunk) = stream.message().await.unwrap() {
+            assert_eq!(chunk.offset, 1000 + data.len() as u64);
+            data.extend_from_slice(&chunk.data);
+            digest = chunk.sha256;
+        }
+        assert_eq!(data, &image[1000..6000]);
+        assert_eq!(digest, Some(to_hex(&Sha256::digest(&image[1000..6000]))));
+
+        let escape = client
+            .read_dump(pb::ReadDumpRequest {
+                source: Some(pb::read_dump_request::Source::Path(
+                    "../etc/passwd".to_string(),
+                )),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(escape.code(), tonic::Code::NotFound);
+
+        stop.send(()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropped_upload_removes_staging_file() {
+        let dir = crate::test_support::scratch_dir("openflash_grpc_dropped_upload");
+        let service = GrpcService::new(shared()).with_artifact_root(&dir);
+        let (client, stop) = start(service).await;
+
+        let header = |overwrite| pb::UploadChunk {
+            payload: Some(pb::upload_chunk::Payload::Header(pb::UploadHeader {
+                name: "fw.bin".to_string(),
+                total_size: 8,
+                sha256: None,
+                overwrite,
+            })),
+        };
+        let data = pb::UploadChunk {
+            payload: Some(pb::upload_chunk::Payload::Data(vec![0xA5; 4])),
+        };
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(header(false)).await.unwrap();
+        tx.send(data.clone()).await.unwrap();
+        let mut uploader = client.clone();
+        let upload =
+            tokio::spawn(async move { uploader.upload_image(ReceiverStream::new(rx)).await });
+
+        // The client goes away half-way through the image
+        let staging = dir.join(limits::UPLOAD_DIR).join(".fw.bin.partial");
+        for _ in 0..100 {
+            if staging.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(staging.exists());
+        upload.abort();
+        drop(tx);
+        for _ in 0..100 {
+            if !staging.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!staging.exists());
+
+        let messages = vec![header(true), data.clone(), data];
+        let uploaded = client
+            .clone()
+            .upload_image(tokio_stream::iter(messages))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(uploaded.size, 8);
+
+        stop.send(()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rest_credentials_apply() {
+        use crate::server::RestApiConfig;
+
+        let config = ServerConfig {
+            rest: RestApiConfig {
+                auth: AuthMethod::ApiKey {
+                    header_name: "X-API-Key".to_string(),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        let service = GrpcService::new(server)
+            .with_credentials(Credentials::default().with_api_key("secret"));
+        let (mut client, stop) = start(service).await;
+
+        let request = |key: Option<&str>| {
+            let mut request = Request::new(pb::GetServerInfoRequest {});
+            if let Some(key) = key {
+                request
+                    .metadata_mut()
+                    .insert("x-api-key", key.parse().unwrap());
+            }
+            request
+        };
+        let err = client.get_server_info(request(None)).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        let err = client
+            .get_server_info(request(Some("nope")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        assert!(client
+            .get_server_info(request(Some("secret")))
+            .await
+            .is_ok());
+        stop.send(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_before_credentials() {
+        use crate::server::{RateLimitConfig, RestApiConfig};
+
+        let config = ServerConfig {
+            rest: RestApiConfig {
+                auth: AuthMethod::ApiKey {
+                    header_name: "X-API-Key".to_string(),
+                },
+                rate_limit: RateLimitConfig {
+                    requests_per_minute: 1,
+                    burst_size: 2,
+                    enabled: true,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = Arc::new(Mutex::new(OpenFlashServer::new(config)));
+        let service = GrpcService::new(server)
+            .with_credentials(Credentials::default().with_api_key("secret"));
+        let (mut client, stop) = start(service).await;
+
+        let request = |key: &str| {
+            let mut request = Request::new(pb::GetServerInfoRequest {});
+            request
+                .metadata_mut()
+                .insert("x-api-key", key.parse().unwrap());
+            request
+        };
+        let err = client.get_server_info(request("nope")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        let err = client.get_server_info(request("guess")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        // Guessing drained the peer's bucket, so even the right key waits
+        let err = client.get_server_info(request("secret")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        stop.send(()).unwrap();
+    }
+}
@@ -0,0 +1,1767 @@
+//! Job executor for the OpenFlash server
+//!
+//! Runs the jobs queued in an [`OpenFlashServer`](crate::server::OpenFlashServer)
+//! on the devices of its pool. Every [`PoolDevice`] gets a worker thread
+//! that takes the next job it can run, executes it through the device's
+//! [`DeviceTransport`] and reports back:
+//! - progress through `update_job_progress`, so REST, WebSocket and gRPC
+//!   clients see it
+//! - `timeout_secs` is checked between transfers; an expired job ends as
+//!   `JobStatus::TimedOut`
+//! - a job cancelled through the server stops at the next transfer
+//! - read dumps, analysis reports and a JSON record of every finished job
+//!   are written to the artifact directory
+//! - `callback_url` receives the final job status as a JSON `POST`
+//!
+//! Workers keep their device's transport open between jobs in the
+//! executor's [`Connections`], which health checks share so that a device
+//! is never opened twice.
+//!
+//! Transports are created by a [`TransportFactory`]; [`connect_uri`]
+//! opens programmers at `serial://` and `tcp://` URIs and provides the
+//! in-memory `mem://` chip used for dry runs. On raw NAND chips
+//! ([`DeviceTransport::nand`]) write jobs run through [`ChipProgrammer`]
+//! and clones through [`ChipCloner`], so bad blocks are skipped and each
+//! block is erased once; erase jobs leave bad blocks alone on every chip.
+
+use crate::ai::AiAnalyzer;
+use crate::programmer::{ProgrammerLink, ProgrammerTransport};
+use crate::server::{
+    is_safe_artifact_name, lock_server, resolve_artifact, DeviceStatus, Job, JobResult,
+    JobStatusResponse, JobType, PoolDevice, ServerError, SharedServer,
+};
+use crate::write_ops::{
+    ChipCloner, ChipProgrammer, ProgramImage, ProgramJob, ProgramOptions, ProgramTarget, WriteError,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Executor limits and defaults
+pub mod limits {
+    /// Bytes moved per transport call
+    pub const CHUNK_SIZE: usize = 64 * 1024;
+    /// How often idle workers look for new jobs and devices (ms)
+    pub const POLL_INTERVAL_MS: u64 = 100;
+    /// Connect/read/write timeout for webhook requests (s)
+    pub const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+    /// Directory inside the artifact root holding finished job records
+    pub const JOBS_DIR: &str = "jobs";
+    /// Erase block size of `mem://` chips
+    pub const MEM_ERASE_SIZE: u64 = 4096;
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Job execution errors
+#[derive(Debug)]
+pub enum ExecError {
+    /// The device transport could not be opened
+    Connect(String),
+    /// Device communication failed
+    Transport(String),
+    /// The transport cannot perform this operation
+    Unsupported(String),
+    /// Input or output file problem
+    Artifact(String),
+    /// Read-back did not match the expected data
+    VerifyFailed { address: u64 },
+    /// Requested range does not fit the chip
+    OutOfRange {
+        address: u64,
+        length: u64,
+        capacity: u64,
+    },
+    /// Job was cancelled through the server
+    Cancelled,
+    /// Job ran past its `timeout_secs`
+    TimedOut,
+    /// Executor is shutting down
+    Stopped,
+    /// Server rejected a state change
+    Server(ServerError),
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect(s) => write!(f, "Cannot connect to device: {}", s),
+            Self::Transport(s) => write!(f, "Transport error: {}", s),
+            Self::Unsupported(s) => write!(f, "Unsupported: {}", s),
+            Self::Artifact(s) => write!(f, "Artifact error: {}", s),
+            Self::VerifyFailed { address } => write!(f, "Verify failed at 0x{:X}", address),
+            Self::OutOfRange {
+                address,
+                length,
+                capacity,
+            } => write!(
+                f,
+                "Range 0x{:X}+0x{:X} exceeds chip capacity 0x{:X}",
+                address, length, capacity
+            ),
+            Self::Cancelled => write!(f, "Job cancelled"),
+            Self::TimedOut => write!(f, "Job timed out"),
+            Self::Stopped => write!(f, "Executor stopped"),
+            Self::Server(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+impl From<ServerError> for ExecError {
+    fn from(e: ServerError) -> Self {
+        Self::Server(e)
+    }
+}
+
+pub type ExecResult<T> = Result<T, ExecError>;
+
+// ============================================================================
+// Device Transport
+// ============================================================================
+
+/// Byte-addressed access to the chip attached to a pool device
+///
+/// With `include_oob`, addresses cover the raw array (each page followed by
+/// its spare area); chips without a spare area may ignore the flag.
+pub trait DeviceTransport: Send {
+    /// Chip size in bytes
+    fn capacity(&mut self, include_oob: bool) -> ExecResult<u64>;
+    /// Erase granularity in bytes
+    fn erase_size(&mut self) -> ExecResult<u64>;
+    /// Fill `buf` from `address`
+    fn read(&mut self, address: u64, buf: &mut [u8], include_oob: bool) -> ExecResult<()>;
+    /// Replace the contents at `address` with `data`, erasing as the chip
+    /// requires
+    fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()>;
+    /// Erase `length` bytes from `address` (both erase-size aligned)
+    fn erase(&mut self, address: u64, length: u64) -> ExecResult<()>;
+    /// Identify the attached chip (None = the transport cannot tell)
+    fn chip_name(&mut self) -> ExecResult<Option<String>> {
+        Ok(None)
+    }
+    /// Erase blocks the device knows to be bad
+    fn bad_blocks(&mut self) -> ExecResult<Vec<u64>> {
+        Ok(Vec::new())
+    }
+    /// ECC bit corrections made by reads since the last call
+    fn take_ecc_corrections(&mut self) -> ExecResult<u32> {
+        Ok(0)
+    }
+    /// Run a device-specific command for `JobType::Custom`
+    fn custom(
+        &mut self,
+        command: &str,
+        _params: &HashMap<String, String>,
+    ) -> ExecResult<HashMap<String, String>> {
+        Err(ExecError::Unsupported(format!("command '{}'", command)))
+    }
+    /// Page access to a raw NAND chip (None = byte-addressed chip)
+    fn nand(&mut self) -> Option<&mut dyn NandDevice> {
+        None
+    }
+}
+
+/// Raw NAND chip behind a [`DeviceTransport`], driven page by page
+pub trait NandDevice: ProgramTarget {
+    /// Programming engine sized for the chip
+    fn chip_programmer(&self) -> ChipProgrammer;
+}
+
+/// Opens the transport for a pool device
+pub type TransportFactory =
+    Arc<dyn Fn(&PoolDevice) -> ExecResult<Box<dyn DeviceTransport>> + Send + Sync>;
+
+/// Delivers the final status of a job to its `callback_url`
+pub type WebhookSender = Arc<dyn Fn(&str, &JobStatusResponse) -> ExecResult<()> + Send + Sync>;
+
+/// In-memory chip, erased to 0xFF
+///
+/// Clones share the same contents. `latency` is added to every transport
+/// call to emulate a slow device.
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    data: Arc<Mutex<Vec<u8>>>,
+    erase_size: u64,
+    latency: Duration,
+}
+
+impl MemoryTransport {
+    /// Erased chip of `capacity` bytes
+    pub fn new(capacity: usize, erase_size: u64) -> Self {
+        Self {
+            data: Arc::new(Mutex::new(vec![0xFF; capacity])),
+            erase_size: erase_size.max(1),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Delay every transport call by `latency`
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Copy of the chip contents
+    pub fn contents(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn range(&self, address: u64, length: u64) -> ExecResult<std::ops::Range<usize>> {
+        std::thread::sleep(self.latency);
+        let capacity = self.lock().len() as u64;
+        match address.checked_add(length) {
+            Some(end) if end <= capacity => Ok(address as usize..end as usize),
+            _ => Err(ExecError::OutOfRange {
+                address,
+                length,
+                capacity,
+            }),
+        }
+    }
+}
+
+impl DeviceTransport for MemoryTransport {
+    fn capacity(&mut self, _include_oob: bool) -> ExecResult<u64> {
+        Ok(self.lock().len() as u64)
+    }
+
+    fn erase_size(&mut self) -> ExecResult<u64> {
+        Ok(self.erase_size)
+    }
+
+    fn read(&mut self, address: u64, buf: &mut [u8], _include_oob: bool) -> ExecResult<()> {
+        let range = self.range(address, buf.len() as u64)?;
+        buf.copy_from_slice(&self.lock()[range]);
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+        let range = self.range(address, data.len() as u64)?;
+        self.lock()[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+        if address % self.erase_size != 0 || length % self.erase_size != 0 {
+            return Err(ExecError::Transport(format!(
+                "erase 0x{:X}+0x{:X} is not aligned to 0x{:X}",
+                address, length, self.erase_size
+            )));
+        }
+        let range = self.range(address, length)?;
+        self.lock()[range].fill(0xFF);
+        Ok(())
+    }
+}
+
+/// Transport for the URI schemes known to the core library
+///
+/// `serial://<path>` (USB CDC-ACM) and `tcp://<host>:<port>` open the NAND
+/// chip on a programmer ([`ProgrammerTransport`]); `mem://<bytes>` creates
+/// an erased in-memory chip of that size. Other schemes need an
+/// application-supplied [`TransportFactory`].
+pub fn connect_uri(device: &PoolDevice) -> ExecResult<Box<dyn DeviceTransport>> {
+    match device.uri.split_once("://") {
+        Some(("serial", _)) | Some(("tcp", _)) => {
+            let link = ProgrammerLink::connect(&device.uri)?;
+            Ok(Box::new(ProgrammerTransport::open(link)?))
+        }
+        Some(("mem", size)) => {
+            let size = size
+                .trim_end_matches('/')
+                .parse::<usize>()
+                .map_err(|_| ExecError::Transport(format!("invalid size in {}", device.uri)))?;
+            Ok(Box::new(MemoryTransport::new(size, limits::MEM_ERASE_SIZE)))
+        }
+        _ => Err(ExecError::Unsupported(format!(
+            "no transport for {}",
+            device.uri
+        ))),
+    }
+}
+
+// ============================================================================
+// Webhooks
+// ============================================================================
+
+/// `POST` the job status as JSON to a plain `http://` URL
+///
+/// Any 2xx response counts as delivered. HTTPS callbacks need a custom
+/// [`WebhookSender`].
+pub fn post_webhook(url: &str, status: &JobStatusResponse) -> ExecResult<()> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        ExecError::Unsupported(format!("callback URL {} (only http:// is supported)", url))
+    })?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let timeout = Duration::from_secs(limits::WEBHOOK_TIMEOUT_SECS);
+    let io = |e: std::io::Error| ExecError::Transport(format!("callback {}: {}", url, e));
+
+    let addr = addr
+        .to_socket_addrs()
+        .map_err(io)?
+        .next()
+        .ok_or_else(|| ExecError::Transport(format!("callback {}: no address", url)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(io)?;
+    stream.set_read_timeout(Some(timeout)).map_err(io)?;
+    stream.set_write_timeout(Some(timeout)).map_err(io)?;
+
+    let body = serde_json::to_vec(status).map_err(|e| ExecError::Transport(e.to_string()))?;
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        path,
+        authority,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).map_err(io)?;
+    stream.write_all(&body).map_err(io)?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 256];
+    while !response.contains(&b'\n') {
+        match stream.read(&mut buf).map_err(io)? {
+            0 => break,
+            n => response.extend_from_slice(&buf[..n]),
+        }
+    }
+    let status_line = String::from_utf8_lossy(&response);
+    let code = status_line.split_whitespace().nth(1).unwrap_or("");
+    if code.starts_with('2') && code.len() == 3 {
+        Ok(())
+    } else {
+        Err(ExecError::Transport(format!(
+            "callback {} answered {}",
+            url,
+            status_line.lines().next().unwrap_or("nothing")
+        )))
+    }
+}
+
+// ============================================================================
+// Executor
+// ============================================================================
+
+/// Worker runtime executing queued jobs on the device pool
+pub struct JobExecutor {
+    server: SharedServer,
+    factory: TransportFactory,
+    artifact_root: Option<PathBuf>,
+    webhook: WebhookSender,
+    poll_interval: Duration,
+    connections: Connections,
+}
+
+impl JobExecutor {
+    /// Execute jobs of `server`, opening device transports with `factory`
+    pub fn new(server: SharedServer, factory: TransportFactory) -> Self {
+        Self {
+            server,
+            factory,
+            artifact_root: None,
+            webhook: Arc::new(post_webhook),
+            poll_interval: Duration::from_millis(limits::POLL_INTERVAL_MS),
+            connections: Connections::default(),
+        }
+    }
+
+    /// Transports the workers will keep open, for health checks
+    pub fn connections(&self) -> Connections {
+        self.connections.clone()
+    }
+
+    /// Directory for job input and output files and job records; jobs that
+    /// use files fail without it
+    pub fn with_artifact_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.artifact_root = Some(root.into());
+        self
+    }
+
+    /// Replace the default [`post_webhook`] callback delivery
+    pub fn with_webhook<F>(mut self, sender: F) -> Self
+    where
+        F: Fn(&str, &JobStatusResponse) -> ExecResult<()> + Send + Sync + 'static,
+    {
+        self.webhook = Arc::new(sender);
+        self
+    }
+
+    /// How often idle workers poll for jobs and new devices
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Start the dispatcher thread, which keeps one worker per pool device
+    pub fn start(self) -> ExecutorHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let shared = Arc::new(Shared {
+            server: self.server,
+            factory: self.factory,
+            artifact_root: self.artifact_root,
+            webhook: self.webhook,
+            poll_interval: self.poll_interval,
+            connections: self.connections,
+            stop: stop.clone(),
+        });
+        let dispatcher = std::thread::Builder::new()
+            .name("openflash-executor".to_string())
+            .spawn(move || dispatch(shared))
+            .expect("failed to spawn executor thread");
+        ExecutorHandle {
+            stop,
+            dispatcher: Some(dispatcher),
+        }
+    }
+}
+
+impl std::fmt::Debug for JobExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobExecutor")
+            .field("artifact_root", &self.artifact_root)
+            .field("poll_interval", &self.poll_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+type Slot = Arc<Mutex<Option<Box<dyn DeviceTransport>>>>;
+
+/// Transports the workers of a [`JobExecutor`] keep open, one per device
+///
+/// A worker holds its device's slot for the length of a job. Pinging
+/// through [`Self::ping`] instead of opening a second transport keeps
+/// health checks off devices that are busy, off single-connection bridges
+/// that would refuse them and off serial links they would interleave with.
+#[derive(Clone, Default)]
+pub struct Connections {
+    slots: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+impl Connections {
+    /// Read the chip size of `device` over its open transport, or over one
+    /// opened with `factory` and left for its worker; None while a job is
+    /// using the device
+    pub fn ping(&self, device: &PoolDevice, factory: &TransportFactory) -> Option<ExecResult<u64>> {
+        let slot = self.slot(&device.id);
+        let mut transport = match slot.try_lock() {
+            Ok(transport) => transport,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        if transport.is_none() {
+            match factory(device) {
+                Ok(opened) => *transport = Some(opened),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        let size = transport
+            .as_deref_mut()
+            .expect("transport connected")
+            .capacity(false);
+        if size.is_err() {
+            // Reconnect next time
+            *transport = None;
+        }
+        Some(size)
+    }
+
+    fn slot(&self, device_id: &str) -> Slot {
+        lock(&self.slots)
+            .entry(device_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn remove(&self, device_id: &str) {
+        lock(&self.slots).remove(device_id);
+    }
+}
+
+impl std::fmt::Debug for Connections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut devices: Vec<String> = lock(&self.slots).keys().cloned().collect();
+        devices.sort();
+        f.debug_struct("Connections")
+            .field("devices", &devices)
+            .finish()
+    }
+}
+
+/// Running executor; stops when shut down or dropped
+#[derive(Debug)]
+pub struct ExecutorHandle {
+    stop: Arc<AtomicBool>,
+    dispatcher: Option<JoinHandle<()>>,
+}
+
+impl ExecutorHandle {
+    /// Stop all workers and wait for them; running jobs are interrupted at
+    /// the next transfer and re-queued without using up a retry or counting
+    /// against their device
+    pub fn shutdown(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(dispatcher) = self.dispatcher.take() {
+            let _ = dispatcher.join();
+        }
+    }
+}
+
+impl Drop for ExecutorHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// State shared by the dispatcher and all workers
+struct Shared {
+    server: SharedServer,
+    factory: TransportFactory,
+    artifact_root: Option<PathBuf>,
+    webhook: WebhookSender,
+    poll_interval: Duration,
+    connections: Connections,
+    stop: Arc<AtomicBool>,
+}
+
+impl Shared {
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    fn root(&self) -> ExecResult<&Path> {
+        self.artifact_root
+            .as_deref()
+            .ok_or_else(|| ExecError::Artifact("artifact directory is not configured".to_string()))
+    }
+
+    /// Existing file inside the artifact directory
+    fn input_path(&self, relative: &str) -> ExecResult<PathBuf> {
+        resolve_artifact(self.root()?, &path_components(relative)?)
+            .ok_or_else(|| ExecError::Artifact(format!("{} not found", relative)))
+    }
+
+    /// File to create inside the artifact directory, with its parent created
+    fn output_path(&self, relative: &str) -> ExecResult<PathBuf> {
+        let path = path_components(relative)?
+            .iter()
+            .fold(self.root()?.to_path_buf(), |path, c| path.join(c));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| artifact_error(relative, e))?;
+        }
+        Ok(path)
+    }
+
+    fn connect(&self, device_id: &str) -> ExecResult<Box<dyn DeviceTransport>> {
+        let device = lock_server(&self.server)
+            .device_pool
+            .get_device(device_id)
+            .cloned()
+            .ok_or_else(|| ServerError::DeviceNotFound(device_id.to_string()))?;
+        (self.factory)(&device)
+    }
+}
+
+fn path_components(relative: &str) -> ExecResult<Vec<&str>> {
+    let components: Vec<&str> = relative
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    if components.is_empty() || !components.iter().all(|c| is_safe_artifact_name(c)) {
+        return Err(ExecError::Artifact(format!(
+            "invalid artifact path: {}",
+            relative
+        )));
+    }
+    Ok(components)
+}
+
+fn artifact_error(path: &str, e: std::io::Error) -> ExecError {
+    ExecError::Artifact(format!("{}: {}", path, e))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Keep one worker per pool device until stopped
+fn dispatch(shared: Arc<Shared>) {
+    let mut workers: HashMap<String, JoinHandle<()>> = HashMap::new();
+    while !shared.stopped() {
+        let devices: Vec<String> = lock_server(&shared.server)
+            .device_pool
+            .devices
+            .keys()
+            .cloned()
+            .collect();
+        workers.retain(|_, worker| !worker.is_finished());
+        for device_id in devices {
+            if workers.contains_key(&device_id) {
+                continue;
+            }
+            let worker = Worker {
+                shared: shared.clone(),
+                device_id: device_id.clone(),
+                slot: shared.connections.slot(&device_id),
+                transport: None,
+            };
+            let spawned = std::thread::Builder::new()
+                .name(format!("openflash-worker-{}", device_id))
+                .spawn(move || worker.run());
+            if let Ok(handle) = spawned {
+                workers.insert(device_id, handle);
+            }
+        }
+        std::thread::sleep(shared.poll_interval);
+    }
+    for (_, worker) in workers {
+        let _ = worker.join();
+    }
+}
+
+// ============================================================================
+// Worker
+// ============================================================================
+
+/// Executes jobs for one pool device
+struct Worker {
+    shared: Arc<Shared>,
+    device_id: String,
+    /// Where the transport is kept between jobs
+    slot: Slot,
+    /// Taken from the slot during a job; dropped after a transport error
+    transport: Option<Box<dyn DeviceTransport>>,
+}
+
+/// Progress, cancellation and timeout bookkeeping of one job
+struct Run<'a> {
+    shared: &'a Shared,
+    job_id: u64,
+    deadline: Option<Instant>,
+    total: u64,
+    done: u64,
+    reported: u8,
+}
+
+impl Run<'_> {
+    /// Account for `bytes` of finished work, then check whether the job may
+    /// continue
+    fn advance(&mut self, bytes: u64) -> ExecResult<()> {
+        self.done += bytes;
+        if self.shared.stopped() {
+            return Err(ExecError::Stopped);
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(ExecError::TimedOut);
+        }
+        let progress = match self.total {
+            0 => 0,
+            // 100 is reserved for the completed job
+            total => (self.done.min(total) * 99 / total) as u8,
+        };
+        let mut server = lock_server(&self.shared.server);
+        if !server.job_queue.running.contains_key(&self.job_id) {
+            return Err(ExecError::Cancelled);
+        }
+        if progress != self.reported {
+            self.reported = progress;
+            server.update_job_progress(self.job_id, progress)?;
+        }
+        Ok(())
+    }
+}
+
+impl Worker {
+    fn run(mut self) {
+        while !self.shared.stopped() {
+            let job = {
+                let mut server = lock_server(&self.shared.server);
+                if server.device_pool.get_device(&self.device_id).is_none() {
+                    // Device left the pool
+                    self.shared.connections.remove(&self.device_id);
+                    return;
+                }
+                server.start_next_job(&self.device_id)
+            };
+            match job {
+                Some(job) => {
+                    // Health checks wait until the job is done with the device
+                    let slot = self.slot.clone();
+                    let mut connection = lock(&slot);
+                    self.transport = connection.take();
+                    self.execute(job);
+                    *connection = self.transport.take();
+                }
+                None => std::thread::sleep(self.shared.poll_interval),
+            }
+        }
+        // Release the device when the executor stops
+        lock(&self.slot).take();
+    }
+
+    fn execute(&mut self, job: Job) {
+        let shared = self.shared.clone();
+        let mut run = Run {
+            shared: &shared,
+            job_id: job.id,
+            deadline: (job.timeout_secs > 0)
+                .then(|| Instant::now() + Duration::from_secs(job.timeout_secs)),
+            total: 0,
+            done: 0,
+            reported: 0,
+        };
+        let outcome = self.perform(&job, &mut run).map(|mut result| {
+            // Corrections made by the job's reads, for the metrics, and the
+            // chip it ran on, for the access log
+            if let Some(transport) = self.transport.as_deref_mut() {
+                if let Ok(corrected) = transport.take_ecc_corrections() {
+                    result.ecc_corrections = result.ecc_corrections.saturating_add(corrected);
+                }
+                if let Ok(Some(chip)) = transport.chip_name() {
+                    result.data.entry("chip_id".to_string()).or_insert(chip);
+                }
+            }
+            result
+        });
+
+        let mut server = lock_server(&shared.server);
+        let finalized = match outcome {
+            Ok(result) => server.complete_job(job.id, result),
+            // The server has already moved the job to its history
+            Err(ExecError::Cancelled) => Ok(()),
+            Err(ExecError::TimedOut) => server.time_out_job(job.id),
+            // Shutting down is no fault of the job or the device
+            Err(ExecError::Stopped) => {
+                server.interrupt_job(job.id, &ExecError::Stopped.to_string())
+            }
+            Err(err) => {
+                if matches!(err, ExecError::Transport(_)) {
+                    // Reconnect for the next job
+                    self.transport = None;
+                }
+                let failed = server.fail_job(job.id, &err.to_string());
+                if matches!(err, ExecError::Connect(_)) {
+                    // Keep further jobs off the device until an operator
+                    // resets it
+                    let _ = server.set_device_status(&self.device_id, DeviceStatus::Error);
+                }
+                failed
+            }
+        };
+        // Cancelled while finishing up: nothing left to report
+        if finalized.is_err()
+            || server
+                .job_queue
+                .get_job(job.id)
+                .map_or(true, |j| !j.is_finished())
+        {
+            return;
+        }
+        let status = server.get_job_status(job.id);
+        drop(server);
+
+        if let (Some(url), Some(status)) = (&job.callback_url, status) {
+            if let Err(e) = (shared.webhook)(url, &status) {
+                let mut server = lock_server(&shared.server);
+                if let Some(job) = server
+                    .job_queue
+                    .completed
+                    .iter_mut()
+                    .find(|j| j.id == job.id)
+                {
+                    job.metadata
+                        .insert("callback_error".to_string(), e.to_string());
+                }
+            }
+        }
+        self.persist(job.id);
+    }
+
+    /// Write the finished job as `jobs/<id>.json` in the artifact directory
+    fn persist(&self, job_id: u64) {
+        let Some(root) = &self.shared.artifact_root else {
+            return;
+        };
+        let job = lock_server(&self.shared.server)
+            .job_queue
+            .get_job(job_id)
+            .cloned();
+        if let Some(job) = job {
+            let dir = root.join(limits::JOBS_DIR);
+            if let Ok(json) = serde_json::to_vec_pretty(&job) {
+                let _ = std::fs::create_dir_all(&dir)
+                    .and_then(|_| std::fs::write(dir.join(format!("{}.json", job_id)), json));
+            }
+        }
+    }
+
+    fn transport(&mut self) -> ExecResult<&mut dyn DeviceTransport> {
+        if self.transport.is_none() {
+            let transport = self
+                .shared
+                .connect(&self.device_id)
+                .map_err(|e| ExecError::Connect(e.to_string()))?;
+            self.transport = Some(transport);
+        }
+        Ok(self.transport.as_deref_mut().expect("transport connected"))
+    }
+
+    fn perform(&mut self, job: &Job, run: &mut Run) -> ExecResult<JobResult> {
+        match &job.job_type {
+            JobType::Read {
+                output_path,
+                start_address,
+                length,
+                include_oob,
+            } => self.read(run, output_path, *start_address, *length, *include_oob),
+            JobType::Write {
+                input_path,
+                start_address,
+                verify,
+            } => self.write(run, input_path, *start_address, *verify),
+            JobType::Erase {
+                start_address,
+                length,
+            } => self.erase(run, *start_address, *length),
+            JobType::Verify { file_path } => self.verify(run, file_path),
+            JobType::Analyze {
+                input_path,
+                output_path,
+                deep_scan,
+            } => self.analyze(run, input_path, output_path, *deep_scan),
+            JobType::Clone { target_device, .. } => self.clone_to(run, job.id, target_device),
+            JobType::Custom { command, params } => {
+                run.advance(0)?;
+                let data = self.transport()?.custom(command, params)?;
+                Ok(JobResult {
+                    data,
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    fn read(
+        &mut self,
+        run: &mut Run,
+        output_path: &str,
+        start: u64,
+        length: Option<u64>,
+        include_oob: bool,
+    ) -> ExecResult<JobResult> {
+        let path = self.shared.output_path(output_path)?;
+        let transport = self.transport()?;
+        let length = checked_range(transport.capacity(include_oob)?, start, length)?;
+        run.total = length;
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let staging = path.with_file_name(format!(".{}.partial", name));
+        let mut file =
+            std::fs::File::create(&staging).map_err(|e| artifact_error(output_path, e))?;
+        let copied = (|| {
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0u8; limits::CHUNK_SIZE];
+            let mut offset = 0;
+            while offset < length {
+                let n = (length - offset).min(buf.len() as u64) as usize;
+                transport.read(start + offset, &mut buf[..n], include_oob)?;
+                hasher.update(&buf[..n]);
+                file.write_all(&buf[..n])
+                    .map_err(|e| artifact_error(output_path, e))?;
+                offset += n as u64;
+                run.advance(n as u64)?;
+            }
+            file.sync_all()
+                .map_err(|e| artifact_error(output_path, e))?;
+            Ok(to_hex(&hasher.finalize()))
+        })();
+        let checksum = match copied {
+            Ok(checksum) => checksum,
+            Err(e) => {
+                let _ = std::fs::remove_file(&staging);
+                return Err(e);
+            }
+        };
+        std::fs::rename(&staging, &path).map_err(|e| artifact_error(output_path, e))?;
+
+        Ok(JobResult {
+            bytes_processed: length,
+            output_path: Some(output_path.to_string()),
+            checksum: Some(checksum),
+            ..Default::default()
+        })
+    }
+
+    fn write(
+        &mut self,
+        run: &mut Run,
+        input_path: &str,
+        start: u64,
+        verify: bool,
+    ) -> ExecResult<JobResult> {
+        let image = std::fs::read(self.shared.input_path(input_path)?)
+            .map_err(|e| artifact_error(input_path, e))?;
+        let transport = self.transport()?;
+        checked_range(transport.capacity(false)?, start, Some(image.len() as u64))?;
+        if transport.nand().is_some() {
+            run.total = image.len() as u64;
+            let job = program_nand(transport, &image, start, verify, |n| run.advance(n))?;
+            return Ok(JobResult {
+                bytes_processed: image.len() as u64,
+                blocks_processed: job.image_blocks,
+                bad_blocks: job.bad_blocks.iter().map(|&(block, _)| block).collect(),
+                checksum: Some(to_hex(&Sha256::digest(&image))),
+                ..Default::default()
+            });
+        }
+        run.total = image.len() as u64 * if verify { 2 } else { 1 };
+
+        let mut offset = start;
+        for chunk in image.chunks(limits::CHUNK_SIZE) {
+            transport.write(offset, chunk)?;
+            offset += chunk.len() as u64;
+            run.advance(chunk.len() as u64)?;
+        }
+        if verify {
+            compare(transport, run, start, &image)?;
+        }
+
+        Ok(JobResult {
+            bytes_processed: image.len() as u64,
+            checksum: Some(to_hex(&Sha256::digest(&image))),
+            ..Default::default()
+        })
+    }
+
+    fn erase(&mut self, run: &mut Run, start: u64, length: Option<u64>) -> ExecResult<JobResult> {
+        let transport = self.transport()?;
+        let erase_size = transport.erase_size()?.max(1);
+        let capacity = transport.capacity(false)?;
+        if start % erase_size != 0 {
+            return Err(ExecError::Transport(format!(
+                "start address 0x{:X} is not aligned to the 0x{:X} erase size",
+                start, erase_size
+            )));
+        }
+        let length = checked_range(capacity, start, length)?;
+        // Round up to whole erase blocks
+        let length = ((length + erase_size - 1) / erase_size * erase_size).min(capacity - start);
+        run.total = length;
+        let bad: HashSet<u64> = transport.bad_blocks()?.into_iter().collect();
+
+        let step = erase_size * (limits::CHUNK_SIZE as u64 / erase_size).max(1);
+        let mut skipped = Vec::new();
+        let mut offset = 0;
+        while offset < length {
+            let n = (length - offset).min(step);
+            skipped.extend(erase_good_blocks(transport, start + offset, n, &bad)?);
+            offset += n;
+            run.advance(n)?;
+        }
+
+        Ok(JobResult {
+            bytes_processed: length,
+            blocks_processed: (length / erase_size) as u32 - skipped.len() as u32,
+            bad_blocks: skipped
+                .iter()
+                .map(|&address| (address / erase_size) as u32)
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn verify(&mut self, run: &mut Run, file_path: &str) -> ExecResult<JobResult> {
+        let expected = std::fs::read(self.shared.input_path(file_path)?)
+            .map_err(|e| artifact_error(file_path, e))?;
+        let transport = self.transport()?;
+        checked_range(transport.capacity(false)?, 0, Some(expected.len() as u64))?;
+        run.total = expected.len() as u64;
+        compare(transport, run, 0, &expected)?;
+
+        Ok(JobResult {
+            bytes_processed: expected.len() as u64,
+            checksum: Some(to_hex(&Sha256::digest(&expected))),
+            ..Default::default()
+        })
+    }
+
+    fn analyze(
+        &mut self,
+        run: &mut Run,
+        input_path: &str,
+        output_path: &str,
+        deep_scan: bool,
+    ) -> ExecResult<JobResult> {
+        let data = std::fs::read(self.shared.input_path(input_path)?)
+            .map_err(|e| artifact_error(input_path, e))?;
+        let output = self.shared.output_path(output_path)?;
+        run.total = data.len() as u64;
+        run.advance(0)?;
+
+        let report = AiAnalyzer::default()
+            .with_deep_scan(deep_scan)
+            .analyze_v14(&data);
+        let json = serde_json::to_vec_pretty(&report)
+            .map_err(|e| ExecError::Artifact(format!("{}: {}", output_path, e)))?;
+        std::fs::write(&output, json).map_err(|e| artifact_error(output_path, e))?;
+        run.advance(data.len() as u64)?;
+
+        let mut result_data = HashMap::new();
+        result_data.insert("summary".to_string(), report.base.summary.clone());
+        result_data.insert(
+            "data_quality_score".to_string(),
+            report.base.data_quality_score.to_string(),
+        );
+        Ok(JobResult {
+            bytes_processed: data.len() as u64,
+            output_path: Some(output_path.to_string()),
+            data: result_data,
+            ..Default::default()
+        })
+    }
+
+    /// Copy this device's chip onto `target_device`, which is held busy for
+    /// the duration of the clone
+    fn clone_to(
+        &mut self,
+        run: &mut Run,
+        job_id: u64,
+        target_device: &str,
+    ) -> ExecResult<JobResult> {
+        if target_device == self.device_id {
+            return Err(ExecError::Server(ServerError::InvalidRequest(
+                "clone source and target are the same device".to_string(),
+            )));
+        }
+        {
+            let mut server = lock_server(&self.shared.server);
+            let target = server
+                .device_pool
+                .get_device(target_device)
+                .ok_or_else(|| ServerError::DeviceNotFound(target_device.to_string()))?;
+            if !target.is_available() {
+                return Err(ServerError::DeviceBusy(target_device.to_string()).into());
+            }
+            server.set_device_status(target_device, DeviceStatus::Busy)?;
+            if let Some(target) = server.device_pool.get_device_mut(target_device) {
+                target.current_job = Some(job_id);
+            }
+        }
+
+        let copied = self.copy_to(run, target_device);
+
+        let mut server = lock_server(&self.shared.server);
+        if let Some(target) = server.device_pool.get_device_mut(target_device) {
+            target.current_job = None;
+            match &copied {
+                Ok(result) => {
+                    target.jobs_completed += 1;
+                    target.bytes_processed += result.bytes_processed;
+                }
+                Err(_) => target.error_count += 1,
+            }
+            let _ = server.set_device_status(target_device, DeviceStatus::Available);
+        }
+        copied
+    }
+
+    fn copy_to(&mut self, run: &mut Run, target_device: &str) -> ExecResult<JobResult> {
+        let mut target = self.shared.connect(target_device)?;
+        let source = self.transport()?;
+        if target.nand().is_some() {
+            return clone_nand(source, target.as_mut(), run, target_device);
+        }
+        let capacity = source.capacity(false)?;
+        let target_capacity = target.capacity(false)?;
+        if target_capacity < capacity {
+            return Err(ExecError::OutOfRange {
+                address: 0,
+                length: capacity,
+                capacity: target_capacity,
+            });
+        }
+        // Read, write and read back
+        run.total = capacity * 3;
+
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; limits::CHUNK_SIZE];
+        let mut readback = vec![0u8; limits::CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < capacity {
+            let n = (capacity - offset).min(buf.len() as u64) as usize;
+            source.read(offset, &mut buf[..n], false)?;
+            run.advance(n as u64)?;
+            target.write(offset, &buf[..n])?;
+            run.advance(n as u64)?;
+            target.read(offset, &mut readback[..n], false)?;
+            if let Some(i) = (0..n).find(|&i| buf[i] != readback[i]) {
+                return Err(ExecError::VerifyFailed {
+                    address: offset + i as u64,
+                });
+            }
+            hasher.update(&buf[..n]);
+            offset += n as u64;
+            run.advance(n as u64)?;
+        }
+
+        let mut data = HashMap::new();
+        data.insert("target_device".to_string(), target_device.to_string());
+        Ok(JobResult {
+            bytes_processed: capacity,
+            checksum: Some(to_hex(&hasher.finalize())),
+            data,
+            ..Default::default()
+        })
+    }
+}
+
+/// Erase `start..start+length` (erase-size aligned) except the blocks in
+/// `bad`, given by address; returns the bad blocks left alone
+pub(crate) fn erase_good_blocks(
+    transport: &mut dyn DeviceTransport,
+    start: u64,
+    length: u64,
+    bad: &HashSet<u64>,
+) -> ExecResult<Vec<u64>> {
+    let erase_size = transport.erase_size()?.max(1);
+    let end = start + length;
+    let mut skipped = Vec::new();
+    let mut good_from = start;
+    let mut block = start;
+    while block < end {
+        if bad.contains(&block) {
+            if good_from < block {
+                transport.erase(good_from, block - good_from)?;
+            }
+            skipped.push(block);
+            good_from = block + erase_size;
+        }
+        block += erase_size;
+    }
+    if good_from < end {
+        transport.erase(good_from, end - good_from)?;
+    }
+    Ok(skipped)
+}
+
+/// Program `image` at `start` (erase-block aligned) through
+/// [`ChipProgrammer`], which erases, programs and verifies block by block
+/// and shifts the image past bad blocks
+///
+/// `progress` gets the bytes programmed since its last call, after every
+/// block; an error from it stops programming and is returned. The
+/// finished job lists the blocks that went bad.
+pub(crate) fn program_nand<F>(
+    transport: &mut dyn DeviceTransport,
+    image: &[u8],
+    start: u64,
+    verify: bool,
+    mut progress: F,
+) -> ExecResult<ProgramJob>
+where
+    F: FnMut(u64) -> ExecResult<()>,
+{
+    let erase_size = transport.erase_size()?.max(1);
+    if start % erase_size != 0 {
+        return Err(ExecError::Transport(format!(
+            "start address 0x{:X} is not aligned to the 0x{:X} erase size",
+            start, erase_size
+        )));
+    }
+    let markers: Vec<(u32, u8)> = transport
+        .bad_blocks()?
+        .iter()
+        .map(|&address| ((address / erase_size) as u32, 0x00))
+        .collect();
+    let nand = transport
+        .nand()
+        .ok_or_else(|| ExecError::Unsupported("page access to the chip".to_string()))?;
+    let mut programmer = nand.chip_programmer();
+    programmer.set_options(ProgramOptions {
+        verify,
+        // Check for cancellation and timeouts after every block
+        progress_interval: 1,
+        ..Default::default()
+    });
+    programmer
+        .bad_block_table_mut()
+        .scan_factory_bad_blocks(&markers);
+
+    let program = ProgramImage::new(image).at_block((start / erase_size) as u32);
+    let mut job = programmer.plan(&program).map_err(|e| match e {
+        WriteError::DataSizeMismatch { expected, actual } => ExecError::OutOfRange {
+            address: start,
+            length: actual as u64,
+            capacity: start + expected as u64,
+        },
+        e => engine_error(e, None),
+    })?;
+    let mut reported = 0;
+    let mut stopped = None;
+    programmer
+        .program_image(nand, &program, &mut job, |status| {
+            let written = status.bytes_written.min(image.len() as u64);
+            let advanced = progress(written - reported);
+            reported = written;
+            advanced.map_err(|e| stopped = Some(e)).is_ok()
+        })
+        .map_err(|e| engine_error(e, stopped.take()))?;
+    Ok(job)
+}
+
+/// Copy the `source` NAND chip onto the `target` NAND chip through
+/// [`ChipCloner`]: source bad blocks are left out and target bad blocks
+/// skipped
+///
+/// A watcher aborts the clone through its [`CloneAbortHandle`] as soon as
+/// the job is cancelled, times out or the executor stops, without waiting
+/// for the block in progress.
+///
+/// [`CloneAbortHandle`]: crate::write_ops::CloneAbortHandle
+fn clone_nand(
+    source: &mut dyn DeviceTransport,
+    target: &mut dyn DeviceTransport,
+    run: &mut Run,
+    target_device: &str,
+) -> ExecResult<JobResult> {
+    let (Some(source), Some(target)) = (source.nand(), target.nand()) else {
+        return Err(ExecError::Unsupported(
+            "clone onto a NAND chip from a byte-addressed one".to_string(),
+        ));
+    };
+    let (from, to) = (source.chip_programmer(), target.chip_programmer());
+    let (from, to) = (from.geometry(), to.geometry());
+    let cloner = ChipCloner::new(
+        from.page_size,
+        from.pages_per_block,
+        from.total_blocks(),
+        to.page_size,
+        to.pages_per_block,
+        to.total_blocks(),
+    )
+    .map_err(|e| engine_error(e, None))?
+    .with_oob_sizes(from.oob_size, to.oob_size);
+
+    let (shared, job_id, deadline) = (run.shared, run.job_id, run.deadline);
+    let abort = cloner.abort_handle();
+    let finished = AtomicBool::new(false);
+    let mut reported = 0;
+    let mut stopped = None;
+    let result = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !finished.load(Ordering::SeqCst) {
+                let cancelled = !lock_server(&shared.server)
+                    .job_queue
+                    .running
+                    .contains_key(&job_id);
+                if cancelled || shared.stopped() || deadline.is_some_and(|d| Instant::now() >= d) {
+                    abort.abort();
+                    return;
+                }
+                std::thread::sleep(shared.poll_interval);
+            }
+        });
+        let result = cloner.clone_chip(source, target, |progress| {
+            run.total = progress.total_bytes;
+            let advanced = run.advance(progress.bytes_cloned - reported);
+            reported = progress.bytes_cloned;
+            advanced.map_err(|e| stopped = Some(e)).is_ok()
+        });
+        finished.store(true, Ordering::SeqCst);
+        result
+    });
+    let report = result.map_err(|e| {
+        // An abort from the watcher: find out why
+        let stopped = stopped.or_else(|| run.advance(0).err());
+        engine_error(e, stopped)
+    })?;
+
+    let mut data = HashMap::new();
+    data.insert("target_device".to_string(), target_device.to_string());
+    data.insert(
+        "source_bad_blocks".to_string(),
+        report.source_bad_blocks.len().to_string(),
+    );
+    Ok(JobResult {
+        bytes_processed: report.progress.bytes_cloned,
+        blocks_processed: report.mapping.len() as u32,
+        bad_blocks: report.target_bad_blocks,
+        data,
+        ..Default::default()
+    })
+}
+
+/// Executor error for a [`ChipProgrammer`]/[`ChipCloner`] failure; a
+/// cancel requested by the progress callback reports why the job stopped
+fn engine_error(e: WriteError, stopped: Option<ExecError>) -> ExecError {
+    match e {
+        WriteError::Cancelled => stopped.unwrap_or(ExecError::Cancelled),
+        WriteError::IoError(s) => ExecError::Transport(s),
+        e => ExecError::Transport(e.to_string()),
+    }
+}
+
+/// Length of `start..start+length` (default: to the end of the chip),
+/// checked against `capacity`
+fn checked_range(capacity: u64, start: u64, length: Option<u64>) -> ExecResult<u64> {
+    let length = length.unwrap_or(capacity.saturating_sub(start));
+    match start.checked_add(length) {
+        Some(end) if end <= capacity => Ok(length),
+        _ => Err(ExecError::OutOfRange {
+            address: start,
+            length,
+            capacity,
+        }),
+    }
+}
+
+/// Read the chip back from `start` and compare it with `expected`
+fn compare(
+    transport: &mut dyn DeviceTransport,
+    run: &mut Run,
+    start: u64,
+    expected: &[u8],
+) -> ExecResult<()> {
+    let mut buf = vec![0u8; limits::CHUNK_SIZE];
+    let mut offset = 0;
+    for chunk in expected.chunks(limits::CHUNK_SIZE) {
+        let actual = &mut buf[..chunk.len()];
+        transport.read(start + offset, actual, false)?;
+        if let Some(i) = (0..chunk.len()).find(|&i| actual[i] != chunk[i]) {
+            return Err(ExecError::VerifyFailed {
+                address: start + offset + i as u64,
+            });
+        }
+        offset += chunk.len() as u64;
+        run.advance(chunk.len() as u64)?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::JobStatus;
+    use crate::test_support::{
+        chip_factory, scratch_dir, server_with_devices, MemoryNand, NAND_BLOCK,
+    };
+
+    fn submit(server: &SharedServer, job: Job) -> u64 {
+        lock_server(server).submit_job(job).unwrap()
+    }
+
+    fn wait_for(server: &SharedServer, job_id: u64, status: &str) -> JobStatusResponse {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let current = lock_server(server).get_job_status(job_id).unwrap();
+            if current.status == status {
+                return current;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "job {} is {}",
+                job_id,
+                current.status
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_write_read_verify_and_clone() {
+        let source = MemoryTransport::new(256 * 1024, 4096);
+        let target = MemoryTransport::new(512 * 1024, 4096);
+        let chips = [("src", source.clone()), ("dst", target.clone())];
+        let server = server_with_devices(&["src", "dst"]);
+        let root = scratch_dir("openflash_exec_rw");
+        let image: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 256) as u8).collect();
+        std::fs::write(root.join("image.bin"), &image).unwrap();
+        let executor = JobExecutor::new(server.clone(), chip_factory(&chips))
+            .with_artifact_root(&root)
+            .with_poll_interval(Duration::from_millis(5))
+            .start();
+
+        let write = submit(
+            &server,
+            Job::new(
+                "write",
+                JobType::Write {
+                    input_path: "image.bin".to_string(),
+                    start_address: 4096,
+                    verify: true,
+                },
+            )
+            .with_device("src"),
+        );
+        wait_for(&server, write, "completed");
+        assert_eq!(&source.contents()[4096..4096 + image.len()], &image[..]);
+
+        let read = submit(
+            &server,
+            Job::new(
+                "read",
+                JobType::Read {
+                    output_path: "dumps/part.bin".to_string(),
+                    start_address: 4096,
+                    length: Some(image.len() as u64),
+                    include_oob: false,
+                },
+            )
+            .with_device("src"),
+        );
+        let status = wait_for(&server, read, "completed");
+        let result = status.result.unwrap();
+        assert_eq!(std::fs::read(root.join("dumps/part.bin")).unwrap(), image);
+        assert_eq!(result.checksum, Some(to_hex(&Sha256::digest(&image))));
+        assert!(root.join("jobs").join(format!("{}.json", read)).is_file());
+
+        // A clone only runs on its source device and holds the target busy
+        let clone = submit(
+            &server,
+            Job::new(
+                "clone",
+                JobType::Clone {
+                    source_device: "src".to_string(),
+                    target_device: "dst".to_string(),
+                },
+            ),
+        );
+        wait_for(&server, clone, "completed");
+        assert_eq!(&target.contents()[..256 * 1024], &source.contents()[..]);
+        assert_eq!(
+            lock_server(&server)
+                .device_pool
+                .get_device("dst")
+                .unwrap()
+                .status,
+            DeviceStatus::Available
+        );
+
+        std::fs::write(root.join("other.bin"), vec![0u8; 16]).unwrap();
+        let mut verify = Job::new(
+            "verify",
+            JobType::Verify {
+                file_path: "other.bin".to_string(),
+            },
+        )
+        .with_device("src");
+        verify.max_retries = 0;
+        let verify = submit(&server, verify);
+        let failed = wait_for(&server, verify, "failed");
+        assert_eq!(failed.error.as_deref(), Some("Verify failed at 0x0"));
+
+        executor.shutdown();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_cancel_and_timeout() {
+        let chip =
+            MemoryTransport::new(4 * 1024 * 1024, 4096).with_latency(Duration::from_millis(20));
+        let chips = [("dev", chip)];
+        let server = server_with_devices(&["dev"]);
+        let root = scratch_dir("openflash_exec_cancel");
+        let executor = JobExecutor::new(server.clone(), chip_factory(&chips))
+            .with_artifact_root(&root)
+            .with_poll_interval(Duration::from_millis(5))
+            .start();
+
+        let read = |timeout| {
+            Job::new(
+                "slow read",
+                JobType::Read {
+                    output_path: "slow.bin".to_string(),
+                    start_address: 0,
+                    length: None,
+                    include_oob: false,
+                },
+            )
+            .with_timeout(timeout)
+        };
+        let cancelled = submit(&server, read(3600));
+        wait_for(&server, cancelled, "running");
+        lock_server(&server).cancel_job(cancelled).unwrap();
+
+        // The worker stops the cancelled read and moves on
+        let timed_out = submit(&server, read(1));
+        let status = wait_for(&server, timed_out, "timed_out");
+        assert_eq!(status.error.as_deref(), Some("Job timed out"));
+        assert!(!root.join("slow.bin").exists());
+
+        let server_state = lock_server(&server);
+        let device = server_state.device_pool.get_device("dev").unwrap();
+        assert_eq!(device.status, DeviceStatus::Available);
+        assert_eq!(device.error_count, 1);
+        drop(server_state);
+
+        // Shutting down re-queues the running job without charging anyone
+        let interrupted = submit(&server, read(3600));
+        wait_for(&server, interrupted, "running");
+        executor.shutdown();
+        let server_state = lock_server(&server);
+        let job = server_state.job_queue.get_job(interrupted).unwrap();
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.retries, 0);
+        let device = server_state.device_pool.get_device("dev").unwrap();
+        assert_eq!(device.status, DeviceStatus::Available);
+        assert_eq!(device.error_count, 1);
+        assert_eq!(device.current_job, None);
+        drop(server_state);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_nand_write_clone_and_erase_skip_bad_blocks() {
+        let source = MemoryNand::new(16).with_bad_block(2).with_worn_block(4);
+        let target = MemoryNand::new(16).with_bad_block(1);
+        let chips = [("src", source.clone()), ("dst", target.clone())];
+        let server = server_with_devices(&["src", "dst"]);
+        let root = scratch_dir("openflash_exec_nand");
+        let block = NAND_BLOCK as usize;
+        let image: Vec<u8> = (0..5 * block as u32).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(root.join("image.bin"), &image).unwrap();
+        let executor = JobExecutor::new(server.clone(), chip_factory(&chips))
+            .with_artifact_root(&root)
+            .with_poll_interval(Duration::from_millis(5))
+            .start();
+
+        let write = |start_address| {
+            let mut job = Job::new(
+                "write",
+                JobType::Write {
+                    input_path: "image.bin".to_string(),
+                    start_address,
+                    verify: true,
+                },
+            )
+            .with_device("src");
+            job.max_retries = 0;
+            job
+        };
+        let unaligned = submit(&server, write(512));
+        let failed = wait_for(&server, unaligned, "failed");
+        assert!(failed.error.unwrap().contains("not aligned"));
+
+        // The image shifts past the factory bad block and the worn block,
+        // and every block it lands on is erased once
+        let written = submit(&server, write(0));
+        let result = wait_for(&server, written, "completed").result.unwrap();
+        assert_eq!(result.bad_blocks, vec![4]);
+        assert_eq!(result.blocks_processed, 5);
+        for (i, physical) in [0, 1, 3, 5, 6].into_iter().enumerate() {
+            assert_eq!(source.block(physical), &image[i * block..(i + 1) * block]);
+            assert_eq!(source.erase_count(physical), 1);
+        }
+        assert_eq!(source.erase_count(2), 0);
+        assert_eq!(source.erase_count(4), 4);
+
+        // Source bad block 2 is left out, target bad block 1 skipped
+        let clone = submit(
+            &server,
+            Job::new(
+                "clone",
+                JobType::Clone {
+                    source_device: "src".to_string(),
+                    target_device: "dst".to_string(),
+                },
+            ),
+        );
+        let result = wait_for(&server, clone, "completed").result.unwrap();
+        assert_eq!(result.bad_blocks, vec![1]);
+        assert_eq!(result.blocks_processed, 15);
+        assert_eq!(result.data["source_bad_blocks"], "1");
+        assert_eq!(target.block(0), source.block(0));
+        assert_eq!(target.block(2), source.block(1));
+        assert_eq!(target.block(3), source.block(3));
+        assert_eq!(target.erase_count(1), 0);
+
+        let erase = submit(
+            &server,
+            Job::new(
+                "erase",
+                JobType::Erase {
+                    start_address: 0,
+                    length: None,
+                },
+            )
+            .with_device("src"),
+        );
+        let result = wait_for(&server, erase, "completed").result.unwrap();
+        assert_eq!(result.bad_blocks, vec![2]);
+        assert_eq!(result.blocks_processed, 15);
+        assert_eq!(source.erase_count(2), 0);
+        assert_eq!(source.block(0), vec![0xFF; block]);
+
+        executor.shutdown();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_nand_jobs_retry_time_out_and_cancel() {
+        let source = MemoryNand::new(64).with_latency(Duration::from_millis(2));
+        let target = MemoryNand::new(64).with_latency(Duration::from_millis(5));
+        let chips = [("src", source.clone()), ("dst", target.clone())];
+        let server = server_with_devices(&["src", "dst"]);
+        let root = scratch_dir("openflash_exec_nand_errors");
+        let image = vec![0x3C; 4 * NAND_BLOCK as usize];
+        std::fs::write(root.join("image.bin"), &image).unwrap();
+        std::fs::write(root.join("full.bin"), vec![0x5A; 64 * NAND_BLOCK as usize]).unwrap();
+        let executor = JobExecutor::new(server.clone(), chip_factory(&chips))
+            .with_artifact_root(&root)
+            .with_poll_interval(Duration::from_millis(5))
+            .start();
+        let write = |path: &str| {
+            Job::new(
+                "write",
+                JobType::Write {
+                    input_path: path.to_string(),
+                    start_address: 0,
+                    verify: true,
+                },
+            )
+            .with_device("src")
+        };
+
+        // A device error fails the attempt, the retry reconnects and finishes
+        source.fail_next(1);
+        let retried = submit(&server, write("image.bin"));
+        wait_for(&server, retried, "completed");
+        assert_eq!(
+            lock_server(&server)
+                .job_queue
+                .get_job(retried)
+                .unwrap()
+                .retries,
+            1
+        );
+        assert_eq!(source.block(3), &image[3 * NAND_BLOCK as usize..]);
+
+        let timed_out = submit(&server, write("full.bin").with_timeout(1));
+        let status = wait_for(&server, timed_out, "timed_out");
+        assert_eq!(status.error.as_deref(), Some("Job timed out"));
+        assert_eq!(source.block(63), vec![0xFF; NAND_BLOCK as usize]);
+
+        // A cancelled clone is aborted and releases the target
+        let clone = || {
+            Job::new(
+                "clone",
+                JobType::Clone {
+                    source_device: "src".to_string(),
+                    target_device: "dst".to_string(),
+                },
+            )
+        };
+        let timed_out = submit(&server, clone().with_timeout(1));
+        let status = wait_for(&server, timed_out, "timed_out");
+        assert_eq!(status.error.as_deref(), Some("Job timed out"));
+
+        let erased = target.erase_count(1);
+        let clone = submit(&server, clone());
+        wait_for(&server, clone, "running");
+        while target.erase_count(1) == erased {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        lock_server(&server).cancel_job(clone).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while lock_server(&server)
+            .device_pool
+            .get_device("dst")
+            .unwrap()
+            .status
+            != DeviceStatus::Available
+        {
+            assert!(Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(target.erase_count(63), 0);
+
+        executor.shutdown();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_completion_webhook() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/job", listener.local_addr().unwrap());
+        let receiver = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).contains("\"job_id\"") {
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0);
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let chips = [("dev", MemoryTransport::new(64 * 1024, 4096))];
+        let server = server_with_devices(&["dev"]);
+        let root = scratch_dir("openflash_exec_hook");
+        let executor = JobExecutor::new(server.clone(), chip_factory(&chips))
+            .with_poll_interval(Duration::from_millis(5))
+            .start();
+        let erase = submit(
+            &server,
+            Job::new(
+                "erase",
+                JobType::Erase {
+                    start_address: 0,
+                    length: Some(5000),
+                },
+            )
+            .with_callback(&url),
+        );
+
+        let request = receiver.join().unwrap();
+        assert!(request.starts_with("POST /hooks/job HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("\"job_id\":{}", erase)));
+        let status = wait_for(&server, erase, "completed");
+        assert_eq!(status.result.unwrap().bytes_processed, 8192);
+
+        executor.shutdown();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
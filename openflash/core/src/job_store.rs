@@ -0,0 +1,634 @@
+//! Durable storage for the OpenFlash server
+//!
+//! Keeps jobs (with their results) and device registrations across server
+//! restarts, plus an audit trail, in a data directory:
+//! - `snapshot.json`: full state as of the last compaction, replaced
+//!   atomically
+//! - `journal.jsonl`: one sequence-numbered record per job or device change
+//!   since the snapshot
+//! - `audit.jsonl`: job, device and production events with timestamps
+//!
+//! Opening a store replays the snapshot and the journal. A record cut short
+//! by a crash at the end of a file is dropped; damage anywhere else is
+//! reported as [`StoreError::Corrupt`].
+//! [`OpenFlashServer::open`](crate::server::OpenFlashServer::open) turns the
+//! replayed state back into a queue, applying the [`RecoveryPolicy`] to jobs
+//! that were running and the [`RetentionPolicy`] to finished ones.
+
+use crate::server::{Job, PoolDevice, WsMessage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// File names inside the data directory
+pub mod files {
+    pub const SNAPSHOT: &str = "snapshot.json";
+    pub const JOURNAL: &str = "journal.jsonl";
+    pub const AUDIT: &str = "audit.jsonl";
+}
+
+/// Snapshot format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Store errors
+#[derive(Debug)]
+pub enum StoreError {
+    /// File system error
+    Io(std::io::Error),
+    /// A stored record cannot be decoded
+    Corrupt {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    /// Snapshot written by a newer version
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Corrupt {
+                file,
+                line,
+                message,
+            } => write!(f, "{} line {} is corrupt: {}", file, line, message),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported snapshot version {}", v),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// What happens to jobs that were assigned or running when the server
+/// stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RecoveryPolicy {
+    /// Queue them again, counting the interruption as a retry; jobs out of
+    /// retries are marked failed
+    #[default]
+    Requeue,
+    /// Mark them failed
+    MarkFailed,
+}
+
+/// How long finished jobs and audit events are kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Finished jobs kept in the history
+    pub max_finished_jobs: usize,
+    /// Finished jobs older than this are dropped (seconds, None = no limit)
+    pub max_finished_age_secs: Option<u64>,
+    /// Audit events kept
+    pub max_audit_events: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_finished_jobs: 1000,
+            max_finished_age_secs: Some(30 * 24 * 3600), // 30 days
+            max_audit_events: 100_000,
+        }
+    }
+}
+
+/// Persistence configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistenceConfig {
+    /// Data directory
+    pub data_dir: String,
+    /// fsync every record (slower, survives power loss)
+    pub sync_writes: bool,
+    /// Handling of interrupted jobs on start-up
+    pub recovery: RecoveryPolicy,
+    /// Retention of finished jobs and audit events
+    pub retention: RetentionPolicy,
+    /// Journal records after which the state is compacted into a snapshot
+    pub compact_after: usize,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: "openflash-data".to_string(),
+            sync_writes: true,
+            recovery: RecoveryPolicy::default(),
+            retention: RetentionPolicy::default(),
+            compact_after: 10_000,
+        }
+    }
+}
+
+// ============================================================================
+// Records
+// ============================================================================
+
+/// Journal record payload; job and device records hold the full state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op")]
+enum Change {
+    Job { job: Box<Job> },
+    JobRemoved { job_id: u64 },
+    Device { device: Box<PoolDevice> },
+    DeviceRemoved { device_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalRecord {
+    seq: u64,
+    #[serde(flatten)]
+    change: Change,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    /// Last journal sequence number included
+    seq: u64,
+    jobs: Vec<Job>,
+    devices: Vec<PoolDevice>,
+}
+
+/// Audit trail entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Unix epoch ms
+    pub timestamp: u64,
+    /// Event as published on the server's event bus
+    pub event: WsMessage,
+}
+
+/// State read back when a store is opened
+#[derive(Debug, Clone, Default)]
+pub struct StoredState {
+    /// Every stored job, in ID order
+    pub jobs: Vec<Job>,
+    /// Registered devices, in ID order
+    pub devices: Vec<PoolDevice>,
+}
+
+// ============================================================================
+// Store
+// ============================================================================
+
+/// Handle to a data directory; clones share the same files
+#[derive(Clone)]
+pub struct JobStore {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    sync_writes: bool,
+    journal: Mutex<File>,
+    audit: Mutex<File>,
+    seq: AtomicU64,
+    journal_records: AtomicUsize,
+    audit_records: AtomicUsize,
+}
+
+impl std::fmt::Debug for JobStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobStore")
+            .field("dir", &self.inner.dir)
+            .field("journal_records", &self.journal_len())
+            .finish()
+    }
+}
+
+fn lock(file: &Mutex<File>) -> MutexGuard<'_, File> {
+    file.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Parse a JSON-lines file, dropping a torn last line
+///
+/// Returns the decoded values and the length of the intact prefix.
+pub(crate) fn read_lines<T: for<'de> Deserialize<'de>>(path: &Path) -> StoreResult<(Vec<T>, u64)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut reader = BufReader::new(file);
+    let mut values = Vec::new();
+    let mut good_len = 0u64;
+    let mut line = String::new();
+    let mut line_no = 0;
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        line_no += 1;
+        // A torn write can only be the last line
+        if !line.ends_with('\n') {
+            break;
+        }
+        match serde_json::from_str::<T>(line.trim_end()) {
+            Ok(value) => {
+                values.push(value);
+                good_len += read as u64;
+            }
+            Err(e) => {
+                // Anything after the damage means it was not a torn write
+                let mut rest = String::new();
+                if reader.read_line(&mut rest)? == 0 {
+                    break;
+                }
+                return Err(StoreError::Corrupt {
+                    file: name,
+                    line: line_no,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+    Ok((values, good_len))
+}
+
+/// Open a JSON-lines file for appending, cutting off anything past
+/// `good_len`
+pub(crate) fn open_append(path: &Path, good_len: u64) -> StoreResult<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    if file.metadata()?.len() > good_len {
+        file.set_len(good_len)?;
+    }
+    Ok(file)
+}
+
+/// Replace `path` with `contents` so readers see either the old or the new
+/// file
+fn write_atomic(path: &Path, contents: &[u8]) -> StoreResult<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    // Make the rename itself durable where directories can be synced
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+impl JobStore {
+    /// Open or create the store in `dir` and read back its state
+    pub fn open(dir: impl AsRef<Path>, sync_writes: bool) -> StoreResult<(Self, StoredState)> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let snapshot = match std::fs::read(dir.join(files::SNAPSHOT)) {
+            Ok(bytes) => {
+                let snapshot: Snapshot =
+                    serde_json::from_slice(&bytes).map_err(|e| StoreError::Corrupt {
+                        file: files::SNAPSHOT.to_string(),
+                        line: e.line(),
+                        message: e.to_string(),
+                    })?;
+                if snapshot.version > SNAPSHOT_VERSION {
+                    return Err(StoreError::UnsupportedVersion(snapshot.version));
+                }
+                snapshot
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Snapshot {
+                version: SNAPSHOT_VERSION,
+                seq: 0,
+                jobs: Vec::new(),
+                devices: Vec::new(),
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut jobs: HashMap<u64, Job> = snapshot.jobs.into_iter().map(|j| (j.id, j)).collect();
+        let mut devices: HashMap<String, PoolDevice> = snapshot
+            .devices
+            .into_iter()
+            .map(|d| (d.id.clone(), d))
+            .collect();
+
+        let journal_path = dir.join(files::JOURNAL);
+        let (records, journal_len) = read_lines::<JournalRecord>(&journal_path)?;
+        let mut seq = snapshot.seq;
+        // Records already folded into the snapshot survive a crash during
+        // compaction; skip them
+        let pending: Vec<JournalRecord> = records
+            .into_iter()
+            .filter(|r| r.seq > snapshot.seq)
+            .collect();
+        let journal_records = pending.len();
+        for record in pending {
+            seq = seq.max(record.seq);
+            match record.change {
+                Change::Job { job } => {
+                    jobs.insert(job.id, *job);
+                }
+                Change::JobRemoved { job_id } => {
+                    jobs.remove(&job_id);
+                }
+                Change::Device { device } => {
+                    devices.insert(device.id.clone(), *device);
+                }
+                Change::DeviceRemoved { device_id } => {
+                    devices.remove(&device_id);
+                }
+            }
+        }
+
+        let audit_path = dir.join(files::AUDIT);
+        let (audit, audit_len) = read_lines::<AuditEvent>(&audit_path)?;
+
+        let store = Self {
+            inner: Arc::new(Inner {
+                journal: Mutex::new(open_append(&journal_path, journal_len)?),
+                audit: Mutex::new(open_append(&audit_path, audit_len)?),
+                dir,
+                sync_writes,
+                seq: AtomicU64::new(seq),
+                journal_records: AtomicUsize::new(journal_records),
+                audit_records: AtomicUsize::new(audit.len()),
+            }),
+        };
+
+        let mut jobs: Vec<Job> = jobs.into_values().collect();
+        jobs.sort_by_key(|j| j.id);
+        let mut devices: Vec<PoolDevice> = devices.into_values().collect();
+        devices.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok((store, StoredState { jobs, devices }))
+    }
+
+    /// Data directory
+    pub fn dir(&self) -> &Path {
+        &self.inner.dir
+    }
+
+    /// Journal records written since the last compaction
+    pub fn journal_len(&self) -> usize {
+        self.inner.journal_records.load(Ordering::SeqCst)
+    }
+
+    /// Store the current state of a job
+    pub fn save_job(&self, job: &Job) -> StoreResult<()> {
+        self.append(Change::Job {
+            job: Box::new(job.clone()),
+        })
+    }
+
+    /// Forget a job
+    pub fn remove_job(&self, job_id: u64) -> StoreResult<()> {
+        self.append(Change::JobRemoved { job_id })
+    }
+
+    /// Store the current state of a device registration
+    pub fn save_device(&self, device: &PoolDevice) -> StoreResult<()> {
+        self.append(Change::Device {
+            device: Box::new(device.clone()),
+        })
+    }
+
+    /// Forget a device registration
+    pub fn remove_device(&self, device_id: &str) -> StoreResult<()> {
+        self.append(Change::DeviceRemoved {
+            device_id: device_id.to_string(),
+        })
+    }
+
+    fn append(&self, change: Change) -> StoreResult<()> {
+        let mut journal = lock(&self.inner.journal);
+        let record = JournalRecord {
+            seq: self.inner.seq.fetch_add(1, Ordering::SeqCst) + 1,
+            change,
+        };
+        self.write_line(&mut journal, &record)?;
+        self.inner.journal_records.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn write_line<T: Serialize>(&self, file: &mut File, value: &T) -> StoreResult<()> {
+        let mut line = serde_json::to_vec(value)
+            .map_err(|e| StoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+        line.push(b'\n');
+        // One write per record keeps a crash from interleaving records
+        file.write_all(&line)?;
+        if self.inner.sync_writes {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Append an event to the audit trail
+    pub fn append_audit(&self, event: &WsMessage) -> StoreResult<()> {
+        let entry = AuditEvent {
+            timestamp: now_ms(),
+            event: event.clone(),
+        };
+        let mut audit = lock(&self.inner.audit);
+        self.write_line(&mut audit, &entry)?;
+        self.inner.audit_records.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Read the audit trail, oldest first
+    pub fn audit_events(&self) -> StoreResult<Vec<AuditEvent>> {
+        let _audit = lock(&self.inner.audit);
+        Ok(read_lines(&self.inner.dir.join(files::AUDIT))?.0)
+    }
+
+    /// Replace snapshot and journal with the given state and trim the audit
+    /// trail to `max_audit_events`
+    pub fn compact<'a>(
+        &self,
+        jobs: impl IntoIterator<Item = &'a Job>,
+        devices: impl IntoIterator<Item = &'a PoolDevice>,
+        max_audit_events: usize,
+    ) -> StoreResult<()> {
+        {
+            // Hold the journal so no change lands between snapshot and truncation
+            let journal = lock(&self.inner.journal);
+            let mut jobs: Vec<Job> = jobs.into_iter().cloned().collect();
+            jobs.sort_by_key(|j| j.id);
+            let mut devices: Vec<PoolDevice> = devices.into_iter().cloned().collect();
+            devices.sort_by(|a, b| a.id.cmp(&b.id));
+            let snapshot = Snapshot {
+                version: SNAPSHOT_VERSION,
+                seq: self.inner.seq.load(Ordering::SeqCst),
+                jobs,
+                devices,
+            };
+            let bytes = serde_json::to_vec(&snapshot).map_err(|e| {
+                StoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })?;
+            write_atomic(&self.inner.dir.join(files::SNAPSHOT), &bytes)?;
+            journal.set_len(0)?;
+            journal.sync_all()?;
+            self.inner.journal_records.store(0, Ordering::SeqCst);
+        }
+
+        if self.inner.audit_records.load(Ordering::SeqCst) > max_audit_events {
+            let mut audit = lock(&self.inner.audit);
+            let path = self.inner.dir.join(files::AUDIT);
+            let (events, _) = read_lines::<AuditEvent>(&path)?;
+            let keep = &events[events.len().saturating_sub(max_audit_events)..];
+            let mut bytes = Vec::new();
+            for event in keep {
+                serde_json::to_writer(&mut bytes, event).map_err(|e| {
+                    StoreError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                })?;
+                bytes.push(b'\n');
+            }
+            write_atomic(&path, &bytes)?;
+            *audit = open_append(&path, bytes.len() as u64)?;
+            self.inner.audit_records.store(keep.len(), Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{DevicePlatform, JobStatus, JobType};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn erase_job() -> Job {
+        Job::new(
+            "erase",
+            JobType::Erase {
+                start_address: 0,
+                length: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_replay_and_compaction() {
+        let dir = temp_dir("openflash_store_replay");
+        let mut job = erase_job();
+        let device = PoolDevice::new("dev1", "Bench", "mem://4096", DevicePlatform::RP2040);
+        {
+            let (store, state) = JobStore::open(&dir, false).unwrap();
+            assert!(state.jobs.is_empty());
+            store.save_job(&job).unwrap();
+            store.save_device(&device).unwrap();
+            job.status = JobStatus::Cancelled;
+            store.save_job(&job).unwrap();
+            store
+                .append_audit(&WsMessage::JobUpdate {
+                    job_id: job.id,
+                    status: "cancelled".to_string(),
+                    progress: None,
+                })
+                .unwrap();
+        }
+
+        let (store, state) = JobStore::open(&dir, false).unwrap();
+        assert_eq!(state.jobs.len(), 1);
+        assert_eq!(state.jobs[0].status, JobStatus::Cancelled);
+        assert_eq!(state.devices[0].id, "dev1");
+        assert_eq!(store.journal_len(), 3);
+        assert_eq!(store.audit_events().unwrap().len(), 1);
+
+        store.compact(&state.jobs, &state.devices, 0).unwrap();
+        assert_eq!(store.journal_len(), 0);
+        assert!(store.audit_events().unwrap().is_empty());
+        store.remove_device("dev1").unwrap();
+        drop(store);
+
+        let (_, state) = JobStore::open(&dir, false).unwrap();
+        assert_eq!(state.jobs.len(), 1);
+        assert!(state.devices.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_and_corrupt_journal() {
+        let dir = temp_dir("openflash_store_torn");
+        let job = erase_job();
+        {
+            let (store, _) = JobStore::open(&dir, true).unwrap();
+            store.save_job(&job).unwrap();
+        }
+        let journal = dir.join(files::JOURNAL);
+        // Crash in the middle of the next record
+        let mut file = OpenOptions::new().append(true).open(&journal).unwrap();
+        file.write_all(b"{\"seq\":2,\"op\":\"Job\",\"job\":{\"id\"")
+            .unwrap();
+        drop(file);
+
+        {
+            let (store, state) = JobStore::open(&dir, true).unwrap();
+            assert_eq!(state.jobs.len(), 1);
+            // The torn record is cut off before new records are appended
+            store.remove_job(job.id).unwrap();
+        }
+        let (_, state) = JobStore::open(&dir, true).unwrap();
+        assert!(state.jobs.is_empty());
+
+        // Damage followed by more records is not a torn write
+        let mut contents = std::fs::read(&journal).unwrap();
+        contents.splice(0..0, b"garbage\n".iter().copied());
+        std::fs::write(&journal, contents).unwrap();
+        assert!(matches!(
+            JobStore::open(&dir, true),
+            Err(StoreError::Corrupt { line: 1, .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,19 +1,51 @@
+pub mod access;
 pub mod ai;
 pub mod ai_advanced;
 pub mod analysis;
+pub mod backup_repo;
 pub mod cloud;
+pub mod diff_write;
+pub mod discovery;
 pub mod ecc;
 pub mod emmc;
+pub mod emmc_partition;
+pub mod erase;
+#[cfg(feature = "rest-server")]
+pub mod event_stream;
+#[cfg(feature = "grpc-server")]
+pub mod grpc_server;
 pub mod hardware;
+pub mod job_executor;
+pub mod job_store;
+pub mod metrics;
+pub mod nand_bbt;
+pub mod nand_health;
+pub mod nand_geometry;
 pub mod onfi;
+pub mod parallel_dump;
+pub mod production;
+pub mod programmer;
 pub mod protocol;
+pub mod read_retry;
+#[cfg(feature = "rest-server")]
+pub mod rest_server;
+pub mod rpmb;
 pub mod scripting;
 pub mod server;
 pub mod spi_nand;
 pub mod spi_nor;
+#[cfg(test)]
+mod test_support;
 pub mod ufs;
+pub mod ufs_lun;
+pub mod ufs_upiu;
 pub mod write_ops;
 
+pub use access::{
+    hash_secret, operation_name, verify_chain, AccessConfig, AccessError, AccessLog,
+    AccessLogResponse, AccessRecord, AccessResult, Permission, Principal, Role, RolePolicy,
+    UserAccount,
+};
 pub use ai::*;
 pub use analysis::*;
 pub use ecc::*;
@@ -56,7 +88,20 @@ pub use ai_advanced::{
 };
 pub use emmc::{
     crc16, crc7, get_emmc_chip_info, get_emmc_manufacturer_name, parse_boot_size_from_ext_csd,
-    parse_capacity_from_ext_csd, CardState, EmmcChipInfo, EmmcReadResult, ResponseType,
+    health_verdict, parse_capacity_from_ext_csd, BootAreaProtection, BootBusConditions, BootMode,
+    BootPartitionEnable, CardState, EmmcChipInfo, EmmcHealthReport, EmmcHealthVerdict,
+    EmmcReadResult, ExtCsd, HsTiming, LifeTimeEstimate, PartitionAccess, PartitionConfig,
+    PreEolInfo, ResponseType,
+};
+pub use emmc_partition::{
+    parse_write_protect_types, partition_name, switch_argument, switch_write_byte,
+    EmmcPartition, EmmcPartitionError, EmmcPartitionManager, EmmcPartitionResult,
+    EnhancedUserArea, HardwarePartitionLayout, WriteProtectType, HW_PARTITION_CONFIRMATION,
+};
+pub use erase::{
+    check_purge_status, expand_to_groups, EmmcEraseCapabilities, EmmcEraser, EraseError,
+    EraseMethod, EraseOp, ErasePhase, ErasePlan, EraseProgress, EraseResult, EraseSession,
+    EraseStep, UfsEraser,
 };
 pub use hardware::{
     BgaProfile,
@@ -94,6 +139,68 @@ pub use hardware::{
     Tsop48Pinout,
     VoltageLevel,
 };
+pub use job_executor::{
+    connect_uri, post_webhook, Connections, DeviceTransport, ExecError, ExecResult,
+    ExecutorHandle, JobExecutor, MemoryTransport, NandDevice, TransportFactory, WebhookSender,
+};
+pub use job_store::{
+    AuditEvent, JobStore, PersistenceConfig, RecoveryPolicy, RetentionPolicy, StoreError,
+    StoreResult, StoredState,
+};
+pub use backup_repo::{
+    BackupError, BackupManifest, BackupRepository, BackupResult, PruneReport, RepoStats,
+    VerifyReport, REPO_FORMAT_VERSION,
+};
+pub use diff_write::{
+    DiffGeometry, DiffPlan, DiffUnit, DiffWriteReport, DiffWriter, FlashTimings, UnitAction,
+};
+pub use programmer::{ProgrammerLink, ProgrammerTransport};
+pub use discovery::{
+    scan_usb, Announcement, AnnouncementListener, DeviceMonitor, DiscoveryConfig, MonitorHandle,
+    SweepReport,
+};
+pub use nand_bbt::{
+    BbtBlockImage, BbtBlockState, BbtCopy, BbtLayout, BbtLocation, NandBbt, NandBbtError,
+    NandBbtResult, BBT_PATTERN_MAIN, BBT_PATTERN_MIRROR,
+};
+pub use metrics::{
+    escape_label, render as render_metrics, Histogram, LineMetrics, ServerMetrics,
+    StationCounters,
+};
+pub use nand_health::{
+    BlockHealth, BlockHealthStatus, EccPageSource, EccStepResult, HealthReport, HealthScanner,
+    HealthSummary, HostEcc, PageEccRead, WearModel,
+};
+pub use nand_geometry::{
+    cache_read_sequence, detect_ce_count, lun_status_sequence, multi_plane_erase_sequence,
+    multi_plane_read_sequence, page_read_sequence, select_ce, LunStatus, NandAddress,
+    NandGeometry,
+};
+pub use parallel_dump::{
+    DeviceDump, DumpManifest, ManifestChunk, ParallelDumpError, ParallelDumpResult,
+    ParallelDumper,
+};
+pub use production::{
+    expand_template, ProductionRunner, StationError, StationResult, TemplateVars,
+};
+pub use read_retry::{
+    hynix_otp_sequence, HynixOtpSequence, ReadRetryEngine, RetryStats, RetryStep, RetryTable,
+    RetryVendor,
+};
+#[cfg(feature = "rest-server")]
+pub use event_stream::{EventHub, Subscription};
+#[cfg(feature = "grpc-server")]
+pub use grpc_server::{GrpcError, GrpcResult, GrpcServer, GrpcService};
+#[cfg(feature = "rest-server")]
+pub use metrics::MetricsServer;
+#[cfg(feature = "rest-server")]
+pub use rest_server::{
+    openapi_document, ApiRequest, ApiResponse, RestApi, RestError, RestResult, RestServer,
+};
+pub use rpmb::{
+    emmc_select_rpmb, ufs_select_rpmb, RpmbEmulator, RpmbError, RpmbFrame, RpmbKey, RpmbResult,
+    RpmbSession, RpmbStatus, RpmbTransfer, RPMB_KEY_PROGRAM_CONFIRMATION,
+};
 pub use scripting::{
     AnalysisOptions, AnomalyInfo, BatchJob, BatchJobConfig, BatchJobResult, BatchJobStatus,
     BatchJobType, BatchProcessor, ChipDetectionResult, CiArtifact, CiArtifactType, CiJobConfig,
@@ -115,6 +222,9 @@ pub use server::{
     DevicePool,
     // Device Pool
     DeviceStatus,
+    // Events
+    EventBus,
+    EventListener,
     // gRPC
     GrpcConfig,
     Job,
@@ -146,6 +256,7 @@ pub use server::{
     ServerError,
     ServerInfo,
     ServerResult,
+    SharedServer,
     StationConfig,
     StationOperation,
     SubmitJobRequest,
@@ -164,14 +275,23 @@ pub use spi_nor::{
     QuadEnableMethod, SfdpInfo, SfdpParser, SpiNorChipInfo, SpiNorError,
 };
 pub use ufs::{
-    get_ufs_manufacturer_name, select_read_command, DeviceDescriptor, GeometryDescriptor,
-    ReadCommandType, ScsiCdbBuilder, UfsDeviceInfo, UfsError, UfsLun, UfsVersion, UnitDescriptor,
+    get_ufs_manufacturer_name, select_read_command, ConfigurationDescriptor, DeviceDescriptor,
+    GeometryDescriptor, HealthDescriptor, ReadCommandType, ScsiCdbBuilder, UfsDeviceInfo, UfsError,
+    UfsLun, UfsVersion, UnitConfig, UnitDescriptor,
+};
+pub use ufs_lun::{
+    BootLunEnable, UfsLogicalUnit, UfsLunError, UfsLunManager, UfsLunResult,
+    UFS_PROVISION_CONFIRMATION,
+};
+pub use ufs_upiu::{
+    query_result, CommandUpiu, DataUpiu, QueryFields, QueryOpcode, ResponseUpiu,
+    TaskManagementUpiu, Upiu, UpiuHeader,
 };
 pub use write_ops::{
     BackupMetadata, BadBlockEntry, BadBlockReason, BadBlockTable, BlockWearInfo, ChangeTracker,
-    ChipCloner, ChipProgrammer, CloneMode, CloneOptions, ClonePhase, CloneProgress,
-    ProgramOperation, ProgramOptions, ProgramProgress, WearLevelingManager, WearStatistics,
-    WriteError, WriteResult,
+    ChipCloner, ChipProgrammer, CloneAbortHandle, CloneMode, CloneOptions, ClonePhase,
+    CloneProgress, CloneReport, ProgramImage, ProgramJob, ProgramOperation, ProgramOptions,
+    ProgramProgress, ProgramTarget, WearLevelingManager, WearStatistics, WriteError, WriteResult,
 };
 // Cloud & Pro features (v3.0)
 pub use cloud::{
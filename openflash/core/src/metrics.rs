@@ -0,0 +1,798 @@
+//! Prometheus metrics for the OpenFlash server
+//!
+//! The pool and queue only describe the present, so [`ServerMetrics`]
+//! (kept in [`OpenFlashServer::metrics`]) accumulates the history an alert
+//! needs: job duration histograms by job type and outcome, ECC corrections
+//! per device and per-line production counters. [`render`] combines it
+//! with the current [`PoolStats`](crate::server::PoolStats),
+//! [`QueueStats`](crate::server::QueueStats) and device list into the
+//! Prometheus text exposition format (version 0.0.4).
+//!
+//! With the `rest-server` feature, [`MetricsServer`] serves the rendered
+//! text at `GET /metrics` on `ServerConfig.metrics_port`.
+
+use crate::server::{
+    status_name, DeviceStatus, Job, JobStatus, OpenFlashServer, PoolDevice, ProductionStats,
+    ProductionUnitResult,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "rest-server")]
+use crate::server::{lock_server, SharedServer};
+#[cfg(feature = "rest-server")]
+use http_body_util::Full;
+#[cfg(feature = "rest-server")]
+use hyper::body::{Bytes, Incoming};
+#[cfg(feature = "rest-server")]
+use hyper::server::conn::http1;
+#[cfg(feature = "rest-server")]
+use hyper::service::service_fn;
+#[cfg(feature = "rest-server")]
+use hyper::{Method, Request, Response, StatusCode};
+#[cfg(feature = "rest-server")]
+use hyper_util::rt::TokioIo;
+#[cfg(feature = "rest-server")]
+use std::convert::Infallible;
+#[cfg(feature = "rest-server")]
+use std::future::Future;
+#[cfg(feature = "rest-server")]
+use std::net::SocketAddr;
+#[cfg(feature = "rest-server")]
+use tokio::net::TcpListener;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+pub mod limits {
+    /// Upper bounds (seconds) of the job duration histogram buckets
+    pub const JOB_DURATION_BUCKETS: &[f64] = &[
+        1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+    ];
+    /// Content type of the text exposition format
+    pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+    /// Delay before accepting again after a failed accept
+    pub const ACCEPT_BACKOFF_MS: u64 = 100;
+}
+
+// ============================================================================
+// Collected metrics
+// ============================================================================
+
+/// Cumulative histogram with fixed bucket bounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Upper bound of each bucket, ascending (`+Inf` is implied)
+    pub bounds: Vec<f64>,
+    /// Observations per bucket (not cumulative), one extra for `+Inf`
+    pub counts: Vec<u64>,
+    /// Sum of all observations
+    pub sum: f64,
+    /// Number of observations
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Empty histogram over `bounds`
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Record one value
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// `(le, cumulative count)` pairs, ending with `+Inf`
+    pub fn cumulative(&self) -> Vec<(String, u64)> {
+        let mut total = 0;
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                total += count;
+                let le = match self.bounds.get(i) {
+                    Some(bound) => format_value(*bound),
+                    None => "+Inf".to_string(),
+                };
+                (le, total)
+            })
+            .collect()
+    }
+}
+
+/// Units, ECC corrections and bad blocks seen at one station
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StationCounters {
+    pub passed_units: u64,
+    pub failed_units: u64,
+    pub ecc_corrections: u64,
+    pub bad_blocks: u64,
+}
+
+/// Running statistics of one production line
+#[derive(Debug, Clone)]
+pub struct LineMetrics {
+    /// Aggregate statistics, kept up to date by [`LineMetrics::record`]
+    pub stats: ProductionStats,
+    /// Counters per station ID
+    pub stations: BTreeMap<String, StationCounters>,
+    total_cycle_ms: u64,
+    first_unit: Option<Instant>,
+}
+
+impl LineMetrics {
+    /// No units recorded yet
+    pub fn new(line_id: &str) -> Self {
+        Self {
+            stats: ProductionStats {
+                line_id: line_id.to_string(),
+                total_units: 0,
+                passed_units: 0,
+                failed_units: 0,
+                pass_rate: 0.0,
+                avg_cycle_time_ms: 0,
+                units_per_hour: 0.0,
+                failure_reasons: HashMap::new(),
+            },
+            stations: BTreeMap::new(),
+            total_cycle_ms: 0,
+            first_unit: None,
+        }
+    }
+
+    /// Count a finished unit
+    ///
+    /// Failures are grouped by `data["failure_code"]`; units per hour is
+    /// measured from the first unit recorded.
+    pub fn record(&mut self, result: &ProductionUnitResult) {
+        // Throughput is measured from the start of the first unit
+        let first_unit = *self.first_unit.get_or_insert_with(|| {
+            let now = Instant::now();
+            now.checked_sub(ms(result.duration_ms)).unwrap_or(now)
+        });
+        let stats = &mut self.stats;
+        stats.total_units += 1;
+        if result.passed {
+            stats.passed_units += 1;
+        } else {
+            stats.failed_units += 1;
+            let code = result
+                .data
+                .get("failure_code")
+                .cloned()
+                .unwrap_or_else(|| "unknown".to_string());
+            *stats.failure_reasons.entry(code).or_insert(0) += 1;
+        }
+        stats.pass_rate = stats.passed_units as f32 * 100.0 / stats.total_units as f32;
+        self.total_cycle_ms += result.duration_ms;
+        stats.avg_cycle_time_ms = self.total_cycle_ms / stats.total_units;
+        let hours = first_unit.elapsed().as_secs_f32() / 3600.0;
+        if hours > 0.0 {
+            stats.units_per_hour = stats.total_units as f32 / hours;
+        }
+
+        let station = self.stations.entry(result.station_id.clone()).or_default();
+        if result.passed {
+            station.passed_units += 1;
+        } else {
+            station.failed_units += 1;
+        }
+        station.ecc_corrections += u64::from(result.ecc_corrections);
+        station.bad_blocks += u64::from(result.bad_blocks);
+    }
+}
+
+/// History collected by the server for [`render`]
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    /// Job durations keyed by (job type, final status)
+    pub job_durations: BTreeMap<(String, String), Histogram>,
+    /// ECC corrections reported by jobs and production units per device
+    pub device_ecc_corrections: BTreeMap<String, u64>,
+    /// Production statistics per line ID
+    pub lines: BTreeMap<String, LineMetrics>,
+}
+
+impl ServerMetrics {
+    /// Record the duration of a job that has just finished
+    ///
+    /// Jobs that never started (e.g. cancelled while queued) are not
+    /// observed.
+    pub fn observe_job(&mut self, job: &Job) {
+        let status = match &job.status {
+            JobStatus::Completed { .. } => "completed",
+            JobStatus::Failed { .. } => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::TimedOut => "timed_out",
+            _ => return,
+        };
+        let (Some(started), Some(finished)) = (job.started_at, job.completed_at) else {
+            return;
+        };
+        let seconds = finished.saturating_sub(started) as f64 / 1000.0;
+        self.job_durations
+            .entry((job.job_type.name().to_string(), status.to_string()))
+            .or_insert_with(|| Histogram::new(limits::JOB_DURATION_BUCKETS))
+            .observe(seconds);
+    }
+
+    /// Add ECC corrections made on `device_id`
+    pub fn add_ecc_corrections(&mut self, device_id: &str, corrections: u32) {
+        if corrections > 0 {
+            *self
+                .device_ecc_corrections
+                .entry(device_id.to_string())
+                .or_insert(0) += u64::from(corrections);
+        }
+    }
+
+    /// Count a finished production unit
+    ///
+    /// The unit's ECC corrections are also credited to
+    /// `data["device_id"]` when the runner recorded it.
+    pub fn record_unit(&mut self, result: &ProductionUnitResult) {
+        self.lines
+            .entry(result.line_id.clone())
+            .or_insert_with(|| LineMetrics::new(&result.line_id))
+            .record(result);
+        if let Some(device_id) = result.data.get("device_id") {
+            self.add_ecc_corrections(device_id, result.ecc_corrections);
+        }
+    }
+}
+
+fn ms(millis: u64) -> std::time::Duration {
+    std::time::Duration::from_millis(millis)
+}
+
+// ============================================================================
+// Exposition
+// ============================================================================
+
+/// Writes metric families in the text exposition format
+struct Exposition {
+    out: String,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        if !labels.is_empty() {
+            self.out.push('{');
+            for (i, (key, val)) in labels.iter().enumerate() {
+                if i > 0 {
+                    self.out.push(',');
+                }
+                let _ = write!(self.out, "{}=\"{}\"", key, escape_label(val));
+            }
+            self.out.push('}');
+        }
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+}
+
+/// Escape a label value (backslash, double quote and newline)
+pub fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Reads one sample value from a device, station or line
+type Sample<T> = fn(&T) -> f64;
+
+const DEVICE_STATUSES: [DeviceStatus; 6] = [
+    DeviceStatus::Available,
+    DeviceStatus::Busy,
+    DeviceStatus::Offline,
+    DeviceStatus::Error,
+    DeviceStatus::Maintenance,
+    DeviceStatus::Reserved,
+];
+
+/// Render the server's current metrics in the text exposition format
+pub fn render(server: &OpenFlashServer) -> String {
+    let mut exp = Exposition { out: String::new() };
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    exp.family("openflash_info", "gauge", "Server version.");
+    exp.sample("openflash_info", &[("version", &server.version)], 1.0);
+    exp.family(
+        "openflash_uptime_seconds",
+        "gauge",
+        "Seconds since the server started.",
+    );
+    exp.sample(
+        "openflash_uptime_seconds",
+        &[],
+        now_ms.saturating_sub(server.started_at) as f64 / 1000.0,
+    );
+
+    // Pool
+    let pool = server.device_pool.stats();
+    exp.family(
+        "openflash_pool_devices",
+        "gauge",
+        "Devices in the pool by status.",
+    );
+    for status in DEVICE_STATUSES {
+        let count = server
+            .device_pool
+            .devices
+            .values()
+            .filter(|d| d.status == status)
+            .count();
+        exp.sample(
+            "openflash_pool_devices",
+            &[("status", &status_name(&status))],
+            count as f64,
+        );
+    }
+    exp.family(
+        "openflash_pool_jobs_completed_total",
+        "counter",
+        "Jobs completed by devices currently in the pool.",
+    );
+    exp.sample(
+        "openflash_pool_jobs_completed_total",
+        &[],
+        pool.total_jobs_completed as f64,
+    );
+    exp.family(
+        "openflash_pool_bytes_processed_total",
+        "counter",
+        "Bytes processed by devices currently in the pool.",
+    );
+    exp.sample(
+        "openflash_pool_bytes_processed_total",
+        &[],
+        pool.total_bytes_processed as f64,
+    );
+
+    // Queue
+    let queue = server.job_queue.stats();
+    exp.family(
+        "openflash_queue_jobs",
+        "gauge",
+        "Jobs in the queue and its retained history by state.",
+    );
+    for (state, count) in [
+        ("pending", queue.pending_count),
+        ("running", queue.running_count),
+        ("completed", queue.completed_count),
+        ("failed", queue.failed_count),
+        ("cancelled", queue.cancelled_count),
+    ] {
+        exp.sample("openflash_queue_jobs", &[("state", state)], count as f64);
+    }
+
+    // Devices
+    let mut devices: Vec<_> = server.device_pool.devices.values().collect();
+    devices.sort_by(|a, b| a.id.cmp(&b.id));
+    exp.family(
+        "openflash_device_up",
+        "gauge",
+        "1 when the device is available or busy.",
+    );
+    for device in &devices {
+        let up = matches!(device.status, DeviceStatus::Available | DeviceStatus::Busy);
+        exp.sample(
+            "openflash_device_up",
+            &[
+                ("device", &device.id),
+                ("status", &status_name(&device.status)),
+            ],
+            if up { 1.0 } else { 0.0 },
+        );
+    }
+    let per_device: [(&str, &str, &str, Sample<PoolDevice>); 4] = [
+        (
+            "openflash_device_jobs_completed_total",
+            "counter",
+            "Jobs completed by the device.",
+            |d| d.jobs_completed as f64,
+        ),
+        (
+            "openflash_device_bytes_processed_total",
+            "counter",
+            "Bytes read or written by the device.",
+            |d| d.bytes_processed as f64,
+        ),
+        (
+            "openflash_device_errors_total",
+            "counter",
+            "Failed jobs and units on the device.",
+            |d| d.error_count as f64,
+        ),
+        (
+            "openflash_device_last_seen_timestamp_seconds",
+            "gauge",
+            "When the device was last heard from.",
+            |d| d.last_seen as f64 / 1000.0,
+        ),
+    ];
+    for (name, kind, help, value) in per_device {
+        exp.family(name, kind, help);
+        for device in &devices {
+            exp.sample(name, &[("device", &device.id)], value(device));
+        }
+    }
+    exp.family(
+        "openflash_device_ecc_corrections_total",
+        "counter",
+        "ECC bit corrections reported by jobs and production units on the device.",
+    );
+    for device in &devices {
+        let corrections = server
+            .metrics
+            .device_ecc_corrections
+            .get(&device.id)
+            .copied()
+            .unwrap_or(0);
+        exp.sample(
+            "openflash_device_ecc_corrections_total",
+            &[("device", &device.id)],
+            corrections as f64,
+        );
+    }
+
+    // Jobs
+    exp.family(
+        "openflash_job_duration_seconds",
+        "histogram",
+        "Time from start to finish of jobs by type and final status.",
+    );
+    for ((job_type, status), histogram) in &server.metrics.job_durations {
+        let labels = [("type", job_type.as_str()), ("status", status.as_str())];
+        for (le, count) in histogram.cumulative() {
+            exp.sample(
+                "openflash_job_duration_seconds_bucket",
+                &[labels[0], labels[1], ("le", &le)],
+                count as f64,
+            );
+        }
+        exp.sample("openflash_job_duration_seconds_sum", &labels, histogram.sum);
+        exp.sample(
+            "openflash_job_duration_seconds_count",
+            &labels,
+            histogram.count as f64,
+        );
+    }
+
+    // Production
+    let lines = &server.metrics.lines;
+    exp.family(
+        "openflash_production_units_total",
+        "counter",
+        "Production units by line, station and result.",
+    );
+    for (line, metrics) in lines {
+        for (station, counters) in &metrics.stations {
+            for (result, count) in [
+                ("pass", counters.passed_units),
+                ("fail", counters.failed_units),
+            ] {
+                exp.sample(
+                    "openflash_production_units_total",
+                    &[("line", line), ("station", station), ("result", result)],
+                    count as f64,
+                );
+            }
+        }
+    }
+    exp.family(
+        "openflash_production_failures_total",
+        "counter",
+        "Failed production units by line and failure code.",
+    );
+    for (line, metrics) in lines {
+        let reasons: BTreeMap<_, _> = metrics.stats.failure_reasons.iter().collect();
+        for (reason, count) in reasons {
+            exp.sample(
+                "openflash_production_failures_total",
+                &[("line", line), ("reason", reason)],
+                *count as f64,
+            );
+        }
+    }
+    let per_station: [(&str, &str, Sample<StationCounters>); 2] = [
+        (
+            "openflash_production_ecc_corrections_total",
+            "ECC bit corrections seen on production units by station.",
+            |c| c.ecc_corrections as f64,
+        ),
+        (
+            "openflash_production_bad_blocks_total",
+            "Bad blocks found on production units by station.",
+            |c| c.bad_blocks as f64,
+        ),
+    ];
+    for (name, help, value) in per_station {
+        exp.family(name, "counter", help);
+        for (line, metrics) in lines {
+            for (station, counters) in &metrics.stations {
+                exp.sample(
+                    name,
+                    &[("line", line), ("station", station)],
+                    value(counters),
+                );
+            }
+        }
+    }
+    let per_line: [(&str, &str, Sample<ProductionStats>); 3] = [
+        (
+            "openflash_production_pass_ratio",
+            "Fraction of production units that passed.",
+            |s| s.pass_rate as f64 / 100.0,
+        ),
+        (
+            "openflash_production_cycle_time_seconds",
+            "Average production cycle time.",
+            |s| s.avg_cycle_time_ms as f64 / 1000.0,
+        ),
+        (
+            "openflash_production_units_per_hour",
+            "Production throughput since the first unit.",
+            |s| s.units_per_hour as f64,
+        ),
+    ];
+    for (name, help, value) in per_line {
+        exp.family(name, "gauge", help);
+        for (line, metrics) in lines {
+            exp.sample(name, &[("line", line)], value(&metrics.stats));
+        }
+    }
+
+    exp.out
+}
+
+// ============================================================================
+// HTTP endpoint
+// ============================================================================
+
+#[cfg(feature = "rest-server")]
+fn respond(server: &SharedServer, req: &Request<Incoming>) -> Response<Full<Bytes>> {
+    let (status, content_type, body) = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => (
+            StatusCode::OK,
+            limits::CONTENT_TYPE,
+            render(&lock_server(server)),
+        ),
+        (_, "/metrics") => (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "text/plain",
+            "method not allowed\n".to_string(),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            "text/plain",
+            "not found\n".to_string(),
+        ),
+    };
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    if let Ok(value) = content_type.parse() {
+        response
+            .headers_mut()
+            .insert(hyper::header::CONTENT_TYPE, value);
+    }
+    response
+}
+
+/// Listening Prometheus endpoint
+///
+/// Only available with the `rest-server` feature.
+#[cfg(feature = "rest-server")]
+pub struct MetricsServer {
+    server: SharedServer,
+    listener: TcpListener,
+}
+
+#[cfg(feature = "rest-server")]
+impl MetricsServer {
+    /// Bind `host:port` for scrapes of `server`
+    pub async fn bind(server: SharedServer, host: &str, port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((host, port)).await?;
+        Ok(Self { server, listener })
+    }
+
+    /// Address actually bound (useful with port 0)
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve until the process exits
+    pub async fn serve(self) -> std::io::Result<()> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve until `shutdown` completes
+    pub async fn serve_with_shutdown<F>(self, shutdown: F) -> std::io::Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        loop {
+            let accepted = tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                accepted = self.listener.accept() => accepted,
+            };
+            let stream = match accepted {
+                Ok((stream, _)) => stream,
+                Err(_) => {
+                    tokio::time::sleep(ms(limits::ACCEPT_BACKOFF_MS)).await;
+                    continue;
+                }
+            };
+            let server = self.server.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let response = respond(&server, &req);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{DevicePlatform, JobPriority, JobResult, JobType, ServerConfig};
+
+    fn unit(station: &str, passed: bool, ecc: u32) -> ProductionUnitResult {
+        let mut data = HashMap::new();
+        data.insert("device_id".to_string(), "dev1".to_string());
+        if !passed {
+            data.insert("failure_code".to_string(), "verify".to_string());
+        }
+        ProductionUnitResult {
+            serial_number: "SN".to_string(),
+            line_id: "line\"1".to_string(),
+            station_id: station.to_string(),
+            passed,
+            timestamp: 0,
+            duration_ms: 2000,
+            chip_info: None,
+            bad_blocks: 1,
+            ecc_corrections: ecc,
+            failure_reason: None,
+            data,
+        }
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        histogram.observe(9.0);
+        assert_eq!(
+            histogram.cumulative(),
+            vec![
+                ("1".to_string(), 1),
+                ("5".to_string(), 2),
+                ("+Inf".to_string(), 3)
+            ]
+        );
+        assert_eq!((histogram.sum, histogram.count), (12.5, 3));
+    }
+
+    #[test]
+    fn test_render_reports_jobs_devices_and_production() {
+        let mut server = OpenFlashServer::new(ServerConfig::default());
+        let mut device = PoolDevice::new("dev1", "Socket 1", "tcp://x", DevicePlatform::RP2040);
+        device.status = DeviceStatus::Available;
+        server.register_device(device).unwrap();
+        let job = Job::new(
+            "erase",
+            JobType::Erase {
+                start_address: 0,
+                length: None,
+            },
+        )
+        .with_priority(JobPriority::Normal);
+        let job_id = server.submit_job(job).unwrap();
+        server.start_next_job("dev1").unwrap();
+        let result = JobResult {
+            ecc_corrections: 4,
+            ..Default::default()
+        };
+        server.complete_job(job_id, result).unwrap();
+        server.record_production_result(unit("s1", true, 2));
+        server.record_production_result(unit("s1", false, 0));
+
+        let text = render(&server);
+        assert!(text.contains("# TYPE openflash_job_duration_seconds histogram\n"));
+        assert!(text.contains(
+            "openflash_job_duration_seconds_count{type=\"erase\",status=\"completed\"} 1\n"
+        ));
+        assert!(text.contains("openflash_pool_devices{status=\"available\"} 1\n"));
+        assert!(text.contains("openflash_queue_jobs{state=\"completed\"} 1\n"));
+        assert!(text.contains("openflash_device_ecc_corrections_total{device=\"dev1\"} 6\n"));
+        assert!(text.contains(
+            "openflash_production_units_total{line=\"line\\\"1\",station=\"s1\",result=\"fail\"} 1\n"
+        ));
+        assert!(text.contains(
+            "openflash_production_failures_total{line=\"line\\\"1\",reason=\"verify\"} 1\n"
+        ));
+        assert!(text.contains("openflash_production_pass_ratio{line=\"line\\\"1\"} 0.5\n"));
+        assert!(text.contains("openflash_production_cycle_time_seconds{line=\"line\\\"1\"} 2\n"));
+    }
+
+    #[cfg(feature = "rest-server")]
+    #[tokio::test]
+    async fn test_metrics_server_serves_scrapes() {
+        use std::sync::{Arc, Mutex};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let shared = Arc::new(Mutex::new(OpenFlashServer::new(ServerConfig::default())));
+        let metrics = MetricsServer::bind(shared, "127.0.0.1", 0).await.unwrap();
+        let addr = metrics.local_addr().unwrap();
+        tokio::spawn(metrics.serve());
+
+        let fetch = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                path
+            );
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = fetch("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(response.contains("openflash_info{version=\"2.0.0\"} 1"));
+        assert!(fetch("/other").await.starts_with("HTTP/1.1 404"));
+    }
+}
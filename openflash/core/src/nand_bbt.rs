@@ -0,0 +1,832 @@
+//! On-flash bad block table compatible with Linux/U-Boot `nand_bbt`
+//!
+//! The kernel's default flash BBT keeps two copies per chip in the last
+//! blocks of the chip: the main table tagged `Bbt0` and the mirror tagged
+//! `1tbB`, each carrying a version byte. The newest valid copy wins. Every
+//! block takes 2 bits, LSB first:
+//!
+//! | bits | meaning                         |
+//! |------|---------------------------------|
+//! | `11` | good                            |
+//! | `10` | worn (marked bad at runtime)    |
+//! | `00` | factory bad                     |
+//!
+//! The pattern and version live either in the OOB of the first table page
+//! (offsets 8 and 12, table data at the start of the page) or, for
+//! controllers using `NAND_BBT_NO_OOB`, in-band at the start of the page
+//! with the table following the version byte.
+
+use crate::nand_geometry::NandGeometry;
+use crate::write_ops::{BadBlockReason, BadBlockTable, ProgramTarget, WriteError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// On-flash BBT errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NandBbtError {
+    /// No table found for a chip
+    NotFound { chip: u32 },
+    /// No usable block left in the BBT area
+    NoSpace { chip: u32 },
+    /// Dump is shorter than the geometry requires
+    DumpTooShort { expected: usize, actual: usize },
+    /// Geometry cannot hold a table
+    InvalidGeometry(String),
+    /// Device access failed
+    Write(WriteError),
+}
+
+impl fmt::Display for NandBbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NandBbtError::NotFound { chip } => write!(f, "No bad block table on chip {}", chip),
+            NandBbtError::NoSpace { chip } => {
+                write!(f, "No good block left for the BBT on chip {}", chip)
+            }
+            NandBbtError::DumpTooShort { expected, actual } => write!(
+                f,
+                "Dump too short: expected {} bytes, got {}",
+                expected, actual
+            ),
+            NandBbtError::InvalidGeometry(msg) => write!(f, "Invalid geometry: {}", msg),
+            NandBbtError::Write(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NandBbtError {}
+
+impl From<WriteError> for NandBbtError {
+    fn from(e: WriteError) -> Self {
+        NandBbtError::Write(e)
+    }
+}
+
+pub type NandBbtResult<T> = Result<T, NandBbtError>;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Main table pattern
+pub const BBT_PATTERN_MAIN: &[u8; 4] = b"Bbt0";
+
+/// Mirror table pattern
+pub const BBT_PATTERN_MIRROR: &[u8; 4] = b"1tbB";
+
+/// Number of blocks at the end of each chip reserved for the tables
+pub const BBT_MAX_BLOCKS: u32 = 4;
+
+/// Pattern/version placement in the OOB of the first table page
+pub mod bbt_oob {
+    pub const PATTERN_OFFSET: usize = 8;
+    pub const VERSION_OFFSET: usize = 12;
+    pub const DATA_OFFSET: usize = 0;
+}
+
+/// In-band pattern/version placement (`NAND_BBT_NO_OOB`)
+pub mod bbt_inband {
+    pub const PATTERN_OFFSET: usize = 0;
+    pub const VERSION_OFFSET: usize = 4;
+    pub const DATA_OFFSET: usize = 5;
+}
+
+/// 2-bit on-flash block codes
+pub mod bbt_code {
+    pub const GOOD: u8 = 0b11;
+    pub const WORN: u8 = 0b10;
+    pub const FACTORY_BAD: u8 = 0b00;
+}
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Block state as tracked by `nand_bbt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BbtBlockState {
+    Good,
+    /// Marked bad at runtime (erase/program failure)
+    Worn,
+    /// Holds or may hold a BBT copy; stored as good on flash
+    Reserved,
+    FactoryBad,
+}
+
+impl BbtBlockState {
+    /// Decode the 2-bit on-flash code
+    pub fn from_code(code: u8) -> Self {
+        match code & 0b11 {
+            bbt_code::GOOD => BbtBlockState::Good,
+            bbt_code::FACTORY_BAD => BbtBlockState::FactoryBad,
+            _ => BbtBlockState::Worn,
+        }
+    }
+
+    /// Encode to the 2-bit on-flash code
+    pub fn to_code(&self) -> u8 {
+        match self {
+            BbtBlockState::Good | BbtBlockState::Reserved => bbt_code::GOOD,
+            BbtBlockState::Worn => bbt_code::WORN,
+            BbtBlockState::FactoryBad => bbt_code::FACTORY_BAD,
+        }
+    }
+
+    pub fn is_bad(&self) -> bool {
+        matches!(self, BbtBlockState::Worn | BbtBlockState::FactoryBad)
+    }
+}
+
+/// Where the pattern and version are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BbtLayout {
+    /// Pattern in OOB (kernel default)
+    Oob,
+    /// Pattern in the page data (`NAND_BBT_NO_OOB`)
+    InBand,
+}
+
+impl BbtLayout {
+    fn data_offset(&self) -> usize {
+        match self {
+            BbtLayout::Oob => bbt_oob::DATA_OFFSET,
+            BbtLayout::InBand => bbt_inband::DATA_OFFSET,
+        }
+    }
+}
+
+/// Table copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BbtCopy {
+    Main,
+    Mirror,
+}
+
+impl BbtCopy {
+    pub fn pattern(&self) -> &'static [u8; 4] {
+        match self {
+            BbtCopy::Main => BBT_PATTERN_MAIN,
+            BbtCopy::Mirror => BBT_PATTERN_MIRROR,
+        }
+    }
+}
+
+/// Location of a table copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BbtLocation {
+    pub chip: u32,
+    pub copy: BbtCopy,
+    /// Device-wide block number
+    pub block: u32,
+    pub version: u8,
+}
+
+/// Pages (data, OOB) of one table copy ready to program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BbtBlockImage {
+    pub location: BbtLocation,
+    pub pages: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+// ============================================================================
+// Bad Block Table
+// ============================================================================
+
+/// Device-wide flash BBT, one table per chip
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NandBbt {
+    page_size: u32,
+    oob_size: u32,
+    pages_per_block: u32,
+    blocks_per_chip: u32,
+    chips: u32,
+    layout: BbtLayout,
+    states: Vec<BbtBlockState>,
+    /// Known table copies (main and mirror per chip)
+    locations: Vec<BbtLocation>,
+}
+
+impl NandBbt {
+    /// Empty (all good) table; one chip per CE like the kernel's PERCHIP tables
+    pub fn new(geometry: &NandGeometry, layout: BbtLayout) -> NandBbtResult<Self> {
+        let bbt = Self {
+            page_size: geometry.page_size,
+            oob_size: geometry.oob_size,
+            pages_per_block: geometry.pages_per_block,
+            blocks_per_chip: geometry.blocks_per_ce(),
+            chips: geometry.ce_count.max(1) as u32,
+            layout,
+            states: vec![BbtBlockState::Good; geometry.total_blocks() as usize],
+            locations: Vec::new(),
+        };
+        if bbt.blocks_per_chip <= BBT_MAX_BLOCKS {
+            return Err(NandBbtError::InvalidGeometry(format!(
+                "{} blocks per chip",
+                bbt.blocks_per_chip
+            )));
+        }
+        if layout == BbtLayout::Oob && (bbt.oob_size as usize) < bbt_oob::VERSION_OFFSET + 1 {
+            return Err(NandBbtError::InvalidGeometry(format!(
+                "{}-byte OOB too small for the BBT pattern",
+                bbt.oob_size
+            )));
+        }
+        if bbt.table_pages() > bbt.pages_per_block {
+            return Err(NandBbtError::InvalidGeometry(
+                "table does not fit in one block".into(),
+            ));
+        }
+        let mut bbt = bbt;
+        bbt.reserve_table_area();
+        Ok(bbt)
+    }
+
+    /// Mark the BBT area of every chip reserved (unless bad)
+    fn reserve_table_area(&mut self) {
+        for chip in 0..self.chips {
+            for block in self.table_area(chip) {
+                if !self.states[block as usize].is_bad() {
+                    self.states[block as usize] = BbtBlockState::Reserved;
+                }
+            }
+        }
+    }
+
+    /// Candidate blocks for the tables of `chip`, last block first
+    pub fn table_area(&self, chip: u32) -> Vec<u32> {
+        let last = (chip + 1) * self.blocks_per_chip - 1;
+        (0..BBT_MAX_BLOCKS).map(|i| last - i).collect()
+    }
+
+    /// Table length per chip in bytes
+    pub fn table_bytes(&self) -> usize {
+        (self.blocks_per_chip as usize * 2 + 7) / 8
+    }
+
+    /// Pages occupied by one table copy
+    pub fn table_pages(&self) -> u32 {
+        let len = self.layout.data_offset() + self.table_bytes();
+        ((len + self.page_size as usize - 1) / self.page_size as usize) as u32
+    }
+
+    pub fn layout(&self) -> BbtLayout {
+        self.layout
+    }
+
+    pub fn chips(&self) -> u32 {
+        self.chips
+    }
+
+    /// Known table copies
+    pub fn locations(&self) -> &[BbtLocation] {
+        &self.locations
+    }
+
+    /// Current table version of `chip`
+    pub fn version(&self, chip: u32) -> u8 {
+        self.locations
+            .iter()
+            .filter(|l| l.chip == chip)
+            .map(|l| l.version)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn state(&self, block: u32) -> Option<BbtBlockState> {
+        self.states.get(block as usize).copied()
+    }
+
+    pub fn is_bad(&self, block: u32) -> bool {
+        self.state(block).map(|s| s.is_bad()).unwrap_or(true)
+    }
+
+    /// Mark a block bad at runtime
+    pub fn mark_worn(&mut self, block: u32) {
+        if let Some(state) = self.states.get_mut(block as usize) {
+            if *state != BbtBlockState::FactoryBad {
+                *state = BbtBlockState::Worn;
+            }
+        }
+    }
+
+    /// Mark a block factory bad
+    pub fn mark_factory_bad(&mut self, block: u32) {
+        if let Some(state) = self.states.get_mut(block as usize) {
+            *state = BbtBlockState::FactoryBad;
+        }
+    }
+
+    /// All bad blocks with their state
+    pub fn bad_blocks(&self) -> Vec<(u32, BbtBlockState)> {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.is_bad())
+            .map(|(b, s)| (b as u32, *s))
+            .collect()
+    }
+
+    // ------------------------------------------------------------------------
+    // Encoding
+    // ------------------------------------------------------------------------
+
+    /// Encode the 2-bit table of one chip
+    pub fn encode_chip(&self, chip: u32) -> Vec<u8> {
+        let mut table = vec![0xFF; self.table_bytes()];
+        let first = (chip * self.blocks_per_chip) as usize;
+        for i in 0..self.blocks_per_chip as usize {
+            let code = self.states[first + i].to_code();
+            let shift = (i % 4) * 2;
+            table[i / 4] &= !((!code & 0b11) << shift);
+        }
+        table
+    }
+
+    /// Load the 2-bit table of one chip
+    pub fn decode_chip(&mut self, chip: u32, table: &[u8]) {
+        let first = (chip * self.blocks_per_chip) as usize;
+        for i in 0..(self.blocks_per_chip as usize).min(table.len() * 4) {
+            let code = (table[i / 4] >> ((i % 4) * 2)) & 0b11;
+            self.states[first + i] = BbtBlockState::from_code(code);
+        }
+    }
+
+    /// Pages of a table copy with pattern and version
+    fn block_image(&self, location: BbtLocation) -> BbtBlockImage {
+        let page_size = self.page_size as usize;
+        let data_offset = self.layout.data_offset();
+        let mut data = vec![0xFF; self.table_pages() as usize * page_size];
+        let mut first_oob = vec![0xFF; self.oob_size as usize];
+
+        let table = self.encode_chip(location.chip);
+        data[data_offset..data_offset + table.len()].copy_from_slice(&table);
+        match self.layout {
+            BbtLayout::Oob => {
+                first_oob[bbt_oob::PATTERN_OFFSET..bbt_oob::PATTERN_OFFSET + 4]
+                    .copy_from_slice(location.copy.pattern());
+                first_oob[bbt_oob::VERSION_OFFSET] = location.version;
+            }
+            BbtLayout::InBand => {
+                data[bbt_inband::PATTERN_OFFSET..bbt_inband::PATTERN_OFFSET + 4]
+                    .copy_from_slice(location.copy.pattern());
+                data[bbt_inband::VERSION_OFFSET] = location.version;
+            }
+        }
+
+        let pages = data
+            .chunks(page_size)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let oob = if i == 0 {
+                    first_oob.clone()
+                } else {
+                    vec![0xFF; self.oob_size as usize]
+                };
+                (chunk.to_vec(), oob)
+            })
+            .collect();
+        BbtBlockImage { location, pages }
+    }
+
+    // ------------------------------------------------------------------------
+    // Scanning
+    // ------------------------------------------------------------------------
+
+    /// Find and load the newest table of every chip
+    fn scan<F>(&mut self, mut read_page: F) -> NandBbtResult<()>
+    where
+        F: FnMut(u32, u32, &mut [u8], &mut [u8]) -> NandBbtResult<()>,
+    {
+        let mut data = vec![0u8; self.page_size as usize];
+        let mut oob = vec![0u8; self.oob_size as usize];
+        self.locations.clear();
+
+        for chip in 0..self.chips {
+            let mut found: Vec<BbtLocation> = Vec::new();
+            for block in self.table_area(chip) {
+                read_page(block, 0, &mut data, &mut oob)?;
+                let (pattern, version) = match self.layout {
+                    BbtLayout::Oob => (
+                        &oob[bbt_oob::PATTERN_OFFSET..bbt_oob::PATTERN_OFFSET + 4],
+                        oob[bbt_oob::VERSION_OFFSET],
+                    ),
+                    BbtLayout::InBand => (
+                        &data[bbt_inband::PATTERN_OFFSET..bbt_inband::PATTERN_OFFSET + 4],
+                        data[bbt_inband::VERSION_OFFSET],
+                    ),
+                };
+                let copy = [BbtCopy::Main, BbtCopy::Mirror]
+                    .into_iter()
+                    .find(|c| c.pattern() == pattern);
+                if let Some(copy) = copy {
+                    // First match from the top wins, like the kernel's search
+                    if !found.iter().any(|l| l.copy == copy) {
+                        found.push(BbtLocation {
+                            chip,
+                            copy,
+                            block,
+                            version,
+                        });
+                    }
+                }
+            }
+
+            let newest = found
+                .iter()
+                .max_by_key(|l| (l.version, l.copy == BbtCopy::Main))
+                .copied()
+                .ok_or(NandBbtError::NotFound { chip })?;
+
+            let mut table = Vec::with_capacity(self.table_pages() as usize * data.len());
+            for page in 0..self.table_pages() {
+                read_page(newest.block, page, &mut data, &mut oob)?;
+                table.extend_from_slice(&data);
+            }
+            let offset = self.layout.data_offset();
+            self.decode_chip(chip, &table[offset..offset + self.table_bytes()]);
+            self.locations.extend(found);
+        }
+
+        self.reserve_table_area();
+        Ok(())
+    }
+
+    /// Load the tables from a raw dump (data + OOB per page)
+    pub fn from_dump(
+        geometry: &NandGeometry,
+        layout: BbtLayout,
+        dump: &[u8],
+    ) -> NandBbtResult<Self> {
+        let mut bbt = Self::new(geometry, layout)?;
+        let page_size = bbt.page_size as usize;
+        let raw_page = page_size + bbt.oob_size as usize;
+        let expected = bbt.states.len() * bbt.pages_per_block as usize * raw_page;
+        if dump.len() < expected {
+            return Err(NandBbtError::DumpTooShort {
+                expected,
+                actual: dump.len(),
+            });
+        }
+
+        let pages_per_block = bbt.pages_per_block;
+        bbt.scan(|block, page, data, oob| {
+            let start = (block * pages_per_block + page) as usize * raw_page;
+            data.copy_from_slice(&dump[start..start + page_size]);
+            oob.copy_from_slice(&dump[start + page_size..start + raw_page]);
+            Ok(())
+        })?;
+        Ok(bbt)
+    }
+
+    /// Load the tables from a device
+    pub fn read_from<T: ProgramTarget>(
+        geometry: &NandGeometry,
+        layout: BbtLayout,
+        target: &mut T,
+    ) -> NandBbtResult<Self> {
+        let mut bbt = Self::new(geometry, layout)?;
+        bbt.scan(|block, page, data, oob| {
+            target
+                .read_page(block, page, data, oob)
+                .map_err(NandBbtError::from)
+        })?;
+        Ok(bbt)
+    }
+
+    // ------------------------------------------------------------------------
+    // Writing
+    // ------------------------------------------------------------------------
+
+    /// Pick the main and mirror blocks of `chip`
+    ///
+    /// Existing locations are kept while still good; otherwise the highest
+    /// good blocks of the BBT area are used, as the kernel does.
+    fn select_blocks(&self, chip: u32) -> NandBbtResult<[u32; 2]> {
+        let mut chosen: Vec<u32> = Vec::new();
+        for copy in [BbtCopy::Main, BbtCopy::Mirror] {
+            let existing = self
+                .locations
+                .iter()
+                .find(|l| l.chip == chip && l.copy == copy && !self.is_bad(l.block))
+                .map(|l| l.block)
+                .filter(|b| !chosen.contains(b));
+            let block = existing
+                .or_else(|| {
+                    self.table_area(chip).into_iter().find(|b| {
+                        !self.is_bad(*b)
+                            && !chosen.contains(b)
+                            && !self
+                                .locations
+                                .iter()
+                                .any(|l| l.block == *b && l.copy != copy && !self.is_bad(l.block))
+                    })
+                })
+                .ok_or(NandBbtError::NoSpace { chip })?;
+            chosen.push(block);
+        }
+        Ok([chosen[0], chosen[1]])
+    }
+
+    /// Table copies to program, with the version of each chip bumped
+    pub fn write_plan(&mut self) -> NandBbtResult<Vec<BbtBlockImage>> {
+        let mut images = Vec::new();
+        let mut locations = Vec::new();
+        for chip in 0..self.chips {
+            let version = self.version(chip).wrapping_add(1).max(1);
+            let [main, mirror] = self.select_blocks(chip)?;
+            for (copy, block) in [(BbtCopy::Main, main), (BbtCopy::Mirror, mirror)] {
+                let location = BbtLocation {
+                    chip,
+                    copy,
+                    block,
+                    version,
+                };
+                images.push(self.block_image(location));
+                locations.push(location);
+            }
+        }
+        self.locations = locations;
+        Ok(images)
+    }
+
+    /// Write the tables to a device
+    ///
+    /// A BBT block failing to erase or program is marked worn and the table
+    /// moves to the next good block of the area.
+    pub fn write_to<T: ProgramTarget>(
+        &mut self,
+        target: &mut T,
+    ) -> NandBbtResult<Vec<BbtLocation>> {
+        let previous = self.locations.clone();
+        loop {
+            let images = self.write_plan()?;
+            let mut failed = None;
+
+            'images: for image in &images {
+                let block = image.location.block;
+                match target.erase_block(block) {
+                    Ok(()) => {}
+                    Err(WriteError::EraseFailed(_)) => {
+                        failed = Some(block);
+                        break;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+                for (page, (data, oob)) in image.pages.iter().enumerate() {
+                    match target.program_page(block, page as u32, data, oob) {
+                        Ok(()) => {}
+                        Err(WriteError::ProgramFailed { .. }) => {
+                            failed = Some(block);
+                            break 'images;
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+
+            match failed {
+                Some(block) => {
+                    // The bad block is recorded in the next attempt's table
+                    self.mark_worn(block);
+                    self.locations = previous.clone();
+                }
+                None => return Ok(self.locations.clone()),
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // BadBlockTable interop
+    // ------------------------------------------------------------------------
+
+    /// Copy bad blocks from a programmer's `BadBlockTable`
+    pub fn import_bad_blocks(&mut self, table: &BadBlockTable) {
+        for block in table.bad_blocks() {
+            match table.reason(block) {
+                Some(BadBlockReason::Factory) => self.mark_factory_bad(block),
+                _ => self.mark_worn(block),
+            }
+        }
+    }
+
+    /// Record the device's bad blocks in a `BadBlockTable`
+    pub fn export_bad_blocks(&self, table: &mut BadBlockTable) -> NandBbtResult<()> {
+        for (block, state) in self.bad_blocks() {
+            let reason = match state {
+                BbtBlockState::FactoryBad => BadBlockReason::Factory,
+                _ => BadBlockReason::WearOut,
+            };
+            table.mark_bad(block, reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    struct MockNand {
+        geometry: NandGeometry,
+        pages: HashMap<(u32, u32), (Vec<u8>, Vec<u8>)>,
+        erase_fail: HashSet<u32>,
+    }
+
+    impl MockNand {
+        fn new(geometry: NandGeometry) -> Self {
+            Self {
+                geometry,
+                pages: HashMap::new(),
+                erase_fail: HashSet::new(),
+            }
+        }
+
+        fn dump(&self) -> Vec<u8> {
+            let g = &self.geometry;
+            let mut out = Vec::new();
+            for block in 0..g.total_blocks() {
+                for page in 0..g.pages_per_block {
+                    match self.pages.get(&(block, page)) {
+                        Some((d, o)) => {
+                            out.extend_from_slice(d);
+                            out.extend_from_slice(o);
+                        }
+                        None => out.extend(vec![0xFF; g.raw_page_size() as usize]),
+                    }
+                }
+            }
+            out
+        }
+    }
+
+    impl ProgramTarget for MockNand {
+        fn erase_block(&mut self, block: u32) -> Result<(), WriteError> {
+            if self.erase_fail.contains(&block) {
+                return Err(WriteError::EraseFailed(block));
+            }
+            self.pages.retain(|&(b, _), _| b != block);
+            Ok(())
+        }
+
+        fn program_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &[u8],
+            oob: &[u8],
+        ) -> Result<(), WriteError> {
+            self.pages
+                .insert((block, page), (data.to_vec(), oob.to_vec()));
+            Ok(())
+        }
+
+        fn read_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &mut [u8],
+            oob: &mut [u8],
+        ) -> Result<(), WriteError> {
+            match self.pages.get(&(block, page)) {
+                Some((d, o)) => {
+                    data.copy_from_slice(d);
+                    oob.copy_from_slice(o);
+                }
+                None => {
+                    data.fill(0xFF);
+                    oob.fill(0xFF);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn geometry() -> NandGeometry {
+        NandGeometry::new(512, 16, 4, 64).with_ce_count(2)
+    }
+
+    #[test]
+    fn test_bbt_encoding() {
+        let mut bbt = NandBbt::new(&geometry(), BbtLayout::Oob).unwrap();
+        assert_eq!(bbt.table_bytes(), 16);
+        bbt.mark_factory_bad(1);
+        bbt.mark_worn(6);
+        let table = bbt.encode_chip(0);
+        // Block 1 = 00, blocks 0/2/3 = 11
+        assert_eq!(table[0], 0b1111_0011);
+        // Block 6 = 10
+        assert_eq!(table[1], 0b1110_1111);
+        // Reserved BBT area is stored as good
+        assert_eq!(table[15], 0xFF);
+
+        let mut other = NandBbt::new(&geometry(), BbtLayout::Oob).unwrap();
+        other.decode_chip(0, &table);
+        assert_eq!(other.state(1), Some(BbtBlockState::FactoryBad));
+        assert_eq!(other.state(6), Some(BbtBlockState::Worn));
+        assert_eq!(other.state(5), Some(BbtBlockState::Good));
+        assert_eq!(BbtBlockState::from_code(0b01), BbtBlockState::Worn);
+    }
+
+    #[test]
+    fn test_bbt_write_and_scan() {
+        let geometry = geometry();
+        let mut nand = MockNand::new(geometry.clone());
+        nand.erase_fail.insert(63);
+
+        let mut bbt = NandBbt::new(&geometry, BbtLayout::Oob).unwrap();
+        bbt.mark_factory_bad(3);
+        bbt.mark_worn(70);
+        let locations = bbt.write_to(&mut nand).unwrap();
+
+        // Block 63 failed, chip 0 tables moved down; chip 1 uses its top blocks
+        let blocks: Vec<(u32, BbtCopy, u32)> = locations
+            .iter()
+            .map(|l| (l.chip, l.copy, l.block))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, BbtCopy::Main, 62),
+                (0, BbtCopy::Mirror, 61),
+                (1, BbtCopy::Main, 127),
+                (1, BbtCopy::Mirror, 126),
+            ]
+        );
+        let (_, oob) = &nand.pages[&(62, 0)];
+        assert_eq!(&oob[8..12], b"Bbt0");
+        assert_eq!(oob[12], 1);
+        assert_eq!(&nand.pages[&(61, 0)].1[8..12], b"1tbB");
+
+        let loaded = NandBbt::from_dump(&geometry, BbtLayout::Oob, &nand.dump()).unwrap();
+        assert_eq!(
+            loaded.bad_blocks(),
+            vec![
+                (3, BbtBlockState::FactoryBad),
+                (63, BbtBlockState::Worn),
+                (70, BbtBlockState::Worn),
+            ]
+        );
+        assert_eq!(loaded.version(0), 1);
+
+        // Rewrite bumps the version and keeps the locations
+        let mut loaded = loaded;
+        loaded.mark_worn(10);
+        loaded.write_to(&mut nand).unwrap();
+        let again = NandBbt::read_from(&geometry, BbtLayout::Oob, &mut nand).unwrap();
+        assert_eq!(again.version(0), 2);
+        assert!(again.is_bad(10));
+        assert_eq!(again.locations()[0].block, 62);
+
+        let mut table = BadBlockTable::new(geometry.total_blocks(), 10);
+        again.export_bad_blocks(&mut table).unwrap();
+        assert_eq!(table.reason(3), Some(BadBlockReason::Factory));
+        assert_eq!(table.reason(10), Some(BadBlockReason::WearOut));
+    }
+
+    #[test]
+    fn test_bbt_inband_and_newest_copy() {
+        let geometry = NandGeometry::new(512, 16, 4, 64);
+        let mut bbt = NandBbt::new(&geometry, BbtLayout::InBand).unwrap();
+        bbt.mark_worn(5);
+        let images = bbt.write_plan().unwrap();
+        let main = &images[0].pages[0].0;
+        assert_eq!(&main[..4], b"Bbt0");
+        assert_eq!(main[4], 1);
+        assert_eq!(main[5 + 1], 0b1111_1011);
+
+        let mut nand = MockNand::new(geometry.clone());
+        for image in &images {
+            for (page, (d, o)) in image.pages.iter().enumerate() {
+                nand.program_page(image.location.block, page as u32, d, o)
+                    .unwrap();
+            }
+        }
+
+        // Newer mirror wins over a stale main
+        bbt.mark_worn(9);
+        let newer = bbt.write_plan().unwrap();
+        let mirror = &newer[1];
+        nand.erase_block(mirror.location.block).unwrap();
+        for (page, (d, o)) in mirror.pages.iter().enumerate() {
+            nand.program_page(mirror.location.block, page as u32, d, o)
+                .unwrap();
+        }
+        let loaded = NandBbt::from_dump(&geometry, BbtLayout::InBand, &nand.dump()).unwrap();
+        assert!(loaded.is_bad(9));
+        assert_eq!(loaded.version(0), 2);
+
+        assert_eq!(
+            NandBbt::from_dump(&geometry, BbtLayout::Oob, &nand.dump()),
+            Err(NandBbtError::NotFound { chip: 0 })
+        );
+        assert!(matches!(
+            NandBbt::from_dump(&geometry, BbtLayout::InBand, &[0u8; 16]),
+            Err(NandBbtError::DumpTooShort { .. })
+        ));
+    }
+}
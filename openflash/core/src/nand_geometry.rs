@@ -0,0 +1,602 @@
+//! Multi-die parallel NAND geometry and command sequences
+//!
+//! Packages of 4 GB and up usually stack several dies behind separate
+//! chip-enables (CE), and each CE may expose several LUNs. This module
+//! describes that organisation, maps linear block numbers onto
+//! CE/LUN/block/page addresses, and builds the packet sequences for
+//! page reads, cache reads (0x31/0x3F), multi-plane operations and
+//! per-LUN status polling (0x78).
+
+use crate::onfi::{select_onfi_parameter_page, NandChipInfo};
+use crate::protocol::{nand_commands, Command, Packet};
+use serde::{Deserialize, Serialize};
+
+/// Multi-plane parallel NAND opcodes
+pub mod commands {
+    /// Multi-plane read (queue plane, more to follow)
+    pub const READ_MULTIPLANE: u8 = 0x32;
+    /// Change read column enhanced
+    pub const CHANGE_READ_COLUMN_ENH: u8 = 0x06;
+    /// Change read column confirm
+    pub const CHANGE_READ_COLUMN_END: u8 = 0xE0;
+    /// Multi-plane block erase (queue plane, more to follow)
+    pub const ERASE_MULTIPLANE: u8 = 0xD1;
+}
+
+// ============================================================================
+// Geometry
+// ============================================================================
+
+/// Package organisation of a parallel NAND device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NandGeometry {
+    pub page_size: u32,
+    pub oob_size: u32,
+    pub pages_per_block: u32,
+    pub blocks_per_lun: u32,
+    pub luns_per_ce: u8,
+    pub ce_count: u8,
+    /// Planes per LUN (1 if multi-plane is not supported)
+    pub planes: u8,
+    pub row_cycles: u8,
+    pub column_cycles: u8,
+    /// Read cache (0x31/0x3F) supported
+    pub cache_read: bool,
+    /// Read status enhanced (0x78) supported
+    pub status_enhanced: bool,
+}
+
+impl NandGeometry {
+    /// Single-die geometry with default addressing (2 column, 3 row cycles)
+    pub fn new(page_size: u32, oob_size: u32, pages_per_block: u32, blocks_per_lun: u32) -> Self {
+        Self {
+            page_size,
+            oob_size,
+            pages_per_block,
+            blocks_per_lun,
+            luns_per_ce: 1,
+            ce_count: 1,
+            planes: 1,
+            row_cycles: 3,
+            column_cycles: 2,
+            cache_read: false,
+            status_enhanced: false,
+        }
+    }
+
+    /// Single-die geometry from an ID-table entry
+    pub fn from_chip_info(info: &NandChipInfo) -> Self {
+        let block_bytes = info.page_size as u64 * info.block_size as u64;
+        let blocks = (info.size_mb as u64 * 1024 * 1024)
+            .checked_div(block_bytes)
+            .unwrap_or(0) as u32;
+        Self::new(info.page_size, info.oob_size, info.block_size, blocks)
+    }
+
+    /// Geometry of one CE from a raw ONFI parameter page read
+    ///
+    /// `ce_count` is not part of the parameter page; set it with
+    /// `with_ce_count` after probing each CE (see `detect_ce_count`).
+    pub fn from_onfi_parameter_page(raw: &[u8]) -> Option<Self> {
+        let page = select_onfi_parameter_page(raw)?.data;
+
+        let features = u16::from_le_bytes([page[6], page[7]]);
+        let optional = u16::from_le_bytes([page[8], page[9]]);
+        let cycles = page[101];
+        let interleave_bits = page[114] & 0x0F;
+        // Features bit 3: multi-plane program/erase, bit 6: multi-plane read
+        let multi_plane = features & 0x0048 != 0;
+
+        Some(Self {
+            page_size: u32::from_le_bytes([page[80], page[81], page[82], page[83]]),
+            oob_size: u16::from_le_bytes([page[84], page[85]]) as u32,
+            pages_per_block: u32::from_le_bytes([page[92], page[93], page[94], page[95]]),
+            blocks_per_lun: u32::from_le_bytes([page[96], page[97], page[98], page[99]]),
+            luns_per_ce: page[100].max(1),
+            ce_count: 1,
+            planes: if multi_plane {
+                1u8 << interleave_bits.min(3)
+            } else {
+                1
+            },
+            row_cycles: (cycles & 0x0F).max(1),
+            column_cycles: (cycles >> 4).max(1),
+            cache_read: optional & 0x0002 != 0,
+            status_enhanced: optional & 0x0008 != 0,
+        })
+    }
+
+    /// Set the number of populated chip-enables
+    pub fn with_ce_count(mut self, ce_count: u8) -> Self {
+        self.ce_count = ce_count.max(1);
+        self
+    }
+
+    /// Total LUNs across all CEs
+    pub fn total_luns(&self) -> u32 {
+        self.ce_count as u32 * self.luns_per_ce as u32
+    }
+
+    /// Blocks behind one CE
+    pub fn blocks_per_ce(&self) -> u32 {
+        self.blocks_per_lun * self.luns_per_ce as u32
+    }
+
+    /// Blocks across the whole package
+    pub fn total_blocks(&self) -> u32 {
+        self.blocks_per_lun * self.total_luns()
+    }
+
+    /// Package capacity in bytes (main area only)
+    pub fn capacity(&self) -> u64 {
+        self.total_blocks() as u64 * self.pages_per_block as u64 * self.page_size as u64
+    }
+
+    /// Raw page length including OOB
+    pub fn raw_page_size(&self) -> u32 {
+        self.page_size + self.oob_size
+    }
+
+    /// Map a package-wide block/page onto a CE/LUN address
+    pub fn locate(&self, block: u32, page: u32) -> Option<NandAddress> {
+        if block >= self.total_blocks() || page >= self.pages_per_block {
+            return None;
+        }
+        let lun_index = block / self.blocks_per_lun.max(1);
+        Some(NandAddress {
+            ce: (lun_index / self.luns_per_ce as u32) as u8,
+            lun: (lun_index % self.luns_per_ce as u32) as u8,
+            block: block % self.blocks_per_lun,
+            page,
+            column: 0,
+        })
+    }
+
+    /// Package-wide block number of an address
+    pub fn global_block(&self, addr: &NandAddress) -> u32 {
+        (addr.ce as u32 * self.luns_per_ce as u32 + addr.lun as u32) * self.blocks_per_lun
+            + addr.block
+    }
+
+    /// Plane a block belongs to
+    pub fn plane_of(&self, block: u32) -> u8 {
+        (block % self.planes.max(1) as u32) as u8
+    }
+
+    fn page_bits(&self) -> u32 {
+        address_bits(self.pages_per_block)
+    }
+
+    fn block_bits(&self) -> u32 {
+        address_bits(self.blocks_per_lun)
+    }
+
+    /// Row address (page | block | LUN) for an address
+    pub fn row_address(&self, addr: &NandAddress) -> u32 {
+        let page_bits = self.page_bits();
+        let lun_shift = page_bits + self.block_bits();
+        addr.page | (addr.block << page_bits) | ((addr.lun as u32) << lun_shift)
+    }
+
+    /// Row address of LUN `lun` (block 0, page 0), used by 0x78
+    pub fn lun_row_address(&self, lun: u8) -> u32 {
+        (lun as u32) << (self.page_bits() + self.block_bits())
+    }
+
+    /// Column then row address cycles for an address
+    pub fn address_cycles(&self, addr: &NandAddress) -> Vec<u8> {
+        let mut cycles = Vec::with_capacity((self.column_cycles + self.row_cycles) as usize);
+        cycles.extend(
+            addr.column
+                .to_le_bytes()
+                .iter()
+                .take(self.column_cycles as usize),
+        );
+        cycles.extend(
+            self.row_address(addr)
+                .to_le_bytes()
+                .iter()
+                .take(self.row_cycles as usize),
+        );
+        cycles
+    }
+}
+
+/// Bits needed to address `count` items
+fn address_bits(count: u32) -> u32 {
+    if count <= 1 {
+        0
+    } else {
+        32 - (count - 1).leading_zeros()
+    }
+}
+
+/// Count populated chip-enables from per-CE parameter page reads
+///
+/// CE0 must answer with a valid parameter page; each following CE counts
+/// while it returns the same page (identical dies).
+pub fn detect_ce_count(per_ce_raw: &[Vec<u8>]) -> u8 {
+    let first = match per_ce_raw
+        .first()
+        .and_then(|raw| select_onfi_parameter_page(raw))
+    {
+        Some(page) => page.data,
+        None => return 0,
+    };
+
+    let mut count = 1u8;
+    for raw in &per_ce_raw[1..] {
+        match select_onfi_parameter_page(raw) {
+            Some(page) if page.data == first => count = count.saturating_add(1),
+            _ => break,
+        }
+    }
+    count
+}
+
+/// Physical address inside a multi-die package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NandAddress {
+    pub ce: u8,
+    pub lun: u8,
+    /// Block within the LUN
+    pub block: u32,
+    /// Page within the block
+    pub page: u32,
+    /// Byte offset within the page
+    pub column: u16,
+}
+
+// ============================================================================
+// Status
+// ============================================================================
+
+/// Status register bits (0x70 / 0x78)
+pub mod status_bits {
+    pub const FAIL: u8 = 0x01;
+    pub const FAILC: u8 = 0x02;
+    pub const CSP: u8 = 0x08;
+    pub const ARDY: u8 = 0x20;
+    pub const RDY: u8 = 0x40;
+    pub const WP_N: u8 = 0x80;
+}
+
+/// Decoded status of one LUN
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LunStatus {
+    pub raw: u8,
+}
+
+impl LunStatus {
+    pub fn new(raw: u8) -> Self {
+        Self { raw }
+    }
+
+    /// LUN accepts new commands
+    pub fn is_ready(&self) -> bool {
+        self.raw & status_bits::RDY != 0
+    }
+
+    /// Array operation (including cache operations) finished
+    pub fn is_array_ready(&self) -> bool {
+        self.raw & status_bits::ARDY != 0
+    }
+
+    /// Last operation failed
+    pub fn failed(&self) -> bool {
+        self.raw & status_bits::FAIL != 0
+    }
+
+    /// Previous cached operation failed
+    pub fn previous_failed(&self) -> bool {
+        self.raw & status_bits::FAILC != 0
+    }
+
+    pub fn write_protected(&self) -> bool {
+        self.raw & status_bits::WP_N == 0
+    }
+}
+
+// ============================================================================
+// Command Sequences
+// ============================================================================
+
+fn cmd(opcode: u8) -> Packet {
+    Packet::new(Command::NandCmd, &[opcode])
+}
+
+fn addr_cycles(cycles: &[u8]) -> impl Iterator<Item = Packet> + '_ {
+    cycles.iter().map(|c| Packet::new(Command::NandAddr, &[*c]))
+}
+
+fn read_data(len: u32) -> Packet {
+    Packet::new(
+        Command::NandReadData,
+        &(len.min(u16::MAX as u32) as u16).to_le_bytes(),
+    )
+}
+
+/// Select chip-enable `ce` (deasserts the others)
+pub fn select_ce(ce: u8) -> Packet {
+    Packet::new(Command::NandSelectCe, &[ce])
+}
+
+/// Single page read: CE select, 00h, address, 30h, data out
+pub fn page_read_sequence(geom: &NandGeometry, addr: &NandAddress) -> Vec<Packet> {
+    let mut packets = vec![select_ce(addr.ce), cmd(nand_commands::READ1)];
+    packets.extend(addr_cycles(&geom.address_cycles(addr)));
+    packets.push(cmd(nand_commands::READ2));
+    packets.push(read_data(geom.raw_page_size()));
+    packets
+}
+
+/// Sequential cache read of `count` pages starting at `start`
+///
+/// Issues 00h-addr-30h once, then one cache command per page: 31h (move
+/// the page to the cache register and start loading the next) before each
+/// page's data and 3Fh before the last page's data. Stops at the end of
+/// the block.
+pub fn cache_read_sequence(geom: &NandGeometry, start: &NandAddress, count: u32) -> Vec<Packet> {
+    let count = count.min(geom.pages_per_block.saturating_sub(start.page));
+    if count == 0 {
+        return Vec::new();
+    }
+    if count == 1 || !geom.cache_read {
+        let mut packets = Vec::new();
+        for i in 0..count {
+            let addr = NandAddress {
+                page: start.page + i,
+                ..*start
+            };
+            packets.extend(page_read_sequence(geom, &addr));
+        }
+        return packets;
+    }
+
+    let mut packets = vec![select_ce(start.ce), cmd(nand_commands::READ1)];
+    packets.extend(addr_cycles(&geom.address_cycles(start)));
+    packets.push(cmd(nand_commands::READ2));
+
+    for i in 0..count {
+        if i == count - 1 {
+            packets.push(cmd(nand_commands::READ_CACHE_END));
+        } else {
+            packets.push(cmd(nand_commands::READ_CACHE_SEQ));
+        }
+        packets.push(read_data(geom.raw_page_size()));
+    }
+    packets
+}
+
+/// Multi-plane page read of the same page in one block per plane
+///
+/// All addresses must share CE and LUN and sit in distinct planes.
+pub fn multi_plane_read_sequence(
+    geom: &NandGeometry,
+    addrs: &[NandAddress],
+) -> Option<Vec<Packet>> {
+    let first = addrs.first()?;
+    if addrs.len() > geom.planes as usize
+        || addrs.iter().any(|a| a.ce != first.ce || a.lun != first.lun)
+    {
+        return None;
+    }
+    let mut planes: Vec<u8> = addrs.iter().map(|a| geom.plane_of(a.block)).collect();
+    planes.sort_unstable();
+    planes.dedup();
+    if planes.len() != addrs.len() {
+        return None;
+    }
+
+    let mut packets = vec![select_ce(first.ce)];
+    for (i, addr) in addrs.iter().enumerate() {
+        packets.push(cmd(nand_commands::READ1));
+        packets.extend(addr_cycles(&geom.address_cycles(addr)));
+        if i == addrs.len() - 1 {
+            packets.push(cmd(nand_commands::READ2));
+        } else {
+            packets.push(cmd(commands::READ_MULTIPLANE));
+        }
+    }
+    for addr in addrs {
+        packets.push(cmd(commands::CHANGE_READ_COLUMN_ENH));
+        packets.extend(addr_cycles(&geom.address_cycles(addr)));
+        packets.push(cmd(commands::CHANGE_READ_COLUMN_END));
+        packets.push(read_data(geom.raw_page_size()));
+    }
+    Some(packets)
+}
+
+/// Multi-plane block erase (60h-row-D1h ... 60h-row-D0h)
+pub fn multi_plane_erase_sequence(
+    geom: &NandGeometry,
+    addrs: &[NandAddress],
+) -> Option<Vec<Packet>> {
+    let first = addrs.first()?;
+    if addrs.len() > geom.planes as usize
+        || addrs.iter().any(|a| a.ce != first.ce || a.lun != first.lun)
+    {
+        return None;
+    }
+
+    let mut packets = vec![select_ce(first.ce)];
+    for (i, addr) in addrs.iter().enumerate() {
+        let row = geom.row_address(&NandAddress { page: 0, ..*addr });
+        packets.push(cmd(nand_commands::BLOCKERASE));
+        packets.extend(addr_cycles(
+            &row.to_le_bytes()[..geom.row_cycles.min(4) as usize],
+        ));
+        if i == addrs.len() - 1 {
+            packets.push(cmd(nand_commands::ERASESTART));
+        } else {
+            packets.push(cmd(commands::ERASE_MULTIPLANE));
+        }
+    }
+    Some(packets)
+}
+
+/// Read status of one LUN
+///
+/// Uses 78h with the LUN row address when supported so other LUNs on the
+/// same CE keep working; falls back to 70h on single-LUN parts.
+pub fn lun_status_sequence(geom: &NandGeometry, ce: u8, lun: u8) -> Vec<Packet> {
+    let mut packets = vec![select_ce(ce)];
+    if geom.status_enhanced && geom.luns_per_ce > 1 {
+        packets.push(cmd(nand_commands::READ_STATUS_ENHANCED));
+        let row = geom.lun_row_address(lun).to_le_bytes();
+        packets.extend(addr_cycles(&row[..geom.row_cycles.min(4) as usize]));
+    } else {
+        packets.push(cmd(nand_commands::READSTATUS));
+    }
+    packets.push(read_data(1));
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onfi::write_param_page_crc;
+
+    fn onfi_page(luns: u8) -> Vec<u8> {
+        let mut page = vec![0u8; 256];
+        page[0..4].copy_from_slice(b"ONFI");
+        page[4] = 0x04;
+        page[6] = 0x48; // multi-plane program/erase + read
+        page[8] = 0x0A; // read cache + read status enhanced
+        page[80..84].copy_from_slice(&8192u32.to_le_bytes());
+        page[84..86].copy_from_slice(&448u16.to_le_bytes());
+        page[92..96].copy_from_slice(&256u32.to_le_bytes());
+        page[96..100].copy_from_slice(&2048u32.to_le_bytes());
+        page[100] = luns;
+        page[101] = 0x23;
+        page[114] = 1;
+        write_param_page_crc(&mut page);
+        page
+    }
+
+    fn geometry() -> NandGeometry {
+        NandGeometry::from_onfi_parameter_page(&onfi_page(2))
+            .unwrap()
+            .with_ce_count(2)
+    }
+
+    #[test]
+    fn test_geometry_from_onfi() {
+        let geom = geometry();
+        assert_eq!(geom.luns_per_ce, 2);
+        assert_eq!(geom.ce_count, 2);
+        assert_eq!(geom.planes, 2);
+        assert_eq!(geom.row_cycles, 3);
+        assert_eq!(geom.column_cycles, 2);
+        assert!(geom.cache_read);
+        assert!(geom.status_enhanced);
+        assert_eq!(geom.total_blocks(), 2048 * 4);
+        assert_eq!(geom.capacity(), 8192 * 256 * 8192);
+    }
+
+    #[test]
+    fn test_locate_and_row_address() {
+        let geom = geometry();
+        let addr = geom.locate(2048 * 3 + 5, 7).unwrap();
+        assert_eq!((addr.ce, addr.lun, addr.block, addr.page), (1, 1, 5, 7));
+        assert_eq!(geom.global_block(&addr), 2048 * 3 + 5);
+
+        // 8 page bits, 11 block bits, LUN above
+        assert_eq!(geom.row_address(&addr), 7 | (5 << 8) | (1 << 19));
+        assert_eq!(geom.address_cycles(&addr), vec![0, 0, 7, 5, 0x08]);
+        assert!(geom.locate(2048 * 4, 0).is_none());
+    }
+
+    #[test]
+    fn test_detect_ce_count() {
+        let page = onfi_page(2);
+        let other = onfi_page(1);
+        assert_eq!(detect_ce_count(&[page.clone(), page.clone(), other]), 2);
+        assert_eq!(detect_ce_count(&[vec![0xFF; 256], page]), 0);
+    }
+
+    #[test]
+    fn test_cache_read_sequence() {
+        let geom = geometry();
+        let start = geom.locate(10, 0).unwrap();
+        let packets = cache_read_sequence(&geom, &start, 3);
+
+        // Commands and data reads in order; address cycles left out
+        let steps: Vec<Option<u8>> = packets
+            .iter()
+            .filter_map(|p| match p.cmd {
+                Command::NandCmd => Some(Some(p.args[0])),
+                Command::NandReadData => Some(None),
+                _ => None,
+            })
+            .collect();
+        let expected = [
+            Some(0x00),
+            Some(0x30),
+            Some(0x31),
+            None,
+            Some(0x31),
+            None,
+            Some(0x3F),
+            None,
+        ];
+        assert_eq!(steps, expected);
+        assert_eq!(packets[0].cmd, Command::NandSelectCe);
+
+        // Clamped at the end of the block
+        let tail = geom.locate(10, 255).unwrap();
+        let reads = cache_read_sequence(&geom, &tail, 4)
+            .iter()
+            .filter(|p| p.cmd == Command::NandReadData)
+            .count();
+        assert_eq!(reads, 1);
+    }
+
+    #[test]
+    fn test_multi_plane_sequences() {
+        let geom = geometry();
+        let a = geom.locate(4, 0).unwrap();
+        let b = geom.locate(5, 0).unwrap();
+
+        let read = multi_plane_read_sequence(&geom, &[a, b]).unwrap();
+        let opcodes: Vec<u8> = read
+            .iter()
+            .filter(|p| p.cmd == Command::NandCmd)
+            .map(|p| p.args[0])
+            .collect();
+        assert_eq!(
+            opcodes,
+            vec![0x00, 0x32, 0x00, 0x30, 0x06, 0xE0, 0x06, 0xE0]
+        );
+
+        let erase = multi_plane_erase_sequence(&geom, &[a, b]).unwrap();
+        let opcodes: Vec<u8> = erase
+            .iter()
+            .filter(|p| p.cmd == Command::NandCmd)
+            .map(|p| p.args[0])
+            .collect();
+        assert_eq!(opcodes, vec![0x60, 0xD1, 0x60, 0xD0]);
+
+        // Same plane twice is rejected
+        let c = geom.locate(6, 0).unwrap();
+        assert!(multi_plane_read_sequence(&geom, &[a, c]).is_none());
+    }
+
+    #[test]
+    fn test_lun_status() {
+        let geom = geometry();
+        let packets = lun_status_sequence(&geom, 1, 1);
+        assert_eq!(packets[0].args[0], 1);
+        assert_eq!(packets[1].args[0], nand_commands::READ_STATUS_ENHANCED);
+        assert_eq!(packets[4].args[0], 0x08); // LUN bit in row cycle 3
+
+        let single = NandGeometry::new(2048, 64, 64, 1024);
+        let packets = lun_status_sequence(&single, 0, 0);
+        assert_eq!(packets[1].args[0], nand_commands::READSTATUS);
+
+        let status = LunStatus::new(0xE0);
+        assert!(status.is_ready() && status.is_array_ready());
+        assert!(!status.failed() && !status.write_protected());
+        assert!(LunStatus::new(0x41).failed());
+    }
+}
@@ -0,0 +1,876 @@
+//! NAND wear estimation from measured ECC bit-error rates
+//!
+//! Reads every page of a block through ECC and records the corrected bits
+//! of each ECC step, plus the read-retry level a page needed. The worst
+//! step of a block, relative to what the ECC can correct, is the best
+//! available indicator of how worn the block is.
+//!
+//! Remaining life uses a power-law model: the raw bit error rate grows as
+//! `(P/E cycles)^exponent` and reaches the correction limit at the rated
+//! endurance, so a block whose worst step needs `ratio` of the correction
+//! limit has used `ratio^(1/exponent)` of its life. A page that only
+//! decoded after a read retry has shifted thresholds and is treated as at
+//! least `retry_wear` used. `HostEcc` with a [`ReadRetryEngine`] retries
+//! pages with an uncorrectable step at the chip's retry levels.
+
+use crate::ecc::{decode_sectors, encode_with_ecc, EccAlgorithm};
+use crate::read_retry::{ReadRetryEngine, RetryStep};
+use crate::write_ops::{ProgramTarget, WriteError, WriteResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+// ============================================================================
+// Page Reads
+// ============================================================================
+
+/// Outcome of one ECC step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EccStepResult {
+    /// Decoded, with this many bits corrected
+    Corrected(u32),
+    /// More errors than the ECC can correct
+    Uncorrectable,
+}
+
+/// ECC outcome of one page read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageEccRead {
+    /// Per-step results, in page order
+    pub steps: Vec<EccStepResult>,
+    /// Read-retry level the page decoded at (0 = default thresholds)
+    pub retry_level: u8,
+    /// Page is erased; steps count the 0 bits in each step
+    pub erased: bool,
+}
+
+/// Source of per-step ECC results
+///
+/// Implemented by `HostEcc` for raw reads decoded on the host. Programmers
+/// with on-die ECC implement it directly.
+pub trait EccPageSource {
+    /// Read `page` of `block` and report its ECC outcome
+    fn read_page_ecc(&mut self, block: u32, page: u32) -> WriteResult<PageEccRead>;
+}
+
+/// Host-side ECC decode over raw page reads
+pub struct HostEcc<'a, T: ProgramTarget> {
+    target: &'a mut T,
+    page_size: u32,
+    oob_size: u32,
+    algorithm: EccAlgorithm,
+    ecc_offset: u32,
+    retry: Option<ReadRetryEngine>,
+}
+
+impl<'a, T: ProgramTarget> HostEcc<'a, T> {
+    /// Decode with `algorithm`, ECC bytes at `ecc_offset` in the OOB
+    pub fn new(
+        target: &'a mut T,
+        page_size: u32,
+        oob_size: u32,
+        algorithm: EccAlgorithm,
+        ecc_offset: u32,
+    ) -> Self {
+        Self {
+            target,
+            page_size,
+            oob_size,
+            algorithm,
+            ecc_offset,
+            retry: None,
+        }
+    }
+
+    /// Re-read pages with an uncorrectable step through `engine`, which
+    /// must use the same ECC algorithm; the target has to accept raw bus
+    /// sequences ([`ProgramTarget::send_packets`])
+    pub fn with_read_retry(mut self, engine: ReadRetryEngine) -> Self {
+        self.retry = Some(engine);
+        self
+    }
+
+    /// Read-retry engine, with its statistics and per-block levels
+    pub fn read_retry(&self) -> Option<&ReadRetryEngine> {
+        self.retry.as_ref()
+    }
+
+    fn read_raw(&mut self, block: u32, page: u32) -> WriteResult<(Vec<u8>, Vec<u8>)> {
+        let mut data = vec![0u8; self.page_size as usize];
+        let mut oob = vec![0u8; self.oob_size as usize];
+        self.target.read_page(block, page, &mut data, &mut oob)?;
+        Ok((data, oob))
+    }
+
+    fn ecc_bytes<'o>(&self, data: &[u8], oob: &'o [u8]) -> &'o [u8] {
+        let (_, blank_ecc) = encode_with_ecc(data, &self.algorithm);
+        let start = (self.ecc_offset as usize).min(oob.len());
+        let end = (start + blank_ecc.len()).min(oob.len());
+        &oob[start..end]
+    }
+
+    /// Re-read `page` at the engine's retry levels; returns the read at the
+    /// level that decoded, if any. Default thresholds are restored whatever
+    /// happens.
+    fn retry_page(&mut self, block: u32, page: u32) -> WriteResult<Option<PageEccRead>> {
+        let Some(mut engine) = self.retry.take() else {
+            return Ok(None);
+        };
+        let index = block * engine.pages_per_block() + page;
+        let mut packets = engine.begin(index);
+        let result = loop {
+            if let Err(e) = self.target.send_packets(&packets) {
+                break Err(e);
+            }
+            let (data, oob) = match self.read_raw(block, page) {
+                Ok(raw) => raw,
+                Err(e) => break Err(e),
+            };
+            match engine.submit(&data, self.ecc_bytes(&data, &oob)) {
+                RetryStep::Recovered { level, .. } => {
+                    break Ok(Some(PageEccRead {
+                        steps: self.decode(data, &oob),
+                        retry_level: level,
+                        erased: false,
+                    }))
+                }
+                RetryStep::Retry { packets: next, .. } => packets = next,
+                RetryStep::Exhausted { .. } => break Ok(None),
+            }
+        };
+        let restored = self.target.send_packets(&engine.finish());
+        self.retry = Some(engine);
+        let recovered = result?;
+        restored?;
+        Ok(recovered)
+    }
+
+    fn decode(&self, mut data: Vec<u8>, oob: &[u8]) -> Vec<EccStepResult> {
+        let ecc = self.ecc_bytes(&data, oob).to_vec();
+        decode_sectors(&mut data, &ecc, &self.algorithm)
+            .into_iter()
+            .map(|r| match r {
+                Ok(bits) => EccStepResult::Corrected(bits),
+                Err(_) => EccStepResult::Uncorrectable,
+            })
+            .collect()
+    }
+}
+
+impl<T: ProgramTarget> EccPageSource for HostEcc<'_, T> {
+    fn read_page_ecc(&mut self, block: u32, page: u32) -> WriteResult<PageEccRead> {
+        let (data, oob) = self.read_raw(block, page)?;
+        let ecc = self.ecc_bytes(&data, &oob);
+
+        // ECC of an erased page is all 0xFF, so count its bit flips
+        // directly; like the kernel, more flips than the ECC strength in a
+        // step make it uncorrectable
+        if !ecc.is_empty() && ecc.iter().all(|&b| b == 0xFF) {
+            let limit = self.algorithm.correction_capability();
+            let steps = data
+                .chunks(512)
+                .map(|step| match step.iter().map(|b| b.count_zeros()).sum() {
+                    bits if bits > limit => EccStepResult::Uncorrectable,
+                    bits => EccStepResult::Corrected(bits),
+                })
+                .collect();
+            return Ok(PageEccRead {
+                steps,
+                retry_level: 0,
+                erased: true,
+            });
+        }
+
+        let steps = self.decode(data, &oob);
+        if steps.contains(&EccStepResult::Uncorrectable) {
+            if let Some(read) = self.retry_page(block, page)? {
+                return Ok(read);
+            }
+        }
+        Ok(PageEccRead {
+            steps,
+            retry_level: 0,
+            erased: false,
+        })
+    }
+}
+
+// ============================================================================
+// Wear Model
+// ============================================================================
+
+/// Parameters for turning bit errors into remaining life
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WearModel {
+    /// Rated program/erase endurance
+    pub rated_pe_cycles: u32,
+    /// Power-law exponent of RBER growth with P/E cycles
+    pub exponent: f64,
+    /// Fraction of the correction limit at which a block counts as worn
+    pub warn_ratio: f64,
+    /// Fraction of the correction limit at which a block is flagged
+    pub critical_ratio: f64,
+    /// Minimum wear assumed for a block that needed read retry
+    pub retry_wear: f64,
+}
+
+impl WearModel {
+    /// SLC, 100k cycles
+    pub fn slc() -> Self {
+        Self {
+            rated_pe_cycles: 100_000,
+            ..Self::mlc()
+        }
+    }
+
+    /// MLC, 3k cycles
+    pub fn mlc() -> Self {
+        Self {
+            rated_pe_cycles: 3_000,
+            exponent: 2.0,
+            warn_ratio: 0.5,
+            critical_ratio: 0.75,
+            retry_wear: 0.8,
+        }
+    }
+
+    /// TLC, 1k cycles
+    pub fn tlc() -> Self {
+        Self {
+            rated_pe_cycles: 1_000,
+            ..Self::mlc()
+        }
+    }
+}
+
+impl Default for WearModel {
+    fn default() -> Self {
+        Self::mlc()
+    }
+}
+
+// ============================================================================
+// Block Health
+// ============================================================================
+
+/// Health classification of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockHealthStatus {
+    /// Well within the correction limit
+    Good,
+    /// Past `warn_ratio`, or needed read retry
+    Worn,
+    /// Past `critical_ratio`: approaching the correction limit
+    Critical,
+    /// At least one uncorrectable step
+    Failed,
+    /// Nothing was read
+    Unmeasured,
+}
+
+impl BlockHealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockHealthStatus::Good => "good",
+            BlockHealthStatus::Worn => "worn",
+            BlockHealthStatus::Critical => "critical",
+            BlockHealthStatus::Failed => "failed",
+            BlockHealthStatus::Unmeasured => "unmeasured",
+        }
+    }
+}
+
+/// Measured and estimated health of one block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHealth {
+    pub block: u32,
+    pub pages_read: u32,
+    pub erased_pages: u32,
+    pub steps_read: u32,
+    pub corrected_bits: u64,
+    /// Corrected bits of the worst step
+    pub max_step_bits: u32,
+    pub uncorrectable_steps: u32,
+    pub max_retry_level: u8,
+    /// Estimated fraction of life used (0.0 - 1.0)
+    pub wear: f64,
+    pub estimated_pe_used: u32,
+    pub remaining_pe: u32,
+    pub status: BlockHealthStatus,
+}
+
+impl BlockHealth {
+    fn new(block: u32) -> Self {
+        Self {
+            block,
+            pages_read: 0,
+            erased_pages: 0,
+            steps_read: 0,
+            corrected_bits: 0,
+            max_step_bits: 0,
+            uncorrectable_steps: 0,
+            max_retry_level: 0,
+            wear: 0.0,
+            estimated_pe_used: 0,
+            remaining_pe: 0,
+            status: BlockHealthStatus::Unmeasured,
+        }
+    }
+
+    /// Average corrected bits per ECC step
+    pub fn mean_step_bits(&self) -> f64 {
+        if self.steps_read == 0 {
+            return 0.0;
+        }
+        self.corrected_bits as f64 / self.steps_read as f64
+    }
+
+    fn evaluate(&mut self, ecc_limit: u32, model: &WearModel) {
+        if self.steps_read == 0 && self.uncorrectable_steps == 0 {
+            self.status = BlockHealthStatus::Unmeasured;
+            self.wear = 0.0;
+            self.estimated_pe_used = 0;
+            self.remaining_pe = model.rated_pe_cycles;
+            return;
+        }
+
+        let ratio = match (ecc_limit, self.max_step_bits) {
+            (_, 0) => 0.0,
+            (0, _) => 1.0,
+            (limit, bits) => (bits as f64 / limit as f64).min(1.0),
+        };
+        let mut wear = ratio.powf(1.0 / model.exponent.max(f64::EPSILON));
+        if self.max_retry_level > 0 {
+            wear = wear.max(model.retry_wear);
+        }
+        if self.uncorrectable_steps > 0 {
+            wear = 1.0;
+        }
+        self.wear = wear.clamp(0.0, 1.0);
+        self.estimated_pe_used = (self.wear * model.rated_pe_cycles as f64).round() as u32;
+        self.remaining_pe = model.rated_pe_cycles - self.estimated_pe_used;
+
+        self.status = if self.uncorrectable_steps > 0 {
+            BlockHealthStatus::Failed
+        } else if ratio >= model.critical_ratio {
+            BlockHealthStatus::Critical
+        } else if ratio >= model.warn_ratio || self.max_retry_level > 0 {
+            BlockHealthStatus::Worn
+        } else {
+            BlockHealthStatus::Good
+        };
+    }
+}
+
+// ============================================================================
+// Scanner
+// ============================================================================
+
+/// Accumulates page reads into per-block health
+#[derive(Debug, Clone)]
+pub struct HealthScanner {
+    ecc_limit: u32,
+    model: WearModel,
+    blocks: BTreeMap<u32, BlockHealth>,
+}
+
+impl HealthScanner {
+    /// Scanner for an ECC correcting `ecc_limit` bits per step
+    pub fn new(ecc_limit: u32, model: WearModel) -> Self {
+        Self {
+            ecc_limit,
+            model,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Scanner using the correction limit of `algorithm`
+    pub fn for_algorithm(algorithm: &EccAlgorithm, model: WearModel) -> Self {
+        Self::new(algorithm.correction_capability(), model)
+    }
+
+    /// Record the ECC outcome of one page
+    pub fn record_page(&mut self, block: u32, read: &PageEccRead) {
+        let health = self
+            .blocks
+            .entry(block)
+            .or_insert_with(|| BlockHealth::new(block));
+        health.pages_read += 1;
+        if read.erased {
+            health.erased_pages += 1;
+        }
+        health.max_retry_level = health.max_retry_level.max(read.retry_level);
+        for step in &read.steps {
+            match *step {
+                EccStepResult::Corrected(bits) => {
+                    health.steps_read += 1;
+                    health.corrected_bits += bits as u64;
+                    health.max_step_bits = health.max_step_bits.max(bits);
+                }
+                EccStepResult::Uncorrectable => health.uncorrectable_steps += 1,
+            }
+        }
+    }
+
+    /// Record a page that could not be read at any retry level
+    pub fn record_unreadable(&mut self, block: u32) {
+        let health = self
+            .blocks
+            .entry(block)
+            .or_insert_with(|| BlockHealth::new(block));
+        health.pages_read += 1;
+        health.uncorrectable_steps += 1;
+    }
+
+    /// Read every page of `blocks` from `source`
+    ///
+    /// Read failures other than I/O errors are recorded as unreadable
+    /// pages. `progress(block, done, total)` is called after each block;
+    /// returning `false` cancels the scan.
+    pub fn scan<S, F>(
+        &mut self,
+        source: &mut S,
+        blocks: Range<u32>,
+        pages_per_block: u32,
+        mut progress: F,
+    ) -> WriteResult<()>
+    where
+        S: EccPageSource,
+        F: FnMut(u32, u32, u32) -> bool,
+    {
+        let total = blocks.end.saturating_sub(blocks.start);
+        for (done, block) in blocks.enumerate() {
+            self.blocks
+                .entry(block)
+                .or_insert_with(|| BlockHealth::new(block));
+            for page in 0..pages_per_block {
+                match source.read_page_ecc(block, page) {
+                    Ok(read) => self.record_page(block, &read),
+                    Err(e @ WriteError::IoError(_)) => return Err(e),
+                    Err(_) => self.record_unreadable(block),
+                }
+            }
+            if !progress(block, done as u32 + 1, total) {
+                return Err(WriteError::Cancelled);
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate every recorded block
+    pub fn report(&self) -> HealthReport {
+        let blocks = self
+            .blocks
+            .values()
+            .cloned()
+            .map(|mut health| {
+                health.evaluate(self.ecc_limit, &self.model);
+                health
+            })
+            .collect();
+        HealthReport {
+            ecc_limit: self.ecc_limit,
+            model: self.model,
+            blocks,
+        }
+    }
+}
+
+// ============================================================================
+// Report
+// ============================================================================
+
+/// Block counts and overall wear
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HealthSummary {
+    pub good: u32,
+    pub worn: u32,
+    pub critical: u32,
+    pub failed: u32,
+    pub unmeasured: u32,
+    /// Mean wear of measured blocks
+    pub mean_wear: f64,
+    /// Lowest remaining P/E estimate of a measured, non-failed block
+    pub min_remaining_pe: Option<u32>,
+}
+
+/// Per-block health table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub ecc_limit: u32,
+    pub model: WearModel,
+    /// Blocks in ascending order
+    pub blocks: Vec<BlockHealth>,
+}
+
+impl HealthReport {
+    /// Health of one block
+    pub fn block(&self, block: u32) -> Option<&BlockHealth> {
+        self.blocks
+            .binary_search_by_key(&block, |b| b.block)
+            .ok()
+            .map(|i| &self.blocks[i])
+    }
+
+    /// Blocks that are critical or failed
+    pub fn flagged(&self) -> Vec<&BlockHealth> {
+        self.blocks
+            .iter()
+            .filter(|b| {
+                matches!(
+                    b.status,
+                    BlockHealthStatus::Critical | BlockHealthStatus::Failed
+                )
+            })
+            .collect()
+    }
+
+    /// Counts per status and overall wear
+    pub fn summary(&self) -> HealthSummary {
+        let mut summary = HealthSummary::default();
+        let mut wear_sum = 0.0;
+        let mut measured = 0u32;
+        for b in &self.blocks {
+            match b.status {
+                BlockHealthStatus::Good => summary.good += 1,
+                BlockHealthStatus::Worn => summary.worn += 1,
+                BlockHealthStatus::Critical => summary.critical += 1,
+                BlockHealthStatus::Failed => summary.failed += 1,
+                BlockHealthStatus::Unmeasured => summary.unmeasured += 1,
+            }
+            if b.status != BlockHealthStatus::Unmeasured {
+                wear_sum += b.wear;
+                measured += 1;
+            }
+            if !matches!(
+                b.status,
+                BlockHealthStatus::Unmeasured | BlockHealthStatus::Failed
+            ) {
+                summary.min_remaining_pe = Some(
+                    summary
+                        .min_remaining_pe
+                        .map_or(b.remaining_pe, |m| m.min(b.remaining_pe)),
+                );
+            }
+        }
+        if measured > 0 {
+            summary.mean_wear = wear_sum / measured as f64;
+        }
+        summary
+    }
+
+    /// Heatmap-ready CSV, one row per block
+    ///
+    /// `row`/`col` place block `n` at `n / columns`, `n % columns`.
+    pub fn to_csv(&self, columns: u32) -> String {
+        let columns = columns.max(1);
+        let mut csv = String::from(
+            "block,row,col,pages_read,erased_pages,max_step_bits,mean_step_bits,\
+             uncorrectable_steps,max_retry_level,wear_percent,remaining_pe,status\n",
+        );
+        for b in &self.blocks {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{:.3},{},{},{:.1},{},{}",
+                b.block,
+                b.block / columns,
+                b.block % columns,
+                b.pages_read,
+                b.erased_pages,
+                b.max_step_bits,
+                b.mean_step_bits(),
+                b.uncorrectable_steps,
+                b.max_retry_level,
+                b.wear * 100.0,
+                b.remaining_pe,
+                b.status.as_str()
+            );
+        }
+        csv
+    }
+
+    /// Wear grid for `total_blocks` blocks, `columns` blocks per row
+    ///
+    /// Unmeasured blocks are `None`.
+    pub fn heatmap(&self, total_blocks: u32, columns: u32) -> Vec<Vec<Option<f64>>> {
+        let columns = columns.max(1) as usize;
+        let mut cells = vec![None; total_blocks as usize];
+        for b in &self.blocks {
+            if b.status != BlockHealthStatus::Unmeasured {
+                if let Some(cell) = cells.get_mut(b.block as usize) {
+                    *cell = Some(b.wear);
+                }
+            }
+        }
+        cells.chunks(columns).map(|row| row.to_vec()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Command, Packet};
+    use crate::read_retry::RetryTable;
+
+    fn page(steps: &[u32], retry_level: u8) -> PageEccRead {
+        PageEccRead {
+            steps: steps.iter().map(|&b| EccStepResult::Corrected(b)).collect(),
+            retry_level,
+            erased: false,
+        }
+    }
+
+    #[test]
+    fn test_wear_estimation_and_flags() {
+        let mut scanner =
+            HealthScanner::for_algorithm(&EccAlgorithm::Bch { t: 8 }, WearModel::mlc());
+        scanner.record_page(0, &page(&[0, 1, 0, 0], 0));
+        scanner.record_page(1, &page(&[2, 4, 1, 0], 0));
+        scanner.record_page(2, &page(&[6, 7, 3, 2], 0));
+        scanner.record_page(3, &page(&[1, 0, 0, 0], 2));
+        scanner.record_page(4, &page(&[1, 0], 0));
+        scanner.record_page(
+            4,
+            &PageEccRead {
+                steps: vec![EccStepResult::Corrected(3), EccStepResult::Uncorrectable],
+                retry_level: 0,
+                erased: false,
+            },
+        );
+        scanner.record_unreadable(5);
+        let report = scanner.report();
+
+        let b0 = report.block(0).unwrap();
+        assert_eq!(b0.status, BlockHealthStatus::Good);
+        // (1/8)^(1/2) of 3000 cycles
+        assert_eq!(b0.estimated_pe_used, 1061);
+        assert_eq!(b0.remaining_pe, 1939);
+        assert!((b0.mean_step_bits() - 0.25).abs() < 1e-9);
+
+        assert_eq!(report.block(1).unwrap().status, BlockHealthStatus::Worn);
+        assert_eq!(report.block(2).unwrap().status, BlockHealthStatus::Critical);
+        let b3 = report.block(3).unwrap();
+        assert_eq!(b3.status, BlockHealthStatus::Worn);
+        assert!((b3.wear - 0.8).abs() < 1e-9);
+        assert_eq!(report.block(4).unwrap().status, BlockHealthStatus::Failed);
+        assert_eq!(report.block(5).unwrap().remaining_pe, 0);
+
+        let flagged: Vec<u32> = report.flagged().iter().map(|b| b.block).collect();
+        assert_eq!(flagged, vec![2, 4, 5]);
+
+        let summary = report.summary();
+        assert_eq!(
+            (summary.good, summary.worn, summary.critical, summary.failed),
+            (1, 2, 1, 2)
+        );
+        assert_eq!(
+            summary.min_remaining_pe,
+            Some(report.block(2).unwrap().remaining_pe)
+        );
+
+        let csv = report.to_csv(4);
+        assert_eq!(csv.lines().count(), 7);
+        assert!(csv
+            .lines()
+            .nth(3)
+            .unwrap()
+            .starts_with("2,0,2,1,0,7,4.500,0,0,"));
+        assert!(csv.lines().nth(6).unwrap().ends_with(",100.0,0,failed"));
+
+        let grid = report.heatmap(8, 4);
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid[1][0], Some(1.0));
+        assert_eq!(grid[1][2], None);
+    }
+
+    struct RawNand {
+        pages: std::collections::HashMap<(u32, u32), (Vec<u8>, Vec<u8>)>,
+    }
+
+    impl ProgramTarget for RawNand {
+        fn erase_block(&mut self, _block: u32) -> WriteResult<()> {
+            Ok(())
+        }
+
+        fn program_page(
+            &mut self,
+            _block: u32,
+            _page: u32,
+            _data: &[u8],
+            _oob: &[u8],
+        ) -> WriteResult<()> {
+            Ok(())
+        }
+
+        fn read_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &mut [u8],
+            oob: &mut [u8],
+        ) -> WriteResult<()> {
+            if block == 3 {
+                return Err(WriteError::ProgramFailed { block, page });
+            }
+            match self.pages.get(&(block, page)) {
+                Some((d, o)) => {
+                    data.copy_from_slice(d);
+                    oob.copy_from_slice(o);
+                }
+                None => {
+                    data.fill(0xFF);
+                    oob.fill(0xFF);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_scan_with_host_ecc() {
+        let data = vec![0xA5u8; 1024];
+        let (_, ecc) = encode_with_ecc(&data, &EccAlgorithm::Hamming);
+        let mut oob = vec![0xFF; 32];
+        oob[2..2 + ecc.len()].copy_from_slice(&ecc);
+
+        let mut nand = RawNand {
+            pages: std::collections::HashMap::new(),
+        };
+        nand.pages.insert((0, 0), (data.clone(), oob.clone()));
+        // Erased page with one flipped bit in the second step
+        let mut erased = vec![0xFF; 1024];
+        erased[600] = 0xFE;
+        nand.pages.insert((1, 1), (erased, vec![0xFF; 32]));
+        // Erased page with a step beyond the single-bit limit
+        let mut worn = vec![0xFF; 1024];
+        worn[10] = 0xFC;
+        nand.pages.insert((2, 0), (worn, vec![0xFF; 32]));
+
+        let mut source = HostEcc::new(&mut nand, 1024, 32, EccAlgorithm::Hamming, 2);
+        let read = source.read_page_ecc(1, 1).unwrap();
+        assert!(read.erased);
+        assert_eq!(
+            read.steps,
+            vec![EccStepResult::Corrected(0), EccStepResult::Corrected(1)]
+        );
+
+        let mut scanner = HealthScanner::for_algorithm(&EccAlgorithm::Hamming, WearModel::slc());
+        let mut calls = 0;
+        scanner
+            .scan(&mut source, 0..4, 2, |_, done, total| {
+                calls += 1;
+                assert_eq!(total, 4);
+                done < 4
+            })
+            .unwrap_err();
+        assert_eq!(calls, 4);
+
+        let report = scanner.report();
+        let b0 = report.block(0).unwrap();
+        assert_eq!((b0.pages_read, b0.erased_pages, b0.steps_read), (2, 1, 4));
+        assert_eq!(b0.status, BlockHealthStatus::Good);
+        assert_eq!(report.block(1).unwrap().status, BlockHealthStatus::Critical);
+        assert_eq!(report.block(2).unwrap().status, BlockHealthStatus::Failed);
+        assert_eq!(report.block(3).unwrap().uncorrectable_steps, 2);
+    }
+
+    /// Page that only decodes once the chip reads at retry level 2 or up
+    struct ShiftedNand {
+        data: Vec<u8>,
+        oob: Vec<u8>,
+        level: u8,
+        switches: Vec<u8>,
+    }
+
+    impl ProgramTarget for ShiftedNand {
+        fn erase_block(&mut self, _block: u32) -> WriteResult<()> {
+            Ok(())
+        }
+
+        fn program_page(
+            &mut self,
+            _block: u32,
+            _page: u32,
+            _data: &[u8],
+            _oob: &[u8],
+        ) -> WriteResult<()> {
+            Ok(())
+        }
+
+        fn read_page(
+            &mut self,
+            _block: u32,
+            _page: u32,
+            data: &mut [u8],
+            oob: &mut [u8],
+        ) -> WriteResult<()> {
+            data.copy_from_slice(&self.data);
+            oob.copy_from_slice(&self.oob);
+            if self.level < 2 {
+                // Two flips in the first step
+                data[0] ^= 0x01;
+                data[100] ^= 0x01;
+            }
+            Ok(())
+        }
+
+        fn send_packets(&mut self, packets: &[Packet]) -> WriteResult<()> {
+            // Micron SET FEATURES: the level is the first data byte
+            for packet in packets.iter().filter(|p| p.cmd == Command::NandWriteData) {
+                self.level = packet.args[1];
+                self.switches.push(self.level);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_host_ecc_read_retry() {
+        let data = vec![0x3Cu8; 1024];
+        let (_, ecc) = encode_with_ecc(&data, &EccAlgorithm::Hamming);
+        let mut oob = vec![0xFF; 32];
+        oob[2..2 + ecc.len()].copy_from_slice(&ecc);
+        let mut nand = ShiftedNand {
+            data,
+            oob,
+            level: 0,
+            switches: Vec::new(),
+        };
+
+        // Without an engine the page stays uncorrectable
+        let mut source = HostEcc::new(&mut nand, 1024, 32, EccAlgorithm::Hamming, 2);
+        let read = source.read_page_ecc(0, 1).unwrap();
+        assert_eq!(read.steps[0], EccStepResult::Uncorrectable);
+        assert_eq!(read.retry_level, 0);
+
+        let engine = ReadRetryEngine::new(RetryTable::micron(4), EccAlgorithm::Hamming, 64);
+        let mut source = source.with_read_retry(engine);
+        let read = source.read_page_ecc(0, 1).unwrap();
+        assert_eq!(
+            read.steps,
+            vec![EccStepResult::Corrected(0), EccStepResult::Corrected(0)]
+        );
+        assert_eq!(read.retry_level, 2);
+        let engine = source.read_retry().unwrap();
+        assert_eq!(engine.block_level(0), Some(2));
+        assert_eq!(engine.stats().pages_recovered, 1);
+        assert_eq!(nand.switches, vec![1, 2, 0]);
+
+        // A target without raw bus access fails instead of guessing
+        let mut raw = RawNand {
+            pages: std::collections::HashMap::new(),
+        };
+        let mut broken = vec![0x3Cu8; 1024];
+        broken[0] ^= 0x01;
+        broken[100] ^= 0x01;
+        raw.pages.insert((0, 0), (broken, nand.oob.clone()));
+        let engine = ReadRetryEngine::new(RetryTable::micron(4), EccAlgorithm::Hamming, 64);
+        let mut source =
+            HostEcc::new(&mut raw, 1024, 32, EccAlgorithm::Hamming, 2).with_read_retry(engine);
+        assert!(matches!(
+            source.read_page_ecc(0, 0),
+            Err(WriteError::IoError(_))
+        ));
+    }
+}
@@ -324,6 +324,7 @@ pub mod manufacturers {
     pub const WINBOND: u8 = 0xEF;
     pub const GIGADEVICE: u8 = 0xC8;
     pub const ESMT: u8 = 0x92;
+    pub const SANDISK: u8 = 0x45;
 }
 
 /// Get manufacturer name from ID
@@ -339,6 +340,7 @@ pub fn get_manufacturer_name(id: u8) -> &'static str {
         manufacturers::WINBOND => "Winbond",
         manufacturers::GIGADEVICE => "GigaDevice",
         manufacturers::ESMT => "ESMT",
+        manufacturers::SANDISK => "SanDisk",
         _ => "Unknown",
     }
 }
@@ -1318,39 +1320,187 @@ fn get_chip_info_generic(mfr: u8, device: u8) -> Option<NandChipInfo> {
     })
 }
 
-/// Parse ONFI parameter page (256 bytes)
-pub fn parse_onfi_parameter_page(data: &[u8]) -> Option<NandChipInfo> {
-    if data.len() < 256 {
+// ============================================================================
+// Parameter Page Integrity (ONFI / JEDEC)
+// ============================================================================
+
+/// ONFI parameter page length in bytes
+pub const ONFI_PARAM_PAGE_SIZE: usize = 256;
+/// JEDEC JESD230 parameter page length in bytes
+pub const JEDEC_PARAM_PAGE_SIZE: usize = 512;
+
+/// CRC-16 seed defined by ONFI ("ON" in ASCII), also used by JESD230
+const PARAM_PAGE_CRC_SEED: u16 = 0x4F4E;
+
+/// CRC-16 used by ONFI and JEDEC parameter pages
+///
+/// Polynomial 0x8005, initial value 0x4F4E, MSB first, no final XOR.
+pub fn onfi_crc16(data: &[u8]) -> u16 {
+    let mut crc = PARAM_PAGE_CRC_SEED;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x8005;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Store the CRC of a parameter page in its last two bytes (little-endian)
+///
+/// Works for both 256-byte ONFI and 512-byte JEDEC pages.
+pub fn write_param_page_crc(page: &mut [u8]) {
+    if page.len() < 2 {
+        return;
+    }
+    let crc_offset = page.len() - 2;
+    let crc = onfi_crc16(&page[..crc_offset]);
+    page[crc_offset..].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Check the CRC stored in the last two bytes of a parameter page
+pub fn verify_param_page_crc(page: &[u8]) -> bool {
+    if page.len() < 2 {
+        return false;
+    }
+    let crc_offset = page.len() - 2;
+    let stored = u16::from_le_bytes([page[crc_offset], page[crc_offset + 1]]);
+    onfi_crc16(&page[..crc_offset]) == stored
+}
+
+/// Where a validated parameter page came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParamPageSource {
+    /// Redundant copy N (0 = first) had a valid CRC
+    Copy(u8),
+    /// No single copy was valid; bytewise majority vote produced a valid page
+    MajorityVote,
+}
+
+/// A parameter page that passed CRC verification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatedParamPage {
+    pub data: Vec<u8>,
+    pub source: ParamPageSource,
+}
+
+/// Pick a CRC-valid parameter page out of a raw read of redundant copies
+///
+/// Tries each copy in order, then falls back to a bytewise majority vote
+/// across all complete copies.
+///
+/// # Arguments
+/// * `raw` - Raw READ PARAMETER PAGE data (one or more back-to-back copies)
+/// * `page_size` - Size of one copy (256 for ONFI, 512 for JEDEC)
+/// * `signature` - Expected 4-byte signature
+///
+/// # Returns
+/// * `Some(ValidatedParamPage)` if a copy or the vote passed CRC
+/// * `None` if every candidate failed
+pub fn select_param_page(
+    raw: &[u8],
+    page_size: usize,
+    signature: &[u8; 4],
+) -> Option<ValidatedParamPage> {
+    if page_size < 6 || raw.len() < page_size {
         return None;
     }
 
-    // Check ONFI signature "ONFI"
-    if &data[0..4] != b"ONFI" {
+    let copies: Vec<&[u8]> = raw.chunks_exact(page_size).collect();
+
+    for (i, copy) in copies.iter().enumerate() {
+        if &copy[0..4] == signature && verify_param_page_crc(copy) {
+            return Some(ValidatedParamPage {
+                data: copy.to_vec(),
+                source: ParamPageSource::Copy(i.min(u8::MAX as usize) as u8),
+            });
+        }
+    }
+
+    if copies.len() < 3 {
         return None;
     }
 
-    // Parse manufacturer (bytes 32-43)
-    let manufacturer = String::from_utf8_lossy(&data[32..44]).trim().to_string();
+    let voted = majority_vote(&copies);
+    if &voted[0..4] == signature && verify_param_page_crc(&voted) {
+        return Some(ValidatedParamPage {
+            data: voted,
+            source: ParamPageSource::MajorityVote,
+        });
+    }
+
+    None
+}
+
+/// Select a valid ONFI parameter page from a raw read
+pub fn select_onfi_parameter_page(raw: &[u8]) -> Option<ValidatedParamPage> {
+    select_param_page(raw, ONFI_PARAM_PAGE_SIZE, b"ONFI")
+}
+
+/// Select a valid JEDEC parameter page from a raw read
+pub fn select_jedec_parameter_page(raw: &[u8]) -> Option<ValidatedParamPage> {
+    select_param_page(raw, JEDEC_PARAM_PAGE_SIZE, b"JESD")
+}
+
+/// Bytewise majority vote across equally sized copies
+fn majority_vote(copies: &[&[u8]]) -> Vec<u8> {
+    let len = copies[0].len();
+    let mut out = Vec::with_capacity(len);
+    let mut counts: Vec<(u8, usize)> = Vec::with_capacity(copies.len());
+
+    for offset in 0..len {
+        counts.clear();
+        for copy in copies {
+            let byte = copy[offset];
+            match counts.iter_mut().find(|(b, _)| *b == byte) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((byte, 1)),
+            }
+        }
+        // Ties go to the earliest copy
+        let mut best = counts[0];
+        for &entry in &counts[1..] {
+            if entry.1 > best.1 {
+                best = entry;
+            }
+        }
+        out.push(best.0);
+    }
+
+    out
+}
+
+/// Cell type from the bits-per-cell field (byte 102 in ONFI and JEDEC pages)
+fn cell_type_from_bits(bits_per_cell: u8) -> CellType {
+    match bits_per_cell {
+        2 => CellType::MLC,
+        3 => CellType::TLC,
+        4 => CellType::QLC,
+        _ => CellType::SLC,
+    }
+}
 
-    // Parse model (bytes 44-63)
-    let model = String::from_utf8_lossy(&data[44..64]).trim().to_string();
+/// Build chip info from the geometry block shared by ONFI and JEDEC pages
+fn chip_info_from_param_page(page: &[u8]) -> NandChipInfo {
+    let manufacturer = String::from_utf8_lossy(&page[32..44]).trim().to_string();
+    let model = String::from_utf8_lossy(&page[44..64]).trim().to_string();
 
-    // Parse geometry
-    let page_size = u32::from_le_bytes([data[80], data[81], data[82], data[83]]);
-    let oob_size = u16::from_le_bytes([data[84], data[85]]) as u32;
-    let pages_per_block = u32::from_le_bytes([data[92], data[93], data[94], data[95]]);
-    let blocks_per_lun = u32::from_le_bytes([data[96], data[97], data[98], data[99]]);
-    let luns = data[100];
+    let features = u16::from_le_bytes([page[6], page[7]]);
+    let page_size = u32::from_le_bytes([page[80], page[81], page[82], page[83]]);
+    let oob_size = u16::from_le_bytes([page[84], page[85]]) as u32;
+    let pages_per_block = u32::from_le_bytes([page[92], page[93], page[94], page[95]]);
+    let blocks_per_lun = u32::from_le_bytes([page[96], page[97], page[98], page[99]]);
+    let luns = page[100];
 
     let total_blocks = blocks_per_lun * luns as u32;
     let size_mb =
         (total_blocks as u64 * pages_per_block as u64 * page_size as u64 / 1024 / 1024) as u32;
 
-    // Parse timing (simplified)
-    let t_prog = u16::from_le_bytes([data[133], data[134]]);
-    let t_r = u16::from_le_bytes([data[139], data[140]]);
-
-    Some(NandChipInfo {
+    NandChipInfo {
         manufacturer,
         model,
         size_mb,
@@ -1358,15 +1508,178 @@ pub fn parse_onfi_parameter_page(data: &[u8]) -> Option<NandChipInfo> {
         block_size: pages_per_block,
         oob_size,
         voltage: "3.3V".into(),
-        timing: NandTiming {
-            tR: (t_r / 1000).min(255) as u8,
-            ..NandTiming::default()
-        },
-        bus_width: 8,
-        cell_type: CellType::SLC, // Would need to parse features byte
+        timing: NandTiming::default(),
+        bus_width: if features & 0x0001 != 0 { 16 } else { 8 },
+        cell_type: cell_type_from_bits(page[102]),
+    }
+}
+
+// ============================================================================
+// ONFI Extended Parameter Page
+// ============================================================================
+
+/// Extended parameter page section types
+pub mod ext_param_section {
+    /// Unused section slot
+    pub const UNUSED: u8 = 0;
+    /// Additional section type/length pairs (continues the chain)
+    pub const SECTION_TYPES: u8 = 1;
+    /// Extended ECC information
+    pub const ECC_INFO: u8 = 2;
+}
+
+/// One section of the extended parameter page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtendedParamSection {
+    pub section_type: u8,
+    pub data: Vec<u8>,
+}
+
+/// ONFI 2.1+ extended parameter page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnfiExtendedParamPage {
+    /// Sections in chain order, including type-1 continuation sections
+    pub sections: Vec<ExtendedParamSection>,
+    /// Decoded ECC section, if present
+    pub ecc_info: Option<ExtendedEccInfo>,
+    /// Which redundant copy passed CRC
+    pub copy: u8,
+}
+
+/// Parse the extended parameter page that follows the ONFI parameter pages
+///
+/// The main page gives the extended page length (bytes 12-13, in 16-byte
+/// units) and the number of parameter page copies (byte 14). The extended
+/// page copies follow immediately after those copies in the raw read.
+///
+/// # Arguments
+/// * `raw` - Raw READ PARAMETER PAGE data including the extended copies
+///
+/// # Returns
+/// * `Some(OnfiExtendedParamPage)` if a copy passed CRC and signature checks
+/// * `None` if the chip does not advertise one or every copy is corrupt
+pub fn parse_onfi_extended_parameter_page(raw: &[u8]) -> Option<OnfiExtendedParamPage> {
+    let main = select_onfi_parameter_page(raw)?.data;
+
+    // Features bit 7: extended parameter page supported
+    let features = u16::from_le_bytes([main[6], main[7]]);
+    if features & 0x0080 == 0 {
+        return None;
+    }
+
+    let ext_len = u16::from_le_bytes([main[12], main[13]]) as usize * 16;
+    let copies = match main[14] {
+        0 => 3,
+        n => n as usize,
+    };
+    if ext_len < 32 {
+        return None;
+    }
+
+    let start = copies * ONFI_PARAM_PAGE_SIZE;
+    if raw.len() < start + ext_len {
+        return None;
+    }
+
+    for (i, ext) in raw[start..].chunks_exact(ext_len).enumerate() {
+        // Extended page CRC covers everything after the CRC field itself
+        let stored = u16::from_le_bytes([ext[0], ext[1]]);
+        if &ext[2..6] != b"EPPS" || onfi_crc16(&ext[2..]) != stored {
+            continue;
+        }
+        if let Some(mut page) = parse_ext_param_sections(ext) {
+            page.copy = i.min(u8::MAX as usize) as u8;
+            return Some(page);
+        }
+    }
+
+    None
+}
+
+/// Walk the section chain of a verified extended parameter page
+fn parse_ext_param_sections(ext: &[u8]) -> Option<OnfiExtendedParamPage> {
+    // Section descriptors: 8 (type, length) pairs at bytes 16-31
+    let mut descriptors: Vec<(u8, usize)> = ext[16..32]
+        .chunks_exact(2)
+        .map(|d| (d[0], d[1] as usize * 16))
+        .collect();
+
+    let mut sections = Vec::new();
+    let mut ecc_info = None;
+    let mut cursor = 32;
+    let mut index = 0;
+
+    while index < descriptors.len() {
+        let (section_type, len) = descriptors[index];
+        index += 1;
+
+        if section_type == ext_param_section::UNUSED || len == 0 {
+            continue;
+        }
+        if cursor + len > ext.len() {
+            return None;
+        }
+
+        let data = ext[cursor..cursor + len].to_vec();
+        cursor += len;
+
+        match section_type {
+            ext_param_section::SECTION_TYPES => {
+                descriptors.extend(data.chunks_exact(2).map(|d| (d[0], d[1] as usize * 16)));
+            }
+            ext_param_section::ECC_INFO if ecc_info.is_none() && data.len() >= 2 => {
+                ecc_info = Some(ExtendedEccInfo {
+                    ecc_bits: data[0],
+                    codeword_size: 1u16.checked_shl(data[1] as u32).unwrap_or(0),
+                    max_correctable_bits: data[0],
+                });
+            }
+            _ => {}
+        }
+
+        sections.push(ExtendedParamSection { section_type, data });
+    }
+
+    Some(OnfiExtendedParamPage {
+        sections,
+        ecc_info,
+        copy: 0,
     })
 }
 
+// ============================================================================
+// JEDEC JESD230 Parameter Page
+// ============================================================================
+
+/// Parse a JEDEC JESD230 parameter page (toggle-mode Samsung/Toshiba parts)
+///
+/// # Arguments
+/// * `raw` - Raw READ PARAMETER PAGE data (address 0x40), one or more copies
+///
+/// # Returns
+/// * `Some(NandChipInfo)` if a copy or the majority vote passed CRC
+/// * `None` otherwise
+pub fn parse_jedec_parameter_page(raw: &[u8]) -> Option<NandChipInfo> {
+    let page = select_jedec_parameter_page(raw)?.data;
+    Some(chip_info_from_param_page(&page))
+}
+
+/// Parse ONFI parameter page
+///
+/// Accepts one or more back-to-back 256-byte copies. The CRC at bytes
+/// 254-255 is verified and redundant copies are used when the first is
+/// corrupt.
+pub fn parse_onfi_parameter_page(data: &[u8]) -> Option<NandChipInfo> {
+    let page = select_onfi_parameter_page(data)?.data;
+    let mut info = chip_info_from_param_page(&page);
+
+    // tR maximum page read time (bytes 139-140, microseconds)
+    let t_r = u16::from_le_bytes([page[139], page[140]]);
+    info.timing.tR = (t_r / 1000).min(255) as u8;
+
+    Some(info)
+}
+
 // ============================================================================
 // ONFI Version Detection
 // ============================================================================
@@ -1389,18 +1702,16 @@ mod revision_bits {
 
 /// Detect ONFI version from parameter page
 ///
+/// Accepts one or more raw copies; the first CRC-valid copy is used.
 /// The revision field is at bytes 4-5 of the parameter page.
 /// Each bit indicates support for a specific ONFI version.
 /// Returns the highest supported version.
 pub fn detect_onfi_version(param_page: &[u8]) -> OnfiVersion {
-    if param_page.len() < 6 {
-        return OnfiVersion::Unknown;
-    }
-
-    // Check ONFI signature
-    if &param_page[0..4] != b"ONFI" {
-        return OnfiVersion::Unknown;
-    }
+    // Only trust a copy that passes CRC
+    let param_page = match select_onfi_parameter_page(param_page) {
+        Some(page) => page.data,
+        None => return OnfiVersion::Unknown,
+    };
 
     // Parse revision field (bytes 4-5, little-endian)
     let revision = u16::from_le_bytes([param_page[4], param_page[5]]);
@@ -1632,6 +1943,7 @@ mod tests {
         param_page[4] = 0x00;
         param_page[5] = 0x10; // 0x1000 = bit 12
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Onfi50);
     }
 
@@ -1643,6 +1955,7 @@ mod tests {
         param_page[4] = 0x00;
         param_page[5] = 0x02; // 0x0200 = bit 9
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Onfi40);
     }
 
@@ -1654,6 +1967,7 @@ mod tests {
         param_page[4] = 0x40; // 0x0040 = bit 6
         param_page[5] = 0x00;
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Onfi30);
     }
 
@@ -1665,6 +1979,7 @@ mod tests {
         param_page[4] = 0x04; // 0x0004 = bit 2
         param_page[5] = 0x00;
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Onfi20);
     }
 
@@ -1676,6 +1991,7 @@ mod tests {
         param_page[4] = 0x02; // 0x0002 = bit 1
         param_page[5] = 0x00;
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Onfi10);
     }
 
@@ -1687,6 +2003,7 @@ mod tests {
         param_page[4] = 0x00;
         param_page[5] = 0x00;
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Unknown);
     }
 
@@ -1697,6 +2014,7 @@ mod tests {
         param_page[4] = 0x00;
         param_page[5] = 0x10;
 
+        write_param_page_crc(&mut param_page);
         assert_eq!(detect_onfi_version(&param_page), OnfiVersion::Unknown);
     }
 
@@ -2000,6 +2318,167 @@ mod tests {
         assert_eq!(chip_info.bus_width, 16);
         assert!(chip_info.supports_x16());
     }
+
+    // ========================================================================
+    // Parameter Page Integrity Tests
+    // ========================================================================
+
+    fn build_onfi_page() -> Vec<u8> {
+        let mut page = vec![0u8; ONFI_PARAM_PAGE_SIZE];
+        page[0..4].copy_from_slice(b"ONFI");
+        page[4] = 0x04; // ONFI 2.0
+        page[6] = 0x01; // x16 bus
+        page[32..44].copy_from_slice(b"MICRON      ");
+        page[44..64].copy_from_slice(b"MT29F64G08CBAAA     ");
+        page[80..84].copy_from_slice(&8192u32.to_le_bytes());
+        page[84..86].copy_from_slice(&448u16.to_le_bytes());
+        page[92..96].copy_from_slice(&256u32.to_le_bytes());
+        page[96..100].copy_from_slice(&4096u32.to_le_bytes());
+        page[100] = 1;
+        page[102] = 2; // MLC
+        write_param_page_crc(&mut page);
+        page
+    }
+
+    #[test]
+    fn test_param_page_crc_roundtrip() {
+        let mut page = build_onfi_page();
+        assert!(verify_param_page_crc(&page));
+        page[50] ^= 0x20;
+        assert!(!verify_param_page_crc(&page));
+    }
+
+    #[test]
+    fn test_onfi_parse_geometry() {
+        let info = parse_onfi_parameter_page(&build_onfi_page()).unwrap();
+        assert_eq!(info.manufacturer, "MICRON");
+        assert_eq!(info.model, "MT29F64G08CBAAA");
+        assert_eq!(info.page_size, 8192);
+        assert_eq!(info.oob_size, 448);
+        assert_eq!(info.block_size, 256);
+        assert_eq!(info.size_mb, 8192);
+        assert_eq!(info.bus_width, 16);
+        assert_eq!(info.cell_type, CellType::MLC);
+    }
+
+    #[test]
+    fn test_onfi_rejects_bad_crc() {
+        let mut page = build_onfi_page();
+        page[81] ^= 0x01;
+        assert!(parse_onfi_parameter_page(&page).is_none());
+        assert_eq!(detect_onfi_version(&page), OnfiVersion::Unknown);
+    }
+
+    #[test]
+    fn test_onfi_falls_back_to_redundant_copy() {
+        let good = build_onfi_page();
+        let mut corrupt = good.clone();
+        corrupt[81] ^= 0x01;
+
+        let mut raw = corrupt.clone();
+        raw.extend(&good);
+        raw.extend(&good);
+
+        let selected = select_onfi_parameter_page(&raw).unwrap();
+        assert_eq!(selected.source, ParamPageSource::Copy(1));
+        assert_eq!(parse_onfi_parameter_page(&raw).unwrap().page_size, 8192);
+        assert_eq!(detect_onfi_version(&raw), OnfiVersion::Onfi20);
+    }
+
+    #[test]
+    fn test_onfi_majority_vote() {
+        let good = build_onfi_page();
+        let mut raw = Vec::new();
+        for offset in [10, 90, 200] {
+            let mut copy = good.clone();
+            copy[offset] ^= 0xFF;
+            raw.extend(copy);
+        }
+
+        let selected = select_onfi_parameter_page(&raw).unwrap();
+        assert_eq!(selected.source, ParamPageSource::MajorityVote);
+        assert_eq!(selected.data, good);
+    }
+
+    #[test]
+    fn test_onfi_extended_parameter_page_chain() {
+        let mut main = build_onfi_page();
+        main[6] |= 0x80; // extended parameter page supported
+        main[12..14].copy_from_slice(&5u16.to_le_bytes()); // 80 bytes
+        main[14] = 3;
+        write_param_page_crc(&mut main);
+
+        let mut ext = vec![0u8; 80];
+        ext[2..6].copy_from_slice(b"EPPS");
+        ext[16] = ext_param_section::SECTION_TYPES;
+        ext[17] = 1;
+        ext[18] = ext_param_section::ECC_INFO;
+        ext[19] = 1;
+        // Continuation section: one more vendor section
+        ext[32] = 0x10;
+        ext[33] = 1;
+        // ECC section: 40 bits per 1KiB codeword
+        ext[48] = 40;
+        ext[49] = 10;
+        ext[64] = 0xAA;
+        let crc = onfi_crc16(&ext[2..]);
+        ext[0..2].copy_from_slice(&crc.to_le_bytes());
+
+        let mut corrupt_ext = ext.clone();
+        corrupt_ext[48] = 0;
+
+        let mut raw = Vec::new();
+        for _ in 0..3 {
+            raw.extend(&main);
+        }
+        raw.extend(&corrupt_ext);
+        raw.extend(&ext);
+
+        let page = parse_onfi_extended_parameter_page(&raw).unwrap();
+        assert_eq!(page.copy, 1);
+        let types: Vec<u8> = page.sections.iter().map(|s| s.section_type).collect();
+        assert_eq!(types, vec![1, 2, 0x10]);
+        assert_eq!(page.sections[2].data[0], 0xAA);
+
+        let ecc = page.ecc_info.unwrap();
+        assert_eq!(ecc.ecc_bits, 40);
+        assert_eq!(ecc.codeword_size, 1024);
+
+        // Not advertised in the features field
+        let plain = build_onfi_page();
+        assert!(parse_onfi_extended_parameter_page(&plain).is_none());
+    }
+
+    #[test]
+    fn test_jedec_parameter_page() {
+        let mut page = vec![0u8; JEDEC_PARAM_PAGE_SIZE];
+        page[0..4].copy_from_slice(b"JESD");
+        page[4] = 0x04;
+        page[32..44].copy_from_slice(b"TOSHIBA     ");
+        page[44..64].copy_from_slice(b"TH58TEG7DDKTA20     ");
+        page[80..84].copy_from_slice(&16384u32.to_le_bytes());
+        page[84..86].copy_from_slice(&1280u16.to_le_bytes());
+        page[92..96].copy_from_slice(&256u32.to_le_bytes());
+        page[96..100].copy_from_slice(&2092u32.to_le_bytes());
+        page[100] = 1;
+        page[102] = 3;
+        write_param_page_crc(&mut page);
+
+        let mut corrupt = page.clone();
+        corrupt[0] = b'X';
+        let mut raw = corrupt;
+        raw.extend(&page);
+
+        let info = parse_jedec_parameter_page(&raw).unwrap();
+        assert_eq!(info.manufacturer, "TOSHIBA");
+        assert_eq!(info.page_size, 16384);
+        assert_eq!(info.oob_size, 1280);
+        assert_eq!(info.cell_type, CellType::TLC);
+        assert_eq!(info.bus_width, 8);
+
+        // An ONFI page is not a JEDEC page
+        assert!(parse_jedec_parameter_page(&build_onfi_page()).is_none());
+    }
 }
 
 #[cfg(test)]
@@ -2042,6 +2521,7 @@ mod proptests {
             param_page[4] = (revision & 0xFF) as u8;
             param_page[5] = ((revision >> 8) & 0xFF) as u8;
 
+            write_param_page_crc(&mut param_page);
             let detected = detect_onfi_version(&param_page);
 
             // Determine expected version based on highest bit set
@@ -2089,6 +2569,7 @@ mod proptests {
             param_page[4] = (revision & 0xFF) as u8;
             param_page[5] = ((revision >> 8) & 0xFF) as u8;
 
+            write_param_page_crc(&mut param_page);
             let detected = detect_onfi_version(&param_page);
 
             // Should return highest version
@@ -2129,6 +2610,7 @@ mod proptests {
             param_page[4] = (revision & 0xFF) as u8;
             param_page[5] = ((revision >> 8) & 0xFF) as u8;
 
+            write_param_page_crc(&mut param_page);
             let detected = detect_onfi_version(&param_page);
 
             prop_assert_eq!(detected, OnfiVersion::Unknown,
@@ -2386,5 +2868,28 @@ mod proptests {
             prop_assert_eq!(bytes_le[1], bytes_be[0],
                 "Byte swap mismatch for 0x{:04X}", word);
         }
+        /// Property test: one corrupted byte per copy is repaired by the vote
+        #[test]
+        fn prop_param_page_majority_vote(
+            body in proptest::collection::vec(proptest::num::u8::ANY, 250),
+            offsets in proptest::collection::hash_set(0usize..254, 3),
+            flip in 1u8..=255,
+        ) {
+            let mut page = vec![0u8; 256];
+            page[0..4].copy_from_slice(b"ONFI");
+            page[4..254].copy_from_slice(&body);
+            write_param_page_crc(&mut page);
+
+            let mut raw = Vec::new();
+            for offset in &offsets {
+                let mut copy = page.clone();
+                copy[*offset] ^= flip;
+                raw.extend(copy);
+            }
+
+            let selected = select_onfi_parameter_page(&raw);
+            prop_assert!(selected.is_some());
+            prop_assert_eq!(selected.unwrap().data, page);
+        }
     }
 }
@@ -0,0 +1,1110 @@
+//! Distributed parallel dumps
+//!
+//! Spreads dumps over several devices of an
+//! [`OpenFlashServer`](crate::server::OpenFlashServer) pool:
+//! - [`ParallelDumper::run`] reads one image split into the chunks of a
+//!   [`ParallelDumpJob`]. The devices see the same chip (through a
+//!   multiplexed socket) or identical boards; each takes the next free
+//!   chunk, a failed chunk is retried on a device that has not tried it
+//!   yet, and every chunk is hashed (and read twice with `verify`). The
+//!   chunks are merged into one image described by a [`DumpManifest`].
+//! - [`ParallelDumper::dump_each`] reads different chips at the same time,
+//!   one per device, into per-device files.
+//!
+//! Devices are held `Busy` while a dump runs, so the job executor leaves
+//! them alone, and chunk progress is published as `ChunkUpdate` events.
+
+use crate::job_executor::{limits::CHUNK_SIZE, DeviceTransport, ExecError, TransportFactory};
+use crate::server::{
+    lock_server, ChunkStatus, DeviceStatus, ParallelDumpConfig, ParallelDumpJob, ParallelJobStatus,
+    ServerError, SharedServer,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Parallel dump limits and defaults
+pub mod limits {
+    /// Reads of one chunk before the dump fails
+    pub const MAX_CHUNK_ATTEMPTS: u32 = 3;
+    /// Merged image inside the output directory
+    pub const IMAGE_FILE: &str = "image.bin";
+    /// Manifest inside the output directory
+    pub const MANIFEST_FILE: &str = "manifest.json";
+    /// How long a device waits when no chunk is free for it (ms)
+    pub const IDLE_WAIT_MS: u64 = 10;
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Parallel dump errors
+#[derive(Debug)]
+pub enum ParallelDumpError {
+    /// No requested device is free to take part
+    NoDevices,
+    /// Dump configuration cannot be used
+    InvalidConfig(String),
+    /// A chunk could not be read within its attempts
+    ChunkFailed { index: usize, error: String },
+    /// A chunk file no longer matches the hash taken when it was read
+    ChecksumMismatch { file: String },
+    /// Output file problem
+    Io(std::io::Error),
+    /// Server rejected a state change
+    Server(ServerError),
+}
+
+impl std::fmt::Display for ParallelDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDevices => write!(f, "No device available for the dump"),
+            Self::InvalidConfig(s) => write!(f, "Invalid dump configuration: {}", s),
+            Self::ChunkFailed { index, error } => write!(f, "Chunk {} failed: {}", index, error),
+            Self::ChecksumMismatch { file } => write!(f, "Checksum mismatch in {}", file),
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            Self::Server(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParallelDumpError {}
+
+impl From<std::io::Error> for ParallelDumpError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ServerError> for ParallelDumpError {
+    fn from(e: ServerError) -> Self {
+        Self::Server(e)
+    }
+}
+
+pub type ParallelDumpResult<T> = Result<T, ParallelDumpError>;
+
+// ============================================================================
+// Results
+// ============================================================================
+
+/// One chunk of a merged image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestChunk {
+    pub index: usize,
+    pub start_address: u64,
+    pub length: u64,
+    /// Device whose read was kept
+    pub device_id: String,
+    /// SHA-256 of the chunk
+    pub checksum: String,
+    /// Reads it took
+    pub attempts: u32,
+}
+
+/// Description of a finished split dump, written as `manifest.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpManifest {
+    pub job_id: u64,
+    pub total_size: u64,
+    pub chunk_size: u64,
+    /// Merged image file name (None = chunk files were kept)
+    pub image: Option<String>,
+    /// SHA-256 of the whole image
+    pub checksum: String,
+    /// Devices that took part
+    pub devices: Vec<String>,
+    pub chunks: Vec<ManifestChunk>,
+    /// Unix epoch ms
+    pub started_at: u64,
+    /// Unix epoch ms
+    pub completed_at: u64,
+}
+
+/// Outcome for one device of [`ParallelDumper::dump_each`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceDump {
+    pub device_id: String,
+    /// File name inside the output directory
+    pub output_file: String,
+    pub bytes: u64,
+    /// SHA-256 of the dump
+    pub checksum: Option<String>,
+    pub error: Option<String>,
+}
+
+// ============================================================================
+// Dumper
+// ============================================================================
+
+/// Runs parallel dumps on the devices of a server pool
+pub struct ParallelDumper {
+    server: SharedServer,
+    factory: TransportFactory,
+    devices: Option<Vec<String>>,
+    max_attempts: u32,
+    include_oob: bool,
+}
+
+impl ParallelDumper {
+    /// Dump with devices of `server`, opening their transports with
+    /// `factory`
+    pub fn new(server: SharedServer, factory: TransportFactory) -> Self {
+        Self {
+            server,
+            factory,
+            devices: None,
+            max_attempts: limits::MAX_CHUNK_ATTEMPTS,
+            include_oob: false,
+        }
+    }
+
+    /// Use exactly these devices instead of the first `device_count`
+    /// available ones
+    pub fn with_devices<I, S>(mut self, devices: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.devices = Some(devices.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Reads of one chunk before the dump fails
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Dump the raw array including spare areas
+    pub fn with_oob(mut self, include_oob: bool) -> Self {
+        self.include_oob = include_oob;
+        self
+    }
+
+    /// Read the chunks of `job` on the reserved devices and merge them
+    ///
+    /// `job.status` tracks the dump and ends as `Completed` or `Failed`.
+    pub fn run(&self, job: &mut ParallelDumpJob) -> ParallelDumpResult<DumpManifest> {
+        job.started_at = Some(now_ms());
+        let outcome = self.run_split(job);
+        job.completed_at = Some(now_ms());
+        match &outcome {
+            Ok(manifest) => {
+                job.status = ParallelJobStatus::Completed {
+                    duration_ms: manifest.completed_at - manifest.started_at,
+                    output_file: match &manifest.image {
+                        Some(image) => output_path(&job.config, image),
+                        None => output_path(&job.config, limits::MANIFEST_FILE),
+                    }
+                    .display()
+                    .to_string(),
+                    checksum: manifest.checksum.clone(),
+                };
+            }
+            Err(e) => job.status = ParallelJobStatus::Failed(e.to_string()),
+        }
+        outcome
+    }
+
+    /// Dump the whole chip of every reserved device into
+    /// `<output_dir>/<device>.bin`
+    ///
+    /// A failing device does not stop the others; its [`DeviceDump`]
+    /// carries the error. The results are also written as `manifest.json`.
+    pub fn dump_each(&self, config: &ParallelDumpConfig) -> ParallelDumpResult<Vec<DeviceDump>> {
+        let reservation = self.reserve(config, None)?;
+        std::fs::create_dir_all(&config.output_dir)?;
+
+        let dumps: Vec<DeviceDump> = std::thread::scope(|scope| {
+            let workers: Vec<_> = reservation
+                .devices()
+                .into_iter()
+                .map(|device_id| {
+                    let reservation = &reservation;
+                    scope.spawn(move || self.dump_device(reservation, config, device_id))
+                })
+                .collect();
+            workers
+                .into_iter()
+                .filter_map(|worker| worker.join().ok())
+                .collect()
+        });
+
+        let manifest =
+            serde_json::to_vec_pretty(&dumps).map_err(|e| ParallelDumpError::Io(e.into()))?;
+        std::fs::write(output_path(config, limits::MANIFEST_FILE), manifest)?;
+        Ok(dumps)
+    }
+
+    fn dump_device(
+        &self,
+        reservation: &Reservation,
+        config: &ParallelDumpConfig,
+        device_id: String,
+    ) -> DeviceDump {
+        let output_file = format!("{}.bin", file_stem(&device_id));
+        let path = output_path(config, &output_file);
+        let mut dump = DeviceDump {
+            device_id: device_id.clone(),
+            output_file,
+            bytes: 0,
+            checksum: None,
+            error: None,
+        };
+        let outcome = self
+            .connect(reservation, &device_id)
+            .and_then(|mut transport| {
+                let capacity = transport.capacity(self.include_oob)?;
+                let checksum = read_range(
+                    transport.as_mut(),
+                    0,
+                    capacity,
+                    self.include_oob,
+                    config.verify,
+                    &path,
+                )?;
+                Ok((capacity, checksum))
+            });
+        match outcome {
+            Ok((bytes, checksum)) => {
+                reservation.record(&device_id, bytes);
+                dump.bytes = bytes;
+                dump.checksum = Some(checksum);
+            }
+            Err(e) => {
+                reservation.record_error(&device_id);
+                dump.error = Some(e.to_string());
+            }
+        }
+        dump
+    }
+
+    fn run_split(&self, job: &mut ParallelDumpJob) -> ParallelDumpResult<DumpManifest> {
+        if job.chunks.is_empty() {
+            return Err(ParallelDumpError::InvalidConfig(
+                "the job has no chunks".to_string(),
+            ));
+        }
+        let started_at = job.started_at.unwrap_or_else(now_ms);
+        let reservation = self.reserve(&job.config, Some(job.id))?;
+        std::fs::create_dir_all(&job.config.output_dir)?;
+        let devices = reservation.devices();
+
+        let chunk_count = job.chunks.len();
+        job.status = ParallelJobStatus::Running {
+            completed_chunks: 0,
+            total_chunks: chunk_count,
+            bytes_completed: 0,
+        };
+        let state = Mutex::new(Split {
+            job,
+            tried: vec![HashSet::new(); chunk_count],
+            attempts: vec![0; chunk_count],
+            live: devices.iter().cloned().collect(),
+            max_attempts: self.max_attempts,
+            failure: None,
+            last_error: None,
+        });
+        std::thread::scope(|scope| {
+            for device_id in &devices {
+                let (state, reservation) = (&state, &reservation);
+                scope.spawn(move || self.split_worker(state, reservation, device_id));
+            }
+        });
+
+        let split = state.into_inner().unwrap_or_else(|e| e.into_inner());
+        if let Some(failure) = split.failure {
+            return Err(failure);
+        }
+        let job = split.job;
+        if let Some(chunk) = job
+            .chunks
+            .iter()
+            .find(|c| c.status != ChunkStatus::Completed)
+        {
+            return Err(ParallelDumpError::ChunkFailed {
+                index: chunk.index,
+                error: split
+                    .last_error
+                    .unwrap_or_else(|| "no device left to read it".to_string()),
+            });
+        }
+
+        let attempts = split.attempts;
+        self.merge(job, devices, attempts, started_at)
+    }
+
+    /// Take chunks for `device_id` until none are left
+    fn split_worker(&self, state: &Mutex<Split>, reservation: &Reservation, device_id: &str) {
+        let mut transport: Option<Box<dyn DeviceTransport>> = None;
+        loop {
+            if transport.is_none() {
+                let connected = self.connect(reservation, device_id).and_then(|mut t| {
+                    let total = lock(state).job.total_size;
+                    let capacity = t.capacity(self.include_oob)?;
+                    if capacity < total {
+                        return Err(ExecError::OutOfRange {
+                            address: 0,
+                            length: total,
+                            capacity,
+                        });
+                    }
+                    Ok(t)
+                });
+                match connected {
+                    Ok(t) => transport = Some(t),
+                    Err(e) => {
+                        reservation.record_error(device_id);
+                        lock(state).leave(device_id, Some(e.to_string()));
+                        return;
+                    }
+                }
+            }
+
+            let claimed = {
+                let mut split = lock(state);
+                let claimed = split.claim(device_id);
+                if let Claim::Chunk(index) = claimed {
+                    let _ = lock_server(&self.server).update_chunk(split.job, index);
+                }
+                claimed
+            };
+            let index = match claimed {
+                Claim::Chunk(index) => index,
+                Claim::Wait => {
+                    std::thread::sleep(Duration::from_millis(limits::IDLE_WAIT_MS));
+                    continue;
+                }
+                Claim::Done => break,
+            };
+
+            let (start, length, path, verify) = {
+                let split = lock(state);
+                let chunk = &split.job.chunks[index];
+                let path = PathBuf::from(&chunk.output_file);
+                (
+                    chunk.start_address,
+                    chunk.length,
+                    path,
+                    split.job.config.verify,
+                )
+            };
+            let read = transport
+                .as_deref_mut()
+                .map_or(Err(ExecError::Stopped), |t| {
+                    read_range(t, start, length, self.include_oob, verify, &path)
+                });
+
+            let mut split = lock(state);
+            match read {
+                Ok(checksum) => {
+                    reservation.record(device_id, length);
+                    split.complete(index, checksum);
+                }
+                Err(e) => {
+                    reservation.record_error(device_id);
+                    if matches!(e, ExecError::Transport(_)) {
+                        // Reconnect before the next chunk
+                        transport = None;
+                    }
+                    split.fail(index, e.to_string());
+                }
+            }
+            let _ = lock_server(&self.server).update_chunk(split.job, index);
+        }
+        lock(state).leave(device_id, None);
+    }
+
+    /// Check the chunk files against their hashes and build the manifest,
+    /// merging them into one image when `merge_output` is set
+    fn merge(
+        &self,
+        job: &mut ParallelDumpJob,
+        devices: Vec<String>,
+        attempts: Vec<u32>,
+        started_at: u64,
+    ) -> ParallelDumpResult<DumpManifest> {
+        let merge = job.config.merge_output;
+        job.status = if merge {
+            ParallelJobStatus::Merging
+        } else {
+            ParallelJobStatus::Verifying
+        };
+        let image_path = output_path(&job.config, limits::IMAGE_FILE);
+        let staging = output_path(&job.config, &format!(".{}.partial", limits::IMAGE_FILE));
+        let mut image = if merge {
+            Some(std::fs::File::create(&staging)?)
+        } else {
+            None
+        };
+
+        let mut hasher = Sha256::new();
+        let mut chunks = Vec::with_capacity(job.chunks.len());
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let merged = (|| {
+            for chunk in &job.chunks {
+                let mut file = std::fs::File::open(&chunk.output_file)?;
+                let mut chunk_hasher = Sha256::new();
+                loop {
+                    let n = file.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    chunk_hasher.update(&buf[..n]);
+                    hasher.update(&buf[..n]);
+                    if let Some(image) = image.as_mut() {
+                        image.write_all(&buf[..n])?;
+                    }
+                }
+                let checksum = to_hex(&chunk_hasher.finalize());
+                if chunk.checksum.as_deref() != Some(checksum.as_str()) {
+                    return Err(ParallelDumpError::ChecksumMismatch {
+                        file: chunk.output_file.clone(),
+                    });
+                }
+                chunks.push(ManifestChunk {
+                    index: chunk.index,
+                    start_address: chunk.start_address,
+                    length: chunk.length,
+                    device_id: chunk.device_id.clone().unwrap_or_default(),
+                    checksum,
+                    attempts: attempts[chunk.index],
+                });
+            }
+            if let Some(image) = image.as_mut() {
+                image.sync_all()?;
+            }
+            Ok(())
+        })();
+        if let Err(e) = merged {
+            let _ = std::fs::remove_file(&staging);
+            return Err(e);
+        }
+        if merge {
+            std::fs::rename(&staging, &image_path)?;
+            // The image and the manifest hashes replace the chunk files
+            for chunk in &job.chunks {
+                let _ = std::fs::remove_file(&chunk.output_file);
+            }
+        }
+
+        let manifest = DumpManifest {
+            job_id: job.id,
+            total_size: job.total_size,
+            chunk_size: job.config.chunk_size,
+            image: merge.then(|| limits::IMAGE_FILE.to_string()),
+            checksum: to_hex(&hasher.finalize()),
+            devices,
+            chunks,
+            started_at,
+            completed_at: now_ms(),
+        };
+        let json =
+            serde_json::to_vec_pretty(&manifest).map_err(|e| ParallelDumpError::Io(e.into()))?;
+        std::fs::write(output_path(&job.config, limits::MANIFEST_FILE), json)?;
+        Ok(manifest)
+    }
+
+    /// Hold the requested devices busy for the dump
+    fn reserve(
+        &self,
+        config: &ParallelDumpConfig,
+        job_id: Option<u64>,
+    ) -> ParallelDumpResult<Reservation<'_>> {
+        let mut server = lock_server(&self.server);
+        let devices = match &self.devices {
+            Some(devices) => {
+                for id in devices {
+                    let device = server
+                        .device_pool
+                        .get_device(id)
+                        .ok_or_else(|| ServerError::DeviceNotFound(id.clone()))?;
+                    if !device.is_available() {
+                        return Err(ServerError::DeviceBusy(id.clone()).into());
+                    }
+                }
+                devices.clone()
+            }
+            None => {
+                let mut available: Vec<String> = server
+                    .device_pool
+                    .devices
+                    .values()
+                    .filter(|d| d.is_available())
+                    .map(|d| d.id.clone())
+                    .collect();
+                available.sort();
+                available.truncate(config.device_count.max(1));
+                available
+            }
+        };
+        if devices.is_empty() {
+            return Err(ParallelDumpError::NoDevices);
+        }
+        for id in &devices {
+            server.set_device_status(id, DeviceStatus::Busy)?;
+            if let Some(device) = server.device_pool.get_device_mut(id) {
+                device.current_job = job_id;
+            }
+        }
+        Ok(Reservation {
+            server: &self.server,
+            tallies: Mutex::new(
+                devices
+                    .into_iter()
+                    .map(|id| (id, Tally::default()))
+                    .collect(),
+            ),
+        })
+    }
+
+    fn connect(
+        &self,
+        reservation: &Reservation,
+        device_id: &str,
+    ) -> Result<Box<dyn DeviceTransport>, ExecError> {
+        let device = lock_server(&self.server)
+            .device_pool
+            .get_device(device_id)
+            .cloned()
+            .ok_or_else(|| ServerError::DeviceNotFound(device_id.to_string()))?;
+        (self.factory)(&device).map_err(|e| {
+            // Like the job executor, keep the device out of further work
+            // until an operator resets it
+            reservation.disable(device_id);
+            ExecError::Connect(e.to_string())
+        })
+    }
+}
+
+impl std::fmt::Debug for ParallelDumper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParallelDumper")
+            .field("devices", &self.devices)
+            .field("max_attempts", &self.max_attempts)
+            .field("include_oob", &self.include_oob)
+            .finish_non_exhaustive()
+    }
+}
+
+// ============================================================================
+// Chunk Scheduling
+// ============================================================================
+
+/// Shared state of a split dump
+struct Split<'j> {
+    job: &'j mut ParallelDumpJob,
+    /// Devices that have read each chunk
+    tried: Vec<HashSet<String>>,
+    attempts: Vec<u32>,
+    /// Devices still taking chunks
+    live: HashSet<String>,
+    max_attempts: u32,
+    failure: Option<ParallelDumpError>,
+    last_error: Option<String>,
+}
+
+enum Claim {
+    Chunk(usize),
+    /// Chunks are left, but none for this device right now
+    Wait,
+    Done,
+}
+
+impl Split<'_> {
+    /// Next chunk for `device`: one it has not tried yet, otherwise one that
+    /// every remaining device has already tried
+    fn claim(&mut self, device: &str) -> Claim {
+        if self.failure.is_some() {
+            return Claim::Done;
+        }
+        let pending = |i: &usize| self.job.chunks[*i].status == ChunkStatus::Pending;
+        let indices = 0..self.job.chunks.len();
+        let fresh = indices
+            .clone()
+            .filter(pending)
+            .find(|&i| !self.tried[i].contains(device));
+        let retry = || {
+            indices
+                .clone()
+                .filter(pending)
+                .find(|&i| self.live.iter().all(|d| self.tried[i].contains(d)))
+        };
+        if let Some(index) = fresh.or_else(retry) {
+            self.tried[index].insert(device.to_string());
+            self.attempts[index] += 1;
+            let chunk = &mut self.job.chunks[index];
+            chunk.status = ChunkStatus::Running;
+            chunk.device_id = Some(device.to_string());
+            return Claim::Chunk(index);
+        }
+        let unfinished = self
+            .job
+            .chunks
+            .iter()
+            .any(|c| matches!(c.status, ChunkStatus::Pending | ChunkStatus::Running));
+        if unfinished {
+            Claim::Wait
+        } else {
+            Claim::Done
+        }
+    }
+
+    fn complete(&mut self, index: usize, checksum: String) {
+        let chunk = &mut self.job.chunks[index];
+        chunk.status = ChunkStatus::Completed;
+        chunk.checksum = Some(checksum);
+        self.job.status = ParallelJobStatus::Running {
+            completed_chunks: self
+                .job
+                .chunks
+                .iter()
+                .filter(|c| c.status == ChunkStatus::Completed)
+                .count(),
+            total_chunks: self.job.chunks.len(),
+            bytes_completed: self.job.bytes_completed(),
+        };
+    }
+
+    /// Put the chunk back for another device, or fail the dump once it is
+    /// out of attempts
+    fn fail(&mut self, index: usize, error: String) {
+        let chunk = &mut self.job.chunks[index];
+        if self.attempts[index] >= self.max_attempts {
+            chunk.status = ChunkStatus::Failed(error.clone());
+            self.failure = Some(ParallelDumpError::ChunkFailed { index, error });
+        } else {
+            chunk.status = ChunkStatus::Pending;
+            chunk.device_id = None;
+            self.last_error = Some(error);
+        }
+    }
+
+    fn leave(&mut self, device: &str, error: Option<String>) {
+        self.live.remove(device);
+        if error.is_some() {
+            self.last_error = error;
+        }
+    }
+}
+
+// ============================================================================
+// Device Reservation
+// ============================================================================
+
+#[derive(Debug, Default)]
+struct Tally {
+    bytes: u64,
+    errors: u32,
+}
+
+/// Devices held busy for one dump; released with their statistics when
+/// dropped
+struct Reservation<'a> {
+    server: &'a SharedServer,
+    tallies: Mutex<Vec<(String, Tally)>>,
+}
+
+impl Reservation<'_> {
+    fn devices(&self) -> Vec<String> {
+        lock(&self.tallies)
+            .iter()
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn update(&self, device: &str, f: impl FnOnce(&mut Tally)) {
+        if let Some((_, tally)) = lock(&self.tallies).iter_mut().find(|(id, _)| id == device) {
+            f(tally);
+        }
+    }
+
+    fn record(&self, device: &str, bytes: u64) {
+        self.update(device, |tally| tally.bytes += bytes);
+    }
+
+    fn record_error(&self, device: &str) {
+        self.update(device, |tally| tally.errors += 1);
+    }
+
+    fn disable(&self, device: &str) {
+        let _ = lock_server(self.server).set_device_status(device, DeviceStatus::Error);
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let tallies = std::mem::take(&mut *lock(&self.tallies));
+        let mut server = lock_server(self.server);
+        for (id, tally) in tallies {
+            let Some(device) = server.device_pool.get_device_mut(&id) else {
+                continue;
+            };
+            device.current_job = None;
+            device.bytes_processed += tally.bytes;
+            device.error_count += tally.errors;
+            if tally.bytes > 0 {
+                device.jobs_completed += 1;
+            }
+            // A device disabled during the dump stays in its error state
+            if device.status == DeviceStatus::Busy {
+                let _ = server.set_device_status(&id, DeviceStatus::Available);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+/// Read `length` bytes from `start` into `path` and return their SHA-256;
+/// with `verify` the range is read again and compared with the file
+fn read_range(
+    transport: &mut dyn DeviceTransport,
+    start: u64,
+    length: u64,
+    include_oob: bool,
+    verify: bool,
+    path: &Path,
+) -> Result<String, ExecError> {
+    let artifact = |e: std::io::Error| ExecError::Artifact(format!("{}: {}", path.display(), e));
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let staging = path.with_file_name(format!(".{}.partial", name));
+
+    let read = (|| {
+        let mut file = std::fs::File::create(&staging).map_err(artifact)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < length {
+            let n = (length - offset).min(buf.len() as u64) as usize;
+            transport.read(start + offset, &mut buf[..n], include_oob)?;
+            hasher.update(&buf[..n]);
+            file.write_all(&buf[..n]).map_err(artifact)?;
+            offset += n as u64;
+        }
+        file.sync_all().map_err(artifact)?;
+
+        if verify {
+            let mut file = std::fs::File::open(&staging).map_err(artifact)?;
+            let mut expected = vec![0u8; CHUNK_SIZE];
+            let mut offset = 0;
+            while offset < length {
+                let n = (length - offset).min(buf.len() as u64) as usize;
+                transport.read(start + offset, &mut buf[..n], include_oob)?;
+                file.read_exact(&mut expected[..n]).map_err(artifact)?;
+                if let Some(i) = (0..n).find(|&i| buf[i] != expected[i]) {
+                    return Err(ExecError::VerifyFailed {
+                        address: start + offset + i as u64,
+                    });
+                }
+                offset += n as u64;
+            }
+        }
+        Ok(to_hex(&hasher.finalize()))
+    })();
+    match read {
+        Ok(checksum) => {
+            std::fs::rename(&staging, path).map_err(artifact)?;
+            Ok(checksum)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&staging);
+            Err(e)
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn output_path(config: &ParallelDumpConfig, name: &str) -> PathBuf {
+    Path::new(&config.output_dir).join(name)
+}
+
+/// Device ID usable as a file name
+fn file_stem(device_id: &str) -> String {
+    device_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_string()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_executor::{ExecResult, MemoryTransport};
+    use crate::server::PoolDevice;
+    use crate::test_support::{chip_factory, scratch_dir, server_with_devices};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Transport whose reads fail `failures` times once `after` reads on
+    /// this connection went through
+    struct Flaky {
+        chip: MemoryTransport,
+        failures: Arc<Mutex<u32>>,
+        after: u32,
+        reads: u32,
+    }
+
+    impl DeviceTransport for Flaky {
+        fn capacity(&mut self, include_oob: bool) -> ExecResult<u64> {
+            self.chip.capacity(include_oob)
+        }
+
+        fn erase_size(&mut self) -> ExecResult<u64> {
+            self.chip.erase_size()
+        }
+
+        fn read(&mut self, address: u64, buf: &mut [u8], include_oob: bool) -> ExecResult<()> {
+            self.reads += 1;
+            let mut failures = lock(&self.failures);
+            if self.reads > self.after && *failures > 0 {
+                *failures -= 1;
+                return Err(ExecError::Transport("read glitch".to_string()));
+            }
+            self.chip.read(address, buf, include_oob)
+        }
+
+        fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+            self.chip.write(address, data)
+        }
+
+        fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+            self.chip.erase(address, length)
+        }
+    }
+
+    fn pattern_chip(size: usize) -> MemoryTransport {
+        let mut chip = MemoryTransport::new(size, 4096);
+        let data: Vec<u8> = (0..size as u32).map(|i| (i * 31 % 251) as u8).collect();
+        chip.write(0, &data).unwrap();
+        chip
+    }
+
+    fn config(root: &Path, chunk_size: u64) -> ParallelDumpConfig {
+        ParallelDumpConfig {
+            device_count: 4,
+            chunk_size,
+            output_dir: root.display().to_string(),
+            merge_output: true,
+            verify: true,
+        }
+    }
+
+    #[test]
+    fn test_split_dump_retries_on_another_device() {
+        // Slow enough that every device gets a chunk
+        let chip = pattern_chip(300 * 1024).with_latency(Duration::from_millis(5));
+        let server = server_with_devices(&["a", "b", "c"]);
+        let root = scratch_dir("openflash_pdump_split");
+        let failures: HashMap<&str, u32> = [("a", 0), ("b", 2), ("c", 0)].into();
+        let failures: HashMap<String, Arc<Mutex<u32>>> = failures
+            .into_iter()
+            .map(|(id, n)| (id.to_string(), Arc::new(Mutex::new(n))))
+            .collect();
+        let shared_chip = chip.clone();
+        let factory: TransportFactory = Arc::new(move |device: &PoolDevice| {
+            Ok(Box::new(Flaky {
+                chip: shared_chip.clone(),
+                failures: failures[&device.id].clone(),
+                after: 0,
+                reads: 0,
+            }) as Box<dyn DeviceTransport>)
+        });
+
+        let mut job = ParallelDumpJob::new(300 * 1024, config(&root, 64 * 1024));
+        let manifest = ParallelDumper::new(server.clone(), factory)
+            .run(&mut job)
+            .unwrap();
+
+        let image = std::fs::read(root.join(limits::IMAGE_FILE)).unwrap();
+        assert_eq!(image, chip.contents());
+        assert_eq!(manifest.checksum, to_hex(&Sha256::digest(&image)));
+        assert_eq!(manifest.chunks.len(), 5);
+        assert_eq!(manifest.chunks[4].length, 44 * 1024);
+        assert!(manifest.chunks.iter().any(|c| c.attempts > 1));
+        assert!(matches!(job.status, ParallelJobStatus::Completed { .. }));
+        assert!(!root.join("chunk_0000.bin").exists());
+        let saved: DumpManifest =
+            serde_json::from_slice(&std::fs::read(root.join(limits::MANIFEST_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(saved, manifest);
+
+        let server = lock_server(&server);
+        assert!(server
+            .device_pool
+            .devices
+            .values()
+            .all(|d| d.is_available()));
+        assert_eq!(
+            server
+                .device_pool
+                .devices
+                .values()
+                .map(|d| d.bytes_processed)
+                .sum::<u64>(),
+            300 * 1024
+        );
+        drop(server);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_split_dump_fails_after_attempts() {
+        let chip = pattern_chip(128 * 1024);
+        let server = server_with_devices(&["a", "b"]);
+        let root = scratch_dir("openflash_pdump_fail");
+        let failures = Arc::new(Mutex::new(u32::MAX));
+        let factory: TransportFactory = Arc::new(move |_: &PoolDevice| {
+            Ok(Box::new(Flaky {
+                chip: chip.clone(),
+                failures: failures.clone(),
+                after: 0,
+                reads: 0,
+            }) as Box<dyn DeviceTransport>)
+        });
+
+        let mut job = ParallelDumpJob::new(128 * 1024, config(&root, 64 * 1024));
+        let err = ParallelDumper::new(server.clone(), factory)
+            .with_max_attempts(2)
+            .run(&mut job)
+            .unwrap_err();
+        assert!(matches!(err, ParallelDumpError::ChunkFailed { .. }));
+        assert!(matches!(job.status, ParallelJobStatus::Failed(_)));
+        assert!(!root.join(limits::IMAGE_FILE).exists());
+        let server = lock_server(&server);
+        assert!(server
+            .device_pool
+            .devices
+            .values()
+            .all(|d| d.is_available()));
+        drop(server);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_split_dump_recovers_from_mid_chunk_errors() {
+        // 128K chunks take two reads and two more to verify: "a" fails in
+        // the middle of its first chunk, "b" while verifying it
+        let chip = pattern_chip(512 * 1024).with_latency(Duration::from_millis(1));
+        let server = server_with_devices(&["a", "b", "dead"]);
+        let root = scratch_dir("openflash_pdump_midchunk");
+        let plan: HashMap<String, (u32, Arc<Mutex<u32>>)> = [("a", 1), ("b", 2)]
+            .into_iter()
+            .map(|(id, after)| (id.to_string(), (after, Arc::new(Mutex::new(1)))))
+            .collect();
+        let shared_chip = chip.clone();
+        let factory: TransportFactory = Arc::new(move |device: &PoolDevice| {
+            let (after, failures) = plan
+                .get(&device.id)
+                .ok_or_else(|| ExecError::Connect(format!("no chip on {}", device.id)))?;
+            Ok(Box::new(Flaky {
+                chip: shared_chip.clone(),
+                failures: failures.clone(),
+                after: *after,
+                reads: 0,
+            }) as Box<dyn DeviceTransport>)
+        });
+
+        let mut job = ParallelDumpJob::new(512 * 1024, config(&root, 128 * 1024));
+        let manifest = ParallelDumper::new(server.clone(), factory)
+            .with_max_attempts(3)
+            .run(&mut job)
+            .unwrap();
+
+        let image = std::fs::read(root.join(limits::IMAGE_FILE)).unwrap();
+        assert_eq!(image, chip.contents());
+        assert_eq!(manifest.chunks.len(), 4);
+        assert!(manifest.chunks.iter().any(|c| c.attempts > 1));
+        // Failed reads leave no partial chunk files behind
+        let leftovers: Vec<_> = std::fs::read_dir(&root)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".partial"))
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+        let server = lock_server(&server);
+        let device = |id| server.device_pool.get_device(id).unwrap();
+        assert_eq!(device("a").error_count, 1);
+        assert_eq!(device("b").error_count, 1);
+        assert!(device("a").is_available() && device("b").is_available());
+        assert_eq!(device("dead").status, DeviceStatus::Error);
+        assert_eq!(
+            device("a").bytes_processed + device("b").bytes_processed,
+            512 * 1024
+        );
+        drop(server);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_dump_each_device() {
+        let chips = [
+            ("one", pattern_chip(64 * 1024)),
+            ("two", MemoryTransport::new(32 * 1024, 4096)),
+        ];
+        let server = server_with_devices(&["one", "two", "dead"]);
+        let root = scratch_dir("openflash_pdump_each");
+
+        let mut dumps = ParallelDumper::new(server.clone(), chip_factory(&chips))
+            .dump_each(&config(&root, 64 * 1024))
+            .unwrap();
+        dumps.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        assert_eq!(dumps.len(), 3);
+        assert!(dumps[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("no chip on dead"));
+        for dump in &dumps[1..] {
+            let data = std::fs::read(root.join(&dump.output_file)).unwrap();
+            let (_, chip) = chips.iter().find(|(id, _)| *id == dump.device_id).unwrap();
+            assert_eq!(data, chip.contents());
+            assert_eq!(dump.checksum, Some(to_hex(&Sha256::digest(&data))));
+        }
+        assert!(root.join(limits::MANIFEST_FILE).is_file());
+        let server = lock_server(&server);
+        assert_eq!(
+            server.device_pool.get_device("dead").unwrap().status,
+            DeviceStatus::Error
+        );
+        assert!(server.device_pool.get_device("one").unwrap().is_available());
+        drop(server);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,1280 @@
+//! Production line runner
+//!
+//! Executes the [`StationOperation`] list of a station of a
+//! [`ProductionLineConfig`] on one unit at a time, through the station's
+//! pool device:
+//! - every unit gets a serial number built from the station's first
+//!   `MarkSerial` format (see [`expand_template`]); `MarkSerial` with an
+//!   offset also writes it to the chip
+//! - the unit passes when every operation succeeded and the station's
+//!   [`PassCriteria`] hold (bad blocks, ECC corrections, verify match,
+//!   cycle time)
+//! - results are published as `ProductionResult` server events, appended
+//!   to the line log as JSON lines, CSV or text, and counted in the live
+//!   [`ProductionStats`]
+//!
+//! `CustomTest` runs the transport command `custom_test` with the script
+//! path and serial; a `passed=false` answer fails the unit.
+//!
+//! Erases leave bad blocks alone. On raw NAND chips `Program` runs through
+//! [`ChipProgrammer`](crate::write_ops::ChipProgrammer), which verifies
+//! each block as it programs it and shifts the image past bad blocks, and
+//! `MarkSerial` programs the serial into pages that are still erased.
+
+use crate::job_executor::{
+    erase_good_blocks, limits::CHUNK_SIZE, program_nand, DeviceTransport, ExecError, NandDevice,
+    TransportFactory,
+};
+use crate::metrics::LineMetrics;
+use crate::server::{
+    lock_server, DeviceStatus, PassCriteria, ProductionLineConfig, ProductionStats,
+    ProductionUnitResult, ServerError, SharedServer, StationConfig, StationOperation,
+    VerificationMode,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Production runner defaults
+pub mod limits {
+    /// Serial format of stations without a `MarkSerial` operation
+    pub const DEFAULT_SERIAL_FORMAT: &str = "{line}-{seq:06}";
+    /// Quick verification compares one transfer out of this many
+    pub const QUICK_VERIFY_STRIDE: u64 = 16;
+    /// Columns of CSV logs
+    pub const CSV_HEADER: &str = "timestamp,serial_number,line_id,station_id,passed,\
+                                  duration_ms,chip_info,bad_blocks,ecc_corrections,failure_reason";
+}
+
+/// Failure categories counted in `ProductionStats::failure_reasons`
+pub mod failure {
+    pub const CHIP_MISMATCH: &str = "chip_mismatch";
+    pub const ERASE_FAILED: &str = "erase_failed";
+    pub const PROGRAM_FAILED: &str = "program_failed";
+    pub const VERIFY_FAILED: &str = "verify_failed";
+    pub const DUMP_FAILED: &str = "dump_failed";
+    pub const CUSTOM_TEST: &str = "custom_test";
+    pub const SERIAL_FAILED: &str = "serial_failed";
+    pub const BAD_BLOCKS: &str = "bad_blocks";
+    pub const ECC_CORRECTIONS: &str = "ecc_corrections";
+    pub const MATCH_PERCENT: &str = "match_percent";
+    pub const TIMEOUT: &str = "timeout";
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// Station errors; a unit that fails its tests is a result, not an error
+#[derive(Debug)]
+pub enum StationError {
+    /// No station with this ID in the line
+    UnknownStation(String),
+    /// Station has no device assigned
+    NoDevice(String),
+    /// Invalid serial or path template, or log format
+    InvalidConfig(String),
+    /// The station device could not be opened
+    Device(ExecError),
+    /// Writing the production log failed
+    Log(std::io::Error),
+    /// Server rejected a state change
+    Server(ServerError),
+}
+
+impl std::fmt::Display for StationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownStation(id) => write!(f, "Unknown station: {}", id),
+            Self::NoDevice(id) => write!(f, "Station {} has no device assigned", id),
+            Self::InvalidConfig(s) => write!(f, "Invalid production config: {}", s),
+            Self::Device(e) => write!(f, "{}", e),
+            Self::Log(e) => write!(f, "Production log error: {}", e),
+            Self::Server(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StationError {}
+
+impl From<ServerError> for StationError {
+    fn from(e: ServerError) -> Self {
+        Self::Server(e)
+    }
+}
+
+pub type StationResult<T> = Result<T, StationError>;
+
+// ============================================================================
+// Templates
+// ============================================================================
+
+/// Values of the `{name}` / `{name:width}` placeholders in serial formats
+/// and dump paths
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateVars<'a> {
+    /// `{seq}`: unit sequence number
+    pub seq: u64,
+    /// `{line}`
+    pub line: &'a str,
+    /// `{station}`
+    pub station: &'a str,
+    /// `{serial}`: only set for dump paths
+    pub serial: Option<&'a str>,
+    /// `{date}` (YYYYMMDD) and `{time}` (HHMMSS), UTC, from this Unix
+    /// time in ms
+    pub now_ms: u64,
+}
+
+/// Fill in a serial format or path template
+///
+/// A width zero-pads the value, e.g. `{seq:06}` gives `000042`.
+pub fn expand_template(template: &str, vars: &TemplateVars) -> StationResult<String> {
+    let invalid = |why: &str| StationError::InvalidConfig(format!("{} in '{}'", why, template));
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| invalid("unclosed '{'"))?
+            + open;
+        let field = &rest[open + 1..close];
+        let (name, width) = match field.split_once(':') {
+            Some((name, width)) => (
+                name,
+                width
+                    .parse::<usize>()
+                    .map_err(|_| invalid("bad placeholder width"))?,
+            ),
+            None => (field, 0),
+        };
+        let (year, month, day, hour, minute, second) = utc(vars.now_ms);
+        let value = match name {
+            "seq" => vars.seq.to_string(),
+            "line" => vars.line.to_string(),
+            "station" => vars.station.to_string(),
+            "date" => format!("{:04}{:02}{:02}", year, month, day),
+            "time" => format!("{:02}{:02}{:02}", hour, minute, second),
+            "serial" if vars.serial.is_some() => vars.serial.unwrap_or_default().to_string(),
+            _ => return Err(invalid(&format!("unknown placeholder '{{{}}}'", name))),
+        };
+        let _ = write!(out, "{:0>width$}", value, width = width);
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// UTC calendar date and time of a Unix time in ms
+fn utc(ms: u64) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = ms / 1000;
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days to civil date (proleptic Gregorian, era-based)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (
+        year,
+        month,
+        day,
+        (rem / 3600) as u32,
+        (rem % 3600 / 60) as u32,
+        (rem % 60) as u32,
+    )
+}
+
+// ============================================================================
+// Runner
+// ============================================================================
+
+/// Runs units through the stations of one production line
+pub struct ProductionRunner {
+    line: ProductionLineConfig,
+    server: SharedServer,
+    factory: TransportFactory,
+    base_dir: PathBuf,
+    next_seq: u64,
+    metrics: LineMetrics,
+}
+
+impl ProductionRunner {
+    /// Runner for `line`, whose station devices are pool devices of
+    /// `server` opened with `factory`
+    pub fn new(
+        line: ProductionLineConfig,
+        server: SharedServer,
+        factory: TransportFactory,
+    ) -> StationResult<Self> {
+        if line.logging.enabled && !matches!(line.logging.format.as_str(), "json" | "csv" | "text")
+        {
+            return Err(StationError::InvalidConfig(format!(
+                "unknown log format '{}'",
+                line.logging.format
+            )));
+        }
+        // Reject bad templates before the first unit
+        for station in &line.stations {
+            let vars = TemplateVars {
+                seq: 1,
+                line: &line.line_id,
+                station: &station.id,
+                serial: None,
+                now_ms: 0,
+            };
+            expand_template(serial_format(station), &vars)?;
+            for op in &station.operations {
+                if let StationOperation::Dump { output_path } = op {
+                    let vars = TemplateVars {
+                        serial: Some(""),
+                        ..vars
+                    };
+                    expand_template(output_path, &vars)?;
+                }
+            }
+        }
+        let metrics = LineMetrics::new(&line.line_id);
+        Ok(Self {
+            line,
+            server,
+            factory,
+            base_dir: PathBuf::from("."),
+            next_seq: 1,
+            metrics,
+        })
+    }
+
+    /// Directory relative firmware, golden, dump and script paths are
+    /// resolved against (default: the working directory)
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = dir.into();
+        self
+    }
+
+    /// Sequence number of the next unit, e.g. to continue after a restart
+    pub fn with_next_seq(mut self, seq: u64) -> Self {
+        self.next_seq = seq;
+        self
+    }
+
+    /// The line being run
+    pub fn line(&self) -> &ProductionLineConfig {
+        &self.line
+    }
+
+    /// Statistics of the units run so far
+    pub fn stats(&self) -> &ProductionStats {
+        &self.metrics.stats
+    }
+
+    /// Run the next unit on `station_id`
+    ///
+    /// The station device is held busy for the unit. The result is
+    /// published, logged and counted before it is returned.
+    pub fn run_unit(&mut self, station_id: &str) -> StationResult<ProductionUnitResult> {
+        let station = self
+            .line
+            .stations
+            .iter()
+            .find(|s| s.id == station_id)
+            .cloned()
+            .ok_or_else(|| StationError::UnknownStation(station_id.to_string()))?;
+        let device_id = station
+            .device_id
+            .clone()
+            .ok_or_else(|| StationError::NoDevice(station.id.clone()))?;
+
+        let device = {
+            let mut server = lock_server(&self.server);
+            let device = server
+                .device_pool
+                .get_device(&device_id)
+                .cloned()
+                .ok_or_else(|| ServerError::DeviceNotFound(device_id.clone()))?;
+            if !device.is_available() {
+                return Err(ServerError::DeviceBusy(device_id).into());
+            }
+            server.set_device_status(&device_id, DeviceStatus::Busy)?;
+            device
+        };
+        let transport = match (self.factory)(&device) {
+            Ok(transport) => transport,
+            Err(e) => {
+                let _ =
+                    lock_server(&self.server).set_device_status(&device_id, DeviceStatus::Error);
+                return Err(StationError::Device(ExecError::Connect(e.to_string())));
+            }
+        };
+
+        let started_ms = now_ms();
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let serial = expand_template(
+            serial_format(&station),
+            &TemplateVars {
+                seq,
+                line: &self.line.line_id,
+                station: &station.id,
+                serial: None,
+                now_ms: started_ms,
+            },
+        )?;
+
+        let mut unit = Unit {
+            line: &self.line,
+            station: &station,
+            base_dir: &self.base_dir,
+            transport,
+            started: Instant::now(),
+            seq,
+            serial,
+            serial_range: None,
+            chip_info: None,
+            bad_blocks: BTreeSet::new(),
+            ecc_corrections: 0,
+            match_percent: None,
+            bytes: 0,
+            data: HashMap::new(),
+        };
+        let outcome = unit.run();
+        let mut result = unit.finish(outcome, started_ms);
+        result
+            .data
+            .insert("device_id".to_string(), device_id.clone());
+        let bytes = unit.bytes;
+        drop(unit);
+
+        {
+            let mut server = lock_server(&self.server);
+            if let Some(device) = server.device_pool.get_device_mut(&device_id) {
+                device.release(result.passed, bytes);
+            }
+            let _ = server.set_device_status(&device_id, DeviceStatus::Available);
+            server.record_production_result(result.clone());
+        }
+        self.metrics.record(&result);
+        self.log(&result).map_err(StationError::Log)?;
+        Ok(result)
+    }
+
+    /// Append the result to `<log_dir>/<line_id>.<jsonl|csv|log>`
+    fn log(&self, result: &ProductionUnitResult) -> std::io::Result<()> {
+        let logging = &self.line.logging;
+        if !logging.enabled {
+            return Ok(());
+        }
+        let extension = match logging.format.as_str() {
+            "json" => "jsonl",
+            "csv" => "csv",
+            _ => "log",
+        };
+        std::fs::create_dir_all(&logging.log_dir)?;
+        let path = Path::new(&logging.log_dir).join(format!("{}.{}", self.line.line_id, extension));
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        let line = match logging.format.as_str() {
+            "json" => serde_json::to_string(result)?,
+            "csv" => {
+                let mut line = String::new();
+                if file.metadata()?.len() == 0 {
+                    line.push_str(limits::CSV_HEADER);
+                    line.push('\n');
+                }
+                let fields = [
+                    result.timestamp.to_string(),
+                    result.serial_number.clone(),
+                    result.line_id.clone(),
+                    result.station_id.clone(),
+                    result.passed.to_string(),
+                    result.duration_ms.to_string(),
+                    result.chip_info.clone().unwrap_or_default(),
+                    result.bad_blocks.to_string(),
+                    result.ecc_corrections.to_string(),
+                    result.failure_reason.clone().unwrap_or_default(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                line.push_str(&fields.join(","));
+                line
+            }
+            _ => format!(
+                "{} {} {} {} {}ms{}",
+                result.timestamp,
+                result.station_id,
+                result.serial_number,
+                if result.passed { "PASS" } else { "FAIL" },
+                result.duration_ms,
+                result
+                    .failure_reason
+                    .as_ref()
+                    .map(|r| format!(" {}", r))
+                    .unwrap_or_default()
+            ),
+        };
+        writeln!(file, "{}", line)
+    }
+}
+
+impl std::fmt::Debug for ProductionRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProductionRunner")
+            .field("line_id", &self.line.line_id)
+            .field("base_dir", &self.base_dir)
+            .field("next_seq", &self.next_seq)
+            .finish_non_exhaustive()
+    }
+}
+
+fn serial_format(station: &StationConfig) -> &str {
+    station
+        .operations
+        .iter()
+        .find_map(|op| match op {
+            StationOperation::MarkSerial { format, .. } => Some(format.as_str()),
+            _ => None,
+        })
+        .unwrap_or(limits::DEFAULT_SERIAL_FORMAT)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// ============================================================================
+// Unit
+// ============================================================================
+
+/// Why a unit failed: the statistics category and a message
+#[derive(Debug)]
+struct Failure {
+    code: &'static str,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Map an error of a `code` operation
+    fn of(code: &'static str) -> impl Fn(ExecError) -> Failure {
+        move |e| Failure::new(code, e.to_string())
+    }
+}
+
+/// One unit going through a station
+struct Unit<'a> {
+    line: &'a ProductionLineConfig,
+    station: &'a StationConfig,
+    base_dir: &'a Path,
+    transport: Box<dyn DeviceTransport>,
+    started: Instant,
+    seq: u64,
+    serial: String,
+    /// Chip bytes holding the written serial; verification skips them
+    serial_range: Option<Range<u64>>,
+    chip_info: Option<String>,
+    bad_blocks: BTreeSet<u64>,
+    ecc_corrections: u32,
+    /// Lowest match of the `Verify` operations
+    match_percent: Option<f32>,
+    bytes: u64,
+    data: HashMap<String, String>,
+}
+
+impl Unit<'_> {
+    fn run(&mut self) -> Result<(), Failure> {
+        let criteria = &self.station.pass_criteria;
+        for (i, op) in self.station.operations.iter().enumerate() {
+            let op_started = Instant::now();
+            let (name, outcome) = match op {
+                StationOperation::DetectChip { expected } => ("detect", self.detect(expected)),
+                StationOperation::Erase { full } => ("erase", self.erase(*full)),
+                StationOperation::Program {
+                    firmware_path,
+                    verify,
+                } => ("program", self.program(firmware_path, *verify)),
+                StationOperation::Verify {
+                    golden_path,
+                    tolerance,
+                } => ("verify", self.verify(golden_path, *tolerance)),
+                StationOperation::Dump { output_path } => ("dump", self.dump(output_path)),
+                StationOperation::CustomTest { script_path } => {
+                    ("custom_test", self.custom_test(script_path))
+                }
+                StationOperation::MarkSerial { offset, .. } => ("serial", self.mark(*offset)),
+            };
+            if self.line.logging.detailed_timing {
+                self.data.insert(
+                    format!("op{}_{}_ms", i + 1, name),
+                    op_started.elapsed().as_millis().to_string(),
+                );
+            }
+            outcome?;
+            self.check_time(criteria)?;
+        }
+        self.check_criteria(criteria)
+    }
+
+    fn check_time(&self, criteria: &PassCriteria) -> Result<(), Failure> {
+        let elapsed = self.started.elapsed();
+        if criteria.max_time_secs > 0 && elapsed > Duration::from_secs(criteria.max_time_secs) {
+            return Err(Failure::new(
+                failure::TIMEOUT,
+                format!(
+                    "took {:.1}s, limit {}s",
+                    elapsed.as_secs_f32(),
+                    criteria.max_time_secs
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_criteria(&self, criteria: &PassCriteria) -> Result<(), Failure> {
+        if self.bad_blocks.len() as u64 > u64::from(criteria.max_bad_blocks) {
+            return Err(Failure::new(
+                failure::BAD_BLOCKS,
+                format!(
+                    "{} bad blocks, limit {}",
+                    self.bad_blocks.len(),
+                    criteria.max_bad_blocks
+                ),
+            ));
+        }
+        if self.ecc_corrections > criteria.max_ecc_corrections {
+            return Err(Failure::new(
+                failure::ECC_CORRECTIONS,
+                format!(
+                    "{} ECC corrections, limit {}",
+                    self.ecc_corrections, criteria.max_ecc_corrections
+                ),
+            ));
+        }
+        if let Some(percent) = self.match_percent {
+            if percent < criteria.min_match_percent {
+                return Err(Failure::new(
+                    failure::MATCH_PERCENT,
+                    format!(
+                        "{:.3}% match, need {}%",
+                        percent, criteria.min_match_percent
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, outcome: Result<(), Failure>, started_ms: u64) -> ProductionUnitResult {
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        self.data.insert("seq".to_string(), self.seq.to_string());
+        let failure_reason = match outcome {
+            Ok(()) => None,
+            Err(failure) => {
+                self.data
+                    .insert("failure_code".to_string(), failure.code.to_string());
+                Some(failure.message)
+            }
+        };
+        ProductionUnitResult {
+            serial_number: self.serial.clone(),
+            line_id: self.line.line_id.clone(),
+            station_id: self.station.id.clone(),
+            passed: failure_reason.is_none(),
+            timestamp: started_ms,
+            duration_ms,
+            chip_info: self.chip_info.clone(),
+            bad_blocks: self.bad_blocks.len() as u32,
+            ecc_corrections: self.ecc_corrections,
+            failure_reason,
+            data: std::mem::take(&mut self.data),
+        }
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        self.base_dir.join(path)
+    }
+
+    fn refresh_bad_blocks(&mut self, code: &'static str) -> Result<(), Failure> {
+        let blocks = self.transport.bad_blocks().map_err(Failure::of(code))?;
+        self.bad_blocks.extend(blocks);
+        Ok(())
+    }
+
+    fn count_ecc(&mut self, code: &'static str) -> Result<(), Failure> {
+        let corrected = self
+            .transport
+            .take_ecc_corrections()
+            .map_err(Failure::of(code))?;
+        self.ecc_corrections = self.ecc_corrections.saturating_add(corrected);
+        Ok(())
+    }
+
+    fn detect(&mut self, expected: &Option<String>) -> Result<(), Failure> {
+        let code = failure::CHIP_MISMATCH;
+        let chip = self.transport.chip_name().map_err(Failure::of(code))?;
+        let capacity = self.transport.capacity(false).map_err(Failure::of(code))?;
+        self.data
+            .insert("capacity".to_string(), capacity.to_string());
+        self.chip_info = chip.clone();
+        self.refresh_bad_blocks(code)?;
+
+        let Some(expected) = expected.as_ref().or(self.line.expected_chip.as_ref()) else {
+            return Ok(());
+        };
+        match chip {
+            Some(chip) if chip.eq_ignore_ascii_case(expected) => Ok(()),
+            Some(chip) => Err(Failure::new(
+                code,
+                format!("found {}, expected {}", chip, expected),
+            )),
+            None => Err(Failure::new(
+                code,
+                format!("chip not identified, expected {}", expected),
+            )),
+        }
+    }
+
+    /// Erase the whole chip, or with `full = false` only the blocks the
+    /// station's firmware images cover
+    fn erase(&mut self, full: bool) -> Result<(), Failure> {
+        let code = failure::ERASE_FAILED;
+        let capacity = self.transport.capacity(false).map_err(Failure::of(code))?;
+        let block = self
+            .transport
+            .erase_size()
+            .map_err(Failure::of(code))?
+            .max(1);
+        let length = if full {
+            capacity
+        } else {
+            let mut largest = None;
+            for op in &self.station.operations {
+                if let StationOperation::Program { firmware_path, .. } = op {
+                    let path = self.firmware(firmware_path)?;
+                    let len = std::fs::metadata(&path)
+                        .map_err(|e| Failure::new(code, format!("{}: {}", path.display(), e)))?
+                        .len();
+                    largest = largest.max(Some(len));
+                }
+            }
+            match largest {
+                Some(len) => ((len + block - 1) / block * block).min(capacity),
+                None => capacity,
+            }
+        };
+        let bad: HashSet<u64> = self
+            .transport
+            .bad_blocks()
+            .map_err(Failure::of(code))?
+            .into_iter()
+            .collect();
+        erase_good_blocks(self.transport.as_mut(), 0, length, &bad).map_err(Failure::of(code))?;
+        self.bytes += length;
+        self.refresh_bad_blocks(code)
+    }
+
+    fn firmware(&self, firmware_path: &str) -> Result<PathBuf, Failure> {
+        if !firmware_path.is_empty() {
+            return Ok(self.resolve(firmware_path));
+        }
+        self.line
+            .default_firmware
+            .as_deref()
+            .map(|path| self.resolve(path))
+            .ok_or_else(|| Failure::new(failure::PROGRAM_FAILED, "no firmware configured"))
+    }
+
+    fn program(&mut self, firmware_path: &str, verify: bool) -> Result<(), Failure> {
+        let code = failure::PROGRAM_FAILED;
+        let path = self.firmware(firmware_path)?;
+        let image = std::fs::read(&path)
+            .map_err(|e| Failure::new(code, format!("{}: {}", path.display(), e)))?;
+        let capacity = self.transport.capacity(false).map_err(Failure::of(code))?;
+        if image.len() as u64 > capacity {
+            return Err(Failure::new(
+                code,
+                format!(
+                    "image of {} bytes exceeds chip capacity {}",
+                    image.len(),
+                    capacity
+                ),
+            ));
+        }
+        self.data.insert(
+            "firmware_checksum".to_string(),
+            to_hex(&Sha256::digest(&image)),
+        );
+        if self.transport.nand().is_some() {
+            // Blocks are verified as they are programmed; the image may
+            // have shifted past bad blocks, so it cannot be read back in one
+            let verify = verify && self.line.verification != VerificationMode::None;
+            let job = program_nand(self.transport.as_mut(), &image, 0, verify, |_| Ok(()))
+                .map_err(Failure::of(code))?;
+            let erase_size = self.transport.erase_size().map_err(Failure::of(code))?;
+            self.bad_blocks.extend(
+                job.bad_blocks
+                    .iter()
+                    .map(|&(block, _)| block as u64 * erase_size),
+            );
+            self.bytes += image.len() as u64;
+            return self.count_ecc(code);
+        }
+
+        let mut offset = 0u64;
+        for chunk in image.chunks(CHUNK_SIZE) {
+            self.transport
+                .write(offset, chunk)
+                .map_err(Failure::of(code))?;
+            offset += chunk.len() as u64;
+        }
+        self.bytes += image.len() as u64;
+
+        if verify {
+            self.verify_image(&image)?;
+        }
+        Ok(())
+    }
+
+    /// Read back a programmed image as the line's `VerificationMode` says
+    fn verify_image(&mut self, image: &[u8]) -> Result<(), Failure> {
+        let code = failure::VERIFY_FAILED;
+        let mode = self.line.verification;
+        if mode == VerificationMode::None {
+            return Ok(());
+        }
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        for (i, expected) in image.chunks(CHUNK_SIZE).enumerate() {
+            if mode == VerificationMode::Quick && i as u64 % limits::QUICK_VERIFY_STRIDE != 0 {
+                continue;
+            }
+            let start = (i * CHUNK_SIZE) as u64;
+            let actual = &mut buf[..expected.len()];
+            self.transport
+                .read(start, actual, false)
+                .map_err(Failure::of(code))?;
+            self.bytes += expected.len() as u64;
+            if mode == VerificationMode::Checksum {
+                hasher.update(&*actual);
+            } else if let Some(j) = (0..expected.len()).find(|&j| actual[j] != expected[j]) {
+                return Err(Failure::new(
+                    code,
+                    format!("mismatch at 0x{:X}", start + j as u64),
+                ));
+            }
+        }
+        if mode == VerificationMode::Checksum
+            && hasher.finalize().as_slice() != Sha256::digest(image).as_slice()
+        {
+            return Err(Failure::new(code, "checksum mismatch"));
+        }
+        self.count_ecc(code)
+    }
+
+    /// Compare the chip with a golden image; `tolerance` is the percentage
+    /// of bytes allowed to differ
+    fn verify(&mut self, golden_path: &str, tolerance: f32) -> Result<(), Failure> {
+        let code = failure::VERIFY_FAILED;
+        let path = self.resolve(golden_path);
+        let golden = std::fs::read(&path)
+            .map_err(|e| Failure::new(code, format!("{}: {}", path.display(), e)))?;
+        let mut matched = 0u64;
+        let mut compared = 0u64;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        for (i, expected) in golden.chunks(CHUNK_SIZE).enumerate() {
+            let start = (i * CHUNK_SIZE) as u64;
+            let actual = &mut buf[..expected.len()];
+            self.transport
+                .read(start, actual, false)
+                .map_err(Failure::of(code))?;
+            for (j, (a, e)) in actual.iter().zip(expected).enumerate() {
+                let address = start + j as u64;
+                if self
+                    .serial_range
+                    .as_ref()
+                    .is_some_and(|r| r.contains(&address))
+                {
+                    continue;
+                }
+                compared += 1;
+                matched += u64::from(a == e);
+            }
+        }
+        self.bytes += golden.len() as u64;
+        self.count_ecc(code)?;
+
+        let percent = match compared {
+            0 => 100.0,
+            n => (matched as f64 * 100.0 / n as f64) as f32,
+        };
+        self.data
+            .insert("match_percent".to_string(), format!("{:.3}", percent));
+        self.match_percent = Some(self.match_percent.map_or(percent, |p| p.min(percent)));
+        if percent < 100.0 - tolerance {
+            return Err(Failure::new(
+                code,
+                format!("{:.3}% match, tolerance {}%", percent, tolerance),
+            ));
+        }
+        Ok(())
+    }
+
+    fn dump(&mut self, output_path: &str) -> Result<(), Failure> {
+        let code = failure::DUMP_FAILED;
+        let relative = expand_template(
+            output_path,
+            &TemplateVars {
+                seq: self.seq,
+                line: &self.line.line_id,
+                station: &self.station.id,
+                serial: Some(&self.serial),
+                now_ms: now_ms(),
+            },
+        )
+        .map_err(|e| Failure::new(code, e.to_string()))?;
+        let path = self.resolve(&relative);
+        let io = |e: std::io::Error| Failure::new(code, format!("{}: {}", path.display(), e));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(io)?;
+        }
+        let capacity = self.transport.capacity(false).map_err(Failure::of(code))?;
+        let mut file = std::fs::File::create(&path).map_err(io)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let mut offset = 0;
+        while offset < capacity {
+            let n = (capacity - offset).min(buf.len() as u64) as usize;
+            self.transport
+                .read(offset, &mut buf[..n], false)
+                .map_err(Failure::of(code))?;
+            hasher.update(&buf[..n]);
+            file.write_all(&buf[..n]).map_err(io)?;
+            offset += n as u64;
+        }
+        self.bytes += capacity;
+        self.data
+            .insert("dump_path".to_string(), path.display().to_string());
+        self.data
+            .insert("dump_checksum".to_string(), to_hex(&hasher.finalize()));
+        self.count_ecc(code)
+    }
+
+    fn custom_test(&mut self, script_path: &str) -> Result<(), Failure> {
+        let code = failure::CUSTOM_TEST;
+        let mut params = HashMap::new();
+        params.insert(
+            "script_path".to_string(),
+            self.resolve(script_path).display().to_string(),
+        );
+        params.insert("serial".to_string(), self.serial.clone());
+        let output = self
+            .transport
+            .custom("custom_test", &params)
+            .map_err(Failure::of(code))?;
+        let passed = output.get("passed").map_or(true, |p| p != "false");
+        let message = output.get("message").cloned();
+        self.data.extend(
+            output
+                .into_iter()
+                .map(|(key, value)| (format!("test_{}", key), value)),
+        );
+        if passed {
+            Ok(())
+        } else {
+            Err(Failure::new(
+                code,
+                message.unwrap_or_else(|| format!("{} failed", script_path)),
+            ))
+        }
+    }
+
+    /// Write the serial at `offset` and read it back
+    fn mark(&mut self, offset: Option<u64>) -> Result<(), Failure> {
+        let code = failure::SERIAL_FAILED;
+        let Some(offset) = offset else {
+            return Ok(());
+        };
+        let serial = self.serial.clone().into_bytes();
+        match self.transport.nand() {
+            Some(nand) => {
+                program_erased(nand, offset, &serial).map_err(|e| Failure::new(code, e))?
+            }
+            None => self
+                .transport
+                .write(offset, &serial)
+                .map_err(Failure::of(code))?,
+        }
+        let mut readback = vec![0u8; serial.len()];
+        self.transport
+            .read(offset, &mut readback, false)
+            .map_err(Failure::of(code))?;
+        if readback != serial {
+            return Err(Failure::new(
+                code,
+                format!("serial readback mismatch at 0x{:X}", offset),
+            ));
+        }
+        self.bytes += serial.len() as u64;
+        self.serial_range = Some(offset..offset + serial.len() as u64);
+        self.data
+            .insert("serial_offset".to_string(), format!("0x{:X}", offset));
+        Ok(())
+    }
+}
+
+/// Program `data` at `offset` into NAND pages that are still erased, so no
+/// block is erased and rewritten for a few bytes
+fn program_erased(nand: &mut dyn NandDevice, offset: u64, data: &[u8]) -> Result<(), String> {
+    let programmer = nand.chip_programmer();
+    let geometry = programmer.geometry();
+    let page_size = geometry.page_size as u64;
+    let pages_per_block = geometry.pages_per_block as u64;
+    let mut page = vec![0u8; geometry.page_size as usize];
+    let mut oob = vec![0u8; geometry.oob_size as usize];
+    let end = offset + data.len() as u64;
+    let mut index = offset / page_size;
+    while index * page_size < end {
+        let block = (index / pages_per_block) as u32;
+        let in_block = (index % pages_per_block) as u32;
+        nand.read_page(block, in_block, &mut page, &mut oob)
+            .map_err(|e| e.to_string())?;
+        if page.iter().chain(&oob).any(|&b| b != 0xFF) {
+            return Err(format!("page {} for the serial is not erased", index));
+        }
+        let base = index * page_size;
+        let (from, to) = (offset.max(base), end.min(base + page_size));
+        page[(from - base) as usize..(to - base) as usize]
+            .copy_from_slice(&data[(from - offset) as usize..(to - offset) as usize]);
+        nand.program_page(block, in_block, &page, &oob)
+            .map_err(|e| e.to_string())?;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_executor::{ExecResult, MemoryTransport};
+    use crate::server::{PoolDevice, ProductionLogging};
+    use crate::test_support::{
+        chip_factory, scratch_dir, server_with_devices, MemoryNand, NAND_BLOCK,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Memory chip reporting an ID, bad blocks and ECC corrections
+    struct TestChip {
+        chip: MemoryTransport,
+        bad_blocks: Vec<u64>,
+        ecc_per_read: u32,
+        ecc: u32,
+    }
+
+    impl DeviceTransport for TestChip {
+        fn capacity(&mut self, include_oob: bool) -> ExecResult<u64> {
+            self.chip.capacity(include_oob)
+        }
+
+        fn erase_size(&mut self) -> ExecResult<u64> {
+            self.chip.erase_size()
+        }
+
+        fn read(&mut self, address: u64, buf: &mut [u8], include_oob: bool) -> ExecResult<()> {
+            self.ecc += self.ecc_per_read;
+            self.chip.read(address, buf, include_oob)
+        }
+
+        fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+            self.chip.write(address, data)
+        }
+
+        fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+            self.chip.erase(address, length)
+        }
+
+        fn chip_name(&mut self) -> ExecResult<Option<String>> {
+            Ok(Some("W25N01GV".to_string()))
+        }
+
+        fn bad_blocks(&mut self) -> ExecResult<Vec<u64>> {
+            Ok(self.bad_blocks.clone())
+        }
+
+        fn take_ecc_corrections(&mut self) -> ExecResult<u32> {
+            Ok(std::mem::take(&mut self.ecc))
+        }
+    }
+
+    fn line(root: &Path, format: &str, operations: Vec<StationOperation>) -> ProductionLineConfig {
+        ProductionLineConfig {
+            line_id: "L1".to_string(),
+            name: "Test line".to_string(),
+            stations: vec![StationConfig {
+                id: "S1".to_string(),
+                name: "Station 1".to_string(),
+                device_id: Some("dev".to_string()),
+                operations,
+                pass_criteria: PassCriteria::default(),
+            }],
+            default_firmware: Some("fw.bin".to_string()),
+            expected_chip: Some("w25n01gv".to_string()),
+            auto_start: false,
+            verification: VerificationMode::Full,
+            logging: ProductionLogging {
+                log_dir: root.join("logs").display().to_string(),
+                format: format.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn setup(
+        dir: &str,
+        bad_blocks: Vec<u64>,
+        ecc_per_read: u32,
+    ) -> (SharedServer, TransportFactory, MemoryTransport, PathBuf) {
+        let root = scratch_dir(dir);
+        let firmware: Vec<u8> = (0..40_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(root.join("fw.bin"), firmware).unwrap();
+
+        let chip = MemoryTransport::new(128 * 1024, 4096);
+        let shared_chip = chip.clone();
+        let factory: TransportFactory = Arc::new(move |_: &PoolDevice| {
+            Ok(Box::new(TestChip {
+                chip: shared_chip.clone(),
+                bad_blocks: bad_blocks.clone(),
+                ecc_per_read,
+                ecc: 0,
+            }) as Box<dyn DeviceTransport>)
+        });
+        (server_with_devices(&["dev"]), factory, chip, root)
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let vars = TemplateVars {
+            seq: 42,
+            line: "L1",
+            station: "S2",
+            serial: None,
+            // 2024-02-29 13:05:09 UTC
+            now_ms: 1_709_211_909_000,
+        };
+        assert_eq!(
+            expand_template("OF-{line}{station}-{date}T{time}-{seq:06}", &vars).unwrap(),
+            "OF-L1S2-20240229T130509-000042"
+        );
+        assert!(expand_template("{serial}", &vars).is_err());
+        assert!(expand_template("{seq", &vars).is_err());
+        assert!(expand_template("{bogus}", &vars).is_err());
+    }
+
+    #[test]
+    fn test_station_programs_marks_and_logs_units() {
+        let (server, factory, chip, root) = setup("openflash_prod_pass", vec![7], 0);
+        let operations = vec![
+            StationOperation::DetectChip { expected: None },
+            StationOperation::Erase { full: false },
+            StationOperation::Program {
+                firmware_path: String::new(),
+                verify: true,
+            },
+            StationOperation::MarkSerial {
+                format: "SN{seq:04}".to_string(),
+                offset: Some(0x100),
+            },
+            StationOperation::Verify {
+                golden_path: "fw.bin".to_string(),
+                tolerance: 0.0,
+            },
+            StationOperation::Dump {
+                output_path: "dumps/{serial}.bin".to_string(),
+            },
+        ];
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        lock_server(&server)
+            .events
+            .subscribe(move |e| sink.lock().unwrap().push(e.clone()));
+
+        let mut runner = ProductionRunner::new(line(&root, "csv", operations), server, factory)
+            .unwrap()
+            .with_base_dir(&root)
+            .with_next_seq(7);
+        let first = runner.run_unit("S1").unwrap();
+        let second = runner.run_unit("S1").unwrap();
+
+        assert!(first.passed, "{:?}", first.failure_reason);
+        assert_eq!(first.serial_number, "SN0007");
+        assert_eq!(second.serial_number, "SN0008");
+        assert_eq!(first.chip_info.as_deref(), Some("W25N01GV"));
+        assert_eq!(first.bad_blocks, 1);
+        assert_eq!(first.data["match_percent"], "100.000");
+        assert_eq!(&chip.contents()[0x100..0x106], b"SN0008");
+        let dump = std::fs::read(root.join("dumps/SN0007.bin")).unwrap();
+        assert_eq!(&dump[0x100..0x106], b"SN0007");
+
+        let stats = runner.stats();
+        assert_eq!((stats.total_units, stats.passed_units), (2, 2));
+        assert_eq!(stats.pass_rate, 100.0);
+        assert!(stats.units_per_hour > 0.0);
+        let log = std::fs::read_to_string(root.join("logs/L1.csv")).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], limits::CSV_HEADER);
+        assert!(lines[2].contains(",SN0008,L1,S1,true,"));
+        let results = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| matches!(e, crate::server::WsMessage::ProductionResult { .. }))
+            .count();
+        assert_eq!(results, 2);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_nand_station_skips_bad_blocks() {
+        let root = scratch_dir("openflash_prod_nand");
+        let block = NAND_BLOCK as usize;
+        let firmware: Vec<u8> = (0..3 * block as u32).map(|i| (i % 249) as u8).collect();
+        std::fs::write(root.join("fw.bin"), &firmware).unwrap();
+        let chip = MemoryNand::new(32).with_bad_block(1).with_worn_block(3);
+        let factory = chip_factory(&[("dev", chip.clone())]);
+        let server = server_with_devices(&["dev"]);
+        let serial_offset = 20 * NAND_BLOCK + 8;
+        let operations = vec![
+            StationOperation::DetectChip { expected: None },
+            StationOperation::Erase { full: true },
+            StationOperation::Program {
+                firmware_path: String::new(),
+                verify: true,
+            },
+            StationOperation::MarkSerial {
+                format: "SN{seq:04}".to_string(),
+                offset: Some(serial_offset),
+            },
+        ];
+        let mut config = line(&root, "json", operations);
+        config.expected_chip = None;
+        let mut runner = ProductionRunner::new(config.clone(), server.clone(), factory.clone())
+            .unwrap()
+            .with_base_dir(&root);
+
+        // The firmware lands on blocks 0, 2 and 4; the factory bad block is
+        // never erased and the worn block counts against the unit
+        let unit = runner.run_unit("S1").unwrap();
+        assert!(unit.passed, "{:?}", unit.failure_reason);
+        assert_eq!(unit.bad_blocks, 2);
+        for (i, physical) in [0, 2, 4].into_iter().enumerate() {
+            assert_eq!(chip.block(physical), &firmware[i * block..(i + 1) * block]);
+        }
+        assert_eq!(chip.erase_count(1), 0);
+        assert_eq!(&chip.block(20)[8..14], unit.serial_number.as_bytes());
+
+        // A device error fails the unit, the next one passes
+        chip.fail_next(1);
+        let unit = runner.run_unit("S1").unwrap();
+        assert!(!unit.passed);
+        assert_eq!(unit.data["failure_code"], failure::ERASE_FAILED);
+        assert!(runner.run_unit("S1").unwrap().passed);
+
+        // Without an erase the serial page is already programmed
+        config.stations[0].operations.remove(1);
+        let mut runner = ProductionRunner::new(config, server, factory)
+            .unwrap()
+            .with_base_dir(&root);
+        let unit = runner.run_unit("S1").unwrap();
+        assert_eq!(unit.data["failure_code"], failure::SERIAL_FAILED);
+        assert!(unit.failure_reason.unwrap().contains("not erased"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_pass_criteria_fail_units() {
+        let (server, factory, _chip, root) = setup("openflash_prod_fail", vec![1, 2, 3], 150);
+        let operations = vec![
+            StationOperation::DetectChip { expected: None },
+            StationOperation::Program {
+                firmware_path: "fw.bin".to_string(),
+                verify: true,
+            },
+        ];
+        let mut config = line(&root, "json", operations);
+        config.stations[0].pass_criteria.max_bad_blocks = 2;
+        let mut runner = ProductionRunner::new(config.clone(), server.clone(), factory.clone())
+            .unwrap()
+            .with_base_dir(&root);
+        let unit = runner.run_unit("S1").unwrap();
+        assert!(!unit.passed);
+        assert_eq!(unit.data["failure_code"], failure::BAD_BLOCKS);
+
+        config.stations[0].pass_criteria.max_bad_blocks = 20;
+        config.expected_chip = Some("MT29F4G08".to_string());
+        let mut runner = ProductionRunner::new(config.clone(), server.clone(), factory.clone())
+            .unwrap()
+            .with_base_dir(&root);
+        let unit = runner.run_unit("S1").unwrap();
+        assert_eq!(unit.data["failure_code"], failure::CHIP_MISMATCH);
+
+        // The 40 kB read-back is a single read
+        config.expected_chip = None;
+        let mut runner = ProductionRunner::new(config, server.clone(), factory)
+            .unwrap()
+            .with_base_dir(&root);
+        let unit = runner.run_unit("S1").unwrap();
+        assert_eq!(unit.data["failure_code"], failure::ECC_CORRECTIONS);
+        assert_eq!(
+            unit.failure_reason.as_deref(),
+            Some("150 ECC corrections, limit 100")
+        );
+        assert_eq!(runner.stats().failure_reasons[failure::ECC_CORRECTIONS], 1);
+
+        let log = std::fs::read_to_string(root.join("logs/L1.jsonl")).unwrap();
+        assert_eq!(log.lines().count(), 3);
+        assert!(lock_server(&server)
+            .device_pool
+            .get_device("dev")
+            .unwrap()
+            .is_available());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
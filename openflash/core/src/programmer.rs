@@ -0,0 +1,878 @@
+//! Host side of the OpenFlash programmer protocol
+//!
+//! [`ProgrammerLink`] talks to the RP2040 programmer firmware over USB
+//! CDC-ACM (`serial:///dev/ttyACM0`), or over TCP (`tcp://host:port`) when
+//! the same port is exposed by a serial-to-TCP bridge such as ser2net. The
+//! Raspberry Pi daemon's Unix socket protocol is a different one and is not
+//! spoken here. [`ProgrammerTransport`] drives the parallel NAND chip on the
+//! programmer and implements [`DeviceTransport`], so the job executor,
+//! parallel dumps and production stations run on real hardware through
+//! [`connect_uri`](crate::job_executor::connect_uri). It is also a
+//! [`ProgramTarget`], so `openflash write` programs through
+//! [`ChipProgrammer`] with its retries and bad block handling.
+//!
+//! Framing follows the firmware's `usb_handler`: every command is a 64-byte
+//! [`Packet`] and most replies are `[command, status]` with status 0 on
+//! success, followed by a fixed payload for `NandReadId` (5 ID bytes) and
+//! `EmmcReadExtCsd` (512 bytes, only after an OK status). A command the
+//! firmware does not know is answered by the single byte 0xFF. Page
+//! commands take the page index (u32 LE) and the transfer length (u16 LE);
+//! `NandReadPage` streams the page bytes with no header, and for
+//! `NandWritePage` the page bytes follow the command right away and the
+//! reply comes once the page is programmed. `NandErase` takes the index of
+//! any page in the block. Raw bus sequences (`NandCmd`, `NandAddr`,
+//! `NandWriteData`, `NandSelectCe`) get status replies, and `NandReadData`
+//! streams its bytes like a page read.
+
+use crate::emmc::ext_csd;
+use crate::job_executor::{DeviceTransport, ExecError, ExecResult, NandDevice};
+use crate::onfi::{get_chip_info, CellType, NandChipInfo};
+use crate::protocol::{Command, Packet};
+use crate::write_ops::{ChipProgrammer, ProgramTarget, WriteError, WriteResult};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+pub mod limits {
+    /// Size of every command packet
+    pub const PACKET_SIZE: usize = 64;
+    /// Status byte of a successful reply
+    pub const STATUS_OK: u8 = 0x00;
+    /// Single-byte reply to a command the firmware does not know
+    pub const UNKNOWN_COMMAND: u8 = 0xFF;
+    /// Largest page transfer the firmware buffers (4096 + 256 spare bytes)
+    pub const MAX_TRANSFER: usize = 4352;
+    /// Connect, read and write timeout of a link (ms)
+    pub const IO_TIMEOUT_MS: u64 = 5_000;
+    /// Length of a parallel NAND ID
+    pub const NAND_ID_LEN: usize = 5;
+    /// Rated erase cycles per block, by cell type
+    pub const SLC_ERASE_CYCLES: u32 = 100_000;
+    pub const MLC_ERASE_CYCLES: u32 = 3_000;
+    pub const TLC_ERASE_CYCLES: u32 = 1_000;
+    pub const QLC_ERASE_CYCLES: u32 = 500;
+}
+
+// ============================================================================
+// Link
+// ============================================================================
+
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// Command channel to one programmer
+pub struct ProgrammerLink {
+    stream: Box<dyn Stream>,
+    uri: String,
+}
+
+impl std::fmt::Debug for ProgrammerLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgrammerLink")
+            .field("uri", &self.uri)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProgrammerLink {
+    /// Link over an already open byte stream; `uri` names it in errors
+    pub fn new(stream: impl Read + Write + Send + 'static, uri: &str) -> Self {
+        Self {
+            stream: Box::new(stream),
+            uri: uri.to_string(),
+        }
+    }
+
+    /// Open `serial://<path>` or `tcp://<host>:<port>`
+    pub fn connect(uri: &str) -> ExecResult<Self> {
+        let stream: Box<dyn Stream> = match uri.split_once("://") {
+            Some(("serial", path)) => Box::new(open_serial(Path::new(path))?),
+            Some(("tcp", addr)) => Box::new(connect_tcp(addr.trim_end_matches('/'))?),
+            _ => {
+                return Err(ExecError::Unsupported(format!(
+                    "no programmer link for {}",
+                    uri
+                )))
+            }
+        };
+        Ok(Self {
+            stream,
+            uri: uri.to_string(),
+        })
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Send `cmd` and check its `[command, status]` reply; any payload is
+    /// left on the link for the caller
+    pub fn command(&mut self, cmd: Command, args: &[u8]) -> ExecResult<()> {
+        host_only(cmd)?;
+        self.send(&Packet::new(cmd, args).to_bytes())?;
+        let status = self.reply_status(cmd)?;
+        self.check(cmd, status)
+    }
+
+    /// Check that the programmer answers
+    pub fn ping(&mut self) -> ExecResult<()> {
+        self.command(Command::Ping, &[])
+    }
+
+    /// ID bytes of the parallel NAND chip
+    pub fn read_nand_id(&mut self) -> ExecResult<[u8; limits::NAND_ID_LEN]> {
+        self.command(Command::NandReadId, &[])?;
+        let mut id = [0u8; limits::NAND_ID_LEN];
+        self.receive(&mut id)?;
+        Ok(id)
+    }
+
+    /// EXT_CSD register of the eMMC device
+    pub fn read_ext_csd(&mut self) -> ExecResult<Vec<u8>> {
+        self.command(Command::EmmcReadExtCsd, &[])?;
+        let mut data = vec![0u8; ext_csd::SIZE];
+        self.receive(&mut data)?;
+        Ok(data)
+    }
+
+    /// Read page `page` into `buf` (main area, then spare bytes if `buf`
+    /// is longer than a page)
+    pub fn read_page(&mut self, page: u32, buf: &mut [u8]) -> ExecResult<()> {
+        let args = page_args(page, buf.len())?;
+        self.send(&Packet::new(Command::NandReadPage, &args).to_bytes())?;
+        self.receive(buf)
+    }
+
+    /// Run a raw bus sequence (e.g. from [`crate::read_retry`] or
+    /// [`crate::nand_geometry`]) and return the bytes clocked out by its
+    /// `NandReadData` packets
+    pub fn execute(&mut self, packets: &[Packet]) -> ExecResult<Vec<u8>> {
+        let mut data = Vec::new();
+        for packet in packets {
+            host_only(packet.cmd)?;
+            match packet.cmd {
+                Command::NandReadData => {
+                    let count = u16::from_le_bytes([packet.args[0], packet.args[1]]);
+                    let start = data.len();
+                    data.resize(start + count as usize, 0);
+                    self.send(&packet.to_bytes())?;
+                    self.receive(&mut data[start..])?;
+                }
+                Command::NandCmd
+                | Command::NandAddr
+                | Command::NandWriteData
+                | Command::NandSelectCe => {
+                    self.send(&packet.to_bytes())?;
+                    let status = self.reply_status(packet.cmd)?;
+                    self.check(packet.cmd, status)?;
+                }
+                cmd => {
+                    return Err(ExecError::Unsupported(format!(
+                        "{:?} in a raw bus sequence",
+                        cmd
+                    )))
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    /// Program page `page` with `data`
+    pub fn program_page(&mut self, page: u32, data: &[u8]) -> ExecResult<()> {
+        let status = self.program_status(page, data)?;
+        self.check(Command::NandWritePage, status)
+    }
+
+    /// Erase the block holding page `page`
+    pub fn erase_block(&mut self, page: u32) -> ExecResult<()> {
+        let status = self.erase_status(page)?;
+        self.check(Command::NandErase, status)
+    }
+
+    /// Program a page and return the chip status instead of failing on it
+    fn program_status(&mut self, page: u32, data: &[u8]) -> ExecResult<u8> {
+        let args = page_args(page, data.len())?;
+        self.send(&Packet::new(Command::NandWritePage, &args).to_bytes())?;
+        self.send(data)?;
+        self.reply_status(Command::NandWritePage)
+    }
+
+    /// Erase a block and return the chip status instead of failing on it
+    fn erase_status(&mut self, page: u32) -> ExecResult<u8> {
+        self.send(&Packet::new(Command::NandErase, &page.to_le_bytes()).to_bytes())?;
+        self.reply_status(Command::NandErase)
+    }
+
+    fn send(&mut self, data: &[u8]) -> ExecResult<()> {
+        self.stream
+            .write_all(data)
+            .and_then(|()| self.stream.flush())
+            .map_err(|e| ExecError::Transport(format!("{}: {}", self.uri, e)))
+    }
+
+    fn receive(&mut self, buf: &mut [u8]) -> ExecResult<()> {
+        self.stream.read_exact(buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::WouldBlock => {
+                ExecError::Transport(format!("no reply from {}", self.uri))
+            }
+            _ => ExecError::Transport(format!("{}: {}", self.uri, e)),
+        })
+    }
+
+    /// Status byte of a `[command, status]` reply
+    fn reply_status(&mut self, cmd: Command) -> ExecResult<u8> {
+        let mut echo = [0u8; 1];
+        self.receive(&mut echo)?;
+        if echo[0] == limits::UNKNOWN_COMMAND {
+            return Err(ExecError::Unsupported(format!(
+                "{:?} is not implemented by the firmware on {}",
+                cmd, self.uri
+            )));
+        }
+        if echo[0] != cmd as u8 {
+            return Err(ExecError::Transport(format!(
+                "{} answered 0x{:02X} to {:?}",
+                self.uri, echo[0], cmd
+            )));
+        }
+        let mut status = [0u8; 1];
+        self.receive(&mut status)?;
+        Ok(status[0])
+    }
+
+    fn check(&self, cmd: Command, status: u8) -> ExecResult<()> {
+        if status != limits::STATUS_OK {
+            return Err(ExecError::Transport(format!(
+                "{:?} failed on {} with status 0x{:02X}",
+                cmd, self.uri, status
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Refuse commands that only exist in host-side planning code
+fn host_only(cmd: Command) -> ExecResult<()> {
+    if cmd.is_host_only() {
+        return Err(ExecError::Unsupported(format!(
+            "{:?} is host-only and not handled by the programmer firmware",
+            cmd
+        )));
+    }
+    Ok(())
+}
+
+fn page_args(page: u32, length: usize) -> ExecResult<[u8; 6]> {
+    if length > limits::MAX_TRANSFER {
+        return Err(ExecError::Transport(format!(
+            "page transfer of {} bytes exceeds the firmware buffer of {}",
+            length,
+            limits::MAX_TRANSFER
+        )));
+    }
+    let mut args = [0u8; 6];
+    args[..4].copy_from_slice(&page.to_le_bytes());
+    args[4..].copy_from_slice(&(length as u16).to_le_bytes());
+    Ok(args)
+}
+
+/// Open a CDC-ACM port in raw mode; the baud rate is irrelevant over USB
+#[cfg(unix)]
+fn open_serial(path: &Path) -> ExecResult<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)
+        .map_err(|e| ExecError::Connect(format!("{}: {}", path.display(), e)))?;
+    let fd = file.as_raw_fd();
+    // SAFETY: `fd` is an open descriptor owned by `file` and `tio` is a
+    // plain C struct filled in by tcgetattr before use
+    let configured = unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        libc::tcgetattr(fd, &mut tio) == 0 && {
+            libc::cfmakeraw(&mut tio);
+            // Reads return after at most VTIME tenths of a second
+            tio.c_cc[libc::VMIN] = 0;
+            tio.c_cc[libc::VTIME] = (limits::IO_TIMEOUT_MS / 100).min(255) as libc::cc_t;
+            libc::tcsetattr(fd, libc::TCSANOW, &tio) == 0
+        }
+    };
+    if !configured {
+        return Err(ExecError::Connect(format!(
+            "{}: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_serial(path: &Path) -> ExecResult<File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| ExecError::Connect(format!("{}: {}", path.display(), e)))
+}
+
+fn connect_tcp(addr: &str) -> ExecResult<TcpStream> {
+    let timeout = Duration::from_millis(limits::IO_TIMEOUT_MS);
+    let connect_error = |e: std::io::Error| ExecError::Connect(format!("{}: {}", addr, e));
+    let mut last = None;
+    for socket in addr.to_socket_addrs().map_err(connect_error)? {
+        match TcpStream::connect_timeout(&socket, timeout) {
+            Ok(stream) => {
+                stream
+                    .set_read_timeout(Some(timeout))
+                    .map_err(connect_error)?;
+                stream
+                    .set_write_timeout(Some(timeout))
+                    .map_err(connect_error)?;
+                let _ = stream.set_nodelay(true);
+                return Ok(stream);
+            }
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(match last {
+        Some(e) => connect_error(e),
+        None => ExecError::Connect(format!("{} does not resolve", addr)),
+    })
+}
+
+// ============================================================================
+// NAND Transport
+// ============================================================================
+
+/// [`DeviceTransport`] for the parallel NAND chip on a programmer
+///
+/// Addresses are linear over the main area, or over pages followed by
+/// their spare bytes with `include_oob`. Writes and erases cover whole
+/// blocks, so no data is read back and lost when a block fails, and they
+/// refuse blocks carrying a bad block marker.
+#[derive(Debug)]
+pub struct ProgrammerTransport {
+    link: ProgrammerLink,
+    chip: NandChipInfo,
+    /// Bad blocks found by the first scan
+    bad: Option<Vec<u32>>,
+}
+
+impl ProgrammerTransport {
+    /// Ping the programmer and identify its chip from the NAND ID
+    pub fn open(mut link: ProgrammerLink) -> ExecResult<Self> {
+        link.ping()?;
+        let id = link.read_nand_id()?;
+        let chip = get_chip_info(&id).ok_or_else(|| {
+            let id: Vec<String> = id.iter().map(|b| format!("{:02X}", b)).collect();
+            ExecError::Connect(format!("unknown NAND {} on {}", id.join(" "), link.uri))
+        })?;
+        if chip.page_size == 0 || chip.block_size == 0 {
+            return Err(ExecError::Connect(format!(
+                "{} {} has no page geometry",
+                chip.manufacturer, chip.model
+            )));
+        }
+        Ok(Self {
+            link,
+            chip,
+            bad: None,
+        })
+    }
+
+    /// Chip found by [`ProgrammerTransport::open`]
+    pub fn chip(&self) -> &NandChipInfo {
+        &self.chip
+    }
+
+    /// Programming engine sized for the chip, rated for its cell type
+    pub fn chip_programmer(&self) -> ChipProgrammer {
+        let max_erase_cycles = match self.chip.cell_type {
+            CellType::SLC => limits::SLC_ERASE_CYCLES,
+            CellType::MLC => limits::MLC_ERASE_CYCLES,
+            CellType::TLC => limits::TLC_ERASE_CYCLES,
+            CellType::QLC => limits::QLC_ERASE_CYCLES,
+        };
+        ChipProgrammer::new(
+            self.chip.page_size,
+            self.chip.block_size,
+            (self.page_count() / self.chip.block_size as u64) as u32,
+            self.chip.oob_size,
+            max_erase_cycles,
+        )
+    }
+
+    fn page_size(&self) -> u64 {
+        self.chip.page_size as u64
+    }
+
+    fn page_count(&self) -> u64 {
+        self.chip.size_mb as u64 * 1024 * 1024 / self.page_size()
+    }
+
+    fn stride(&self, include_oob: bool) -> u64 {
+        if include_oob {
+            self.page_size() + self.chip.oob_size as u64
+        } else {
+            self.page_size()
+        }
+    }
+
+    /// Whole blocks covered by `address`/`length`, none of them bad
+    fn good_blocks(&mut self, action: &str, address: u64, length: u64) -> ExecResult<Range<u32>> {
+        let block_size = self.erase_size()?;
+        if address % block_size != 0 || length % block_size != 0 {
+            return Err(ExecError::Transport(format!(
+                "{} 0x{:X}+0x{:X} is not aligned to 0x{:X}",
+                action, address, length, block_size
+            )));
+        }
+        self.check_range(address, length, false)?;
+        let first = (address / block_size) as u32;
+        let blocks = first..first + (length / block_size) as u32;
+        let bad = self.scan_bad_blocks()?;
+        if let Some(block) = bad.iter().find(|b| blocks.contains(b)) {
+            return Err(ExecError::Transport(format!(
+                "{} 0x{:X}+0x{:X} covers bad block {}",
+                action, address, length, block
+            )));
+        }
+        Ok(blocks)
+    }
+
+    /// Blocks with a marker in the first spare byte of their first, second
+    /// or last page, scanned once
+    fn scan_bad_blocks(&mut self) -> ExecResult<&[u32]> {
+        if self.bad.is_none() {
+            let pages_per_block = self.chip.block_size;
+            let mut page = vec![0u8; self.stride(true) as usize];
+            let mut bad = Vec::new();
+            for block in 0..(self.page_count() / pages_per_block as u64) as u32 {
+                let first = block * pages_per_block;
+                for offset in [0, 1, pages_per_block - 1] {
+                    self.link.read_page(first + offset, &mut page)?;
+                    if page[self.chip.page_size as usize] != 0xFF {
+                        bad.push(block);
+                        break;
+                    }
+                }
+            }
+            self.bad = Some(bad);
+        }
+        Ok(self.bad.as_deref().unwrap_or_default())
+    }
+
+    fn check_range(&self, address: u64, length: u64, include_oob: bool) -> ExecResult<()> {
+        let capacity = self.page_count() * self.stride(include_oob);
+        match address.checked_add(length) {
+            Some(end) if end <= capacity => Ok(()),
+            _ => Err(ExecError::OutOfRange {
+                address,
+                length,
+                capacity,
+            }),
+        }
+    }
+}
+
+impl DeviceTransport for ProgrammerTransport {
+    fn capacity(&mut self, include_oob: bool) -> ExecResult<u64> {
+        Ok(self.page_count() * self.stride(include_oob))
+    }
+
+    fn erase_size(&mut self) -> ExecResult<u64> {
+        Ok(self.chip.block_size as u64 * self.page_size())
+    }
+
+    fn read(&mut self, address: u64, buf: &mut [u8], include_oob: bool) -> ExecResult<()> {
+        self.check_range(address, buf.len() as u64, include_oob)?;
+        let stride = self.stride(include_oob);
+        let mut page = vec![0u8; stride as usize];
+        let mut done = 0;
+        while done < buf.len() {
+            let position = address + done as u64;
+            let offset = (position % stride) as usize;
+            self.link.read_page((position / stride) as u32, &mut page)?;
+            let n = (page.len() - offset).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&page[offset..offset + n]);
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+        let blocks = self.good_blocks("write", address, data.len() as u64)?;
+        let pages_per_block = self.chip.block_size;
+        let block_size = self.erase_size()? as usize;
+        for (block, chunk) in blocks.zip(data.chunks(block_size)) {
+            let first_page = block * pages_per_block;
+            self.link.erase_block(first_page)?;
+            for (i, page) in chunk.chunks(self.page_size() as usize).enumerate() {
+                if page.iter().any(|&b| b != 0xFF) {
+                    self.link.program_page(first_page + i as u32, page)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+        let pages_per_block = self.chip.block_size;
+        for block in self.good_blocks("erase", address, length)? {
+            self.link.erase_block(block * pages_per_block)?;
+        }
+        Ok(())
+    }
+
+    fn chip_name(&mut self) -> ExecResult<Option<String>> {
+        Ok(Some(format!(
+            "{} {}",
+            self.chip.manufacturer, self.chip.model
+        )))
+    }
+
+    /// Blocks whose first spare byte on page 0, 1 or the last page is not
+    /// 0xFF
+    fn bad_blocks(&mut self) -> ExecResult<Vec<u64>> {
+        let block_size = self.erase_size()?;
+        Ok(self
+            .scan_bad_blocks()?
+            .iter()
+            .map(|&block| block as u64 * block_size)
+            .collect())
+    }
+
+    fn nand(&mut self) -> Option<&mut dyn NandDevice> {
+        Some(self)
+    }
+}
+
+impl NandDevice for ProgrammerTransport {
+    fn chip_programmer(&self) -> ChipProgrammer {
+        ProgrammerTransport::chip_programmer(self)
+    }
+}
+
+/// Page access for [`ChipProgrammer::program_image`]; a FAIL status from
+/// the chip surfaces as `EraseFailed`/`ProgramFailed` so the block is
+/// retried and then marked bad
+impl ProgramTarget for ProgrammerTransport {
+    fn erase_block(&mut self, block: u32) -> WriteResult<()> {
+        let status = self
+            .link
+            .erase_status(block * self.chip.block_size)
+            .map_err(|e| WriteError::IoError(e.to_string()))?;
+        if status != limits::STATUS_OK {
+            return Err(WriteError::EraseFailed(block));
+        }
+        Ok(())
+    }
+
+    fn program_page(&mut self, block: u32, page: u32, data: &[u8], oob: &[u8]) -> WriteResult<()> {
+        if oob.first().is_some_and(|&b| b != 0xFF) {
+            // May be a bad block marker, rescan next time
+            self.bad = None;
+        }
+        let raw = [data, oob].concat();
+        let status = self
+            .link
+            .program_status(block * self.chip.block_size + page, &raw)
+            .map_err(|e| WriteError::IoError(e.to_string()))?;
+        if status != limits::STATUS_OK {
+            return Err(WriteError::ProgramFailed { block, page });
+        }
+        Ok(())
+    }
+
+    fn read_page(
+        &mut self,
+        block: u32,
+        page: u32,
+        data: &mut [u8],
+        oob: &mut [u8],
+    ) -> WriteResult<()> {
+        let mut raw = vec![0u8; data.len() + oob.len()];
+        self.link
+            .read_page(block * self.chip.block_size + page, &mut raw)
+            .map_err(|e| WriteError::IoError(e.to_string()))?;
+        let (main, spare) = raw.split_at(data.len());
+        data.copy_from_slice(main);
+        oob.copy_from_slice(spare);
+        Ok(())
+    }
+
+    fn send_packets(&mut self, packets: &[Packet]) -> WriteResult<()> {
+        self.link
+            .execute(packets)
+            .map(|_| ())
+            .map_err(|e| WriteError::IoError(e.to_string()))
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job_executor::connect_uri;
+    use crate::server::{DevicePlatform, PoolDevice};
+    use crate::test_support::{fake_programmer, FACTORY_BAD_BLOCK, WORN_BLOCK};
+    use crate::write_ops::{BadBlockReason, ProgramImage};
+
+    const STRIDE: usize = 2048 + 64;
+
+    /// Stream replaying a `>`/`<` transcript: host writes must match the
+    /// next `>` lines and reads are served from the `<` lines after them
+    struct Replay {
+        steps: Vec<(bool, Vec<u8>)>,
+        step: usize,
+        offset: usize,
+    }
+
+    impl Replay {
+        fn new(transcript: &str) -> Self {
+            let steps = transcript
+                .lines()
+                .filter_map(|line| {
+                    let (dir, bytes) = line.split_once(' ')?;
+                    let host = match dir {
+                        ">" => true,
+                        "<" => false,
+                        _ => return None,
+                    };
+                    let bytes = bytes
+                        .split_whitespace()
+                        .map(|b| u8::from_str_radix(b, 16).unwrap())
+                        .collect();
+                    Some((host, bytes))
+                })
+                .collect();
+            Self {
+                steps,
+                step: 0,
+                offset: 0,
+            }
+        }
+
+        fn advance(&mut self, n: usize) {
+            self.offset += n;
+            if self.offset == self.steps[self.step].1.len() {
+                self.step += 1;
+                self.offset = 0;
+            }
+        }
+    }
+
+    impl Read for Replay {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.steps.get(self.step) {
+                Some((false, bytes)) => {
+                    let n = buf.len().min(bytes.len() - self.offset);
+                    buf[..n].copy_from_slice(&bytes[self.offset..self.offset + n]);
+                    self.advance(n);
+                    Ok(n)
+                }
+                _ => Err(std::io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    impl Write for Replay {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let bytes = match self.steps.get(self.step) {
+                Some((true, bytes)) => bytes,
+                Some((false, _)) => panic!("host wrote {:02X?} before reading the reply", buf),
+                None => return Err(std::io::ErrorKind::BrokenPipe.into()),
+            };
+            let n = buf.len().min(bytes.len() - self.offset);
+            assert_eq!(&buf[..n], &bytes[self.offset..self.offset + n]);
+            self.advance(n);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_firmware_session() {
+        let transcript = include_str!("../tests/fixtures/rp2040_nand_session.txt");
+        let mut link = ProgrammerLink::new(Replay::new(transcript), "replay");
+        link.ping().unwrap();
+        assert_eq!(link.read_nand_id().unwrap(), [0xEC, 0xF1, 0x00, 0x95, 0x40]);
+        let mut page = [0u8; 8];
+        link.read_page(0x40, &mut page).unwrap();
+        assert_eq!(page, [0x5A, 0xA5, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        link.program_page(0x41, &[0x11, 0x22, 0x33, 0x44]).unwrap();
+        assert_eq!(link.program_status(0xC0, &[0x00, 0x00]).unwrap(), 0x01);
+        link.erase_block(0x40).unwrap();
+        assert!(matches!(link.read_ext_csd(), Err(ExecError::Transport(_))));
+        let id = link
+            .execute(&[
+                Packet::new(Command::NandSelectCe, &[0]),
+                Packet::new(Command::NandCmd, &[0x90]),
+                Packet::new(Command::NandAddr, &[0x00]),
+                Packet::new(Command::NandReadData, &[0x05, 0x00]),
+            ])
+            .unwrap();
+        assert_eq!(id, [0xEC, 0xF1, 0x00, 0x95, 0x40]);
+        assert!(matches!(
+            link.execute(&[Packet::new(Command::NandSelectCe, &[1])]),
+            Err(ExecError::Transport(_))
+        ));
+        assert!(matches!(
+            link.command(Command::EmmcSwitch, &[0x03, 0xB3, 0x08, 0x00]),
+            Err(ExecError::Unsupported(_))
+        ));
+        assert!(matches!(
+            link.command(Command::NandReadStatus, &[]),
+            Err(ExecError::Unsupported(_))
+        ));
+        assert!(link.ping().is_err());
+    }
+
+    #[test]
+    fn test_nand_over_tcp() {
+        let uri = fake_programmer();
+        let mut link = ProgrammerLink::connect(&uri).unwrap();
+        assert_eq!(link.read_ext_csd().unwrap()[ext_csd::EXT_CSD_REV], 8);
+        assert!(matches!(
+            link.command(Command::SpiNorChipErase, &[]),
+            Err(ExecError::Unsupported(_))
+        ));
+        let mut nand = ProgrammerTransport::open(link).unwrap();
+        assert_eq!(nand.chip_name().unwrap().unwrap(), "Samsung K9F1G08U0B");
+        assert_eq!(nand.capacity(false).unwrap(), 128 * 1024 * 1024);
+        assert_eq!(nand.capacity(true).unwrap(), 65536 * STRIDE as u64);
+        assert_eq!(nand.erase_size().unwrap(), 128 * 1024);
+
+        // Writes cover whole good blocks
+        let image: Vec<u8> = (0..2 * 128 * 1024u32).map(|i| (i % 251) as u8).collect();
+        nand.write(128 * 1024, &image).unwrap();
+        let mut back = vec![0u8; image.len()];
+        nand.read(128 * 1024, &mut back, false).unwrap();
+        assert_eq!(back, image);
+        assert!(matches!(
+            nand.write(128 * 1024 - 1000, &image),
+            Err(ExecError::Transport(_))
+        ));
+        let bad = FACTORY_BAD_BLOCK as u64 * 128 * 1024;
+        assert!(matches!(
+            nand.write(bad, &image[..128 * 1024]),
+            Err(ExecError::Transport(_))
+        ));
+        assert!(matches!(
+            nand.erase(bad - 128 * 1024, 2 * 128 * 1024),
+            Err(ExecError::Transport(_))
+        ));
+        let mut page = vec![0u8; STRIDE];
+        nand.read(bad / 2048 * STRIDE as u64, &mut page, true)
+            .unwrap();
+        assert_eq!(page[2048], 0x00);
+
+        // A marker on the last page of a block counts as well
+        let mut marker = vec![0xFF; 64];
+        marker[0] = 0x00;
+        nand.program_page(9, 63, &[0xFF; 2048], &marker).unwrap();
+        assert_eq!(
+            nand.bad_blocks().unwrap(),
+            vec![FACTORY_BAD_BLOCK as u64 * 128 * 1024, 9 * 128 * 1024]
+        );
+        assert!(nand.erase(9 * 128 * 1024, 128 * 1024).is_err());
+
+        let mut block = vec![0xFF; 128 * 1024];
+        block[..4096].fill(0x11);
+        nand.write(0, &block).unwrap();
+        let mut head = vec![0u8; 4097];
+        nand.read(0, &mut head, false).unwrap();
+        assert!(head[..4096].iter().all(|&b| b == 0x11));
+        assert_eq!(head[4096], 0xFF);
+
+        // Raw reads interleave the spare area
+        let mut raw = vec![0u8; STRIDE + 4];
+        nand.read(0, &mut raw, true).unwrap();
+        assert!(raw[2048..STRIDE].iter().all(|&b| b == 0xFF));
+        assert_eq!(&raw[STRIDE..], &[0x11; 4]);
+
+        nand.erase(0, 128 * 1024).unwrap();
+        nand.read(0, &mut head, false).unwrap();
+        assert!(head.iter().all(|&b| b == 0xFF));
+        assert!(matches!(nand.erase(1, 4096), Err(ExecError::Transport(_))));
+        assert!(matches!(
+            nand.read(128 * 1024 * 1024, &mut head, false),
+            Err(ExecError::OutOfRange { .. })
+        ));
+
+        // The executor's factory opens the same device by URI
+        drop(nand);
+        let device = PoolDevice::new("pi", "pi", &uri, DevicePlatform::RaspberryPi);
+        let mut transport = connect_uri(&device).unwrap();
+        assert_eq!(transport.erase_size().unwrap(), 128 * 1024);
+        let usb = PoolDevice::new("usb", "usb", "usb://1-1", DevicePlatform::RP2040);
+        assert!(matches!(
+            connect_uri(&usb).err(),
+            Some(ExecError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_program_image_over_tcp() {
+        let link = ProgrammerLink::connect(&fake_programmer()).unwrap();
+        let mut nand = ProgrammerTransport::open(link).unwrap();
+        let block_bytes = 128 * 1024;
+        assert_eq!(
+            nand.bad_blocks().unwrap(),
+            vec![FACTORY_BAD_BLOCK as u64 * block_bytes]
+        );
+
+        let mut programmer = nand.chip_programmer();
+        programmer
+            .bad_block_table_mut()
+            .scan_factory_bad_blocks(&[(FACTORY_BAD_BLOCK, 0x00)]);
+        let data: Vec<u8> = (0..6 * block_bytes as u32)
+            .map(|i| (i % 253) as u8)
+            .collect();
+        let image = ProgramImage::new(&data);
+        let mut job = programmer.plan(&image).unwrap();
+        let done = programmer
+            .program_image(&mut nand, &image, &mut job, |_| true)
+            .unwrap();
+
+        // The worn block is retried and marked bad; it and the factory bad
+        // block are skipped and the rest of the image shifts past them
+        assert_eq!(done.verify_retries, 3);
+        assert_eq!(
+            job.bad_blocks,
+            vec![(WORN_BLOCK, BadBlockReason::ProgramFail)]
+        );
+        assert_eq!(
+            job.completed,
+            vec![(0, 0), (1, 1), (2, 2), (3, 4), (4, 6), (5, 7)]
+        );
+        for (logical, physical) in job.completed {
+            let mut back = vec![0u8; block_bytes as usize];
+            nand.read(physical as u64 * block_bytes, &mut back, false)
+                .unwrap();
+            let start = logical as usize * block_bytes as usize;
+            assert_eq!(back, &data[start..start + block_bytes as usize]);
+        }
+    }
+}
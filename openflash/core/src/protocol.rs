@@ -33,6 +33,9 @@ pub enum Command {
     NandReadId = 0x14,
     NandErase = 0x15,
     NandReadStatus = 0x16,
+    NandWriteData = 0x17, // Latch raw data bytes (args[0] = count)
+    NandReadData = 0x18,  // Clock out raw data bytes (args[0..2] = count LE)
+    NandSelectCe = 0x19,  // Select chip-enable (args[0] = CE index)
 
     // SPI NAND commands (0x20-0x3F)
     SpiNandReadId = 0x20,
@@ -58,9 +61,14 @@ pub enum Command {
     EmmcReadMultiple = 0x45,  // Read multiple blocks
     EmmcWriteBlock = 0x46,    // Write single block
     EmmcWriteMultiple = 0x47, // Write multiple blocks
-    EmmcErase = 0x48,         // Erase blocks
+    EmmcErase = 0x48,         // CMD35/36/38 (args[0..4] = start, [4..8] = end, [8..12] = CMD38 arg, LE)
     EmmcGetStatus = 0x49,     // Get card status
     EmmcSetPartition = 0x4A,  // Select partition (user/boot/rpmb)
+    // Host-only (see `is_host_only`): planned by rpmb/emmc_partition, no firmware handler yet
+    EmmcSetBlockCount = 0x4B, // CMD23 (args[0..4] = argument LE, bit 31 = reliable write)
+    EmmcSwitch = 0x4C,        // CMD6 SWITCH (args[0..4] = argument LE), waits for busy
+    EmmcSendWpType = 0x4D,    // CMD31 (args[0..4] = group address LE), returns 8 bytes
+    EmmcClearWp = 0x4E,       // CMD29 (args[0..4] = group address LE)
 
     // SPI NOR commands (0x60-0x7F)
     SpiNorReadJedecId = 0x60,   // Read JEDEC ID
@@ -94,6 +102,8 @@ pub enum Command {
     UfsWrite16 = 0x86,        // SCSI WRITE(16) command
     UfsSelectLun = 0x87,      // Select logical unit
     UfsGetStatus = 0x88,      // Get device status
+    // Host-only (see `is_host_only`): planned by ufs_upiu, no firmware handler yet
+    UfsUpiu = 0x89, // Raw UPIU exchange (args[0..2] = request length LE, bulk in/out)
 
     // Advanced Write Operations (0xA0-0xBF) - v1.7
     FullChipProgram = 0xA0,    // Full chip programming with verify
@@ -207,6 +217,9 @@ impl Command {
             0x07 | 0x14 => Some(Command::NandReadId),
             0x15 => Some(Command::NandErase),
             0x16 => Some(Command::NandReadStatus),
+            0x17 => Some(Command::NandWriteData),
+            0x18 => Some(Command::NandReadData),
+            0x19 => Some(Command::NandSelectCe),
 
             // SPI NAND
             0x20 => Some(Command::SpiNandReadId),
@@ -235,6 +248,10 @@ impl Command {
             0x48 => Some(Command::EmmcErase),
             0x49 => Some(Command::EmmcGetStatus),
             0x4A => Some(Command::EmmcSetPartition),
+            0x4B => Some(Command::EmmcSetBlockCount),
+            0x4C => Some(Command::EmmcSwitch),
+            0x4D => Some(Command::EmmcSendWpType),
+            0x4E => Some(Command::EmmcClearWp),
 
             // SPI NOR
             0x60 => Some(Command::SpiNorReadJedecId),
@@ -268,6 +285,7 @@ impl Command {
             0x86 => Some(Command::UfsWrite16),
             0x87 => Some(Command::UfsSelectLun),
             0x88 => Some(Command::UfsGetStatus),
+            0x89 => Some(Command::UfsUpiu),
 
             // Advanced Write Operations (v1.7)
             0xA0 => Some(Command::FullChipProgram),
@@ -402,6 +420,10 @@ impl Command {
                 | Command::EmmcErase
                 | Command::EmmcGetStatus
                 | Command::EmmcSetPartition
+                | Command::EmmcSetBlockCount
+                | Command::EmmcSwitch
+                | Command::EmmcSendWpType
+                | Command::EmmcClearWp
         )
     }
 
@@ -445,6 +467,7 @@ impl Command {
                 | Command::UfsWrite16
                 | Command::UfsSelectLun
                 | Command::UfsGetStatus
+                | Command::UfsUpiu
         )
     }
 
@@ -571,6 +594,21 @@ impl Command {
                 | Command::CloudStatus
         )
     }
+
+    /// Check if command only exists in host-side planning code
+    ///
+    /// Packets are built for these, but no programmer firmware handles
+    /// them yet, so `ProgrammerLink` refuses to send them.
+    pub fn is_host_only(&self) -> bool {
+        matches!(
+            self,
+            Command::EmmcSetBlockCount
+                | Command::EmmcSwitch
+                | Command::EmmcSendWpType
+                | Command::EmmcClearWp
+                | Command::UfsUpiu
+        )
+    }
 }
 
 /// Protocol packet structure (64 bytes total)
@@ -623,6 +661,11 @@ pub mod nand_commands {
     pub const ERASESTART: u8 = 0xD0;
     pub const READSTATUS: u8 = 0x70;
     pub const RESET: u8 = 0xFF;
+    pub const READ_CACHE_SEQ: u8 = 0x31;
+    pub const READ_CACHE_END: u8 = 0x3F;
+    pub const READ_STATUS_ENHANCED: u8 = 0x78;
+    pub const SET_FEATURES: u8 = 0xEF;
+    pub const GET_FEATURES: u8 = 0xEE;
 }
 
 /// SPI NAND flash commands (re-exported from spi_nand module)
@@ -708,6 +751,15 @@ mod tests {
         assert_eq!(Command::from_u8(0x07), Some(Command::NandReadId));
     }
 
+    #[test]
+    fn test_host_only_commands() {
+        assert!(Command::EmmcSwitch.is_host_only());
+        assert!(Command::EmmcClearWp.is_host_only());
+        assert!(!Command::EmmcReadExtCsd.is_host_only());
+        assert!(Command::UfsUpiu.is_host_only());
+        assert!(!Command::NandReadData.is_host_only());
+    }
+
     #[test]
     fn test_spi_nand_command_detection() {
         assert!(Command::SpiNandReadId.is_spi_nand());
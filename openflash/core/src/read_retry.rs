@@ -0,0 +1,723 @@
+//! Parallel NAND read-retry for worn MLC/TLC flash
+//!
+//! When ECC fails on a page, most MLC/TLC parts can be re-read with shifted
+//! read reference voltages. Every vendor exposes this differently, so this
+//! module turns each vendor's sequence into `NandCmd`/`NandAddr`/`NandWriteData`
+//! packets and drives a per-page retry loop:
+//!
+//! 1. `ReadRetryEngine::begin` returns packets that select the starting level
+//! 2. The host reads the page and hands it to `ReadRetryEngine::submit`
+//! 3. `submit` either returns the corrected data or the packets for the next level
+//! 4. `ReadRetryEngine::finish` returns packets that restore default thresholds
+//!
+//! The engine performs no I/O itself, so it works over any transport.
+
+use crate::ecc::{decode_with_ecc, EccAlgorithm};
+use crate::onfi::manufacturers;
+use crate::protocol::{nand_commands, Command, Packet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// ============================================================================
+// Vendor Commands
+// ============================================================================
+
+/// Vendor-specific read-retry opcodes
+pub mod commands {
+    /// Hynix: set parameter registers
+    pub const HYNIX_SET_PARAM: u8 = 0x36;
+    /// Hynix: commit parameter registers
+    pub const HYNIX_COMMIT: u8 = 0x16;
+    /// Hynix: OTP access sequence opcodes
+    pub const HYNIX_OTP_ENTER: [u8; 4] = [0x17, 0x04, 0x19, 0x00];
+    /// Hynix: leave OTP read mode
+    pub const HYNIX_OTP_EXIT: u8 = 0x38;
+
+    /// Micron: ONFI feature address for read-retry level
+    pub const MICRON_FEATURE_READ_RETRY: u8 = 0x89;
+
+    /// Toshiba/Kioxia: enter read-retry mode
+    pub const TOSHIBA_PRE_1: u8 = 0x5C;
+    pub const TOSHIBA_PRE_2: u8 = 0xC5;
+    /// Toshiba/Kioxia: set retry register
+    pub const TOSHIBA_SET_PARAM: u8 = 0x55;
+    /// Toshiba/Kioxia: apply shifted thresholds to next read
+    pub const TOSHIBA_ENABLE_1: u8 = 0x26;
+    pub const TOSHIBA_ENABLE_2: u8 = 0x5D;
+
+    /// Samsung: set retry register
+    pub const SAMSUNG_SET_PARAM: u8 = 0xA1;
+
+    /// SanDisk: enter read-retry mode
+    pub const SANDISK_PRE_1: u8 = 0x3B;
+    pub const SANDISK_PRE_2: u8 = 0xB9;
+    /// SanDisk: set retry register
+    pub const SANDISK_SET_PARAM: u8 = 0x53;
+}
+
+// ============================================================================
+// Retry Tables
+// ============================================================================
+
+/// Read-retry sequence family
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetryVendor {
+    /// SK Hynix, per-chip table stored in OTP
+    Hynix,
+    /// Micron, ONFI SET FEATURES 0x89
+    Micron,
+    /// Toshiba/Kioxia
+    Toshiba,
+    /// Samsung
+    Samsung,
+    /// SanDisk
+    SanDisk,
+}
+
+impl RetryVendor {
+    /// Map a JEDEC manufacturer ID to its read-retry family
+    pub fn from_manufacturer_id(id: u8) -> Option<Self> {
+        match id {
+            manufacturers::HYNIX => Some(Self::Hynix),
+            manufacturers::MICRON | manufacturers::INTEL => Some(Self::Micron),
+            manufacturers::TOSHIBA => Some(Self::Toshiba),
+            manufacturers::SAMSUNG => Some(Self::Samsung),
+            manufacturers::SANDISK => Some(Self::SanDisk),
+            _ => None,
+        }
+    }
+}
+
+/// Hynix 20nm MLC retry registers (8 registers per set)
+const HYNIX_REGS_20NM: [u8; 8] = [0xCC, 0xBF, 0xAA, 0xAB, 0xCD, 0xAD, 0xAE, 0xAF];
+/// Hynix 1xnm MLC retry registers (4 registers per set)
+const HYNIX_REGS_1XNM: [u8; 4] = [0x38, 0x39, 0x3A, 0x3B];
+
+/// Toshiba/Kioxia 19nm/A19nm retry registers
+const TOSHIBA_REGS: [u8; 4] = [0x04, 0x05, 0x06, 0x07];
+/// Toshiba/Kioxia retry offsets, level 0 is the default
+const TOSHIBA_LEVELS: [[u8; 4]; 8] = [
+    [0x00, 0x00, 0x00, 0x00],
+    [0x04, 0x04, 0x7C, 0x7E],
+    [0x00, 0x7C, 0x78, 0x78],
+    [0x7C, 0x76, 0x74, 0x72],
+    [0x08, 0x08, 0x00, 0x00],
+    [0x0B, 0x7E, 0x76, 0x74],
+    [0x10, 0x76, 0x72, 0x70],
+    [0x02, 0x00, 0x7E, 0x7C],
+];
+
+/// Samsung 21nm/1xnm retry registers
+const SAMSUNG_REGS: [u8; 4] = [0xA7, 0xA4, 0xA5, 0xA6];
+/// Samsung retry offsets, level 0 is the default
+const SAMSUNG_LEVELS: [[u8; 4]; 15] = [
+    [0x00, 0x00, 0x00, 0x00],
+    [0x05, 0x0A, 0x00, 0x00],
+    [0x28, 0x00, 0xEC, 0xD8],
+    [0xED, 0xF5, 0xED, 0xE6],
+    [0x0A, 0x0F, 0x05, 0x00],
+    [0x0F, 0x0A, 0xFB, 0xEC],
+    [0xE8, 0xEF, 0xE8, 0xDC],
+    [0xF1, 0xFB, 0xFE, 0xF0],
+    [0x0A, 0x00, 0xFB, 0xEC],
+    [0xD0, 0xE2, 0xD0, 0xC2],
+    [0x14, 0x0F, 0xFB, 0xEC],
+    [0xE8, 0xFB, 0xE8, 0xDC],
+    [0x1E, 0x14, 0xFB, 0xEC],
+    [0xFB, 0xFF, 0xFB, 0xF8],
+    [0x07, 0x0C, 0x02, 0x00],
+];
+
+/// SanDisk 1x/1y MLC retry registers
+const SANDISK_REGS: [u8; 3] = [0x04, 0x05, 0x07];
+/// SanDisk retry offsets, level 0 is the default
+const SANDISK_LEVELS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00],
+    [0xF0, 0xF0, 0xF0],
+    [0xE0, 0xE0, 0xE0],
+    [0xD0, 0xD0, 0xD0],
+    [0x10, 0x20, 0x30],
+    [0x20, 0x40, 0x60],
+    [0xC0, 0xD0, 0xB0],
+    [0xB0, 0xC0, 0xA0],
+];
+
+/// Default number of Micron retry levels (level 0 = default thresholds)
+pub const MICRON_DEFAULT_LEVELS: u8 = 8;
+
+/// A vendor read-retry table
+///
+/// For register-based vendors each level is one value per register.
+/// Micron uses a single feature parameter, so `registers` is empty and
+/// each level is just its index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryTable {
+    pub vendor: RetryVendor,
+    pub registers: Vec<u8>,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl RetryTable {
+    /// Built-in table for a vendor
+    ///
+    /// Returns `None` for Hynix, whose table must be read from the chip's
+    /// OTP area (see `hynix_otp_sequence` and `RetryTable::from_hynix_otp`).
+    pub fn for_vendor(vendor: RetryVendor) -> Option<Self> {
+        let (registers, levels): (Vec<u8>, Vec<Vec<u8>>) = match vendor {
+            RetryVendor::Hynix => return None,
+            RetryVendor::Micron => return Some(Self::micron(MICRON_DEFAULT_LEVELS)),
+            RetryVendor::Toshiba => (
+                TOSHIBA_REGS.to_vec(),
+                TOSHIBA_LEVELS.iter().map(|l| l.to_vec()).collect(),
+            ),
+            RetryVendor::Samsung => (
+                SAMSUNG_REGS.to_vec(),
+                SAMSUNG_LEVELS.iter().map(|l| l.to_vec()).collect(),
+            ),
+            RetryVendor::SanDisk => (
+                SANDISK_REGS.to_vec(),
+                SANDISK_LEVELS.iter().map(|l| l.to_vec()).collect(),
+            ),
+        };
+
+        Some(Self {
+            vendor,
+            registers,
+            levels,
+        })
+    }
+
+    /// Micron table with `level_count` levels (from the ONFI vendor block)
+    pub fn micron(level_count: u8) -> Self {
+        Self {
+            vendor: RetryVendor::Micron,
+            registers: Vec::new(),
+            levels: (0..level_count.max(1)).map(|l| vec![l]).collect(),
+        }
+    }
+
+    /// Parse a Hynix retry table read from OTP
+    ///
+    /// Layout: `[set_count, reg_count]` followed by 8 redundant copies of
+    /// `set_count * reg_count` values and their bitwise inverse. The first
+    /// copy whose inverse checks out is used.
+    ///
+    /// # Arguments
+    /// * `data` - Raw bytes returned by the OTP read sequence
+    ///
+    /// # Returns
+    /// * `Some(RetryTable)` if a consistent copy was found
+    pub fn from_hynix_otp(data: &[u8]) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+
+        let set_count = data[0] as usize;
+        let reg_count = data[1] as usize;
+        let registers: Vec<u8> = match reg_count {
+            8 => HYNIX_REGS_20NM.to_vec(),
+            4 => HYNIX_REGS_1XNM.to_vec(),
+            _ => return None,
+        };
+        if set_count == 0 {
+            return None;
+        }
+
+        let table_len = set_count * reg_count;
+        for copy in data[2..].chunks_exact(table_len * 2).take(HYNIX_OTP_COPIES) {
+            let (values, inverse) = copy.split_at(table_len);
+            if values.iter().zip(inverse).all(|(v, i)| v ^ i == 0xFF) {
+                return Some(Self {
+                    vendor: RetryVendor::Hynix,
+                    registers,
+                    levels: values.chunks(reg_count).map(|s| s.to_vec()).collect(),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Number of levels including the default (level 0)
+    pub fn level_count(&self) -> u8 {
+        self.levels.len().min(u8::MAX as usize) as u8
+    }
+
+    /// Packets that switch the chip to `level`
+    pub fn level_packets(&self, level: u8) -> Vec<Packet> {
+        let values = match self.levels.get(level as usize) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        let mut packets = Vec::new();
+        match self.vendor {
+            RetryVendor::Micron => {
+                packets.push(cmd(nand_commands::SET_FEATURES));
+                packets.push(addr(commands::MICRON_FEATURE_READ_RETRY));
+                packets.push(data(&[values[0], 0, 0, 0]));
+            }
+            RetryVendor::Hynix => {
+                packets.push(cmd(commands::HYNIX_SET_PARAM));
+                for (reg, val) in self.registers.iter().zip(values) {
+                    packets.push(addr(*reg));
+                    packets.push(data(&[*val]));
+                }
+                packets.push(cmd(commands::HYNIX_COMMIT));
+            }
+            RetryVendor::Toshiba => {
+                packets.push(cmd(commands::TOSHIBA_PRE_1));
+                packets.push(cmd(commands::TOSHIBA_PRE_2));
+                for (reg, val) in self.registers.iter().zip(values) {
+                    packets.push(cmd(commands::TOSHIBA_SET_PARAM));
+                    packets.push(addr(*reg));
+                    packets.push(data(&[*val]));
+                }
+                if level != 0 {
+                    packets.push(cmd(commands::TOSHIBA_ENABLE_1));
+                    packets.push(cmd(commands::TOSHIBA_ENABLE_2));
+                }
+            }
+            RetryVendor::Samsung => {
+                for (reg, val) in self.registers.iter().zip(values) {
+                    packets.push(cmd(commands::SAMSUNG_SET_PARAM));
+                    packets.push(addr(0x00));
+                    packets.push(addr(*reg));
+                    packets.push(data(&[*val]));
+                }
+            }
+            RetryVendor::SanDisk => {
+                packets.push(cmd(commands::SANDISK_PRE_1));
+                packets.push(cmd(commands::SANDISK_PRE_2));
+                for (reg, val) in self.registers.iter().zip(values) {
+                    packets.push(cmd(commands::SANDISK_SET_PARAM));
+                    packets.push(addr(*reg));
+                    packets.push(data(&[*val]));
+                }
+            }
+        }
+
+        // Toshiba leaves retry mode only through a reset
+        if self.vendor == RetryVendor::Toshiba && level == 0 {
+            packets.push(cmd(nand_commands::RESET));
+        }
+
+        packets
+    }
+}
+
+/// Number of redundant table copies in Hynix OTP
+const HYNIX_OTP_COPIES: usize = 8;
+
+/// Packet sequence that reads the Hynix retry table from OTP
+#[derive(Debug, Clone)]
+pub struct HynixOtpSequence {
+    /// Packets that enter OTP mode and load the table page
+    pub setup: Vec<Packet>,
+    /// `NandReadData` packet whose response is the raw table
+    pub read: Packet,
+    /// Packets that leave OTP mode
+    pub teardown: Vec<Packet>,
+}
+
+/// Build the Hynix OTP table read sequence
+///
+/// # Arguments
+/// * `reg_count` - Registers per set (8 for 20nm, 4 for 1xnm parts)
+/// * `set_count` - Retry sets stored in OTP (usually 8)
+pub fn hynix_otp_sequence(reg_count: u8, set_count: u8) -> HynixOtpSequence {
+    let mut setup = vec![
+        cmd(nand_commands::RESET),
+        cmd(commands::HYNIX_SET_PARAM),
+        addr(0xFF),
+        data(&[0x40]),
+        addr(0xCC),
+        data(&[0x4D]),
+        cmd(commands::HYNIX_COMMIT),
+    ];
+    setup.extend(commands::HYNIX_OTP_ENTER.iter().map(|c| cmd(*c)));
+    setup.extend([0x00, 0x00, 0x00, 0x02, 0x00].iter().map(|a| addr(*a)));
+    setup.push(cmd(nand_commands::READ2));
+
+    let len = 2 + reg_count as usize * set_count as usize * 2 * HYNIX_OTP_COPIES;
+    let len = (len as u16).to_le_bytes();
+
+    HynixOtpSequence {
+        setup,
+        read: Packet::new(Command::NandReadData, &len),
+        teardown: vec![cmd(nand_commands::RESET), cmd(commands::HYNIX_OTP_EXIT)],
+    }
+}
+
+fn cmd(opcode: u8) -> Packet {
+    Packet::new(Command::NandCmd, &[opcode])
+}
+
+fn addr(cycle: u8) -> Packet {
+    Packet::new(Command::NandAddr, &[cycle])
+}
+
+fn data(bytes: &[u8]) -> Packet {
+    let mut args = Vec::with_capacity(bytes.len() + 1);
+    args.push(bytes.len() as u8);
+    args.extend_from_slice(bytes);
+    Packet::new(Command::NandWriteData, &args)
+}
+
+// ============================================================================
+// Retry Engine
+// ============================================================================
+
+/// Outcome of submitting a page read to the engine
+#[derive(Debug, Clone)]
+pub enum RetryStep {
+    /// ECC decoded at `level`
+    Recovered {
+        data: Vec<u8>,
+        level: u8,
+        corrected_bits: u32,
+    },
+    /// Send `packets`, re-read the page and submit again
+    Retry { level: u8, packets: Vec<Packet> },
+    /// Every level failed to decode
+    Exhausted { levels_tried: u8 },
+}
+
+/// Read-retry counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetryStats {
+    pub pages_clean: u64,
+    pub pages_recovered: u64,
+    pub pages_failed: u64,
+    pub retries_issued: u64,
+}
+
+/// Per-page read-retry state machine
+#[derive(Debug, Clone)]
+pub struct ReadRetryEngine {
+    table: RetryTable,
+    algorithm: EccAlgorithm,
+    pages_per_block: u32,
+    /// Level currently programmed into the chip
+    active_level: u8,
+    /// Level that last decoded each block
+    block_levels: HashMap<u32, u8>,
+    current_page: Option<u32>,
+    start_level: u8,
+    attempts: u8,
+    stats: RetryStats,
+}
+
+impl ReadRetryEngine {
+    pub fn new(table: RetryTable, algorithm: EccAlgorithm, pages_per_block: u32) -> Self {
+        Self {
+            table,
+            algorithm,
+            pages_per_block: pages_per_block.max(1),
+            active_level: 0,
+            block_levels: HashMap::new(),
+            current_page: None,
+            start_level: 0,
+            attempts: 0,
+            stats: RetryStats::default(),
+        }
+    }
+
+    /// Start reading `page`
+    ///
+    /// Blocks that previously needed a retry start at the level that worked.
+    /// Returns packets to send before the first read (may be empty).
+    pub fn begin(&mut self, page: u32) -> Vec<Packet> {
+        let block = page / self.pages_per_block;
+        self.current_page = Some(page);
+        self.start_level = self.block_levels.get(&block).copied().unwrap_or(0);
+        self.attempts = 0;
+        self.switch_to(self.start_level)
+    }
+
+    /// Submit the page read at the active level
+    ///
+    /// # Arguments
+    /// * `data` - Page main area as read
+    /// * `ecc` - ECC bytes for `data` from the spare area
+    pub fn submit(&mut self, data: &[u8], ecc: &[u8]) -> RetryStep {
+        let page = match self.current_page {
+            Some(p) => p,
+            None => return RetryStep::Exhausted { levels_tried: 0 },
+        };
+
+        let mut buf = data.to_vec();
+        self.attempts = self.attempts.saturating_add(1);
+
+        if let Ok(corrected_bits) = decode_with_ecc(&mut buf, ecc, &self.algorithm) {
+            let level = self.active_level;
+            let block = page / self.pages_per_block;
+            if level == 0 {
+                self.block_levels.remove(&block);
+            } else {
+                self.block_levels.insert(block, level);
+            }
+            if self.attempts == 1 && level == 0 {
+                self.stats.pages_clean += 1;
+            } else {
+                self.stats.pages_recovered += 1;
+            }
+            self.current_page = None;
+            return RetryStep::Recovered {
+                data: buf,
+                level,
+                corrected_bits,
+            };
+        }
+
+        let level_count = self.table.level_count();
+        if self.attempts >= level_count {
+            self.stats.pages_failed += 1;
+            self.current_page = None;
+            return RetryStep::Exhausted {
+                levels_tried: self.attempts,
+            };
+        }
+
+        let level = ((self.start_level as u16 + self.attempts as u16) % level_count as u16) as u8;
+        self.stats.retries_issued += 1;
+        RetryStep::Retry {
+            level,
+            packets: self.switch_to(level),
+        }
+    }
+
+    /// Restore default read thresholds
+    ///
+    /// Must be sent once the caller is done with the chip, including after
+    /// errors, so later reads are not taken at a shifted level.
+    pub fn finish(&mut self) -> Vec<Packet> {
+        self.current_page = None;
+        self.switch_to(0)
+    }
+
+    /// Level currently programmed into the chip
+    pub fn active_level(&self) -> u8 {
+        self.active_level
+    }
+
+    /// Level that last decoded `block`, if it needed a retry
+    pub fn block_level(&self, block: u32) -> Option<u8> {
+        self.block_levels.get(&block).copied()
+    }
+
+    /// Blocks that needed a retry and the level that worked
+    pub fn block_levels(&self) -> &HashMap<u32, u8> {
+        &self.block_levels
+    }
+
+    pub fn stats(&self) -> &RetryStats {
+        &self.stats
+    }
+
+    pub fn table(&self) -> &RetryTable {
+        &self.table
+    }
+
+    pub fn pages_per_block(&self) -> u32 {
+        self.pages_per_block
+    }
+
+    fn switch_to(&mut self, level: u8) -> Vec<Packet> {
+        if level == self.active_level {
+            return Vec::new();
+        }
+        self.active_level = level;
+        self.table.level_packets(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::encode_with_ecc;
+
+    /// Simulated chip: a page reads cleanly only at `good_level`
+    struct MockChip {
+        level: u8,
+        good_level: u8,
+        page: Vec<u8>,
+        ecc: Vec<u8>,
+    }
+
+    impl MockChip {
+        fn new(good_level: u8) -> Self {
+            let page: Vec<u8> = (0..512).map(|i| (i * 7) as u8).collect();
+            let (_, ecc) = encode_with_ecc(&page, &EccAlgorithm::Hamming);
+            Self {
+                level: 0,
+                good_level,
+                page,
+                ecc,
+            }
+        }
+
+        fn apply(&mut self, table: &RetryTable, packets: &[Packet]) {
+            for level in 0..table.level_count() {
+                if !packets.is_empty() && table.level_packets(level).len() == packets.len() {
+                    let expected: Vec<_> = table
+                        .level_packets(level)
+                        .iter()
+                        .map(|p| p.to_bytes())
+                        .collect();
+                    let got: Vec<_> = packets.iter().map(|p| p.to_bytes()).collect();
+                    if expected == got {
+                        self.level = level;
+                        return;
+                    }
+                }
+            }
+        }
+
+        fn read(&self) -> Vec<u8> {
+            let mut data = self.page.clone();
+            if self.level != self.good_level {
+                // Two flipped bits in one sector defeat Hamming
+                data[0] ^= 0x01;
+                data[1] ^= 0x01;
+            }
+            data
+        }
+    }
+
+    fn run(engine: &mut ReadRetryEngine, chip: &mut MockChip, page: u32) -> RetryStep {
+        let table = engine.table().clone();
+        let packets = engine.begin(page);
+        chip.apply(&table, &packets);
+        loop {
+            match engine.submit(&chip.read(), &chip.ecc.clone()) {
+                RetryStep::Retry { packets, .. } => chip.apply(&table, &packets),
+                step => return step,
+            }
+        }
+    }
+
+    #[test]
+    fn test_vendor_from_manufacturer() {
+        assert_eq!(
+            RetryVendor::from_manufacturer_id(0xAD),
+            Some(RetryVendor::Hynix)
+        );
+        assert_eq!(
+            RetryVendor::from_manufacturer_id(0x45),
+            Some(RetryVendor::SanDisk)
+        );
+        assert_eq!(RetryVendor::from_manufacturer_id(0xC2), None);
+    }
+
+    #[test]
+    fn test_micron_level_packets() {
+        let table = RetryTable::micron(8);
+        let packets = table.level_packets(3);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].cmd, Command::NandCmd);
+        assert_eq!(packets[0].args[0], nand_commands::SET_FEATURES);
+        assert_eq!(packets[1].cmd, Command::NandAddr);
+        assert_eq!(packets[1].args[0], commands::MICRON_FEATURE_READ_RETRY);
+        assert_eq!(packets[2].cmd, Command::NandWriteData);
+        assert_eq!(&packets[2].args[..5], &[4, 3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hynix_otp_parse() {
+        let values: Vec<u8> = (0..64).collect();
+        let mut otp = vec![8, 8];
+        // First copy corrupt, second valid
+        otp.extend(vec![0u8; 128]);
+        otp.extend(&values);
+        otp.extend(values.iter().map(|v| !v));
+
+        let table = RetryTable::from_hynix_otp(&otp).unwrap();
+        assert_eq!(table.level_count(), 8);
+        assert_eq!(table.registers, HYNIX_REGS_20NM.to_vec());
+        assert_eq!(table.levels[1], (8..16).collect::<Vec<u8>>());
+
+        assert!(RetryTable::from_hynix_otp(&[8, 8, 0, 0]).is_none());
+        assert!(RetryTable::from_hynix_otp(&[8, 5]).is_none());
+    }
+
+    #[test]
+    fn test_hynix_otp_sequence_length() {
+        let seq = hynix_otp_sequence(8, 8);
+        assert_eq!(seq.read.cmd, Command::NandReadData);
+        let len = u16::from_le_bytes([seq.read.args[0], seq.read.args[1]]);
+        assert_eq!(len, 2 + 8 * 8 * 2 * 8);
+        assert_eq!(
+            seq.teardown.last().unwrap().args[0],
+            commands::HYNIX_OTP_EXIT
+        );
+    }
+
+    #[test]
+    fn test_clean_read_needs_no_retry() {
+        let mut engine = ReadRetryEngine::new(RetryTable::micron(8), EccAlgorithm::Hamming, 64);
+        let mut chip = MockChip::new(0);
+
+        match run(&mut engine, &mut chip, 10) {
+            RetryStep::Recovered { level, .. } => assert_eq!(level, 0),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(engine.stats().pages_clean, 1);
+        assert!(engine.block_levels().is_empty());
+    }
+
+    #[test]
+    fn test_retry_recovers_and_records_level() {
+        for vendor in [
+            RetryVendor::Micron,
+            RetryVendor::Toshiba,
+            RetryVendor::Samsung,
+            RetryVendor::SanDisk,
+        ] {
+            let table = RetryTable::for_vendor(vendor).unwrap();
+            let mut engine = ReadRetryEngine::new(table, EccAlgorithm::Hamming, 64);
+            let mut chip = MockChip::new(5);
+
+            match run(&mut engine, &mut chip, 130) {
+                RetryStep::Recovered { data, level, .. } => {
+                    assert_eq!(level, 5);
+                    assert_eq!(data, chip.page);
+                }
+                other => panic!("{:?}: unexpected {:?}", vendor, other),
+            }
+            assert_eq!(engine.block_level(2), Some(5));
+            assert_eq!(engine.stats().retries_issued, 5);
+
+            // Next page in the same block starts at the recorded level
+            assert!(engine.begin(131).is_empty());
+            assert_eq!(engine.active_level(), 5);
+
+            let restore = engine.finish();
+            chip.apply(engine.table(), &restore);
+            assert_eq!(engine.active_level(), 0);
+            assert_eq!(chip.level, 0);
+        }
+    }
+
+    #[test]
+    fn test_retry_exhausted() {
+        let mut engine = ReadRetryEngine::new(RetryTable::micron(4), EccAlgorithm::Hamming, 64);
+        let mut chip = MockChip::new(9);
+
+        match run(&mut engine, &mut chip, 0) {
+            RetryStep::Exhausted { levels_tried } => assert_eq!(levels_tried, 4),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(engine.stats().pages_failed, 1);
+        assert!(!engine.finish().is_empty());
+        assert_eq!(engine.active_level(), 0);
+    }
+
+    #[test]
+    fn test_toshiba_default_resets() {
+        let table = RetryTable::for_vendor(RetryVendor::Toshiba).unwrap();
+        let packets = table.level_packets(0);
+        assert_eq!(packets.last().unwrap().args[0], nand_commands::RESET);
+        assert!(RetryTable::for_vendor(RetryVendor::Hynix).is_none());
+    }
+}
@@ -0,0 +1,1549 @@
+//! REST API front end for the OpenFlash server
+//!
+//! Serves [`OpenFlashServer`](crate::server::OpenFlashServer) over HTTP/1.1
+//! (optionally TLS) and enforces everything described by
+//! [`RestApiConfig`]: the route prefix, API-key, bearer or basic
+//! authentication, per-client token-bucket rate limiting and CORS. Request
+//! handling lives in [`RestApi::handle`], which works on plain
+//! [`ApiRequest`]/[`ApiResponse`] values so it can be exercised without
+//! sockets; [`RestServer`] is the thin hyper/tokio transport.
+//!
+//! Endpoints (relative to the prefix):
+//! - `GET /info` - [`ServerInfo`]
+//! - `GET /devices` - [`DeviceListResponse`]
+//! - `POST /jobs` - [`SubmitJobRequest`] -> [`SubmitJobResponse`]
+//! - `GET /jobs/{id}`, `DELETE /jobs/{id}` - [`JobStatusResponse`]
+//! - `GET /artifacts/{path}` - file download from the artifact directory
+//! - `GET /access-log` - [`AccessLogResponse`] (see [`access`](crate::access))
+//! - `GET /openapi.json` - OpenAPI 3 document (never requires auth)
+//!
+//! `POST`, `PUT` and `DELETE` requests must carry `Content-Type:
+//! application/json` (415 otherwise), so a browser can only send them
+//! cross-origin after a CORS preflight.
+//!
+//! When WebSocket support is enabled, `WebSocketConfig.path` (outside the
+//! prefix) upgrades to the live [`event_stream`](crate::event_stream) after
+//! the same origin, authentication and rate-limit checks.
+//!
+//! Only available with the `rest-server` feature.
+
+use crate::access::{
+    AccessConfig, AccessLogResponse, Credentials, Permission, Principal, RateLimiter,
+};
+use crate::event_stream::EventHub;
+use crate::server::{
+    lock_server, resolve_artifact, AuthMethod, DeviceListResponse, JobStatusResponse,
+    RestApiConfig, ServerError, ServerInfo, SharedServer, SubmitJobRequest, SubmitJobResponse,
+};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Body, Bytes, Frame, Incoming, SizeHint};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::BufReader;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Transport limits
+pub mod limits {
+    /// Largest accepted request body
+    pub const MAX_BODY_BYTES: usize = 1024 * 1024;
+    /// Chunk size used when streaming artifacts
+    pub const FILE_CHUNK_SIZE: usize = 64 * 1024;
+    /// Back-off after a failed `accept()` (e.g. out of file descriptors)
+    pub const ACCEPT_BACKOFF_MS: u64 = 100;
+}
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// REST server start-up errors
+#[derive(Debug)]
+pub enum RestError {
+    /// Socket or file I/O error
+    Io(std::io::Error),
+    /// Certificate or key could not be loaded
+    Tls(String),
+    /// `RestApiConfig` cannot be served as configured
+    InvalidConfig(String),
+}
+
+impl std::fmt::Display for RestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Tls(s) => write!(f, "TLS error: {}", s),
+            Self::InvalidConfig(s) => write!(f, "Invalid REST configuration: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for RestError {}
+
+impl From<std::io::Error> for RestError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+pub type RestResult<T> = Result<T, RestError>;
+
+/// JSON body of every non-2xx response
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiErrorBody {
+    /// HTTP status code
+    pub status: u16,
+    /// Human readable error
+    pub error: String,
+}
+
+// ============================================================================
+// Requests and Responses
+// ============================================================================
+
+/// Transport independent HTTP request
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    /// Upper-case method (`GET`, `POST`, ...)
+    pub method: String,
+    /// Request target, optionally with a query string
+    pub path: String,
+    /// Headers with lower-case names
+    pub headers: Vec<(String, String)>,
+    /// Request body
+    pub body: Vec<u8>,
+    /// Remote address, used as the rate-limit key for anonymous clients
+    pub peer: Option<IpAddr>,
+}
+
+impl ApiRequest {
+    pub fn new(method: &str, path: &str) -> Self {
+        Self {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            peer: None,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn with_peer(mut self, peer: IpAddr) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    /// First header with the given (case-insensitive) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Response payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseBody {
+    /// In-memory body
+    Bytes(Vec<u8>),
+    /// File streamed from disk by the transport
+    File { path: PathBuf, len: u64 },
+}
+
+/// Transport independent HTTP response
+#[derive(Debug, Clone)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+}
+
+impl ApiResponse {
+    /// Empty response
+    pub fn empty(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: ResponseBody::Bytes(Vec::new()),
+        }
+    }
+
+    /// JSON response
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self {
+                status,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: ResponseBody::Bytes(body),
+            },
+            Err(e) => Self::error(500, &format!("serialization failed: {}", e)),
+        }
+    }
+
+    /// [`ApiErrorBody`] response
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(
+            status,
+            &ApiErrorBody {
+                status,
+                error: message.to_string(),
+            },
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    /// First header with the given (case-insensitive) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// In-memory body, empty for streamed files
+    pub fn body_bytes(&self) -> &[u8] {
+        match &self.body {
+            ResponseBody::Bytes(b) => b,
+            ResponseBody::File { .. } => &[],
+        }
+    }
+}
+
+/// HTTP status for a server-side error
+fn status_for(err: &ServerError) -> u16 {
+    match err {
+        ServerError::DeviceNotFound(_) | ServerError::JobNotFound(_) => 404,
+        ServerError::DeviceBusy(_) | ServerError::JobFailed { .. } => 409,
+        ServerError::InvalidRequest(_) | ServerError::InvalidConfig(_) => 400,
+        ServerError::AuthFailed(_) => 401,
+        ServerError::PermissionDenied(_) => 403,
+        ServerError::RateLimitExceeded => 429,
+        ServerError::QueueFull | ServerError::DeviceOffline(_) => 503,
+        ServerError::Timeout(_) => 504,
+        ServerError::ConnectionFailed(_) => 502,
+        ServerError::InternalError(_) => 500,
+    }
+}
+
+// ============================================================================
+// OpenAPI
+// ============================================================================
+
+/// Build the OpenAPI 3 document for `config`
+///
+/// Component schemas are derived from the API types, so the document stays
+/// in sync with what the handlers actually (de)serialize.
+pub fn openapi_document(config: &RestApiConfig, version: &str) -> serde_json::Value {
+    use serde_json::json;
+
+    let mut gen = schemars::gen::SchemaSettings::openapi3().into_generator();
+    let submit_request = gen.subschema_for::<SubmitJobRequest>();
+    let submit_response = gen.subschema_for::<SubmitJobResponse>();
+    let job_status = gen.subschema_for::<JobStatusResponse>();
+    let devices = gen.subschema_for::<DeviceListResponse>();
+    let info = gen.subschema_for::<ServerInfo>();
+    let error = gen.subschema_for::<ApiErrorBody>();
+    let access_log = gen.subschema_for::<AccessLogResponse>();
+    let schemas: serde_json::Map<String, serde_json::Value> = gen
+        .take_definitions()
+        .into_iter()
+        .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or_default()))
+        .collect();
+
+    let json_body = |schema: &schemars::schema::Schema| json!({ "content": { "application/json": { "schema": schema } } });
+    let error_response = |description: &str| {
+        let mut body = json_body(&error);
+        body["description"] = json!(description);
+        body
+    };
+    let with_description = |mut body: serde_json::Value, description: &str| {
+        body["description"] = json!(description);
+        body
+    };
+    let job_id_param = json!([{
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "uint64", "minimum": 0 }
+    }]);
+
+    let prefix = normalized_prefix(&config.prefix);
+    let mut paths = serde_json::Map::new();
+    paths.insert(
+        format!("{}/info", prefix),
+        json!({ "get": {
+            "operationId": "getServerInfo",
+            "summary": "Server, pool and queue statistics",
+            "responses": { "200": with_description(json_body(&info), "Server information") }
+        }}),
+    );
+    paths.insert(
+        format!("{}/devices", prefix),
+        json!({ "get": {
+            "operationId": "listDevices",
+            "summary": "Devices registered in the pool",
+            "responses": { "200": with_description(json_body(&devices), "Device list") }
+        }}),
+    );
+    paths.insert(
+        format!("{}/jobs", prefix),
+        json!({ "post": {
+            "operationId": "submitJob",
+            "summary": "Queue a job",
+            "requestBody": { "required": true, "content": { "application/json": { "schema": submit_request } } },
+            "responses": {
+                "201": with_description(json_body(&submit_response), "Job queued"),
+                "400": error_response("Malformed job request"),
+                "403": error_response("Role may not run this job"),
+                "503": error_response("Job queue is full")
+            }
+        }}),
+    );
+    paths.insert(
+        format!("{}/jobs/{{id}}", prefix),
+        json!({
+            "get": {
+                "operationId": "getJob",
+                "summary": "Job status",
+                "parameters": job_id_param,
+                "responses": {
+                    "200": with_description(json_body(&job_status), "Job status"),
+                    "404": error_response("Unknown job")
+                }
+            },
+            "delete": {
+                "operationId": "cancelJob",
+                "summary": "Cancel a queued or running job",
+                "parameters": job_id_param,
+                "responses": {
+                    "200": with_description(json_body(&job_status), "Job cancelled"),
+                    "403": error_response("Role may not cancel this job"),
+                    "404": error_response("Unknown job"),
+                    "409": error_response("Job already finished")
+                }
+            }
+        }),
+    );
+    paths.insert(
+        format!("{}/artifacts/{{path}}", prefix),
+        json!({ "get": {
+            "operationId": "downloadArtifact",
+            "summary": "Download a file from the artifact directory",
+            "parameters": [{
+                "name": "path",
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" }
+            }],
+            "responses": {
+                "200": {
+                    "description": "Artifact contents",
+                    "content": { "application/octet-stream": {
+                        "schema": { "type": "string", "format": "binary" }
+                    } }
+                },
+                "403": error_response("Role may not access artifacts"),
+                "404": error_response("Unknown artifact")
+            }
+        }}),
+    );
+    paths.insert(
+        format!("{}/access-log", prefix),
+        json!({ "get": {
+            "operationId": "getAccessLog",
+            "summary": "Hash-chained record of job decisions and runs",
+            "responses": {
+                "200": with_description(json_body(&access_log), "Access log"),
+                "400": error_response("Access control is not enabled"),
+                "403": error_response("Role may not read the access log")
+            }
+        }}),
+    );
+    paths.insert(
+        format!("{}/openapi.json", prefix),
+        json!({ "get": {
+            "operationId": "getOpenApi",
+            "summary": "This document",
+            "security": [],
+            "responses": { "200": { "description": "OpenAPI document" } }
+        }}),
+    );
+
+    let mut components = json!({ "schemas": schemas });
+    let mut doc = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "OpenFlash Server API",
+            "version": version
+        },
+        "paths": paths,
+    });
+    let scheme = match &config.auth {
+        AuthMethod::None => None,
+        AuthMethod::ApiKey { header_name } => Some(json!({
+            "type": "apiKey",
+            "in": "header",
+            "name": header_name
+        })),
+        AuthMethod::BearerToken => Some(json!({ "type": "http", "scheme": "bearer" })),
+        AuthMethod::BasicAuth => Some(json!({ "type": "http", "scheme": "basic" })),
+    };
+    if let Some(scheme) = scheme {
+        components["securitySchemes"] = json!({ "openflash": scheme });
+        doc["security"] = json!([{ "openflash": [] }]);
+    }
+    doc["components"] = components;
+    doc
+}
+
+/// Prefix with a leading and without a trailing slash (`""` for the root)
+fn normalized_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+// ============================================================================
+// Request Handling
+// ============================================================================
+
+/// Request handler enforcing a [`RestApiConfig`] in front of a shared
+/// [`OpenFlashServer`](crate::server::OpenFlashServer)
+pub struct RestApi {
+    server: SharedServer,
+    config: RestApiConfig,
+    prefix: String,
+    credentials: Credentials,
+    access: AccessConfig,
+    artifact_root: Option<PathBuf>,
+    events: Option<EventHub>,
+    limiter: Mutex<RateLimiter>,
+    openapi: Vec<u8>,
+}
+
+impl std::fmt::Debug for RestApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RestApi")
+            .field("config", &self.config)
+            .field("credentials", &self.credentials)
+            .field("access_control", &self.access.enabled)
+            .field("artifact_root", &self.artifact_root)
+            .field("events", &self.events)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RestApi {
+    /// Serve `server` using its own `config.rest`, with the event stream
+    /// enabled if `config.websocket` asks for it
+    pub fn new(server: SharedServer) -> Self {
+        let (config, websocket) = {
+            let server = lock_server(&server);
+            (server.config.rest.clone(), server.config.websocket.clone())
+        };
+        let api = Self::with_server_config(server, config);
+        if websocket.enabled {
+            api.with_events(EventHub::new(websocket))
+        } else {
+            api
+        }
+    }
+
+    fn with_server_config(server: SharedServer, config: RestApiConfig) -> Self {
+        let (version, access) = {
+            let server = lock_server(&server);
+            (server.version.clone(), server.config.access.clone())
+        };
+        Self {
+            prefix: normalized_prefix(&config.prefix),
+            limiter: Mutex::new(RateLimiter::new(&config.rate_limit)),
+            openapi: serde_json::to_vec_pretty(&openapi_document(&config, &version))
+                .unwrap_or_default(),
+            server,
+            config,
+            credentials: Credentials::default(),
+            access,
+            artifact_root: None,
+            events: None,
+        }
+    }
+
+    /// Replace the REST configuration
+    pub fn with_config(self, config: RestApiConfig) -> Self {
+        Self {
+            credentials: self.credentials,
+            artifact_root: self.artifact_root,
+            events: self.events,
+            ..Self::with_server_config(self.server, config)
+        }
+    }
+
+    /// Accept the secrets of `credentials`, replacing those given so far
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Accept an API key (for [`AuthMethod::ApiKey`])
+    pub fn with_api_key(mut self, key: &str) -> Self {
+        self.credentials = self.credentials.with_api_key(key);
+        self
+    }
+
+    /// Accept a bearer token (for [`AuthMethod::BearerToken`])
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.credentials = self.credentials.with_bearer_token(token);
+        self
+    }
+
+    /// Accept a user/password pair (for [`AuthMethod::BasicAuth`])
+    pub fn with_basic_user(mut self, user: &str, password: &str) -> Self {
+        self.credentials = self.credentials.with_basic_user(user, password);
+        self
+    }
+
+    /// Directory served under `/artifacts`; downloads are disabled without it
+    pub fn with_artifact_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.artifact_root = Some(root.into());
+        self
+    }
+
+    /// Serve the WebSocket event stream from `hub`, replacing any hub set
+    /// up by [`RestApi::new`]
+    pub fn with_events(mut self, hub: EventHub) -> Self {
+        hub.attach(&mut lock_server(&self.server));
+        self.events = Some(hub);
+        self
+    }
+
+    pub fn config(&self) -> &RestApiConfig {
+        &self.config
+    }
+
+    /// Event stream, if enabled
+    pub fn events(&self) -> Option<&EventHub> {
+        self.events.as_ref()
+    }
+
+    pub fn server(&self) -> &SharedServer {
+        &self.server
+    }
+
+    /// Reject configurations that cannot be served safely
+    pub fn validate(&self) -> RestResult<()> {
+        let has_users = self.access.enabled && !self.access.users.is_empty();
+        if !(self.credentials.supports(&self.config.auth) || has_users) {
+            return Err(RestError::InvalidConfig(format!(
+                "{:?} authentication requires at least one credential",
+                self.config.auth
+            )));
+        }
+        if let AuthMethod::ApiKey { header_name } = &self.config.auth {
+            if header_name.is_empty() {
+                return Err(RestError::InvalidConfig(
+                    "API key header name is empty".to_string(),
+                ));
+            }
+        }
+        if self.config.https && (self.config.cert_path.is_none() || self.config.key_path.is_none())
+        {
+            return Err(RestError::InvalidConfig(
+                "HTTPS requires cert_path and key_path".to_string(),
+            ));
+        }
+        if let Some(root) = &self.artifact_root {
+            if !root.is_dir() {
+                return Err(RestError::InvalidConfig(format!(
+                    "artifact directory {} does not exist",
+                    root.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one request
+    pub fn handle(&self, req: &ApiRequest) -> ApiResponse {
+        let path = req.path.split('?').next().unwrap_or_default();
+        let route = if self.prefix.is_empty() {
+            Some(path)
+        } else {
+            path.strip_prefix(self.prefix.as_str())
+                .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        let route = match route {
+            Some(route) => route.trim_end_matches('/'),
+            None => return ApiResponse::error(404, "not found"),
+        };
+
+        let origin = req.header("origin");
+        let allowed_origin = origin.and_then(|o| self.allowed_origin(o));
+        if origin.is_some() && allowed_origin.is_none() {
+            return ApiResponse::error(403, "origin not allowed");
+        }
+
+        let response = if req.method == "OPTIONS" {
+            self.preflight()
+        } else if matches!(req.method.as_str(), "POST" | "PUT" | "DELETE") && !is_json(req) {
+            // Browsers cannot send this cross-origin without a preflight
+            ApiResponse::error(415, "content-type must be application/json")
+        } else if route == "/openapi.json" && req.method == "GET" {
+            ApiResponse {
+                status: 200,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: ResponseBody::Bytes(self.openapi.clone()),
+            }
+        } else {
+            match self.admit(req) {
+                Ok(principal) => self.route(req, route, &principal),
+                Err(response) => response,
+            }
+        };
+
+        match allowed_origin {
+            Some(origin) => {
+                let mut response = response.with_header("access-control-allow-origin", &origin);
+                if origin != "*" {
+                    response = response.with_header("vary", "Origin");
+                }
+                response
+            }
+            None => response,
+        }
+    }
+
+    /// Origin, authentication and rate-limit checks for a WebSocket upgrade
+    pub fn admit_websocket(&self, req: &ApiRequest) -> Result<(), ApiResponse> {
+        if let Some(origin) = req.header("origin") {
+            if self.allowed_origin(origin).is_none() {
+                return Err(ApiResponse::error(403, "origin not allowed"));
+            }
+        }
+        self.admit(req).map(|_| ())
+    }
+
+    fn allowed_origin(&self, origin: &str) -> Option<String> {
+        let origins = &self.config.cors_origins;
+        if origins.iter().any(|o| o == "*") {
+            Some("*".to_string())
+        } else if origins.iter().any(|o| o.eq_ignore_ascii_case(origin)) {
+            Some(origin.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn preflight(&self) -> ApiResponse {
+        let mut allow_headers = "authorization, content-type".to_string();
+        if let AuthMethod::ApiKey { header_name } = &self.config.auth {
+            allow_headers.push_str(", ");
+            allow_headers.push_str(&header_name.to_ascii_lowercase());
+        }
+        ApiResponse::empty(204)
+            .with_header("access-control-allow-methods", "GET, POST, DELETE, OPTIONS")
+            .with_header("access-control-allow-headers", &allow_headers)
+            .with_header("access-control-max-age", "600")
+    }
+
+    /// Rate-limit the peer, then authenticate and rate-limit the principal
+    ///
+    /// The peer's bucket is charged before any credential is looked at, so
+    /// guessing keys or passwords is throttled like any other request.
+    fn admit(&self, req: &ApiRequest) -> Result<Principal, ApiResponse> {
+        let peer = peer_id(req);
+        self.rate_limit(&peer)?;
+        let principal = self.authenticate(req)?;
+        if principal.id != peer {
+            self.rate_limit(&principal.id)?;
+        }
+        Ok(principal)
+    }
+
+    /// Identify the client, see [`Credentials::authenticate`]
+    fn authenticate(&self, req: &ApiRequest) -> Result<Principal, ApiResponse> {
+        let header = |name: &str| req.header(name);
+        self.credentials
+            .authenticate(&self.access, &self.config.auth, header, &peer_id(req))
+            .ok_or_else(|| match &self.config.auth {
+                AuthMethod::ApiKey { .. } => ApiResponse::error(401, "missing or invalid API key"),
+                AuthMethod::BearerToken => {
+                    ApiResponse::error(401, "missing or invalid bearer token")
+                        .with_header("www-authenticate", "Bearer realm=\"OpenFlash\"")
+                }
+                _ => ApiResponse::error(401, "missing or invalid credentials")
+                    .with_header("www-authenticate", "Basic realm=\"OpenFlash\""),
+            })
+    }
+
+    fn rate_limit(&self, client: &str) -> Result<(), ApiResponse> {
+        let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+        limiter.check(client, Instant::now()).map_err(|wait| {
+            let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            ApiResponse::error(429, "rate limit exceeded")
+                .with_header("retry-after", &secs.max(1).to_string())
+        })
+    }
+
+    fn route(&self, req: &ApiRequest, route: &str, principal: &Principal) -> ApiResponse {
+        let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+        let method = req.method.as_str();
+        match (method, segments.as_slice()) {
+            ("GET", ["info"]) => ApiResponse::json(200, &lock_server(&self.server).server_info()),
+            ("GET", ["devices"]) => {
+                ApiResponse::json(200, &lock_server(&self.server).list_devices())
+            }
+            ("POST", ["jobs"]) => self.submit_job(req, principal),
+            ("GET", ["jobs", id]) => match parse_job_id(id) {
+                Ok(id) => match lock_server(&self.server).get_job_status(id) {
+                    Some(status) => ApiResponse::json(200, &status),
+                    None => ApiResponse::error(404, &ServerError::JobNotFound(id).to_string()),
+                },
+                Err(response) => response,
+            },
+            ("DELETE", ["jobs", id]) => match parse_job_id(id) {
+                Ok(id) => self.cancel_job(id, principal),
+                Err(response) => response,
+            },
+            ("GET", ["artifacts", rest @ ..]) if !rest.is_empty() => {
+                match lock_server(&self.server).authorize(principal, Permission::Artifacts) {
+                    Ok(()) => self.artifact(rest),
+                    Err(e) => ApiResponse::error(status_for(&e), &e.to_string()),
+                }
+            }
+            ("GET", ["access-log"]) => self.access_log(principal),
+            (_, ["info"]) | (_, ["devices"]) | (_, ["openapi.json"]) | (_, ["access-log"]) => {
+                method_not_allowed("GET")
+            }
+            (_, ["jobs"]) => method_not_allowed("POST"),
+            (_, ["jobs", _]) => method_not_allowed("GET, DELETE"),
+            (_, ["artifacts", _, ..]) => method_not_allowed("GET"),
+            _ => ApiResponse::error(404, "not found"),
+        }
+    }
+
+    fn submit_job(&self, req: &ApiRequest, principal: &Principal) -> ApiResponse {
+        let request: SubmitJobRequest = match serde_json::from_slice(&req.body) {
+            Ok(r) => r,
+            Err(e) => return ApiResponse::error(400, &format!("invalid job request: {}", e)),
+        };
+        let job = match request.to_job() {
+            Ok(job) => job,
+            Err(e) => return ApiResponse::error(status_for(&e), &e.to_string()),
+        };
+
+        let mut server = lock_server(&self.server);
+        match server.submit_job_as(principal, job) {
+            Ok(job_id) => {
+                let location = format!("{}/jobs/{}", self.prefix, job_id);
+                ApiResponse::json(
+                    201,
+                    &SubmitJobResponse {
+                        job_id,
+                        status: "queued".to_string(),
+                        message: format!(
+                            "Job queued at position {}",
+                            server.job_queue.pending.len()
+                        ),
+                        estimated_wait: None,
+                    },
+                )
+                .with_header("location", &location)
+            }
+            Err(e) => ApiResponse::error(status_for(&e), &e.to_string()),
+        }
+    }
+
+    fn cancel_job(&self, id: u64, principal: &Principal) -> ApiResponse {
+        let mut server = lock_server(&self.server);
+        match server.cancel_job_as(principal, id) {
+            Ok(()) => match server.get_job_status(id) {
+                Some(status) => ApiResponse::json(200, &status),
+                None => ApiResponse::empty(204),
+            },
+            // Finished jobs stay visible in the history but cannot be cancelled
+            Err(ServerError::JobNotFound(_)) if server.get_job_status(id).is_some() => {
+                ApiResponse::error(409, &format!("Job {} already finished", id))
+            }
+            Err(e) => ApiResponse::error(status_for(&e), &e.to_string()),
+        }
+    }
+
+    fn access_log(&self, principal: &Principal) -> ApiResponse {
+        let server = lock_server(&self.server);
+        match server
+            .authorize(principal, Permission::ViewAccessLog)
+            .and_then(|()| server.read_access_log())
+        {
+            Ok(log) => ApiResponse::json(200, &log),
+            Err(e) => ApiResponse::error(status_for(&e), &e.to_string()),
+        }
+    }
+
+    fn artifact(&self, segments: &[&str]) -> ApiResponse {
+        let root = match &self.artifact_root {
+            Some(root) => root,
+            None => return ApiResponse::error(404, "artifact downloads are not enabled"),
+        };
+        let path = match resolve_url_artifact(root, segments) {
+            Some(path) => path,
+            None => return ApiResponse::error(404, "artifact not found"),
+        };
+        let len = match std::fs::metadata(&path) {
+            Ok(meta) => meta.len(),
+            Err(_) => return ApiResponse::error(404, "artifact not found"),
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().replace(['"', '\\'], "_"))
+            .unwrap_or_default();
+        ApiResponse {
+            status: 200,
+            headers: vec![
+                (
+                    "content-type".to_string(),
+                    "application/octet-stream".to_string(),
+                ),
+                (
+                    "content-disposition".to_string(),
+                    format!("attachment; filename=\"{}\"", file_name),
+                ),
+            ],
+            body: ResponseBody::File { path, len },
+        }
+    }
+}
+
+fn method_not_allowed(allow: &str) -> ApiResponse {
+    ApiResponse::error(405, "method not allowed").with_header("allow", allow)
+}
+
+/// Rate-limit key of the connection a request arrived on
+fn peer_id(req: &ApiRequest) -> String {
+    match req.peer {
+        Some(ip) => format!("ip:{}", ip),
+        None => "anonymous".to_string(),
+    }
+}
+
+/// Whether the request body is declared as JSON
+fn is_json(req: &ApiRequest) -> bool {
+    req.header("content-type")
+        .and_then(|ct| ct.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn parse_job_id(id: &str) -> Result<u64, ApiResponse> {
+    id.parse()
+        .map_err(|_| ApiResponse::error(400, &format!("invalid job id: {}", id)))
+}
+
+/// Decode `%XX` escapes; `None` for malformed escapes or non UTF-8 results
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Map percent-encoded URL segments to a file inside `root`
+fn resolve_url_artifact(root: &Path, segments: &[&str]) -> Option<PathBuf> {
+    let decoded = segments
+        .iter()
+        .map(|s| percent_decode(s))
+        .collect::<Option<Vec<_>>>()?;
+    let components: Vec<&str> = decoded.iter().map(String::as_str).collect();
+    resolve_artifact(root, &components)
+}
+
+// ============================================================================
+// Transport
+// ============================================================================
+
+type ResponseBodyStream = http_body_util::combinators::BoxBody<Bytes, std::io::Error>;
+
+/// Streams an artifact from disk in [`limits::FILE_CHUNK_SIZE`] frames
+struct FileBody {
+    file: tokio::fs::File,
+    remaining: u64,
+    buf: Vec<u8>,
+}
+
+impl Body for FileBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, std::io::Error>>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let want = this.remaining.min(this.buf.len() as u64) as usize;
+        let mut read_buf = ReadBuf::new(&mut this.buf[..want]);
+        match Pin::new(&mut this.file).poll_read(cx, &mut read_buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(Ok(())) => {
+                let filled = read_buf.filled();
+                if filled.is_empty() {
+                    return Poll::Ready(Some(Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "artifact shrank while being sent",
+                    ))));
+                }
+                this.remaining -= filled.len() as u64;
+                Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(filled)))))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+fn full_body(bytes: Vec<u8>) -> ResponseBodyStream {
+    Full::new(Bytes::from(bytes))
+        .map_err(|never: Infallible| match never {})
+        .boxed()
+}
+
+async fn into_hyper_response(response: ApiResponse) -> Response<ResponseBodyStream> {
+    let (status, headers, body, len) = match response.body {
+        ResponseBody::Bytes(bytes) => {
+            let len = bytes.len() as u64;
+            (response.status, response.headers, full_body(bytes), len)
+        }
+        ResponseBody::File { path, len } => match tokio::fs::File::open(&path).await {
+            Ok(file) => {
+                let body = FileBody {
+                    file,
+                    remaining: len,
+                    buf: vec![0; limits::FILE_CHUNK_SIZE],
+                };
+                (response.status, response.headers, body.boxed(), len)
+            }
+            Err(_) => {
+                let error = ApiResponse::error(404, "artifact not found");
+                let bytes = error.body_bytes().to_vec();
+                let len = bytes.len() as u64;
+                (error.status, error.headers, full_body(bytes), len)
+            }
+        },
+    };
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header("content-length", len);
+    for (name, value) in &headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    builder.body(body).unwrap_or_else(|_| {
+        let mut fallback = Response::new(full_body(Vec::new()));
+        *fallback.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+        fallback
+    })
+}
+
+fn api_request(parts: &hyper::http::request::Parts, peer: SocketAddr) -> ApiRequest {
+    let mut request = ApiRequest::new(
+        parts.method.as_str(),
+        parts
+            .uri
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/"),
+    )
+    .with_peer(peer.ip());
+    for (name, value) in &parts.headers {
+        if let Ok(value) = value.to_str() {
+            request = request.with_header(name.as_str(), value);
+        }
+    }
+    request
+}
+
+fn is_websocket_upgrade(req: &Request<Incoming>, hub: &EventHub) -> bool {
+    let header_has = |name: &str, token: &str| {
+        req.headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case(token))
+    };
+    req.method() == hyper::Method::GET
+        && req.uri().path().trim_end_matches('/') == hub.config().path.trim_end_matches('/')
+        && header_has("connection", "upgrade")
+        && header_has("upgrade", "websocket")
+}
+
+/// Complete the WebSocket handshake and hand the connection to `hub`
+async fn upgrade_websocket(
+    api: Arc<RestApi>,
+    hub: EventHub,
+    req: Request<Incoming>,
+    peer: SocketAddr,
+) -> Response<ResponseBodyStream> {
+    let (parts, body) = req.into_parts();
+    let request = api_request(&parts, peer);
+    if let Err(response) = api.admit_websocket(&request) {
+        return into_hyper_response(response).await;
+    }
+    if request.header("sec-websocket-version") != Some("13") {
+        return into_hyper_response(
+            ApiResponse::error(426, "unsupported WebSocket version")
+                .with_header("sec-websocket-version", "13"),
+        )
+        .await;
+    }
+    let accept = match request.header("sec-websocket-key") {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => return into_hyper_response(ApiResponse::error(400, "missing WebSocket key")).await,
+    };
+    let guard = match hub.try_acquire() {
+        Some(guard) => guard,
+        None => {
+            return into_hyper_response(ApiResponse::error(503, "too many WebSocket connections"))
+                .await
+        }
+    };
+
+    let on_upgrade = hyper::upgrade::on(Request::from_parts(parts, body));
+    tokio::spawn(async move {
+        if let Ok(upgraded) = on_upgrade.await {
+            let ws =
+                WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None).await;
+            hub.run_connection(ws, guard).await;
+        }
+    });
+
+    Response::builder()
+        .status(hyper::StatusCode::SWITCHING_PROTOCOLS)
+        .header("connection", "Upgrade")
+        .header("upgrade", "websocket")
+        .header("sec-websocket-accept", accept)
+        .body(full_body(Vec::new()))
+        .unwrap_or_else(|_| Response::new(full_body(Vec::new())))
+}
+
+async fn dispatch(
+    api: Arc<RestApi>,
+    req: Request<Incoming>,
+    peer: SocketAddr,
+) -> Response<ResponseBodyStream> {
+    if let Some(hub) = api.events().filter(|hub| is_websocket_upgrade(&req, hub)) {
+        let hub = hub.clone();
+        return upgrade_websocket(api, hub, req, peer).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let body = match Limited::new(body, limits::MAX_BODY_BYTES).collect().await {
+        Ok(collected) => collected.to_bytes().to_vec(),
+        Err(e) if e.downcast_ref::<LengthLimitError>().is_some() => {
+            return into_hyper_response(ApiResponse::error(413, "request body too large")).await;
+        }
+        Err(_) => {
+            return into_hyper_response(ApiResponse::error(400, "failed to read request body"))
+                .await;
+        }
+    };
+
+    let request = api_request(&parts, peer).with_body(body);
+    into_hyper_response(api.handle(&request)).await
+}
+
+async fn serve_connection<S>(api: Arc<RestApi>, stream: S, peer: SocketAddr)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |req| {
+        let api = api.clone();
+        async move { Ok::<_, Infallible>(dispatch(api, req, peer).await) }
+    });
+    // Client disconnects and protocol errors only affect this connection
+    let _ = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades()
+        .await;
+}
+
+/// Load a PEM certificate chain and private key into a TLS acceptor
+pub fn load_tls_acceptor(cert_path: &Path, key_path: &Path) -> RestResult<TlsAcceptor> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(std::fs::File::open(cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(RestError::Tls(format!(
+            "no certificates found in {}",
+            cert_path.display()
+        )));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(std::fs::File::open(key_path)?))?
+        .ok_or_else(|| RestError::Tls(format!("no private key found in {}", key_path.display())))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| RestError::Tls(e.to_string()))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| RestError::Tls(e.to_string()))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Listening REST server
+pub struct RestServer {
+    api: Arc<RestApi>,
+    listener: TcpListener,
+    tls: Option<TlsAcceptor>,
+}
+
+impl RestServer {
+    /// Validate `api`, load TLS material and bind `host:port`
+    pub async fn bind(api: RestApi) -> RestResult<Self> {
+        api.validate()?;
+        let config = api.config();
+        let tls = match (config.https, &config.cert_path, &config.key_path) {
+            (true, Some(cert), Some(key)) => {
+                Some(load_tls_acceptor(Path::new(cert), Path::new(key))?)
+            }
+            _ => None,
+        };
+        let listener = TcpListener::bind((config.host.as_str(), config.port)).await?;
+        Ok(Self {
+            api: Arc::new(api),
+            listener,
+            tls,
+        })
+    }
+
+    /// Address actually bound (useful with port 0)
+    pub fn local_addr(&self) -> RestResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Serve until the process exits
+    pub async fn serve(self) -> RestResult<()> {
+        self.serve_with_shutdown(std::future::pending()).await
+    }
+
+    /// Serve until `shutdown` completes; connections already accepted are
+    /// allowed to finish on their own
+    pub async fn serve_with_shutdown<F>(self, shutdown: F) -> RestResult<()>
+    where
+        F: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        loop {
+            let accepted = tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                accepted = self.listener.accept() => accepted,
+            };
+            let (stream, peer) = match accepted {
+                Ok(conn) => conn,
+                Err(_) => {
+                    tokio::time::sleep(Duration::from_millis(limits::ACCEPT_BACKOFF_MS)).await;
+                    continue;
+                }
+            };
+            let api = self.api.clone();
+            match &self.tls {
+                Some(acceptor) => {
+                    let acceptor = acceptor.clone();
+                    tokio::spawn(async move {
+                        if let Ok(stream) = acceptor.accept(stream).await {
+                            serve_connection(api, stream, peer).await;
+                        }
+                    });
+                }
+                None => {
+                    tokio::spawn(serve_connection(api, stream, peer));
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{OpenFlashServer, RateLimitConfig, ServerConfig};
+    use base64::Engine;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn shared() -> SharedServer {
+        Arc::new(Mutex::new(OpenFlashServer::new(ServerConfig::default())))
+    }
+
+    #[test]
+    fn test_auth_prefix_cors_and_jobs() {
+        let config = RestApiConfig {
+            auth: AuthMethod::ApiKey {
+                header_name: "X-API-Key".to_string(),
+            },
+            cors_origins: vec!["https://lab.example".to_string()],
+            ..Default::default()
+        };
+        let api = RestApi::new(shared())
+            .with_config(config)
+            .with_api_key("secret");
+        assert!(api.validate().is_ok());
+
+        // Prefix is enforced and the OpenAPI document needs no key
+        assert_eq!(api.handle(&ApiRequest::new("GET", "/info")).status, 404);
+        assert_eq!(
+            api.handle(&ApiRequest::new("GET", "/api/v1extra/info"))
+                .status,
+            404
+        );
+        assert_eq!(
+            api.handle(&ApiRequest::new("GET", "/api/v1/openapi.json"))
+                .status,
+            200
+        );
+        assert_eq!(
+            api.handle(&ApiRequest::new("GET", "/api/v1/info")).status,
+            401
+        );
+        let wrong = ApiRequest::new("GET", "/api/v1/info").with_header("X-API-Key", "nope");
+        assert_eq!(api.handle(&wrong).status, 401);
+
+        let submit = ApiRequest::new("POST", "/api/v1/jobs")
+            .with_header("x-api-key", "secret")
+            .with_header("Origin", "https://lab.example");
+        assert_eq!(api.handle(&submit).status, 415);
+        let submit = submit
+            .with_header("Content-Type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{"name":"dump","job_type":"erase","params":{},"priority":null,
+                "device_id":null,"interface":null,"tags":null,"timeout":null,
+                "callback_url":null}"#,
+            );
+        let response = api.handle(&submit);
+        assert_eq!(response.status, 201);
+        assert_eq!(
+            response.header("access-control-allow-origin"),
+            Some("https://lab.example")
+        );
+        let created: SubmitJobResponse = serde_json::from_slice(response.body_bytes()).unwrap();
+        assert_eq!(
+            response.header("location"),
+            Some(format!("/api/v1/jobs/{}", created.job_id).as_str())
+        );
+
+        let job_path = format!("/api/v1/jobs/{}", created.job_id);
+        let get = ApiRequest::new("GET", &job_path).with_header("X-API-Key", "secret");
+        let status: JobStatusResponse =
+            serde_json::from_slice(api.handle(&get).body_bytes()).unwrap();
+        assert_eq!(status.status, "queued");
+
+        let cancel = ApiRequest::new("DELETE", &job_path).with_header("X-API-Key", "secret");
+        assert_eq!(api.handle(&cancel).status, 415);
+        let cancel = cancel.with_header("Content-Type", "application/json");
+        assert_eq!(api.handle(&cancel).status, 200);
+        assert_eq!(api.handle(&cancel).status, 409);
+        let put = ApiRequest::new("PUT", &job_path)
+            .with_header("X-API-Key", "secret")
+            .with_header("Content-Type", "application/json");
+        assert_eq!(api.handle(&put).status, 405);
+
+        // Disallowed origins are refused, preflight skips authentication
+        let evil = ApiRequest::new("GET", "/api/v1/info")
+            .with_header("X-API-Key", "secret")
+            .with_header("Origin", "https://evil.example");
+        assert_eq!(api.handle(&evil).status, 403);
+        let preflight =
+            ApiRequest::new("OPTIONS", "/api/v1/jobs").with_header("Origin", "https://lab.example");
+        let response = api.handle(&preflight);
+        assert_eq!(response.status, 204);
+        assert!(response
+            .header("access-control-allow-headers")
+            .unwrap()
+            .contains("x-api-key"));
+
+        let doc = openapi_document(api.config(), "2.0.0");
+        assert!(doc["paths"]["/api/v1/jobs"]["post"].is_object());
+        assert!(doc["components"]["schemas"]["SubmitJobRequest"].is_object());
+        assert!(doc["components"]["schemas"]["JobResult"].is_object());
+        assert_eq!(
+            doc["components"]["securitySchemes"]["openflash"]["name"],
+            "X-API-Key"
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_basic_auth_and_artifacts() {
+        let dir = std::env::temp_dir().join(format!("openflash_rest_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dumps")).unwrap();
+        std::fs::write(dir.join("dumps/chip.bin"), b"flash").unwrap();
+
+        let config = RestApiConfig {
+            auth: AuthMethod::BasicAuth,
+            rate_limit: RateLimitConfig {
+                requests_per_minute: 60,
+                burst_size: 2,
+                enabled: true,
+            },
+            ..Default::default()
+        };
+        let api = RestApi::new(shared())
+            .with_config(config)
+            .with_basic_user("alice", "pw")
+            .with_basic_user("bob", "pw2")
+            .with_artifact_root(&dir);
+        let auth = |user: &str, pw: &str| {
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pw))
+            )
+        };
+        let alice_ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let bob_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let artifact = ApiRequest::new("GET", "/api/v1/artifacts/dumps/chip.bin")
+            .with_peer(alice_ip)
+            .with_header("Authorization", &auth("alice", "pw"));
+        let response = api.handle(&artifact);
+        assert_eq!(response.status, 200);
+        assert!(matches!(response.body, ResponseBody::File { len: 5, .. }));
+        let escape = ApiRequest::new("GET", "/api/v1/artifacts/dumps/%2e%2e/..%2fetc")
+            .with_peer(alice_ip)
+            .with_header("Authorization", &auth("alice", "pw"));
+        assert_eq!(api.handle(&escape).status, 404);
+
+        // alice has used her burst, bob still has his own bucket
+        let info = ApiRequest::new("GET", "/api/v1/info")
+            .with_peer(alice_ip)
+            .with_header("Authorization", &auth("alice", "pw"));
+        let limited = api.handle(&info);
+        assert_eq!(limited.status, 429);
+        assert!(limited.header("retry-after").is_some());
+        let bob = ApiRequest::new("GET", "/api/v1/info")
+            .with_peer(bob_ip)
+            .with_header("Authorization", &auth("bob", "pw2"));
+        assert_eq!(api.handle(&bob).status, 200);
+        let bad = ApiRequest::new("GET", "/api/v1/info")
+            .with_peer(bob_ip)
+            .with_header("Authorization", &auth("bob", "pw"));
+        assert_eq!(api.handle(&bad).status, 401);
+        // Failed logins drain the peer's bucket before credentials are checked
+        assert_eq!(api.handle(&bad).status, 429);
+        assert_eq!(api.handle(&bob).status, 429);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_roles_and_access_log() {
+        use crate::access::{AccessConfig, Role, UserAccount};
+
+        let config = ServerConfig {
+            rest: RestApiConfig {
+                auth: AuthMethod::ApiKey {
+                    header_name: "X-API-Key".to_string(),
+                },
+                ..Default::default()
+            },
+            access: AccessConfig {
+                enabled: true,
+                ..Default::default()
+            }
+            .with_user(UserAccount::new("olga", Role::Operator, "op-key"))
+            .with_user(UserAccount::new("root", Role::Admin, "admin-key")),
+            ..Default::default()
+        };
+        let api =
+            RestApi::new(Arc::new(Mutex::new(OpenFlashServer::new(config)))).with_api_key("legacy");
+        let submit = |key: &str, job_type: &str| {
+            let body = format!(
+                r#"{{"name":"job","job_type":"{}","params":{{"output_path":"d.bin"}},
+                "priority":null,"device_id":null,"interface":null,"tags":null,
+                "timeout":null,"callback_url":null}}"#,
+                job_type
+            );
+            api.handle(
+                &ApiRequest::new("POST", "/api/v1/jobs")
+                    .with_header("X-API-Key", key)
+                    .with_header("Content-Type", "application/json")
+                    .with_body(body),
+            )
+        };
+
+        assert_eq!(submit("op-key", "erase").status, 403);
+        let created = submit("op-key", "read");
+        assert_eq!(created.status, 201);
+        let created: SubmitJobResponse = serde_json::from_slice(created.body_bytes()).unwrap();
+        // Start-up keys act with the default (viewer) role
+        assert_eq!(submit("legacy", "read").status, 403);
+        let info = ApiRequest::new("GET", "/api/v1/info").with_header("X-API-Key", "legacy");
+        assert_eq!(api.handle(&info).status, 200);
+
+        let log = |key: &str| {
+            api.handle(&ApiRequest::new("GET", "/api/v1/access-log").with_header("X-API-Key", key))
+        };
+        assert_eq!(log("op-key").status, 403);
+        let cancel = ApiRequest::new("DELETE", &format!("/api/v1/jobs/{}", created.job_id))
+            .with_header("X-API-Key", "admin-key")
+            .with_header("Content-Type", "application/json");
+        assert_eq!(api.handle(&cancel).status, 200);
+
+        let response = log("admin-key");
+        assert_eq!(response.status, 200);
+        let log: AccessLogResponse = serde_json::from_slice(response.body_bytes()).unwrap();
+        assert!(log.verified);
+        let summary: Vec<(&str, &str)> = log
+            .records
+            .iter()
+            .map(|r| (r.action.as_str(), r.outcome.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("submit", "denied"),
+                ("submit", "allowed"),
+                ("submit", "denied"),
+                ("cancel", "allowed")
+            ]
+        );
+        assert_eq!(log.records[0].user, "user:olga");
+        assert_eq!(log.records[3].user, "user:root");
+        assert!(log.records[2].user.starts_with("key:"));
+        assert_eq!(log.records[1].job_id, Some(created.job_id));
+        assert_eq!(log.head, log.records[3].hash);
+    }
+
+    #[test]
+    fn test_access_users_without_auth_scheme() {
+        use crate::access::{AccessConfig, Role, UserAccount};
+
+        let config = ServerConfig {
+            access: AccessConfig {
+                enabled: true,
+                ..Default::default()
+            }
+            .with_user(UserAccount::new("olga", Role::Operator, "op-key")),
+            ..Default::default()
+        };
+        assert!(matches!(config.rest.auth, AuthMethod::None));
+        let api = RestApi::new(Arc::new(Mutex::new(OpenFlashServer::new(config))));
+        assert!(api.validate().is_ok());
+        let submit = |authorization: Option<&str>| {
+            let mut req = ApiRequest::new("POST", "/api/v1/jobs")
+                .with_header("Content-Type", "application/json")
+                .with_body(
+                    r#"{"name":"job","job_type":"read","params":{"output_path":"d.bin"},
+                    "priority":null,"device_id":null,"interface":null,"tags":null,
+                    "timeout":null,"callback_url":null}"#,
+                );
+            if let Some(value) = authorization {
+                req = req.with_header("Authorization", value);
+            }
+            api.handle(&req).status
+        };
+
+        // Anonymous callers act as viewers, users log in with a bearer token
+        assert_eq!(submit(None), 403);
+        assert_eq!(submit(Some("Bearer op-key")), 201);
+        assert_eq!(submit(Some("Bearer wrong")), 401);
+    }
+
+    #[tokio::test]
+    async fn test_serves_http_on_loopback() {
+        let config = RestApiConfig {
+            port: 0,
+            ..Default::default()
+        };
+        assert_eq!(config.host, "127.0.0.1");
+        let server = RestServer::bind(RestApi::new(shared()).with_config(config))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(addr.ip().is_loopback());
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(server.serve_with_shutdown(async {
+            let _ = stopped.await;
+        }));
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /api/v1/devices HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+        assert!(raw.starts_with("HTTP/1.1 200"));
+        let body = raw.split("\r\n\r\n").nth(1).unwrap();
+        let devices: DeviceListResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(devices.total, 0);
+
+        stop.send(()).unwrap();
+        task.await.unwrap().unwrap();
+    }
+}
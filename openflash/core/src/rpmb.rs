@@ -0,0 +1,1026 @@
+//! Replay Protected Memory Block (RPMB) access for eMMC and UFS
+//!
+//! Implements the JEDEC 512-byte RPMB data frame, HMAC-SHA256 frame
+//! authentication, and the request/response flows for key programming,
+//! write counter reads, authenticated writes and authenticated reads.
+//!
+//! Operations are expressed as transport-neutral `RpmbTransfer` steps that
+//! map onto eMMC CMD23/CMD25/CMD18 packets or UFS SECURITY PROTOCOL OUT/IN
+//! CDBs on the RPMB well-known LUN. `RpmbEmulator` is a software RPMB that
+//! follows the same rules as a real device. The eMMC CMD23 packet is
+//! host-only ([`Command::is_host_only`]): no programmer firmware handles it
+//! yet.
+
+use crate::protocol::{Command, Packet};
+use crate::ufs::{security_protocol, ScsiCdbBuilder, UfsLun};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// RPMB errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpmbError {
+    /// Frame buffer is not a multiple of 512 bytes
+    InvalidFrame,
+    /// Data length is not a multiple of the 256-byte half sector
+    InvalidLength(usize),
+    /// Response MAC did not match
+    AuthenticationFailed,
+    /// Response nonce did not match the request
+    NonceMismatch,
+    /// Response type did not match the request
+    UnexpectedResponse { expected: u16, actual: u16 },
+    /// Device reported a failure
+    Device(RpmbStatus),
+    /// Irreversible operation attempted without confirmation
+    ConfirmationRequired,
+}
+
+impl fmt::Display for RpmbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpmbError::InvalidFrame => write!(f, "Invalid RPMB frame"),
+            RpmbError::InvalidLength(len) => {
+                write!(f, "RPMB data length {} is not a multiple of 256", len)
+            }
+            RpmbError::AuthenticationFailed => write!(f, "RPMB response MAC mismatch"),
+            RpmbError::NonceMismatch => write!(f, "RPMB response nonce mismatch"),
+            RpmbError::UnexpectedResponse { expected, actual } => write!(
+                f,
+                "Unexpected RPMB response 0x{:04X} (expected 0x{:04X})",
+                actual, expected
+            ),
+            RpmbError::Device(status) => write!(f, "RPMB device error: {:?}", status),
+            RpmbError::ConfirmationRequired => {
+                write!(f, "RPMB key programming requires explicit confirmation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RpmbError {}
+
+pub type RpmbResult<T> = Result<T, RpmbError>;
+
+// ============================================================================
+// Frame Format
+// ============================================================================
+
+/// RPMB frame size in bytes
+pub const RPMB_FRAME_SIZE: usize = 512;
+/// Payload bytes carried by one frame (one RPMB half sector)
+pub const RPMB_DATA_SIZE: usize = 256;
+/// Authentication key / MAC size
+pub const RPMB_KEY_SIZE: usize = 32;
+/// Nonce size
+pub const RPMB_NONCE_SIZE: usize = 16;
+
+/// Phrase that must be passed to `RpmbSession::program_key`
+///
+/// The key can be written once per device and never changed or read back.
+pub const RPMB_KEY_PROGRAM_CONFIRMATION: &str = "PROGRAM RPMB KEY PERMANENTLY";
+
+/// Frame field offsets (all multi-byte fields are big-endian)
+mod offsets {
+    pub const KEY_MAC: usize = 196;
+    pub const DATA: usize = 228;
+    pub const NONCE: usize = 484;
+    pub const WRITE_COUNTER: usize = 500;
+    pub const ADDRESS: usize = 504;
+    pub const BLOCK_COUNT: usize = 506;
+    pub const RESULT: usize = 508;
+    pub const REQ_RESP: usize = 510;
+}
+
+/// Request message types
+pub mod request {
+    pub const KEY_PROGRAM: u16 = 0x0001;
+    pub const READ_COUNTER: u16 = 0x0002;
+    pub const AUTH_WRITE: u16 = 0x0003;
+    pub const AUTH_READ: u16 = 0x0004;
+    pub const RESULT_READ: u16 = 0x0005;
+}
+
+/// Response message types
+pub mod response {
+    pub const KEY_PROGRAM: u16 = 0x0100;
+    pub const READ_COUNTER: u16 = 0x0200;
+    pub const AUTH_WRITE: u16 = 0x0300;
+    pub const AUTH_READ: u16 = 0x0400;
+}
+
+/// Operation result reported by the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpmbStatus {
+    Ok,
+    GeneralFailure,
+    AuthenticationFailure,
+    CounterFailure,
+    AddressFailure,
+    WriteFailure,
+    ReadFailure,
+    KeyNotProgrammed,
+    Unknown(u8),
+}
+
+impl RpmbStatus {
+    /// Bit set in the result field once the write counter has expired
+    pub const COUNTER_EXPIRED: u16 = 0x0080;
+
+    pub fn from_u16(value: u16) -> Self {
+        match value & 0x7F {
+            0 => RpmbStatus::Ok,
+            1 => RpmbStatus::GeneralFailure,
+            2 => RpmbStatus::AuthenticationFailure,
+            3 => RpmbStatus::CounterFailure,
+            4 => RpmbStatus::AddressFailure,
+            5 => RpmbStatus::WriteFailure,
+            6 => RpmbStatus::ReadFailure,
+            7 => RpmbStatus::KeyNotProgrammed,
+            n => RpmbStatus::Unknown(n as u8),
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            RpmbStatus::Ok => 0,
+            RpmbStatus::GeneralFailure => 1,
+            RpmbStatus::AuthenticationFailure => 2,
+            RpmbStatus::CounterFailure => 3,
+            RpmbStatus::AddressFailure => 4,
+            RpmbStatus::WriteFailure => 5,
+            RpmbStatus::ReadFailure => 6,
+            RpmbStatus::KeyNotProgrammed => 7,
+            RpmbStatus::Unknown(n) => n as u16 & 0x7F,
+        }
+    }
+}
+
+/// 256-bit RPMB authentication key
+#[derive(Clone, PartialEq, Eq)]
+pub struct RpmbKey([u8; RPMB_KEY_SIZE]);
+
+impl RpmbKey {
+    pub fn new(key: [u8; RPMB_KEY_SIZE]) -> Self {
+        Self(key)
+    }
+
+    pub fn from_slice(key: &[u8]) -> Option<Self> {
+        let key: [u8; RPMB_KEY_SIZE] = key.try_into().ok()?;
+        Some(Self(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; RPMB_KEY_SIZE] {
+        &self.0
+    }
+
+    /// HMAC-SHA256 over bytes 228..512 of each frame
+    pub fn mac(&self, frames: &[RpmbFrame]) -> [u8; RPMB_KEY_SIZE] {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        for frame in frames {
+            mac.update(&frame.bytes[offsets::DATA..]);
+        }
+        mac.finalize().into_bytes().into()
+    }
+
+    /// Check the MAC carried in the last frame
+    pub fn verify(&self, frames: &[RpmbFrame]) -> bool {
+        let last = match frames.last() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        for frame in frames {
+            mac.update(&frame.bytes[offsets::DATA..]);
+        }
+        mac.verify_slice(&last.key_mac()).is_ok()
+    }
+}
+
+impl fmt::Debug for RpmbKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print key material
+        write!(f, "RpmbKey(..)")
+    }
+}
+
+/// One 512-byte RPMB data frame
+#[derive(Clone, PartialEq, Eq)]
+pub struct RpmbFrame {
+    bytes: [u8; RPMB_FRAME_SIZE],
+}
+
+impl fmt::Debug for RpmbFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpmbFrame")
+            .field("req_resp", &format_args!("0x{:04X}", self.req_resp()))
+            .field("result", &self.result())
+            .field("address", &self.address())
+            .field("block_count", &self.block_count())
+            .field("write_counter", &self.write_counter())
+            .finish()
+    }
+}
+
+impl Default for RpmbFrame {
+    fn default() -> Self {
+        Self {
+            bytes: [0u8; RPMB_FRAME_SIZE],
+        }
+    }
+}
+
+impl RpmbFrame {
+    /// Empty frame with the given request/response type
+    pub fn new(req_resp: u16) -> Self {
+        let mut frame = Self::default();
+        frame.set_req_resp(req_resp);
+        frame
+    }
+
+    /// Parse one frame
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let bytes: [u8; RPMB_FRAME_SIZE] = data.get(..RPMB_FRAME_SIZE)?.try_into().ok()?;
+        Some(Self { bytes })
+    }
+
+    /// Parse back-to-back frames
+    pub fn parse_all(data: &[u8]) -> RpmbResult<Vec<Self>> {
+        if data.is_empty() || data.len() % RPMB_FRAME_SIZE != 0 {
+            return Err(RpmbError::InvalidFrame);
+        }
+        Ok(data
+            .chunks_exact(RPMB_FRAME_SIZE)
+            .filter_map(Self::parse)
+            .collect())
+    }
+
+    pub fn to_bytes(&self) -> [u8; RPMB_FRAME_SIZE] {
+        self.bytes
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.bytes[offset], self.bytes[offset + 1]])
+    }
+
+    fn write_u16(&mut self, offset: usize, value: u16) {
+        self.bytes[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn key_mac(&self) -> [u8; RPMB_KEY_SIZE] {
+        let mut out = [0u8; RPMB_KEY_SIZE];
+        out.copy_from_slice(&self.bytes[offsets::KEY_MAC..offsets::DATA]);
+        out
+    }
+
+    pub fn set_key_mac(&mut self, value: &[u8; RPMB_KEY_SIZE]) {
+        self.bytes[offsets::KEY_MAC..offsets::DATA].copy_from_slice(value);
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.bytes[offsets::DATA..offsets::NONCE]
+    }
+
+    pub fn set_data(&mut self, data: &[u8]) {
+        let len = data.len().min(RPMB_DATA_SIZE);
+        self.bytes[offsets::DATA..offsets::DATA + len].copy_from_slice(&data[..len]);
+    }
+
+    pub fn nonce(&self) -> [u8; RPMB_NONCE_SIZE] {
+        let mut out = [0u8; RPMB_NONCE_SIZE];
+        out.copy_from_slice(&self.bytes[offsets::NONCE..offsets::WRITE_COUNTER]);
+        out
+    }
+
+    pub fn set_nonce(&mut self, nonce: &[u8; RPMB_NONCE_SIZE]) {
+        self.bytes[offsets::NONCE..offsets::WRITE_COUNTER].copy_from_slice(nonce);
+    }
+
+    pub fn write_counter(&self) -> u32 {
+        let o = offsets::WRITE_COUNTER;
+        u32::from_be_bytes([
+            self.bytes[o],
+            self.bytes[o + 1],
+            self.bytes[o + 2],
+            self.bytes[o + 3],
+        ])
+    }
+
+    pub fn set_write_counter(&mut self, counter: u32) {
+        let o = offsets::WRITE_COUNTER;
+        self.bytes[o..o + 4].copy_from_slice(&counter.to_be_bytes());
+    }
+
+    pub fn address(&self) -> u16 {
+        self.read_u16(offsets::ADDRESS)
+    }
+
+    pub fn set_address(&mut self, address: u16) {
+        self.write_u16(offsets::ADDRESS, address);
+    }
+
+    pub fn block_count(&self) -> u16 {
+        self.read_u16(offsets::BLOCK_COUNT)
+    }
+
+    pub fn set_block_count(&mut self, count: u16) {
+        self.write_u16(offsets::BLOCK_COUNT, count);
+    }
+
+    pub fn result(&self) -> RpmbStatus {
+        RpmbStatus::from_u16(self.read_u16(offsets::RESULT))
+    }
+
+    /// Write counter has reached its maximum; no more writes are possible
+    pub fn counter_expired(&self) -> bool {
+        self.read_u16(offsets::RESULT) & RpmbStatus::COUNTER_EXPIRED != 0
+    }
+
+    pub fn set_result(&mut self, status: RpmbStatus, counter_expired: bool) {
+        let mut value = status.to_u16();
+        if counter_expired {
+            value |= RpmbStatus::COUNTER_EXPIRED;
+        }
+        self.write_u16(offsets::RESULT, value);
+    }
+
+    pub fn req_resp(&self) -> u16 {
+        self.read_u16(offsets::REQ_RESP)
+    }
+
+    pub fn set_req_resp(&mut self, value: u16) {
+        self.write_u16(offsets::REQ_RESP, value);
+    }
+}
+
+/// Generate a fresh request nonce from the operating system's random
+/// number generator
+///
+/// The nonce is what ties a read response to its request, so it must not
+/// be predictable. Panics if the OS generator is unavailable.
+pub fn generate_nonce() -> [u8; RPMB_NONCE_SIZE] {
+    let mut nonce = [0u8; RPMB_NONCE_SIZE];
+    getrandom::getrandom(&mut nonce).expect("OS random number generator unavailable");
+    nonce
+}
+
+// ============================================================================
+// Transport Mapping
+// ============================================================================
+
+/// eMMC PARTITION_ACCESS value that selects the RPMB partition
+pub const EMMC_PARTITION_RPMB: u8 = 3;
+
+/// One step of an RPMB operation
+#[derive(Debug, Clone)]
+pub enum RpmbTransfer {
+    /// Send request frames (reliable write for authenticated writes and key programming)
+    Write {
+        frames: Vec<RpmbFrame>,
+        reliable: bool,
+    },
+    /// Read `count` response frames
+    Read { count: u16 },
+}
+
+impl RpmbTransfer {
+    /// eMMC command packets for this step (RPMB partition must be selected)
+    ///
+    /// CMD23 sets the block count (bit 31 = reliable write), then CMD25
+    /// or CMD18 moves the frames. Frame data follows in the bulk phase.
+    pub fn emmc_packets(&self) -> Vec<Packet> {
+        let (count, reliable, cmd) = match self {
+            RpmbTransfer::Write { frames, reliable } => {
+                (frames.len() as u16, *reliable, Command::EmmcWriteMultiple)
+            }
+            RpmbTransfer::Read { count } => (*count, false, Command::EmmcReadMultiple),
+        };
+
+        let mut arg = count as u32;
+        if reliable {
+            arg |= 1 << 31;
+        }
+
+        let mut transfer_args = [0u8; 6];
+        transfer_args[4..6].copy_from_slice(&count.to_le_bytes());
+
+        vec![
+            Packet::new(Command::EmmcSetBlockCount, &arg.to_le_bytes()),
+            Packet::new(cmd, &transfer_args),
+        ]
+    }
+
+    /// UFS SECURITY PROTOCOL OUT/IN CDB for this step (sent to the RPMB W-LUN)
+    pub fn ufs_cdb(&self) -> [u8; 12] {
+        match self {
+            RpmbTransfer::Write { frames, .. } => ScsiCdbBuilder::build_security_protocol_out(
+                security_protocol::UFS_RPMB,
+                security_protocol::RPMB_REGION_0,
+                (frames.len() * RPMB_FRAME_SIZE) as u32,
+            ),
+            RpmbTransfer::Read { count } => ScsiCdbBuilder::build_security_protocol_in(
+                security_protocol::UFS_RPMB,
+                security_protocol::RPMB_REGION_0,
+                *count as u32 * RPMB_FRAME_SIZE as u32,
+            ),
+        }
+    }
+
+    /// Frame data to send in the bulk phase (empty for reads)
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            RpmbTransfer::Write { frames, .. } => {
+                frames.iter().flat_map(|frame| frame.to_bytes()).collect()
+            }
+            RpmbTransfer::Read { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Packet that switches an eMMC to its RPMB partition
+pub fn emmc_select_rpmb() -> Packet {
+    Packet::new(Command::EmmcSetPartition, &[EMMC_PARTITION_RPMB])
+}
+
+/// Packet that selects the UFS RPMB well-known LUN
+pub fn ufs_select_rpmb() -> Packet {
+    Packet::new(Command::UfsSelectLun, &[UfsLun::Rpmb.to_lun_id()])
+}
+
+// ============================================================================
+// Host Session
+// ============================================================================
+
+/// Host side of RPMB operations
+///
+/// Each operation returns the transfers to run; the frames read back are
+/// then passed to the matching `finish_*` method, which checks the nonce,
+/// response type, device result and MAC.
+#[derive(Debug, Clone)]
+pub struct RpmbSession {
+    key: RpmbKey,
+    nonce: [u8; RPMB_NONCE_SIZE],
+    expected_counter: Option<u32>,
+}
+
+impl RpmbSession {
+    pub fn new(key: RpmbKey) -> Self {
+        Self {
+            key,
+            nonce: [0u8; RPMB_NONCE_SIZE],
+            expected_counter: None,
+        }
+    }
+
+    /// Program the authentication key (one-time, irreversible)
+    ///
+    /// `confirmation` must equal `RPMB_KEY_PROGRAM_CONFIRMATION`.
+    pub fn program_key(&self, confirmation: &str) -> RpmbResult<Vec<RpmbTransfer>> {
+        if confirmation != RPMB_KEY_PROGRAM_CONFIRMATION {
+            return Err(RpmbError::ConfirmationRequired);
+        }
+
+        let mut frame = RpmbFrame::new(request::KEY_PROGRAM);
+        frame.set_key_mac(self.key.as_bytes());
+
+        Ok(vec![
+            RpmbTransfer::Write {
+                frames: vec![frame],
+                reliable: true,
+            },
+            result_read_request(),
+            RpmbTransfer::Read { count: 1 },
+        ])
+    }
+
+    /// Check the key programming result
+    pub fn finish_program_key(&self, responses: &[RpmbFrame]) -> RpmbResult<()> {
+        let frame = single_response(responses, response::KEY_PROGRAM)?;
+        check_status(frame)
+    }
+
+    /// Read the write counter
+    pub fn read_counter(&mut self) -> Vec<RpmbTransfer> {
+        self.nonce = generate_nonce();
+        let mut frame = RpmbFrame::new(request::READ_COUNTER);
+        frame.set_nonce(&self.nonce);
+
+        vec![
+            RpmbTransfer::Write {
+                frames: vec![frame],
+                reliable: false,
+            },
+            RpmbTransfer::Read { count: 1 },
+        ]
+    }
+
+    /// Verify the counter response and return the counter
+    pub fn finish_read_counter(&self, responses: &[RpmbFrame]) -> RpmbResult<u32> {
+        let frame = single_response(responses, response::READ_COUNTER)?;
+        check_status(frame)?;
+        self.check_authentic(responses)?;
+        Ok(frame.write_counter())
+    }
+
+    /// Authenticated write of `data` (multiple of 256 bytes) at half-sector `address`
+    ///
+    /// `write_counter` must be the value from a fresh `read_counter`.
+    pub fn authenticated_write(
+        &mut self,
+        address: u16,
+        write_counter: u32,
+        data: &[u8],
+    ) -> RpmbResult<Vec<RpmbTransfer>> {
+        if data.is_empty() || data.len() % RPMB_DATA_SIZE != 0 {
+            return Err(RpmbError::InvalidLength(data.len()));
+        }
+
+        let count = (data.len() / RPMB_DATA_SIZE) as u16;
+        let mut frames: Vec<RpmbFrame> = data
+            .chunks(RPMB_DATA_SIZE)
+            .map(|chunk| {
+                let mut frame = RpmbFrame::new(request::AUTH_WRITE);
+                frame.set_data(chunk);
+                frame.set_write_counter(write_counter);
+                frame.set_address(address);
+                frame.set_block_count(count);
+                frame
+            })
+            .collect();
+
+        let mac = self.key.mac(&frames);
+        if let Some(last) = frames.last_mut() {
+            last.set_key_mac(&mac);
+        }
+        self.expected_counter = Some(write_counter.wrapping_add(1));
+
+        Ok(vec![
+            RpmbTransfer::Write {
+                frames,
+                reliable: true,
+            },
+            result_read_request(),
+            RpmbTransfer::Read { count: 1 },
+        ])
+    }
+
+    /// Verify the write result and return the new write counter
+    pub fn finish_write(&self, responses: &[RpmbFrame]) -> RpmbResult<u32> {
+        let frame = single_response(responses, response::AUTH_WRITE)?;
+        check_status(frame)?;
+        if !self.key.verify(responses) {
+            return Err(RpmbError::AuthenticationFailed);
+        }
+        if let Some(expected) = self.expected_counter {
+            if frame.write_counter() != expected {
+                return Err(RpmbError::Device(RpmbStatus::CounterFailure));
+            }
+        }
+        Ok(frame.write_counter())
+    }
+
+    /// Authenticated read of `blocks` half sectors starting at `address`
+    pub fn authenticated_read(&mut self, address: u16, blocks: u16) -> Vec<RpmbTransfer> {
+        self.nonce = generate_nonce();
+        let mut frame = RpmbFrame::new(request::AUTH_READ);
+        frame.set_nonce(&self.nonce);
+        frame.set_address(address);
+        frame.set_block_count(blocks);
+
+        vec![
+            RpmbTransfer::Write {
+                frames: vec![frame],
+                reliable: false,
+            },
+            RpmbTransfer::Read {
+                count: blocks.max(1),
+            },
+        ]
+    }
+
+    /// Verify read responses and return the data
+    pub fn finish_read(&self, responses: &[RpmbFrame]) -> RpmbResult<Vec<u8>> {
+        let last = responses.last().ok_or(RpmbError::InvalidFrame)?;
+        for frame in responses {
+            if frame.req_resp() != response::AUTH_READ {
+                return Err(RpmbError::UnexpectedResponse {
+                    expected: response::AUTH_READ,
+                    actual: frame.req_resp(),
+                });
+            }
+        }
+        check_status(last)?;
+        self.check_authentic(responses)?;
+        Ok(responses
+            .iter()
+            .flat_map(|frame| frame.data().to_vec())
+            .collect())
+    }
+
+    fn check_authentic(&self, responses: &[RpmbFrame]) -> RpmbResult<()> {
+        if responses.iter().any(|frame| frame.nonce() != self.nonce) {
+            return Err(RpmbError::NonceMismatch);
+        }
+        if !self.key.verify(responses) {
+            return Err(RpmbError::AuthenticationFailed);
+        }
+        Ok(())
+    }
+}
+
+fn result_read_request() -> RpmbTransfer {
+    RpmbTransfer::Write {
+        frames: vec![RpmbFrame::new(request::RESULT_READ)],
+        reliable: false,
+    }
+}
+
+fn single_response(responses: &[RpmbFrame], expected: u16) -> RpmbResult<&RpmbFrame> {
+    let frame = responses.last().ok_or(RpmbError::InvalidFrame)?;
+    if frame.req_resp() != expected {
+        return Err(RpmbError::UnexpectedResponse {
+            expected,
+            actual: frame.req_resp(),
+        });
+    }
+    Ok(frame)
+}
+
+fn check_status(frame: &RpmbFrame) -> RpmbResult<()> {
+    match frame.result() {
+        RpmbStatus::Ok => Ok(()),
+        status => Err(RpmbError::Device(status)),
+    }
+}
+
+// ============================================================================
+// Software RPMB
+// ============================================================================
+
+/// Software RPMB partition following JEDEC request/response rules
+#[derive(Debug, Clone)]
+pub struct RpmbEmulator {
+    key: Option<RpmbKey>,
+    write_counter: u32,
+    data: Vec<u8>,
+    /// Result of the last write-type request, returned on result read
+    last_result: RpmbFrame,
+    /// Frames queued for the next read
+    pending: Vec<RpmbFrame>,
+}
+
+impl RpmbEmulator {
+    /// Create an unprogrammed RPMB of `size_kb` KiB (multiple of 128 KiB on eMMC)
+    pub fn new(size_kb: u32) -> Self {
+        Self {
+            key: None,
+            write_counter: 0,
+            data: vec![0u8; size_kb as usize * 1024],
+            last_result: RpmbFrame::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn write_counter(&self) -> u32 {
+        self.write_counter
+    }
+
+    pub fn is_key_programmed(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Number of 256-byte half sectors
+    pub fn half_sectors(&self) -> u32 {
+        (self.data.len() / RPMB_DATA_SIZE) as u32
+    }
+
+    /// Run a list of transfers and return every frame read back
+    pub fn execute(&mut self, transfers: &[RpmbTransfer]) -> Vec<RpmbFrame> {
+        let mut out = Vec::new();
+        for transfer in transfers {
+            match transfer {
+                RpmbTransfer::Write { frames, reliable } => self.write_frames(frames, *reliable),
+                RpmbTransfer::Read { count } => out.extend(self.read_frames(*count)),
+            }
+        }
+        out
+    }
+
+    /// Handle frames sent by the host
+    pub fn write_frames(&mut self, frames: &[RpmbFrame], reliable: bool) {
+        let first = match frames.first() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        match first.req_resp() {
+            request::KEY_PROGRAM => {
+                let mut result = RpmbFrame::new(response::KEY_PROGRAM);
+                let status = if self.key.is_some() || !reliable {
+                    RpmbStatus::GeneralFailure
+                } else {
+                    self.key = Some(RpmbKey::new(first.key_mac()));
+                    RpmbStatus::Ok
+                };
+                result.set_result(status, false);
+                self.last_result = result;
+            }
+            request::READ_COUNTER => {
+                let mut resp = RpmbFrame::new(response::READ_COUNTER);
+                resp.set_nonce(&first.nonce());
+                self.pending = vec![self.sign(resp, |resp| match self.key {
+                    Some(_) => resp.set_write_counter(self.write_counter),
+                    None => resp.set_result(RpmbStatus::KeyNotProgrammed, false),
+                })];
+            }
+            request::AUTH_WRITE => {
+                self.last_result = self.authenticated_write(frames, reliable);
+            }
+            request::AUTH_READ => {
+                self.pending = self.authenticated_read(first);
+            }
+            request::RESULT_READ => {
+                self.pending = vec![self.last_result.clone()];
+            }
+            _ => {
+                let mut result = RpmbFrame::new(first.req_resp() << 8);
+                result.set_result(RpmbStatus::GeneralFailure, false);
+                self.last_result = result;
+            }
+        }
+    }
+
+    /// Return queued response frames
+    pub fn read_frames(&mut self, count: u16) -> Vec<RpmbFrame> {
+        let n = (count as usize).min(self.pending.len());
+        self.pending.drain(..n).collect()
+    }
+
+    fn sign(&self, mut frame: RpmbFrame, fill: impl FnOnce(&mut RpmbFrame)) -> RpmbFrame {
+        fill(&mut frame);
+        if let Some(key) = &self.key {
+            let mac = key.mac(std::slice::from_ref(&frame));
+            frame.set_key_mac(&mac);
+        }
+        frame
+    }
+
+    fn authenticated_write(&mut self, frames: &[RpmbFrame], reliable: bool) -> RpmbFrame {
+        let first = &frames[0];
+        let mut result = RpmbFrame::new(response::AUTH_WRITE);
+        result.set_address(first.address());
+        result.set_write_counter(self.write_counter);
+
+        let expired = self.write_counter == u32::MAX;
+        let status = match &self.key {
+            None => RpmbStatus::KeyNotProgrammed,
+            Some(key) if !key.verify(frames) => RpmbStatus::AuthenticationFailure,
+            Some(_) if expired => RpmbStatus::WriteFailure,
+            Some(_) if first.write_counter() != self.write_counter => RpmbStatus::CounterFailure,
+            Some(_) if !reliable || first.block_count() as usize != frames.len() => {
+                RpmbStatus::GeneralFailure
+            }
+            Some(_) => {
+                let start = first.address() as usize * RPMB_DATA_SIZE;
+                let end = start + frames.len() * RPMB_DATA_SIZE;
+                if end > self.data.len() {
+                    RpmbStatus::AddressFailure
+                } else {
+                    for (i, frame) in frames.iter().enumerate() {
+                        let offset = start + i * RPMB_DATA_SIZE;
+                        self.data[offset..offset + RPMB_DATA_SIZE].copy_from_slice(frame.data());
+                    }
+                    self.write_counter += 1;
+                    result.set_write_counter(self.write_counter);
+                    RpmbStatus::Ok
+                }
+            }
+        };
+
+        result.set_result(status, self.write_counter == u32::MAX);
+        self.sign(result, |_| {})
+    }
+
+    fn authenticated_read(&self, request: &RpmbFrame) -> Vec<RpmbFrame> {
+        let count = request.block_count().max(1) as usize;
+        let start = request.address() as usize * RPMB_DATA_SIZE;
+        let end = start + count * RPMB_DATA_SIZE;
+
+        let status = if self.key.is_none() {
+            RpmbStatus::KeyNotProgrammed
+        } else if end > self.data.len() {
+            RpmbStatus::AddressFailure
+        } else {
+            RpmbStatus::Ok
+        };
+
+        let mut frames: Vec<RpmbFrame> = (0..count)
+            .map(|i| {
+                let mut frame = RpmbFrame::new(response::AUTH_READ);
+                frame.set_nonce(&request.nonce());
+                frame.set_address(request.address());
+                frame.set_block_count(count as u16);
+                frame.set_result(status, false);
+                if status == RpmbStatus::Ok {
+                    let offset = start + i * RPMB_DATA_SIZE;
+                    frame.set_data(&self.data[offset..offset + RPMB_DATA_SIZE]);
+                }
+                frame
+            })
+            .collect();
+
+        if let Some(key) = &self.key {
+            let mac = key.mac(&frames);
+            if let Some(last) = frames.last_mut() {
+                last.set_key_mac(&mac);
+            }
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> RpmbKey {
+        RpmbKey::new([0x5A; RPMB_KEY_SIZE])
+    }
+
+    fn programmed() -> RpmbEmulator {
+        let mut rpmb = RpmbEmulator::new(128);
+        let session = RpmbSession::new(key());
+        let transfers = session.program_key(RPMB_KEY_PROGRAM_CONFIRMATION).unwrap();
+        let responses = rpmb.execute(&transfers);
+        session.finish_program_key(&responses).unwrap();
+        rpmb
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        let mut frame = RpmbFrame::new(request::AUTH_WRITE);
+        frame.set_write_counter(0x01020304);
+        frame.set_address(0x0A0B);
+        frame.set_block_count(2);
+        frame.set_result(RpmbStatus::CounterFailure, true);
+
+        let bytes = frame.to_bytes();
+        assert_eq!(&bytes[500..504], &[1, 2, 3, 4]);
+        assert_eq!(&bytes[504..506], &[0x0A, 0x0B]);
+        assert_eq!(&bytes[506..508], &[0, 2]);
+        assert_eq!(&bytes[508..510], &[0x00, 0x83]);
+        assert_eq!(&bytes[510..512], &[0x00, 0x03]);
+
+        let parsed = RpmbFrame::parse(&bytes).unwrap();
+        assert_eq!(parsed.result(), RpmbStatus::CounterFailure);
+        assert!(parsed.counter_expired());
+        assert!(RpmbFrame::parse_all(&bytes[..300]).is_err());
+    }
+
+    #[test]
+    fn test_mac_known_answer() {
+        // HMAC-SHA256 with an all-zero key over 284 zero bytes
+        let frame = RpmbFrame::default();
+        let mac = RpmbKey::new([0u8; 32]).mac(&[frame]);
+        let mut expected = HmacSha256::new_from_slice(&[0u8; 32]).unwrap();
+        expected.update(&[0u8; 284]);
+        assert_eq!(mac.to_vec(), expected.finalize().into_bytes().to_vec());
+    }
+
+    #[test]
+    fn test_key_program_requires_confirmation() {
+        let session = RpmbSession::new(key());
+        assert_eq!(
+            session.program_key("yes").unwrap_err(),
+            RpmbError::ConfirmationRequired
+        );
+
+        let mut rpmb = programmed();
+        assert!(rpmb.is_key_programmed());
+
+        // Second programming attempt is rejected by the device
+        let transfers = session.program_key(RPMB_KEY_PROGRAM_CONFIRMATION).unwrap();
+        let responses = rpmb.execute(&transfers);
+        assert_eq!(
+            session.finish_program_key(&responses).unwrap_err(),
+            RpmbError::Device(RpmbStatus::GeneralFailure)
+        );
+    }
+
+    #[test]
+    fn test_write_then_read() {
+        let mut rpmb = programmed();
+        let mut session = RpmbSession::new(key());
+
+        let responses = rpmb.execute(&session.read_counter());
+        let counter = session.finish_read_counter(&responses).unwrap();
+        assert_eq!(counter, 0);
+
+        let data: Vec<u8> = (0..512).map(|i| i as u8).collect();
+        let transfers = session.authenticated_write(4, counter, &data).unwrap();
+        let responses = rpmb.execute(&transfers);
+        assert_eq!(session.finish_write(&responses).unwrap(), 1);
+
+        let responses = rpmb.execute(&session.authenticated_read(4, 2));
+        assert_eq!(session.finish_read(&responses).unwrap(), data);
+    }
+
+    #[test]
+    fn test_replayed_write_rejected() {
+        let mut rpmb = programmed();
+        let mut session = RpmbSession::new(key());
+
+        let transfers = session.authenticated_write(0, 0, &[0xAA; 256]).unwrap();
+        let responses = rpmb.execute(&transfers);
+        session.finish_write(&responses).unwrap();
+
+        // Same frames again: counter no longer matches
+        let responses = rpmb.execute(&transfers);
+        assert_eq!(
+            session.finish_write(&responses).unwrap_err(),
+            RpmbError::Device(RpmbStatus::CounterFailure)
+        );
+        assert_eq!(rpmb.write_counter(), 1);
+    }
+
+    #[test]
+    fn test_wrong_key_detected() {
+        let mut rpmb = programmed();
+        let mut wrong = RpmbSession::new(RpmbKey::new([0x11; 32]));
+
+        let responses = rpmb.execute(&wrong.read_counter());
+        assert_eq!(
+            wrong.finish_read_counter(&responses).unwrap_err(),
+            RpmbError::AuthenticationFailed
+        );
+
+        let transfers = wrong.authenticated_write(0, 0, &[0u8; 256]).unwrap();
+        let responses = rpmb.execute(&transfers);
+        assert_eq!(
+            wrong.finish_write(&responses).unwrap_err(),
+            RpmbError::Device(RpmbStatus::AuthenticationFailure)
+        );
+    }
+
+    #[test]
+    fn test_nonce_checked() {
+        let mut rpmb = programmed();
+        let mut session = RpmbSession::new(key());
+        let responses = rpmb.execute(&session.read_counter());
+        // Start another request; old responses no longer match
+        let _ = session.read_counter();
+        assert_eq!(
+            session.finish_read_counter(&responses).unwrap_err(),
+            RpmbError::NonceMismatch
+        );
+    }
+
+    #[test]
+    fn test_unprogrammed_device() {
+        let mut rpmb = RpmbEmulator::new(128);
+        let mut session = RpmbSession::new(key());
+        let responses = rpmb.execute(&session.read_counter());
+        assert_eq!(
+            session.finish_read_counter(&responses).unwrap_err(),
+            RpmbError::Device(RpmbStatus::KeyNotProgrammed)
+        );
+    }
+
+    #[test]
+    fn test_transport_mapping() {
+        let mut session = RpmbSession::new(key());
+        let transfers = session.authenticated_write(0, 0, &[0u8; 512]).unwrap();
+
+        let packets = transfers[0].emmc_packets();
+        assert_eq!(packets[0].cmd, Command::EmmcSetBlockCount);
+        assert_eq!(
+            u32::from_le_bytes([
+                packets[0].args[0],
+                packets[0].args[1],
+                packets[0].args[2],
+                packets[0].args[3]
+            ]),
+            0x8000_0002
+        );
+        assert_eq!(packets[1].cmd, Command::EmmcWriteMultiple);
+        assert_eq!(transfers[0].payload().len(), 1024);
+
+        let read = transfers[2].emmc_packets();
+        assert_eq!(read[1].cmd, Command::EmmcReadMultiple);
+
+        let cdb = transfers[0].ufs_cdb();
+        assert_eq!(cdb[0], crate::ufs::scsi::SECURITY_PROTOCOL_OUT);
+        assert_eq!(cdb[1], 0xEC);
+        assert_eq!(u32::from_be_bytes([cdb[6], cdb[7], cdb[8], cdb[9]]), 1024);
+
+        assert_eq!(emmc_select_rpmb().args[0], 3);
+        assert_eq!(ufs_select_rpmb().args[0], 0xC4);
+    }
+}
@@ -2,9 +2,18 @@
 //! Provides REST API, WebSocket, gRPC interfaces, device farm management,
 //! parallel dumping, and production line integration
 
+use crate::access::{
+    self, AccessConfig, AccessError, AccessLog, AccessLogResponse, AccessRecord, Permission,
+    Principal, Role,
+};
+use crate::discovery::{self, DiscoveryConfig};
+use crate::job_store::{JobStore, PersistenceConfig, RecoveryPolicy, StoreError};
+use crate::metrics::ServerMetrics;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // ============================================================================
@@ -28,8 +37,12 @@ pub enum ServerError {
     QueueFull,
     /// Invalid configuration
     InvalidConfig(String),
+    /// Malformed or incomplete API request
+    InvalidRequest(String),
     /// Authentication failed
     AuthFailed(String),
+    /// The caller's role does not allow the operation
+    PermissionDenied(String),
     /// Rate limit exceeded
     RateLimitExceeded,
     /// Connection failed
@@ -50,7 +63,9 @@ impl std::fmt::Display for ServerError {
             Self::JobFailed { job_id, reason } => write!(f, "Job {} failed: {}", job_id, reason),
             Self::QueueFull => write!(f, "Job queue is full"),
             Self::InvalidConfig(s) => write!(f, "Invalid configuration: {}", s),
+            Self::InvalidRequest(s) => write!(f, "Invalid request: {}", s),
             Self::AuthFailed(s) => write!(f, "Authentication failed: {}", s),
+            Self::PermissionDenied(s) => write!(f, "Permission denied: {}", s),
             Self::RateLimitExceeded => write!(f, "Rate limit exceeded"),
             Self::ConnectionFailed(s) => write!(f, "Connection failed: {}", s),
             Self::Timeout(s) => write!(f, "Timeout: {}", s),
@@ -63,6 +78,18 @@ impl std::error::Error for ServerError {}
 
 pub type ServerResult<T> = Result<T, ServerError>;
 
+impl From<StoreError> for ServerError {
+    fn from(e: StoreError) -> Self {
+        Self::InternalError(format!("Job store: {}", e))
+    }
+}
+
+impl From<AccessError> for ServerError {
+    fn from(e: AccessError) -> Self {
+        Self::InternalError(e.to_string())
+    }
+}
+
 // ============================================================================
 // Device Pool Management
 // ============================================================================
@@ -88,10 +115,13 @@ pub enum DeviceStatus {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DevicePlatform {
     RP2040,
+    RP2350,
     STM32F1,
     STM32F4,
     ESP32,
     ESP32S3,
+    RaspberryPi,
+    OrangePi,
     Unknown,
 }
 
@@ -99,10 +129,13 @@ impl DevicePlatform {
     pub fn from_str(s: &str) -> Self {
         match s.to_uppercase().as_str() {
             "RP2040" => Self::RP2040,
+            "RP2350" => Self::RP2350,
             "STM32F1" | "STM32F103" => Self::STM32F1,
             "STM32F4" | "STM32F401" | "STM32F411" | "STM32F446" => Self::STM32F4,
             "ESP32" => Self::ESP32,
             "ESP32S3" | "ESP32-S3" => Self::ESP32S3,
+            "RASPBERRYPI" | "RASPBERRY_PI" | "RPI" => Self::RaspberryPi,
+            "ORANGEPI" | "ORANGE_PI" | "OPI" => Self::OrangePi,
             _ => Self::Unknown,
         }
     }
@@ -165,6 +198,9 @@ pub struct PoolDevice {
     pub bytes_processed: u64,
     /// Error count
     pub error_count: u32,
+    /// Jobs failed since the last successful one
+    #[serde(default)]
+    pub consecutive_failures: u32,
     /// Tags for filtering
     pub tags: Vec<String>,
     /// Custom metadata
@@ -187,6 +223,7 @@ impl PoolDevice {
             jobs_completed: 0,
             bytes_processed: 0,
             error_count: 0,
+            consecutive_failures: 0,
             tags: Vec::new(),
             metadata: HashMap::new(),
         }
@@ -218,8 +255,10 @@ impl PoolDevice {
         if success {
             self.jobs_completed += 1;
             self.bytes_processed += bytes;
+            self.consecutive_failures = 0;
         } else {
             self.error_count += 1;
+            self.consecutive_failures += 1;
         }
     }
 }
@@ -353,6 +392,7 @@ impl Default for DevicePool {
 
 /// Pool statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct PoolStats {
     pub total_devices: usize,
     pub available_devices: usize,
@@ -374,6 +414,12 @@ pub fn generate_job_id() -> u64 {
     JOB_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
 }
 
+/// Make [`generate_job_id`] continue after `last_id` (used when jobs are
+/// restored from a store)
+pub fn reserve_job_ids(last_id: u64) {
+    JOB_ID_COUNTER.fetch_max(last_id.saturating_add(1), Ordering::SeqCst);
+}
+
 /// Job priority levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum JobPriority {
@@ -389,6 +435,19 @@ impl Default for JobPriority {
     }
 }
 
+impl JobPriority {
+    /// Parse a priority name as used by the REST API and CLI
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "normal" => Some(Self::Normal),
+            "high" => Some(Self::High),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+}
+
 /// Job status
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobStatus {
@@ -452,6 +511,21 @@ pub enum JobType {
     },
 }
 
+impl JobType {
+    /// Lower-case variant name, e.g. for metric labels
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Read { .. } => "read",
+            Self::Write { .. } => "write",
+            Self::Erase { .. } => "erase",
+            Self::Verify { .. } => "verify",
+            Self::Analyze { .. } => "analyze",
+            Self::Clone { .. } => "clone",
+            Self::Custom { .. } => "custom",
+        }
+    }
+}
+
 /// Job definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Job {
@@ -638,6 +712,7 @@ impl Job {
 
 /// Job result data
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct JobResult {
     /// Bytes processed
     pub bytes_processed: u64,
@@ -732,6 +807,12 @@ impl JobQueue {
                     return false;
                 }
             }
+            // Clones run on the device holding the source chip
+            if let JobType::Clone { source_device, .. } = &job.job_type {
+                if source_device != &device.id {
+                    return false;
+                }
+            }
             true
         })?;
 
@@ -791,6 +872,37 @@ impl JobQueue {
         }
     }
 
+    /// Put a running job back in the queue as it was submitted
+    pub fn requeue_job(&mut self, job_id: u64) -> ServerResult<()> {
+        if let Some(mut job) = self.running.remove(&job_id) {
+            job.status = JobStatus::Queued;
+            job.started_at = None;
+            self.pending.push(job);
+            self.pending
+                .sort_by_key(|job| std::cmp::Reverse(job.priority));
+            Ok(())
+        } else {
+            Err(ServerError::JobNotFound(job_id))
+        }
+    }
+
+    /// Time out a running job; timed-out jobs are not retried
+    pub fn time_out_job(&mut self, job_id: u64) -> ServerResult<()> {
+        if let Some(mut job) = self.running.remove(&job_id) {
+            job.status = JobStatus::TimedOut;
+            job.completed_at = Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+            );
+            self.add_to_history(job);
+            Ok(())
+        } else {
+            Err(ServerError::JobNotFound(job_id))
+        }
+    }
+
     /// Cancel a job
     pub fn cancel_job(&mut self, job_id: u64) -> ServerResult<()> {
         // Check pending
@@ -850,6 +962,7 @@ impl Default for JobQueue {
 
 /// Queue statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct QueueStats {
     pub pending_count: usize,
     pub running_count: usize,
@@ -913,7 +1026,8 @@ pub struct RestApiConfig {
     pub auth: AuthMethod,
     /// Rate limiting
     pub rate_limit: RateLimitConfig,
-    /// CORS allowed origins
+    /// CORS allowed origins (`"*"` allows any); empty refuses every
+    /// cross-origin request
     pub cors_origins: Vec<String>,
     /// API prefix (e.g., "/api/v1")
     pub prefix: String,
@@ -922,14 +1036,14 @@ pub struct RestApiConfig {
 impl Default for RestApiConfig {
     fn default() -> Self {
         Self {
-            host: "0.0.0.0".to_string(),
+            host: "127.0.0.1".to_string(),
             port: 8080,
             https: false,
             cert_path: None,
             key_path: None,
             auth: AuthMethod::None,
             rate_limit: RateLimitConfig::default(),
-            cors_origins: vec!["*".to_string()],
+            cors_origins: Vec::new(),
             prefix: "/api/v1".to_string(),
         }
     }
@@ -937,6 +1051,7 @@ impl Default for RestApiConfig {
 
 /// REST API request for submitting a job
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct SubmitJobRequest {
     /// Job name
     pub name: String,
@@ -958,8 +1073,119 @@ pub struct SubmitJobRequest {
     pub callback_url: Option<String>,
 }
 
+impl SubmitJobRequest {
+    /// Build a queueable [`Job`] from the request.
+    ///
+    /// `job_type` selects the [`JobType`] variant and `params` supplies its
+    /// fields; unknown job types become [`JobType::Custom`] with the
+    /// parameters stringified.
+    pub fn to_job(&self) -> ServerResult<Job> {
+        let job_type = match self.job_type.to_ascii_lowercase().as_str() {
+            "read" => JobType::Read {
+                output_path: self.str_param("output_path")?,
+                start_address: self.u64_param("start_address")?.unwrap_or(0),
+                length: self.u64_param("length")?,
+                include_oob: self.bool_param("include_oob")?.unwrap_or(false),
+            },
+            "write" => JobType::Write {
+                input_path: self.str_param("input_path")?,
+                start_address: self.u64_param("start_address")?.unwrap_or(0),
+                verify: self.bool_param("verify")?.unwrap_or(true),
+            },
+            "erase" => JobType::Erase {
+                start_address: self.u64_param("start_address")?.unwrap_or(0),
+                length: self.u64_param("length")?,
+            },
+            "verify" => JobType::Verify {
+                file_path: self.str_param("file_path")?,
+            },
+            "analyze" => JobType::Analyze {
+                input_path: self.str_param("input_path")?,
+                output_path: self.str_param("output_path")?,
+                deep_scan: self.bool_param("deep_scan")?.unwrap_or(false),
+            },
+            "clone" => JobType::Clone {
+                source_device: self.str_param("source_device")?,
+                target_device: self.str_param("target_device")?,
+            },
+            "" => return Err(ServerError::InvalidRequest("job_type is empty".to_string())),
+            other => JobType::Custom {
+                command: other.to_string(),
+                params: self
+                    .params
+                    .iter()
+                    .map(|(k, v)| match v {
+                        serde_json::Value::String(s) => (k.clone(), s.clone()),
+                        other => (k.clone(), other.to_string()),
+                    })
+                    .collect(),
+            },
+        };
+
+        let mut job = Job::new(&self.name, job_type);
+        if let Some(priority) = &self.priority {
+            let priority = JobPriority::parse(priority).ok_or_else(|| {
+                ServerError::InvalidRequest(format!("unknown priority: {}", priority))
+            })?;
+            job = job.with_priority(priority);
+        }
+        if let Some(device_id) = &self.device_id {
+            job = job.with_device(device_id);
+        }
+        if let Some(interface) = &self.interface {
+            job = job.with_interface(interface);
+        }
+        if let Some(tags) = &self.tags {
+            job.required_tags = tags.clone();
+        }
+        if let Some(timeout) = self.timeout {
+            job = job.with_timeout(timeout);
+        }
+        if let Some(url) = &self.callback_url {
+            job = job.with_callback(url);
+        }
+        Ok(job)
+    }
+
+    fn str_param(&self, key: &str) -> ServerResult<String> {
+        match self.params.get(key) {
+            Some(serde_json::Value::String(s)) if !s.is_empty() => Ok(s.clone()),
+            Some(_) => Err(ServerError::InvalidRequest(format!(
+                "parameter '{}' must be a non-empty string",
+                key
+            ))),
+            None => Err(ServerError::InvalidRequest(format!(
+                "missing parameter '{}'",
+                key
+            ))),
+        }
+    }
+
+    fn u64_param(&self, key: &str) -> ServerResult<Option<u64>> {
+        match self.params.get(key) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(v) => v.as_u64().map(Some).ok_or_else(|| {
+                ServerError::InvalidRequest(format!(
+                    "parameter '{}' must be an unsigned integer",
+                    key
+                ))
+            }),
+        }
+    }
+
+    fn bool_param(&self, key: &str) -> ServerResult<Option<bool>> {
+        match self.params.get(key) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(v) => v.as_bool().map(Some).ok_or_else(|| {
+                ServerError::InvalidRequest(format!("parameter '{}' must be a boolean", key))
+            }),
+        }
+    }
+}
+
 /// REST API response for job submission
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct SubmitJobResponse {
     /// Job ID
     pub job_id: u64,
@@ -973,6 +1199,7 @@ pub struct SubmitJobResponse {
 
 /// REST API response for job status
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct JobStatusResponse {
     /// Job ID
     pub job_id: u64,
@@ -998,6 +1225,7 @@ pub struct JobStatusResponse {
 
 /// REST API response for device list
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct DeviceListResponse {
     /// Total devices
     pub total: usize,
@@ -1007,6 +1235,7 @@ pub struct DeviceListResponse {
 
 /// Device info for API response
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct DeviceInfo {
     /// Device ID
     pub id: String,
@@ -1046,7 +1275,7 @@ impl From<&PoolDevice> for DeviceInfo {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum WsMessage {
-    /// Subscribe to job updates
+    /// Subscribe to job updates (empty = all jobs)
     Subscribe {
         job_ids: Vec<u64>,
     },
@@ -1054,7 +1283,7 @@ pub enum WsMessage {
     Unsubscribe {
         job_ids: Vec<u64>,
     },
-    /// Subscribe to device updates
+    /// Subscribe to device updates (empty = all devices)
     SubscribeDevices {
         device_ids: Vec<String>,
     },
@@ -1074,11 +1303,28 @@ pub enum WsMessage {
         job_id: u64,
         error: String,
     },
-    /// Device status update
+    /// Device status update ("connected" and "disconnected" when a device
+    /// joins or leaves the pool)
     DeviceUpdate {
         device_id: String,
         status: String,
     },
+    /// Subscribe to production unit results (empty = all lines)
+    SubscribeProduction {
+        line_ids: Vec<String>,
+    },
+    /// Parallel dump chunk status update
+    ChunkUpdate {
+        job_id: u64,
+        chunk_index: usize,
+        status: String,
+        device_id: Option<String>,
+        progress: u8,
+    },
+    /// Production unit finished
+    ProductionResult {
+        result: ProductionUnitResult,
+    },
     /// Error message
     Error {
         code: String,
@@ -1145,7 +1391,7 @@ impl Default for GrpcConfig {
     fn default() -> Self {
         Self {
             enabled: false,
-            host: "0.0.0.0".to_string(),
+            host: "127.0.0.1".to_string(),
             port: 50051,
             tls: false,
             cert_path: None,
@@ -1183,6 +1429,15 @@ pub struct ServerConfig {
     pub metrics_port: u16,
     /// Log level
     pub log_level: String,
+    /// Durable job queue and device registry (None = in memory only)
+    #[serde(default)]
+    pub persistence: Option<PersistenceConfig>,
+    /// Users, roles and the access log
+    #[serde(default)]
+    pub access: AccessConfig,
+    /// Device auto-discovery, health checks and quarantine
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
 }
 
 impl Default for ServerConfig {
@@ -1198,10 +1453,62 @@ impl Default for ServerConfig {
             metrics_enabled: true,
             metrics_port: 9090,
             log_level: "info".to_string(),
+            persistence: None,
+            access: AccessConfig::default(),
+            discovery: DiscoveryConfig::default(),
         }
     }
 }
 
+// ============================================================================
+// Event Bus
+// ============================================================================
+
+/// Callback receiving every event emitted by [`OpenFlashServer`]
+pub type EventListener = Box<dyn Fn(&WsMessage) + Send + Sync>;
+
+/// Fan-out of server events (job, device, chunk and production updates)
+/// to listeners such as the WebSocket stream
+#[derive(Default)]
+pub struct EventBus {
+    listeners: Vec<EventListener>,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    /// Register a listener
+    pub fn subscribe<F>(&mut self, listener: F)
+    where
+        F: Fn(&WsMessage) + Send + Sync + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Deliver an event to every listener
+    pub fn emit(&self, event: WsMessage) {
+        for listener in &self.listeners {
+            listener(&event);
+        }
+    }
+
+    /// Number of registered listeners
+    pub fn listener_count(&self) -> usize {
+        self.listeners.len()
+    }
+}
+
+/// Lower-case status name used in events
+pub(crate) fn status_name<T: std::fmt::Debug>(status: &T) -> String {
+    format!("{:?}", status).to_lowercase()
+}
+
 // ============================================================================
 // OpenFlash Server
 // ============================================================================
@@ -1219,13 +1526,25 @@ pub struct OpenFlashServer {
     pub started_at: u64,
     /// Server version
     pub version: String,
+    /// Event listeners
+    pub events: EventBus,
+    /// Durable storage, when `config.persistence` is set
+    pub store: Option<JobStore>,
+    /// History exported by [`metrics::render`](crate::metrics::render)
+    pub metrics: ServerMetrics,
+    /// Record of job decisions and runs, when `config.access` is enabled
+    pub access_log: Option<AccessLog>,
 }
 
 impl OpenFlashServer {
     /// Create a new server instance
     pub fn new(config: ServerConfig) -> Self {
+        let access_log = config.access.enabled.then(AccessLog::in_memory);
+        let mut device_pool = DevicePool::new(config.max_devices);
+        device_pool.health_check_interval = config.discovery.interval_secs;
+        device_pool.device_timeout = config.discovery.offline_after_secs;
         Self {
-            device_pool: DevicePool::new(config.max_devices),
+            device_pool,
             job_queue: JobQueue::new(config.max_queue_size),
             config,
             started_at: SystemTime::now()
@@ -1233,7 +1552,158 @@ impl OpenFlashServer {
                 .unwrap_or_default()
                 .as_millis() as u64,
             version: "2.0.0".to_string(),
+            events: EventBus::default(),
+            store: None,
+            metrics: ServerMetrics::default(),
+            access_log,
+        }
+    }
+
+    /// Create a server, restoring jobs and devices from
+    /// `config.persistence` when it is set
+    ///
+    /// Restored devices start `Offline` until they report in again. Jobs
+    /// that were assigned or running are handled according to the
+    /// configured [`RecoveryPolicy`], finished jobs are trimmed by the
+    /// retention policy, and the recovered state is compacted into a fresh
+    /// snapshot.
+    ///
+    /// With access control enabled, the access log is opened from
+    /// `config.access.log_path` or the data directory; a log whose hash
+    /// chain is broken is refused.
+    pub fn open(config: ServerConfig) -> ServerResult<Self> {
+        let persistence = match &config.persistence {
+            Some(persistence) => persistence.clone(),
+            None => {
+                let mut server = Self::new(config);
+                server.open_access_log()?;
+                return Ok(server);
+            }
+        };
+        let (store, state) = JobStore::open(&persistence.data_dir, persistence.sync_writes)?;
+        let mut server = Self::new(config);
+        server.open_access_log()?;
+        server.job_queue.max_history_size = persistence.retention.max_finished_jobs;
+
+        for mut device in state.devices {
+            device.status = DeviceStatus::Offline;
+            device.current_job = None;
+            server.device_pool.add_device(device)?;
+        }
+
+        let mut interrupted = Vec::new();
+        for mut job in state.jobs {
+            reserve_job_ids(job.id);
+            match job.status {
+                JobStatus::Queued => server.job_queue.pending.push(job),
+                JobStatus::Assigned { .. } | JobStatus::Running { .. } => {
+                    let requeue = persistence.recovery == RecoveryPolicy::Requeue
+                        && job.retries < job.max_retries;
+                    if requeue {
+                        job.retries += 1;
+                        job.status = JobStatus::Queued;
+                        job.started_at = None;
+                        server.job_queue.pending.push(job.clone());
+                    } else {
+                        job.fail("Interrupted by server restart");
+                        server.job_queue.completed.push(job.clone());
+                    }
+                    interrupted.push(job);
+                }
+                _ => server.job_queue.completed.push(job),
+            }
+        }
+        server
+            .job_queue
+            .pending
+            .sort_by_key(|job| std::cmp::Reverse(job.priority));
+        server.job_queue.completed.sort_by_key(|j| j.completed_at);
+
+        let audit = store.clone();
+        server.events.subscribe(move |event| {
+            let audited = match event {
+                // Progress ticks are not worth keeping
+                WsMessage::JobUpdate {
+                    status, progress, ..
+                } => !(status == "running" && progress.unwrap_or(0) > 0),
+                WsMessage::JobCompleted { .. }
+                | WsMessage::JobFailed { .. }
+                | WsMessage::DeviceUpdate { .. }
+                | WsMessage::ProductionResult { .. }
+                | WsMessage::Error { .. } => true,
+                _ => false,
+            };
+            if audited {
+                let _ = audit.append_audit(event);
+            }
+        });
+        server.store = Some(store);
+        server.compact_store()?;
+
+        for job in interrupted {
+            match &job.status {
+                JobStatus::Failed { error, .. } => server.events.emit(WsMessage::JobFailed {
+                    job_id: job.id,
+                    error: error.clone(),
+                }),
+                _ => server.emit_job(job.id, "queued", None),
+            }
+        }
+        Ok(server)
+    }
+
+    fn open_access_log(&mut self) -> ServerResult<()> {
+        let access = &self.config.access;
+        if !access.enabled {
+            return Ok(());
+        }
+        let path = match (&access.log_path, &self.config.persistence) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(persistence)) => {
+                Path::new(&persistence.data_dir).join(access::limits::LOG_FILE)
+            }
+            (None, None) => return Ok(()),
+        };
+        self.access_log = Some(AccessLog::open(&path)?);
+        Ok(())
+    }
+
+    /// Apply the retention policy and rewrite the store as a snapshot of
+    /// the current state
+    pub fn compact_store(&mut self) -> ServerResult<()> {
+        let (store, retention) = match (&self.store, &self.config.persistence) {
+            (Some(store), Some(persistence)) => (store.clone(), persistence.retention.clone()),
+            _ => return Ok(()),
+        };
+        if let Some(max_age) = retention.max_finished_age_secs {
+            let cutoff = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let cutoff = cutoff.saturating_sub(max_age.saturating_mul(1000));
+            self.job_queue
+                .completed
+                .retain(|job| job.completed_at.map_or(true, |t| t >= cutoff));
         }
+        let excess = self
+            .job_queue
+            .completed
+            .len()
+            .saturating_sub(retention.max_finished_jobs);
+        self.job_queue.completed.drain(..excess);
+
+        let queue = &self.job_queue;
+        let jobs = queue
+            .pending
+            .iter()
+            .chain(queue.running.values())
+            .chain(queue.completed.iter());
+        store.compact(
+            jobs,
+            self.device_pool.devices.values(),
+            retention.max_audit_events,
+        )?;
+        Ok(())
     }
 
     /// Create with default configuration
@@ -1243,12 +1713,390 @@ impl OpenFlashServer {
 
     /// Register a device
     pub fn register_device(&mut self, device: PoolDevice) -> ServerResult<()> {
-        self.device_pool.add_device(device)
+        let device_id = device.id.clone();
+        self.device_pool.add_device(device)?;
+        if let Err(e) = self.persist_device(&device_id) {
+            let _ = self.device_pool.remove_device(&device_id);
+            return Err(e);
+        }
+        self.emit_device(&device_id, "connected");
+        Ok(())
+    }
+
+    /// Remove a device from the pool
+    pub fn remove_device(&mut self, device_id: &str) -> ServerResult<PoolDevice> {
+        let device = self.device_pool.remove_device(device_id)?;
+        self.journal_device(device_id);
+        self.emit_device(device_id, "disconnected");
+        Ok(device)
+    }
+
+    /// Change a device's status
+    ///
+    /// Making a quarantined device available lifts the quarantine.
+    pub fn set_device_status(&mut self, device_id: &str, status: DeviceStatus) -> ServerResult<()> {
+        let device = self
+            .device_pool
+            .get_device_mut(device_id)
+            .ok_or_else(|| ServerError::DeviceNotFound(device_id.to_string()))?;
+        device.status = status;
+        device.touch();
+        if status == DeviceStatus::Available {
+            device.consecutive_failures = 0;
+            device
+                .metadata
+                .remove(discovery::limits::QUARANTINE_METADATA);
+        }
+        self.journal_device(device_id);
+        self.emit_device(device_id, &status_name(&status));
+        Ok(())
     }
 
     /// Submit a job
     pub fn submit_job(&mut self, job: Job) -> ServerResult<u64> {
-        self.job_queue.submit(job)
+        let job_id = self.job_queue.submit(job)?;
+        // A job is only accepted once it is stored
+        if let Err(e) = self.persist_job(job_id) {
+            self.job_queue.pending.retain(|j| j.id != job_id);
+            return Err(e);
+        }
+        self.emit_job(job_id, "queued", None);
+        Ok(job_id)
+    }
+
+    /// Report progress of a running job
+    pub fn update_job_progress(&mut self, job_id: u64, progress: u8) -> ServerResult<()> {
+        let job = self
+            .job_queue
+            .running
+            .get_mut(&job_id)
+            .ok_or(ServerError::JobNotFound(job_id))?;
+        job.update_progress(progress);
+        self.emit_job(job_id, "running", Some(progress.min(100)));
+        Ok(())
+    }
+
+    /// Complete a running job and release its device
+    pub fn complete_job(&mut self, job_id: u64, result: JobResult) -> ServerResult<()> {
+        let device_id = self.running_device(job_id);
+        let bytes = result.bytes_processed;
+        if let Some(device_id) = &device_id {
+            self.metrics
+                .add_ecc_corrections(device_id, result.ecc_corrections);
+        }
+        self.job_queue.complete_job(job_id, result.clone())?;
+        self.journal_job(job_id);
+        self.observe_finished(job_id);
+        self.audit_run(job_id, device_id.clone(), "completed", None);
+        self.release_device(device_id, true, bytes);
+        self.events.emit(WsMessage::JobCompleted { job_id, result });
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// Re-queue a running job that was interrupted rather than failed,
+    /// e.g. by the executor shutting down
+    ///
+    /// Neither the job's retries nor the device's failure counts are
+    /// charged, so restarts cannot quarantine a healthy device.
+    pub fn interrupt_job(&mut self, job_id: u64, reason: &str) -> ServerResult<()> {
+        let device_id = self.running_device(job_id);
+        self.job_queue.requeue_job(job_id)?;
+        self.journal_job(job_id);
+        self.audit_run(job_id, device_id.clone(), "interrupted", Some(reason));
+        if let Some(device_id) = device_id {
+            if let Some(device) = self.device_pool.get_device_mut(&device_id) {
+                device.current_job = None;
+                if device.status == DeviceStatus::Busy {
+                    device.status = DeviceStatus::Available;
+                }
+                self.journal_device(&device_id);
+                self.emit_device(&device_id, "available");
+            }
+        }
+        self.emit_job(job_id, "queued", None);
+        Ok(())
+    }
+
+    /// Fail a running job; it is re-queued while retries remain
+    pub fn fail_job(&mut self, job_id: u64, error: &str) -> ServerResult<()> {
+        let device_id = self.running_device(job_id);
+        self.job_queue.fail_job(job_id, error)?;
+        self.journal_job(job_id);
+        self.observe_finished(job_id);
+        self.audit_run(job_id, device_id.clone(), "failed", Some(error));
+        self.release_device(device_id, false, 0);
+        if self.job_queue.pending.iter().any(|j| j.id == job_id) {
+            self.emit_job(job_id, "queued", None);
+        } else {
+            self.events.emit(WsMessage::JobFailed {
+                job_id,
+                error: error.to_string(),
+            });
+        }
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// Time out a running job and release its device
+    pub fn time_out_job(&mut self, job_id: u64) -> ServerResult<()> {
+        let device_id = self.running_device(job_id);
+        self.job_queue.time_out_job(job_id)?;
+        self.journal_job(job_id);
+        self.observe_finished(job_id);
+        self.audit_run(job_id, device_id.clone(), "timed_out", None);
+        self.release_device(device_id, false, 0);
+        self.emit_job(job_id, "timed_out", None);
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// Publish the state of one chunk of a parallel dump
+    pub fn update_chunk(&self, job: &ParallelDumpJob, chunk_index: usize) -> ServerResult<()> {
+        let chunk = job.chunks.get(chunk_index).ok_or_else(|| {
+            ServerError::InvalidRequest(format!("job {} has no chunk {}", job.id, chunk_index))
+        })?;
+        let status = match &chunk.status {
+            ChunkStatus::Failed(_) => "failed".to_string(),
+            other => status_name(other),
+        };
+        self.events.emit(WsMessage::ChunkUpdate {
+            job_id: job.id,
+            chunk_index,
+            status,
+            device_id: chunk.device_id.clone(),
+            progress: job.progress(),
+        });
+        Ok(())
+    }
+
+    /// Count and publish a finished production unit
+    pub fn record_production_result(&mut self, result: ProductionUnitResult) {
+        self.metrics.record_unit(&result);
+        self.events.emit(WsMessage::ProductionResult { result });
+    }
+
+    /// Record an attempt at a job in the access log
+    fn audit_run(
+        &mut self,
+        job_id: u64,
+        device_id: Option<String>,
+        outcome: &str,
+        detail: Option<&str>,
+    ) {
+        let job = match (&self.access_log, self.job_queue.get_job(job_id)) {
+            (Some(_), Some(job)) => job,
+            _ => return,
+        };
+        let chip_id = job
+            .result
+            .as_ref()
+            .and_then(|r| r.data.get("chip_id").cloned());
+        let role = job
+            .metadata
+            .get(access::limits::ROLE_METADATA)
+            .and_then(|r| Role::parse(r));
+        let mut record = AccessRecord::new(
+            job.client_id.as_deref().unwrap_or("unknown"),
+            "run",
+            outcome,
+        )
+        .with_role(role)
+        .with_job(job)
+        .with_device(device_id)
+        .with_chip(chip_id);
+        if let Some(detail) = detail {
+            record = record.with_detail(detail);
+        }
+        self.audit(record);
+    }
+
+    /// Append to the access log; failures are reported as events
+    fn audit(&mut self, record: AccessRecord) {
+        if let Some(log) = &mut self.access_log {
+            if let Err(e) = log.append(record) {
+                self.events.emit(WsMessage::Error {
+                    code: "access_log".to_string(),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Add a job that has reached a final state to the duration metrics
+    fn observe_finished(&mut self, job_id: u64) {
+        if let Some(job) = self.job_queue.get_job(job_id) {
+            if job.is_finished() {
+                self.metrics.observe_job(job);
+            }
+        }
+    }
+
+    fn running_device(&self, job_id: u64) -> Option<String> {
+        self.job_queue
+            .running
+            .get(&job_id)
+            .and_then(|job| match &job.status {
+                JobStatus::Running { device_id, .. } | JobStatus::Assigned { device_id } => {
+                    Some(device_id.clone())
+                }
+                _ => None,
+            })
+    }
+
+    /// Release a device after a job; a device that reached
+    /// `quarantine_after_failures` failed jobs in a row is put into
+    /// maintenance, where the jobs pinned to it wait for an operator
+    fn release_device(&mut self, device_id: Option<String>, success: bool, bytes: u64) {
+        let Some(device_id) = device_id else {
+            return;
+        };
+        let limit = self.config.discovery.quarantine_after_failures;
+        let Some(device) = self.device_pool.get_device_mut(&device_id) else {
+            return;
+        };
+        device.release(success, bytes);
+        device.touch();
+        let quarantined = limit > 0 && device.consecutive_failures >= limit;
+        if quarantined {
+            device.status = DeviceStatus::Maintenance;
+            device.metadata.insert(
+                discovery::limits::QUARANTINE_METADATA.to_string(),
+                format!("{} consecutive job failures", device.consecutive_failures),
+            );
+        }
+        self.journal_device(&device_id);
+        if quarantined {
+            self.emit_device(&device_id, "quarantined");
+        } else {
+            self.emit_device(&device_id, "available");
+        }
+    }
+
+    /// Record that a device answered a health check or announced itself
+    ///
+    /// An `Offline` or `Error` device becomes available again unless it is
+    /// quarantined. Returns whether the status changed.
+    pub fn device_heartbeat(&mut self, device_id: &str) -> ServerResult<bool> {
+        let device = self
+            .device_pool
+            .get_device_mut(device_id)
+            .ok_or_else(|| ServerError::DeviceNotFound(device_id.to_string()))?;
+        device.touch();
+        let revived = matches!(device.status, DeviceStatus::Offline | DeviceStatus::Error)
+            && !device
+                .metadata
+                .contains_key(discovery::limits::QUARANTINE_METADATA);
+        if revived {
+            device.status = DeviceStatus::Available;
+            self.journal_device(device_id);
+            self.emit_device(device_id, "available");
+        }
+        Ok(revived)
+    }
+
+    /// Take a device that stopped answering out of service
+    ///
+    /// Its running job fails with `reason` (and is re-queued while retries
+    /// remain) and the device goes `Offline` unless that failure
+    /// quarantined it. Jobs that did not name a device run on another one;
+    /// queued jobs pinned to it stay pinned, as they were authorized for
+    /// and target the chip on that device, and wait for it to come back.
+    /// Returns the IDs of those held jobs.
+    pub fn mark_device_lost(&mut self, device_id: &str, reason: &str) -> ServerResult<Vec<u64>> {
+        let running = self
+            .device_pool
+            .get_device(device_id)
+            .ok_or_else(|| ServerError::DeviceNotFound(device_id.to_string()))?
+            .current_job;
+        if let Some(job_id) = running {
+            if self.job_queue.running.contains_key(&job_id) {
+                self.fail_job(job_id, reason)?;
+            }
+        }
+        if let Some(device) = self.device_pool.get_device_mut(device_id) {
+            if device.status != DeviceStatus::Maintenance {
+                device.status = DeviceStatus::Offline;
+                device.current_job = None;
+                self.journal_device(device_id);
+                self.emit_device(device_id, "offline");
+            }
+        }
+        Ok(self
+            .job_queue
+            .pending
+            .iter()
+            .filter(|job| job.device_id.as_deref() == Some(device_id))
+            .map(|job| job.id)
+            .collect())
+    }
+
+    /// Store the current state of a job
+    fn persist_job(&self, job_id: u64) -> ServerResult<()> {
+        if let (Some(store), Some(job)) = (&self.store, self.job_queue.get_job(job_id)) {
+            store.save_job(job)?;
+        }
+        Ok(())
+    }
+
+    /// Store the current registration of a device, or its removal
+    fn persist_device(&self, device_id: &str) -> ServerResult<()> {
+        if let Some(store) = &self.store {
+            match self.device_pool.get_device(device_id) {
+                Some(device) => store.save_device(device)?,
+                None => store.remove_device(device_id)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// [`Self::persist_job`] for changes that already happened; a failure
+    /// is published instead of returned
+    fn journal_job(&self, job_id: u64) {
+        if let Err(e) = self.persist_job(job_id) {
+            self.emit_store_error(e);
+        }
+    }
+
+    fn journal_device(&self, device_id: &str) {
+        if let Err(e) = self.persist_device(device_id) {
+            self.emit_store_error(e);
+        }
+    }
+
+    fn emit_store_error(&self, error: ServerError) {
+        self.events.emit(WsMessage::Error {
+            code: "store".to_string(),
+            message: error.to_string(),
+        });
+    }
+
+    /// Compact the store once the journal has grown past `compact_after`
+    fn maybe_compact(&mut self) {
+        let due = match (&self.store, &self.config.persistence) {
+            (Some(store), Some(persistence)) => store.journal_len() >= persistence.compact_after,
+            _ => false,
+        };
+        if due {
+            if let Err(e) = self.compact_store() {
+                self.emit_store_error(e);
+            }
+        }
+    }
+
+    fn emit_job(&self, job_id: u64, status: &str, progress: Option<u8>) {
+        self.events.emit(WsMessage::JobUpdate {
+            job_id,
+            status: status.to_string(),
+            progress,
+        });
+    }
+
+    fn emit_device(&self, device_id: &str, status: &str) {
+        self.events.emit(WsMessage::DeviceUpdate {
+            device_id: device_id.to_string(),
+            status: status.to_string(),
+        });
     }
 
     /// Get job status
@@ -1306,7 +2154,102 @@ impl OpenFlashServer {
 
     /// Cancel a job
     pub fn cancel_job(&mut self, job_id: u64) -> ServerResult<()> {
-        self.job_queue.cancel_job(job_id)
+        let device_id = self.running_device(job_id);
+        self.job_queue.cancel_job(job_id)?;
+        self.journal_job(job_id);
+        self.observe_finished(job_id);
+        // A cancelled job is not a device error, so don't count it as one
+        if let Some(device_id) = device_id {
+            if let Some(device) = self.device_pool.get_device_mut(&device_id) {
+                device.status = DeviceStatus::Available;
+                device.current_job = None;
+                self.journal_device(&device_id);
+                self.emit_device(&device_id, "available");
+            }
+        }
+        self.emit_job(job_id, "cancelled", None);
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// Check a permission of `principal`
+    pub fn authorize(&self, principal: &Principal, permission: Permission) -> ServerResult<()> {
+        let access = &self.config.access;
+        if !access.enabled || access.policy(principal.role).allows(permission) {
+            Ok(())
+        } else {
+            Err(ServerError::PermissionDenied(format!(
+                "role {} may not {}",
+                principal.role, permission
+            )))
+        }
+    }
+
+    /// Submit a job on behalf of `principal`, enforcing its role and
+    /// recording the decision in the access log
+    pub fn submit_job_as(&mut self, principal: &Principal, mut job: Job) -> ServerResult<u64> {
+        job.client_id = Some(principal.id.clone());
+        job.metadata.insert(
+            access::limits::ROLE_METADATA.to_string(),
+            principal.role.name().to_string(),
+        );
+        let mut record = AccessRecord::new(&principal.id, "submit", "allowed")
+            .with_role(Some(principal.role))
+            .with_job(&job)
+            .with_device(job.device_id.clone());
+        record.job_id = None;
+        let authorized = self
+            .config
+            .access
+            .authorize_job(principal, &mut job, &self.device_pool);
+        if let Err(reason) = authorized {
+            record.outcome = "denied".to_string();
+            self.audit(record.with_detail(&reason));
+            return Err(ServerError::PermissionDenied(reason));
+        }
+        let job_id = self.submit_job(job)?;
+        record.job_id = Some(job_id);
+        self.audit(record);
+        Ok(job_id)
+    }
+
+    /// Cancel a job on behalf of `principal`; roles without `cancel_any`
+    /// may only cancel their own jobs
+    pub fn cancel_job_as(&mut self, principal: &Principal, job_id: u64) -> ServerResult<()> {
+        let job = self
+            .job_queue
+            .get_job(job_id)
+            .ok_or(ServerError::JobNotFound(job_id))?;
+        let own = job.client_id.as_deref() == Some(principal.id.as_str());
+        let mut record = AccessRecord::new(&principal.id, "cancel", "allowed")
+            .with_role(Some(principal.role))
+            .with_job(job)
+            .with_device(self.running_device(job_id));
+        let access = &self.config.access;
+        if access.enabled && !own && !access.policy(principal.role).cancel_any {
+            record.outcome = "denied".to_string();
+            self.audit(record.with_detail("not the submitter"));
+            return Err(ServerError::PermissionDenied(format!(
+                "role {} may only cancel its own jobs",
+                principal.role
+            )));
+        }
+        self.cancel_job(job_id)?;
+        self.audit(record);
+        Ok(())
+    }
+
+    /// Access log records and whether their chain is intact
+    pub fn read_access_log(&self) -> ServerResult<AccessLogResponse> {
+        let log = self.access_log.as_ref().ok_or_else(|| {
+            ServerError::InvalidRequest("access control is not enabled".to_string())
+        })?;
+        let records = log.records()?;
+        Ok(AccessLogResponse {
+            head: log.head().to_string(),
+            verified: access::verify_chain(&records).is_ok(),
+            records,
+        })
     }
 
     /// List devices
@@ -1354,24 +2297,75 @@ impl OpenFlashServer {
             .collect();
 
         for device_id in available {
-            if let Some(device) = self.device_pool.get_device(&device_id) {
-                if let Some(mut job) = self.job_queue.next_job_for_device(device) {
-                    job.start(&device_id);
-                    if let Some(d) = self.device_pool.get_device_mut(&device_id) {
-                        d.assign_job(job.id);
-                    }
-                    assignments.push((job.id, device_id.clone()));
-                    self.job_queue.running.insert(job.id, job);
-                }
+            if let Some(job) = self.start_next_job(&device_id) {
+                assignments.push((job.id, device_id));
             }
         }
 
         assignments
     }
+
+    /// Start the next queued job that can run on an available device
+    ///
+    /// The job is marked running and the device busy; the returned copy is
+    /// what the caller should execute.
+    pub fn start_next_job(&mut self, device_id: &str) -> Option<Job> {
+        let device = self.device_pool.get_device(device_id)?;
+        if !device.is_available() {
+            return None;
+        }
+        let mut job = self.job_queue.next_job_for_device(device)?;
+        job.start(device_id);
+        if let Some(d) = self.device_pool.get_device_mut(device_id) {
+            d.assign_job(job.id);
+        }
+        self.job_queue.running.insert(job.id, job.clone());
+        self.journal_job(job.id);
+        self.journal_device(device_id);
+        self.emit_job(job.id, "running", Some(0));
+        self.emit_device(device_id, "busy");
+        Some(job)
+    }
+}
+
+/// Server state shared between the network front ends and the rest of the
+/// application (device registration, queue processing)
+pub type SharedServer = Arc<Mutex<OpenFlashServer>>;
+
+/// Lock a [`SharedServer`], recovering the state if a previous holder panicked
+pub fn lock_server(server: &SharedServer) -> MutexGuard<'_, OpenFlashServer> {
+    server.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether `name` is usable as a single path component inside an artifact
+/// directory
+pub fn is_safe_artifact_name(name: &str) -> bool {
+    !(name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains(['/', '\\', '\0'])
+        || Path::new(name).is_absolute())
+}
+
+/// Map path components to an existing regular file inside `root`, refusing
+/// anything that could escape it (`..`, separators inside components,
+/// symlinks pointing out)
+pub fn resolve_artifact(root: &Path, components: &[&str]) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in components {
+        if !is_safe_artifact_name(component) {
+            return None;
+        }
+        path.push(component);
+    }
+    let root = root.canonicalize().ok()?;
+    let path = path.canonicalize().ok()?;
+    (path.starts_with(&root) && path.is_file()).then_some(path)
 }
 
 /// Server information
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "rest-server", derive(schemars::JsonSchema))]
 pub struct ServerInfo {
     /// Server name
     pub name: String,
@@ -1612,8 +2606,13 @@ pub enum StationOperation {
     Dump { output_path: String },
     /// Run custom test
     CustomTest { script_path: String },
-    /// Mark serial number
-    MarkSerial { format: String },
+    /// Mark serial number; with `offset`, the serial is also written to
+    /// the chip there
+    MarkSerial {
+        format: String,
+        #[serde(default)]
+        offset: Option<u64>,
+    },
 }
 
 /// Pass criteria for station
@@ -1735,6 +2734,7 @@ pub struct ProductionStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::scratch_dir;
 
     #[test]
     fn test_device_pool_creation() {
@@ -1974,4 +2974,136 @@ mod tests {
         assert!(json.contains("JobUpdate"));
         assert!(json.contains("123"));
     }
+
+    #[test]
+    fn test_submit_job_request_to_job() {
+        let request: SubmitJobRequest = serde_json::from_value(serde_json::json!({
+            "name": "dump",
+            "job_type": "read",
+            "params": { "output_path": "dump.bin", "length": 4096 },
+            "priority": "high",
+            "device_id": null,
+            "interface": "nand",
+            "tags": ["lab"],
+            "timeout": 60,
+            "callback_url": null
+        }))
+        .unwrap();
+        let job = request.to_job().unwrap();
+        assert_eq!(job.priority, JobPriority::High);
+        assert_eq!(job.timeout_secs, 60);
+        assert_eq!(job.required_tags, vec!["lab".to_string()]);
+        assert!(matches!(
+            job.job_type,
+            JobType::Read {
+                length: Some(4096),
+                start_address: 0,
+                ..
+            }
+        ));
+
+        let mut missing = request.clone();
+        missing.params.clear();
+        assert!(matches!(
+            missing.to_job(),
+            Err(ServerError::InvalidRequest(_))
+        ));
+        let mut bad_priority = request;
+        bad_priority.priority = Some("urgent".to_string());
+        assert!(bad_priority.to_job().is_err());
+    }
+
+    #[test]
+    fn test_server_emits_job_and_device_events() {
+        let mut server = OpenFlashServer::with_defaults();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        server
+            .events
+            .subscribe(move |event| sink.lock().unwrap().push(event.clone()));
+
+        let mut device = PoolDevice::new("dev1", "Bench", "usb://1", DevicePlatform::RP2040);
+        device.status = DeviceStatus::Available;
+        server.register_device(device).unwrap();
+        let job_id = server
+            .submit_job(Job::new(
+                "erase",
+                JobType::Erase {
+                    start_address: 0,
+                    length: None,
+                },
+            ))
+            .unwrap();
+        assert_eq!(server.process_queue().len(), 1);
+        server
+            .complete_job(
+                job_id,
+                JobResult {
+                    bytes_processed: 2048,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let device = server.device_pool.get_device("dev1").unwrap();
+        assert_eq!(device.status, DeviceStatus::Available);
+        assert_eq!(device.bytes_processed, 2048);
+        let seen = seen.lock().unwrap();
+        assert!(
+            matches!(&seen[0], WsMessage::DeviceUpdate { status, .. } if status == "connected")
+        );
+        assert!(matches!(&seen[1], WsMessage::JobUpdate { status, .. } if status == "queued"));
+        assert!(matches!(
+            &seen[2],
+            WsMessage::JobUpdate {
+                progress: Some(0),
+                ..
+            }
+        ));
+        assert!(matches!(&seen[3], WsMessage::DeviceUpdate { status, .. } if status == "busy"));
+        assert!(
+            matches!(&seen[4], WsMessage::DeviceUpdate { status, .. } if status == "available")
+        );
+        assert!(matches!(&seen[5], WsMessage::JobCompleted { job_id: id, .. } if *id == job_id));
+    }
+
+    #[test]
+    fn test_open_recovers_interrupted_jobs() {
+        let dir = scratch_dir("openflash_server");
+        let config = ServerConfig {
+            persistence: Some(PersistenceConfig {
+                data_dir: dir.to_string_lossy().into_owned(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let (running, queued) = {
+            let mut server = OpenFlashServer::open(config.clone()).unwrap();
+            let mut device = PoolDevice::new("dev1", "Bench", "usb://1", DevicePlatform::RP2040);
+            device.status = DeviceStatus::Available;
+            server.register_device(device).unwrap();
+            let erase = JobType::Erase {
+                start_address: 0,
+                length: None,
+            };
+            let running = server.submit_job(Job::new("erase", erase.clone())).unwrap();
+            let queued = server.submit_job(Job::new("erase", erase)).unwrap();
+            assert_eq!(server.process_queue().len(), 1);
+            (running, queued)
+        };
+
+        let server = OpenFlashServer::open(config).unwrap();
+        let device = server.device_pool.get_device("dev1").unwrap();
+        assert_eq!(device.status, DeviceStatus::Offline);
+        assert_eq!(device.current_job, None);
+        assert!(server.job_queue.running.is_empty());
+        let pending: Vec<u64> = server.job_queue.pending.iter().map(|j| j.id).collect();
+        assert!(pending.contains(&running) && pending.contains(&queued));
+        let recovered = server.job_queue.get_job(running).unwrap();
+        assert_eq!(recovered.retries, 1);
+        assert!(generate_job_id() > running.max(queued));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
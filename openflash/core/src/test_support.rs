@@ -0,0 +1,358 @@
+//! Fixtures shared by the unit tests of the server-side modules
+
+use crate::emmc::ext_csd;
+use crate::job_executor::{DeviceTransport, ExecError, ExecResult, NandDevice, TransportFactory};
+use crate::programmer::limits::{MAX_TRANSFER, PACKET_SIZE, UNKNOWN_COMMAND};
+use crate::protocol::Command;
+use crate::server::{
+    DevicePlatform, DeviceStatus, OpenFlashServer, PoolDevice, ServerConfig, SharedServer,
+};
+use crate::write_ops::{ChipProgrammer, ProgramTarget, WriteError, WriteResult};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Empty directory `<tmp>/<name>_<pid>`
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// Server with one available RP2040 programmer per id, at `test://<id>`
+pub fn server_with_devices(ids: &[&str]) -> SharedServer {
+    let mut server = OpenFlashServer::new(ServerConfig::default());
+    for id in ids {
+        let uri = format!("test://{}", id);
+        server
+            .register_device(PoolDevice::new(id, id, &uri, DevicePlatform::RP2040))
+            .unwrap();
+        server
+            .set_device_status(id, DeviceStatus::Available)
+            .unwrap();
+    }
+    Arc::new(Mutex::new(server))
+}
+
+/// Factory connecting each device to a clone of the chip listed under its
+/// id; other devices fail to connect
+pub fn chip_factory<C>(chips: &[(&str, C)]) -> TransportFactory
+where
+    C: DeviceTransport + Clone + Sync + 'static,
+{
+    let chips: HashMap<String, C> = chips
+        .iter()
+        .map(|(id, chip)| (id.to_string(), chip.clone()))
+        .collect();
+    Arc::new(move |device: &PoolDevice| {
+        chips
+            .get(&device.id)
+            .map(|chip| Box::new(chip.clone()) as Box<dyn DeviceTransport>)
+            .ok_or_else(|| ExecError::Transport(format!("no chip on {}", device.id)))
+    })
+}
+
+/// Samsung K9F1G08U0B: 2048 + 64 byte pages, 64 pages per block
+const CHIP_ID: [u8; 5] = [0xEC, 0xF1, 0x00, 0x95, 0x40];
+const STRIDE: usize = 2048 + 64;
+
+/// Block carrying a factory bad block marker
+pub const FACTORY_BAD_BLOCK: u32 = 5;
+/// Block whose pages always fail to program
+pub const WORN_BLOCK: u32 = 3;
+
+/// Programmer firmware answering on `127.0.0.1`, one connection at a
+/// time, with the framing of the RP2040 `usb_handler`; returns its `tcp://`
+/// URI
+pub fn fake_programmer() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("tcp://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut marker = vec![0xFF; STRIDE];
+        marker[2048] = 0x00;
+        let mut pages: HashMap<u32, Vec<u8>> = HashMap::from([(FACTORY_BAD_BLOCK * 64, marker)]);
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { return };
+            let _ = stream.set_nodelay(true);
+            let mut packet = [0u8; PACKET_SIZE];
+            while stream.read_exact(&mut packet).is_ok() {
+                let cmd = packet[0];
+                let page = u32::from_le_bytes(packet[1..5].try_into().unwrap());
+                let length =
+                    (u16::from_le_bytes([packet[5], packet[6]]) as usize).min(MAX_TRANSFER);
+                match Command::from_u8(cmd) {
+                    Some(Command::Ping) => stream.write_all(&[cmd, 0x00]).unwrap(),
+                    Some(Command::NandReadId) => {
+                        stream.write_all(&[cmd, 0x00]).unwrap();
+                        stream.write_all(&CHIP_ID).unwrap();
+                    }
+                    // Page bytes with no header
+                    Some(Command::NandReadPage) => {
+                        let data = pages.get(&page).cloned().unwrap_or(vec![0xFF; STRIDE]);
+                        stream.write_all(&data[..length]).unwrap();
+                    }
+                    Some(Command::NandWritePage) => {
+                        let mut data = vec![0u8; length];
+                        stream.read_exact(&mut data).unwrap();
+                        if page / 64 == WORN_BLOCK {
+                            stream.write_all(&[cmd, 0x01]).unwrap();
+                            continue;
+                        }
+                        let cells = pages.entry(page).or_insert(vec![0xFF; STRIDE]);
+                        // Programming only clears bits
+                        for (cell, byte) in cells.iter_mut().zip(data) {
+                            *cell &= byte;
+                        }
+                        stream.write_all(&[cmd, 0x00]).unwrap();
+                    }
+                    Some(Command::NandErase) => {
+                        let first = page - page % 64;
+                        pages.retain(|p, _| *p < first || *p >= first + 64);
+                        stream.write_all(&[cmd, 0x00]).unwrap();
+                    }
+                    Some(Command::EmmcReadExtCsd) => {
+                        let mut ext = vec![0u8; ext_csd::SIZE];
+                        ext[ext_csd::EXT_CSD_REV] = 8;
+                        stream.write_all(&[cmd, 0x00]).unwrap();
+                        stream.write_all(&ext).unwrap();
+                    }
+                    _ => stream.write_all(&[UNKNOWN_COMMAND]).unwrap(),
+                }
+            }
+        }
+    });
+    uri
+}
+
+/// Geometry of [`MemoryNand`]: 512 + 16 byte pages, 4 pages per block
+pub const NAND_PAGE: usize = 512;
+pub const NAND_OOB: usize = 16;
+pub const NAND_PAGES_PER_BLOCK: u32 = 4;
+pub const NAND_BLOCK: u64 = NAND_PAGE as u64 * NAND_PAGES_PER_BLOCK as u64;
+
+/// In-memory raw NAND chip; clones share the same contents
+///
+/// Programming only clears bits, worn blocks fail to program, and every
+/// page operation sleeps for the latency and can be made to fail with an
+/// I/O error.
+#[derive(Debug, Clone)]
+pub struct MemoryNand {
+    blocks: u32,
+    state: Arc<Mutex<NandState>>,
+}
+
+#[derive(Debug, Default)]
+struct NandState {
+    /// Page index -> main area followed by spare area
+    pages: HashMap<u32, Vec<u8>>,
+    worn: HashSet<u32>,
+    erases: HashMap<u32, u32>,
+    io_failures: u32,
+    latency: Duration,
+}
+
+impl MemoryNand {
+    /// Erased chip of `blocks` blocks
+    pub fn new(blocks: u32) -> Self {
+        Self {
+            blocks,
+            state: Arc::default(),
+        }
+    }
+
+    /// Put a factory bad block marker on `block`
+    pub fn with_bad_block(self, block: u32) -> Self {
+        let mut page = vec![0xFF; NAND_PAGE + NAND_OOB];
+        page[NAND_PAGE] = 0x00;
+        self.lock().pages.insert(block * NAND_PAGES_PER_BLOCK, page);
+        self
+    }
+
+    /// Make programming `block` fail
+    pub fn with_worn_block(self, block: u32) -> Self {
+        self.lock().worn.insert(block);
+        self
+    }
+
+    /// Delay every page operation by `latency`
+    pub fn with_latency(self, latency: Duration) -> Self {
+        self.lock().latency = latency;
+        self
+    }
+
+    /// Fail the next `count` page operations with an I/O error
+    pub fn fail_next(&self, count: u32) {
+        self.lock().io_failures = count;
+    }
+
+    /// Main area of `block`
+    pub fn block(&self, block: u32) -> Vec<u8> {
+        let state = self.lock();
+        (0..NAND_PAGES_PER_BLOCK)
+            .flat_map(
+                |page| match state.pages.get(&(block * NAND_PAGES_PER_BLOCK + page)) {
+                    Some(data) => data[..NAND_PAGE].to_vec(),
+                    None => vec![0xFF; NAND_PAGE],
+                },
+            )
+            .collect()
+    }
+
+    /// How often `block` was erased
+    pub fn erase_count(&self, block: u32) -> u32 {
+        self.lock().erases.get(&block).copied().unwrap_or(0)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NandState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sleep, then fail if an I/O error is pending
+    fn operate(&self) -> WriteResult<MutexGuard<'_, NandState>> {
+        let latency = self.lock().latency;
+        std::thread::sleep(latency);
+        let mut state = self.lock();
+        if state.io_failures > 0 {
+            state.io_failures -= 1;
+            return Err(WriteError::IoError("USB disconnected".into()));
+        }
+        Ok(state)
+    }
+}
+
+impl ProgramTarget for MemoryNand {
+    fn erase_block(&mut self, block: u32) -> WriteResult<()> {
+        let mut state = self.operate()?;
+        *state.erases.entry(block).or_default() += 1;
+        let first = block * NAND_PAGES_PER_BLOCK;
+        state
+            .pages
+            .retain(|&p, _| p < first || p >= first + NAND_PAGES_PER_BLOCK);
+        Ok(())
+    }
+
+    fn program_page(&mut self, block: u32, page: u32, data: &[u8], oob: &[u8]) -> WriteResult<()> {
+        let mut state = self.operate()?;
+        if state.worn.contains(&block) {
+            return Err(WriteError::ProgramFailed { block, page });
+        }
+        let cells = state
+            .pages
+            .entry(block * NAND_PAGES_PER_BLOCK + page)
+            .or_insert(vec![0xFF; NAND_PAGE + NAND_OOB]);
+        for (cell, byte) in cells.iter_mut().zip(data.iter().chain(oob)) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+
+    fn read_page(
+        &mut self,
+        block: u32,
+        page: u32,
+        data: &mut [u8],
+        oob: &mut [u8],
+    ) -> WriteResult<()> {
+        let state = self.operate()?;
+        let erased = vec![0xFF; NAND_PAGE + NAND_OOB];
+        let raw = state
+            .pages
+            .get(&(block * NAND_PAGES_PER_BLOCK + page))
+            .unwrap_or(&erased);
+        data.copy_from_slice(&raw[..data.len()]);
+        oob.copy_from_slice(&raw[NAND_PAGE..NAND_PAGE + oob.len()]);
+        Ok(())
+    }
+}
+
+impl NandDevice for MemoryNand {
+    fn chip_programmer(&self) -> ChipProgrammer {
+        ChipProgrammer::new(
+            NAND_PAGE as u32,
+            NAND_PAGES_PER_BLOCK,
+            self.blocks,
+            NAND_OOB as u32,
+            100_000,
+        )
+    }
+}
+
+fn transport_error(e: WriteError) -> ExecError {
+    ExecError::Transport(e.to_string())
+}
+
+/// Byte access over the main area, in whole blocks for writes
+impl DeviceTransport for MemoryNand {
+    fn capacity(&mut self, _include_oob: bool) -> ExecResult<u64> {
+        Ok(self.blocks as u64 * NAND_BLOCK)
+    }
+
+    fn erase_size(&mut self) -> ExecResult<u64> {
+        Ok(NAND_BLOCK)
+    }
+
+    fn read(&mut self, address: u64, buf: &mut [u8], _include_oob: bool) -> ExecResult<()> {
+        let mut page = vec![0u8; NAND_PAGE];
+        let mut oob = vec![0u8; NAND_OOB];
+        let mut done = 0;
+        while done < buf.len() {
+            let position = address + done as u64;
+            let index = (position / NAND_PAGE as u64) as u32;
+            let offset = (position % NAND_PAGE as u64) as usize;
+            self.read_page(
+                index / NAND_PAGES_PER_BLOCK,
+                index % NAND_PAGES_PER_BLOCK,
+                &mut page,
+                &mut oob,
+            )
+            .map_err(transport_error)?;
+            let n = (NAND_PAGE - offset).min(buf.len() - done);
+            buf[done..done + n].copy_from_slice(&page[offset..offset + n]);
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: u64, data: &[u8]) -> ExecResult<()> {
+        let first = (address / NAND_BLOCK) as u32;
+        for (i, block) in data.chunks(NAND_BLOCK as usize).enumerate() {
+            self.erase_block(first + i as u32)
+                .map_err(transport_error)?;
+            for (page, chunk) in block.chunks(NAND_PAGE).enumerate() {
+                self.program_page(first + i as u32, page as u32, chunk, &[0xFF; NAND_OOB])
+                    .map_err(transport_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, address: u64, length: u64) -> ExecResult<()> {
+        let first = (address / NAND_BLOCK) as u32;
+        for block in first..first + (length / NAND_BLOCK) as u32 {
+            self.erase_block(block).map_err(transport_error)?;
+        }
+        Ok(())
+    }
+
+    /// Blocks with a marker on their first page
+    fn bad_blocks(&mut self) -> ExecResult<Vec<u64>> {
+        let state = self.lock();
+        Ok((0..self.blocks)
+            .filter(|block| {
+                state
+                    .pages
+                    .get(&(block * NAND_PAGES_PER_BLOCK))
+                    .is_some_and(|page| page[NAND_PAGE] != 0xFF)
+            })
+            .map(|block| block as u64 * NAND_BLOCK)
+            .collect())
+    }
+
+    fn nand(&mut self) -> Option<&mut dyn NandDevice> {
+        Some(self)
+    }
+}
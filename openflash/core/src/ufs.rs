@@ -1,6 +1,7 @@
 //! UFS (Universal Flash Storage) module
 //! Contains UFS device information, descriptor types, and SCSI command builders
 
+use crate::emmc::{health_verdict, EmmcHealthVerdict, LifeTimeEstimate, PreEolInfo};
 use serde::{Deserialize, Serialize};
 
 /// UFS device information
@@ -65,8 +66,22 @@ impl UfsVersion {
     }
 }
 
+/// Number of logical units addressable on a UFS device
+pub const MAX_LUNS: u8 = 32;
+
+/// Well-known logical unit IDs (UPIU LUN field)
+pub mod wlun {
+    pub const REPORT_LUNS: u8 = 0x81;
+    pub const BOOT: u8 = 0xB0;
+    pub const RPMB: u8 = 0xC4;
+    pub const UFS_DEVICE: u8 = 0xD0;
+}
+
 /// UFS Logical Unit types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Two values are equal when they address the same LUN id, so a raw
+/// `Logical(1)` compares equal to `BootA`.
+#[derive(Debug, Clone, Copy, Eq, Serialize, Deserialize)]
 pub enum UfsLun {
     /// User data LUN (main storage)
     UserData,
@@ -74,18 +89,48 @@ pub enum UfsLun {
     BootA,
     /// Boot LUN B
     BootB,
+    /// Any other logical unit (3..31); build with [`UfsLun::logical`]
+    Logical(u8),
     /// Replay Protected Memory Block
     Rpmb,
+    /// REPORT LUNS well-known LUN
+    ReportLuns,
+    /// BOOT well-known LUN (routes to the LUN selected by bBootLunEn)
+    Boot,
+    /// UFS Device well-known LUN
+    UfsDevice,
+}
+
+impl PartialEq for UfsLun {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_lun_id() == other.to_lun_id()
+    }
 }
 
 impl UfsLun {
+    /// Build a logical unit from its id, validating the range
+    ///
+    /// Ids 0..2 map to the named user-data and boot variants; ids at or
+    /// above `MAX_LUNS` are rejected.
+    pub fn logical(id: u8) -> Option<Self> {
+        if id < MAX_LUNS {
+            Self::from_lun_id(id)
+        } else {
+            None
+        }
+    }
+
     /// Get LUN number for SCSI commands
     pub fn to_lun_id(&self) -> u8 {
         match self {
             UfsLun::UserData => 0x00,
             UfsLun::BootA => 0x01,
             UfsLun::BootB => 0x02,
-            UfsLun::Rpmb => 0xC4,
+            UfsLun::Logical(id) => *id,
+            UfsLun::Rpmb => wlun::RPMB,
+            UfsLun::ReportLuns => wlun::REPORT_LUNS,
+            UfsLun::Boot => wlun::BOOT,
+            UfsLun::UfsDevice => wlun::UFS_DEVICE,
         }
     }
 
@@ -95,18 +140,31 @@ impl UfsLun {
             0x00 => Some(UfsLun::UserData),
             0x01 => Some(UfsLun::BootA),
             0x02 => Some(UfsLun::BootB),
-            0xC4 => Some(UfsLun::Rpmb),
+            id if id < MAX_LUNS => Some(UfsLun::Logical(id)),
+            wlun::RPMB => Some(UfsLun::Rpmb),
+            wlun::REPORT_LUNS => Some(UfsLun::ReportLuns),
+            wlun::BOOT => Some(UfsLun::Boot),
+            wlun::UFS_DEVICE => Some(UfsLun::UfsDevice),
             _ => None,
         }
     }
 
+    /// Check if this is a well-known LUN
+    pub fn is_well_known(&self) -> bool {
+        self.to_lun_id() & 0x80 != 0
+    }
+
     /// Get human-readable name
     pub fn as_str(&self) -> &'static str {
         match self {
             UfsLun::UserData => "User Data",
             UfsLun::BootA => "Boot LUN A",
             UfsLun::BootB => "Boot LUN B",
+            UfsLun::Logical(_) => "Logical Unit",
             UfsLun::Rpmb => "RPMB",
+            UfsLun::ReportLuns => "REPORT LUNS W-LUN",
+            UfsLun::Boot => "BOOT W-LUN",
+            UfsLun::UfsDevice => "UFS Device W-LUN",
         }
     }
 }
@@ -143,6 +201,70 @@ pub mod scsi {
     pub const SYNCHRONIZE_CACHE_10: u8 = 0x35;
     /// Unmap - TRIM/discard blocks
     pub const UNMAP: u8 = 0x42;
+    /// Format Unit - reinitialize the whole logical unit
+    pub const FORMAT_UNIT: u8 = 0x04;
+    /// Security Protocol In - read RPMB frames
+    pub const SECURITY_PROTOCOL_IN: u8 = 0xA2;
+    /// Security Protocol Out - send RPMB frames
+    pub const SECURITY_PROTOCOL_OUT: u8 = 0xB5;
+}
+
+/// SECURITY PROTOCOL IN/OUT protocol identifiers
+pub mod security_protocol {
+    /// UFS RPMB security protocol
+    pub const UFS_RPMB: u8 = 0xEC;
+    /// Protocol specific field for RPMB region 0
+    pub const RPMB_REGION_0: u16 = 0x0001;
+}
+
+/// UFS flag IDNs (QUERY READ/SET/CLEAR FLAG)
+pub mod flags {
+    pub const DEVICE_INIT: u8 = 0x01;
+    pub const PERMANENT_WP_EN: u8 = 0x02;
+    pub const POWER_ON_WP_EN: u8 = 0x03;
+    pub const BACKGROUND_OPS_EN: u8 = 0x04;
+    pub const DEVICE_LIFE_SPAN_MODE_EN: u8 = 0x05;
+    /// fPurgeEnable - start a purge of unmapped physical memory
+    pub const PURGE_ENABLE: u8 = 0x06;
+    pub const REFRESH_ENABLE: u8 = 0x07;
+    pub const PHY_RESOURCE_REMOVAL: u8 = 0x08;
+    pub const BUSY_RTC: u8 = 0x09;
+    pub const PERMANENTLY_DISABLE_FW_UPDATE: u8 = 0x0B;
+    pub const WRITE_BOOSTER_EN: u8 = 0x0E;
+}
+
+/// UFS attribute IDNs (QUERY READ/WRITE ATTRIBUTE)
+pub mod attributes {
+    pub const BOOT_LUN_EN: u8 = 0x00;
+    pub const CURRENT_POWER_MODE: u8 = 0x02;
+    pub const ACTIVE_ICC_LEVEL: u8 = 0x03;
+    pub const OUT_OF_ORDER_DATA_EN: u8 = 0x04;
+    pub const BACKGROUND_OP_STATUS: u8 = 0x05;
+    /// bPurgeStatus - progress of a purge started with fPurgeEnable
+    pub const PURGE_STATUS: u8 = 0x06;
+    pub const MAX_DATA_IN_SIZE: u8 = 0x07;
+    pub const MAX_DATA_OUT_SIZE: u8 = 0x08;
+    pub const DYN_CAP_NEEDED: u8 = 0x09;
+    pub const REF_CLK_FREQ: u8 = 0x0A;
+    pub const CONFIG_DESCR_LOCK: u8 = 0x0B;
+    pub const MAX_NUM_OF_RTT: u8 = 0x0C;
+    pub const EXCEPTION_EVENT_CONTROL: u8 = 0x0D;
+    pub const EXCEPTION_EVENT_STATUS: u8 = 0x0E;
+    pub const SECONDS_PASSED: u8 = 0x0F;
+    pub const CONTEXT_CONF: u8 = 0x10;
+    pub const DEVICE_FFU_STATUS: u8 = 0x14;
+    pub const PSA_STATE: u8 = 0x15;
+    pub const PSA_DATA_SIZE: u8 = 0x16;
+}
+
+/// bPurgeStatus values
+pub mod purge_status {
+    pub const IDLE: u8 = 0x00;
+    pub const IN_PROGRESS: u8 = 0x01;
+    pub const STOPPED_PREMATURELY: u8 = 0x02;
+    pub const COMPLETED: u8 = 0x03;
+    pub const FAILED_QUEUE_NOT_EMPTY: u8 = 0x04;
+    pub const GENERAL_FAILURE: u8 = 0x05;
 }
 
 /// UFS descriptor type constants
@@ -631,6 +753,300 @@ impl GeometryDescriptor {
     }
 }
 
+/// Health Descriptor - wear and end-of-life information
+/// Size: 37 bytes (UFS 2.1), 45 bytes with refresh fields (UFS 3.0+)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HealthDescriptor {
+    /// Descriptor length
+    pub length: u8,
+    /// Descriptor type (should be 0x09)
+    pub descriptor_type: u8,
+    /// bPreEOLInfo - reserved block consumption
+    pub pre_eol_info: PreEolInfo,
+    /// bDeviceLifeTimeEstA - SLC area wear
+    pub life_time_est_a: LifeTimeEstimate,
+    /// bDeviceLifeTimeEstB - MLC/TLC area wear
+    pub life_time_est_b: LifeTimeEstimate,
+    /// bVendorPropInfo (32 bytes)
+    pub vendor_prop_info: Vec<u8>,
+    /// dRefreshTotalCount (UFS 3.0+)
+    pub refresh_total_count: Option<u32>,
+    /// dRefreshProgress (UFS 3.0+)
+    pub refresh_progress: Option<u32>,
+}
+
+impl HealthDescriptor {
+    /// Minimum descriptor length
+    pub const MIN_LENGTH: usize = 37;
+    /// Length including the refresh fields
+    pub const REFRESH_LENGTH: usize = 45;
+
+    /// Parse Health Descriptor from raw bytes
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::MIN_LENGTH {
+            return None;
+        }
+
+        // Verify descriptor type
+        if data[1] != descriptors::DEVICE_HEALTH {
+            return None;
+        }
+
+        let has_refresh =
+            data.len() >= Self::REFRESH_LENGTH && data[0] as usize >= Self::REFRESH_LENGTH;
+        let be32 = |o: usize| u32::from_be_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+
+        Some(Self {
+            length: data[0],
+            descriptor_type: data[1],
+            pre_eol_info: PreEolInfo::from_byte(data[2]),
+            life_time_est_a: LifeTimeEstimate::from_byte(data[3]),
+            life_time_est_b: LifeTimeEstimate::from_byte(data[4]),
+            vendor_prop_info: data[5..37].to_vec(),
+            refresh_total_count: has_refresh.then(|| be32(37)),
+            refresh_progress: has_refresh.then(|| be32(41)),
+        })
+    }
+
+    /// Health verdict from the pre-EOL and life time estimates
+    pub fn verdict(&self) -> EmmcHealthVerdict {
+        health_verdict(
+            self.pre_eol_info,
+            self.life_time_est_a,
+            self.life_time_est_b,
+        )
+    }
+}
+
+/// Number of unit configuration entries per Configuration Descriptor index
+pub const UNITS_PER_CONFIG_DESCRIPTOR: usize = 8;
+
+/// bMemoryType values
+pub mod memory_type {
+    pub const NORMAL: u8 = 0x00;
+    pub const SYSTEM_CODE: u8 = 0x01;
+    pub const NON_PERSISTENT: u8 = 0x02;
+    pub const ENHANCED_1: u8 = 0x03;
+    pub const ENHANCED_2: u8 = 0x04;
+    pub const ENHANCED_3: u8 = 0x05;
+    pub const ENHANCED_4: u8 = 0x06;
+}
+
+/// bLUWriteProtect values
+pub mod lu_write_protect {
+    pub const NONE: u8 = 0x00;
+    pub const POWER_ON: u8 = 0x01;
+    pub const PERMANENT: u8 = 0x02;
+}
+
+/// bBootLunID values
+pub mod boot_lun_id {
+    pub const NONE: u8 = 0x00;
+    pub const BOOT_A: u8 = 0x01;
+    pub const BOOT_B: u8 = 0x02;
+}
+
+/// Unit configuration entry of the Configuration Descriptor
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct UnitConfig {
+    /// LU enable flag
+    pub lu_enable: u8,
+    /// Boot LUN ID
+    pub boot_lun_id: u8,
+    /// LU write protect
+    pub lu_write_protect: u8,
+    /// Memory type
+    pub memory_type: u8,
+    /// Size in allocation units
+    pub num_alloc_units: u32,
+    /// Data reliability
+    pub data_reliability: u8,
+    /// Logical block size (2^n bytes)
+    pub logical_block_size: u8,
+    /// Provisioning type
+    pub provisioning_type: u8,
+    /// Context capabilities
+    pub context_capabilities: u16,
+    /// Remaining entry bytes (HPB / WriteBooster fields), kept verbatim
+    pub extra: Vec<u8>,
+}
+
+impl UnitConfig {
+    /// Fixed part of a unit configuration entry
+    pub const BASE_LENGTH: usize = 16;
+
+    fn parse(data: &[u8]) -> Self {
+        Self {
+            lu_enable: data[0],
+            boot_lun_id: data[1],
+            lu_write_protect: data[2],
+            memory_type: data[3],
+            num_alloc_units: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            data_reliability: data[8],
+            logical_block_size: data[9],
+            provisioning_type: data[10],
+            context_capabilities: u16::from_be_bytes([data[11], data[12]]),
+            extra: data[Self::BASE_LENGTH..].to_vec(),
+        }
+    }
+
+    fn write(&self, out: &mut [u8]) {
+        out[0] = self.lu_enable;
+        out[1] = self.boot_lun_id;
+        out[2] = self.lu_write_protect;
+        out[3] = self.memory_type;
+        out[4..8].copy_from_slice(&self.num_alloc_units.to_be_bytes());
+        out[8] = self.data_reliability;
+        out[9] = self.logical_block_size;
+        out[10] = self.provisioning_type;
+        out[11..13].copy_from_slice(&self.context_capabilities.to_be_bytes());
+        let extra = self.extra.len().min(out.len() - Self::BASE_LENGTH);
+        out[Self::BASE_LENGTH..Self::BASE_LENGTH + extra].copy_from_slice(&self.extra[..extra]);
+    }
+
+    /// Check if LUN is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.lu_enable != 0
+    }
+}
+
+/// Configuration Descriptor - writable LUN provisioning layout
+/// Size: 144 bytes (UFS 2.x), 230 bytes (UFS 3.1)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigurationDescriptor {
+    /// Descriptor length
+    pub length: u8,
+    /// Descriptor type (should be 0x01)
+    pub descriptor_type: u8,
+    /// Another Configuration Descriptor index follows
+    pub conf_desc_continue: u8,
+    /// Boot enable flag
+    pub boot_enable: u8,
+    /// Descriptor access enable
+    pub desc_access_enable: u8,
+    /// Initial power mode
+    pub init_power_mode: u8,
+    /// High priority LUN
+    pub high_priority_lun: u8,
+    /// Secure removal type
+    pub secure_removal_type: u8,
+    /// Initial active ICC level
+    pub init_active_icc_level: u8,
+    /// Periodic RTC update frequency
+    pub periodic_rtc_update: u16,
+    /// Remaining header bytes (HPB / WriteBooster fields), kept verbatim
+    pub header_extra: Vec<u8>,
+    /// Unit configuration entries (8 per index)
+    pub units: Vec<UnitConfig>,
+}
+
+impl ConfigurationDescriptor {
+    /// Header length (UFS 2.x)
+    pub const HEADER_LENGTH: usize = 0x10;
+    /// Header length (UFS 3.1)
+    pub const HEADER_LENGTH_V31: usize = 0x16;
+    /// Unit entry length (UFS 3.1)
+    pub const UNIT_LENGTH_V31: usize = 0x1A;
+
+    /// Parse Configuration Descriptor, inferring the header and entry size
+    /// from the descriptor length
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let len = (data.first().copied()? as usize).min(data.len());
+        [Self::HEADER_LENGTH, Self::HEADER_LENGTH_V31]
+            .iter()
+            .filter(|&&header| len > header)
+            .find(|&&header| {
+                let body = len - header;
+                body % UNITS_PER_CONFIG_DESCRIPTOR == 0
+                    && body / UNITS_PER_CONFIG_DESCRIPTOR >= UnitConfig::BASE_LENGTH
+            })
+            .and_then(|&header| {
+                let entry = (len - header) / UNITS_PER_CONFIG_DESCRIPTOR;
+                Self::parse_with_layout(&data[..len], header, entry)
+            })
+    }
+
+    /// Parse using the layout from the Device Descriptor
+    /// (bUD0BaseOffset / bUDConfigPLength)
+    pub fn parse_with_layout(data: &[u8], header_len: usize, unit_len: usize) -> Option<Self> {
+        if header_len < 11 || unit_len < UnitConfig::BASE_LENGTH {
+            return None;
+        }
+        if data.len() < header_len + unit_len * UNITS_PER_CONFIG_DESCRIPTOR {
+            return None;
+        }
+
+        // Verify descriptor type
+        if data[1] != descriptors::CONFIGURATION {
+            return None;
+        }
+
+        let units = (0..UNITS_PER_CONFIG_DESCRIPTOR)
+            .map(|i| {
+                let start = header_len + i * unit_len;
+                UnitConfig::parse(&data[start..start + unit_len])
+            })
+            .collect();
+
+        Some(Self {
+            length: data[0],
+            descriptor_type: data[1],
+            conf_desc_continue: data[2],
+            boot_enable: data[3],
+            desc_access_enable: data[4],
+            init_power_mode: data[5],
+            high_priority_lun: data[6],
+            secure_removal_type: data[7],
+            init_active_icc_level: data[8],
+            periodic_rtc_update: u16::from_be_bytes([data[9], data[10]]),
+            header_extra: data[11..header_len].to_vec(),
+            units,
+        })
+    }
+
+    /// Header length of this descriptor
+    pub fn header_length(&self) -> usize {
+        11 + self.header_extra.len()
+    }
+
+    /// Unit entry length of this descriptor
+    pub fn unit_length(&self) -> usize {
+        self.units
+            .iter()
+            .map(|u| UnitConfig::BASE_LENGTH + u.extra.len())
+            .max()
+            .unwrap_or(UnitConfig::BASE_LENGTH)
+    }
+
+    /// Serialize to bytes in the parsed layout
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_len = self.header_length();
+        let unit_len = self.unit_length();
+        let mut data = vec![0u8; header_len + unit_len * UNITS_PER_CONFIG_DESCRIPTOR];
+        data[0] = data.len() as u8;
+        data[1] = self.descriptor_type;
+        data[2] = self.conf_desc_continue;
+        data[3] = self.boot_enable;
+        data[4] = self.desc_access_enable;
+        data[5] = self.init_power_mode;
+        data[6] = self.high_priority_lun;
+        data[7] = self.secure_removal_type;
+        data[8] = self.init_active_icc_level;
+        data[9..11].copy_from_slice(&self.periodic_rtc_update.to_be_bytes());
+        data[11..header_len].copy_from_slice(&self.header_extra);
+        for (i, unit) in self
+            .units
+            .iter()
+            .take(UNITS_PER_CONFIG_DESCRIPTOR)
+            .enumerate()
+        {
+            let start = header_len + i * unit_len;
+            unit.write(&mut data[start..start + unit_len]);
+        }
+        data
+    }
+}
+
 // ============================================================================
 // SCSI Command Builders
 // ============================================================================
@@ -758,6 +1174,68 @@ impl ScsiCdbBuilder {
         cdb
     }
 
+    /// Build UNMAP CDB
+    /// Returns 10-byte CDB; the parameter list follows in the data-out phase
+    pub fn build_unmap(parameter_list_length: u16) -> [u8; 10] {
+        let mut cdb = [0u8; 10];
+        cdb[0] = scsi::UNMAP;
+        cdb[7..9].copy_from_slice(&parameter_list_length.to_be_bytes());
+        cdb
+    }
+
+    /// Build UNMAP parameter list for (LBA, block count) ranges
+    pub fn build_unmap_parameter_list(ranges: &[(u64, u32)]) -> Vec<u8> {
+        let descriptors_len = (ranges.len() * 16) as u16;
+        let mut data = Vec::with_capacity(8 + descriptors_len as usize);
+        data.extend_from_slice(&(descriptors_len + 6).to_be_bytes());
+        data.extend_from_slice(&descriptors_len.to_be_bytes());
+        data.extend_from_slice(&[0u8; 4]);
+        for &(lba, count) in ranges {
+            data.extend_from_slice(&lba.to_be_bytes());
+            data.extend_from_slice(&count.to_be_bytes());
+            data.extend_from_slice(&[0u8; 4]);
+        }
+        data
+    }
+
+    /// Build FORMAT UNIT CDB (default format, no parameter list)
+    /// Returns 6-byte CDB
+    pub fn build_format_unit() -> [u8; 6] {
+        let mut cdb = [0u8; 6];
+        cdb[0] = scsi::FORMAT_UNIT;
+        cdb
+    }
+
+    /// Build SECURITY PROTOCOL IN CDB (RPMB response frames)
+    /// Returns 12-byte CDB
+    pub fn build_security_protocol_in(
+        protocol: u8,
+        protocol_specific: u16,
+        allocation_length: u32,
+    ) -> [u8; 12] {
+        let mut cdb = [0u8; 12];
+        cdb[0] = scsi::SECURITY_PROTOCOL_IN;
+        cdb[1] = protocol;
+        cdb[2..4].copy_from_slice(&protocol_specific.to_be_bytes());
+        cdb[6..10].copy_from_slice(&allocation_length.to_be_bytes());
+        cdb
+    }
+
+    /// Build SECURITY PROTOCOL OUT CDB (RPMB request frames)
+    /// Returns 12-byte CDB
+    pub fn build_security_protocol_out(
+        protocol: u8,
+        protocol_specific: u16,
+        transfer_length: u32,
+    ) -> [u8; 12] {
+        let mut cdb = [0u8; 12];
+        cdb[0] = scsi::SECURITY_PROTOCOL_OUT;
+        cdb[1] = protocol;
+        cdb[2..4].copy_from_slice(&protocol_specific.to_be_bytes());
+        cdb[6..10].copy_from_slice(&transfer_length.to_be_bytes());
+        cdb
+    }
+
     /// Extract LBA from READ(10) CDB
     pub fn extract_lba_from_read10(cdb: &[u8; 10]) -> u32 {
         u32::from_be_bytes([cdb[2], cdb[3], cdb[4], cdb[5]])
@@ -826,6 +1304,8 @@ pub enum UfsError {
     InvalidCommand,
     /// Device busy
     DeviceBusy,
+    /// Query request failed with a query response code
+    QueryError(u8),
 }
 
 impl UfsError {
@@ -895,6 +1375,23 @@ impl UfsError {
             UfsError::DescriptorParseError => "Failed to parse descriptor",
             UfsError::InvalidCommand => "Invalid command",
             UfsError::DeviceBusy => "Device is busy",
+            UfsError::QueryError(code) => Self::query_response_description(*code),
+        }
+    }
+
+    /// Get query response code description
+    fn query_response_description(code: u8) -> &'static str {
+        match code {
+            0xF6 => "Query parameter not readable",
+            0xF7 => "Query parameter not writeable",
+            0xF8 => "Query parameter already written",
+            0xF9 => "Query invalid length",
+            0xFA => "Query invalid value",
+            0xFB => "Query invalid selector",
+            0xFC => "Query invalid index",
+            0xFD => "Query invalid IDN",
+            0xFE => "Query invalid opcode",
+            _ => "Query general failure",
         }
     }
 
@@ -1024,6 +1521,80 @@ mod tests {
         assert_eq!(UfsLun::from_lun_id(0xFF), None);
     }
 
+    #[test]
+    fn test_ufs_lun_extended() {
+        assert_eq!(UfsLun::from_lun_id(0x05), Some(UfsLun::Logical(5)));
+        assert_eq!(UfsLun::from_lun_id(0x1F), Some(UfsLun::Logical(31)));
+        assert_eq!(UfsLun::from_lun_id(0x20), None);
+        assert_eq!(UfsLun::from_lun_id(0x81), Some(UfsLun::ReportLuns));
+        assert_eq!(UfsLun::from_lun_id(0xB0), Some(UfsLun::Boot));
+        assert_eq!(UfsLun::from_lun_id(0xD0), Some(UfsLun::UfsDevice));
+        assert_eq!(UfsLun::Logical(7).to_lun_id(), 7);
+        assert!(UfsLun::Rpmb.is_well_known());
+        assert!(!UfsLun::Logical(7).is_well_known());
+    }
+
+    #[test]
+    fn test_ufs_lun_logical_constructor() {
+        assert_eq!(UfsLun::logical(1), Some(UfsLun::BootA));
+        assert!(matches!(UfsLun::logical(1), Some(UfsLun::BootA)));
+        assert!(matches!(UfsLun::logical(3), Some(UfsLun::Logical(3))));
+        assert_eq!(UfsLun::logical(MAX_LUNS), None);
+        assert_eq!(UfsLun::logical(wlun::RPMB), None);
+        assert_eq!(UfsLun::Logical(1), UfsLun::BootA);
+        assert_ne!(UfsLun::Logical(4), UfsLun::Logical(5));
+    }
+
+    #[test]
+    fn test_health_descriptor_parse() {
+        let mut data = vec![0u8; HealthDescriptor::REFRESH_LENGTH];
+        data[0] = HealthDescriptor::REFRESH_LENGTH as u8;
+        data[1] = descriptors::DEVICE_HEALTH;
+        data[2] = 0x02; // pre-EOL warning
+        data[3] = 0x01;
+        data[4] = 0x08;
+        data[37..41].copy_from_slice(&3u32.to_be_bytes());
+
+        let desc = HealthDescriptor::parse(&data).unwrap();
+        assert_eq!(desc.pre_eol_info, PreEolInfo::Warning);
+        assert_eq!(desc.life_time_est_b.max_percent(), Some(80));
+        assert_eq!(desc.refresh_total_count, Some(3));
+        assert_eq!(desc.verdict(), EmmcHealthVerdict::Worn);
+
+        let desc = HealthDescriptor::parse(&data[..HealthDescriptor::MIN_LENGTH]).unwrap();
+        assert_eq!(desc.refresh_total_count, None);
+        data[1] = descriptors::GEOMETRY;
+        assert!(HealthDescriptor::parse(&data).is_none());
+    }
+
+    #[test]
+    fn test_configuration_descriptor_roundtrip() {
+        for (header, unit) in [
+            (ConfigurationDescriptor::HEADER_LENGTH, UnitConfig::BASE_LENGTH),
+            (
+                ConfigurationDescriptor::HEADER_LENGTH_V31,
+                ConfigurationDescriptor::UNIT_LENGTH_V31,
+            ),
+        ] {
+            let len = header + unit * UNITS_PER_CONFIG_DESCRIPTOR;
+            let mut data = vec![0u8; len];
+            data[0] = len as u8;
+            data[1] = descriptors::CONFIGURATION;
+            data[3] = 1; // boot enable
+            data[header] = 1; // LU0 enable
+            data[header + 4..header + 8].copy_from_slice(&0x1000u32.to_be_bytes());
+            data[header + unit + 1] = boot_lun_id::BOOT_A;
+
+            let desc = ConfigurationDescriptor::parse(&data).unwrap();
+            assert_eq!(desc.header_length(), header);
+            assert_eq!(desc.unit_length(), unit);
+            assert!(desc.units[0].is_enabled());
+            assert_eq!(desc.units[0].num_alloc_units, 0x1000);
+            assert_eq!(desc.units[1].boot_lun_id, boot_lun_id::BOOT_A);
+            assert_eq!(desc.to_bytes(), data);
+        }
+    }
+
     #[test]
     fn test_manufacturer_names() {
         assert_eq!(get_ufs_manufacturer_name(manufacturers::SAMSUNG), "Samsung");
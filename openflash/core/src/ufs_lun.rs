@@ -0,0 +1,709 @@
+//! UFS logical unit management
+//!
+//! Enumerates all logical units of a UFS device through their Unit
+//! descriptors, reads the Health descriptor, reprovisions the LUN layout
+//! through the Configuration descriptor and switches bBootLunEn.
+//!
+//! Everything is expressed as QUERY REQUEST UPIUs; the manager performs no
+//! I/O. Writing the Configuration descriptor wipes the affected LUNs (and is
+//! permanent once bConfigDescrLock is set), so it is only produced after an
+//! explicit confirmation string.
+
+use crate::ufs::{
+    attributes, boot_lun_id, descriptors, lu_write_protect, memory_type, ConfigurationDescriptor,
+    DeviceDescriptor, GeometryDescriptor, HealthDescriptor, UfsLun, UnitDescriptor, MAX_LUNS,
+    UNITS_PER_CONFIG_DESCRIPTOR,
+};
+use crate::ufs_upiu::{QueryFields, Upiu};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// ============================================================================
+// Error Types
+// ============================================================================
+
+/// UFS logical unit errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UfsLunError {
+    /// LUN index outside the supported range
+    InvalidLun(u8),
+    /// Descriptor data is malformed
+    InvalidDescriptor(u8),
+    /// Configuration descriptor index outside the supported range
+    InvalidConfigIndex(u8),
+    /// bConfigDescrLock is set, the layout can no longer be changed
+    ConfigLocked,
+    /// Memory type not supported by the device
+    UnsupportedMemoryType(u8),
+    /// bBootLunID value is not Boot LU A or B
+    InvalidBootLunId(u8),
+    /// More than one LUN claims the same boot LUN ID
+    DuplicateBootLun(u8),
+    /// No enabled LUN carries the requested boot LUN ID
+    BootLunMissing(BootLunEnable),
+    /// Requested layout does not fit in the device
+    ExceedsCapacity { requested: u64, capacity: u64 },
+    /// Reprovisioning attempted without confirmation
+    ConfirmationRequired,
+}
+
+impl fmt::Display for UfsLunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UfsLunError::InvalidLun(lun) => write!(f, "Invalid logical unit {}", lun),
+            UfsLunError::InvalidDescriptor(idn) => {
+                write!(f, "Malformed descriptor (IDN 0x{:02X})", idn)
+            }
+            UfsLunError::InvalidConfigIndex(index) => {
+                write!(f, "Invalid configuration descriptor index {}", index)
+            }
+            UfsLunError::ConfigLocked => write!(f, "Configuration descriptor is locked"),
+            UfsLunError::UnsupportedMemoryType(t) => {
+                write!(f, "Memory type {} not supported by device", t)
+            }
+            UfsLunError::InvalidBootLunId(id) => write!(f, "Invalid boot LUN ID {}", id),
+            UfsLunError::DuplicateBootLun(id) => {
+                write!(f, "Boot LUN ID {} assigned to more than one LUN", id)
+            }
+            UfsLunError::BootLunMissing(boot) => {
+                write!(f, "No enabled LUN is configured as {}", boot.as_str())
+            }
+            UfsLunError::ExceedsCapacity {
+                requested,
+                capacity,
+            } => write!(
+                f,
+                "LUN layout needs {} allocation units, device has {}",
+                requested, capacity
+            ),
+            UfsLunError::ConfirmationRequired => {
+                write!(f, "LUN reprovisioning requires explicit confirmation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UfsLunError {}
+
+pub type UfsLunResult<T> = Result<T, UfsLunError>;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Confirmation string required to write the Configuration descriptor
+pub const UFS_PROVISION_CONFIRMATION: &str = "REPROVISION UFS LUNS";
+
+/// Read length used for descriptor queries (device returns its actual length)
+pub const DESCRIPTOR_READ_LENGTH: u16 = 0xFF;
+
+/// Capacity adjustment factor of normal memory (1.0 in 1/256 units)
+const CAP_ADJ_FAC_NORMAL: u64 = 0x100;
+
+// ============================================================================
+// Boot LUN Selection
+// ============================================================================
+
+/// bBootLunEn attribute value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootLunEnable {
+    /// Boot disabled
+    Disabled,
+    /// Boot from the LUN with bBootLunID = Boot LU A
+    BootA,
+    /// Boot from the LUN with bBootLunID = Boot LU B
+    BootB,
+    Reserved(u8),
+}
+
+impl BootLunEnable {
+    pub fn from_attribute(value: u32) -> Self {
+        match value {
+            0 => BootLunEnable::Disabled,
+            1 => BootLunEnable::BootA,
+            2 => BootLunEnable::BootB,
+            n => BootLunEnable::Reserved(n as u8),
+        }
+    }
+
+    pub fn to_attribute(&self) -> u32 {
+        match self {
+            BootLunEnable::Disabled => 0,
+            BootLunEnable::BootA => 1,
+            BootLunEnable::BootB => 2,
+            BootLunEnable::Reserved(n) => *n as u32,
+        }
+    }
+
+    /// bBootLunID a LUN must carry to be selected
+    pub fn boot_lun_id(&self) -> Option<u8> {
+        match self {
+            BootLunEnable::BootA => Some(boot_lun_id::BOOT_A),
+            BootLunEnable::BootB => Some(boot_lun_id::BOOT_B),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootLunEnable::Disabled => "Disabled",
+            BootLunEnable::BootA => "Boot LU A",
+            BootLunEnable::BootB => "Boot LU B",
+            BootLunEnable::Reserved(_) => "Reserved",
+        }
+    }
+}
+
+// ============================================================================
+// Logical Units
+// ============================================================================
+
+/// Logical unit summary from its Unit descriptor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UfsLogicalUnit {
+    pub lun: UfsLun,
+    pub enabled: bool,
+    /// bBootLunID (0 = not a boot LUN)
+    pub boot_lun_id: u8,
+    /// bLUWriteProtect
+    pub write_protect: u8,
+    pub memory_type: u8,
+    pub block_size: u32,
+    pub block_count: u64,
+    pub capacity_bytes: u64,
+    pub provisioning_type: u8,
+}
+
+impl UfsLogicalUnit {
+    pub fn from_unit_descriptor(desc: &UnitDescriptor) -> Option<Self> {
+        Some(Self {
+            lun: UfsLun::logical(desc.unit_index)?,
+            enabled: desc.is_enabled(),
+            boot_lun_id: desc.boot_lun_id,
+            write_protect: desc.lu_write_protect,
+            memory_type: desc.memory_type,
+            block_size: desc.get_block_size_bytes(),
+            block_count: desc.logical_block_count,
+            capacity_bytes: desc.get_capacity_bytes(),
+            provisioning_type: desc.provisioning_type,
+        })
+    }
+
+    /// Human-readable write-protect state
+    pub fn write_protect_str(&self) -> &'static str {
+        match self.write_protect {
+            lu_write_protect::NONE => "None",
+            lu_write_protect::POWER_ON => "Power-on",
+            lu_write_protect::PERMANENT => "Permanent",
+            _ => "Reserved",
+        }
+    }
+}
+
+// ============================================================================
+// LUN Manager
+// ============================================================================
+
+/// UFS logical unit manager
+#[derive(Debug, Clone)]
+pub struct UfsLunManager {
+    device: DeviceDescriptor,
+    geometry: GeometryDescriptor,
+    units: Vec<UfsLogicalUnit>,
+    boot_lun_en: Option<BootLunEnable>,
+    config_locked: bool,
+    next_tag: u8,
+}
+
+impl UfsLunManager {
+    pub fn new(device: DeviceDescriptor, geometry: GeometryDescriptor) -> Self {
+        Self {
+            device,
+            geometry,
+            units: Vec::new(),
+            boot_lun_en: None,
+            config_locked: false,
+            next_tag: 0,
+        }
+    }
+
+    fn query(&mut self, fields: QueryFields) -> Upiu {
+        let tag = self.next_tag;
+        self.next_tag = self.next_tag.wrapping_add(1);
+        Upiu::query(tag, fields)
+    }
+
+    /// Number of logical units the device supports (bMaxNumberLU)
+    pub fn max_luns(&self) -> u8 {
+        match self.geometry.max_num_luns {
+            0 => 8,
+            1 => MAX_LUNS,
+            n => n.min(MAX_LUNS),
+        }
+    }
+
+    /// Number of Configuration descriptor indices covering all LUNs
+    pub fn config_descriptor_count(&self) -> u8 {
+        (self.max_luns() / UNITS_PER_CONFIG_DESCRIPTOR as u8).max(1)
+    }
+
+    /// Allocation unit size in bytes
+    /// (bAllocationUnitSize segments of dSegmentSize 512-byte units)
+    pub fn allocation_unit_bytes(&self) -> u64 {
+        self.geometry.allocation_unit_size as u64 * self.geometry.segment_size as u64 * 512
+    }
+
+    /// Total allocation units available for LUNs
+    pub fn total_allocation_units(&self) -> u64 {
+        match self.allocation_unit_bytes() {
+            0 => 0,
+            unit => self.geometry.get_total_capacity_bytes() / unit,
+        }
+    }
+
+    /// Allocation units needed for `bytes` of normal memory, rounded up
+    pub fn allocation_units_for(&self, bytes: u64) -> u32 {
+        match self.allocation_unit_bytes() {
+            0 => 0,
+            unit => ((bytes + unit - 1) / unit) as u32,
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // Enumeration
+    // ------------------------------------------------------------------------
+
+    /// Queries reading every Unit descriptor plus bBootLunEn and
+    /// bConfigDescrLock
+    pub fn enumerate(&mut self) -> Vec<Upiu> {
+        let mut requests: Vec<Upiu> = (0..self.max_luns())
+            .map(|lun| {
+                self.query(QueryFields::read_descriptor(
+                    descriptors::UNIT,
+                    lun,
+                    0,
+                    DESCRIPTOR_READ_LENGTH,
+                ))
+            })
+            .collect();
+        requests.push(self.read_boot_lun_en());
+        requests.push(self.query(QueryFields::read_attribute(
+            attributes::CONFIG_DESCR_LOCK,
+            0,
+            0,
+        )));
+        requests
+    }
+
+    /// Record a Unit descriptor read back during enumeration
+    pub fn add_unit_descriptor(&mut self, data: &[u8]) -> UfsLunResult<&UfsLogicalUnit> {
+        let unit = UnitDescriptor::parse(data)
+            .and_then(|desc| UfsLogicalUnit::from_unit_descriptor(&desc))
+            .ok_or(UfsLunError::InvalidDescriptor(descriptors::UNIT))?;
+        let id = unit.lun.to_lun_id();
+        if id >= self.max_luns() {
+            return Err(UfsLunError::InvalidLun(id));
+        }
+        self.units.retain(|u| u.lun != unit.lun);
+        self.units.push(unit);
+        self.units.sort_by_key(|u| u.lun.to_lun_id());
+        Ok(self
+            .units
+            .iter()
+            .find(|u| u.lun.to_lun_id() == id)
+            .expect("unit just inserted"))
+    }
+
+    /// Record the bConfigDescrLock attribute
+    pub fn set_config_locked(&mut self, value: u32) {
+        self.config_locked = value != 0;
+    }
+
+    /// All enumerated logical units, enabled or not
+    pub fn units(&self) -> &[UfsLogicalUnit] {
+        &self.units
+    }
+
+    /// Enabled logical units
+    pub fn enabled_units(&self) -> Vec<&UfsLogicalUnit> {
+        self.units.iter().filter(|u| u.enabled).collect()
+    }
+
+    /// Logical unit carrying a boot LUN ID
+    pub fn boot_unit(&self, boot: BootLunEnable) -> Option<&UfsLogicalUnit> {
+        let id = boot.boot_lun_id()?;
+        self.units.iter().find(|u| u.enabled && u.boot_lun_id == id)
+    }
+
+    /// Logical unit the device currently boots from
+    pub fn active_boot_unit(&self) -> Option<&UfsLogicalUnit> {
+        self.boot_unit(self.boot_lun_en?)
+    }
+
+    // ------------------------------------------------------------------------
+    // Health
+    // ------------------------------------------------------------------------
+
+    /// Query reading the Health descriptor
+    pub fn read_health(&mut self) -> Upiu {
+        self.query(QueryFields::read_descriptor(
+            descriptors::DEVICE_HEALTH,
+            0,
+            0,
+            DESCRIPTOR_READ_LENGTH,
+        ))
+    }
+
+    /// Parse a Health descriptor read back with `read_health`
+    pub fn parse_health(data: &[u8]) -> UfsLunResult<HealthDescriptor> {
+        HealthDescriptor::parse(data)
+            .ok_or(UfsLunError::InvalidDescriptor(descriptors::DEVICE_HEALTH))
+    }
+
+    // ------------------------------------------------------------------------
+    // Boot LUN
+    // ------------------------------------------------------------------------
+
+    /// Query reading bBootLunEn
+    pub fn read_boot_lun_en(&mut self) -> Upiu {
+        self.query(QueryFields::read_attribute(attributes::BOOT_LUN_EN, 0, 0))
+    }
+
+    /// Record the bBootLunEn attribute
+    pub fn set_boot_lun_en(&mut self, value: u32) {
+        self.boot_lun_en = Some(BootLunEnable::from_attribute(value));
+    }
+
+    /// Current bBootLunEn, if read
+    pub fn boot_lun_en(&self) -> Option<BootLunEnable> {
+        self.boot_lun_en
+    }
+
+    /// Query switching bBootLunEn
+    ///
+    /// The target must be an enabled LUN with the matching bBootLunID, so a
+    /// phone is never left pointing at a boot LUN that does not exist.
+    pub fn select_boot_lun(&mut self, boot: BootLunEnable) -> UfsLunResult<Upiu> {
+        if boot != BootLunEnable::Disabled && self.boot_unit(boot).is_none() {
+            return Err(UfsLunError::BootLunMissing(boot));
+        }
+        self.boot_lun_en = Some(boot);
+        Ok(self.query(QueryFields::write_attribute(
+            attributes::BOOT_LUN_EN,
+            0,
+            0,
+            boot.to_attribute(),
+        )))
+    }
+
+    // ------------------------------------------------------------------------
+    // Provisioning
+    // ------------------------------------------------------------------------
+
+    /// Query reading Configuration descriptor `index` (LUNs index*8..index*8+7)
+    pub fn read_configuration(&mut self, index: u8) -> UfsLunResult<Upiu> {
+        if index >= self.config_descriptor_count() {
+            return Err(UfsLunError::InvalidConfigIndex(index));
+        }
+        Ok(self.query(QueryFields::read_descriptor(
+            descriptors::CONFIGURATION,
+            index,
+            0,
+            DESCRIPTOR_READ_LENGTH,
+        )))
+    }
+
+    /// Parse a Configuration descriptor using the Device descriptor layout
+    pub fn parse_configuration(&self, data: &[u8]) -> UfsLunResult<ConfigurationDescriptor> {
+        let header = self.device.ud0_base_offset as usize;
+        let unit = self.device.ud_config_p_length as usize;
+        let parsed = if header != 0 && unit != 0 {
+            ConfigurationDescriptor::parse_with_layout(data, header, unit)
+        } else {
+            ConfigurationDescriptor::parse(data)
+        };
+        parsed.ok_or(UfsLunError::InvalidDescriptor(descriptors::CONFIGURATION))
+    }
+
+    /// Allocation units consumed by a unit, applying the capacity adjustment
+    /// factor of its memory type
+    fn consumed_units(&self, memory: u8, alloc_units: u32) -> UfsLunResult<u64> {
+        let factor = match memory {
+            memory_type::NORMAL => CAP_ADJ_FAC_NORMAL,
+            memory_type::SYSTEM_CODE => self.geometry.sys_code_cap_adj_fac as u64,
+            memory_type::NON_PERSISTENT => self.geometry.non_persist_cap_adj_fac as u64,
+            memory_type::ENHANCED_1 => self.geometry.enh1_cap_adj_fac as u64,
+            memory_type::ENHANCED_2 => self.geometry.enh2_cap_adj_fac as u64,
+            other => return Err(UfsLunError::UnsupportedMemoryType(other)),
+        };
+        if factor == 0 || self.geometry.supported_memory_types & (1 << memory) == 0 {
+            return Err(UfsLunError::UnsupportedMemoryType(memory));
+        }
+        Ok(alloc_units as u64 * factor / CAP_ADJ_FAC_NORMAL)
+    }
+
+    /// Check a full LUN layout (all Configuration descriptor indices)
+    pub fn validate_layout(&self, configs: &[ConfigurationDescriptor]) -> UfsLunResult<()> {
+        if self.config_locked {
+            return Err(UfsLunError::ConfigLocked);
+        }
+        let max_luns = self.max_luns() as usize;
+        let mut requested = 0u64;
+        let mut boot_ids = [false; 3];
+
+        for (lun, unit) in configs
+            .iter()
+            .flat_map(|c| c.units.iter().take(UNITS_PER_CONFIG_DESCRIPTOR))
+            .enumerate()
+            .filter(|(_, u)| u.is_enabled())
+        {
+            if lun >= max_luns {
+                return Err(UfsLunError::InvalidLun(lun as u8));
+            }
+            requested += self.consumed_units(unit.memory_type, unit.num_alloc_units)?;
+            match unit.boot_lun_id {
+                boot_lun_id::NONE => {}
+                id @ (boot_lun_id::BOOT_A | boot_lun_id::BOOT_B) => {
+                    if boot_ids[id as usize] {
+                        return Err(UfsLunError::DuplicateBootLun(id));
+                    }
+                    boot_ids[id as usize] = true;
+                }
+                id => return Err(UfsLunError::InvalidBootLunId(id)),
+            }
+        }
+
+        let capacity = self.total_allocation_units();
+        if requested > capacity {
+            return Err(UfsLunError::ExceedsCapacity {
+                requested,
+                capacity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Queries writing a new LUN layout
+    ///
+    /// `configs[i]` is written to Configuration descriptor index `i`. This
+    /// erases the reprovisioned LUNs, so `confirmation` must equal
+    /// `UFS_PROVISION_CONFIRMATION`. When `lock` is set bConfigDescrLock is
+    /// written afterwards and the layout becomes permanent.
+    pub fn provision(
+        &mut self,
+        configs: &[ConfigurationDescriptor],
+        lock: bool,
+        confirmation: &str,
+    ) -> UfsLunResult<Vec<Upiu>> {
+        if confirmation != UFS_PROVISION_CONFIRMATION {
+            return Err(UfsLunError::ConfirmationRequired);
+        }
+        if configs.len() > self.config_descriptor_count() as usize {
+            return Err(UfsLunError::InvalidConfigIndex(configs.len() as u8 - 1));
+        }
+        self.validate_layout(configs)?;
+
+        let last = configs.len().saturating_sub(1);
+        let mut requests: Vec<Upiu> = configs
+            .iter()
+            .enumerate()
+            .map(|(index, config)| {
+                let mut config = config.clone();
+                config.descriptor_type = descriptors::CONFIGURATION;
+                // bConfDescContinue: only the last write commits the layout
+                config.conf_desc_continue = (index != last) as u8;
+                self.query(QueryFields::write_descriptor(
+                    descriptors::CONFIGURATION,
+                    index as u8,
+                    0,
+                    config.to_bytes(),
+                ))
+            })
+            .collect();
+
+        if lock {
+            requests.push(self.query(QueryFields::write_attribute(
+                attributes::CONFIG_DESCR_LOCK,
+                0,
+                0,
+                1,
+            )));
+            self.config_locked = true;
+        }
+        // Unit descriptors are stale until re-enumerated
+        self.units.clear();
+        Ok(requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ufs::UnitConfig;
+    use crate::ufs_upiu::QueryOpcode;
+
+    fn manager() -> UfsLunManager {
+        let mut dev = vec![0u8; DeviceDescriptor::MIN_LENGTH];
+        dev[0] = 32;
+        dev[1] = descriptors::DEVICE;
+        dev[26] = ConfigurationDescriptor::HEADER_LENGTH as u8;
+        dev[27] = UnitConfig::BASE_LENGTH as u8;
+
+        let mut geo = vec![0u8; GeometryDescriptor::MIN_LENGTH];
+        geo[0] = 72;
+        geo[1] = descriptors::GEOMETRY;
+        // 64 GiB raw, 4 MiB allocation units (1 segment of 8192 sectors)
+        geo[4..12].copy_from_slice(&(64u64 << 21).to_be_bytes());
+        geo[12] = MAX_LUNS;
+        geo[13..17].copy_from_slice(&8192u32.to_be_bytes());
+        geo[17] = 1;
+        geo[30..32].copy_from_slice(&0x8009u16.to_be_bytes()); // normal, enh1
+        geo[48..50].copy_from_slice(&0x0300u16.to_be_bytes()); // enh1 = 3x
+
+        UfsLunManager::new(
+            DeviceDescriptor::parse(&dev).unwrap(),
+            GeometryDescriptor::parse(&geo).unwrap(),
+        )
+    }
+
+    fn unit_descriptor(lun: u8, enabled: bool, boot: u8, blocks: u64) -> Vec<u8> {
+        let mut data = vec![0u8; UnitDescriptor::MIN_LENGTH];
+        data[0] = 45;
+        data[1] = descriptors::UNIT;
+        data[2] = lun;
+        data[3] = enabled as u8;
+        data[4] = boot;
+        data[10] = 12;
+        data[11..19].copy_from_slice(&blocks.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_enumerate_and_boot_switch() {
+        let mut mgr = manager();
+        assert_eq!(mgr.max_luns(), 32);
+        assert_eq!(mgr.config_descriptor_count(), 4);
+        assert_eq!(mgr.total_allocation_units(), 64 * 256);
+
+        let requests = mgr.enumerate();
+        assert_eq!(requests.len(), 34);
+        match &requests[31] {
+            Upiu::QueryRequest { fields, .. } => {
+                assert_eq!(fields.opcode, QueryOpcode::ReadDescriptor);
+                assert_eq!(fields.idn, descriptors::UNIT);
+                assert_eq!(fields.index, 31);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        mgr.add_unit_descriptor(&unit_descriptor(0, true, 0, 1 << 20))
+            .unwrap();
+        mgr.add_unit_descriptor(&unit_descriptor(1, true, boot_lun_id::BOOT_A, 1024))
+            .unwrap();
+        let lun = mgr
+            .add_unit_descriptor(&unit_descriptor(20, true, boot_lun_id::BOOT_B, 1024))
+            .unwrap();
+        assert_eq!(lun.lun, UfsLun::Logical(20));
+        mgr.add_unit_descriptor(&unit_descriptor(5, false, 0, 0))
+            .unwrap();
+        assert_eq!(mgr.units().len(), 4);
+        assert_eq!(mgr.enabled_units().len(), 3);
+
+        mgr.set_boot_lun_en(1);
+        assert_eq!(mgr.active_boot_unit().unwrap().lun, UfsLun::BootA);
+
+        let upiu = mgr.select_boot_lun(BootLunEnable::BootB).unwrap();
+        match upiu {
+            Upiu::QueryRequest { fields, .. } => {
+                assert_eq!(fields.idn, attributes::BOOT_LUN_EN);
+                assert_eq!(fields.value, 2);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(mgr.active_boot_unit().unwrap().lun, UfsLun::Logical(20));
+        assert_eq!(
+            mgr.select_boot_lun(BootLunEnable::Reserved(3)),
+            Err(UfsLunError::BootLunMissing(BootLunEnable::Reserved(3)))
+        );
+    }
+
+    #[test]
+    fn test_provision_validation() {
+        let mut mgr = manager();
+        let len = ConfigurationDescriptor::HEADER_LENGTH
+            + UnitConfig::BASE_LENGTH * UNITS_PER_CONFIG_DESCRIPTOR;
+        let mut raw = vec![0u8; len];
+        raw[0] = len as u8;
+        raw[1] = descriptors::CONFIGURATION;
+        let mut config = mgr.parse_configuration(&raw).unwrap();
+
+        config.units[0] = UnitConfig {
+            lu_enable: 1,
+            num_alloc_units: mgr.allocation_units_for(32 << 30),
+            logical_block_size: 12,
+            ..Default::default()
+        };
+        config.units[1] = UnitConfig {
+            lu_enable: 1,
+            boot_lun_id: boot_lun_id::BOOT_A,
+            memory_type: memory_type::ENHANCED_1,
+            num_alloc_units: 16,
+            logical_block_size: 12,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            mgr.provision(&[config.clone()], false, "yes"),
+            Err(UfsLunError::ConfirmationRequired)
+        );
+
+        let requests = mgr
+            .provision(&[config.clone()], true, UFS_PROVISION_CONFIRMATION)
+            .unwrap();
+        assert_eq!(requests.len(), 2);
+        match &requests[0] {
+            Upiu::QueryRequest { fields, .. } => {
+                assert_eq!(fields.opcode, QueryOpcode::WriteDescriptor);
+                assert_eq!(fields.data.len(), len);
+                assert_eq!(fields.data[2], 0); // bConfDescContinue
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            mgr.validate_layout(&[config.clone()]),
+            Err(UfsLunError::ConfigLocked)
+        );
+
+        let mut mgr = manager();
+        config.units[2] = config.units[1].clone();
+        assert_eq!(
+            mgr.validate_layout(&[config.clone()]),
+            Err(UfsLunError::DuplicateBootLun(boot_lun_id::BOOT_A))
+        );
+        config.units[2] = UnitConfig {
+            lu_enable: 1,
+            num_alloc_units: mgr.allocation_units_for(40 << 30),
+            ..Default::default()
+        };
+        assert!(matches!(
+            mgr.provision(&[config.clone()], false, UFS_PROVISION_CONFIRMATION),
+            Err(UfsLunError::ExceedsCapacity { .. })
+        ));
+        config.units[2].memory_type = memory_type::ENHANCED_3;
+        assert_eq!(
+            mgr.validate_layout(&[config]),
+            Err(UfsLunError::UnsupportedMemoryType(memory_type::ENHANCED_3))
+        );
+    }
+
+    #[test]
+    fn test_parse_health() {
+        let mut data = vec![0u8; HealthDescriptor::MIN_LENGTH];
+        data[0] = HealthDescriptor::MIN_LENGTH as u8;
+        data[1] = descriptors::DEVICE_HEALTH;
+        data[2] = 0x03;
+        let health = UfsLunManager::parse_health(&data).unwrap();
+        assert_eq!(health.verdict(), crate::emmc::EmmcHealthVerdict::Critical);
+        assert!(UfsLunManager::parse_health(&data[..10]).is_err());
+    }
+}
@@ -0,0 +1,902 @@
+//! UFS Protocol Information Units (UPIU)
+//!
+//! Encodes and decodes the UPIUs exchanged with a UFS device: NOP OUT/IN,
+//! COMMAND, DATA OUT/IN, RESPONSE, READY TO TRANSFER, TASK MANAGEMENT
+//! REQUEST/RESPONSE, QUERY REQUEST/RESPONSE and REJECT.
+//!
+//! `QueryRequest` covers descriptor, attribute and flag access. Failed
+//! RESPONSE UPIUs are turned into `UfsError` through the SCSI status and
+//! `UfsError::from_sense_data`; failed QUERY RESPONSE UPIUs map to
+//! `UfsError::QueryError` with the query response code.
+//!
+//! The `UfsUpiu` packet that frames an exchange is host-only
+//! ([`Command::is_host_only`]): no programmer firmware handles it yet, so
+//! this module is planning code for a future UFS bridge.
+
+use crate::protocol::{Command, Packet};
+use crate::ufs::UfsError;
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// UPIU header size
+pub const UPIU_HEADER_SIZE: usize = 12;
+
+/// Size of a UPIU without data segment (header + transaction fields)
+pub const UPIU_BASE_SIZE: usize = 32;
+
+/// UPIU transaction codes
+pub mod transaction {
+    // Initiator to target
+    pub const NOP_OUT: u8 = 0x00;
+    pub const COMMAND: u8 = 0x01;
+    pub const DATA_OUT: u8 = 0x02;
+    pub const TASK_MGMT_REQUEST: u8 = 0x04;
+    pub const QUERY_REQUEST: u8 = 0x16;
+
+    // Target to initiator
+    pub const NOP_IN: u8 = 0x20;
+    pub const RESPONSE: u8 = 0x21;
+    pub const DATA_IN: u8 = 0x22;
+    pub const TASK_MGMT_RESPONSE: u8 = 0x24;
+    pub const READY_TO_TRANSFER: u8 = 0x31;
+    pub const QUERY_RESPONSE: u8 = 0x36;
+    pub const REJECT: u8 = 0x3F;
+}
+
+/// COMMAND UPIU flags
+pub mod command_flags {
+    /// Data is transferred from the device (DATA IN)
+    pub const READ: u8 = 0x40;
+    /// Data is transferred to the device (DATA OUT)
+    pub const WRITE: u8 = 0x20;
+    /// Task attribute mask (0 = simple)
+    pub const TASK_ATTRIBUTE_MASK: u8 = 0x03;
+}
+
+/// QUERY REQUEST function
+pub mod query_function {
+    pub const STANDARD_READ: u8 = 0x01;
+    pub const STANDARD_WRITE: u8 = 0x81;
+}
+
+/// QUERY RESPONSE codes
+pub mod query_response {
+    pub const SUCCESS: u8 = 0x00;
+    pub const PARAMETER_NOT_READABLE: u8 = 0xF6;
+    pub const PARAMETER_NOT_WRITEABLE: u8 = 0xF7;
+    pub const PARAMETER_ALREADY_WRITTEN: u8 = 0xF8;
+    pub const INVALID_LENGTH: u8 = 0xF9;
+    pub const INVALID_VALUE: u8 = 0xFA;
+    pub const INVALID_SELECTOR: u8 = 0xFB;
+    pub const INVALID_INDEX: u8 = 0xFC;
+    pub const INVALID_IDN: u8 = 0xFD;
+    pub const INVALID_OPCODE: u8 = 0xFE;
+    pub const GENERAL_FAILURE: u8 = 0xFF;
+}
+
+/// RESPONSE UPIU response field
+pub mod upiu_response {
+    pub const TARGET_SUCCESS: u8 = 0x00;
+    pub const TARGET_FAILURE: u8 = 0x01;
+}
+
+/// SCSI status codes carried in RESPONSE UPIUs
+pub mod scsi_status {
+    pub const GOOD: u8 = 0x00;
+    pub const CHECK_CONDITION: u8 = 0x02;
+    pub const CONDITION_MET: u8 = 0x04;
+    pub const BUSY: u8 = 0x08;
+    pub const RESERVATION_CONFLICT: u8 = 0x18;
+    pub const TASK_SET_FULL: u8 = 0x28;
+    pub const ACA_ACTIVE: u8 = 0x30;
+    pub const TASK_ABORTED: u8 = 0x40;
+}
+
+/// Task management functions
+pub mod task_function {
+    pub const ABORT_TASK: u8 = 0x01;
+    pub const ABORT_TASK_SET: u8 = 0x02;
+    pub const CLEAR_TASK_SET: u8 = 0x04;
+    pub const LOGICAL_UNIT_RESET: u8 = 0x08;
+    pub const QUERY_TASK: u8 = 0x80;
+    pub const QUERY_TASK_SET: u8 = 0x81;
+}
+
+/// Task management service responses
+pub mod task_response {
+    pub const COMPLETE: u8 = 0x00;
+    pub const NOT_SUPPORTED: u8 = 0x04;
+    pub const FAILED: u8 = 0x05;
+    pub const SUCCEEDED: u8 = 0x08;
+    pub const INCORRECT_LUN: u8 = 0x09;
+}
+
+// ============================================================================
+// Header
+// ============================================================================
+
+/// Common 12-byte UPIU header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct UpiuHeader {
+    /// Transaction code (bits 5:0)
+    pub transaction_type: u8,
+    pub flags: u8,
+    pub lun: u8,
+    pub task_tag: u8,
+    /// Initiator ID (7:4) and command set type (3:0)
+    pub iid_command_set: u8,
+    /// Query function or task management function
+    pub function: u8,
+    pub response: u8,
+    pub status: u8,
+    pub ehs_length: u8,
+    pub device_info: u8,
+    pub data_segment_length: u16,
+}
+
+impl UpiuHeader {
+    pub fn new(transaction_type: u8, lun: u8, task_tag: u8) -> Self {
+        Self {
+            transaction_type,
+            lun,
+            task_tag,
+            ..Default::default()
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < UPIU_HEADER_SIZE {
+            return None;
+        }
+        Some(Self {
+            transaction_type: data[0] & 0x3F,
+            flags: data[1],
+            lun: data[2],
+            task_tag: data[3],
+            iid_command_set: data[4],
+            function: data[5],
+            response: data[6],
+            status: data[7],
+            ehs_length: data[8],
+            device_info: data[9],
+            data_segment_length: u16::from_be_bytes([data[10], data[11]]),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; UPIU_HEADER_SIZE] {
+        let mut bytes = [0u8; UPIU_HEADER_SIZE];
+        bytes[0] = self.transaction_type & 0x3F;
+        bytes[1] = self.flags;
+        bytes[2] = self.lun;
+        bytes[3] = self.task_tag;
+        bytes[4] = self.iid_command_set;
+        bytes[5] = self.function;
+        bytes[6] = self.response;
+        bytes[7] = self.status;
+        bytes[8] = self.ehs_length;
+        bytes[9] = self.device_info;
+        bytes[10..12].copy_from_slice(&self.data_segment_length.to_be_bytes());
+        bytes
+    }
+}
+
+// ============================================================================
+// Query Requests
+// ============================================================================
+
+/// Query opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueryOpcode {
+    Nop = 0x00,
+    ReadDescriptor = 0x01,
+    WriteDescriptor = 0x02,
+    ReadAttribute = 0x03,
+    WriteAttribute = 0x04,
+    ReadFlag = 0x05,
+    SetFlag = 0x06,
+    ClearFlag = 0x07,
+    ToggleFlag = 0x08,
+}
+
+impl QueryOpcode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => Some(QueryOpcode::Nop),
+            0x01 => Some(QueryOpcode::ReadDescriptor),
+            0x02 => Some(QueryOpcode::WriteDescriptor),
+            0x03 => Some(QueryOpcode::ReadAttribute),
+            0x04 => Some(QueryOpcode::WriteAttribute),
+            0x05 => Some(QueryOpcode::ReadFlag),
+            0x06 => Some(QueryOpcode::SetFlag),
+            0x07 => Some(QueryOpcode::ClearFlag),
+            0x08 => Some(QueryOpcode::ToggleFlag),
+            _ => None,
+        }
+    }
+
+    /// Query function used for this opcode
+    pub fn function(&self) -> u8 {
+        match self {
+            QueryOpcode::Nop
+            | QueryOpcode::ReadDescriptor
+            | QueryOpcode::ReadAttribute
+            | QueryOpcode::ReadFlag => query_function::STANDARD_READ,
+            _ => query_function::STANDARD_WRITE,
+        }
+    }
+}
+
+/// Transaction specific fields of QUERY REQUEST/RESPONSE UPIUs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryFields {
+    pub opcode: QueryOpcode,
+    pub idn: u8,
+    pub index: u8,
+    pub selector: u8,
+    /// Descriptor length (descriptor opcodes)
+    pub length: u16,
+    /// Attribute value, or flag value in the low bit
+    pub value: u32,
+    /// Descriptor data (data segment)
+    pub data: Vec<u8>,
+}
+
+impl QueryFields {
+    fn new(opcode: QueryOpcode, idn: u8, index: u8, selector: u8) -> Self {
+        Self {
+            opcode,
+            idn,
+            index,
+            selector,
+            length: 0,
+            value: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn read_descriptor(idn: u8, index: u8, selector: u8, length: u16) -> Self {
+        Self {
+            length,
+            ..Self::new(QueryOpcode::ReadDescriptor, idn, index, selector)
+        }
+    }
+
+    pub fn write_descriptor(idn: u8, index: u8, selector: u8, data: Vec<u8>) -> Self {
+        Self {
+            length: data.len() as u16,
+            data,
+            ..Self::new(QueryOpcode::WriteDescriptor, idn, index, selector)
+        }
+    }
+
+    pub fn read_attribute(idn: u8, index: u8, selector: u8) -> Self {
+        Self::new(QueryOpcode::ReadAttribute, idn, index, selector)
+    }
+
+    pub fn write_attribute(idn: u8, index: u8, selector: u8, value: u32) -> Self {
+        Self {
+            value,
+            ..Self::new(QueryOpcode::WriteAttribute, idn, index, selector)
+        }
+    }
+
+    pub fn read_flag(idn: u8) -> Self {
+        Self::new(QueryOpcode::ReadFlag, idn, 0, 0)
+    }
+
+    pub fn set_flag(idn: u8) -> Self {
+        Self::new(QueryOpcode::SetFlag, idn, 0, 0)
+    }
+
+    pub fn clear_flag(idn: u8) -> Self {
+        Self::new(QueryOpcode::ClearFlag, idn, 0, 0)
+    }
+
+    pub fn toggle_flag(idn: u8) -> Self {
+        Self::new(QueryOpcode::ToggleFlag, idn, 0, 0)
+    }
+
+    /// Flag value from a READ/SET/CLEAR/TOGGLE FLAG response
+    pub fn flag(&self) -> bool {
+        self.value & 0x01 != 0
+    }
+
+    fn encode(&self, out: &mut [u8]) {
+        out[12] = self.opcode as u8;
+        out[13] = self.idn;
+        out[14] = self.index;
+        out[15] = self.selector;
+        out[18..20].copy_from_slice(&self.length.to_be_bytes());
+        out[20..24].copy_from_slice(&self.value.to_be_bytes());
+    }
+
+    fn decode(base: &[u8], data: &[u8]) -> Result<Self, UfsError> {
+        let opcode = QueryOpcode::from_u8(base[12]).ok_or(UfsError::InvalidCommand)?;
+        Ok(Self {
+            opcode,
+            idn: base[13],
+            index: base[14],
+            selector: base[15],
+            length: u16::from_be_bytes([base[18], base[19]]),
+            value: u32::from_be_bytes([base[20], base[21], base[22], base[23]]),
+            data: data.to_vec(),
+        })
+    }
+}
+
+// ============================================================================
+// UPIU
+// ============================================================================
+
+/// SCSI COMMAND UPIU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandUpiu {
+    pub lun: u8,
+    pub task_tag: u8,
+    /// `command_flags::READ` and/or `command_flags::WRITE`
+    pub flags: u8,
+    pub expected_length: u32,
+    /// CDB, zero padded to 16 bytes
+    pub cdb: [u8; 16],
+}
+
+impl CommandUpiu {
+    pub fn new(lun: u8, task_tag: u8, cdb: &[u8], flags: u8, expected_length: u32) -> Self {
+        let mut padded = [0u8; 16];
+        let len = cdb.len().min(16);
+        padded[..len].copy_from_slice(&cdb[..len]);
+        Self {
+            lun,
+            task_tag,
+            flags,
+            expected_length,
+            cdb: padded,
+        }
+    }
+}
+
+/// RESPONSE UPIU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseUpiu {
+    pub lun: u8,
+    pub task_tag: u8,
+    pub flags: u8,
+    pub response: u8,
+    /// SCSI status
+    pub status: u8,
+    pub residual_count: u32,
+    /// Sense data (without the 2-byte length prefix)
+    pub sense_data: Vec<u8>,
+}
+
+impl ResponseUpiu {
+    /// Map the response to a result, decoding sense data on CHECK CONDITION
+    pub fn result(&self) -> Result<(), UfsError> {
+        match self.status {
+            scsi_status::GOOD | scsi_status::CONDITION_MET
+                if self.response == upiu_response::TARGET_SUCCESS =>
+            {
+                Ok(())
+            }
+            scsi_status::CHECK_CONDITION => Err(UfsError::from_sense_data(&self.sense_data)),
+            scsi_status::BUSY | scsi_status::TASK_SET_FULL => Err(UfsError::DeviceBusy),
+            _ if self.response != upiu_response::TARGET_SUCCESS => {
+                Err(UfsError::ProtocolError(self.response))
+            }
+            status => Err(UfsError::ProtocolError(status)),
+        }
+    }
+}
+
+/// DATA IN / DATA OUT UPIU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataUpiu {
+    pub lun: u8,
+    pub task_tag: u8,
+    pub offset: u32,
+    pub data: Vec<u8>,
+}
+
+/// Task management request/response UPIU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskManagementUpiu {
+    pub lun: u8,
+    pub task_tag: u8,
+    /// `task_function` value
+    pub function: u8,
+    /// Response field (responses only)
+    pub response: u8,
+    /// Input parameters (requests) or output parameters (responses)
+    pub params: [u32; 3],
+}
+
+impl TaskManagementUpiu {
+    /// Request managing `target_tag` on `lun`
+    pub fn request(lun: u8, task_tag: u8, function: u8, target_tag: u8) -> Self {
+        Self {
+            lun,
+            task_tag,
+            function,
+            response: 0,
+            params: [lun as u32, target_tag as u32, 0],
+        }
+    }
+
+    /// Service response of a TASK MANAGEMENT RESPONSE
+    pub fn service_response(&self) -> u8 {
+        self.params[0] as u8
+    }
+
+    pub fn result(&self) -> Result<(), UfsError> {
+        if self.response != upiu_response::TARGET_SUCCESS {
+            return Err(UfsError::ProtocolError(self.response));
+        }
+        match self.service_response() {
+            task_response::COMPLETE | task_response::SUCCEEDED => Ok(()),
+            task_response::INCORRECT_LUN => Err(UfsError::InvalidLun(self.lun)),
+            other => Err(UfsError::ProtocolError(other)),
+        }
+    }
+}
+
+/// Decoded UPIU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Upiu {
+    NopOut {
+        task_tag: u8,
+    },
+    NopIn {
+        task_tag: u8,
+        response: u8,
+    },
+    Command(CommandUpiu),
+    DataOut(DataUpiu),
+    DataIn(DataUpiu),
+    Response(ResponseUpiu),
+    ReadyToTransfer {
+        lun: u8,
+        task_tag: u8,
+        offset: u32,
+        count: u32,
+    },
+    TaskManagementRequest(TaskManagementUpiu),
+    TaskManagementResponse(TaskManagementUpiu),
+    QueryRequest {
+        task_tag: u8,
+        fields: QueryFields,
+    },
+    QueryResponse {
+        task_tag: u8,
+        response: u8,
+        fields: QueryFields,
+    },
+    Reject {
+        lun: u8,
+        task_tag: u8,
+        response: u8,
+        status: u8,
+    },
+}
+
+impl Upiu {
+    /// Build a QUERY REQUEST UPIU
+    pub fn query(task_tag: u8, fields: QueryFields) -> Self {
+        Upiu::QueryRequest { task_tag, fields }
+    }
+
+    /// Transaction code of this UPIU
+    pub fn transaction_type(&self) -> u8 {
+        match self {
+            Upiu::NopOut { .. } => transaction::NOP_OUT,
+            Upiu::NopIn { .. } => transaction::NOP_IN,
+            Upiu::Command(_) => transaction::COMMAND,
+            Upiu::DataOut(_) => transaction::DATA_OUT,
+            Upiu::DataIn(_) => transaction::DATA_IN,
+            Upiu::Response(_) => transaction::RESPONSE,
+            Upiu::ReadyToTransfer { .. } => transaction::READY_TO_TRANSFER,
+            Upiu::TaskManagementRequest(_) => transaction::TASK_MGMT_REQUEST,
+            Upiu::TaskManagementResponse(_) => transaction::TASK_MGMT_RESPONSE,
+            Upiu::QueryRequest { .. } => transaction::QUERY_REQUEST,
+            Upiu::QueryResponse { .. } => transaction::QUERY_RESPONSE,
+            Upiu::Reject { .. } => transaction::REJECT,
+        }
+    }
+
+    /// Encode to bytes (32-byte base followed by the data segment)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut base = [0u8; UPIU_BASE_SIZE];
+        let mut header = UpiuHeader::new(self.transaction_type(), 0, 0);
+        let mut segment: Vec<u8> = Vec::new();
+
+        match self {
+            Upiu::NopOut { task_tag } => header.task_tag = *task_tag,
+            Upiu::NopIn { task_tag, response } => {
+                header.task_tag = *task_tag;
+                header.response = *response;
+            }
+            Upiu::Command(cmd) => {
+                header.lun = cmd.lun;
+                header.task_tag = cmd.task_tag;
+                header.flags = cmd.flags;
+                base[12..16].copy_from_slice(&cmd.expected_length.to_be_bytes());
+                base[16..32].copy_from_slice(&cmd.cdb);
+            }
+            Upiu::DataOut(d) | Upiu::DataIn(d) => {
+                header.lun = d.lun;
+                header.task_tag = d.task_tag;
+                base[12..16].copy_from_slice(&d.offset.to_be_bytes());
+                base[16..20].copy_from_slice(&(d.data.len() as u32).to_be_bytes());
+                segment = d.data.clone();
+            }
+            Upiu::Response(r) => {
+                header.lun = r.lun;
+                header.task_tag = r.task_tag;
+                header.flags = r.flags;
+                header.response = r.response;
+                header.status = r.status;
+                base[12..16].copy_from_slice(&r.residual_count.to_be_bytes());
+                if !r.sense_data.is_empty() {
+                    segment.extend_from_slice(&(r.sense_data.len() as u16).to_be_bytes());
+                    segment.extend_from_slice(&r.sense_data);
+                }
+            }
+            Upiu::ReadyToTransfer {
+                lun,
+                task_tag,
+                offset,
+                count,
+            } => {
+                header.lun = *lun;
+                header.task_tag = *task_tag;
+                base[12..16].copy_from_slice(&offset.to_be_bytes());
+                base[16..20].copy_from_slice(&count.to_be_bytes());
+            }
+            Upiu::TaskManagementRequest(tm) | Upiu::TaskManagementResponse(tm) => {
+                header.lun = tm.lun;
+                header.task_tag = tm.task_tag;
+                header.function = tm.function;
+                header.response = tm.response;
+                for (i, p) in tm.params.iter().enumerate() {
+                    base[12 + i * 4..16 + i * 4].copy_from_slice(&p.to_be_bytes());
+                }
+            }
+            Upiu::QueryRequest { task_tag, fields } => {
+                header.task_tag = *task_tag;
+                header.function = fields.opcode.function();
+                fields.encode(&mut base);
+                segment = fields.data.clone();
+            }
+            Upiu::QueryResponse {
+                task_tag,
+                response,
+                fields,
+            } => {
+                header.task_tag = *task_tag;
+                header.function = fields.opcode.function();
+                header.response = *response;
+                fields.encode(&mut base);
+                segment = fields.data.clone();
+            }
+            Upiu::Reject {
+                lun,
+                task_tag,
+                response,
+                status,
+            } => {
+                header.lun = *lun;
+                header.task_tag = *task_tag;
+                header.response = *response;
+                header.status = *status;
+            }
+        }
+
+        header.data_segment_length = segment.len() as u16;
+        base[..UPIU_HEADER_SIZE].copy_from_slice(&header.to_bytes());
+
+        let mut out = base.to_vec();
+        out.extend_from_slice(&segment);
+        out
+    }
+
+    /// Decode a UPIU from bytes
+    pub fn decode(data: &[u8]) -> Result<Self, UfsError> {
+        if data.len() < UPIU_BASE_SIZE {
+            return Err(UfsError::ProtocolError(0));
+        }
+        let header = UpiuHeader::parse(data).ok_or(UfsError::ProtocolError(0))?;
+        // Extra header segments precede the data segment in 4-byte units
+        let segment_start = UPIU_BASE_SIZE + header.ehs_length as usize * 4;
+        let segment_end = segment_start + header.data_segment_length as usize;
+        if data.len() < segment_end {
+            return Err(UfsError::ProtocolError(header.transaction_type));
+        }
+        let segment = &data[segment_start..segment_end];
+        let be32 = |o: usize| u32::from_be_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
+
+        let upiu = match header.transaction_type {
+            transaction::NOP_OUT => Upiu::NopOut {
+                task_tag: header.task_tag,
+            },
+            transaction::NOP_IN => Upiu::NopIn {
+                task_tag: header.task_tag,
+                response: header.response,
+            },
+            transaction::COMMAND => {
+                let mut cdb = [0u8; 16];
+                cdb.copy_from_slice(&data[16..32]);
+                Upiu::Command(CommandUpiu {
+                    lun: header.lun,
+                    task_tag: header.task_tag,
+                    flags: header.flags,
+                    expected_length: be32(12),
+                    cdb,
+                })
+            }
+            transaction::DATA_OUT | transaction::DATA_IN => {
+                let d = DataUpiu {
+                    lun: header.lun,
+                    task_tag: header.task_tag,
+                    offset: be32(12),
+                    data: segment.to_vec(),
+                };
+                if header.transaction_type == transaction::DATA_OUT {
+                    Upiu::DataOut(d)
+                } else {
+                    Upiu::DataIn(d)
+                }
+            }
+            transaction::RESPONSE => {
+                let sense_data = if segment.len() >= 2 {
+                    let len = u16::from_be_bytes([segment[0], segment[1]]) as usize;
+                    segment[2..(2 + len).min(segment.len())].to_vec()
+                } else {
+                    Vec::new()
+                };
+                Upiu::Response(ResponseUpiu {
+                    lun: header.lun,
+                    task_tag: header.task_tag,
+                    flags: header.flags,
+                    response: header.response,
+                    status: header.status,
+                    residual_count: be32(12),
+                    sense_data,
+                })
+            }
+            transaction::READY_TO_TRANSFER => Upiu::ReadyToTransfer {
+                lun: header.lun,
+                task_tag: header.task_tag,
+                offset: be32(12),
+                count: be32(16),
+            },
+            transaction::TASK_MGMT_REQUEST | transaction::TASK_MGMT_RESPONSE => {
+                let tm = TaskManagementUpiu {
+                    lun: header.lun,
+                    task_tag: header.task_tag,
+                    function: header.function,
+                    response: header.response,
+                    params: [be32(12), be32(16), be32(20)],
+                };
+                if header.transaction_type == transaction::TASK_MGMT_REQUEST {
+                    Upiu::TaskManagementRequest(tm)
+                } else {
+                    Upiu::TaskManagementResponse(tm)
+                }
+            }
+            transaction::QUERY_REQUEST => Upiu::QueryRequest {
+                task_tag: header.task_tag,
+                fields: QueryFields::decode(data, segment)?,
+            },
+            transaction::QUERY_RESPONSE => Upiu::QueryResponse {
+                task_tag: header.task_tag,
+                response: header.response,
+                fields: QueryFields::decode(data, segment)?,
+            },
+            transaction::REJECT => Upiu::Reject {
+                lun: header.lun,
+                task_tag: header.task_tag,
+                response: header.response,
+                status: header.status,
+            },
+            other => return Err(UfsError::ProtocolError(other)),
+        };
+        Ok(upiu)
+    }
+
+    /// Check a target-to-initiator UPIU for failure
+    ///
+    /// RESPONSE UPIUs are decoded through their SCSI status and sense data,
+    /// QUERY RESPONSE UPIUs through the query response code.
+    pub fn result(&self) -> Result<(), UfsError> {
+        match self {
+            Upiu::Response(r) => r.result(),
+            Upiu::QueryResponse { response, .. } if *response != query_response::SUCCESS => {
+                Err(UfsError::QueryError(*response))
+            }
+            Upiu::TaskManagementResponse(tm) => tm.result(),
+            Upiu::NopIn { response, .. } if *response != upiu_response::TARGET_SUCCESS => {
+                Err(UfsError::ProtocolError(*response))
+            }
+            Upiu::Reject { .. } => Err(UfsError::InvalidCommand),
+            _ => Ok(()),
+        }
+    }
+
+    /// Protocol packet announcing this UPIU; the encoded bytes follow in
+    /// the bulk phase and the response UPIU is read back the same way
+    pub fn packet(&self) -> Packet {
+        let len = self.encode().len() as u16;
+        Packet::new(Command::UfsUpiu, &len.to_le_bytes())
+    }
+}
+
+/// Extract the queried value from a QUERY RESPONSE
+///
+/// Returns the descriptor data, attribute value or flag as `QueryFields`
+/// after checking the response code and matching opcode.
+pub fn query_result(response: &Upiu, opcode: QueryOpcode) -> Result<&QueryFields, UfsError> {
+    response.result()?;
+    match response {
+        Upiu::QueryResponse { fields, .. } if fields.opcode == opcode => Ok(fields),
+        Upiu::QueryResponse { fields, .. } => Err(UfsError::QueryError(fields.opcode as u8)),
+        _ => Err(UfsError::ProtocolError(response.transaction_type())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ufs::{attributes, descriptors, flags, ScsiCdbBuilder};
+
+    #[test]
+    fn test_command_upiu_roundtrip() {
+        let cdb = ScsiCdbBuilder::build_read10(0x1234, 8, 0);
+        let upiu = Upiu::Command(CommandUpiu::new(2, 7, &cdb, command_flags::READ, 8 * 4096));
+        let bytes = upiu.encode();
+        assert_eq!(bytes.len(), UPIU_BASE_SIZE);
+        assert_eq!(bytes[0], transaction::COMMAND);
+        assert_eq!(bytes[1], command_flags::READ);
+        assert_eq!(bytes[2], 2);
+        assert_eq!(&bytes[12..16], &(8u32 * 4096).to_be_bytes());
+        assert_eq!(&bytes[16..26], &cdb);
+        assert_eq!(Upiu::decode(&bytes).unwrap(), upiu);
+    }
+
+    #[test]
+    fn test_query_requests() {
+        let upiu = Upiu::query(
+            1,
+            QueryFields::read_descriptor(descriptors::DEVICE, 0, 0, 0x59),
+        );
+        let bytes = upiu.encode();
+        assert_eq!(bytes[0], transaction::QUERY_REQUEST);
+        assert_eq!(bytes[5], query_function::STANDARD_READ);
+        assert_eq!(bytes[12], QueryOpcode::ReadDescriptor as u8);
+        assert_eq!(u16::from_be_bytes([bytes[18], bytes[19]]), 0x59);
+
+        let upiu = Upiu::query(
+            2,
+            QueryFields::write_attribute(attributes::BOOT_LUN_EN, 0, 0, 2),
+        );
+        let bytes = upiu.encode();
+        assert_eq!(bytes[5], query_function::STANDARD_WRITE);
+        assert_eq!(bytes[13], attributes::BOOT_LUN_EN);
+        assert_eq!(&bytes[20..24], &2u32.to_be_bytes());
+
+        let fields =
+            QueryFields::write_descriptor(descriptors::CONFIGURATION, 0, 0, vec![0xAA; 16]);
+        let upiu = Upiu::query(3, fields);
+        let bytes = upiu.encode();
+        assert_eq!(bytes.len(), UPIU_BASE_SIZE + 16);
+        assert_eq!(Upiu::decode(&bytes).unwrap(), upiu);
+
+        let upiu = Upiu::query(4, QueryFields::set_flag(flags::PURGE_ENABLE));
+        assert_eq!(Upiu::decode(&upiu.encode()).unwrap(), upiu);
+    }
+
+    #[test]
+    fn test_query_response_codes() {
+        let mut fields = QueryFields::read_attribute(attributes::REF_CLK_FREQ, 0, 0);
+        fields.value = 1;
+        let ok = Upiu::QueryResponse {
+            task_tag: 1,
+            response: query_response::SUCCESS,
+            fields: fields.clone(),
+        };
+        let decoded = Upiu::decode(&ok.encode()).unwrap();
+        assert_eq!(
+            query_result(&decoded, QueryOpcode::ReadAttribute)
+                .unwrap()
+                .value,
+            1
+        );
+
+        let failed = Upiu::QueryResponse {
+            task_tag: 1,
+            response: query_response::INVALID_IDN,
+            fields,
+        };
+        let err = query_result(&failed, QueryOpcode::ReadAttribute).unwrap_err();
+        assert_eq!(err, UfsError::QueryError(query_response::INVALID_IDN));
+        assert_eq!(err.description(), "Query invalid IDN");
+    }
+
+    #[test]
+    fn test_response_sense_handling() {
+        let sense = UfsError::ScsiError {
+            sense_key: 0x05,
+            asc: 0x24,
+            ascq: 0x00,
+        }
+        .to_sense_data()
+        .unwrap();
+        let upiu = Upiu::Response(ResponseUpiu {
+            lun: 0,
+            task_tag: 9,
+            flags: 0,
+            response: upiu_response::TARGET_SUCCESS,
+            status: scsi_status::CHECK_CONDITION,
+            residual_count: 0,
+            sense_data: sense,
+        });
+        let bytes = upiu.encode();
+        assert_eq!(u16::from_be_bytes([bytes[10], bytes[11]]), 20);
+        let decoded = Upiu::decode(&bytes).unwrap();
+        assert_eq!(
+            decoded.result(),
+            Err(UfsError::ScsiError {
+                sense_key: 0x05,
+                asc: 0x24,
+                ascq: 0x00
+            })
+        );
+
+        let busy = Upiu::Response(ResponseUpiu {
+            lun: 0,
+            task_tag: 1,
+            flags: 0,
+            response: upiu_response::TARGET_SUCCESS,
+            status: scsi_status::BUSY,
+            residual_count: 0,
+            sense_data: Vec::new(),
+        });
+        assert_eq!(busy.result(), Err(UfsError::DeviceBusy));
+    }
+
+    #[test]
+    fn test_task_management_and_nop() {
+        let req = Upiu::TaskManagementRequest(TaskManagementUpiu::request(
+            1,
+            5,
+            task_function::ABORT_TASK,
+            3,
+        ));
+        assert_eq!(Upiu::decode(&req.encode()).unwrap(), req);
+
+        let resp = Upiu::TaskManagementResponse(TaskManagementUpiu {
+            lun: 1,
+            task_tag: 5,
+            function: task_function::ABORT_TASK,
+            response: upiu_response::TARGET_SUCCESS,
+            params: [task_response::INCORRECT_LUN as u32, 0, 0],
+        });
+        assert_eq!(resp.result(), Err(UfsError::InvalidLun(1)));
+
+        let nop = Upiu::NopOut { task_tag: 0x11 };
+        assert_eq!(Upiu::decode(&nop.encode()).unwrap(), nop);
+        assert_eq!(nop.packet().cmd, Command::UfsUpiu);
+
+        assert!(Upiu::decode(&[0u8; 8]).is_err());
+        let mut truncated = Upiu::DataIn(DataUpiu {
+            lun: 0,
+            task_tag: 0,
+            offset: 0,
+            data: vec![1, 2, 3, 4],
+        })
+        .encode();
+        truncated.pop();
+        assert!(Upiu::decode(&truncated).is_err());
+    }
+}
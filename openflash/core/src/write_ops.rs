@@ -3,8 +3,13 @@
 //! Provides full chip programming, bad block management, wear leveling,
 //! incremental backup/restore, and chip-to-chip cloning.
 
+use crate::ecc::{encode_with_ecc, EccAlgorithm};
+use crate::nand_geometry::{NandAddress, NandGeometry};
+use crate::protocol::Packet;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 // ============================================================================
 // Error Types
@@ -35,6 +40,8 @@ pub enum WriteError {
     Cancelled,
     /// I/O error
     IoError(String),
+    /// Resumed job does not belong to this image
+    ResumeMismatch,
 }
 
 impl std::fmt::Display for WriteError {
@@ -73,6 +80,7 @@ impl std::fmt::Display for WriteError {
             }
             WriteError::Cancelled => write!(f, "Operation cancelled"),
             WriteError::IoError(e) => write!(f, "I/O error: {}", e),
+            WriteError::ResumeMismatch => write!(f, "Programming job does not match image"),
         }
     }
 }
@@ -180,7 +188,13 @@ impl BadBlockTable {
     }
 
     /// Allocate a spare block
+    ///
+    /// A table without a spare area only records bad blocks, for callers
+    /// that skip them instead of remapping
     fn allocate_spare(&mut self) -> WriteResult<Option<u32>> {
+        if self.spare_count == 0 {
+            return Ok(None);
+        }
         if self.next_spare >= self.spare_blocks.len() {
             return Err(WriteError::NoSpareBlocks);
         }
@@ -190,6 +204,11 @@ impl BadBlockTable {
         Ok(Some(spare))
     }
 
+    /// Get the reason a block was marked bad
+    pub fn reason(&self, block: u32) -> Option<BadBlockReason> {
+        self.entries.get(&block).map(|e| e.reason)
+    }
+
     /// Get all bad blocks
     pub fn bad_blocks(&self) -> Vec<u32> {
         self.entries.keys().copied().collect()
@@ -496,6 +515,8 @@ pub struct ChipProgrammer {
     total_blocks: u32,
     /// OOB size per page
     oob_size: u32,
+    /// CE/LUN organisation of the package
+    geometry: NandGeometry,
     /// Bad block table
     bbt: BadBlockTable,
     /// Wear leveling manager
@@ -518,12 +539,35 @@ impl ChipProgrammer {
             pages_per_block,
             total_blocks,
             oob_size,
-            bbt: BadBlockTable::new(total_blocks, 2), // 2% spare
+            geometry: NandGeometry::new(page_size, oob_size, pages_per_block, total_blocks),
+            // Bad blocks are skipped, not remapped to a spare area
+            bbt: BadBlockTable::new(total_blocks, 0),
             wear_manager: WearLevelingManager::new(total_blocks, max_erase_cycles),
             options: ProgramOptions::default(),
         }
     }
 
+    /// Create chip programmer for a multi-CE/multi-LUN package
+    ///
+    /// Block numbers stay package-wide; use `nand_address` to find the
+    /// CE and LUN a block lives on.
+    pub fn from_geometry(geometry: NandGeometry, max_erase_cycles: u32) -> Self {
+        let mut programmer = Self::new(
+            geometry.page_size,
+            geometry.pages_per_block,
+            geometry.total_blocks(),
+            geometry.oob_size,
+            max_erase_cycles,
+        );
+        programmer.geometry = geometry;
+        programmer
+    }
+
+    /// Get package geometry
+    pub fn geometry(&self) -> &NandGeometry {
+        &self.geometry
+    }
+
     /// Set programming options
     pub fn set_options(&mut self, options: ProgramOptions) {
         self.options = options;
@@ -558,6 +602,21 @@ impl ChipProgrammer {
         total_page as u64 * self.page_size as u64
     }
 
+    /// Map package-wide block and page to a CE/LUN address
+    pub fn nand_address(&self, block: u32, page: u32) -> WriteResult<NandAddress> {
+        self.geometry
+            .locate(block, page)
+            .ok_or(WriteError::InvalidAddress { block, page })
+    }
+
+    /// Map linear address to a CE/LUN address
+    pub fn address_to_nand_address(&self, address: u64) -> WriteResult<NandAddress> {
+        let (block, page) = self.address_to_block_page(address);
+        let mut addr = self.nand_address(block, page)?;
+        addr.column = (address % self.page_size as u64) as u16;
+        Ok(addr)
+    }
+
     /// Prepare block for programming (erase if needed)
     pub fn prepare_block(&mut self, block: u32) -> WriteResult<u32> {
         // Check if block is bad
@@ -621,6 +680,445 @@ impl ChipProgrammer {
     }
 }
 
+// ============================================================================
+// Programming Engine
+// ============================================================================
+
+/// Page-level access to the chip being programmed
+///
+/// Implementations report a failed erase or program as
+/// `WriteError::EraseFailed` / `WriteError::ProgramFailed` (the chip's status
+/// FAIL bit); those are retried and lead to the block being marked bad. Any
+/// other error aborts the job, which can then be resumed.
+pub trait ProgramTarget {
+    /// Erase a physical block
+    fn erase_block(&mut self, block: u32) -> WriteResult<()>;
+    /// Program one page of a physical block (data and OOB)
+    fn program_page(&mut self, block: u32, page: u32, data: &[u8], oob: &[u8]) -> WriteResult<()>;
+    /// Read one page of a physical block back into `data` and `oob`
+    fn read_page(
+        &mut self,
+        block: u32,
+        page: u32,
+        data: &mut [u8],
+        oob: &mut [u8],
+    ) -> WriteResult<()>;
+    /// Send a raw bus sequence, such as a read-retry level switch
+    fn send_packets(&mut self, packets: &[Packet]) -> WriteResult<()> {
+        if packets.is_empty() {
+            return Ok(());
+        }
+        Err(WriteError::IoError("no raw bus access to the chip".into()))
+    }
+}
+
+/// Best effort: program 0x00 into the first spare byte of page 0 so the
+/// block reads as bad in later scans
+fn write_bad_block_marker<T: ProgramTarget + ?Sized>(
+    target: &mut T,
+    block: u32,
+    page_size: u32,
+    oob_size: u32,
+) {
+    let mut marker = vec![0xFF; oob_size as usize];
+    if let Some(first) = marker.first_mut() {
+        *first = 0x00;
+        let blank = vec![0xFF; page_size as usize];
+        let _ = target.program_page(block, 0, &blank, &marker);
+    }
+}
+
+/// Image to program, with optional OOB image and ECC generation
+#[derive(Debug, Clone)]
+pub struct ProgramImage<'a> {
+    /// Main area data (padded with 0xFF to a page boundary)
+    pub data: &'a [u8],
+    /// OOB data, `oob_size` bytes per page
+    pub oob: Option<&'a [u8]>,
+    /// First block to program
+    pub start_block: u32,
+    /// ECC to generate into the OOB area
+    pub ecc: EccAlgorithm,
+    /// Offset of the generated ECC bytes in the OOB area
+    pub ecc_offset: u32,
+}
+
+impl<'a> ProgramImage<'a> {
+    /// Image without OOB data or ECC, starting at block 0
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            oob: None,
+            start_block: 0,
+            ecc: EccAlgorithm::None,
+            // Keep the bad block marker bytes free
+            ecc_offset: 2,
+        }
+    }
+
+    /// Program the given OOB image alongside the data
+    pub fn with_oob(mut self, oob: &'a [u8]) -> Self {
+        self.oob = Some(oob);
+        self
+    }
+
+    /// Generate ECC into the OOB area at `offset`
+    pub fn with_ecc(mut self, ecc: EccAlgorithm, offset: u32) -> Self {
+        self.ecc = ecc;
+        self.ecc_offset = offset;
+        self
+    }
+
+    /// Start programming at `block`
+    pub fn at_block(mut self, block: u32) -> Self {
+        self.start_block = block;
+        self
+    }
+}
+
+/// Resumable programming job state
+///
+/// Serialize it between runs; passing it back to `program_image` continues
+/// with the first block that was not completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgramJob {
+    /// First logical block of the image
+    pub start_block: u32,
+    /// Number of blocks the image occupies
+    pub image_blocks: u32,
+    /// Image length in bytes
+    pub image_len: u64,
+    /// Checksum of data and OOB image, to reject resuming with another image
+    pub image_checksum: u64,
+    /// Completed blocks as (logical, physical)
+    pub completed: Vec<(u32, u32)>,
+    /// Blocks marked bad during the job, in order
+    pub bad_blocks: Vec<(u32, BadBlockReason)>,
+    /// Whole-block retries after verify or program failures
+    pub verify_retries: u32,
+}
+
+impl ProgramJob {
+    /// Next image block index to program
+    pub fn next_block(&self) -> u32 {
+        self.completed.len() as u32
+    }
+
+    /// Check if all blocks have been programmed
+    pub fn is_complete(&self) -> bool {
+        self.next_block() >= self.image_blocks
+    }
+
+    /// Logical blocks still to program
+    pub fn remaining_blocks(&self) -> Vec<u32> {
+        (self.start_block + self.next_block()..self.start_block + self.image_blocks).collect()
+    }
+}
+
+/// Outcome of one attempt at writing a block
+enum BlockAttempt {
+    Written,
+    Failed(BadBlockReason),
+}
+
+impl ChipProgrammer {
+    fn image_checksum(image: &ProgramImage) -> u64 {
+        let mut checksum = ChangeTracker::calculate_checksum(image.data);
+        if let Some(oob) = image.oob {
+            checksum ^= ChangeTracker::calculate_checksum(oob).rotate_left(1);
+        }
+        checksum
+    }
+
+    /// Plan a programming job for `image`
+    ///
+    /// The image must fit in the good blocks from its start block on, since
+    /// known bad blocks are skipped.
+    pub fn plan(&self, image: &ProgramImage) -> WriteResult<ProgramJob> {
+        let block_bytes = self.pages_per_block as u64 * self.page_size as u64;
+        let image_blocks = ((image.data.len() as u64 + block_bytes - 1) / block_bytes) as u32;
+
+        let good_blocks = (image.start_block.min(self.total_blocks)..self.total_blocks)
+            .filter(|&b| !self.bbt.is_bad(b))
+            .count() as u64;
+        if image_blocks as u64 > good_blocks {
+            return Err(WriteError::DataSizeMismatch {
+                expected: (good_blocks * block_bytes) as usize,
+                actual: image.data.len(),
+            });
+        }
+
+        if let Some(oob) = image.oob {
+            let pages = image_blocks as usize * self.pages_per_block as usize;
+            let page_count =
+                (image.data.len() + self.page_size as usize - 1) / self.page_size as usize;
+            if oob.len() != page_count * self.oob_size as usize
+                && oob.len() != pages * self.oob_size as usize
+            {
+                return Err(WriteError::DataSizeMismatch {
+                    expected: page_count * self.oob_size as usize,
+                    actual: oob.len(),
+                });
+            }
+        }
+
+        let (_, ecc) = encode_with_ecc(&vec![0xFF; self.page_size as usize], &image.ecc);
+        if !ecc.is_empty() && image.ecc_offset as usize + ecc.len() > self.oob_size as usize {
+            return Err(WriteError::DataSizeMismatch {
+                expected: self.oob_size as usize,
+                actual: image.ecc_offset as usize + ecc.len(),
+            });
+        }
+
+        Ok(ProgramJob {
+            start_block: image.start_block,
+            image_blocks,
+            image_len: image.data.len() as u64,
+            image_checksum: Self::image_checksum(image),
+            completed: Vec::new(),
+            bad_blocks: Vec::new(),
+            verify_retries: 0,
+        })
+    }
+
+    /// First good block at or after `from`; without `skip_bad_blocks` a bad
+    /// block there is an error
+    fn next_good_block(&self, from: u32) -> WriteResult<u32> {
+        let mut block = from;
+        while self.bbt.is_bad(block) {
+            if !self.options.skip_bad_blocks {
+                return Err(WriteError::BadBlock(block));
+            }
+            block += 1;
+        }
+        if block >= self.total_blocks {
+            return Err(WriteError::NoSpareBlocks);
+        }
+        Ok(block)
+    }
+
+    /// Data and OOB bytes for one image page (0xFF beyond the image end)
+    fn image_page(&self, image: &ProgramImage, image_page: usize) -> (Vec<u8>, Vec<u8>) {
+        let page_size = self.page_size as usize;
+        let oob_size = self.oob_size as usize;
+
+        let mut data = vec![0xFF; page_size];
+        let start = image_page * page_size;
+        if start < image.data.len() {
+            let end = (start + page_size).min(image.data.len());
+            data[..end - start].copy_from_slice(&image.data[start..end]);
+        }
+
+        let mut oob = vec![0xFF; oob_size];
+        if let Some(src) = image.oob {
+            let start = image_page * oob_size;
+            if start < src.len() {
+                let end = (start + oob_size).min(src.len());
+                oob[..end - start].copy_from_slice(&src[start..end]);
+            }
+        }
+
+        // Blank pages stay erased so they read back as such
+        if data.iter().all(|&b| b == 0xFF) {
+            return (data, oob);
+        }
+        let (_, ecc) = encode_with_ecc(&data, &image.ecc);
+        let offset = image.ecc_offset as usize;
+        if !ecc.is_empty() && offset + ecc.len() <= oob_size {
+            oob[offset..offset + ecc.len()].copy_from_slice(&ecc);
+        }
+        (data, oob)
+    }
+
+    /// Erase, program and verify one physical block
+    fn attempt_block<T: ProgramTarget + ?Sized>(
+        &mut self,
+        target: &mut T,
+        image: &ProgramImage,
+        image_block: u32,
+        physical: u32,
+        progress: &mut ProgramProgress,
+    ) -> WriteResult<BlockAttempt> {
+        let pages_per_block = self.pages_per_block as usize;
+
+        if self.options.erase_before_program {
+            progress.operation = ProgramOperation::Erasing;
+            match target.erase_block(physical) {
+                Ok(()) => {}
+                Err(WriteError::EraseFailed(_)) => {
+                    return Ok(BlockAttempt::Failed(BadBlockReason::EraseFail))
+                }
+                Err(e) => return Err(e),
+            }
+            if self.options.wear_leveling {
+                match self.wear_manager.record_erase(physical) {
+                    Ok(()) => {}
+                    Err(WriteError::WearLimitExceeded(_)) => {
+                        return Ok(BlockAttempt::Failed(BadBlockReason::WearOut))
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        let mut read_data = vec![0u8; self.page_size as usize];
+        let mut read_oob = vec![0u8; self.oob_size as usize];
+        for page in 0..self.pages_per_block {
+            let (data, oob) = self.image_page(
+                image,
+                image_block as usize * pages_per_block + page as usize,
+            );
+            if data.iter().chain(oob.iter()).all(|&b| b == 0xFF) {
+                continue;
+            }
+
+            progress.current_page = page;
+            progress.operation = ProgramOperation::Programming;
+            match target.program_page(physical, page, &data, &oob) {
+                Ok(()) => {}
+                Err(WriteError::ProgramFailed { .. }) => {
+                    return Ok(BlockAttempt::Failed(BadBlockReason::ProgramFail))
+                }
+                Err(e) => return Err(e),
+            }
+            if self.options.wear_leveling {
+                self.wear_manager.record_program(physical);
+            }
+
+            if self.options.verify {
+                progress.operation = ProgramOperation::Verifying;
+                target.read_page(physical, page, &mut read_data, &mut read_oob)?;
+                if self.verify_page(&data, &read_data, physical, page).is_err()
+                    || self.verify_page(&oob, &read_oob, physical, page).is_err()
+                {
+                    return Ok(BlockAttempt::Failed(BadBlockReason::ProgramFail));
+                }
+            }
+        }
+        Ok(BlockAttempt::Written)
+    }
+
+    /// Program `image` to `target`
+    ///
+    /// Blocks are erased, programmed (with generated ECC) and verified.
+    /// Like the kernel's MTD and bootloader `nand write`, bad blocks are
+    /// skipped: each image block goes to the next good block after the
+    /// previous one. A failing block is retried `retry_count` times, then
+    /// marked bad in the table and on the chip (0x00 in its first spare
+    /// byte, so later scans find it) and the rest of the image shifts to
+    /// the next good block. Running out of blocks fails with
+    /// `NoSpareBlocks`. `progress` is called after a block once
+    /// `progress_interval` pages have been written since the last report,
+    /// and at the end; returning `false` cancels the job. On any error `job`
+    /// holds the state to resume from.
+    pub fn program_image<T, F>(
+        &mut self,
+        target: &mut T,
+        image: &ProgramImage,
+        job: &mut ProgramJob,
+        mut progress: F,
+    ) -> WriteResult<ProgramProgress>
+    where
+        T: ProgramTarget + ?Sized,
+        F: FnMut(&ProgramProgress) -> bool,
+    {
+        if job.start_block != image.start_block
+            || job.image_len != image.data.len() as u64
+            || job.image_checksum != Self::image_checksum(image)
+        {
+            return Err(WriteError::ResumeMismatch);
+        }
+
+        // Replay blocks marked bad by an earlier run of this job
+        for &(block, reason) in &job.bad_blocks {
+            if !self.bbt.is_bad(block) {
+                self.bbt.mark_bad(block, reason)?;
+            }
+        }
+
+        let block_bytes = self.pages_per_block as u64 * self.page_size as u64;
+        let total_bytes = job.image_blocks as u64 * block_bytes;
+        let resumed_bytes = job.next_block() as u64 * block_bytes;
+        let started = std::time::Instant::now();
+        let mut status = ProgramProgress {
+            current_block: job.start_block + job.next_block(),
+            current_page: 0,
+            total_blocks: job.image_blocks,
+            bytes_written: resumed_bytes,
+            total_bytes,
+            bad_blocks_skipped: job.bad_blocks.len() as u32,
+            verify_retries: job.verify_retries,
+            eta_seconds: None,
+            operation: ProgramOperation::Idle,
+        };
+        let interval = self.options.progress_interval.max(1);
+        let mut pages_since_report = 0u32;
+
+        while !job.is_complete() {
+            let image_block = job.next_block();
+            let logical = job.start_block + image_block;
+            let mut attempts = 0u8;
+            let from = match job.completed.last() {
+                Some(&(_, physical)) => physical + 1,
+                None => job.start_block,
+            };
+
+            let physical = loop {
+                let physical = self.next_good_block(from)?;
+                status.current_block = physical;
+
+                match self.attempt_block(target, image, image_block, physical, &mut status)? {
+                    BlockAttempt::Written => break physical,
+                    BlockAttempt::Failed(reason) => {
+                        if attempts < self.options.retry_count {
+                            attempts += 1;
+                            job.verify_retries += 1;
+                            status.verify_retries = job.verify_retries;
+                            continue;
+                        }
+                        attempts = 0;
+                        self.bbt.mark_bad(physical, reason)?;
+                        write_bad_block_marker(target, physical, self.page_size, self.oob_size);
+                        job.bad_blocks.push((physical, reason));
+                        status.bad_blocks_skipped += 1;
+                        if !self.options.skip_bad_blocks {
+                            return Err(match reason {
+                                BadBlockReason::EraseFail => WriteError::EraseFailed(physical),
+                                _ => WriteError::ProgramFailed {
+                                    block: physical,
+                                    page: status.current_page,
+                                },
+                            });
+                        }
+                    }
+                }
+            };
+
+            job.completed.push((logical, physical));
+            status.bytes_written += block_bytes;
+            pages_since_report += self.pages_per_block;
+
+            let run_bytes = status.bytes_written - resumed_bytes;
+            let elapsed = started.elapsed().as_secs_f64();
+            if run_bytes > 0 && elapsed > 0.0 {
+                let remaining = (total_bytes - status.bytes_written) as f64;
+                status.eta_seconds = Some((remaining * elapsed / run_bytes as f64).ceil() as u32);
+            }
+
+            if pages_since_report >= interval || job.is_complete() {
+                pages_since_report = 0;
+                if !progress(&status) {
+                    return Err(WriteError::Cancelled);
+                }
+            }
+        }
+
+        status.operation = ProgramOperation::Idle;
+        status.eta_seconds = Some(0);
+        Ok(status)
+    }
+}
+
 // ============================================================================
 // Incremental Backup/Restore
 // ============================================================================
@@ -787,7 +1285,9 @@ pub enum CloneMode {
     Exact,
     /// Skip bad blocks on source, remap on target
     SkipBadBlocks,
-    /// Intelligent clone with wear leveling
+    /// Intelligent clone with wear leveling: skips source bad blocks like
+    /// `SkipBadBlocks` (a copied marker would make the target block read
+    /// as bad) and leaves target blocks that already match untouched
     WearAware,
 }
 
@@ -804,6 +1304,11 @@ pub struct CloneOptions {
     pub preserve_oob: bool,
     /// Number of retries on error
     pub retry_count: u8,
+    /// Leave target blocks that already hold their data untouched, so an
+    /// interrupted clone continues where it stopped (always on for
+    /// `WearAware`)
+    #[serde(default)]
+    pub resume: bool,
 }
 
 impl Default for CloneOptions {
@@ -814,6 +1319,7 @@ impl Default for CloneOptions {
             allow_different_models: false,
             preserve_oob: true,
             retry_count: 3,
+            resume: false,
         }
     }
 }
@@ -879,6 +1385,11 @@ pub struct ChipCloner {
     target_page_size: u32,
     target_pages_per_block: u32,
     target_total_blocks: u32,
+    /// OOB bytes per page on each chip (0 = main area only)
+    source_oob_size: u32,
+    target_oob_size: u32,
+    /// Shared abort flag
+    abort: CloneAbortHandle,
 }
 
 impl ChipCloner {
@@ -912,9 +1423,24 @@ impl ChipCloner {
             target_page_size,
             target_pages_per_block,
             target_total_blocks,
+            source_oob_size: 0,
+            target_oob_size: 0,
+            abort: CloneAbortHandle::default(),
         })
     }
 
+    /// Set OOB sizes, required for bad block scanning and `preserve_oob`
+    pub fn with_oob_sizes(mut self, source_oob_size: u32, target_oob_size: u32) -> Self {
+        self.source_oob_size = source_oob_size;
+        self.target_oob_size = target_oob_size;
+        self
+    }
+
+    /// Handle that aborts a running clone (`Command::CloneAbort`)
+    pub fn abort_handle(&self) -> CloneAbortHandle {
+        self.abort.clone()
+    }
+
     /// Set clone options
     pub fn set_options(&mut self, options: CloneOptions) {
         self.options = options;
@@ -954,6 +1480,8 @@ impl ChipCloner {
     }
 
     /// Create block mapping for clone operation
+    ///
+    /// Only `Exact` maps source bad blocks; the other modes leave them out.
     pub fn create_block_mapping(
         &self,
         source_bad_blocks: &[u32],
@@ -966,7 +1494,7 @@ impl ChipCloner {
         let mut target_block = 0u32;
 
         for source_block in 0..self.source_total_blocks {
-            if self.options.mode == CloneMode::SkipBadBlocks && source_bad.contains(&source_block) {
+            if self.options.mode != CloneMode::Exact && source_bad.contains(&source_block) {
                 continue;
             }
 
@@ -985,6 +1513,365 @@ impl ChipCloner {
     }
 }
 
+/// Abort flag shared between a running clone and its controller
+#[derive(Debug, Clone, Default)]
+pub struct CloneAbortHandle(Arc<AtomicBool>);
+
+impl CloneAbortHandle {
+    /// Request the clone to stop after the current page
+    pub fn abort(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Has an abort been requested?
+    pub fn is_aborted(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Outcome of a finished clone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloneReport {
+    /// Final progress (phase `Complete`)
+    pub progress: CloneProgress,
+    /// Source block -> target block actually written
+    pub mapping: Vec<(u32, u32)>,
+    /// Bad blocks found on the source
+    pub source_bad_blocks: Vec<u32>,
+    /// Bad blocks on the target, including those that failed during the clone
+    pub target_bad_blocks: Vec<u32>,
+    /// Target blocks left untouched because they already matched
+    /// (`WearAware` or `resume`)
+    pub unchanged_blocks: u32,
+}
+
+/// One source block read into memory
+struct CloneBlock {
+    data: Vec<u8>,
+    oob: Vec<u8>,
+}
+
+impl ChipCloner {
+    fn check_abort(&self) -> WriteResult<()> {
+        if self.abort.is_aborted() {
+            return Err(WriteError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Scan factory bad block markers (first OOB byte of pages 0 and 1)
+    fn scan_bad_blocks<T: ProgramTarget + ?Sized>(
+        &self,
+        device: &mut T,
+        total_blocks: u32,
+        page_size: u32,
+        oob_size: u32,
+    ) -> WriteResult<Vec<u32>> {
+        let mut bad = Vec::new();
+        if oob_size == 0 {
+            return Ok(bad);
+        }
+        let mut data = vec![0u8; page_size as usize];
+        let mut oob = vec![0u8; oob_size as usize];
+        for block in 0..total_blocks {
+            self.check_abort()?;
+            for page in 0..2 {
+                let marked = match device.read_page(block, page, &mut data, &mut oob) {
+                    Ok(()) => oob[0] != 0xFF,
+                    Err(WriteError::IoError(e)) => return Err(WriteError::IoError(e)),
+                    Err(_) => true,
+                };
+                if marked {
+                    bad.push(block);
+                    break;
+                }
+            }
+        }
+        Ok(bad)
+    }
+
+    /// Read a whole source block, retrying failed reads
+    fn read_source_block<S: ProgramTarget + ?Sized>(
+        &self,
+        source: &mut S,
+        block: u32,
+    ) -> WriteResult<CloneBlock> {
+        let page_size = self.source_page_size as usize;
+        let oob_size = self.source_oob_size as usize;
+        let mut data = vec![0u8; page_size * self.source_pages_per_block as usize];
+        let mut oob = vec![0u8; oob_size * self.source_pages_per_block as usize];
+        for page in 0..self.source_pages_per_block {
+            let range = page as usize * page_size..(page as usize + 1) * page_size;
+            let oob_range = page as usize * oob_size..(page as usize + 1) * oob_size;
+            let mut attempts = 0u8;
+            loop {
+                self.check_abort()?;
+                match source.read_page(
+                    block,
+                    page,
+                    &mut data[range.clone()],
+                    &mut oob[oob_range.clone()],
+                ) {
+                    Ok(()) => break,
+                    Err(WriteError::IoError(e)) => return Err(WriteError::IoError(e)),
+                    Err(e) if attempts >= self.options.retry_count => return Err(e),
+                    Err(_) => attempts += 1,
+                }
+            }
+        }
+        Ok(CloneBlock { data, oob })
+    }
+
+    /// Target OOB for one page of a source block
+    fn target_oob(&self, block: &CloneBlock, page: usize, source_bad: bool) -> Vec<u8> {
+        let oob_size = self.target_oob_size as usize;
+        let mut oob = vec![0xFF; oob_size];
+        if self.options.preserve_oob {
+            let src_size = self.source_oob_size as usize;
+            let src = &block.oob[page * src_size..(page + 1) * src_size];
+            let n = src_size.min(oob_size);
+            oob[..n].copy_from_slice(&src[..n]);
+        } else if source_bad && page == 0 && oob_size > 0 {
+            // Exact mode carries the bad block marker over even without OOB
+            oob[0] = 0x00;
+        }
+        oob
+    }
+
+    /// Does the target block already hold exactly this data?
+    fn target_matches<T: ProgramTarget + ?Sized>(
+        &self,
+        target: &mut T,
+        physical: u32,
+        block: &CloneBlock,
+        source_bad: bool,
+    ) -> WriteResult<bool> {
+        let page_size = self.target_page_size as usize;
+        let mut data = vec![0u8; page_size];
+        let mut oob = vec![0u8; self.target_oob_size as usize];
+        for page in 0..self.target_pages_per_block as usize {
+            target.read_page(physical, page as u32, &mut data, &mut oob)?;
+            if data[..] != block.data[page * page_size..(page + 1) * page_size]
+                || oob != self.target_oob(block, page, source_bad)
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Erase, program and verify one target block
+    fn write_target_block<T: ProgramTarget + ?Sized>(
+        &self,
+        target: &mut T,
+        physical: u32,
+        block: &CloneBlock,
+        source_bad: bool,
+        progress: &mut CloneProgress,
+    ) -> WriteResult<BlockAttempt> {
+        progress.phase = ClonePhase::ErasingTarget;
+        match target.erase_block(physical) {
+            Ok(()) => {}
+            Err(WriteError::EraseFailed(_)) => {
+                return Ok(BlockAttempt::Failed(BadBlockReason::EraseFail))
+            }
+            Err(e) => return Err(e),
+        }
+
+        let page_size = self.target_page_size as usize;
+        let mut read_data = vec![0u8; page_size];
+        let mut read_oob = vec![0u8; self.target_oob_size as usize];
+        for page in 0..self.target_pages_per_block as usize {
+            self.check_abort()?;
+            let data = &block.data[page * page_size..(page + 1) * page_size];
+            let oob = self.target_oob(block, page, source_bad);
+            if data.iter().chain(oob.iter()).all(|&b| b == 0xFF) {
+                continue;
+            }
+
+            progress.phase = ClonePhase::Copying;
+            match target.program_page(physical, page as u32, data, &oob) {
+                Ok(()) => {}
+                Err(WriteError::ProgramFailed { .. }) => {
+                    return Ok(BlockAttempt::Failed(BadBlockReason::ProgramFail))
+                }
+                Err(e) => return Err(e),
+            }
+
+            if self.options.verify {
+                progress.phase = ClonePhase::Verifying;
+                target.read_page(physical, page as u32, &mut read_data, &mut read_oob)?;
+                if read_data != data || read_oob != oob {
+                    progress.verify_errors += 1;
+                    return Ok(BlockAttempt::Failed(BadBlockReason::ProgramFail));
+                }
+            }
+        }
+        Ok(BlockAttempt::Written)
+    }
+
+    /// Copy `source` onto `target`
+    ///
+    /// Both chips are scanned for factory bad block markers, then every
+    /// source block is read once and written to the next good target block.
+    /// `Exact` copies source bad blocks (with their markers), `SkipBadBlocks`
+    /// and `WearAware` leave them out. `WearAware`, and any mode with
+    /// `resume`, leaves target blocks that already hold the right data
+    /// untouched. A block that fails to erase, program or verify is retried
+    /// `retry_count` times, then the target block is treated as bad and the
+    /// data moves to the next one.
+    ///
+    /// `progress` is called after each scan and each block; returning
+    /// `false`, or aborting through `abort_handle`, stops the clone with
+    /// `WriteError::Cancelled`.
+    pub fn clone_chip<S, T, F>(
+        &self,
+        source: &mut S,
+        target: &mut T,
+        mut progress: F,
+    ) -> WriteResult<CloneReport>
+    where
+        S: ProgramTarget + ?Sized,
+        T: ProgramTarget + ?Sized,
+        F: FnMut(&CloneProgress) -> bool,
+    {
+        self.check_compatibility()?;
+        // Pages are copied one-to-one, so the page layout must match
+        if self.source_page_size != self.target_page_size
+            || self.source_pages_per_block != self.target_pages_per_block
+        {
+            return Err(WriteError::ChipMismatch {
+                source: format!("{}x{}", self.source_page_size, self.source_pages_per_block),
+                target: format!("{}x{}", self.target_page_size, self.target_pages_per_block),
+            });
+        }
+        self.abort.reset();
+
+        let block_bytes = self.source_page_size as u64 * self.source_pages_per_block as u64;
+        let mut status = CloneProgress {
+            current_block: 0,
+            total_blocks: self.source_total_blocks,
+            bytes_cloned: 0,
+            total_bytes: self.source_capacity(),
+            source_bad_blocks: 0,
+            target_remapped: 0,
+            verify_errors: 0,
+            phase: ClonePhase::ScanningSource,
+        };
+        let mut report_progress = |status: &CloneProgress| -> WriteResult<()> {
+            if !progress(status) {
+                return Err(WriteError::Cancelled);
+            }
+            Ok(())
+        };
+
+        let source_bad = self.scan_bad_blocks(
+            source,
+            self.source_total_blocks,
+            self.source_page_size,
+            self.source_oob_size,
+        )?;
+        if self.options.mode != CloneMode::Exact {
+            status.source_bad_blocks = source_bad.len() as u32;
+        }
+        report_progress(&status)?;
+
+        status.phase = ClonePhase::ScanningTarget;
+        let mut target_bad = self.scan_bad_blocks(
+            target,
+            self.target_total_blocks,
+            self.target_page_size,
+            self.target_oob_size,
+        )?;
+        report_progress(&status)?;
+
+        let mapping = self.create_block_mapping(&source_bad, &target_bad);
+        let copy_blocks: Vec<u32> = (0..self.source_total_blocks)
+            .filter(|b| mapping.contains_key(b))
+            .collect();
+        let expected = match self.options.mode {
+            CloneMode::Exact => self.source_total_blocks,
+            _ => self.source_total_blocks - source_bad.len() as u32,
+        };
+        if (copy_blocks.len() as u32) < expected {
+            return Err(WriteError::NoSpareBlocks);
+        }
+        status.total_blocks = copy_blocks.len() as u32;
+        status.total_bytes = status.total_blocks as u64 * block_bytes;
+
+        let source_bad_set: HashSet<u32> = source_bad.iter().copied().collect();
+        let mut target_bad_set: HashSet<u32> = target_bad.iter().copied().collect();
+        let mut next_target = 0u32;
+        let mut written = Vec::with_capacity(copy_blocks.len());
+        let mut unchanged = 0u32;
+
+        for &source_block in &copy_blocks {
+            self.check_abort()?;
+            status.current_block = source_block;
+            status.phase = ClonePhase::Copying;
+            let block = self.read_source_block(source, source_block)?;
+            let is_bad = source_bad_set.contains(&source_block);
+            let mut attempts = 0u8;
+
+            let physical = loop {
+                while target_bad_set.contains(&next_target) {
+                    next_target += 1;
+                }
+                if next_target >= self.target_total_blocks {
+                    return Err(WriteError::NoSpareBlocks);
+                }
+                let physical = next_target;
+
+                if (self.options.mode == CloneMode::WearAware || self.options.resume)
+                    && self.target_matches(target, physical, &block, is_bad)?
+                {
+                    unchanged += 1;
+                    break physical;
+                }
+
+                match self.write_target_block(target, physical, &block, is_bad, &mut status)? {
+                    BlockAttempt::Written => break physical,
+                    BlockAttempt::Failed(_) if attempts < self.options.retry_count => {
+                        attempts += 1;
+                    }
+                    BlockAttempt::Failed(_) => {
+                        attempts = 0;
+                        write_bad_block_marker(
+                            target,
+                            physical,
+                            self.target_page_size,
+                            self.target_oob_size,
+                        );
+                        target_bad_set.insert(physical);
+                        target_bad.push(physical);
+                        status.target_remapped += 1;
+                    }
+                }
+            };
+
+            next_target = physical + 1;
+            written.push((source_block, physical));
+            status.bytes_cloned += block_bytes;
+            report_progress(&status)?;
+        }
+
+        status.phase = ClonePhase::Complete;
+        report_progress(&status)?;
+        target_bad.sort_unstable();
+        Ok(CloneReport {
+            progress: status,
+            mapping: written,
+            source_bad_blocks: source_bad,
+            target_bad_blocks: target_bad,
+            unchanged_blocks: unchanged,
+        })
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -1110,6 +1997,25 @@ mod tests {
         assert_eq!(programmer.capacity(), 128 * 1024 * 1024);
     }
 
+    #[test]
+    fn test_chip_programmer_multi_die_address() {
+        let mut geometry = NandGeometry::new(4096, 224, 128, 1024).with_ce_count(2);
+        geometry.luns_per_ce = 2;
+        let programmer = ChipProgrammer::from_geometry(geometry, 3000);
+
+        assert_eq!(programmer.capacity(), 4 * 1024 * 128 * 4096);
+
+        let addr = programmer.nand_address(2 * 1024 + 7, 3).unwrap();
+        assert_eq!((addr.ce, addr.lun, addr.block, addr.page), (1, 0, 7, 3));
+
+        let linear = programmer.block_page_to_address(3 * 1024 + 1, 2) + 100;
+        let addr = programmer.address_to_nand_address(linear).unwrap();
+        assert_eq!((addr.ce, addr.lun, addr.block, addr.page), (1, 1, 1, 2));
+        assert_eq!(addr.column, 100);
+
+        assert!(programmer.nand_address(4 * 1024, 0).is_err());
+    }
+
     #[test]
     fn test_backup_metadata() {
         let meta = BackupMetadata::new_full("TEST_CHIP".to_string(), 128 * 1024 * 1024, 2048, 64);
@@ -1161,6 +2067,38 @@ mod tests {
         assert!(!mapping.values().any(|&v| v == 7 || v == 15));
     }
 
+    #[test]
+    fn test_clone_block_mapping_by_mode() {
+        let mut cloner = ChipCloner::new(2048, 64, 6, 2048, 64, 8).unwrap();
+        let mapping = |cloner: &ChipCloner| {
+            let mapping = cloner.create_block_mapping(&[2], &[1]);
+            let mut pairs: Vec<(u32, u32)> = mapping.into_iter().collect();
+            pairs.sort_unstable();
+            pairs
+        };
+
+        // WearAware leaves source bad blocks out like SkipBadBlocks
+        for mode in [CloneMode::SkipBadBlocks, CloneMode::WearAware] {
+            cloner.set_options(CloneOptions {
+                mode,
+                ..Default::default()
+            });
+            assert_eq!(
+                mapping(&cloner),
+                vec![(0, 0), (1, 2), (3, 3), (4, 4), (5, 5)]
+            );
+        }
+
+        cloner.set_options(CloneOptions {
+            mode: CloneMode::Exact,
+            ..Default::default()
+        });
+        assert_eq!(
+            mapping(&cloner),
+            vec![(0, 0), (1, 2), (2, 3), (3, 4), (4, 5), (5, 6)]
+        );
+    }
+
     #[test]
     fn test_clone_progress() {
         let progress = CloneProgress {
@@ -1177,6 +2115,362 @@ mod tests {
         assert!((progress.percent_complete() - 50.0).abs() < 0.1);
     }
 
+    /// In-memory NAND with injectable failures
+    struct MockNand {
+        page_size: usize,
+        oob_size: usize,
+        pages: HashMap<(u32, u32), (Vec<u8>, Vec<u8>)>,
+        erase_fail: HashSet<u32>,
+        program_fail: HashSet<u32>,
+        /// Remaining corrupted read-backs per block
+        flaky_reads: HashMap<u32, u32>,
+        /// Fail with an I/O error after this many page programs
+        io_fail_after: Option<u32>,
+        programs: u32,
+    }
+
+    impl MockNand {
+        fn new(page_size: usize, oob_size: usize) -> Self {
+            Self {
+                page_size,
+                oob_size,
+                pages: HashMap::new(),
+                erase_fail: HashSet::new(),
+                program_fail: HashSet::new(),
+                flaky_reads: HashMap::new(),
+                io_fail_after: None,
+                programs: 0,
+            }
+        }
+    }
+
+    impl ProgramTarget for MockNand {
+        fn erase_block(&mut self, block: u32) -> WriteResult<()> {
+            if self.erase_fail.contains(&block) {
+                return Err(WriteError::EraseFailed(block));
+            }
+            self.pages.retain(|&(b, _), _| b != block);
+            Ok(())
+        }
+
+        fn program_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &[u8],
+            oob: &[u8],
+        ) -> WriteResult<()> {
+            if self.io_fail_after == Some(self.programs) {
+                return Err(WriteError::IoError("USB disconnected".into()));
+            }
+            self.programs += 1;
+            if self.program_fail.contains(&block) {
+                return Err(WriteError::ProgramFailed { block, page });
+            }
+            self.pages
+                .insert((block, page), (data.to_vec(), oob.to_vec()));
+            Ok(())
+        }
+
+        fn read_page(
+            &mut self,
+            block: u32,
+            page: u32,
+            data: &mut [u8],
+            oob: &mut [u8],
+        ) -> WriteResult<()> {
+            let erased = (vec![0xFF; self.page_size], vec![0xFF; self.oob_size]);
+            let (d, o) = self.pages.get(&(block, page)).unwrap_or(&erased);
+            data.copy_from_slice(d);
+            oob.copy_from_slice(o);
+            if let Some(n) = self.flaky_reads.get_mut(&block) {
+                if *n > 0 {
+                    *n -= 1;
+                    data[0] ^= 0x01;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn clone_source(blocks: u32) -> MockNand {
+        let mut nand = MockNand::new(512, 16);
+        for block in 0..blocks {
+            for page in 0..4u32 {
+                let data = vec![(block * 4 + page) as u8; 512];
+                let mut oob = vec![0xFF; 16];
+                oob[4] = block as u8;
+                nand.program_page(block, page, &data, &oob).unwrap();
+            }
+        }
+        nand.programs = 0;
+        nand
+    }
+
+    fn mark_factory_bad(nand: &mut MockNand, block: u32) {
+        let mut oob = vec![0xFF; 16];
+        oob[0] = 0x00;
+        nand.pages.insert((block, 0), (vec![0xFF; 512], oob));
+    }
+
+    #[test]
+    fn test_clone_skip_bad_blocks_with_remap() {
+        let mut source = clone_source(8);
+        mark_factory_bad(&mut source, 2);
+        let mut target = MockNand::new(512, 16);
+        mark_factory_bad(&mut target, 1);
+        target.program_fail.insert(3);
+        // Two reads go to the bad block scan, the third fails verify
+        target.flaky_reads.insert(4, 3);
+
+        let cloner = ChipCloner::new(512, 4, 8, 512, 4, 10)
+            .unwrap()
+            .with_oob_sizes(16, 16);
+        let mut phases = Vec::new();
+        let report = cloner
+            .clone_chip(&mut source, &mut target, |p| {
+                phases.push(p.phase);
+                true
+            })
+            .unwrap();
+
+        assert_eq!(report.source_bad_blocks, vec![2]);
+        assert_eq!(report.target_bad_blocks, vec![1, 3]);
+        assert_eq!(
+            report.mapping,
+            vec![(0, 0), (1, 2), (3, 4), (4, 5), (5, 6), (6, 7), (7, 8)]
+        );
+        assert_eq!(report.progress.source_bad_blocks, 1);
+        assert_eq!(report.progress.target_remapped, 1);
+        assert_eq!(report.progress.verify_errors, 1);
+        assert_eq!(report.progress.phase, ClonePhase::Complete);
+        assert!((report.progress.percent_complete() - 100.0).abs() < 0.1);
+        assert_eq!(phases[0], ClonePhase::ScanningSource);
+        assert_eq!(phases[1], ClonePhase::ScanningTarget);
+
+        for &(src, dst) in &report.mapping {
+            assert_eq!(target.pages[&(dst, 1)], source.pages[&(src, 1)]);
+        }
+    }
+
+    #[test]
+    fn test_clone_skip_bad_blocks_resume() {
+        let mut source = clone_source(6);
+        mark_factory_bad(&mut source, 2);
+        let mut target = MockNand::new(512, 16);
+        let mut cloner = ChipCloner::new(512, 4, 6, 512, 4, 6)
+            .unwrap()
+            .with_oob_sizes(16, 16);
+
+        // Interrupted after two blocks
+        let result = cloner.clone_chip(&mut source, &mut target, |p| p.bytes_cloned < 2 * 2048);
+        assert_eq!(result.unwrap_err(), WriteError::Cancelled);
+        let programs = target.programs;
+
+        cloner.set_options(CloneOptions {
+            resume: true,
+            ..Default::default()
+        });
+        let report = cloner
+            .clone_chip(&mut source, &mut target, |_| true)
+            .unwrap();
+        assert_eq!(report.unchanged_blocks, 2);
+        assert_eq!(report.progress.source_bad_blocks, 1);
+        assert_eq!(report.mapping, vec![(0, 0), (1, 1), (3, 2), (4, 3), (5, 4)]);
+        assert_eq!(target.programs, programs + 3 * 4);
+    }
+
+    #[test]
+    fn test_clone_exact_wear_aware_and_abort() {
+        let mut source = clone_source(4);
+        mark_factory_bad(&mut source, 1);
+        let mut target = MockNand::new(512, 16);
+
+        let mut cloner = ChipCloner::new(512, 4, 4, 512, 4, 4)
+            .unwrap()
+            .with_oob_sizes(16, 16);
+        cloner.set_options(CloneOptions {
+            mode: CloneMode::Exact,
+            preserve_oob: false,
+            ..Default::default()
+        });
+        let report = cloner
+            .clone_chip(&mut source, &mut target, |_| true)
+            .unwrap();
+        assert_eq!(report.mapping.len(), 4);
+        // Marker carried over without copying the rest of the OOB
+        assert_eq!(target.pages[&(1, 0)].1[0], 0x00);
+        assert_eq!(target.pages[&(0, 0)].1, vec![0xFF; 16]);
+
+        // A second pass finds nothing to rewrite
+        cloner.set_options(CloneOptions {
+            mode: CloneMode::WearAware,
+            preserve_oob: false,
+            ..Default::default()
+        });
+        let mut target_copy = MockNand::new(512, 16);
+        target_copy.pages = target.pages.clone();
+        mark_factory_bad(&mut target_copy, 1);
+        let report = cloner
+            .clone_chip(&mut source, &mut target_copy, |_| true)
+            .unwrap();
+        assert_eq!(report.unchanged_blocks, 3);
+        assert_eq!(target_copy.programs, 0);
+
+        // Abort from another controller (Command::CloneAbort)
+        let handle = cloner.abort_handle();
+        let result = cloner.clone_chip(&mut source, &mut target, |p| {
+            if p.phase == ClonePhase::ScanningTarget {
+                handle.abort();
+            }
+            true
+        });
+        assert_eq!(result.unwrap_err(), WriteError::Cancelled);
+        assert!(handle.is_aborted());
+    }
+
+    fn small_programmer() -> ChipProgrammer {
+        // 100 blocks of 4 pages
+        let mut programmer = ChipProgrammer::new(512, 4, 100, 16, 100000);
+        programmer.set_options(ProgramOptions {
+            progress_interval: 4,
+            ..Default::default()
+        });
+        programmer
+    }
+
+    #[test]
+    fn test_program_image_with_ecc_and_bad_blocks() {
+        let mut programmer = small_programmer();
+        let mut nand = MockNand::new(512, 16);
+        nand.erase_fail.insert(1);
+        nand.program_fail.insert(2);
+        nand.flaky_reads.insert(4, 1);
+
+        let data: Vec<u8> = (0..512 * 4 * 3 + 100).map(|i| (i % 251) as u8).collect();
+        let image = ProgramImage::new(&data).with_ecc(EccAlgorithm::Hamming, 2);
+        let mut job = programmer.plan(&image).unwrap();
+        assert_eq!(job.image_blocks, 4);
+        assert_eq!(job.remaining_blocks(), vec![0, 1, 2, 3]);
+
+        let mut reports = 0;
+        let result = programmer
+            .program_image(&mut nand, &image, &mut job, |_| {
+                reports += 1;
+                true
+            })
+            .unwrap();
+
+        assert!(job.is_complete());
+        assert_eq!(reports, 4);
+        assert!((result.percent_complete() - 100.0).abs() < 0.1);
+        // Block 1 failed erase, block 2 failed program, the rest shifted
+        assert_eq!(job.completed, vec![(0, 0), (1, 3), (2, 4), (3, 5)]);
+        assert_eq!(
+            job.bad_blocks,
+            vec![
+                (1, BadBlockReason::EraseFail),
+                (2, BadBlockReason::ProgramFail)
+            ]
+        );
+        assert_eq!(result.bad_blocks_skipped, 2);
+        // Flaky block 4 recovered on the first retry
+        assert_eq!(job.verify_retries, 3 + 3 + 1);
+        // Block 1 still programs, so it carries a bad block marker
+        assert_eq!(nand.pages[&(1, 0)].1[0], 0x00);
+
+        let (page, oob) = &nand.pages[&(3, 0)];
+        assert_eq!(&page[..], &data[2048..2560]);
+        let (_, ecc) = encode_with_ecc(&data[2048..2560], &EccAlgorithm::Hamming);
+        assert_eq!(&oob[2..2 + ecc.len()], &ecc[..]);
+        assert_eq!(&oob[..2], &[0xFF, 0xFF]);
+        // Tail page past the image stays erased
+        assert!(!nand.pages.contains_key(&(5, 2)));
+    }
+
+    #[test]
+    fn test_program_image_resume() {
+        let mut programmer = small_programmer();
+        let mut nand = MockNand::new(512, 16);
+        nand.io_fail_after = Some(6);
+
+        let data = vec![0x5A; 512 * 4 * 3];
+        let oob = vec![0xA5; 16 * 4 * 3];
+        let image = ProgramImage::new(&data).with_oob(&oob).at_block(10);
+        let mut job = programmer.plan(&image).unwrap();
+
+        let err = programmer
+            .program_image(&mut nand, &image, &mut job, |_| true)
+            .unwrap_err();
+        assert!(matches!(err, WriteError::IoError(_)));
+        assert_eq!(job.completed, vec![(10, 10)]);
+
+        // Job survives a restart of the tool
+        let saved = serde_json::to_string(&job).unwrap();
+        let mut job: ProgramJob = serde_json::from_str(&saved).unwrap();
+        nand.io_fail_after = None;
+
+        let other = vec![0u8; data.len()];
+        let mismatched = ProgramImage::new(&other).at_block(10);
+        assert!(matches!(
+            programmer.program_image(&mut nand, &mismatched, &mut job, |_| true),
+            Err(WriteError::ResumeMismatch)
+        ));
+
+        let mut seen = Vec::new();
+        programmer
+            .program_image(&mut nand, &image, &mut job, |p| {
+                seen.push(p.current_block);
+                p.current_block < 11
+            })
+            .unwrap_err();
+        assert_eq!(seen, vec![11]);
+        programmer
+            .program_image(&mut nand, &image, &mut job, |_| true)
+            .unwrap();
+        assert_eq!(job.completed, vec![(10, 10), (11, 11), (12, 12)]);
+        assert_eq!(nand.pages[&(12, 3)], (vec![0x5A; 512], vec![0xA5; 16]));
+    }
+
+    #[test]
+    fn test_program_plan_limits() {
+        let mut programmer = small_programmer();
+        let data = vec![0u8; 512 * 4 * 2];
+        assert!(programmer
+            .plan(&ProgramImage::new(&data).at_block(98))
+            .is_ok());
+        assert!(matches!(
+            programmer.plan(&ProgramImage::new(&data).at_block(99)),
+            Err(WriteError::DataSizeMismatch { .. })
+        ));
+
+        // A full-chip image fits until a block goes bad
+        let full = vec![0u8; 512 * 4 * 100];
+        assert!(programmer.plan(&ProgramImage::new(&full)).is_ok());
+        programmer
+            .bad_block_table_mut()
+            .scan_factory_bad_blocks(&[(40, 0x00)]);
+        assert!(matches!(
+            programmer.plan(&ProgramImage::new(&full)),
+            Err(WriteError::DataSizeMismatch { expected, .. }) if expected == 512 * 4 * 99
+        ));
+
+        // A block failing at the end of the chip leaves nowhere to shift to
+        let mut nand = MockNand::new(512, 16);
+        nand.program_fail.insert(99);
+        let image = ProgramImage::new(&data).at_block(98);
+        let mut job = programmer.plan(&image).unwrap();
+        assert!(matches!(
+            programmer.program_image(&mut nand, &image, &mut job, |_| true),
+            Err(WriteError::NoSpareBlocks)
+        ));
+        assert_eq!(job.completed, vec![(98, 98)]);
+        let oob = vec![0u8; 10];
+        assert!(programmer
+            .plan(&ProgramImage::new(&data).with_oob(&oob))
+            .is_err());
+    }
+
     #[test]
     fn test_program_options_default() {
         let opts = ProgramOptions::default();
@@ -329,6 +329,41 @@ impl<'d, SPI: embassy_rp::spi::Instance> EmmcController<'d, SPI> {
         true
     }
 
+    /// Read EXT_CSD register (512 bytes)
+    pub async fn read_ext_csd(&mut self, buf: &mut [u8]) -> bool {
+        if !self.initialized || buf.len() < 512 {
+            return false;
+        }
+
+        let r1 = self.send_cmd_hold(commands::SEND_EXT_CSD, 0);
+        if r1 != 0x00 {
+            self.cs_high();
+            return false;
+        }
+
+        let mut token = [0u8; 1];
+        for _ in 0..10000 {
+            let _ = self.spi.blocking_read(&mut token);
+            if token[0] == data_tokens::START_BLOCK {
+                break;
+            }
+        }
+
+        if token[0] != data_tokens::START_BLOCK {
+            self.cs_high();
+            return false;
+        }
+
+        let _ = self.spi.blocking_read(&mut buf[..512]);
+
+        let mut crc = [0u8; 2];
+        let _ = self.spi.blocking_read(&mut crc);
+
+        self.cs_high();
+        let _ = self.spi.blocking_write(&[0xFF]);
+        true
+    }
+
     /// Read a single 512-byte block
     pub async fn read_block(&mut self, block_addr: u32, buf: &mut [u8; 512]) -> bool {
         if !self.initialized {
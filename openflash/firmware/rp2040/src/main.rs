@@ -200,7 +200,8 @@ async fn main(spawner: Spawner) {
     // Create command handler with NAND and SPI NOR controllers
     let mut handler = UsbHandler::new(class, nand);
     handler.set_spi_nor(spi_nor);
-    info!("Command handler initialized with SPI NOR support");
+    handler.set_emmc(emmc);
+    info!("Command handler initialized with SPI NOR and eMMC support");
 
     // Main loop
     loop {
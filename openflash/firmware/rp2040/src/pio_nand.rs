@@ -388,8 +388,11 @@ impl<'d> NandController<'d> {
     /// Erase a block
     pub async fn erase_block(&mut self, block_addr: u32) -> bool {
         // Calculate page address from block (assuming 64 pages/block)
-        let page_addr = block_addr * 64;
-        
+        self.erase_block_at(block_addr * 64).await
+    }
+
+    /// Erase the block holding page `page_addr`, whatever the block size
+    pub async fn erase_block_at(&mut self, page_addr: u32) -> bool {
         // Send ERASE command
         self.send_command(commands::BLOCK_ERASE_1ST).await;
         
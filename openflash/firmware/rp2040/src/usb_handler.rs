@@ -2,17 +2,27 @@
 //! 
 //! Handles USB protocol commands for parallel NAND, SPI NAND, SPI NOR, and eMMC interfaces.
 //! Requirements: 9.1
+//!
+//! Every command is one USB packet `[command, args...]`. Replies are
+//! `[command, status]` plus any fixed payload (e.g. the 5 ID bytes of
+//! `NandReadId`); an unknown command gets the single byte `0xFF`.
+//! `NandReadPage` and `NandReadData` stream their bytes with no header,
+//! `NandWritePage` takes the page bytes right after the command and replies
+//! once the page is programmed, and `EmmcReadExtCsd` sends its 512 bytes
+//! after an OK reply.
 
 use defmt::*;
 use embassy_time::Timer;
-use embassy_rp::peripherals::SPI0;
+use embassy_rp::peripherals::{SPI0, SPI1};
 use embassy_usb::class::cdc_acm::CdcAcmClass;
 use embassy_usb::driver::Driver;
 
+use crate::emmc::EmmcController;
 use crate::pio_nand::NandController;
 use crate::spi_nor::SpiNorController;
 
 const MAX_PAGE_SIZE: usize = 4352; // 4096 + 256 OOB
+const EXT_CSD_SIZE: usize = 512;
 const PACKET_SIZE: usize = 64;
 
 /// USB Protocol Commands
@@ -25,12 +35,19 @@ pub enum Command {
     Reset = 0x08,
     SetInterface = 0x09,
     
-    // Parallel NAND commands (legacy 0x03-0x07, new 0x10-0x1F)
-    NandCmd = 0x03,
-    NandAddr = 0x04,
-    NandReadPage = 0x05,
-    NandWritePage = 0x06,
-    ReadId = 0x07,
+    // Parallel NAND commands (0x10-0x1F, legacy 0x03-0x07 still accepted)
+    NandCmd = 0x10,
+    NandAddr = 0x11,
+    NandReadPage = 0x12,
+    NandWritePage = 0x13,
+    ReadId = 0x14,
+    NandErase = 0x15,
+    NandWriteData = 0x17,
+    NandReadData = 0x18,
+    NandSelectCe = 0x19,
+
+    // eMMC commands (0x40-0x5F)
+    EmmcReadExtCsd = 0x43,
     
     // SPI NOR commands (0x60-0x7F)
     SpiNorReadJedecId = 0x60,
@@ -62,12 +79,19 @@ impl Command {
             0x08 => Some(Command::Reset),
             0x09 => Some(Command::SetInterface),
             
-            // Parallel NAND (legacy)
-            0x03 => Some(Command::NandCmd),
-            0x04 => Some(Command::NandAddr),
-            0x05 => Some(Command::NandReadPage),
-            0x06 => Some(Command::NandWritePage),
-            0x07 => Some(Command::ReadId),
+            // Parallel NAND (legacy 0x03-0x07 mapped to new values)
+            0x03 | 0x10 => Some(Command::NandCmd),
+            0x04 | 0x11 => Some(Command::NandAddr),
+            0x05 | 0x12 => Some(Command::NandReadPage),
+            0x06 | 0x13 => Some(Command::NandWritePage),
+            0x07 | 0x14 => Some(Command::ReadId),
+            0x15 => Some(Command::NandErase),
+            0x17 => Some(Command::NandWriteData),
+            0x18 => Some(Command::NandReadData),
+            0x19 => Some(Command::NandSelectCe),
+
+            // eMMC
+            0x43 => Some(Command::EmmcReadExtCsd),
             
             // SPI NOR
             0x60 => Some(Command::SpiNorReadJedecId),
@@ -115,6 +139,7 @@ pub struct UsbHandler<'d, D: Driver<'d>> {
     pub class: CdcAcmClass<'d, D>,
     nand: NandController<'d>,
     spi_nor: Option<SpiNorController<'d, SPI0>>,
+    emmc: Option<EmmcController<'d, SPI1>>,
     page_buffer: [u8; MAX_PAGE_SIZE],
     current_interface: FlashInterface,
 }
@@ -125,6 +150,7 @@ impl<'d, D: Driver<'d>> UsbHandler<'d, D> {
             class,
             nand,
             spi_nor: None,
+            emmc: None,
             page_buffer: [0xFF; MAX_PAGE_SIZE],
             current_interface: FlashInterface::ParallelNand,
         }
@@ -135,6 +161,11 @@ impl<'d, D: Driver<'d>> UsbHandler<'d, D> {
         self.spi_nor = Some(spi_nor);
     }
 
+    /// Set the eMMC controller
+    pub fn set_emmc(&mut self, emmc: EmmcController<'d, SPI1>) {
+        self.emmc = Some(emmc);
+    }
+
     pub async fn handle_commands(&mut self) {
         let mut cmd_buf = [0u8; PACKET_SIZE];
         
@@ -173,6 +204,13 @@ impl<'d, D: Driver<'d>> UsbHandler<'d, D> {
             Some(Command::NandReadPage) => self.handle_read_page(args).await,
             Some(Command::NandWritePage) => self.handle_write_page(args).await,
             Some(Command::ReadId) => self.handle_read_id().await,
+            Some(Command::NandErase) => self.handle_erase(args).await,
+            Some(Command::NandWriteData) => self.handle_nand_write_data(args).await,
+            Some(Command::NandReadData) => self.handle_nand_read_data(args).await,
+            Some(Command::NandSelectCe) => self.handle_nand_select_ce(args).await,
+
+            // eMMC commands
+            Some(Command::EmmcReadExtCsd) => self.handle_emmc_read_ext_csd().await,
             
             // SPI NOR commands
             Some(Command::SpiNorReadJedecId) => self.handle_spi_nor_read_jedec_id().await,
@@ -316,6 +354,87 @@ impl<'d, D: Driver<'d>> UsbHandler<'d, D> {
         self.send_response(&response).await;
     }
 
+    /// Handle NAND block erase command (0x15)
+    /// Args: [page_0, page_1, page_2, page_3] - any page of the block
+    async fn handle_erase(&mut self, args: &[u8]) {
+        if args.len() >= 4 {
+            let page_addr = u32::from_le_bytes([args[0], args[1], args[2], args[3]]);
+            info!("ERASE: page={}", page_addr);
+
+            if self.nand.erase_block_at(page_addr).await {
+                self.send_response(&[Command::NandErase as u8, Status::Ok as u8]).await;
+            } else {
+                warn!("Block erase failed at page {}", page_addr);
+                self.send_response(&[Command::NandErase as u8, Status::Error as u8]).await;
+            }
+        } else {
+            self.send_response(&[Command::NandErase as u8, Status::Error as u8]).await;
+        }
+    }
+
+    /// Handle NAND raw data write command (0x17)
+    /// Args: [count, data...] - latches `count` bytes on the bus
+    async fn handle_nand_write_data(&mut self, args: &[u8]) {
+        if !args.is_empty() && args.len() > args[0] as usize {
+            let count = args[0] as usize;
+            info!("NAND_WRITE_DATA: {} bytes", count);
+            self.nand.write_data(&args[1..1 + count]);
+            self.send_response(&[Command::NandWriteData as u8, Status::Ok as u8]).await;
+        } else {
+            self.send_response(&[Command::NandWriteData as u8, Status::Error as u8]).await;
+        }
+    }
+
+    /// Handle NAND raw data read command (0x18)
+    /// Args: [count_lo, count_hi] - clocks out `count` bytes once the chip is ready
+    async fn handle_nand_read_data(&mut self, args: &[u8]) {
+        if args.len() >= 2 {
+            let count = u16::from_le_bytes([args[0], args[1]]) as usize;
+            info!("NAND_READ_DATA: {} bytes", count);
+            self.nand.wait_ready().await;
+
+            let mut offset = 0;
+            while offset < count {
+                let size = (count - offset).min(MAX_PAGE_SIZE);
+                self.nand.read_data(&mut self.page_buffer[..size]);
+                self.send_data_chunked(size).await;
+                offset += size;
+            }
+        } else {
+            self.send_response(&[Command::NandReadData as u8, Status::Error as u8]).await;
+        }
+    }
+
+    /// Handle NAND chip-enable select command (0x19)
+    /// Args: [ce] - this board wires a single CE#, so only CE 0 exists
+    async fn handle_nand_select_ce(&mut self, args: &[u8]) {
+        if args.first() == Some(&0) {
+            info!("NAND_SELECT_CE: 0");
+            self.send_response(&[Command::NandSelectCe as u8, Status::Ok as u8]).await;
+        } else {
+            self.send_response(&[Command::NandSelectCe as u8, Status::Error as u8]).await;
+        }
+    }
+
+    // ========== eMMC Command Handlers ==========
+
+    /// Handle eMMC Read EXT_CSD command (0x43)
+    async fn handle_emmc_read_ext_csd(&mut self) {
+        if let Some(ref mut emmc) = self.emmc {
+            info!("EMMC_READ_EXT_CSD");
+            let ready = emmc.is_initialized() || emmc.init().await;
+            if ready && emmc.read_ext_csd(&mut self.page_buffer[..EXT_CSD_SIZE]).await {
+                // Send header then data
+                self.send_response(&[Command::EmmcReadExtCsd as u8, Status::Ok as u8]).await;
+                self.send_data_chunked(EXT_CSD_SIZE).await;
+            } else {
+                self.send_response(&[Command::EmmcReadExtCsd as u8, Status::Error as u8]).await;
+            }
+        } else {
+            self.send_response(&[Command::EmmcReadExtCsd as u8, Status::Error as u8]).await;
+        }
+    }
+
     // ========== SPI NOR Command Handlers ==========
 
     /// Handle SPI NOR Read JEDEC ID command (0x60)
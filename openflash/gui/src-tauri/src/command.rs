@@ -619,6 +619,42 @@ pub async fn read_ufs_device_info(
     }
 }
 
+/// Decoded EXT_CSD together with the derived eMMC health report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmmcHealthInfo {
+    pub ext_csd: openflash_core::emmc::ExtCsd,
+    pub health: openflash_core::emmc::EmmcHealthReport,
+}
+
+/// Read EXT_CSD from the connected eMMC and build a health report
+#[tauri::command]
+pub async fn read_emmc_health(
+    device_manager: State<'_, Mutex<DeviceManager>>,
+) -> Result<EmmcHealthInfo, String> {
+    let response = if mock::is_mock_connected() {
+        mock::process_mock_command(openflash_core::protocol::Command::EmmcReadExtCsd, &[])
+    } else {
+        let device = {
+            let manager = device_manager.lock().map_err(|e| e.to_string())?;
+            manager.get_active_device().ok_or("No device connected")?
+        };
+
+        let dev = device.lock().await;
+        dev.send_command(openflash_core::protocol::Command::EmmcReadExtCsd, &[])
+            .await?
+    };
+
+    if response.len() < 2 || response[1] != 0x00 {
+        return Err("Failed to read EXT_CSD".to_string());
+    }
+
+    let ext_csd = openflash_core::emmc::ExtCsd::parse(&response[2..])
+        .ok_or("Failed to parse EXT_CSD")?;
+    let health = openflash_core::emmc::EmmcHealthReport::from_ext_csd(&ext_csd);
+
+    Ok(EmmcHealthInfo { ext_csd, health })
+}
+
 /// Select UFS LUN for operations
 #[tauri::command]
 pub async fn ufs_select_lun(
@@ -53,6 +53,8 @@ pub fn run() {
             // UFS commands (v1.6)
             command::read_ufs_device_info,
             command::ufs_select_lun,
+            // eMMC commands
+            command::read_emmc_health,
             // AI commands (v1.3)
             command::ai_analyze_dump,
             command::ai_detect_patterns,
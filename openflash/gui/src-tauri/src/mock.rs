@@ -379,6 +379,38 @@ pub fn process_mock_command(cmd: Command, args: &[u8]) -> Vec<u8> {
         Command::UfsSelectLun => vec![0x87, 0x00],
         Command::UfsGetStatus => vec![0x88, 0x00, 0x00], // Status OK
 
+        // eMMC commands
+        Command::EmmcReadExtCsd => {
+            // Return mock EXT_CSD of a moderately used eMMC 5.1 part
+            use openflash_core::emmc::ext_csd;
+            let mut ext = [0u8; ext_csd::SIZE];
+            ext[ext_csd::EXT_CSD_REV] = 8;
+            ext[ext_csd::SEC_COUNT..ext_csd::SEC_COUNT + 4]
+                .copy_from_slice(&0x01D5_A000u32.to_le_bytes()); // ~16GB
+            ext[ext_csd::PARTITION_CONFIG] = 0x48; // boot ack, boot from BOOT1
+            ext[ext_csd::BOOT_BUS_CONDITIONS] = 0x02; // x8
+            ext[ext_csd::HS_TIMING] = 0x02; // HS200
+            ext[ext_csd::DEVICE_TYPE] = 0x57;
+            ext[ext_csd::BOOT_SIZE_MULT] = 32; // 4MB
+            ext[ext_csd::RPMB_SIZE_MULT] = 32; // 4MB
+            ext[ext_csd::HC_ERASE_GRP_SIZE] = 1;
+            ext[ext_csd::HC_WP_GRP_SIZE] = 16;
+            ext[ext_csd::SEC_FEATURE_SUPPORT] = 0x55;
+            ext[ext_csd::CACHE_CTRL] = 1;
+            ext[ext_csd::CACHE_SIZE..ext_csd::CACHE_SIZE + 4]
+                .copy_from_slice(&512u32.to_le_bytes());
+            ext[ext_csd::FIRMWARE_VERSION..ext_csd::FIRMWARE_VERSION + 8]
+                .copy_from_slice(b"GD01    ");
+            ext[ext_csd::PRE_EOL_INFO] = 1;
+            ext[ext_csd::DEVICE_LIFE_TIME_EST_TYP_A] = 0x01;
+            ext[ext_csd::DEVICE_LIFE_TIME_EST_TYP_B] = 0x04;
+            ext[ext_csd::BKOPS_SUPPORT] = 1;
+
+            let mut data = vec![0x43, 0x00];
+            data.extend_from_slice(&ext);
+            data
+        }
+
         Command::Reset => vec![0x08, 0x00],
 
         Command::BusConfig => vec![0x02, 0x00],
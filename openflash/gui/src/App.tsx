@@ -7,6 +7,7 @@ import { BitmapView } from "./components/BitmapView";
 import { AiAnalysis } from "./components/AiAnalysis";
 import { SpiNorOperations } from "./components/SpiNorOperations";
 import { UfsLunSelector } from "./components/UfsLunSelector";
+import { EmmcHealth } from "./components/EmmcHealth";
 import { PlatformInfo } from "./components/PlatformInfo";
 import { NetworkDeviceDialog } from "./components/NetworkDeviceDialog";
 import "./styles.css";
@@ -15,6 +16,7 @@ import "./components/BitmapView.css";
 import "./components/AiAnalysis.css";
 import "./components/SpiNorOperations.css";
 import "./components/UfsLunSelector.css";
+import "./components/EmmcHealth.css";
 import "./components/PlatformInfo.css";
 import "./components/NetworkDeviceDialog.css";
 
@@ -635,6 +637,14 @@ function App() {
                   />
                 )}
 
+                {/* eMMC health report */}
+                {chipInfo && chipInfo.interface === "Emmc" && (
+                  <EmmcHealth
+                    disabled={!selectedDevice || isWorking}
+                    onStatusChange={setStatus}
+                  />
+                )}
+
                 {!selectedDevice && !dumpData && (
                   <div className="empty" style={{ marginTop: "2rem" }}>
                     <p>Connect a device or load a dump file to get started</p>
@@ -0,0 +1,180 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import "./EmmcHealth.css";
+
+type LifeTimeEstimate =
+  | "Undefined"
+  | "Exceeded"
+  | { Used: { min_percent: number; max_percent: number } }
+  | { Reserved: number };
+
+type PreEolInfo = "Undefined" | "Normal" | "Warning" | "Urgent" | { Reserved: number };
+
+type Verdict = "Good" | "Worn" | "Critical" | "Unknown";
+
+interface EmmcHealthReport {
+  version: string;
+  firmware_version: string;
+  capacity_bytes: number;
+  pre_eol_info: PreEolInfo;
+  life_time_est_a: LifeTimeEstimate;
+  life_time_est_b: LifeTimeEstimate;
+  vendor_health_report: string;
+  bkops_status: number;
+  verdict: Verdict;
+  notes: string[];
+}
+
+interface ExtCsd {
+  boot_size_bytes: number;
+  rpmb_size_bytes: number;
+  hs_timing: string | { Reserved: number };
+  cache: { size_bytes: number; enabled: boolean };
+  partition_config: { boot_ack: boolean; boot_partition: string | { Reserved: number } };
+  partitioning: { gp_sizes: number[] };
+}
+
+interface EmmcHealthInfo {
+  ext_csd: ExtCsd;
+  health: EmmcHealthReport;
+}
+
+interface EmmcHealthProps {
+  disabled: boolean;
+  onStatusChange: (status: string) => void;
+}
+
+export function EmmcHealth({ disabled, onStatusChange }: EmmcHealthProps) {
+  const [info, setInfo] = useState<EmmcHealthInfo | null>(null);
+  const [isReading, setIsReading] = useState(false);
+
+  function formatCapacity(bytes: number): string {
+    if (bytes < 1024) return `${bytes} B`;
+    if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+    if (bytes < 1024 * 1024 * 1024) return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+    return `${(bytes / 1024 / 1024 / 1024).toFixed(2)} GB`;
+  }
+
+  function formatLifeTime(est: LifeTimeEstimate): string {
+    if (typeof est === "string") return est;
+    if ("Used" in est) return `${est.Used.min_percent}-${est.Used.max_percent}% used`;
+    return `Reserved (0x${est.Reserved.toString(16)})`;
+  }
+
+  function lifePercent(est: LifeTimeEstimate): number {
+    if (est === "Exceeded") return 100;
+    if (typeof est === "object" && "Used" in est) return est.Used.max_percent;
+    return 0;
+  }
+
+  function formatEnum(value: string | { Reserved: number }): string {
+    return typeof value === "string" ? value : `Reserved (${value.Reserved})`;
+  }
+
+  async function handleRead() {
+    if (disabled || isReading) return;
+
+    setIsReading(true);
+    try {
+      const result = await invoke<EmmcHealthInfo>("read_emmc_health");
+      setInfo(result);
+      onStatusChange(`eMMC health: ${result.health.verdict}`);
+    } catch (e) {
+      onStatusChange(`Failed to read EXT_CSD: ${e}`);
+    } finally {
+      setIsReading(false);
+    }
+  }
+
+  function exportJson() {
+    if (!info) return;
+    const blob = new Blob([JSON.stringify(info, null, 2)], { type: "application/json" });
+    const url = URL.createObjectURL(blob);
+    const a = document.createElement("a");
+    a.href = url;
+    a.download = "emmc_health.json";
+    a.click();
+    URL.revokeObjectURL(url);
+  }
+
+  const health = info?.health;
+
+  return (
+    <div className="emmc-health">
+      <h3>eMMC Health</h3>
+
+      <div className="emmc-health-actions">
+        <button onClick={handleRead} disabled={disabled || isReading}>
+          {isReading ? "Reading..." : "Read EXT_CSD"}
+        </button>
+        {info && <button onClick={exportJson}>Export JSON</button>}
+      </div>
+
+      {info && health && (
+        <>
+          <div className={`emmc-verdict verdict-${health.verdict.toLowerCase()}`}>
+            {health.verdict.toUpperCase()}
+          </div>
+
+          <div className="emmc-health-grid">
+            <span className="label">Version:</span>
+            <span className="value">{health.version}</span>
+            <span className="label">Firmware:</span>
+            <span className="value">{health.firmware_version}</span>
+            <span className="label">Capacity:</span>
+            <span className="value">{formatCapacity(health.capacity_bytes)}</span>
+            <span className="label">Boot / RPMB:</span>
+            <span className="value">
+              2 x {formatCapacity(info.ext_csd.boot_size_bytes)} / {formatCapacity(info.ext_csd.rpmb_size_bytes)}
+            </span>
+            <span className="label">Boot from:</span>
+            <span className="value">
+              {formatEnum(info.ext_csd.partition_config.boot_partition)}
+              {info.ext_csd.partition_config.boot_ack ? " (ack)" : ""}
+            </span>
+            <span className="label">Timing:</span>
+            <span className="value">{formatEnum(info.ext_csd.hs_timing)}</span>
+            <span className="label">Cache:</span>
+            <span className="value">
+              {formatCapacity(info.ext_csd.cache.size_bytes)} {info.ext_csd.cache.enabled ? "(on)" : "(off)"}
+            </span>
+            <span className="label">Pre-EOL:</span>
+            <span className="value">{formatEnum(health.pre_eol_info)}</span>
+          </div>
+
+          {[
+            ["SLC (Type A)", health.life_time_est_a],
+            ["MLC (Type B)", health.life_time_est_b],
+          ].map(([name, est]) => (
+            <div className="emmc-life" key={name as string}>
+              <div className="emmc-life-header">
+                <span>{name as string}</span>
+                <span>{formatLifeTime(est as LifeTimeEstimate)}</span>
+              </div>
+              <div className="emmc-life-bar">
+                <div
+                  className="emmc-life-fill"
+                  style={{ width: `${lifePercent(est as LifeTimeEstimate)}%` }}
+                />
+              </div>
+            </div>
+          ))}
+
+          {health.vendor_health_report && (
+            <div className="emmc-vendor-report" title="Vendor proprietary health report">
+              {health.vendor_health_report}
+            </div>
+          )}
+
+          {health.notes.length > 0 && (
+            <ul className="emmc-notes">
+              {health.notes.map((note) => (
+                <li key={note}>{note}</li>
+              ))}
+            </ul>
+          )}
+        </>
+      )}
+    </div>
+  );
+}